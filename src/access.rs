@@ -0,0 +1,312 @@
+//! `access_window`: when a server may be connected to, as agreed with
+//! whoever runs it, e.g. `Mon-Fri 09:00-17:00 Europe/Berlin`. Outside it,
+//! `conn`, `exec` and `cp` refuse the server unless `--override-window`
+//! gives a reason, which the connection log keeps.
+
+use std::fmt;
+
+use jiff::{
+    civil::{Date, DateTime},
+    tz::{AmbiguousOffset, Offset, TimeZone},
+    Span, Timestamp,
+};
+
+use crate::{
+    error::{self, JumpError},
+    server::Server,
+    warn,
+};
+
+/// Day names, Monday first as in the windows
+const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Days of the week and a time range in a time zone
+#[derive(Debug, Clone)]
+pub struct Window {
+    /// Whether it opens on each day, Monday first
+    days: [bool; 7],
+    /// Minutes into the day it opens
+    open: u32,
+    /// Minutes into the day it closes, on the next day when not after
+    /// `open`
+    close: u32,
+    zone_name: String,
+    zone: TimeZone,
+}
+
+impl Window {
+    /// `DAYS OPEN-CLOSE ZONE`, as `Mon-Fri 09:00-17:00 Europe/Berlin` or
+    /// `Sat,Sun 22:00-06:00 UTC`; a window closing at or before it opens
+    /// closes on the next day
+    pub fn parse(text: &str) -> Result<Window, String> {
+        let invalid = || {
+            format!(
+                "`{}` is not a window like `Mon-Fri 09:00-17:00 Europe/Berlin`",
+                text
+            )
+        };
+        let [days, times, zone] = text.split_whitespace().collect::<Vec<_>>()[..] else {
+            return Err(invalid());
+        };
+        let days = parse_days(days).ok_or_else(invalid)?;
+        let (open, close) = times.split_once('-').ok_or_else(invalid)?;
+        let (open, close) = (minutes(open).filter(|open| *open < 24 * 60), minutes(close));
+        let (Some(open), Some(close)) = (open, close) else {
+            return Err(invalid());
+        };
+        if open == close {
+            return Err(format!(
+                "`{}` opens and closes at the same time; 00:00-24:00 is the whole day",
+                text
+            ));
+        }
+        Ok(Window {
+            days,
+            open,
+            close,
+            zone_name: zone.to_owned(),
+            zone: parse_zone(zone)?,
+        })
+    }
+
+    /// Whether the window is open at `utc`, in seconds since the epoch
+    pub fn is_open(&self, utc: i64) -> bool {
+        let local = self.local(utc);
+        let day = local.weekday().to_monday_zero_offset() as usize;
+        let minute = local.hour() as u32 * 60 + local.minute() as u32;
+        let yesterday = (day + 6) % 7;
+        match self.open < self.close {
+            true => self.days[day] && self.open <= minute && minute < self.close,
+            false => {
+                (self.days[day] && minute >= self.open)
+                    || (self.days[yesterday] && minute < self.close)
+            }
+        }
+    }
+
+    /// When the window next opens after `utc`: the opening time on the
+    /// first day of the window that is still to come. A start that falls
+    /// into a daylight saving gap opens at the end of the gap.
+    pub fn next_open(&self, utc: i64) -> Option<i64> {
+        let today = self.local(utc).date();
+        // Two weeks of days, as daylight saving time can skip an opening
+        (0..=14)
+            .filter_map(|ahead| today.checked_add(Span::new().days(ahead)).ok())
+            .filter(|day| self.days[day.weekday().to_monday_zero_offset() as usize])
+            .filter_map(|day| self.opening(day))
+            .find(|opening| *opening > utc && self.is_open(*opening))
+    }
+
+    /// `utc` as the time in the window's zone, e.g.
+    /// `2024-03-04 09:00 Europe/Berlin`
+    pub fn local_time(&self, utc: i64) -> String {
+        format!(
+            "{} {}",
+            self.local(utc).strftime("%Y-%m-%d %H:%M"),
+            self.zone_name
+        )
+    }
+
+    /// `utc` as the date and time on the window's clocks
+    fn local(&self, utc: i64) -> DateTime {
+        self.zone.to_datetime(timestamp(utc))
+    }
+
+    /// When the window opens on `day`, in seconds since the epoch. An
+    /// opening time the clocks pass twice opens at the first; one they skip
+    /// opens as the gap ends.
+    fn opening(&self, day: Date) -> Option<i64> {
+        let open = day.at((self.open / 60) as i8, (self.open % 60) as i8, 0, 0);
+        let ambiguous = self.zone.to_ambiguous_timestamp(open);
+        let earlier = ambiguous.earlier().ok()?;
+        let opening = match ambiguous.offset() {
+            AmbiguousOffset::Gap { .. } => self.zone.following(earlier).next()?.timestamp(),
+            _ => earlier,
+        };
+        Some(opening.as_second())
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ranges = vec![];
+        let mut day = 0;
+        while day < 7 {
+            if !self.days[day] {
+                day += 1;
+                continue;
+            }
+            let first = day;
+            while day < 7 && self.days[day] {
+                day += 1;
+            }
+            ranges.push(match day - first {
+                1 => DAYS[first].to_owned(),
+                _ => format!("{}-{}", DAYS[first], DAYS[day - 1]),
+            });
+        }
+        let clock = |minutes: u32| format!("{:02}:{:02}", minutes / 60, minutes % 60);
+        write!(
+            f,
+            "{} {}-{} {}",
+            ranges.join(","),
+            clock(self.open),
+            clock(self.close),
+            self.zone_name
+        )
+    }
+}
+
+/// `Mon-Fri`, `Sat,Sun` or `Fri-Mon`, any case
+fn parse_days(text: &str) -> Option<[bool; 7]> {
+    let day = |name: &str| DAYS.iter().position(|day| day.eq_ignore_ascii_case(name));
+    let mut days = [false; 7];
+    for part in text.split(',') {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (day(first)?, day(last)?),
+            None => (day(part)?, day(part)?),
+        };
+        let mut i = first;
+        loop {
+            days[i] = true;
+            if i == last {
+                break;
+            }
+            i = (i + 1) % 7;
+        }
+    }
+    Some(days)
+}
+
+/// `HH:MM` as minutes into the day, up to `24:00`
+fn minutes(text: &str) -> Option<u32> {
+    let (hours, minutes) = text.split_once(':')?;
+    if hours.len() != 2 || minutes.len() != 2 {
+        return None;
+    }
+    let (hours, minutes) = (hours.parse::<u32>().ok()?, minutes.parse::<u32>().ok()?);
+    match (hours, minutes) {
+        (0..=23, 0..=59) | (24, 0) => Some(hours * 60 + minutes),
+        _ => None,
+    }
+}
+
+/// `UTC`, an offset east of UTC like `+05:30`, a name of the tz database
+/// like `Europe/Berlin`, or a POSIX TZ rule like `CET-1CEST,M3.5.0,M10.5.0/3`
+fn parse_zone(text: &str) -> Result<TimeZone, String> {
+    if text.eq_ignore_ascii_case("UTC") {
+        return Ok(TimeZone::UTC);
+    }
+    if let Some(offset) = fixed(text) {
+        return Offset::from_seconds(offset)
+            .map(TimeZone::fixed)
+            .map_err(|error| error.to_string());
+    }
+    TimeZone::get(text)
+        .or_else(|_| TimeZone::posix(text))
+        .map_err(|_| {
+            format!(
+                "`{}` is not a time zone: give one like Europe/Berlin, UTC or +05:30",
+                text
+            )
+        })
+}
+
+/// `+05:30`, `-08` or `+0530` as seconds east of UTC
+fn fixed(text: &str) -> Option<i32> {
+    let (sign, digits) = match text.split_at_checked(1)? {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return None,
+    };
+    let (hours, minutes) = match digits.split_once(':') {
+        Some(parts) => parts,
+        None if digits.len() == 4 => digits.split_at(2),
+        None => (digits, "00"),
+    };
+    if hours.len() != 2 || minutes.len() != 2 {
+        return None;
+    }
+    let (hours, minutes) = (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?);
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 3_600 + minutes * 60))
+}
+
+/// Seconds since the epoch as a timestamp, clamped to the range jiff knows
+fn timestamp(utc: i64) -> Timestamp {
+    let utc = utc.clamp(Timestamp::MIN.as_second(), Timestamp::MAX.as_second());
+    Timestamp::from_second(utc).unwrap_or(Timestamp::UNIX_EPOCH)
+}
+
+/// Check a window given on the command line, keeping it as written out
+/// again
+pub fn parse_arg(text: &str) -> Result<String, String> {
+    Window::parse(text).map(|window| window.to_string())
+}
+
+/// The window of `server` when it is closed at `now`, with when it opens
+/// next; an error for a window that cannot be read, as with a time zone
+/// this system does not know
+pub fn closed(server: &Server, now: i64) -> Result<Option<(Window, Option<i64>)>, String> {
+    let Some(text) = &server.access_window else {
+        return Ok(None);
+    };
+    let window = Window::parse(text)?;
+    match window.is_open(now) {
+        true => Ok(None),
+        false => {
+            let next = window.next_open(now);
+            Ok(Some((window, next)))
+        }
+    }
+}
+
+/// Refuse `server` while its window is closed at `now`, unless
+/// `override_window` gives a reason, warning either way. A window that
+/// cannot be read, as with a time zone this system does not know, counts
+/// as closed.
+pub fn pass(server: &Server, override_window: Option<&str>, now: i64) -> error::Result<()> {
+    let closed = match closed(server, now) {
+        Ok(None) => return Ok(()),
+        Ok(Some((window, next))) => {
+            let next = next.map_or("no time this week".to_owned(), |next| {
+                window.local_time(next)
+            });
+            Ok((window, next))
+        }
+        Err(reason) => Err(reason),
+    };
+    match (closed, override_window) {
+        (Ok((window, next)), Some(reason)) => {
+            warn!(
+                "{} is outside its access window {}, next open at {}; going ahead: {}",
+                server.server_name, window, next, reason
+            );
+            Ok(())
+        }
+        (Err(e), Some(reason)) => {
+            warn!(
+                "cannot check the access window of {}: {}; going ahead: {}",
+                server.server_name, e, reason
+            );
+            Ok(())
+        }
+        (Ok((window, next)), None) => Err(JumpError::OutsideWindow {
+            name: server.server_name.clone(),
+            window: window.to_string(),
+            next,
+        }),
+        (Err(reason), None) => Err(JumpError::InvalidWindow {
+            name: server.server_name.clone(),
+            reason,
+        }),
+    }
+}
+
+/// Whether only `--override-window` lets `server` through at `now`: its
+/// window is closed or cannot be read
+pub fn needs_override(server: &Server, now: i64) -> bool {
+    !matches!(closed(server, now), Ok(None))
+}
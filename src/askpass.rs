@@ -0,0 +1,40 @@
+//! Password entry without sshpass. ssh is pointed at the jump binary itself
+//! through `SSH_ASKPASS`, and the re-launched jump answers the password
+//! prompt from its environment.
+
+use std::{env, io};
+
+/// Environment variable carrying the password to the askpass helper
+pub const SECRET_VAR: &str = "JUMP_ASKPASS_PASSWORD";
+
+/// The variables that make ssh ask the running jump binary for `password`
+pub fn env(password: &str) -> io::Result<Vec<(String, String)>> {
+    let exe = env::current_exe()?;
+    Ok(vec![
+        ("SSH_ASKPASS".to_owned(), exe.to_string_lossy().into_owned()),
+        ("SSH_ASKPASS_REQUIRE".to_owned(), "force".to_owned()),
+        (SECRET_VAR.to_owned(), password.to_owned()),
+    ])
+}
+
+/// The prompt when this process was started by ssh as its askpass helper.
+/// ssh passes the prompt as the only argument; jump's own subcommands never
+/// contain spaces, so a jump run from a hook or ProxyCommand is not mistaken
+/// for one.
+pub fn prompt() -> Option<String> {
+    env::var_os(SECRET_VAR)?;
+    let mut args = env::args().skip(1);
+    match (args.next(), args.next()) {
+        (Some(prompt), None) if prompt.contains(' ') => Some(prompt),
+        _ => None,
+    }
+}
+
+/// Answer password prompts only; anything else, like a host key
+/// confirmation, is refused rather than answered with the password
+pub fn answer<'a>(prompt: &str, password: &'a str) -> Option<&'a str> {
+    prompt
+        .to_ascii_lowercase()
+        .contains("password")
+        .then_some(password)
+}
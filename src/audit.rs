@@ -0,0 +1,254 @@
+//! The connection log: which server was connected to, when, by whom and how
+//! it ended. Writing it is best-effort, so a busy or read-only store never
+//! stands between the user and the connection.
+
+use std::{
+    env,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::Config,
+    debug,
+    gate::Ack,
+    server::{ConnectMethods, Server},
+    store::Store,
+};
+
+/// One `conn` or `exec`, or a `cp` through a `confirm_tags` gate. Times are seconds since the Unix epoch; `ended_at`
+/// and `exit_code` stay empty while the session runs, or when jump could not
+/// record the end.
+#[derive(Debug, Serialize)]
+pub struct LogEntry {
+    pub server_name: String,
+    pub local_user: String,
+    /// `conn`, `console`, `exec`, `cp` or `rekey`
+    pub command: String,
+    /// `ssh-key` or `password`; secrets are never logged
+    pub method: String,
+    pub started_at: i64,
+    pub ended_at: Option<i64>,
+    pub exit_code: Option<i32>,
+    /// How the `confirm_tags` gate was passed, `typed` or `--yes-prod`;
+    /// empty for servers without one
+    pub confirmed: Option<String>,
+    /// Why `conn --no-limit` lifted the server's `max_session`
+    pub no_limit: Option<String>,
+    /// `max_session` when jump ended the session at the server's limit
+    pub ended_by: Option<String>,
+    /// Why `--override-window` let the session start outside the server's
+    /// `access_window`
+    pub override_window: Option<String>,
+}
+
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+fn local_user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_owned())
+}
+
+pub fn method_name(method: &ConnectMethods) -> &'static str {
+    match method {
+        ConnectMethods::SSHKey(_) => "ssh-key",
+        ConnectMethods::Password(_) => "password",
+        ConnectMethods::Command(_) => "custom",
+        ConnectMethods::SshConfig(_) => "ssh-config",
+        ConnectMethods::Unsupported(_) => "unsupported",
+    }
+}
+
+/// Record the start of a session and prune entries past the retention
+/// period, returning the entry to finish later. Failures are only logged, and
+/// read-only stores are left alone.
+pub fn start(
+    store: &Store,
+    config: &Config,
+    server: &Server,
+    command: &str,
+    confirmed: Option<Ack>,
+) -> Option<i64> {
+    if store.is_read_only() {
+        return None;
+    }
+    let started_at = now();
+    let entry = LogEntry {
+        server_name: server.server_name.clone(),
+        local_user: local_user(),
+        command: command.to_owned(),
+        method: method_name(&server.method).to_owned(),
+        started_at,
+        ended_at: None,
+        exit_code: None,
+        confirmed: confirmed.map(|ack| ack.as_str().to_owned()),
+        no_limit: None,
+        ended_by: None,
+        override_window: None,
+    };
+    let started = match config.log_retention_days {
+        0 => store.log_start(&entry),
+        days => store.log_start_pruning(&entry, started_at - i64::from(days) * 86_400),
+    };
+    started
+        .inspect_err(|e| debug!("could not write the connection log: {}", e))
+        .ok()
+}
+
+/// Record how a session started with [`start`] ended
+pub fn finish(store: &Store, entry: Option<i64>, exit_code: Option<i32>) {
+    if let Some(id) = entry {
+        if let Err(e) = store.log_end(id, now(), exit_code) {
+            debug!("could not write the connection log: {}", e);
+        }
+    }
+}
+
+/// Record why `--no-limit` lifted the server's `max_session` for the
+/// session started with [`start`]
+pub fn limit_lifted(store: &Store, entry: Option<i64>, reason: &str) {
+    if let Some(id) = entry {
+        if let Err(e) = store.log_limit(id, Some(reason), None) {
+            debug!("could not write the connection log: {}", e);
+        }
+    }
+}
+
+/// Record why `--override-window` let the session started with [`start`]
+/// go ahead outside the server's `access_window`
+pub fn window_overridden(store: &Store, entry: Option<i64>, reason: &str) {
+    if let Some(id) = entry {
+        if let Err(e) = store.log_override(id, reason) {
+            debug!("could not write the connection log: {}", e);
+        }
+    }
+}
+
+/// Record that jump ended the session at the server's `max_session`
+pub fn limit_reached(store: &Store, entry: Option<i64>) {
+    if let Some(id) = entry {
+        if let Err(e) = store.log_limit(id, None, Some("max_session")) {
+            debug!("could not write the connection log: {}", e);
+        }
+    }
+}
+
+/// Parse an age like `30m`, `12h`, `7d` or `2w` into seconds
+pub fn parse_age(age: &str) -> Result<i64, String> {
+    let invalid = || format!("`{}` is not an age like 30m, 12h, 7d or 2w", age);
+    let split = age.len().checked_sub(1).ok_or_else(invalid)?;
+    let (count, unit) = age.split_at(split);
+    let count: i64 = count.parse().map_err(|_| invalid())?;
+    let unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => return Err(invalid()),
+    };
+    Ok(count * unit)
+}
+
+/// Parse a UTC time like `2024-03-01`, `2024-03-01 09:30` or
+/// `2024-03-01T09:30:00` into seconds since the epoch
+pub fn parse_time(time: &str) -> Result<i64, String> {
+    let invalid = || {
+        format!(
+            "`{}` is not a date like 2024-03-01 or 2024-03-01 09:30",
+            time
+        )
+    };
+    let (date, clock) = time
+        .split_once([' ', 'T'])
+        .map_or((time, None), |(date, clock)| (date, Some(clock)));
+    let numbers = |text: &str, sep: char| {
+        text.split(sep)
+            .map(|part| part.parse::<i64>().ok())
+            .collect::<Option<Vec<_>>>()
+    };
+    let (year, month, day) = match numbers(date, '-').as_deref() {
+        Some(&[year, month, day]) if (1..=12).contains(&month) && (1..=31).contains(&day) => {
+            (year, month, day)
+        }
+        _ => return Err(invalid()),
+    };
+    let secs = match clock.map(|clock| numbers(clock, ':')) {
+        None => 0,
+        Some(Some(parts)) if (2..=3).contains(&parts.len()) => {
+            let (hours, minutes) = (parts[0], parts[1]);
+            let seconds = parts.get(2).copied().unwrap_or(0);
+            if !(0..24).contains(&hours) || !(0..60).contains(&minutes) {
+                return Err(invalid());
+            }
+            hours * 3_600 + minutes * 60 + seconds
+        }
+        _ => return Err(invalid()),
+    };
+    // Days since the epoch from the civil date, after Howard Hinnant's
+    // algorithm
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    Ok(days * 86_400 + secs)
+}
+
+/// `2024-03-01 09:30:00`, in UTC
+pub fn format_time(secs: i64) -> String {
+    let (days, secs) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    // Civil date from days since the epoch, after Howard Hinnant's algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+}
+
+/// `secs` the way [`parse_age`] reads it, in the largest unit it is a whole
+/// number of, e.g. `2h` or `90m`
+pub fn format_age(secs: u64) -> String {
+    let (count, unit) = [(604_800, "w"), (86_400, "d"), (3_600, "h"), (60, "m")]
+        .into_iter()
+        .find(|(unit, _)| secs.is_multiple_of(*unit))
+        .map_or((secs, "s"), |(unit, name)| (secs / unit, name));
+    match secs {
+        0 => "0s".to_owned(),
+        _ => format!("{}{}", count, unit),
+    }
+}
+
+/// How long ago `then` was, in its largest whole unit, e.g. `5m ago` or
+/// `3d ago`
+pub fn format_ago(then: i64, now: i64) -> String {
+    let secs = (now - then).max(0);
+    match secs {
+        0..60 => "just now".to_owned(),
+        60..3_600 => format!("{}m ago", secs / 60),
+        3_600..86_400 => format!("{}h ago", secs / 3_600),
+        86_400..604_800 => format!("{}d ago", secs / 86_400),
+        _ => format!("{}w ago", secs / 604_800),
+    }
+}
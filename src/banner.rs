@@ -0,0 +1,68 @@
+//! What `conn` shows of the server it is about to connect to, so that a
+//! terminal among several says where it points: a line on stderr with
+//! `banner = true`, and the terminal title with `set_title = true`. Both
+//! stay quiet with `--quiet`.
+
+use std::io::{self, IsTerminal, Write};
+
+use crate::{
+    config::Config,
+    log::{self, Verbosity},
+    profiles,
+    server::Server,
+    style::Style,
+};
+
+/// `name  user@address:port  [tags]  note`, the name in the color of the
+/// server's tags
+pub fn line(config: &Config, style: Style, server: &Server) -> String {
+    let name = match profiles::color(server, &config.tag_colors) {
+        Some(color) => style.rgb(color, &server.server_name),
+        None => style.bold(&server.server_name),
+    };
+    let mut line = format!(
+        "{}  {}@{}:{}",
+        name, server.username, server.server_address, server.port
+    );
+    if !server.tags.is_empty() {
+        line.push_str(&format!("  [{}]", server.tags.join(", ")));
+    }
+    if let Some(note) = server.note.as_deref().and_then(|note| note.lines().next()) {
+        line.push_str(&format!("  {}", style.cyan(note)));
+    }
+    line
+}
+
+/// Print the banner line when it is turned on
+pub fn show(config: &Config, style: Style, server: &Server) {
+    if config.banner && log::enabled(Verbosity::Normal) {
+        eprintln!("{}", line(config, style, server));
+    }
+}
+
+/// The terminal title set for a session, put back when dropped
+pub struct Title {
+    set: bool,
+}
+
+impl Drop for Title {
+    fn drop(&mut self) {
+        if self.set {
+            // Pop the title pushed before, where the terminal keeps a stack
+            print!("\x1b[23;0t");
+            io::stdout().flush().ok();
+        }
+    }
+}
+
+/// Set the title of the terminal on stdout to the server's name when
+/// `set_title` is on, saving the old one on the terminal's title stack
+pub fn set_title(config: &Config, server: &Server) -> Title {
+    let set = config.set_title && log::enabled(Verbosity::Normal) && io::stdout().is_terminal();
+    if set {
+        let name = server.server_name.replace(char::is_control, "");
+        print!("\x1b[22;0t\x1b]0;{}\x07", name);
+        io::stdout().flush().ok();
+    }
+    Title { set }
+}
@@ -0,0 +1,229 @@
+//! Bundles of servers written by `export` and read by `import`: JSON, either
+//! plain or encrypted with a passphrase. Encrypted bundles start with a
+//! version header, followed by the scrypt cost, salt and nonce, then the
+//! AES-256-GCM ciphertext; the header is authenticated along with it.
+
+use aes_gcm::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{JumpError, Result},
+    server::{ConnectMethods, Password, Server},
+    store::Store,
+};
+
+/// Version of the bundle contents, raised whenever old readers would
+/// misunderstand them
+pub const VERSION: u32 = 1;
+
+const MAGIC: &[u8] = b"jump-bundle 1\n";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+/// scrypt cost, stored in the header so it can be raised later
+const LOG_N: u8 = 15;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Bundle {
+    pub version: u32,
+    /// Whether passwords were exported; otherwise they are empty
+    pub secrets: bool,
+    pub servers: Vec<Server>,
+}
+
+impl Bundle {
+    /// Ids only mean something in the store they come from, so they are left
+    /// out
+    pub fn new(mut servers: Vec<Server>, include_secrets: bool) -> Bundle {
+        for server in &mut servers {
+            server.id = None;
+            if !include_secrets {
+                if let ConnectMethods::Password(password) = &mut server.method {
+                    password.password.clear();
+                }
+            }
+        }
+        Bundle {
+            version: VERSION,
+            secrets: include_secrets,
+            servers,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self).map_err(|e| JumpError::invalid("bundle", e.to_string()))
+    }
+
+    /// Read a plain or encrypted bundle; `passphrase` is only used for the latter
+    pub fn read(data: &[u8], passphrase: Option<&str>) -> Result<Bundle> {
+        let plain;
+        let json = match (is_encrypted(data), passphrase) {
+            (true, Some(passphrase)) => {
+                plain = decrypt(data, passphrase)?;
+                &plain[..]
+            }
+            (true, None) => {
+                return Err(JumpError::invalid(
+                    "bundle",
+                    "it is encrypted, a passphrase is needed",
+                ))
+            }
+            (false, _) => data,
+        };
+        let bundle: Bundle = serde_json::from_slice(json)
+            .map_err(|e| JumpError::invalid("bundle", e.to_string()))?;
+        if bundle.version > VERSION {
+            return Err(JumpError::invalid(
+                "bundle",
+                format!(
+                    "version {} was written by a newer jump, which is needed to read it",
+                    bundle.version
+                ),
+            ));
+        }
+        Ok(bundle)
+    }
+}
+
+/// Whether `data` looks like an encrypted bundle of any version
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(b"jump-bundle ")
+}
+
+pub fn encrypt(plain: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let mut data = MAGIC.to_vec();
+    data.push(LOG_N);
+    data.extend(salt);
+    data.extend(nonce);
+    let cipher = Aes256Gcm::new(&key(passphrase, LOG_N, &salt)?);
+    let payload = Payload {
+        msg: plain,
+        aad: &data,
+    };
+    let ciphertext = cipher
+        .encrypt(&nonce, payload)
+        .map_err(|_| JumpError::invalid("bundle", "encryption failed"))?;
+    data.extend(ciphertext);
+    Ok(data)
+}
+
+pub fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    if !data.starts_with(MAGIC) {
+        return Err(JumpError::invalid(
+            "bundle",
+            "the format was written by a newer jump, which is needed to read it",
+        ));
+    }
+    let header_len = MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+    if data.len() < header_len {
+        return Err(JumpError::invalid("bundle", "the file is truncated"));
+    }
+    let (header, ciphertext) = data.split_at(header_len);
+    let log_n = header[MAGIC.len()];
+    let salt = &header[MAGIC.len() + 1..MAGIC.len() + 1 + SALT_LEN];
+    let nonce = Nonce::from_slice(&header[header_len - NONCE_LEN..]);
+    let cipher = Aes256Gcm::new(&key(passphrase, log_n, salt)?);
+    let payload = Payload {
+        msg: ciphertext,
+        aad: header,
+    };
+    cipher
+        .decrypt(nonce, payload)
+        .map_err(|_| JumpError::WrongPassphrase)
+}
+
+fn key(passphrase: &str, log_n: u8, salt: &[u8]) -> Result<Key<Aes256Gcm>> {
+    let params = scrypt::Params::new(log_n, 8, 1, 32)
+        .map_err(|_| JumpError::invalid("bundle", "unsupported scrypt parameters"))?;
+    let mut key = Key::<Aes256Gcm>::default();
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut key)
+        .map_err(|_| JumpError::invalid("bundle", "key derivation failed"))?;
+    Ok(key)
+}
+
+/// What `import` does with a server whose name is already taken
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Conflict {
+    /// Keep the existing server
+    #[default]
+    Skip,
+    /// Replace the existing server
+    Overwrite,
+    /// Import under the first free `<name>-<n>`
+    Rename,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Added,
+    Skipped,
+    Replaced,
+    Renamed(String),
+}
+
+/// Merge `bundle` into `store` in one transaction. Passwords left out of the
+/// bundle are taken from the server being replaced, when it has one.
+pub fn import(
+    store: &Store,
+    bundle: Bundle,
+    on_conflict: Conflict,
+) -> Result<Vec<(String, Outcome)>> {
+    store.transaction(|| {
+        let mut outcomes = vec![];
+        for mut server in bundle.servers {
+            let name = server.server_name.clone();
+            let existing = store.find(&name)?;
+            server.id = None;
+            let outcome = match (&existing, on_conflict) {
+                (None, _) => Outcome::Added,
+                (Some(_), Conflict::Skip) => {
+                    outcomes.push((name, Outcome::Skipped));
+                    continue;
+                }
+                (Some(existing), Conflict::Overwrite) => {
+                    if !bundle.secrets {
+                        keep_password(&mut server, existing);
+                    }
+                    server.id = existing.id;
+                    server.order_index = existing.order_index;
+                    store.remove(&name)?;
+                    Outcome::Replaced
+                }
+                (Some(_), Conflict::Rename) => {
+                    server.server_name = free_name(store, &name)?;
+                    Outcome::Renamed(server.server_name.clone())
+                }
+            };
+            store.add(&server)?;
+            outcomes.push((name, outcome));
+        }
+        Ok(outcomes)
+    })
+}
+
+/// Give `server` the password of `existing` when both use passwords
+pub fn keep_password(server: &mut Server, existing: &Server) {
+    if let (
+        ConnectMethods::Password(Password { password }),
+        ConnectMethods::Password(Password { password: current }),
+    ) = (&mut server.method, &existing.method)
+    {
+        password.clone_from(current);
+    }
+}
+
+fn free_name(store: &Store, name: &str) -> Result<String> {
+    for n in 2.. {
+        let candidate = format!("{}-{}", name, n);
+        if store.find(&candidate)?.is_none() {
+            return Ok(candidate);
+        }
+    }
+    unreachable!()
+}
@@ -0,0 +1,103 @@
+//! A plain-text list of each profile's servers under `~/.jump/cache`, for
+//! shell prompts and fzf widgets that cannot wait for sqlite on every key.
+//! Each line is `<name>\t<user>@<address>`, in the store's order and ending
+//! in a newline; servers whose name or address holds a tab or a newline are
+//! left out. The file is replaced in one step, so readers never see half of
+//! it.
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::{config, error::Result, server::Server};
+
+/// `~/.jump/cache`
+pub fn dir() -> Result<PathBuf> {
+    Ok(config::jump_dir()?.join("cache"))
+}
+
+/// `servers.txt` for the default profile and `<profile>.txt` for the others,
+/// named after their stores
+pub fn path(profile: &str) -> Result<PathBuf> {
+    let name = match profile {
+        "default" => "servers.txt".to_owned(),
+        _ => format!("{}.txt", profile),
+    };
+    Ok(dir()?.join(name))
+}
+
+/// The profile a file in the cache directory is for, if any
+pub fn profile(file_name: &str) -> Option<&str> {
+    match file_name {
+        "servers.txt" => Some("default"),
+        _ => file_name
+            .strip_suffix(".txt")
+            .filter(|profile| !profile.is_empty() && !profile.starts_with('.')),
+    }
+}
+
+/// The lines of the cache for `servers`
+pub fn render(servers: &[Server]) -> String {
+    servers
+        .iter()
+        .map(|server| {
+            (
+                &server.server_name,
+                format!("{}@{}", server.username, server.server_address),
+            )
+        })
+        .filter(|(name, destination)| !format!("{}{}", name, destination).contains(['\t', '\n']))
+        .map(|(name, destination)| format!("{}\t{}\n", name, destination))
+        .collect()
+}
+
+/// Write the profile's cache, returning whether it changed
+pub fn write(profile: &str, servers: &[Server]) -> Result<bool> {
+    let path = path(profile)?;
+    let text = render(servers);
+    if fs::read_to_string(&path).is_ok_and(|old| old == text) {
+        return Ok(false);
+    }
+    fs::create_dir_all(dir()?)?;
+    replace(&path, &text)?;
+    Ok(true)
+}
+
+/// Write through a file next to `path` renamed over it, named after this
+/// process so that two jumps writing at once do not share it
+fn replace(path: &Path, text: &str) -> Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{}.{}", name, std::process::id()));
+    let written = fs::write(&temp, text).and_then(|_| fs::rename(&temp, path));
+    if let Err(e) = written {
+        fs::remove_file(&temp).ok();
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Remove the profile's cache, returning whether there was one
+pub fn remove(profile: &str) -> Result<bool> {
+    match fs::remove_file(path(profile)?) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The profiles with a cache
+pub fn profiles() -> Result<Vec<String>> {
+    let entries = match fs::read_dir(dir()?) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut profiles = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| profile(&entry.file_name().to_string_lossy()).map(str::to_owned))
+        .collect::<Vec<_>>();
+    profiles.sort();
+    Ok(profiles)
+}
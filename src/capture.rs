@@ -0,0 +1,239 @@
+//! Copies of ssh's diagnostics for post-mortems. The child's stderr is
+//! piped through a thread that writes every chunk both to the terminal and
+//! to the log, so the session itself keeps the terminal on stdin and stdout.
+//! Batch-mode `exec` pipes them the same way to notice a denied login.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    thread,
+};
+
+use crate::{
+    audit,
+    config::{self, Config},
+    error::{JumpError, Result},
+    key_usage, signals,
+    ssh::Invocation,
+};
+
+/// A log grows to this size before it is moved to `<log>.1`, replacing the
+/// previous one, so each log takes at most twice this much space
+pub const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+/// How much of the end of stderr `status_keeping_tail` keeps
+pub const TAIL_SIZE: usize = 64 * 1024;
+
+/// `~/.jump/logs/<server>.log`
+pub fn default_path(server_name: &str) -> Result<PathBuf> {
+    Ok(config::jump_dir()?
+        .join("logs")
+        .join(format!("{}.log", server_name)))
+}
+
+/// Where to copy the stderr of a command for `server_name`: the requested
+/// file, or the default one when `log_connections` is set
+pub fn path(
+    config: &Config,
+    requested: Option<&Path>,
+    server_name: &str,
+) -> Result<Option<PathBuf>> {
+    match requested {
+        Some(path) => Ok(Some(config::expand_path(path)?)),
+        None if config.log_connections => Ok(Some(default_path(server_name)?)),
+        None => Ok(None),
+    }
+}
+
+/// Run `invocation` with its stderr shown as usual and appended to `log`,
+/// after a header with the time and the redacted command line
+pub fn status(invocation: &Invocation, log: &Path) -> Result<ExitStatus> {
+    Ok(tee(invocation, Some(log), None, false, false)?.status)
+}
+
+/// What a command piped through `tee` left behind
+pub struct Teed {
+    pub status: ExitStatus,
+    /// Whether the needle appeared in stderr
+    pub found: bool,
+    /// The last `TAIL_SIZE` bytes of stderr
+    pub tail: String,
+    /// The first `MAX_DEBUG_LINES` lines `ssh -v` added, when asked to
+    /// keep them apart
+    pub debug: Vec<String>,
+}
+
+/// How many `debug1:` lines a session keeps; ssh authenticates in the first
+/// few dozen
+const MAX_DEBUG_LINES: usize = 500;
+
+/// Run `invocation` with its stderr shown as usual, and appended to `log`
+/// when there is one, keeping the end of it and telling whether `needle`
+/// appeared in it
+pub fn status_keeping_tail(
+    invocation: &Invocation,
+    log: Option<&Path>,
+    needle: &'static str,
+) -> Result<Teed> {
+    tee(invocation, log, Some(needle), false, false)
+}
+
+/// The same for an interactive session, started through `signals::spawn`
+/// so that signals reaching jump are passed on to it. With `debug`, the
+/// lines of `ssh -v` are kept off the terminal and the log, and returned.
+pub fn session_keeping_tail(
+    invocation: &Invocation,
+    log: Option<&Path>,
+    needle: &'static str,
+    debug: bool,
+) -> Result<Teed> {
+    tee(invocation, log, Some(needle), true, debug)
+}
+
+fn tee(
+    invocation: &Invocation,
+    log: Option<&Path>,
+    needle: Option<&'static str>,
+    session: bool,
+    debug: bool,
+) -> Result<Teed> {
+    let mut file = match log {
+        Some(log) => Some(open_with_header(invocation, log)?),
+        None => None,
+    };
+    let mut command = invocation.command();
+    command.stderr(Stdio::piped());
+    let spawned = match session {
+        true => signals::spawn(&mut command),
+        false => command.spawn(),
+    };
+    let mut child = spawned.map_err(|source| JumpError::Spawn {
+        program: invocation.program.display().to_string(),
+        source,
+    })?;
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let tee = thread::spawn(move || {
+        let mut buf = [0; 4096];
+        // The end of the previous chunk, for a needle split across two
+        let mut seen = Vec::new();
+        let mut found = false;
+        let mut tail = Vec::new();
+        let mut filter = debug.then(DebugFilter::default);
+        // Reads return as soon as anything arrives, so prompts show up
+        // without waiting for a newline
+        while let Ok(n) = stderr.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            let shown = match &mut filter {
+                Some(filter) => filter.feed(&buf[..n]),
+                None => buf[..n].to_vec(),
+            };
+            let mut terminal = io::stderr();
+            terminal.write_all(&shown).ok();
+            terminal.flush().ok();
+            if let Some(file) = &mut file {
+                file.write_all(&shown).ok();
+            }
+            tail.extend_from_slice(&shown);
+            tail.drain(..tail.len().saturating_sub(TAIL_SIZE));
+            if let Some(needle) = needle.filter(|_| !found) {
+                seen.extend_from_slice(&shown);
+                found = seen
+                    .windows(needle.len())
+                    .any(|window| window == needle.as_bytes());
+                seen.drain(..seen.len().saturating_sub(needle.len()));
+            }
+        }
+        (
+            found,
+            tail,
+            filter.map(|filter| filter.lines).unwrap_or_default(),
+        )
+    });
+    let status = child.wait();
+    if session {
+        signals::ended();
+    }
+    let status = status?;
+    let (found, tail, debug) = tee.join().unwrap_or_default();
+    Ok(Teed {
+        status,
+        found,
+        tail: String::from_utf8_lossy(&tail).into_owned(),
+        debug,
+    })
+}
+
+/// Splits the `debug1:` lines off stderr, passing everything else through
+/// as it arrives; only the start of a line that may be one is held back
+#[derive(Default)]
+struct DebugFilter {
+    /// The current line so far, while it may be or is a debug line
+    held: Vec<u8>,
+    /// Whether the current line is passed through
+    passing: bool,
+    lines: Vec<String>,
+}
+
+impl DebugFilter {
+    /// The part of `chunk` to show
+    fn feed(&mut self, chunk: &[u8]) -> Vec<u8> {
+        let prefix = key_usage::DEBUG_PREFIX.as_bytes();
+        let mut shown = Vec::with_capacity(chunk.len());
+        for &byte in chunk {
+            if self.passing {
+                shown.push(byte);
+            } else {
+                self.held.push(byte);
+                let start = &self.held[..self.held.len().min(prefix.len())];
+                if !prefix.starts_with(start) {
+                    shown.append(&mut self.held);
+                    self.passing = true;
+                } else if byte == b'\n' {
+                    let line = String::from_utf8_lossy(&self.held);
+                    if self.lines.len() < MAX_DEBUG_LINES {
+                        self.lines.push(line.trim_end().to_owned());
+                    }
+                    self.held.clear();
+                }
+            }
+            if byte == b'\n' {
+                self.passing = false;
+            }
+        }
+        shown
+    }
+}
+
+/// Append the stderr of `invocation`, captured while it ran, to `log` after
+/// the same header as `status`
+pub fn append(invocation: &Invocation, log: &Path, stderr: &[u8]) -> Result<()> {
+    open_with_header(invocation, log)?.write_all(stderr)?;
+    Ok(())
+}
+
+fn open_with_header(invocation: &Invocation, log: &Path) -> Result<File> {
+    let mut file = open(log)?;
+    writeln!(
+        file,
+        "== {} UTC {}",
+        audit::format_time(audit::now()),
+        invocation
+    )?;
+    Ok(file)
+}
+
+fn open(log: &Path) -> Result<File> {
+    if let Some(dir) = log.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    if fs::metadata(log).is_ok_and(|metadata| metadata.len() >= MAX_LOG_SIZE) {
+        let mut rotated = log.as_os_str().to_owned();
+        rotated.push(".1");
+        fs::rename(log, rotated)?;
+    }
+    Ok(OpenOptions::new().create(true).append(true).open(log)?)
+}
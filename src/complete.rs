@@ -0,0 +1,83 @@
+//! Completion of stored server names. The scripts clap generates are patched
+//! so that server name positions call the hidden `jump __complete-servers`.
+
+use clap_complete::Shell;
+
+const BASH: &str = r#"
+_jump_servers() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" profile=() words=() i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            --profile) profile=(--profile "${COMP_WORDS[i + 1]}"); ((i++)) ;;
+            -*) ;;
+            *) words+=("${COMP_WORDS[i]}") ;;
+        esac
+    done
+    case "${words[*]}" in
+        conn|rm|"rm "*|show|exec|ls|"ls "*|check|"check "*|export|"export "*|"tag add"|"tag rm"|"tag ls"|"option set"|"option unset"|"option ls"|"env set"|"env unset"|"env ls")
+            if [[ "$cur" != -* ]]; then
+                COMPREPLY=($(compgen -W "$(jump "${profile[@]}" __complete-servers 2>/dev/null)" -- "$cur"))
+                return 0
+            fi
+            ;;
+    esac
+    _jump "$@"
+}
+"#;
+
+const ZSH: &str = r#"
+_jump_servers() {
+    local -a servers profile
+    local i=${words[(I)--profile]}
+    (( i > 0 )) && profile=(--profile "${words[i + 1]}")
+    servers=(${(f)"$(jump $profile __complete-servers 2>/dev/null)"})
+    compadd -a servers
+}
+"#;
+
+const FISH: &str = r#"
+function __fish_jump_complete_servers
+    set -l words (string match -v -- '-*' (commandline -opc))
+    switch "$words[2..-1]"
+        case conn rm 'rm *' show exec ls 'ls *' check 'check *' export 'export *' 'tag add' 'tag rm' 'tag ls' 'option set' 'option unset' 'option ls' 'env set' 'env unset' 'env ls'
+            jump __complete-servers 2>/dev/null
+    end
+end
+complete -c jump -f -a "(__fish_jump_complete_servers)"
+"#;
+
+/// Add server name completion to a script generated for `shell`. Shells
+/// without support get the script back unchanged.
+pub fn with_server_names(shell: Shell, script: String) -> String {
+    match shell {
+        Shell::Bash => script.replace("complete -F _jump ", "complete -F _jump_servers ") + BASH,
+        Shell::Zsh => {
+            // The helper has to exist before the script calls `_jump`
+            let script = complete_server_positionals(&script);
+            match script.split_once('\n') {
+                Some((compdef, rest)) => format!("{}\n{}{}", compdef, ZSH, rest),
+                None => script,
+            }
+        }
+        Shell::Fish => script + FISH,
+        _ => script,
+    }
+}
+
+/// Have zsh call the helper for positionals named `server_name`, optional or
+/// not, and for lists of `server_names`
+fn complete_server_positionals(script: &str) -> String {
+    script
+        .split('\n')
+        .map(|line| {
+            let server = ["':server_name", "'::server_name", "'*::server_names"]
+                .iter()
+                .any(|spec| line.starts_with(spec));
+            match line.strip_suffix(":' \\") {
+                Some(spec) if server => format!("{}:_jump_servers' \\", spec),
+                _ => line.to_owned(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,625 @@
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use homedir::my_home;
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use crate::{
+    edit,
+    error::{self, JumpError},
+};
+
+/// User settings read from `~/.jump/config.toml`, or `$JUMP_CONFIG` when set
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Port used by `add` when none is given
+    pub default_port: u32,
+    /// Identity file used by `add ... ssh-key` when none is given
+    pub default_identity: String,
+    /// Seconds between keep-alive probes, passed as `ServerAliveInterval`
+    pub keep_alive: Option<u32>,
+    /// Options placed ahead of per-server options on every ssh command line
+    pub default_ssh_args: Vec<String>,
+    /// Compress traffic on every connection (`ssh -C`)
+    pub compression: bool,
+    /// Share connections through ssh control sockets, as if every server
+    /// had `--multiplex`
+    pub multiplex: bool,
+    /// Keep host keys in `~/.jump/known_hosts`, one file per profile,
+    /// instead of `~/.ssh/known_hosts`
+    pub isolated_known_hosts: bool,
+    /// Whether listings are colored; `--color` overrides it per invocation
+    pub color: ColorChoice,
+    /// Profile whose store is used when `--profile` is not given
+    pub default_profile: String,
+    pub confirm: Confirm,
+    /// Tags whose servers `conn`, `exec` and `cp` only touch after their
+    /// name is typed again, or with `--yes-prod`
+    pub confirm_tags: Vec<String>,
+    /// Refuse to connect whenever agent forwarding would be enabled
+    pub forbid_agent_forwarding: bool,
+    /// Local command run before every `conn`, ahead of the server's own hook
+    pub pre_connect: Option<String>,
+    /// Local command run after every `conn`, after the server's own hook
+    pub post_connect: Option<String>,
+    /// Seconds to wait for a woken server's ssh port to open
+    pub wake_timeout: u64,
+    /// Days `conn` and `exec` are kept in the connection log; 0 keeps them forever
+    pub log_retention_days: u32,
+    /// Copy the stderr of every `conn`, `exec` and `cp` to `~/.jump/logs/<server>.log`
+    pub log_connections: bool,
+    /// HTTP proxy, as `host:port`, for servers without one of their own
+    pub proxy: Option<String>,
+    /// Never change the store, as if `--read-only` was always given
+    pub readonly: bool,
+    /// Exports or `jump edit` documents whose servers are listed with the
+    /// store's at every run, but never changed; relative paths are taken
+    /// from the directory of this file
+    pub include: Vec<PathBuf>,
+    /// Page `ls` and `log` through `$PAGER` when they overflow the terminal
+    pub pager: bool,
+    /// Let `conn` take the start of a server name, offering a menu at a
+    /// terminal when it matches several
+    pub prefix_match: bool,
+    /// Refuse to add a server whose address and port another server has,
+    /// instead of warning
+    pub strict_duplicates: bool,
+    /// Remove expired servers, with a notice, whenever jump runs
+    pub prune_expired: bool,
+    /// Command opening a terminal window for `conn --new-window`, with
+    /// `{cmd}` replaced by the command to run, e.g. `alacritty -e {cmd}`
+    pub terminal_command: Option<String>,
+    /// Tab color of exported terminal profiles by tag, as `#rrggbb`; a
+    /// server takes the color of its first tag that has one
+    pub tag_colors: BTreeMap<String, String>,
+    /// Print the server's name, address, tags and note before `conn`
+    /// starts ssh, the name in its tag color
+    pub banner: bool,
+    /// Set the terminal title to the server's name during `conn`
+    pub set_title: bool,
+    /// How many servers `exec`, `cp --to` and `check` work on at once
+    pub parallel: usize,
+    /// How many of those may go through the same ProxyJump bastion at once
+    pub bastion_parallel: usize,
+    /// Run `conn` to key servers with `ssh -v` to record which key each
+    /// accepted, for `show` and `jump keys usage`
+    pub track_keys: bool,
+    /// Set once the first-run setup was offered, or a command succeeded
+    /// without it, so that `jump` does not offer it again
+    pub setup_done: bool,
+    /// How `conn --persist` reconnects
+    pub persist: Persist,
+    /// How `conn` ends sessions at a server's `max_session`
+    pub max_session: MaxSession,
+    /// Values `add` fills in for matching servers, keyed by an address
+    /// pattern such as `*.corp.example.com` or by `tag:<name>`
+    pub defaults: BTreeMap<String, Defaults>,
+    pub binaries: Binaries,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            default_port: 22,
+            default_identity: "~/.ssh/id_rsa".to_owned(),
+            keep_alive: None,
+            default_ssh_args: vec![],
+            compression: false,
+            multiplex: false,
+            isolated_known_hosts: false,
+            color: ColorChoice::Auto,
+            default_profile: "default".to_owned(),
+            confirm: Confirm::default(),
+            confirm_tags: vec![],
+            forbid_agent_forwarding: false,
+            pre_connect: None,
+            post_connect: None,
+            wake_timeout: 120,
+            log_retention_days: 90,
+            log_connections: false,
+            proxy: None,
+            readonly: false,
+            include: vec![],
+            pager: true,
+            prefix_match: false,
+            strict_duplicates: false,
+            prune_expired: false,
+            terminal_command: None,
+            tag_colors: BTreeMap::new(),
+            banner: false,
+            set_title: false,
+            parallel: 8,
+            bastion_parallel: 4,
+            track_keys: true,
+            setup_done: false,
+            persist: Persist::default(),
+            max_session: MaxSession::default(),
+            defaults: BTreeMap::new(),
+            binaries: Binaries::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Which destructive commands ask before acting
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Confirm {
+    pub rm: bool,
+    /// `exec`, `check` and `cp --to` ask before acting on more servers than
+    /// this at once
+    pub servers: usize,
+}
+
+impl Default for Confirm {
+    fn default() -> Confirm {
+        Confirm {
+            rm: false,
+            servers: 10,
+        }
+    }
+}
+
+/// When `conn --persist` reconnects, and how often
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Persist {
+    /// Seconds to wait before reconnecting, doubled after each attempt that
+    /// fails, up to a minute
+    pub backoff: u64,
+    /// Failed attempts in a row after which jump gives up; 0 never does
+    pub max_retries: u32,
+    /// Seconds a connection must have lasted for ssh failing to mean it
+    /// dropped, rather than that it could not be made
+    pub established: u64,
+}
+
+impl Default for Persist {
+    fn default() -> Persist {
+        Persist {
+            backoff: 2,
+            max_retries: 10,
+            established: 10,
+        }
+    }
+}
+
+/// When `conn` warns about a server's `max_session`, and how long ssh gets
+/// to exit once it is reached
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MaxSession {
+    /// Seconds before the end to warn on the terminal; 0 does not warn
+    pub warning: u64,
+    /// Seconds between SIGTERM and SIGKILL
+    pub grace: u64,
+}
+
+impl Default for MaxSession {
+    fn default() -> MaxSession {
+        MaxSession {
+            warning: 300,
+            grace: 10,
+        }
+    }
+}
+
+/// One `[defaults."<pattern>"]` rule: what `add` uses for the fields not
+/// given on the command line
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Defaults {
+    pub user: Option<String>,
+    /// Identity file of `ssh-key` servers
+    pub identity: Option<String>,
+    pub port: Option<u32>,
+}
+
+/// Explicit locations of the external programs jump spawns
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Binaries {
+    pub ssh: Option<PathBuf>,
+    pub sshpass: Option<PathBuf>,
+    pub scp: Option<PathBuf>,
+    pub sftp: Option<PathBuf>,
+    pub mosh: Option<PathBuf>,
+    pub script: Option<PathBuf>,
+    pub asciinema: Option<PathBuf>,
+    pub nc: Option<PathBuf>,
+    pub git: Option<PathBuf>,
+    pub tmux: Option<PathBuf>,
+    pub sshfs: Option<PathBuf>,
+    pub ssh_keyscan: Option<PathBuf>,
+    pub getent: Option<PathBuf>,
+    pub rsync: Option<PathBuf>,
+    pub ssh_add: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn path() -> Result<PathBuf> {
+        if let Some(path) = env::var_os("JUMP_CONFIG") {
+            return Ok(PathBuf::from(path));
+        }
+        Ok(jump_dir()?.join("config.toml"))
+    }
+
+    pub fn load() -> Result<Config> {
+        let path = Config::path()?;
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        parse(&content).map_err(|e| anyhow!("Invalid config {}: {}", path.display(), e))
+    }
+
+    /// Look up a dotted key such as `confirm.rm`, including defaulted values
+    pub fn get(&self, key: &str) -> Result<Value> {
+        let mut value = Value::try_from(self)?;
+        for part in key.split('.') {
+            value = value
+                .get(part)
+                .cloned()
+                .ok_or(anyhow!("Unknown config key `{}`", key))?;
+        }
+        Ok(value)
+    }
+
+    /// Set a dotted key in the config file, validating the result before writing it
+    pub fn set(key: &str, value: &str) -> Result<()> {
+        let path = Config::path()?;
+        let mut table = read_table(&path)?;
+        let (parents, last) = match key.rsplit_once('.') {
+            Some((parents, last)) => (parents.split('.').collect::<Vec<_>>(), last),
+            None => (vec![], key),
+        };
+        let mut current = &mut table;
+        for part in parents {
+            current = current
+                .entry(part)
+                .or_insert(Value::Table(Table::new()))
+                .as_table_mut()
+                .ok_or(anyhow!("Config key `{}` is not a table", part))?;
+        }
+        current.insert(last.to_owned(), parse_value(value));
+        let content = toml::to_string(&table)?;
+        parse(&content).map_err(|e| anyhow!("Invalid value for `{}`: {}", key, e))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, content)?;
+        Ok(())
+    }
+
+    /// Open the config file in `$EDITOR` and validate it afterwards
+    pub fn edit() -> Result<()> {
+        let path = Config::path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        edit::run_editor(&path)?;
+        Config::load().map(|_| ())
+    }
+}
+
+/// Where jump keeps its config and stores: `~/.jump`, or `%APPDATA%\jump`
+/// on Windows
+pub fn jump_dir() -> error::Result<PathBuf> {
+    if cfg!(windows) {
+        if let Some(appdata) = env::var_os("APPDATA") {
+            return Ok(PathBuf::from(appdata).join("jump"));
+        }
+    }
+    Ok(home_dir()?.join(".jump"))
+}
+
+/// Expand a leading `~` and, on Windows, `%VAR%` references such as
+/// `%USERPROFILE%\.ssh\id_rsa`
+pub fn expand_path(path: &Path) -> error::Result<PathBuf> {
+    let mut path = path.to_path_buf();
+    if cfg!(windows) {
+        path = PathBuf::from(expand_windows_vars(&path.to_string_lossy()));
+    }
+    match path.strip_prefix("~") {
+        Ok(rest) => Ok(home_dir()?.join(rest)),
+        Err(_) => Ok(path),
+    }
+}
+
+fn home_dir() -> error::Result<PathBuf> {
+    my_home()
+        .ok()
+        .flatten()
+        .ok_or(JumpError::HomeDirUnavailable)
+}
+
+/// Replace `%NAME%` with the variable's value, leaving unknown names as they are
+pub fn expand_windows_vars(text: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('%') {
+        let Some(len) = rest[start + 1..].find('%') else {
+            break;
+        };
+        let name = &rest[start + 1..start + 1 + len];
+        expanded.push_str(&rest[..start]);
+        match env::var(name) {
+            Ok(value) if !name.is_empty() => expanded.push_str(&value),
+            _ => expanded.push_str(&rest[start..start + len + 2]),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+fn read_table(path: &Path) -> Result<Table> {
+    if !path.exists() {
+        return Ok(Table::new());
+    }
+    let content = fs::read_to_string(path)?;
+    content
+        .parse::<Table>()
+        .map_err(|e| anyhow!("Invalid config {}: {}", path.display(), e))
+}
+
+fn parse(content: &str) -> Result<Config> {
+    let config: Config = toml::from_str(content).map_err(|e| anyhow!("{}", e))?;
+    validate_ssh_args(&config.default_ssh_args)?;
+    validate_tag_colors(&config.tag_colors)?;
+    validate_defaults(&config.defaults)?;
+    Ok(config)
+}
+
+fn validate_defaults(defaults: &BTreeMap<String, Defaults>) -> Result<()> {
+    for (pattern, rule) in defaults {
+        if pattern.is_empty() || pattern == "tag:" {
+            return Err(anyhow!("defaults rule `{}` matches nothing", pattern));
+        }
+        if let Some(port) = rule.port.filter(|port| !(1..=65535).contains(port)) {
+            return Err(anyhow!(
+                "defaults rule `{}` has port {}, not 1 to 65535",
+                pattern,
+                port
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_tag_colors(colors: &BTreeMap<String, String>) -> Result<()> {
+    for (tag, color) in colors {
+        let hex = color.strip_prefix('#').unwrap_or_default();
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(anyhow!(
+                "tag_colors entry `{}` is `{}`, not a `#rrggbb` color",
+                tag,
+                color
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// ssh options that consume the following argument as their value
+const SSH_VALUE_OPTIONS: &str = "BbcDEeFIiJLlmOoPpQRSWw";
+
+/// Reject anything ssh would not parse as an option, such as a bare
+/// destination, which would otherwise shift every argument after it.
+fn validate_ssh_args(args: &[String]) -> Result<()> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let flags = match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() && !flags.starts_with('-') => flags,
+            _ => {
+                return Err(anyhow!(
+                    "default_ssh_args entry `{}` is not an ssh option",
+                    arg
+                ))
+            }
+        };
+        if flags.len() == 1 && SSH_VALUE_OPTIONS.contains(flags) && args.next().is_none() {
+            return Err(anyhow!(
+                "default_ssh_args option `{}` is missing its value",
+                arg
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Interpret a command-line value as a TOML literal, falling back to a plain string
+fn parse_value(value: &str) -> Value {
+    format!("v = {}", value)
+        .parse::<Table>()
+        .ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or(Value::String(value.to_owned()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tool {
+    Ssh,
+    Sshpass,
+    Scp,
+    Sftp,
+    Mosh,
+    Script,
+    Asciinema,
+    Nc,
+    Git,
+    Tmux,
+    Sshfs,
+    Keyscan,
+    Getent,
+    Rsync,
+    SshAdd,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 15] = [
+        Tool::Ssh,
+        Tool::Sshpass,
+        Tool::Scp,
+        Tool::Sftp,
+        Tool::Mosh,
+        Tool::Script,
+        Tool::Asciinema,
+        Tool::Nc,
+        Tool::Git,
+        Tool::Tmux,
+        Tool::Sshfs,
+        Tool::Keyscan,
+        Tool::Getent,
+        Tool::Rsync,
+        Tool::SshAdd,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tool::Ssh => "ssh",
+            Tool::Sshpass => "sshpass",
+            Tool::Scp => "scp",
+            Tool::Sftp => "sftp",
+            Tool::Mosh => "mosh",
+            Tool::Script => "script",
+            Tool::Asciinema => "asciinema",
+            Tool::Nc => "nc",
+            Tool::Git => "git",
+            Tool::Tmux => "tmux",
+            Tool::Sshfs => "sshfs",
+            Tool::Keyscan => "ssh-keyscan",
+            Tool::Getent => "getent",
+            Tool::Rsync => "rsync",
+            Tool::SshAdd => "ssh-add",
+        }
+    }
+
+    pub fn env_var(&self) -> &'static str {
+        match self {
+            Tool::Ssh => "JUMP_SSH_BIN",
+            Tool::Sshpass => "JUMP_SSHPASS_BIN",
+            Tool::Scp => "JUMP_SCP_BIN",
+            Tool::Sftp => "JUMP_SFTP_BIN",
+            Tool::Mosh => "JUMP_MOSH_BIN",
+            Tool::Script => "JUMP_SCRIPT_BIN",
+            Tool::Asciinema => "JUMP_ASCIINEMA_BIN",
+            Tool::Nc => "JUMP_NC_BIN",
+            Tool::Git => "JUMP_GIT_BIN",
+            Tool::Tmux => "JUMP_TMUX_BIN",
+            Tool::Sshfs => "JUMP_SSHFS_BIN",
+            Tool::Keyscan => "JUMP_SSH_KEYSCAN_BIN",
+            Tool::Getent => "JUMP_GETENT_BIN",
+            Tool::Rsync => "JUMP_RSYNC_BIN",
+            Tool::SshAdd => "JUMP_SSH_ADD_BIN",
+        }
+    }
+
+    /// Whether a location for the tool was given explicitly, rather than
+    /// left to the PATH lookup
+    pub fn is_configured(&self, config: &Config) -> bool {
+        env::var_os(self.env_var()).is_some() || self.configured(&config.binaries).is_some()
+    }
+
+    fn configured<'a>(&self, binaries: &'a Binaries) -> Option<&'a PathBuf> {
+        match self {
+            Tool::Ssh => binaries.ssh.as_ref(),
+            Tool::Sshpass => binaries.sshpass.as_ref(),
+            Tool::Scp => binaries.scp.as_ref(),
+            Tool::Sftp => binaries.sftp.as_ref(),
+            Tool::Mosh => binaries.mosh.as_ref(),
+            Tool::Script => binaries.script.as_ref(),
+            Tool::Asciinema => binaries.asciinema.as_ref(),
+            Tool::Nc => binaries.nc.as_ref(),
+            Tool::Git => binaries.git.as_ref(),
+            Tool::Tmux => binaries.tmux.as_ref(),
+            Tool::Sshfs => binaries.sshfs.as_ref(),
+            Tool::Keyscan => binaries.ssh_keyscan.as_ref(),
+            Tool::Getent => binaries.getent.as_ref(),
+            Tool::Rsync => binaries.rsync.as_ref(),
+            Tool::SshAdd => binaries.ssh_add.as_ref(),
+        }
+    }
+}
+
+/// Where a binary path came from
+#[derive(Debug)]
+pub enum Source {
+    Env(&'static str),
+    Config,
+    SearchPath,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Env(var) => write!(f, "from ${}", var),
+            Source::Config => write!(f, "from config.toml"),
+            Source::SearchPath => write!(f, "from PATH"),
+        }
+    }
+}
+
+/// Resolve the binary for `tool`: environment variable first, then the
+/// config file, then a PATH lookup.
+pub fn resolve(config: &Config, tool: Tool) -> error::Result<(PathBuf, Source)> {
+    let configured = match env::var_os(tool.env_var()) {
+        Some(path) => Some((PathBuf::from(path), Source::Env(tool.env_var()))),
+        None => tool
+            .configured(&config.binaries)
+            .map(|path| (path.clone(), Source::Config)),
+    };
+    match configured {
+        Some((path, source)) => {
+            if path.is_file() {
+                Ok((path, source))
+            } else {
+                Err(JumpError::ToolNotFound {
+                    tool: tool.name().to_owned(),
+                    location: format!("at {} ({})", path.display(), source),
+                })
+            }
+        }
+        None => search_path(tool.name())
+            .map(|path| (path, Source::SearchPath))
+            .ok_or(JumpError::ToolNotFound {
+                tool: tool.name().to_owned(),
+                location: "in PATH".to_owned(),
+            }),
+    }
+}
+
+/// The first executable called `name` in a directory of PATH
+pub fn search_path(name: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file() || path.with_extension("exe").is_file()
+}
@@ -0,0 +1,749 @@
+//! `jump conn` and `jump console`: the session around ssh, from picking the
+//! address and passing the window and gate, through the hooks, waking,
+//! knocking and the host key, to the log entry, the recording, the session
+//! limit and reconnecting. `exec`, `cp` and `rekey` share its steps for
+//! passing gates, logging and running ssh.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use crate::{
+    access, agent, audit, banner, capture,
+    config::{self, Config},
+    debug,
+    error::{self, JumpError},
+    gate,
+    hooks::{post_connect_hooks, pre_connect_hooks, run_hook},
+    hostkey::{self, HostKey},
+    info,
+    key_usage::{self, KeyUse},
+    knock, limit,
+    log::{self, Verbosity},
+    proxy,
+    reconnect::{Next, Reconnect},
+    record, resolve,
+    server::{ConnectMethods, Platform, Server, X11},
+    signals,
+    ssh::{self, ConnOptions, SessionOptions},
+    store::Store,
+    style::Style,
+    warn, wol,
+};
+
+/// How a `conn` session went, for the caller to report
+#[derive(Debug)]
+pub enum Outcome {
+    /// A dry run, with the ssh that would have run, `None` for the built-in
+    /// client
+    DryRun {
+        server: Box<Server>,
+        invocation: Option<ssh::Invocation>,
+    },
+    /// A pre-connect hook failed, so no connection was made
+    HookFailed { hook: String, status: ExitStatus },
+    /// The session ended, for good
+    Ended {
+        /// What ssh or the built-in client exited with, `None` when killed
+        code: Option<i32>,
+        /// The post-connect hooks that failed, with how
+        failed_hooks: Vec<(String, ExitStatus)>,
+        /// The signal that ended the session, after which jump exits as ssh
+        /// did
+        caught: Option<i32>,
+    },
+}
+
+/// Connect to the console server of `server_name` with its
+/// `console_target` as the remote command, which ssh gives a tty
+pub fn console(
+    store: &Store,
+    config: &Config,
+    style: Style,
+    server_name: &str,
+    options: &ConnOptions,
+) -> Result<Outcome> {
+    let device = store.get(server_name)?;
+    let (Some(console_server), Some(target)) =
+        (device.console_server.clone(), device.console_target.clone())
+    else {
+        return Err(JumpError::invalid(
+            "console",
+            format!(
+                "{} has no console server, add one with `jump add ... --console-server \
+                 <SERVER> --console-target <COMMAND>`",
+                device.server_name
+            ),
+        )
+        .into());
+    };
+    let session = SessionOptions {
+        remote_command: Some(target),
+        console: Some(Box::new(device)),
+        ..SessionOptions::default()
+    };
+    self::session(
+        store,
+        config,
+        style,
+        &console_server,
+        options,
+        &session,
+        &[],
+    )
+}
+
+/// Open a `conn` session with `server_name`: pick its address, pass its
+/// window and gate, run the hooks, wake it and knock, check its host key,
+/// then run ssh, or the built-in client, until it ends for good
+pub fn session(
+    store: &Store,
+    config: &Config,
+    style: Style,
+    server_name: &str,
+    options: &ConnOptions,
+    session: &SessionOptions,
+    extra_args: &[String],
+) -> Result<Outcome> {
+    let (server, invocation) =
+        resolve::connection(store, config, server_name, options, session, extra_args)?;
+    if let Some(expires) = server.expires_at.filter(|_| server.expired(audit::now())) {
+        warn!(
+            "{} expired {} UTC, it may be gone",
+            server.server_name,
+            audit::format_time(expires)
+        );
+    }
+    if options.use_native() && options.proxy.is_some() {
+        warn!("--proxy is ignored by the built-in client");
+    }
+    if options.use_native() && !options.via.is_empty() {
+        warn!("--via is ignored by the built-in client");
+    }
+    if options.use_native() && session.persist {
+        warn!("--persist is ignored by the built-in client");
+    }
+    let limit = match (server.max_session, &session.no_limit) {
+        (Some(_), Some(_)) => None,
+        (None, Some(_)) => {
+            warn!(
+                "{} has no max_session, --no-limit changes nothing",
+                server.server_name
+            );
+            None
+        }
+        (_, None) if options.use_native() => {
+            if server.max_session.is_some() {
+                warn!("max_session is not enforced by the built-in client");
+            }
+            None
+        }
+        (max_session, None) => max_session.map(Duration::from_secs),
+    };
+    if limit.is_some() && cfg!(not(unix)) {
+        warn!("max_session is only enforced on unix");
+    }
+    let custom = matches!(server.method, ConnectMethods::Command(_));
+    if server.local_command.is_some() && !session.no_hooks && (custom || options.use_native()) {
+        warn!("the local command only runs through ssh, it is skipped");
+    }
+    let recording = recording_path(&server, session, options.use_native())?;
+    let log = match options.use_native() {
+        true if options.log_file.is_some() => {
+            warn!("--log-file is ignored by the built-in client");
+            None
+        }
+        true => None,
+        false => capture::path(config, options.log_file.as_deref(), &server.server_name)?,
+    };
+    let mut invocation = match (invocation, &recording) {
+        (Some(invocation), Some(path)) => Some(record::wrap(
+            config,
+            invocation,
+            session.record_format,
+            path,
+        )?),
+        (invocation, _) => invocation,
+    };
+    if ssh::x11_mode(&server, session) != X11::Off && std::env::var_os("DISPLAY").is_none() {
+        warn!("DISPLAY is not set, X11 forwarding will not work");
+    }
+    let has_command = session.remote_command.is_some() || server.remote_command.is_some();
+    if session.workdir.is_some() && has_command {
+        warn!("--cd is ignored because a remote command is set");
+    } else if session.workdir.is_some() && server.platform == Some(Platform::Windows) {
+        warn!("--cd is ignored on Windows servers");
+    }
+    if options.dry_run {
+        return Ok(Outcome::DryRun {
+            server: Box::new(server),
+            invocation,
+        });
+    }
+    access::pass(&server, options.override_window.as_deref(), audit::now())?;
+    if let Some(device) = &session.console {
+        access::pass(device, options.override_window.as_deref(), audit::now())?;
+    }
+    let confirmed = gate::pass(config, style, &server, options.yes_prod)?;
+    if !session.no_hooks {
+        for (hook, _) in pre_connect_hooks(config, &server) {
+            let status = run_hook(hook, &server, None)?;
+            if !status.success() {
+                return Ok(Outcome::HookFailed {
+                    hook: hook.to_owned(),
+                    status,
+                });
+            }
+        }
+    }
+    wol::wake_if_needed(config, &server, session.wake)?;
+    if !server.knock.is_empty() {
+        info!("knocking on {}", server.server_address);
+        knock::knock_and_wait(&server, knock::OPEN_TIMEOUT)?;
+    }
+    let first_scan = check_host_key(store, config, &server, options, session.accept_new_hostkey)?;
+    if invocation.is_some() {
+        check_passphrase(config, &server, options)?;
+    }
+    if let Some(dir) = recording.as_ref().and_then(|path| path.parent()) {
+        fs::create_dir_all(dir)?;
+    }
+    banner::show(config, style, &server);
+    if let (Some(device), Some(_)) = (&session.console, &invocation) {
+        info!(
+            "attaching to the console of {} through {}; type ~. at the start of a line to \
+             disconnect, ~? for the other escapes",
+            device.server_name, server.server_name
+        );
+    }
+    info!("connecting to server...");
+    // Until the hooks are done, as closing the terminal signals jump after
+    // ssh as well; not for the built-in client, which runs in jump
+    let signals = match &invocation {
+        Some(_) => Some(signals::catch()?),
+        None => None,
+    };
+    // Not for recordings, whose stderr goes through the recorder's terminal
+    let tracks_key = recording.is_none()
+        && invocation
+            .as_mut()
+            .is_some_and(|invocation| track_key(config, &server, invocation));
+    let entry = match &session.console {
+        // Under the device's name, the console server only being the way
+        // there, and with the device's window when it has one
+        Some(device) => {
+            let mut logged = server.clone();
+            logged.server_name = device.server_name.clone();
+            if device.access_window.is_some() {
+                logged.access_window = device.access_window.clone();
+            }
+            log_start(store, config, &logged, "console", confirmed, options)
+        }
+        None => log_start(store, config, &server, "conn", confirmed, options),
+    };
+    if let (Some(reason), Some(_)) = (&session.no_limit, server.max_session) {
+        audit::limit_lifted(store, entry, reason);
+    }
+    let started = Instant::now();
+    let title = banner::set_title(config, &server);
+    let timer = limit.filter(|_| invocation.is_some()).map(|limit| {
+        limit::start(
+            &server.server_name,
+            limit,
+            &config.max_session,
+            limit::terminal(),
+        )
+    });
+    let reached = || timer.as_ref().is_some_and(limit::Timer::reached);
+    let code = match (invocation, &signals) {
+        (Some(mut invocation), Some(signals)) => {
+            let mut reconnect = session.persist.then(|| Reconnect::new(&config.persist));
+            loop {
+                let started = Instant::now();
+                let received = signals.received();
+                let code = run_session(&invocation, log.as_deref(), tracks_key).map(|teed| {
+                    let code = teed.status.code();
+                    hint_too_many_failures(&server, options, code, &teed.tail);
+                    if tracks_key {
+                        record_key_use(store, &server, &teed.debug);
+                    }
+                    code
+                });
+                // A signal during the session ends it for good
+                let next = match (&mut reconnect, &code) {
+                    (Some(reconnect), Ok(exit)) if signals.received() == received => {
+                        reconnect.after(*exit, started.elapsed())
+                    }
+                    _ => Next::Stop,
+                };
+                let (Next::Reconnect(wait), Some(reconnect)) = (next, &reconnect) else {
+                    break code;
+                };
+                // The limit may come while waiting as well
+                if reached() || !wait_to_reconnect(&server, reconnect, wait, signals) || reached() {
+                    break code;
+                }
+                if recording.is_some() && reconnect.reconnects == 1 {
+                    record::append(&mut invocation, session.record_format);
+                }
+                info!(
+                    "reconnecting to {} (reconnect {})",
+                    server.server_name, reconnect.reconnects
+                );
+            }
+        }
+        (Some(_), None) => unreachable!("signals are caught around ssh"),
+        (None, _) => connect_natively(&server, session, extra_args),
+    };
+    let caught = signals.as_ref().and_then(signals::Catching::caught);
+    if signals.as_ref().is_some_and(signals::Catching::hung_up) {
+        // Writing to the terminal fails once it is gone, and nobody is
+        // left to read it
+        log::set_verbosity(Verbosity::Quiet);
+        std::mem::forget(title);
+    } else {
+        drop(title);
+    }
+    let reached = reached();
+    drop(timer);
+    audit::finish(store, entry, code.as_ref().ok().copied().flatten());
+    if reached {
+        audit::limit_reached(store, entry);
+    }
+    let code = code?;
+    match (reached, server.max_session) {
+        (true, Some(max_session)) => info!(
+            "disconnected from {} at its max_session of {}",
+            server.server_name,
+            audit::format_age(max_session)
+        ),
+        _ => info!("server disconnected"),
+    }
+    if let Some(scan) = first_scan {
+        record_host_key(store, &server, scan, code);
+    }
+    if let Some(path) = &recording {
+        info!("recording saved to {}", path.display());
+    }
+    debug!("session lasted {:.1?}", started.elapsed());
+    let mut failed_hooks = vec![];
+    if !session.no_hooks {
+        for (hook, _) in post_connect_hooks(config, &server) {
+            let status = run_hook(hook, &server, code)?;
+            if !status.success() {
+                failed_hooks.push((hook.to_owned(), status));
+            }
+        }
+    }
+    Ok(Outcome::Ended {
+        code,
+        failed_hooks,
+        caught,
+    })
+}
+
+/// Tell that the connection to `server` dropped and wait `wait` before
+/// connecting again; false when Ctrl-C was pressed twice meanwhile, or
+/// another signal came
+fn wait_to_reconnect(
+    server: &Server,
+    reconnect: &Reconnect,
+    wait: Duration,
+    signals: &signals::Catching,
+) -> bool {
+    let attempts = match reconnect.failures() {
+        (_, 0) => String::new(),
+        (failures, max) => format!(", attempt {} of {}", failures + 1, max),
+    };
+    info!(
+        "connection to {} lost, reconnecting in {}s{}; Ctrl-C twice to stop",
+        server.server_name,
+        wait.as_secs(),
+        attempts
+    );
+    let received = signals.received();
+    let until = Instant::now() + wait;
+    let mut warned = false;
+    loop {
+        match signals.received() - received {
+            0 => {}
+            _ if !signals.interrupted() => return false,
+            1 if !warned => {
+                info!("Ctrl-C again to stop reconnecting");
+                warned = true;
+            }
+            1 => {}
+            _ => return false,
+        }
+        let left = until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return true;
+        }
+        thread::sleep(left.min(Duration::from_millis(100)));
+    }
+}
+
+/// Add `-v` to a session with a key server, whose output tells which key
+/// it accepted, unless `track_keys` is off or ssh is verbose already
+fn track_key(config: &Config, server: &Server, invocation: &mut ssh::Invocation) -> bool {
+    let verbose = invocation
+        .args
+        .iter()
+        .any(|arg| arg.len() > 1 && arg.starts_with('-') && arg[1..].chars().all(|c| c == 'v'));
+    let key = matches!(
+        server.method,
+        ConnectMethods::SSHKey(_) | ConnectMethods::SshConfig(_)
+    );
+    if !config.track_keys || !key || verbose {
+        return false;
+    }
+    invocation.args.insert(0, "-v".to_owned());
+    true
+}
+
+/// Record the key ssh said `server` accepted, if it said so; nothing
+/// depends on it, so failing to is only worth a debug message
+fn record_key_use(store: &Store, server: &Server, debug: &[String]) {
+    if store.is_read_only() {
+        return;
+    }
+    let Some(accepted) = key_usage::accepted(debug) else {
+        debug!("ssh did not say which key {} accepted", server.server_name);
+        return;
+    };
+    let key_use = KeyUse {
+        server_name: server.server_name.clone(),
+        accepted,
+        used_at: audit::now(),
+    };
+    if let Err(e) = store.record_key_use(&key_use) {
+        debug!(
+            "could not record the key {} accepted: {}",
+            server.server_name, e
+        );
+    }
+}
+
+/// Compare the host key `server` presents with the recorded one, before
+/// anything is sent to it. Without a recorded key, starts the scan whose key
+/// gets recorded once the connection succeeded.
+fn check_host_key(
+    store: &Store,
+    config: &Config,
+    server: &Server,
+    options: &ConnOptions,
+    accept_new: bool,
+) -> Result<Option<thread::JoinHandle<error::Result<Vec<HostKey>>>>> {
+    // ssh-keyscan can neither run a custom command, go through a proxy or a
+    // jump host nor follow ~/.ssh/config
+    if matches!(
+        server.method,
+        ConnectMethods::Command(_) | ConnectMethods::SshConfig(_)
+    ) || !options.via.is_empty()
+        || proxy::resolve(config, server, options.proxy.as_ref())?.is_some()
+    {
+        return Ok(None);
+    }
+    let Some(recorded) = server.host_key.as_deref().and_then(HostKey::parse) else {
+        // Nothing to check, so the connection does not wait for it
+        return match hostkey::scan_in_background(config, server, hostkey::TIMEOUT) {
+            Ok(scan) => Ok(Some(scan)),
+            Err(e) => {
+                debug!("not recording the host key: {}", e);
+                Ok(None)
+            }
+        };
+    };
+    let scanned = match hostkey::scan(config, server, hostkey::TIMEOUT) {
+        Ok(scanned) => scanned,
+        Err(e) => {
+            warn!(
+                "could not check the host key of {}: {}",
+                server.server_name, e
+            );
+            return Ok(None);
+        }
+    };
+    match hostkey::check(&recorded, &scanned) {
+        hostkey::Check::Same => Ok(None),
+        hostkey::Check::Changed(found) if accept_new => {
+            warn!(
+                "the host key of {} changed to {}, recording it",
+                server.server_name, found
+            );
+            store.set_host_key(&server.server_name, Some(&found.to_string()))?;
+            Ok(None)
+        }
+        hostkey::Check::Changed(found) => Err(JumpError::HostKeyChanged {
+            name: server.server_name.clone(),
+            recorded: recorded.to_string(),
+            found: found.to_string(),
+        }
+        .into()),
+    }
+}
+
+/// Record the key a first-use scan found, if it is done by now and the
+/// connection got through
+fn record_host_key(
+    store: &Store,
+    server: &Server,
+    scan: thread::JoinHandle<error::Result<Vec<HostKey>>>,
+    code: Option<i32>,
+) {
+    if code.is_none_or(|code| code == ssh::FAILED) {
+        return;
+    }
+    if !scan.is_finished() {
+        debug!("the host key scan of {} did not finish", server.server_name);
+        return;
+    }
+    let key = match scan.join() {
+        Ok(Ok(keys)) => keys.into_iter().next(),
+        _ => None,
+    };
+    let Some(key) = key else {
+        return;
+    };
+    match store.set_host_key(&server.server_name, Some(&key.to_string())) {
+        Ok(()) => info!("recorded the host key of {}: {}", server.server_name, key),
+        Err(e) => debug!("could not record the host key: {}", e),
+    }
+}
+
+/// Where the `conn` session is recorded, if anywhere
+fn recording_path(
+    server: &Server,
+    session: &SessionOptions,
+    native: bool,
+) -> Result<Option<PathBuf>> {
+    if native {
+        if session.record.is_some() {
+            return Err(anyhow::anyhow!(
+                "--record is not supported by the built-in client"
+            ));
+        }
+        if server.always_record {
+            warn!("sessions through the built-in client are not recorded");
+        }
+        return Ok(None);
+    }
+    Ok(match (&session.record, server.always_record) {
+        (Some(Some(path)), _) => Some(config::expand_path(path)?),
+        (Some(None), _) | (None, true) => Some(record::default_path(
+            &server.server_name,
+            session.record_format,
+            audit::now(),
+        )?),
+        (None, false) => None,
+    })
+}
+
+/// Run a `conn` session through the built-in client, which only speaks
+/// plain sessions: no forwarding and no extra ssh options
+#[cfg(feature = "native-ssh")]
+fn connect_natively(
+    server: &Server,
+    session: &SessionOptions,
+    extra_args: &[String],
+) -> Result<Option<i32>> {
+    if ssh::x11_mode(server, session) != X11::Off || session.forward_agent || server.forward_agent {
+        return Err(anyhow::anyhow!(
+            "X11 and agent forwarding are not supported by the built-in client"
+        ));
+    }
+    if !server.options.is_empty() || !extra_args.is_empty() {
+        warn!("ssh options are ignored by the built-in client");
+    }
+    let env = ssh::session_env(server, session);
+    let command = ssh::remote_command(server, session);
+    let code = crate::native::connect(server, &env, command.as_deref())?;
+    Ok(Some(code as i32))
+}
+
+#[cfg(not(feature = "native-ssh"))]
+fn connect_natively(_: &Server, _: &SessionOptions, _: &[String]) -> Result<Option<i32>> {
+    unreachable!("--native only exists with the native-ssh feature")
+}
+
+/// Pass the `confirm_tags` gate of each server in turn, before anything
+/// runs on any of them; nothing to pass for a dry run
+pub fn pass_gates(
+    config: &Config,
+    style: Style,
+    servers: &[Server],
+    options: &ConnOptions,
+) -> Result<Vec<Option<gate::Ack>>> {
+    if options.dry_run {
+        return Ok(vec![None; servers.len()]);
+    }
+    for server in servers {
+        access::pass(server, options.override_window.as_deref(), audit::now())?;
+    }
+    Ok(servers
+        .iter()
+        .map(|server| gate::pass(config, style, server, options.yes_prod))
+        .collect::<error::Result<Vec<_>>>()?)
+}
+
+/// The `--override-window` reason when it is what let `server` through
+pub fn window_override<'a>(server: &Server, options: &'a ConnOptions) -> Option<&'a str> {
+    let needed = access::needs_override(server, audit::now());
+    options.override_window.as_deref().filter(|_| needed)
+}
+
+/// Start the log entry of `command` on `server`, with the reason
+/// `--override-window` gave when it let the server through
+pub fn log_start(
+    store: &Store,
+    config: &Config,
+    server: &Server,
+    command: &str,
+    confirmed: Option<gate::Ack>,
+    options: &ConnOptions,
+) -> Option<i64> {
+    let entry = audit::start(store, config, server, command, confirmed);
+    if let Some(reason) = window_override(server, options) {
+        audit::window_overridden(store, entry, reason);
+    }
+    entry
+}
+
+/// On a dry run, say why the command line has `IdentitiesOnly=yes`, which
+/// jump adds on its own; once per run
+pub fn note_identities_only(server: &Server, options: &ConnOptions, invocation: &ssh::Invocation) {
+    static NOTED: std::sync::Once = std::sync::Once::new();
+    let added = invocation
+        .args
+        .iter()
+        .any(|arg| arg == ssh::IDENTITIES_ONLY);
+    if added && ssh::identities_only(server, options) {
+        NOTED.call_once(|| {
+            info!(
+                "{} keeps ssh to the stored key instead of offering the agent's first; \
+                 --no-identities-only leaves it out",
+                ssh::IDENTITIES_ONLY
+            )
+        });
+    }
+}
+
+/// Before ssh asks for the passphrase of `server`'s key, say so when no
+/// agent could have spared it, or with `--add-to-agent` add the key to the
+/// agent first
+pub fn check_passphrase(config: &Config, server: &Server, options: &ConnOptions) -> Result<()> {
+    let ConnectMethods::SSHKey(key) = &server.method else {
+        return Ok(());
+    };
+    let path = config::expand_path(&key.path)?;
+    if agent::encrypted(&path) != Some(true) {
+        return Ok(());
+    }
+    match (agent::running(), options.add_to_agent) {
+        (false, false) => warn!(
+            "{} has a passphrase and no ssh-agent is running, ssh is about to ask for it",
+            path.display()
+        ),
+        (false, true) => warn!(
+            "{} has a passphrase and no ssh-agent is running to add it to, \
+             ssh is about to ask for it",
+            path.display()
+        ),
+        (true, false) => {}
+        (true, true) => match agent::add(config, &path) {
+            Ok(true) => {}
+            Ok(false) => warn!("ssh-add did not add {}", path.display()),
+            Err(e) => warn!("could not add {} to the agent: {}", path.display(), e),
+        },
+    }
+    Ok(())
+}
+
+/// Point at the agent when ssh gave up on `server` after too many keys,
+/// going by its exit code and the end of its stderr
+pub fn hint_too_many_failures(
+    server: &Server,
+    options: &ConnOptions,
+    code: Option<i32>,
+    stderr: &str,
+) {
+    if code != Some(ssh::FAILED) || !stderr.contains(ssh::TOO_MANY_FAILURES) {
+        return;
+    }
+    match (&server.method, ssh::identities_only(server, options)) {
+        (ConnectMethods::SSHKey(key), false) => warn!(
+            "{} stopped taking keys before ssh got to {}: the agent offers each of its keys \
+             first. Leave out --no-identities-only, or set `identities_only = true` with \
+             `jump edit`, to offer only that one",
+            server.server_name,
+            key.path.display()
+        ),
+        _ => warn!(
+            "{} stopped taking keys after too many tries: the agent offers each of its keys \
+             first (`ssh-add -l` lists them, `ssh-add -D` forgets them)",
+            server.server_name
+        ),
+    }
+}
+
+/// Run `invocation` without a terminal, failing with its stderr
+pub fn run_captured(invocation: &ssh::Invocation) -> std::result::Result<(), String> {
+    let output = run_output(invocation)?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(String::from_utf8_lossy(&output.stderr).into_owned()),
+    }
+}
+
+/// Run `invocation` without a terminal, failing only when it cannot start
+pub fn run_output(invocation: &ssh::Invocation) -> std::result::Result<Output, String> {
+    invocation
+        .command()
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("unable to run {}: {}", invocation.program.display(), e))
+}
+
+/// Run `invocation` to completion, returning its exit code. With a `log`,
+/// stderr is also appended to it.
+pub fn run_invocation(invocation: &ssh::Invocation, log: Option<&Path>) -> Result<Option<i32>> {
+    debug!("running {}", invocation);
+    let started = Instant::now();
+    let status = match log {
+        Some(log) => capture::status(invocation, log)?,
+        None => invocation.status()?,
+    };
+    debug!("{} after {:.1?}", status, started.elapsed());
+    Ok(status.code())
+}
+
+/// Like `run_invocation` with stderr piped through, keeping its end and
+/// telling whether ssh reported that it was denied, as it does when batch
+/// mode leaves it no way to log in
+pub fn run_keeping_tail(invocation: &ssh::Invocation, log: Option<&Path>) -> Result<capture::Teed> {
+    debug!("running {}", invocation);
+    let started = Instant::now();
+    let teed = capture::status_keeping_tail(invocation, log, ssh::AUTH_DENIED)?;
+    debug!("{} after {:.1?}", teed.status, started.elapsed());
+    Ok(teed)
+}
+
+/// `run_keeping_tail` for an interactive session, which signals reaching
+/// jump are passed on to
+fn run_session(
+    invocation: &ssh::Invocation,
+    log: Option<&Path>,
+    tracks_key: bool,
+) -> Result<capture::Teed> {
+    debug!("running {}", invocation);
+    let started = Instant::now();
+    let teed = capture::session_keeping_tail(invocation, log, ssh::AUTH_DENIED, tracks_key)?;
+    debug!("{} after {:.1?}", teed.status, started.elapsed());
+    Ok(teed)
+}
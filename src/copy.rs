@@ -0,0 +1,247 @@
+//! `jump cp`: a copy to or from one server, with rsync when it can resume
+//! and scp otherwise, and `cp --to`, the upload of one source to many
+//! servers at once. Results are handed to the caller as they come in.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::{
+    access, audit, capture,
+    config::{self, Config, Tool},
+    connect, debug, error, gate, report,
+    schedule::{self, Event, Limits},
+    server::Server,
+    ssh::{self, ConnOptions, Invocation},
+    store::Store,
+    style::Style,
+    warn,
+};
+
+/// What `cp --to` uploads where, and how
+pub struct Upload {
+    pub source: String,
+    pub destination: String,
+    pub recursive: bool,
+    pub continue_on_error: bool,
+}
+
+/// A server `upload` is done with
+pub struct Uploaded<'a> {
+    pub server: &'a Server,
+    /// How many servers are done, this one included
+    pub done: usize,
+    pub host: &'a report::Host,
+    /// What scp said, or why it could not run
+    pub message: &'a str,
+}
+
+/// The copy from `source` to `destination`, one of which is on `server`,
+/// with rsync when `resume` is asked for and it is on both ends
+pub fn invocation(
+    config: &Config,
+    server: &Server,
+    options: &ConnOptions,
+    source: &str,
+    destination: &str,
+    recursive: bool,
+    resume: bool,
+) -> Result<Invocation> {
+    if !options.dry_run {
+        connect::check_passphrase(config, server, options)?;
+    }
+    Ok(
+        match resume && rsync_on_both_ends(config, server, options)? {
+            true => ssh::resumable_copy(config, server, options, source, destination, recursive)?,
+            false => ssh::copy(config, server, options, source, destination, recursive)?,
+        },
+    )
+}
+
+/// Run the copy `invocation` to or from `server` once it passed its window
+/// and gate, returning its exit code
+pub fn run(
+    store: &Store,
+    config: &Config,
+    style: Style,
+    server: &Server,
+    options: &ConnOptions,
+    invocation: &Invocation,
+) -> Result<Option<i32>> {
+    access::pass(server, options.override_window.as_deref(), audit::now())?;
+    let confirmed = gate::pass(config, style, server, options.yes_prod)?;
+    let log = capture::path(config, options.log_file.as_deref(), &server.server_name)?;
+    // Only copies through a gate or outside a window are logged, to record
+    // that it was passed
+    let entry = match confirmed.is_some() || connect::window_override(server, options).is_some() {
+        true => connect::log_start(store, config, server, "cp", confirmed, options),
+        false => None,
+    };
+    let code = connect::run_invocation(invocation, log.as_deref());
+    audit::finish(store, entry, code.as_ref().ok().copied().flatten());
+    code
+}
+
+/// Whether `cp --resume` can use rsync, warning when not: it must be
+/// installed here and on the server. A dry run does not ask the server, and
+/// a server that cannot be reached is left to the transfer to report.
+fn rsync_on_both_ends(config: &Config, server: &Server, options: &ConnOptions) -> Result<bool> {
+    let missing = match config::resolve(config, Tool::Rsync) {
+        Err(_) => Some("here".to_owned()),
+        Ok(_) if options.dry_run => None,
+        Ok(_) => {
+            let probe = ssh::exec(
+                config,
+                server,
+                options,
+                &["command -v rsync".to_owned()],
+                ssh::ExecOptions::default(),
+            )?;
+            debug!("running {}", probe);
+            match connect::run_output(&probe) {
+                Ok(output)
+                    if !output.status.success() && output.status.code() != Some(ssh::FAILED) =>
+                {
+                    Some(format!("on {}", server.server_name))
+                }
+                _ => None,
+            }
+        }
+    };
+    if let Some(place) = &missing {
+        warn!(
+            "rsync is not installed {}, copying with scp, which starts over when interrupted",
+            place
+        );
+    }
+    Ok(missing.is_none())
+}
+
+/// The copy `upload` runs for each of `servers`
+pub fn uploads(
+    config: &Config,
+    servers: &[Server],
+    options: &ConnOptions,
+    upload: &Upload,
+) -> Vec<error::Result<Invocation>> {
+    servers
+        .iter()
+        .map(|server| {
+            let destination = format!("{}:{}", server.server_name, upload.destination);
+            ssh::copy(
+                config,
+                server,
+                options,
+                &upload.source,
+                &destination,
+                upload.recursive,
+            )
+        })
+        .collect()
+}
+
+/// Upload to every one of `servers`, which passed their gates as
+/// `confirmed`, as far as `limits` allow, handing each result to `uploaded`
+/// as it comes in. Unless `continue_on_error`, no upload starts once one has
+/// failed; the report entries, in the order of `servers`, have those left
+/// out as skipped.
+#[allow(clippy::too_many_arguments)]
+pub fn upload(
+    store: &Store,
+    config: &Config,
+    servers: &[Server],
+    upload: &Upload,
+    options: &ConnOptions,
+    limits: Limits,
+    report_lines: usize,
+    confirmed: Vec<Option<gate::Ack>>,
+    mut uploaded: impl FnMut(Uploaded),
+) -> Vec<report::Host> {
+    let invocations = uploads(config, servers, options, upload);
+    // Only uploads through a gate or outside a window are logged, to record
+    // that it was passed
+    let entries = servers
+        .iter()
+        .zip(confirmed)
+        .map(|(server, confirmed)| {
+            match confirmed.is_some() || connect::window_override(server, options).is_some() {
+                true => connect::log_start(store, config, server, "cp", confirmed, options),
+                false => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    let bastions = servers
+        .iter()
+        .map(|server| {
+            options
+                .via
+                .first()
+                .cloned()
+                .or_else(|| schedule::bastion(server))
+        })
+        .collect::<Vec<_>>();
+    let mut started = vec![Instant::now(); servers.len()];
+    let mut done = vec![];
+    schedule::run(
+        &bastions,
+        limits,
+        |i| match &invocations[i] {
+            Ok(invocation) => {
+                debug!("running {}", invocation);
+                connect::run_output(invocation)
+            }
+            Err(e) => Err(e.to_string()),
+        },
+        |event| {
+            let (i, output) = match event {
+                Event::Started(i) => {
+                    started[i] = Instant::now();
+                    return true;
+                }
+                Event::Finished(i, output) => (i, output),
+            };
+            let name = &servers[i].server_name;
+            let (host, message) = match output {
+                Ok(output) => {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    let host = report::Host::finished(
+                        name,
+                        started[i].elapsed(),
+                        output.status.code(),
+                        &stderr,
+                        report_lines,
+                    );
+                    (host, stderr.into_owned())
+                }
+                Err(message) => (report::Host::not_started(name, message.clone()), message),
+            };
+            let ok = host.is_ok();
+            uploaded(Uploaded {
+                server: &servers[i],
+                done: done.len() + 1,
+                host: &host,
+                message: &message,
+            });
+            done.push((i, host));
+            ok || upload.continue_on_error
+        },
+    );
+    let hosts = servers
+        .iter()
+        .enumerate()
+        .map(
+            |(i, server)| match done.iter().position(|(done, _)| *done == i) {
+                Some(at) => done.swap_remove(at).1,
+                None => report::Host::new(
+                    &server.server_name,
+                    report::Outcome::Skipped,
+                    Duration::ZERO,
+                ),
+            },
+        )
+        .collect::<Vec<_>>();
+    for (entry, host) in entries.into_iter().zip(&hosts) {
+        audit::finish(store, entry, host.exit_code);
+    }
+    hosts
+}
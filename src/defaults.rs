@@ -0,0 +1,80 @@
+//! The `[defaults."<pattern>"]` rules of the config, which give `add` a
+//! user, identity file and port for servers whose address matches the
+//! pattern, or that have the tag of a `tag:<name>` pattern. Whatever the
+//! command line gives wins. When several rules match, each value comes from
+//! the one with the longest pattern, taken as the most specific.
+
+use crate::{
+    config::{Config, Defaults},
+    select::glob,
+};
+
+/// A value and the pattern of the rule it came from; `None` for the
+/// config's own default
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sourced<T> {
+    pub value: T,
+    pub rule: Option<String>,
+}
+
+/// What the rules give one address
+#[derive(Debug, PartialEq)]
+pub struct Resolved {
+    /// The patterns of the matching rules, most specific first
+    pub matched: Vec<String>,
+    pub user: Option<Sourced<String>>,
+    pub identity: Sourced<String>,
+    pub port: Sourced<u32>,
+}
+
+/// Whether `pattern` covers `address`, ignoring case, or one of `tags`
+pub fn matches(pattern: &str, address: &str, tags: &[String]) -> bool {
+    match pattern.strip_prefix("tag:") {
+        Some(tag) => tags.iter().any(|own| own == tag),
+        None => glob(&pattern.to_ascii_lowercase(), &address.to_ascii_lowercase()),
+    }
+}
+
+pub fn resolve(config: &Config, address: &str, tags: &[String]) -> Resolved {
+    let mut rules = config
+        .defaults
+        .iter()
+        .filter(|(pattern, _)| matches(pattern, address, tags))
+        .collect::<Vec<_>>();
+    // Stable, so patterns of one length stay in the order of their names
+    rules.sort_by_key(|(pattern, _)| std::cmp::Reverse(pattern.len()));
+    let first = |field: fn(&Defaults) -> Option<&str>| {
+        rules.iter().find_map(|(pattern, rule)| {
+            field(rule).map(|value| Sourced {
+                value: value.to_owned(),
+                rule: Some(pattern.to_string()),
+            })
+        })
+    };
+    let user = first(|rule| rule.user.as_deref());
+    let identity = first(|rule| rule.identity.as_deref()).unwrap_or(Sourced {
+        value: config.default_identity.clone(),
+        rule: None,
+    });
+    let port = rules
+        .iter()
+        .find_map(|(pattern, rule)| {
+            rule.port.map(|value| Sourced {
+                value,
+                rule: Some(pattern.to_string()),
+            })
+        })
+        .unwrap_or(Sourced {
+            value: config.default_port,
+            rule: None,
+        });
+    Resolved {
+        matched: rules
+            .iter()
+            .map(|(pattern, _)| pattern.to_string())
+            .collect(),
+        user,
+        identity,
+        port,
+    }
+}
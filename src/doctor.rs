@@ -0,0 +1,300 @@
+//! `jump doctor`: what is wrong with the jump directory and the store, and
+//! how to fix it. Most findings come with a fix `jump doctor --fix` applies;
+//! the rest with the command to run by hand.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config,
+    error::Result,
+    leaks, legacy, multiplex, record,
+    server::{ConnectMethods, SSHKey},
+    ssh::shell_quote,
+    store::Store,
+};
+
+/// A problem and what to do about it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub problem: String,
+    pub fix: Fix,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fix {
+    /// Create a directory closed to others
+    CreateDir(PathBuf),
+    /// Apply the store's pending schema migrations
+    Migrate(PathBuf),
+    /// Close a file to others (chmod 600)
+    Chmod(PathBuf),
+    /// Store a server's method in the current format
+    Method {
+        store: PathBuf,
+        server_name: String,
+        method: String,
+    },
+    /// Delete tags, options and the like left behind by removed servers
+    DanglingRows(PathBuf),
+    /// Remove the lines that give jump a secret from a shell history
+    ScrubHistory(PathBuf),
+    /// Nothing jump can do; the command to run instead
+    Manual(String),
+}
+
+impl Fix {
+    pub fn is_manual(&self) -> bool {
+        matches!(self, Fix::Manual(_))
+    }
+
+    /// What applying the fix does, or the command to run for a manual one
+    pub fn describe(&self) -> String {
+        match self {
+            Fix::CreateDir(dir) => format!("create {} (mode 700)", dir.display()),
+            Fix::Migrate(store) => format!("migrate {}", store.display()),
+            Fix::Chmod(path) => format!("chmod 600 {}", path.display()),
+            Fix::Method {
+                server_name,
+                method,
+                ..
+            } => format!(
+                "store the method of {} as `{}:…`",
+                server_name,
+                method
+                    .split_once(':')
+                    .map_or(method.as_str(), |(kind, _)| kind)
+            ),
+            Fix::DanglingRows(store) => {
+                format!(
+                    "delete the rows of removed servers from {}",
+                    store.display()
+                )
+            }
+            Fix::ScrubHistory(file) => format!("remove those lines from {}", file.display()),
+            Fix::Manual(command) => format!("run `{}`", command),
+        }
+    }
+
+    pub fn apply(&self) -> Result<()> {
+        match self {
+            Fix::CreateDir(dir) => create_dir(dir),
+            Fix::Migrate(store) => Store::open_as_is(store)?.upgrade(),
+            Fix::Chmod(path) => chmod(path),
+            Fix::Method {
+                store,
+                server_name,
+                method,
+            } => Store::open_as_is(store)?.set_raw_method(server_name, method),
+            Fix::DanglingRows(store) => {
+                Store::open_as_is(store)?.remove_dangling_rows().map(|_| ())
+            }
+            Fix::ScrubHistory(file) => leaks::scrub(file).map(|_| ()),
+            Fix::Manual(_) => Ok(()),
+        }
+    }
+}
+
+/// Everything wrong with the jump directory and the store at `store_path`,
+/// in the order the fixes should be applied
+pub fn check(store_path: &Path) -> Result<Vec<Finding>> {
+    let mut findings = vec![];
+    let mut finding = |problem: String, fix: Fix| findings.push(Finding { problem, fix });
+
+    let jump_dir = config::jump_dir()?;
+    if !jump_dir.exists() {
+        finding(
+            format!("{} does not exist", jump_dir.display()),
+            Fix::CreateDir(jump_dir.clone()),
+        );
+    }
+    for dir in [multiplex::socket_dir()?, record::dir()?] {
+        if !dir.exists() {
+            finding(
+                format!("{} does not exist", dir.display()),
+                Fix::CreateDir(dir),
+            );
+        }
+    }
+
+    if !store_path.exists() {
+        finding(
+            format!("there is no store at {}", store_path.display()),
+            Fix::Manual("jump init".to_owned()),
+        );
+        return Ok(findings);
+    }
+    if is_open(store_path) {
+        finding(
+            format!("{} can be read by others", store_path.display()),
+            Fix::Chmod(store_path.to_owned()),
+        );
+    }
+    let store = Store::open_as_is(store_path)?;
+    if !store.is_initialized()? {
+        finding(
+            format!("{} has no servers table", store_path.display()),
+            Fix::Manual("jump init".to_owned()),
+        );
+        return Ok(findings);
+    }
+    let pending = store.pending_migrations()?;
+    if pending > 0 {
+        finding(
+            format!(
+                "{} is missing {} schema change{}",
+                store_path.display(),
+                pending,
+                if pending == 1 { "" } else { "s" }
+            ),
+            Fix::Migrate(store_path.to_owned()),
+        );
+    }
+
+    let mut keys: Vec<PathBuf> = vec![];
+    for (server_name, raw) in store.raw_methods()? {
+        let method = match current_method(&raw) {
+            Some(method) if method == raw => method,
+            Some(method) => {
+                finding(
+                    format!("{} has its method in an old format", server_name),
+                    Fix::Method {
+                        store: store_path.to_owned(),
+                        server_name: server_name.clone(),
+                        method: method.clone(),
+                    },
+                );
+                method
+            }
+            None => {
+                finding(
+                    format!("the method of {} is not one jump knows", server_name),
+                    Fix::Manual("jump edit --all --include-secrets".to_owned()),
+                );
+                continue;
+            }
+        };
+        let ConnectMethods::SSHKey(SSHKey { path }) = ConnectMethods::from(method) else {
+            continue;
+        };
+        let key = config::expand_path(&path)?;
+        if keys.contains(&key) {
+            continue;
+        }
+        if !key.exists() {
+            finding(
+                format!(
+                    "the key {} of {} does not exist",
+                    key.display(),
+                    server_name
+                ),
+                Fix::Manual(format!(
+                    "jump set key <path> --filter name:{}",
+                    shell_quote(&server_name)
+                )),
+            );
+        } else if is_open(&key) {
+            finding(
+                format!("the key {} can be read by others", key.display()),
+                Fix::Chmod(key.clone()),
+            );
+        }
+        keys.push(key);
+    }
+
+    // Older schemas may not have the options table yet
+    if pending > 0 {
+        return Ok(findings);
+    }
+    for (server_name, _) in store.raw_methods()? {
+        let weak = store
+            .options(&server_name)?
+            .into_iter()
+            .filter(|(key, _)| legacy::is_algorithm_option(key))
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        if !weak.is_empty() {
+            finding(
+                format!(
+                    "{} still allows legacy algorithms ({})",
+                    server_name,
+                    weak.join(", ")
+                ),
+                Fix::Manual(format!(
+                    "jump option unset {} --legacy",
+                    shell_quote(&server_name)
+                )),
+            );
+        }
+    }
+    Ok(findings)
+}
+
+/// `raw` as the current `kind:value` serialization, or `None` when it is
+/// not a method at all. Old rows spell the kind out (`password:hunter2`,
+/// `key:~/.ssh/id_rsa`) or hold only a key path (`~/.ssh/id_rsa`).
+pub fn current_method(raw: &str) -> Option<String> {
+    let (kind, value) = match raw.split_once(':') {
+        Some((kind, value)) if !is_path(raw) => (kind, value),
+        _ if is_path(raw) => ("ssh", raw),
+        _ => return None,
+    };
+    let kind = match kind {
+        "ssh" | "pass" | "cmd" | "config" => kind,
+        "ssh-key" | "sshkey" | "key" => "ssh",
+        "password" | "passwd" => "pass",
+        "command" | "custom" => "cmd",
+        _ => return None,
+    };
+    Some(format!("{}:{}", kind, value))
+}
+
+/// Whether a whole method column is a key path rather than `kind:value`
+fn is_path(raw: &str) -> bool {
+    raw.starts_with('~') || raw.starts_with('/')
+}
+
+/// Whether others can read or write `path`
+#[cfg(unix)]
+pub fn is_open(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o077 != 0)
+}
+
+#[cfg(not(unix))]
+pub fn is_open(_: &Path) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn create_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    Ok(fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?)
+}
+
+#[cfg(not(unix))]
+fn create_dir(dir: &Path) -> Result<()> {
+    Ok(fs::create_dir_all(dir)?)
+}
+
+#[cfg(unix)]
+fn chmod(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Ok(fs::set_permissions(
+        path,
+        fs::Permissions::from_mode(0o600),
+    )?)
+}
+
+#[cfg(not(unix))]
+fn chmod(_: &Path) -> Result<()> {
+    Ok(())
+}
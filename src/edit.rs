@@ -0,0 +1,293 @@
+//! `jump edit --all`: the store as a TOML document opened in `$EDITOR`, and
+//! the changes made to it applied in one transaction. An entry's id ties it
+//! to its server, so renaming one is a change rather than a removal and an
+//! addition.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    env,
+    fmt::{self, Display},
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+    process::{self, Command},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    error::{JumpError, Result},
+    server::{ConnectMethods, Server},
+    store::Store,
+    sync,
+};
+
+/// Stands for a password left out of the document
+pub const SECRET: &str = "<secret>";
+
+const HEADER: &str = "\
+# The servers of the store. An entry's id ties it to its server: leave it out
+# for a new server, and delete an entry to remove its server. Passwords shown
+# as \"<secret>\" stay as they are. Save an empty file to change nothing.
+";
+
+/// The servers as `jump edit` shows them, which `include` files may also be
+#[derive(Serialize, Deserialize)]
+pub struct Document {
+    #[serde(default)]
+    pub servers: Vec<Server>,
+}
+
+pub enum Change {
+    Add(Server),
+    Remove(Server),
+    Update { old: Box<Server>, new: Box<Server> },
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Add(server) => write!(f, "+ {}", server.server_name),
+            Change::Remove(server) => write!(f, "- {}", server.server_name),
+            Change::Update { old, new } => {
+                write!(f, "~ {}", old.server_name)?;
+                let (old, new) = (shown(old), shown(new));
+                let fields = sync::differences(&old, &new);
+                if fields.is_empty() {
+                    return write!(f, "\n    password changed");
+                }
+                for field in fields {
+                    write!(
+                        f,
+                        "\n    {}: {} → {}",
+                        field,
+                        render(&old[&field]),
+                        render(&new[&field])
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A server as its changes are shown, without its password
+fn shown(server: &Server) -> Value {
+    let mut value = serde_json::to_value(server).expect("servers serialize");
+    if let Some(password) = value.pointer_mut("/method/Password/password") {
+        *password = Value::from(SECRET);
+    }
+    value
+}
+
+fn render(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => "none".to_owned(),
+        _ => value.to_string(),
+    }
+}
+
+/// The document `servers` are edited as, with `SECRET` for their passwords
+/// unless `include_secrets` is given
+pub fn document(servers: &[Server], include_secrets: bool) -> Result<String> {
+    let servers = servers
+        .iter()
+        .map(|server| {
+            let mut server = serde_json::from_value::<Server>(
+                serde_json::to_value(server).expect("servers serialize"),
+            )
+            .expect("servers deserialize");
+            server.created_at = None;
+            server.updated_at = None;
+            if let ConnectMethods::Password(password) = &mut server.method {
+                if !include_secrets {
+                    password.password = SECRET.to_owned();
+                }
+            }
+            server
+        })
+        .collect();
+    let body = toml::to_string_pretty(&Document { servers })
+        .map_err(|e| JumpError::invalid("servers", e.to_string()))?;
+    Ok(format!("{}\n{}", HEADER, body))
+}
+
+/// What the edited `text` changes about `current`, or why it cannot be used
+pub fn changes(current: &[Server], text: &str) -> std::result::Result<Vec<Change>, String> {
+    let document: Document = toml::from_str(text).map_err(|e| e.to_string())?;
+    let by_id = current
+        .iter()
+        .filter_map(|server| Some((server.id?, server)))
+        .collect::<HashMap<_, _>>();
+    let mut ids = BTreeSet::new();
+    let mut names = BTreeSet::new();
+    let mut changes = vec![];
+    for mut server in document.servers {
+        if !names.insert(server.server_name.clone()) {
+            return Err(format!("{} names more than one entry", server.server_name));
+        }
+        let existing = match server.id {
+            Some(id) => {
+                if !ids.insert(id) {
+                    return Err(format!("id {} is given to more than one entry", id));
+                }
+                Some(
+                    *by_id
+                        .get(&id)
+                        .ok_or_else(|| format!("no server has id {}", id))?,
+                )
+            }
+            None => None,
+        };
+        if let ConnectMethods::Password(password) = &mut server.method {
+            if password.password == SECRET {
+                match existing.map(|existing| &existing.method) {
+                    Some(ConnectMethods::Password(current)) => {
+                        password.password.clone_from(&current.password)
+                    }
+                    _ => {
+                        return Err(format!(
+                        "{}: \"{}\" only stands for a stored password, give the password itself",
+                        server.server_name, SECRET
+                    ))
+                    }
+                }
+            }
+        }
+        let Some(existing) = existing else {
+            changes.push(Change::Add(server));
+            continue;
+        };
+        server.order_index = existing.order_index;
+        server.created_at = existing.created_at;
+        server.updated_at = existing.updated_at;
+        let unchanged = serde_json::to_value(&server).ok() == serde_json::to_value(existing).ok();
+        if !unchanged {
+            changes.push(Change::Update {
+                old: Box::new(clone(existing)),
+                new: Box::new(server),
+            });
+        }
+    }
+    changes.extend(
+        current
+            .iter()
+            .filter(|server| server.id.is_some_and(|id| !ids.contains(&id)))
+            .map(|server| Change::Remove(clone(server))),
+    );
+    Ok(changes)
+}
+
+fn clone(server: &Server) -> Server {
+    let mut copy: Server =
+        serde_json::from_value(serde_json::to_value(server).expect("servers serialize"))
+            .expect("servers deserialize");
+    copy.order_index = server.order_index;
+    copy
+}
+
+/// Apply `changes` in one transaction. Changed servers are replaced, keeping
+/// their id, place in the order and creation time.
+pub fn apply(store: &Store, changes: Vec<Change>) -> Result<()> {
+    store.transaction(|| {
+        for change in &changes {
+            match change {
+                Change::Remove(old) => {
+                    store.remove(&old.server_name)?;
+                }
+                Change::Update { old, .. } => {
+                    store.remove(&old.server_name)?;
+                }
+                Change::Add(_) => {}
+            }
+        }
+        for change in &changes {
+            match change {
+                Change::Add(server) => store.add(server)?,
+                Change::Update { new, .. } => store.add(&Server {
+                    updated_at: None,
+                    ..clone(new)
+                })?,
+                Change::Remove(_) => {}
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Let the user edit `servers` until the document parses. `None` when the
+/// file is saved empty, or without changes.
+pub fn edit(servers: &[Server], include_secrets: bool) -> Result<Option<Vec<Change>>> {
+    let path = env::temp_dir().join(format!("jump-edit-{}.toml", process::id()));
+    let original = document(servers, include_secrets)?;
+    write_private(&path, &original)?;
+    let edited = edit_until_valid(&path, servers, &original);
+    fs::remove_file(&path).ok();
+    edited
+}
+
+fn edit_until_valid(
+    path: &Path,
+    servers: &[Server],
+    original: &str,
+) -> Result<Option<Vec<Change>>> {
+    loop {
+        run_editor(path)?;
+        let text = fs::read_to_string(path)?;
+        let empty = text
+            .lines()
+            .all(|line| line.trim().is_empty() || line.trim_start().starts_with('#'));
+        if empty || text == original {
+            return Ok(None);
+        }
+        match changes(servers, &text) {
+            Ok(changes) => return Ok(Some(changes)),
+            Err(reason) => write_private(path, &annotate(&text, &reason))?,
+        }
+    }
+}
+
+/// `text` headed by `reason`, replacing the reason a previous attempt got
+fn annotate(text: &str, reason: &str) -> String {
+    let mut annotated = String::new();
+    for line in reason.lines() {
+        annotated.push_str(&format!("# error: {}\n", line));
+    }
+    annotated.push_str("# error: fix it and save again, or save an empty file to give up\n");
+    for line in text.lines().skip_while(|line| line.starts_with("# error:")) {
+        annotated.push_str(line);
+        annotated.push('\n');
+    }
+    annotated
+}
+
+/// Passwords may be written to the file, so only its owner can read it
+fn write_private(path: &Path, text: &str) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(text.as_bytes())?;
+    Ok(())
+}
+
+/// Open `path` in `$EDITOR`, or vi, and wait for it to exit
+pub fn run_editor(path: &Path) -> Result<()> {
+    let editor = env::var("EDITOR").unwrap_or("vi".to_owned());
+    let status = Command::new(&editor)
+        .arg(path)
+        .status()
+        .map_err(|source| JumpError::Spawn {
+            program: editor.clone(),
+            source,
+        })?;
+    if !status.success() {
+        return Err(JumpError::CommandFailed {
+            command: editor,
+            message: format!("exited with {}", status),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,191 @@
+//! Failures the library reports, typed so callers can tell them apart. The
+//! binary turns them into `anyhow` errors and exit codes.
+
+use std::{io, path::PathBuf};
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum JumpError {
+    /// No server with this name in the store
+    #[error("No server named {name}")]
+    NotFound { name: String },
+    /// How to connect to a server could not be worked out
+    #[error("Unable to resolve {name}: {reason}")]
+    Unresolved { name: String, reason: String },
+    /// A selector other than a plain name matched no server
+    #[error("No server matches {selector}")]
+    NoMatch { selector: String },
+    /// The start of a name matched several servers where one was needed
+    #[error("{name} matches several servers: {}", candidates.join(", "))]
+    Ambiguous {
+        name: String,
+        candidates: Vec<String>,
+    },
+    /// A server with this name is already stored
+    #[error("A server named {name} already exists")]
+    Duplicate { name: String },
+    /// Other servers already point at the address and port, which
+    /// `strict_duplicates` forbids
+    #[error("{address} is already the address of {}; strict_duplicates forbids adding {name}", others.join(", "))]
+    DuplicateAddress {
+        name: String,
+        address: String,
+        others: Vec<String>,
+    },
+    /// The database could not be opened, read or written
+    #[error("Database error: {0}")]
+    Store(#[from] rusqlite::Error),
+    /// A child process could not be started at all
+    #[error("Unable to run {program}: {source}")]
+    Spawn { program: String, source: io::Error },
+    /// No usable binary for an external tool
+    #[error("{tool} not found {location}")]
+    ToolNotFound { tool: String, location: String },
+    /// A change to a store opened with `--read-only`
+    #[error("Store is read-only")]
+    ReadOnly,
+    /// A change to a server read from an `include` file
+    #[error("{name} comes from {}, which jump does not change; edit that file instead", file.display())]
+    Included { name: String, file: PathBuf },
+    /// The server's method was written by a version of jump, likely a newer
+    /// one, that this one cannot read
+    #[error("{name} uses a connection method this version of jump does not know; it was probably added by a newer jump, which is needed to connect to it")]
+    UnsupportedMethod { name: String },
+    /// A value given by the user that jump cannot work with
+    #[error("Invalid {field}: {reason}")]
+    InvalidInput { field: String, reason: String },
+    /// The connection would forward the agent despite `forbid_agent_forwarding`
+    #[error("Agent forwarding for {name} is forbidden by forbid_agent_forwarding in the config")]
+    AgentForwardingForbidden { name: String },
+    /// An external command ran but reported failure
+    #[error("{command} failed: {message}")]
+    CommandFailed { command: String, message: String },
+    /// A question would have been asked under `--non-interactive`
+    #[error("Not asking {what} in non-interactive mode; {instead}")]
+    NonInteractive { what: String, instead: String },
+    /// ssh was denied in batch mode, where it may not ask for a password or
+    /// passphrase
+    #[error("Authentication to {name} failed (batch mode): no key or agent identity was accepted")]
+    BatchAuthFailed { name: String },
+    /// ssh-keyscan got no host key from the server
+    #[error("No host key could be fetched from {name}")]
+    HostKeyUnavailable { name: String },
+    /// The server presents another host key than the one recorded for it
+    #[error(
+        "The host key of {name} changed from {recorded} to {found}; if the server was rebuilt, \
+         connect with --accept-new-hostkey"
+    )]
+    HostKeyChanged {
+        name: String,
+        recorded: String,
+        found: String,
+    },
+    /// `add --verify=strict` could not connect to the server, so it was
+    /// removed again
+    #[error("Could not connect to {name}, so it was not added: {reason}")]
+    Unverified { name: String, reason: String },
+    /// The name typed at a `confirm_tags` gate was not the server's
+    #[error("{typed:?} is not {name}, so nothing was run on it")]
+    NotConfirmed { name: String, typed: String },
+    /// The server's `access_window` was closed and `--override-window` not
+    /// given
+    #[error(
+        "{name} may only be accessed {window}, next at {next}; pass --override-window <REASON> \
+         to go ahead anyway"
+    )]
+    OutsideWindow {
+        name: String,
+        window: String,
+        next: String,
+    },
+    /// The server's `access_window` could not be read, as with a time zone
+    /// this system does not know, and `--override-window` was not given
+    #[error(
+        "The access window of {name} cannot be read, so it is taken as closed: {reason}; pass \
+         --override-window <REASON> to go ahead anyway"
+    )]
+    InvalidWindow { name: String, reason: String },
+    /// An encrypted bundle did not decrypt with the passphrase given
+    #[error("Wrong passphrase, or the bundle is damaged")]
+    WrongPassphrase,
+    #[error("Unable to determine home directory")]
+    HomeDirUnavailable,
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl JumpError {
+    pub fn invalid(field: &str, reason: impl Into<String>) -> JumpError {
+        JumpError::InvalidInput {
+            field: field.to_owned(),
+            reason: reason.into(),
+        }
+    }
+
+    /// The variant in snake case, for clients telling errors apart without
+    /// parsing the message
+    pub fn kind(&self) -> &'static str {
+        match self {
+            JumpError::NotFound { .. } => "not_found",
+            JumpError::Unresolved { .. } => "unresolved",
+            JumpError::NoMatch { .. } => "no_match",
+            JumpError::Ambiguous { .. } => "ambiguous",
+            JumpError::Duplicate { .. } => "duplicate",
+            JumpError::DuplicateAddress { .. } => "duplicate_address",
+            JumpError::Store(_) => "store",
+            JumpError::Spawn { .. } => "spawn",
+            JumpError::ToolNotFound { .. } => "tool_not_found",
+            JumpError::ReadOnly => "read_only",
+            JumpError::Included { .. } => "included",
+            JumpError::UnsupportedMethod { .. } => "unsupported_method",
+            JumpError::InvalidInput { .. } => "invalid_input",
+            JumpError::AgentForwardingForbidden { .. } => "agent_forwarding_forbidden",
+            JumpError::CommandFailed { .. } => "command_failed",
+            JumpError::NonInteractive { .. } => "non_interactive",
+            JumpError::BatchAuthFailed { .. } => "batch_auth_failed",
+            JumpError::HostKeyUnavailable { .. } => "host_key_unavailable",
+            JumpError::HostKeyChanged { .. } => "host_key_changed",
+            JumpError::Unverified { .. } => "unverified",
+            JumpError::NotConfirmed { .. } => "not_confirmed",
+            JumpError::OutsideWindow { .. } => "outside_window",
+            JumpError::InvalidWindow { .. } => "invalid_window",
+            JumpError::WrongPassphrase => "wrong_passphrase",
+            JumpError::HomeDirUnavailable => "home_dir_unavailable",
+            JumpError::Io(_) => "io",
+        }
+    }
+}
+
+pub type Result<T, E = JumpError> = std::result::Result<T, E>;
+
+/// Exit codes of the `jump` binary; `conn` and `exec` exit with the code of
+/// the remote command or ssh instead once it has started
+pub mod exit {
+    pub const SUCCESS: i32 = 0;
+    /// Anything without a more specific code, and servers that are down
+    /// in `jump check`
+    pub const FAILURE: i32 = 1;
+    /// Invalid command line, as reported by clap
+    pub const USAGE: i32 = 2;
+    pub const NOT_FOUND: i32 = 3;
+    /// A server with the name, or with `strict_duplicates` the address,
+    /// is already stored
+    pub const DUPLICATE: i32 = 4;
+    /// The database could not be opened, read or written, or is read-only
+    pub const STORE: i32 = 5;
+    pub const SPAWN: i32 = 6;
+}
+
+/// The exit code for a failed command
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    match error.downcast_ref::<JumpError>() {
+        Some(
+            JumpError::NotFound { .. } | JumpError::NoMatch { .. } | JumpError::Unresolved { .. },
+        ) => exit::NOT_FOUND,
+        Some(JumpError::Duplicate { .. } | JumpError::DuplicateAddress { .. }) => exit::DUPLICATE,
+        Some(JumpError::Store(_) | JumpError::ReadOnly) => exit::STORE,
+        Some(JumpError::Spawn { .. } | JumpError::ToolNotFound { .. }) => exit::SPAWN,
+        _ => exit::FAILURE,
+    }
+}
@@ -0,0 +1,267 @@
+//! `jump exec`: a command run on one server, or in batch mode on many at
+//! once with each one's output captured, so that the caller can print it
+//! whole under the server's name. A login batch mode denied is told apart
+//! from a command that failed.
+
+use std::{process::Output, time::Instant};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    audit, capture,
+    config::Config,
+    connect, debug,
+    error::{self, exit, JumpError},
+    gate, report,
+    schedule::{self, Event, Limits},
+    server::Server,
+    ssh::{self, ConnOptions, ExecOptions},
+    store::Store,
+};
+
+/// A server `parallel` is done with
+pub struct Finished<'a> {
+    pub server: &'a Server,
+    /// How many servers are done, this one included
+    pub done: usize,
+    /// What ssh printed, unless it could not run
+    pub output: Option<&'a Output>,
+    /// The exit code and the end of stderr, as `on_server` returns them
+    pub result: &'a Result<(i32, String)>,
+}
+
+/// What `on_server` would run, as `--dry-run` prints it
+pub fn dry_run(
+    config: &Config,
+    server: &Server,
+    options: &ConnOptions,
+    exec: ExecOptions,
+    command: &[String],
+) -> Result<String> {
+    #[cfg(feature = "native-ssh")]
+    if options.use_native() {
+        warn_native(options);
+        return Ok(format!(
+            "built-in client: {} {}",
+            ssh::destination(server),
+            command.join(" ")
+        ));
+    }
+    let invocation = ssh::exec(config, server, options, command, exec)?;
+    connect::note_identities_only(server, options, &invocation);
+    Ok(invocation.to_string())
+}
+
+/// Run the command, returning the exit code of the remote command or ssh and
+/// the end of its stderr
+pub fn on_server(
+    store: &Store,
+    config: &Config,
+    server: &Server,
+    options: &ConnOptions,
+    exec: ExecOptions,
+    confirmed: Option<gate::Ack>,
+    command: &[String],
+) -> Result<(i32, String)> {
+    #[cfg(feature = "native-ssh")]
+    if options.use_native() {
+        warn_native(options);
+        let entry = connect::log_start(store, config, server, "exec", confirmed, options);
+        // Like ssh, join the words with spaces for the remote shell
+        let code = crate::native::exec(server, &command.join(" "));
+        audit::finish(store, entry, code.as_ref().ok().map(|code| *code as i32));
+        return Ok((code? as i32, String::new()));
+    }
+    let invocation = ssh::exec(config, server, options, command, exec)?;
+    if exec.batch.is_none() {
+        connect::check_passphrase(config, server, options)?;
+    }
+    let log = capture::path(config, options.log_file.as_deref(), &server.server_name)?;
+    let entry = connect::log_start(store, config, server, "exec", confirmed, options);
+    let teed = connect::run_keeping_tail(&invocation, log.as_deref());
+    audit::finish(
+        store,
+        entry,
+        teed.as_ref().ok().and_then(|teed| teed.status.code()),
+    );
+    let teed = teed?;
+    let code = teed.status.code();
+    connect::hint_too_many_failures(server, options, code, &teed.tail);
+    if exec.batch.is_some() && teed.found && code == Some(ssh::FAILED) {
+        return Err(JumpError::BatchAuthFailed {
+            name: server.server_name.clone(),
+        }
+        .into());
+    }
+    Ok((code.unwrap_or(exit::FAILURE), teed.tail))
+}
+
+#[cfg(feature = "native-ssh")]
+fn warn_native(options: &ConnOptions) {
+    if options.proxy.is_some() {
+        crate::warn!("--proxy is ignored by the built-in client");
+    }
+    if !options.via.is_empty() {
+        crate::warn!("--via is ignored by the built-in client");
+    }
+}
+
+/// Run in batch mode on every server as far as `limits` allow, with output
+/// captured and handed to `finished` whole as each server finishes, so that
+/// servers running at once do not mix their lines. The report entries come
+/// in the order the servers finished.
+#[allow(clippy::too_many_arguments)]
+pub fn parallel(
+    store: &Store,
+    config: &Config,
+    servers: &[Server],
+    options: &ConnOptions,
+    exec: ExecOptions,
+    limits: Limits,
+    report_lines: usize,
+    command: &[String],
+    confirmed: Vec<Option<gate::Ack>>,
+    mut finished: impl FnMut(Finished),
+) -> Result<Vec<report::Host>> {
+    let invocations = servers
+        .iter()
+        .map(|server| ssh::exec(config, server, options, command, exec))
+        .collect::<Vec<_>>();
+    let logs = servers
+        .iter()
+        .map(|server| capture::path(config, options.log_file.as_deref(), &server.server_name))
+        .collect::<error::Result<Vec<_>>>()?;
+    let bastions = servers
+        .iter()
+        .map(|server| {
+            options
+                .via
+                .first()
+                .cloned()
+                .or_else(|| schedule::bastion(server))
+        })
+        .collect::<Vec<_>>();
+    let mut entries = (0..servers.len()).map(|_| None).collect::<Vec<_>>();
+    let mut started = vec![Instant::now(); servers.len()];
+    let mut hosts = vec![None; servers.len()];
+    let mut done = 0;
+    schedule::run(
+        &bastions,
+        limits,
+        |i| {
+            let invocation = invocations[i].as_ref().map_err(|e| anyhow!("{:#}", e))?;
+            debug!("running {}", invocation);
+            connect::run_output(invocation).map_err(|message| anyhow!(message))
+        },
+        |event| {
+            match event {
+                Event::Started(i) => {
+                    started[i] = Instant::now();
+                    if invocations[i].is_ok() {
+                        entries[i] = connect::log_start(
+                            store,
+                            config,
+                            &servers[i],
+                            "exec",
+                            confirmed[i],
+                            options,
+                        );
+                    }
+                }
+                Event::Finished(i, output) => {
+                    let server = &servers[i];
+                    done += 1;
+                    let (output, result) = match output {
+                        Ok(output) => {
+                            let logged = match (logs[i].as_deref(), &invocations[i]) {
+                                (Some(log), Ok(invocation)) => {
+                                    capture::append(invocation, log, &output.stderr)
+                                }
+                                _ => Ok(()),
+                            };
+                            let result = logged
+                                .map_err(anyhow::Error::from)
+                                .and_then(|()| exec_result(server, exec, &output));
+                            (Some(output), result)
+                        }
+                        Err(e) => (None, Err(e)),
+                    };
+                    audit::finish(
+                        store,
+                        entries[i].take(),
+                        result.as_ref().ok().map(|(code, _)| *code),
+                    );
+                    finished(Finished {
+                        server,
+                        done,
+                        output: output.as_ref(),
+                        result: &result,
+                    });
+                    if let Some(output) = &output {
+                        let stderr = String::from_utf8_lossy(&output.stderr);
+                        connect::hint_too_many_failures(
+                            server,
+                            options,
+                            output.status.code(),
+                            &stderr,
+                        );
+                    }
+                    hosts[i] = Some(outcome(server, started[i], &result, report_lines));
+                }
+            }
+            true
+        },
+    );
+    Ok(hosts.into_iter().flatten().collect())
+}
+
+/// The exit code and the end of stderr of a batch-mode `exec` that ran to
+/// the end, or `BatchAuthFailed` when ssh was denied
+fn exec_result(server: &Server, exec: ExecOptions, output: &Output) -> Result<(i32, String)> {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let code = output.status.code();
+    if exec.batch.is_some() && code == Some(ssh::FAILED) && stderr.contains(ssh::AUTH_DENIED) {
+        return Err(JumpError::BatchAuthFailed {
+            name: server.server_name.clone(),
+        }
+        .into());
+    }
+    let tail = stderr.len().saturating_sub(capture::TAIL_SIZE);
+    let tail = (tail..=stderr.len())
+        .find(|at| stderr.is_char_boundary(*at))
+        .unwrap_or(stderr.len());
+    Ok((code.unwrap_or(exit::FAILURE), stderr[tail..].to_owned()))
+}
+
+/// The report entry of `on_server`'s `result`. A login that batch mode
+/// denied reached the server, anything else that kept ssh from running did
+/// not start.
+pub fn outcome(
+    server: &Server,
+    started: Instant,
+    result: &Result<(i32, String)>,
+    lines: usize,
+) -> report::Host {
+    match result {
+        Ok((code, stderr)) => report::Host::finished(
+            &server.server_name,
+            started.elapsed(),
+            Some(*code),
+            stderr,
+            lines,
+        ),
+        Err(e) => match e.downcast_ref::<JumpError>() {
+            Some(JumpError::BatchAuthFailed { .. }) => report::Host {
+                error: Some(format!("{:#}", e)),
+                ..report::Host::finished(
+                    &server.server_name,
+                    started.elapsed(),
+                    Some(ssh::FAILED),
+                    "",
+                    lines,
+                )
+            },
+            _ => report::Host::not_started(&server.server_name, format!("{:#}", e)),
+        },
+    }
+}
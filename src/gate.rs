@@ -0,0 +1,74 @@
+//! `confirm_tags`: servers that have to be named again before `conn`,
+//! `exec` or `cp` touch them, so that a slip of the finger, or a prefix
+//! that resolved further than meant, does not land on production.
+
+use crate::{
+    config::Config,
+    error::{JumpError, Result},
+    prompt,
+    server::Server,
+    style::Style,
+};
+
+/// How a gate was passed, as the connection log records it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ack {
+    /// The server's name was typed at the prompt
+    Typed,
+    /// `--yes-prod` was given
+    Flag,
+}
+
+impl Ack {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Ack::Typed => "typed",
+            Ack::Flag => "--yes-prod",
+        }
+    }
+}
+
+/// The tags of `server` that are in `confirm_tags`
+pub fn tags<'a>(config: &Config, server: &'a Server) -> Vec<&'a str> {
+    server
+        .tags
+        .iter()
+        .filter(|tag| config.confirm_tags.contains(tag))
+        .map(String::as_str)
+        .collect()
+}
+
+/// Stop at `server` when it has a tag in `confirm_tags`, until its name is
+/// typed or `yes` (`--yes-prod`) was given; without a terminal `--yes-prod`
+/// is the only way through. `None` when the server has no gate.
+pub fn pass(config: &Config, style: Style, server: &Server, yes: bool) -> Result<Option<Ack>> {
+    let tags = tags(config, server);
+    if tags.is_empty() {
+        return Ok(None);
+    }
+    if yes {
+        return Ok(Some(Ack::Flag));
+    }
+    prompt::allow(
+        &format!("for the name of {}", server.server_name),
+        "pass --yes-prod to go ahead",
+    )?;
+    let banner = format!(
+        "!!! {} is tagged {} !!!",
+        server.server_name,
+        tags.join(", ")
+    );
+    println!("{}", style.red(&banner));
+    println!(
+        "    {}@{}:{}",
+        server.username, server.server_address, server.port
+    );
+    let typed = prompt::answer("Type the server name to go ahead:", "--yes-prod")?;
+    match typed == server.server_name {
+        true => Ok(Some(Ack::Typed)),
+        false => Err(JumpError::NotConfirmed {
+            name: server.server_name.clone(),
+            typed,
+        }),
+    }
+}
@@ -0,0 +1,60 @@
+use std::process::{Command, ExitStatus};
+
+use anyhow::Result;
+
+use crate::{config::Config, error::JumpError, server::Server};
+
+/// Pre-connect hooks in the order they run: the global one, then the server's
+pub fn pre_connect_hooks<'a>(
+    config: &'a Config,
+    server: &'a Server,
+) -> Vec<(&'a str, &'static str)> {
+    let global = config.pre_connect.as_deref().map(|hook| (hook, "global"));
+    let own = server.pre_connect.as_deref().map(|hook| (hook, "server"));
+    global.into_iter().chain(own).collect()
+}
+
+/// Post-connect hooks in the order they run: the server's, then the global one
+pub fn post_connect_hooks<'a>(
+    config: &'a Config,
+    server: &'a Server,
+) -> Vec<(&'a str, &'static str)> {
+    let own = server.post_connect.as_deref().map(|hook| (hook, "server"));
+    let global = config.post_connect.as_deref().map(|hook| (hook, "global"));
+    own.into_iter().chain(global).collect()
+}
+
+/// Run a hook through the local shell with the target described in
+/// `JUMP_*` variables; post-connect hooks also get `JUMP_EXIT_CODE`
+pub fn run_hook(hook: &str, server: &Server, exit_code: Option<i32>) -> Result<ExitStatus> {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(hook);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(hook);
+        command
+    };
+    command.envs(target_env(server));
+    if let Some(code) = exit_code {
+        command.env("JUMP_EXIT_CODE", code.to_string());
+    }
+    command.status().map_err(|source| {
+        JumpError::Spawn {
+            program: hook.to_owned(),
+            source,
+        }
+        .into()
+    })
+}
+
+/// The `JUMP_*` variables describing the target to hooks and local commands
+pub fn target_env(server: &Server) -> Vec<(String, String)> {
+    vec![
+        ("JUMP_SERVER_NAME".to_owned(), server.server_name.clone()),
+        ("JUMP_USERNAME".to_owned(), server.username.clone()),
+        ("JUMP_ADDRESS".to_owned(), server.server_address.clone()),
+        ("JUMP_PORT".to_owned(), server.port.to_string()),
+    ]
+}
@@ -0,0 +1,207 @@
+//! Host key fingerprints remembered per server, trust on first use. The key
+//! is fetched with ssh-keyscan, so a changed key is caught before ssh sends
+//! anything, a password included.
+
+use std::{
+    fmt,
+    path::Path,
+    process::{Command, Stdio},
+    thread::{self, JoinHandle},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::{self, Config, Tool},
+    error::{JumpError, Result},
+    server::Server,
+    ssh,
+};
+
+/// Seconds ssh-keyscan waits for a server before connecting
+pub const TIMEOUT: u32 = 5;
+
+/// Key types in order of preference, as ssh-keyscan names them
+const PREFERENCE: [&str; 5] = [
+    "ssh-ed25519",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "ssh-rsa",
+];
+
+/// A host key's type and SHA256 fingerprint, stored as
+/// `ssh-ed25519 SHA256:...`
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostKey {
+    pub key_type: String,
+    pub fingerprint: String,
+}
+
+impl HostKey {
+    /// The key a `<type> <base64>` public key line holds
+    pub fn from_public(key_type: &str, blob: &str) -> Option<HostKey> {
+        Some(HostKey {
+            key_type: key_type.to_owned(),
+            fingerprint: fingerprint(&decode(blob)?),
+        })
+    }
+
+    pub fn parse(stored: &str) -> Option<HostKey> {
+        let (key_type, fingerprint) = stored.split_once(' ')?;
+        Some(HostKey {
+            key_type: key_type.to_owned(),
+            fingerprint: fingerprint.to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for HostKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.key_type, self.fingerprint)
+    }
+}
+
+/// What the server presents compared with what was recorded
+#[derive(Debug, PartialEq)]
+pub enum Check {
+    /// It still has the recorded key
+    Same,
+    /// The recorded type of key is now another key, or gone
+    Changed(HostKey),
+}
+
+/// The host keys of `server`, preferred type first
+pub fn scan(config: &Config, server: &Server, timeout: u32) -> Result<Vec<HostKey>> {
+    let (keyscan, _) = config::resolve(config, Tool::Keyscan)?;
+    let target = Target::of(server);
+    keyscan_target(&keyscan, &target, timeout)
+}
+
+/// `scan` in a thread of its own, for a result that is only wanted if it
+/// is there in time
+pub fn scan_in_background(
+    config: &Config,
+    server: &Server,
+    timeout: u32,
+) -> Result<JoinHandle<Result<Vec<HostKey>>>> {
+    let (keyscan, _) = config::resolve(config, Tool::Keyscan)?;
+    let target = Target::of(server);
+    Ok(thread::spawn(move || {
+        keyscan_target(&keyscan, &target, timeout)
+    }))
+}
+
+/// What ssh-keyscan needs of a server
+struct Target {
+    name: String,
+    address: String,
+    port: u32,
+}
+
+impl Target {
+    fn of(server: &Server) -> Target {
+        Target {
+            name: server.server_name.clone(),
+            address: server.server_address.clone(),
+            port: server.port,
+        }
+    }
+}
+
+fn keyscan_target(keyscan: &Path, target: &Target, timeout: u32) -> Result<Vec<HostKey>> {
+    let output = Command::new(keyscan)
+        .args(["-T", &timeout.to_string()])
+        .args(["-p", &target.port.to_string()])
+        .args(["-t", "ed25519,ecdsa,rsa"])
+        .args([ssh::END_OF_OPTIONS, &target.address])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|source| JumpError::Spawn {
+            program: keyscan.display().to_string(),
+            source,
+        })?;
+    let keys = parse_keyscan(&String::from_utf8_lossy(&output.stdout));
+    if keys.is_empty() {
+        return Err(JumpError::HostKeyUnavailable {
+            name: target.name.clone(),
+        });
+    }
+    Ok(keys)
+}
+
+/// The keys in ssh-keyscan's `host type base64` lines, preferred type first
+pub fn parse_keyscan(output: &str) -> Vec<HostKey> {
+    let mut keys = output
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            HostKey::from_public(fields.next()?, fields.next()?)
+        })
+        .collect::<Vec<_>>();
+    keys.sort_by_key(|key| rank(&key.key_type));
+    keys.dedup();
+    keys
+}
+
+/// Compare `scanned` with the `recorded` key: the same type must still be
+/// the same key. When the server no longer has a key of that type, the one
+/// it prefers now is the change to report.
+pub fn check(recorded: &HostKey, scanned: &[HostKey]) -> Check {
+    match scanned
+        .iter()
+        .find(|key| key.key_type == recorded.key_type)
+        .or(scanned.first())
+    {
+        Some(key) if key == recorded => Check::Same,
+        Some(key) => Check::Changed(key.clone()),
+        None => Check::Same,
+    }
+}
+
+/// `SHA256:` and the unpadded base64 of the key's SHA-256, as ssh prints it
+pub fn fingerprint(blob: &[u8]) -> String {
+    format!("SHA256:{}", encode(&Sha256::digest(blob)))
+}
+
+fn rank(key_type: &str) -> usize {
+    PREFERENCE
+        .iter()
+        .position(|preferred| *preferred == key_type)
+        .unwrap_or(PREFERENCE.len())
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | u32::from(*byte) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            encoded.push(char::from(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize]));
+        }
+    }
+    encoded
+}
+
+/// The bytes of standard base64 `text`, padded or not
+pub fn decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut n = 0u32;
+    let mut bits = 0;
+    for c in text.trim_end_matches('=').bytes() {
+        let value = BASE64.iter().position(|digit| *digit == c)? as u32;
+        n = n << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((n >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
@@ -0,0 +1,130 @@
+//! `jump hosts`: the servers as `/etc/hosts` lines, and a block of them kept
+//! up to date in a hosts file between `# BEGIN <marker>` and `# END <marker>`.
+//! Nothing outside the block is ever changed.
+
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    net::{IpAddr, ToSocketAddrs},
+    path::Path,
+};
+
+use crate::{
+    error::{JumpError, Result},
+    server::Server,
+    warn,
+};
+
+/// `<ip> <server_name>` lines for the servers whose address is an IP, and
+/// with `resolve` for the others too, by their first resolved address.
+/// Servers left out are warned about.
+pub fn lines(servers: &[Server], resolve: bool) -> String {
+    let mut lines = String::new();
+    for server in servers {
+        let ip = match server.server_address.parse::<IpAddr>() {
+            Ok(ip) => ip,
+            Err(_) if !resolve => continue,
+            Err(_) => match lookup(&server.server_address) {
+                Some(ip) => ip,
+                None => {
+                    warn!(
+                        "{} left out, {} does not resolve",
+                        server.server_name, server.server_address
+                    );
+                    continue;
+                }
+            },
+        };
+        lines.push_str(&format!("{} {}\n", ip, server.server_name));
+    }
+    lines
+}
+
+fn lookup(host: &str) -> Option<IpAddr> {
+    resolve(host).into_iter().next()
+}
+
+/// The addresses `host` resolves to; none when it does not
+pub fn resolve(host: &str) -> Vec<IpAddr> {
+    match (host, 0).to_socket_addrs() {
+        Ok(addresses) => addresses.map(|address| address.ip()).collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// `content` with the block between the markers replaced by `lines`, or
+/// with the block added at the end when there is none yet
+pub fn replace_block(content: &str, marker: &str, lines: &str) -> Result<String> {
+    let begin = format!("# BEGIN {}", marker);
+    let end = format!("# END {}", marker);
+    let block = format!("{}\n{}{}\n", begin, lines, end);
+    let all = content.lines().collect::<Vec<_>>();
+    let start = all.iter().position(|line| line.trim_end() == begin);
+    let stop = all.iter().position(|line| line.trim_end() == end);
+    let mut replaced = String::new();
+    match (start, stop) {
+        (Some(start), Some(stop)) if start < stop => {
+            for line in &all[..start] {
+                replaced.push_str(line);
+                replaced.push('\n');
+            }
+            replaced.push_str(&block);
+            for line in &all[stop + 1..] {
+                replaced.push_str(line);
+                replaced.push('\n');
+            }
+        }
+        (None, None) => {
+            replaced.push_str(content);
+            if !content.is_empty() && !content.ends_with('\n') {
+                replaced.push('\n');
+            }
+            replaced.push_str(&block);
+        }
+        _ => {
+            return Err(JumpError::invalid(
+                "hosts file",
+                format!(
+                    "`{}` and `{}` do not enclose a block, fix them by hand",
+                    begin, end
+                ),
+            ))
+        }
+    }
+    Ok(replaced)
+}
+
+/// Write `lines` into the block of the hosts file at `path`, returning
+/// whether it changed. The file is replaced in one step, through a copy next
+/// to it with the same permissions.
+pub fn sync(path: &Path, marker: &str, lines: &str) -> Result<bool> {
+    let content = fs::read_to_string(path).map_err(|e| denied(path, e))?;
+    let replaced = replace_block(&content, marker, lines)?;
+    if replaced == content {
+        return Ok(false);
+    }
+    let name = path
+        .file_name()
+        .ok_or_else(|| JumpError::invalid("hosts file", "not a file"))?;
+    let temp = path.with_file_name(format!(".{}.jump", name.to_string_lossy()));
+    let written = fs::write(&temp, &replaced)
+        .and_then(|_| fs::set_permissions(&temp, fs::metadata(path)?.permissions()))
+        .and_then(|_| fs::rename(&temp, path));
+    if let Err(e) = written {
+        fs::remove_file(&temp).ok();
+        return Err(denied(path, e));
+    }
+    Ok(true)
+}
+
+/// Point at sudo when the hosts file belongs to root
+fn denied(path: &Path, error: io::Error) -> JumpError {
+    match error.kind() {
+        ErrorKind::PermissionDenied => JumpError::invalid(
+            &path.display().to_string(),
+            "permission denied; run it as root, keeping your home for the store, e.g. \
+             `sudo --preserve-env=HOME jump hosts sync`",
+        ),
+        _ => JumpError::Io(error),
+    }
+}
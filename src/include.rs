@@ -0,0 +1,88 @@
+//! Servers kept outside the store, in the files `include` names: plain
+//! exports as `jump export` writes them, or TOML documents as `jump edit`
+//! shows them. They are read again at every run, so a change to a file,
+//! say on a shared drive, shows without any sync, and jump never writes to
+//! them.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    bundle::Bundle,
+    config::{self, Config},
+    edit::Document,
+    error::{JumpError, Result},
+    server::Server,
+    store::Store,
+    warn,
+};
+
+/// The servers of every `include` file, in order, each with its `source`;
+/// relative paths are taken from `dir`, that of the config file. A name the
+/// store or an earlier file already has is left out with a warning, and so
+/// is a file that cannot be read, as when its drive is not mounted; one
+/// that does not parse is an error.
+pub fn load(config: &Config, dir: &Path, store: &Store) -> Result<Vec<Server>> {
+    if config.include.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut taken = store
+        .names()?
+        .into_iter()
+        .map(|name| (name, None))
+        .collect::<HashMap<_, Option<PathBuf>>>();
+    let mut servers = vec![];
+    for path in &config.include {
+        let path = dir.join(config::expand_path(path)?);
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("skipping the include {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        for mut server in parse(&path, &data)? {
+            if let Some(first) = taken.get(&server.server_name) {
+                let first = match first {
+                    Some(file) => file.display().to_string(),
+                    None => "the store".to_owned(),
+                };
+                warn!(
+                    "{} in {} is left out, as {} has a server of that name",
+                    server.server_name,
+                    path.display(),
+                    first
+                );
+                continue;
+            }
+            taken.insert(server.server_name.clone(), Some(path.clone()));
+            server.id = None;
+            server.order_index = None;
+            server.source = Some(path.clone());
+            servers.push(server);
+        }
+    }
+    Ok(servers)
+}
+
+/// The servers of one file: TOML for a `.toml` file, an export otherwise
+fn parse(path: &Path, data: &[u8]) -> Result<Vec<Server>> {
+    let invalid =
+        |reason: String| JumpError::invalid("include", format!("{}: {}", path.display(), reason));
+    match path
+        .extension()
+        .is_some_and(|extension| extension == "toml")
+    {
+        true => {
+            let text = String::from_utf8(data.to_vec()).map_err(|e| invalid(e.to_string()))?;
+            let document: Document = toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+            Ok(document.servers)
+        }
+        false => Ok(Bundle::read(data, None)
+            .map_err(|e| invalid(e.to_string()))?
+            .servers),
+    }
+}
@@ -0,0 +1,59 @@
+//! Which local key each server last accepted, for rotating keys. `conn`
+//! runs ssh with `-v` and reads the `debug1:` lines, which are kept off the
+//! terminal, for the key the server accepted; nothing is recorded when they
+//! say otherwise or cannot be made sense of.
+
+/// Prefix of the lines `ssh -v` adds
+pub const DEBUG_PREFIX: &str = "debug1: ";
+
+/// A key a server accepted, as ssh described it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accepted {
+    /// As `SHA256:...`
+    pub fingerprint: String,
+    /// The key file, or the comment of a key only the agent has
+    pub key: String,
+}
+
+/// One server's last accepted key, as stored
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyUse {
+    pub server_name: String,
+    pub accepted: Accepted,
+    /// Seconds since the epoch
+    pub used_at: i64,
+}
+
+/// The key of `Server accepts key:`, once ssh also said that the login went
+/// through with it. `lines` are the `debug1:` lines with or without their
+/// prefix.
+pub fn accepted<S: AsRef<str>>(lines: &[S]) -> Option<Accepted> {
+    let mut accepted = None;
+    let mut authenticated = false;
+    for line in lines {
+        let line = line.as_ref();
+        let line = line.strip_prefix(DEBUG_PREFIX).unwrap_or(line);
+        if let Some(key) = line.strip_prefix("Server accepts key: ") {
+            accepted = parse_key(key);
+        }
+        // `Authenticated to host ([addr]:port) using "publickey".` since
+        // OpenSSH 8.9, `Authentication succeeded (publickey).` before
+        authenticated |= line.contains("using \"publickey\"")
+            || line.starts_with("Authentication succeeded (publickey)");
+    }
+    accepted.filter(|_| authenticated)
+}
+
+/// `<file or comment> <type> <fingerprint>[ explicit][ agent]...`
+fn parse_key(text: &str) -> Option<Accepted> {
+    let words = text.split_whitespace().collect::<Vec<_>>();
+    let at = words
+        .iter()
+        .position(|word| word.starts_with("SHA256:") || word.starts_with("MD5:"))?;
+    // The key type comes right before the fingerprint
+    let key = words.get(..at.checked_sub(1)?)?.join(" ");
+    Some(Accepted {
+        fingerprint: words[at].to_owned(),
+        key,
+    })
+}
@@ -0,0 +1,168 @@
+//! Port knocking: touching a sequence of ports in order so that a firewall
+//! opens the ssh port. TCP knocks are connection attempts given up almost at
+//! once, UDP knocks a single empty datagram.
+
+use std::{
+    fmt::{self, Display},
+    net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    str::FromStr,
+    thread,
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{JumpError, Result},
+    server::Server,
+    wol,
+};
+
+/// Wait between knocks when the server sets none
+pub const DEFAULT_DELAY: Duration = Duration::from_millis(200);
+
+/// How long `conn` waits for the ssh port to open after knocking
+pub const OPEN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a TCP knock waits for an answer it does not need
+const TCP_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// One port of a knock sequence, written `7000` or `7000/udp`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Knock {
+    pub port: u16,
+    pub protocol: Protocol,
+}
+
+impl Display for Knock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let protocol = match self.protocol {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+        };
+        write!(f, "{}/{}", self.port, protocol)
+    }
+}
+
+impl FromStr for Knock {
+    type Err = String;
+
+    fn from_str(knock: &str) -> Result<Knock, String> {
+        let (port, protocol) = knock.split_once('/').unwrap_or((knock, "tcp"));
+        Ok(Knock {
+            port: port
+                .parse()
+                .map_err(|_| format!("`{}` is not a port number", port))?,
+            protocol: Protocol::from_str(protocol, true)
+                .map_err(|_| format!("`{}` is not tcp or udp", protocol))?,
+        })
+    }
+}
+
+impl TryFrom<String> for Knock {
+    type Error = String;
+
+    fn try_from(knock: String) -> Result<Knock, String> {
+        knock.parse()
+    }
+}
+
+impl From<Knock> for String {
+    fn from(knock: Knock) -> String {
+        knock.to_string()
+    }
+}
+
+pub fn parse_arg(knock: &str) -> Result<Knock, String> {
+    knock.parse()
+}
+
+/// A sequence as stored: its knocks separated by spaces
+pub fn format_sequence(knocks: &[Knock]) -> Option<String> {
+    match knocks.is_empty() {
+        true => None,
+        false => Some(
+            knocks
+                .iter()
+                .map(Knock::to_string)
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+    }
+}
+
+/// A stored sequence; knocks that no longer parse are left out
+pub fn parse_sequence(knocks: Option<String>) -> Vec<Knock> {
+    knocks
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter_map(|knock| knock.parse().ok())
+        .collect()
+}
+
+/// Knock on each port of `address` in turn, `delay` apart
+pub fn knock(address: &str, knocks: &[Knock], delay: Duration) -> Result<()> {
+    for (i, knock) in knocks.iter().enumerate() {
+        if i > 0 {
+            thread::sleep(delay);
+        }
+        let target = resolve(address, knock.port)?;
+        match knock.protocol {
+            // Refused or timed out alike, the attempt is the knock
+            Protocol::Tcp => {
+                TcpStream::connect_timeout(&target, TCP_TIMEOUT).ok();
+            }
+            Protocol::Udp => {
+                let local = match target {
+                    SocketAddr::V4(_) => "0.0.0.0:0",
+                    SocketAddr::V6(_) => "[::]:0",
+                };
+                UdpSocket::bind(local)?.send_to(&[], target)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn resolve(address: &str, port: u16) -> Result<SocketAddr> {
+    (address, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| JumpError::invalid("address", format!("{} does not resolve", address)))
+}
+
+/// Knock the server's sequence, then wait up to `timeout` for its ssh port
+/// to open
+pub fn knock_and_wait(server: &Server, timeout: Duration) -> Result<()> {
+    let delay = server
+        .knock_delay
+        .map_or(DEFAULT_DELAY, Duration::from_millis);
+    knock(&server.server_address, &server.knock, delay)?;
+    let open = wol::wait_for_port(
+        &server.server_address,
+        server.port,
+        timeout,
+        Duration::from_millis(250),
+        || {},
+    );
+    match open {
+        true => Ok(()),
+        false => Err(JumpError::CommandFailed {
+            command: "knock".to_owned(),
+            message: format!(
+                "{}:{} did not open within {}s",
+                server.server_address,
+                server.port,
+                timeout.as_secs()
+            ),
+        }),
+    }
+}
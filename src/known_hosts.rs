@@ -0,0 +1,167 @@
+//! A known_hosts file of jump's own, per profile, used instead of
+//! `~/.ssh/known_hosts` with `isolated_known_hosts`. Hosts are written
+//! unhashed so that entries can be mapped back to servers.
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{self, Config},
+    error::Result,
+    hostkey::HostKey,
+    server::Server,
+};
+
+/// One key line of a known_hosts file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// The comma-separated host patterns, as `host` or `[host]:port`
+    pub hosts: Vec<String>,
+    /// `None` for keys whose line does not parse
+    pub key: Option<HostKey>,
+}
+
+impl Entry {
+    /// Whether ssh looked the key up under a hashed name
+    pub fn is_hashed(&self) -> bool {
+        self.hosts.iter().any(|host| host.starts_with('|'))
+    }
+
+    /// Whether ssh would use this key for `server` at any of its addresses
+    pub fn matches(&self, server: &Server) -> bool {
+        names(server).iter().any(|name| self.hosts.contains(name))
+    }
+}
+
+/// `~/.jump/known_hosts` for the default profile, `~/.jump/<profile>.known_hosts`
+/// for the others, next to their stores
+pub fn path(profile: &str) -> Result<PathBuf> {
+    let mut path = config::jump_dir()?;
+    match profile {
+        "default" => path.push("known_hosts"),
+        _ => path.push(format!("{}.known_hosts", profile)),
+    }
+    Ok(path)
+}
+
+/// The `-o` arguments pointing ssh at the profile's file, creating it, when
+/// `isolated_known_hosts` is on
+pub fn args(config: &Config) -> Result<Vec<String>> {
+    Ok(options(config)?
+        .into_iter()
+        .flat_map(|option| ["-o".to_owned(), option])
+        .collect())
+}
+
+/// The same as `Key=Value` options alone, for programs passing them on
+pub fn options(config: &Config) -> Result<Vec<String>> {
+    if !config.isolated_known_hosts {
+        return Ok(vec![]);
+    }
+    let path = path(&config.default_profile)?;
+    create(&path)?;
+    let path = path.to_string_lossy();
+    // ssh splits the value into several files at whitespace unless quoted
+    let value = match path.contains(char::is_whitespace) {
+        true => format!("\"{}\"", path),
+        false => path.into_owned(),
+    };
+    Ok(vec![
+        format!("UserKnownHostsFile={}", value),
+        "HashKnownHosts=no".to_owned(),
+    ])
+}
+
+/// The key lines of the file at `path`; none when there is no file
+pub fn read(path: &Path) -> Result<Vec<Entry>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(text.lines().filter_map(parse_line).collect())
+}
+
+/// Drop the keys of `server` from the file at `path`, returning how many
+/// there were
+pub fn remove(path: &Path, server: &Server) -> Result<usize> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let mut kept = String::new();
+    let mut removed = 0;
+    for line in text.lines() {
+        match parse_line(line) {
+            Some(entry) if entry.matches(server) => removed += 1,
+            _ => {
+                kept.push_str(line);
+                kept.push('\n');
+            }
+        }
+    }
+    if removed > 0 {
+        fs::write(path, kept)?;
+    }
+    Ok(removed)
+}
+
+/// The names ssh files the keys of `server` under: the address, in brackets
+/// with the port when it is not 22
+pub fn names(server: &Server) -> Vec<String> {
+    std::iter::once(&server.server_address)
+        .chain(server.addresses.iter().map(|address| &address.address))
+        .map(|address| match server.port {
+            22 => address.clone(),
+            port => format!("[{}]:{}", address, port),
+        })
+        .collect()
+}
+
+/// A `hosts type base64 [comment]` line; `None` for comments, blank lines
+/// and `@cert-authority` or `@revoked` lines
+fn parse_line(line: &str) -> Option<Entry> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') || line.starts_with('@') {
+        return None;
+    }
+    let mut fields = line.split_whitespace();
+    let hosts = fields.next()?.split(',').map(str::to_owned).collect();
+    let key = match (fields.next(), fields.next()) {
+        (Some(key_type), Some(blob)) => HostKey::from_public(key_type, blob),
+        _ => None,
+    };
+    Some(Entry { hosts, key })
+}
+
+/// Create the file readable by nobody else
+#[cfg(unix)]
+fn create(path: &Path) -> Result<()> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .mode(0o600)
+        .open(path)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn create(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)?;
+    Ok(())
+}
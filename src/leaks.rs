@@ -0,0 +1,224 @@
+//! `jump audit-secrets`: the places jump's secrets end up outside the store.
+//! Shell history holding `jump add ... password <value>`, files under
+//! `~/.jump` others can read, and exports with passwords in plain text
+//! among the paths given. Findings use the fixes of `jump doctor`, with
+//! history scrubbing added.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    bundle::Bundle,
+    config,
+    doctor::{self, Fix},
+    error::Result,
+    server::ConnectMethods,
+    ssh::shell_quote,
+    verify::Severity,
+};
+
+/// Files larger than this are not taken for exports
+const MAX_EXPORT_SIZE: u64 = 16 << 20;
+
+/// A place a secret may have leaked to, and what to do about it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub problem: String,
+    pub fix: Fix,
+}
+
+/// The leaks of the shell histories, of `~/.jump` and of the exports under
+/// `paths`, in that order
+pub fn check(paths: &[PathBuf]) -> Result<Vec<Finding>> {
+    let mut findings = vec![];
+    for file in history_files()? {
+        let Ok(history) = fs::read(&file) else {
+            continue;
+        };
+        let lines = String::from_utf8_lossy(&history)
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| leaks_secret(line))
+            .map(|(i, _)| (i + 1).to_string())
+            .collect::<Vec<_>>();
+        if !lines.is_empty() {
+            findings.push(Finding {
+                severity: Severity::Error,
+                problem: format!(
+                    "{} has a secret given to jump on line{} {}",
+                    file.display(),
+                    if lines.len() == 1 { "" } else { "s" },
+                    lines.join(", ")
+                ),
+                fix: Fix::ScrubHistory(file),
+            });
+        }
+    }
+    let jump_dir = config::jump_dir()?;
+    for file in files(&jump_dir, true) {
+        if doctor::is_open(&file) {
+            findings.push(Finding {
+                severity: severity_under(&jump_dir, &file),
+                problem: format!("{} can be read by others", file.display()),
+                fix: Fix::Chmod(file),
+            });
+        }
+    }
+    for path in paths {
+        for file in files(path, false) {
+            let passwords = export_passwords(&file);
+            if passwords > 0 {
+                let shred = format!("shred -u {}", shell_quote(&file.display().to_string()));
+                findings.push(Finding {
+                    severity: Severity::Error,
+                    problem: format!(
+                        "{} is an export with {} password{} in plain text; \
+                         `jump export --encrypted` writes one without",
+                        file.display(),
+                        passwords,
+                        if passwords == 1 { "" } else { "s" }
+                    ),
+                    fix: Fix::Manual(shred),
+                });
+            }
+        }
+    }
+    Ok(findings)
+}
+
+/// The history files of the usual shells, and `$HISTFILE`, that exist
+pub fn history_files() -> Result<Vec<PathBuf>> {
+    let mut files = env::var_os("HISTFILE")
+        .map(PathBuf::from)
+        .into_iter()
+        .collect::<Vec<_>>();
+    for file in [
+        "~/.bash_history",
+        "~/.zsh_history",
+        "~/.zhistory",
+        "~/.history",
+        "~/.sh_history",
+        "~/.local/share/fish/fish_history",
+    ] {
+        let file = config::expand_path(Path::new(file))?;
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    files.retain(|file| file.is_file());
+    Ok(files)
+}
+
+/// Whether a history line runs jump with a password or a bundle passphrase
+/// on the command line. zsh's `: <time>:0;` and fish's `- cmd: ` prefixes
+/// are taken apart like any other word.
+pub fn leaks_secret(line: &str) -> bool {
+    let words = line
+        .split(|c: char| c.is_whitespace() || c == ';')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    let Some(at) = words
+        .iter()
+        .position(|word| *word == "jump" || word.ends_with("/jump"))
+    else {
+        return false;
+    };
+    let passphrase = words[..at].iter().any(|word| {
+        word.strip_prefix("JUMP_PASSPHRASE=")
+            .is_some_and(|value| !value.is_empty())
+    });
+    let password = words[at..]
+        .windows(2)
+        .any(|pair| pair[0] == "password" && !pair[1].starts_with('-'));
+    passphrase || password
+}
+
+/// Remove the lines `leaks_secret` finds from a history file, keeping its
+/// permissions; the number removed
+pub fn scrub(file: &Path) -> Result<usize> {
+    let history = fs::read(file)?;
+    let history = String::from_utf8_lossy(&history);
+    let mut removed = 0;
+    let mut kept = String::with_capacity(history.len());
+    for line in history.split_inclusive('\n') {
+        match leaks_secret(line) {
+            true => removed += 1,
+            false => kept.push_str(line),
+        }
+    }
+    fs::write(file, kept)?;
+    Ok(removed)
+}
+
+/// Stores, their backups, recordings, the trash and synced copies may hold
+/// passwords or what was typed in a session; the cache, logs and the rest
+/// only names and addresses
+fn severity_under(jump_dir: &Path, file: &Path) -> Severity {
+    let relative = file.strip_prefix(jump_dir).unwrap_or(file);
+    let holds_secrets = relative.components().next().is_some_and(|first| {
+        ["recordings", "trash", "sync"].contains(&&*first.as_os_str().to_string_lossy())
+    }) || relative
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().contains(".db"));
+    match holds_secrets {
+        true => Severity::Error,
+        false => Severity::Warning,
+    }
+}
+
+/// The regular files at or under `path`, without following links;
+/// directories starting with `.` are skipped below `path` unless `hidden`
+fn files(path: &Path, hidden: bool) -> Vec<PathBuf> {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return vec![];
+    };
+    if metadata.is_file() {
+        return vec![path.to_owned()];
+    }
+    if !metadata.is_dir() {
+        return vec![];
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return vec![];
+    };
+    let mut entries = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect::<Vec<_>>();
+    entries.sort();
+    entries
+        .into_iter()
+        .filter(|entry| {
+            hidden
+                || entry.is_file()
+                || !entry
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        })
+        .flat_map(|entry| files(&entry, hidden))
+        .collect()
+}
+
+/// How many passwords a plain export holds; 0 for any other file
+fn export_passwords(file: &Path) -> usize {
+    let small = fs::metadata(file).is_ok_and(|metadata| metadata.len() <= MAX_EXPORT_SIZE);
+    let Some(data) = small.then(|| fs::read(file).ok()).flatten() else {
+        return 0;
+    };
+    if !data.trim_ascii_start().starts_with(b"{") {
+        return 0;
+    }
+    match serde_json::from_slice::<Bundle>(&data) {
+        Ok(bundle) if bundle.secrets => bundle
+            .servers
+            .iter()
+            .filter(|server| match &server.method {
+                ConnectMethods::Password(password) => !password.password.is_empty(),
+                _ => false,
+            })
+            .count(),
+        _ => 0,
+    }
+}
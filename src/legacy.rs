@@ -0,0 +1,41 @@
+//! Old crypto for old devices. Switches and appliances that only offer
+//! `ssh-rsa` host keys, SHA-1 key exchange or CBC ciphers are refused by
+//! current OpenSSH; these stored options re-enable them for one server only.
+
+use crate::server::Server;
+
+/// The ssh options that choose algorithms, and so can re-enable weak ones
+pub const OPTIONS: [&str; 4] = [
+    "HostKeyAlgorithms",
+    "KexAlgorithms",
+    "Ciphers",
+    "PubkeyAcceptedAlgorithms",
+];
+
+/// What `--legacy` sets: each appended with `+` to OpenSSH's defaults, so
+/// modern algorithms are still preferred when the device has them
+pub const BUNDLE: [(&str, &str); 4] = [
+    ("HostKeyAlgorithms", "+ssh-rsa"),
+    (
+        "KexAlgorithms",
+        "+diffie-hellman-group14-sha1,diffie-hellman-group1-sha1",
+    ),
+    ("Ciphers", "+aes128-cbc,aes256-cbc,3des-cbc"),
+    ("PubkeyAcceptedAlgorithms", "+ssh-rsa"),
+];
+
+/// Whether `key` is one of `OPTIONS`, which ssh spells in any case
+pub fn is_algorithm_option(key: &str) -> bool {
+    OPTIONS
+        .iter()
+        .any(|option| option.eq_ignore_ascii_case(key))
+}
+
+/// The algorithm options stored for `server`
+pub fn settings(server: &Server) -> Vec<&(String, String)> {
+    server
+        .options
+        .iter()
+        .filter(|(key, _)| is_algorithm_option(key))
+        .collect()
+}
@@ -0,0 +1,69 @@
+//! The pieces behind the `jump` binary: the server store, the ssh command
+//! builders and the helpers they rely on.
+
+pub mod access;
+pub mod agent;
+pub mod askpass;
+pub mod audit;
+pub mod banner;
+pub mod bundle;
+pub mod cache;
+pub mod capture;
+pub mod complete;
+pub mod config;
+pub mod connect;
+pub mod copy;
+pub mod defaults;
+pub mod doctor;
+pub mod edit;
+pub mod error;
+pub mod exec;
+pub mod gate;
+pub mod hooks;
+pub mod hostkey;
+pub mod hosts;
+pub mod include;
+pub mod key_usage;
+pub mod knock;
+pub mod known_hosts;
+pub mod leaks;
+pub mod legacy;
+pub mod limit;
+pub mod log;
+pub mod markdown;
+pub mod menu;
+pub mod mount;
+pub mod multiplex;
+pub mod naming;
+#[cfg(feature = "native-ssh")]
+pub mod native;
+pub mod output;
+pub mod pager;
+pub mod profiles;
+pub mod prompt;
+pub mod proxy;
+pub mod prune;
+pub mod qr;
+pub mod reconnect;
+pub mod record;
+pub mod rekey;
+pub mod report;
+pub mod resolve;
+pub mod schedule;
+pub mod search;
+pub mod select;
+pub mod serve;
+pub mod server;
+pub mod setup;
+pub mod signals;
+pub mod ssh;
+pub mod ssh_config;
+pub mod store;
+pub mod style;
+pub mod sync;
+pub mod tmux;
+pub mod trash;
+pub mod tui;
+pub mod verify;
+pub mod window;
+pub mod wol;
@@ -0,0 +1,105 @@
+//! `max_session`: a timer running alongside a `conn` session that warns on
+//! the terminal before the server's limit, and ends the session once it is
+//! reached, for shared servers nobody should hold on to.
+
+use std::{
+    fs::OpenOptions,
+    io::{self, IsTerminal, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{audit, config, signals};
+
+/// The terminal jump runs in, written to outside the session
+#[cfg(unix)]
+const TTY: &str = "/dev/tty";
+#[cfg(not(unix))]
+const TTY: &str = "CONOUT$";
+
+/// Runs until dropped, or until the limit ended the session
+pub struct Timer {
+    reached: Arc<AtomicBool>,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+/// Start timing a session to `server_name` that may last `limit`, warning
+/// on `terminal` `settings.warning` seconds before the end
+pub fn start(
+    server_name: &str,
+    limit: Duration,
+    settings: &config::MaxSession,
+    mut terminal: impl Write + Send + 'static,
+) -> Timer {
+    let (stop, stopped) = mpsc::channel::<()>();
+    let reached = Arc::new(AtomicBool::new(false));
+    let flag = reached.clone();
+    let warning = Duration::from_secs(settings.warning);
+    let grace = Duration::from_secs(settings.grace);
+    let message = format!(
+        "{} disconnects in {}, at its max_session of {}",
+        server_name,
+        audit::format_age(settings.warning),
+        audit::format_age(limit.as_secs())
+    );
+    let thread = thread::spawn(move || {
+        let started = Instant::now();
+        // Whether `at` came before the timer was stopped
+        let wait_until = |at: Duration| {
+            let left = at.saturating_sub(started.elapsed());
+            matches!(stopped.recv_timeout(left), Err(RecvTimeoutError::Timeout))
+        };
+        if !warning.is_zero() && warning < limit {
+            if !wait_until(limit - warning) {
+                return;
+            }
+            // The session has the terminal raw, so lines start over
+            // themselves
+            write!(terminal, "\r\njump: {}\r\n", message).ok();
+            terminal.flush().ok();
+        }
+        if wait_until(limit) {
+            flag.store(true, Ordering::SeqCst);
+            signals::end_session(grace);
+        }
+    });
+    Timer {
+        reached,
+        stop: Some(stop),
+        thread: Some(thread),
+    }
+}
+
+/// Where the warning goes: jump's stderr when it is the terminal, else the
+/// terminal itself, so that it shows even with stderr sent elsewhere
+pub fn terminal() -> Box<dyn Write + Send> {
+    if io::stderr().is_terminal() {
+        return Box::new(io::stderr());
+    }
+    match OpenOptions::new().write(true).open(TTY) {
+        Ok(tty) => Box::new(tty),
+        Err(_) => Box::new(io::stderr()),
+    }
+}
+
+impl Timer {
+    /// Whether the limit was reached, and the session ended for it
+    pub fn reached(&self) -> bool {
+        self.reached.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
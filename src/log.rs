@@ -0,0 +1,54 @@
+//! Messages for the person at the terminal, as opposed to output meant for
+//! scripts. Everything here goes to stderr so stdout stays parseable.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Errors only
+    Quiet,
+    /// Errors, warnings and progress
+    Normal,
+    /// Also what jump resolved and ran, and how long it took
+    Verbose,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(verbosity: Verbosity) -> bool {
+    VERBOSITY.load(Ordering::Relaxed) >= verbosity as u8
+}
+
+/// Progress and other informational messages, silenced by `--quiet`
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Verbosity::Normal) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Something the user should fix, silenced by `--quiet`
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Verbosity::Normal) {
+            eprintln!("warning: {}", format_args!($($arg)*));
+        }
+    };
+}
+
+/// Details only shown with `--verbose`
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Verbosity::Verbose) {
+            eprintln!("jump: {}", format_args!($($arg)*));
+        }
+    };
+}
@@ -1,210 +1,3694 @@
 use std::{
-    convert::Infallible,
-    fmt::Display,
-    path::PathBuf,
-    process::{Command, Stdio},
-    str::FromStr,
+    fs,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
-use anyhow::{anyhow, Result};
-use clap::{Args, Parser, Subcommand};
-use homedir::my_home;
-use rusqlite::Connection;
-use serde::{Deserialize, Serialize};
+use anyhow::Result;
+use clap::{
+    builder::ArgPredicate, parser::ValueSource, CommandFactory, FromArgMatches, Parser, Subcommand,
+    ValueEnum,
+};
+use clap_complete::Shell;
+use ratatui::crossterm::terminal;
+
+use jump::{
+    askpass, audit,
+    bundle::{self, Bundle, Conflict, Outcome},
+    cache, complete,
+    config::{self, ColorChoice, Config, Tool},
+    connect, copy, debug, defaults, doctor, edit,
+    error::{self, exit, JumpError},
+    exec, gate, hostkey, hosts, include, info,
+    key_usage::Accepted,
+    knock, known_hosts, leaks, legacy,
+    log::{self, Verbosity},
+    markdown, menu, mount, multiplex, naming,
+    output::{self, OutputFormat, SortKey},
+    pager, profiles, prompt,
+    prune::{self, Pruning},
+    qr, record, rekey,
+    report::{self, ReportOptions},
+    resolve,
+    schedule::{Limits, ScheduleOptions},
+    search,
+    select::{self, Selector, SetField, Setting, Subnet},
+    serve,
+    server::{self, parse_env_pair, parse_tag, Address, ConnectMethods, SSHKey, Server},
+    setup,
+    ssh::{self, ConnOptions, SessionOptions},
+    ssh_config,
+    store::{Place, Store},
+    style::Style,
+    sync::{self, Change, Resolution},
+    tmux, tui,
+    verify::{self, Severity},
+    warn, window, wol,
+};
 
 /// A simple ssh connection management tool
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Jump {
+    /// Store profile to use instead of the configured default
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// When to color output; defaults to the `color` setting
+    #[arg(long, global = true, value_enum)]
+    color: Option<ColorChoice>,
+    /// Print `ls`, `search`, `show` and `check` results as JSON, and errors
+    /// as JSON on stderr
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Also print what jump resolved and ran, and how long it took
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    /// Open the store read-only, refusing every change to it
+    #[arg(long, global = true)]
+    read_only: bool,
+    /// Print `ls` and `log` straight to the terminal, however long
+    #[arg(long, global = true)]
+    no_pager: bool,
+    /// Never ask anything: a question fails instead, naming the flag or
+    /// variable that answers it. The default when stdin is not a terminal
+    #[arg(long, global = true, conflicts_with = "interactive")]
+    non_interactive: bool,
+    /// Ask questions even when stdin is not a terminal
+    #[arg(long, global = true)]
+    interactive: bool,
+    /// Without a subcommand, the interactive interface opens
     #[command(subcommand)]
-    opt: Opt,
+    opt: Option<Opt>,
 }
 
 #[derive(Debug, Subcommand)]
 enum Opt {
-    /// Initialize the jump database
-    Initialize,
+    /// Browse, search and connect to servers in a full-screen interface
+    Tui {
+        /// Probe every server's ssh port in the background, marking each
+        /// row green when it is up, red when not and grey until known
+        #[arg(long)]
+        probe: bool,
+    },
+    /// Create the store, or report on the one there is: where it lives, its
+    /// schema version, row counts and problems. Safe to run again.
+    #[command(alias = "initialize")]
+    Init {
+        /// Move the store aside to `<store>.bak-<time>` and create it anew
+        /// with the servers it still holds, or those of `--from`; the log
+        /// and the other records stay in the backup
+        #[arg(long)]
+        force_recreate: bool,
+        /// Rebuild from this export instead, for a store that cannot be read
+        #[arg(long, requires = "force_recreate")]
+        from: Option<PathBuf>,
+        /// Skip the confirmation prompt
+        #[arg(short, long, requires = "force_recreate")]
+        yes: bool,
+    },
     /// Add a server to current store
-    Add(Server),
-    /// Remove a server in current store
+    Add {
+        #[command(flatten)]
+        server: Box<Server>,
+        /// Connect right after adding and report a failure; with `strict`,
+        /// a server that cannot be reached is not added
+        #[arg(
+            long,
+            value_enum,
+            value_name = "MODE",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "warn"
+        )]
+        verify: Option<Verify>,
+        /// Seconds `--verify` waits for the server to answer
+        #[arg(long, value_name = "SECS", default_value = "5", requires = "verify")]
+        verify_timeout: u32,
+        /// Allow the old algorithms of switches and other old devices; see
+        /// `jump option set --legacy`
+        #[arg(long)]
+        legacy: bool,
+        /// Only list a `Host` of ~/.ssh/config, the name unless given, and
+        /// connect to it with ssh's settings instead of a user, address,
+        /// port and method
+        #[arg(
+            long,
+            value_name = "ALIAS",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "",
+            conflicts_with_all = ["uri", "auto_name"]
+        )]
+        ssh_config: Option<String>,
+        #[arg(skip)]
+        given: Given,
+        /// How to connect; left out with `--uri` or `--auto-name`, the
+        /// default key
+        #[command(subcommand)]
+        method: Option<ConnectMethods>,
+    },
+    /// Remove servers in current store
+    Rm {
+        /// A server name, or a selector such as `tag:old`
+        #[arg(value_name = "SELECTOR", required = true, value_parser = select::parse_arg)]
+        server_names: Vec<Selector>,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// List all servers in current store, or those a selector matches
+    Ls {
+        /// Only servers matching any of these, e.g. `tag:prod user:deploy`
+        #[arg(value_name = "SELECTOR", value_parser = select::parse_arg)]
+        selectors: Vec<Selector>,
+        /// Show every server with which terms of the selectors it matched
+        #[arg(long, requires = "selectors", conflicts_with_all = ["plain", "long"])]
+        explain: bool,
+        /// Servers under a heading for each tag, and the untagged ones last
+        #[arg(long, conflicts_with_all = ["plain", "long", "explain"])]
+        tree: bool,
+        /// Tab-separated name, username, address, port and method, without colors
+        #[arg(long, conflicts_with = "long")]
+        plain: bool,
+        /// A table with the port, method, key, tags, platform, last
+        /// connection and the days the server was added and last changed as
+        /// well, cut to the terminal width
+        #[arg(short, long)]
+        long: bool,
+        /// Order by this instead of the order set by `jump move`
+        #[arg(long, value_enum)]
+        sort: Option<SortKey>,
+        /// Only servers logging in as this user; `*` and `?` work
+        #[arg(long)]
+        user: Option<String>,
+        /// Only servers whose IP address is in this network, e.g. `10.1.2.0/24`
+        #[arg(long, value_name = "CIDR", value_parser = select::parse_subnet)]
+        subnet: Option<Subnet>,
+        /// Resolve host names for `--subnet` instead of leaving them out
+        #[arg(long, requires = "subnet")]
+        resolve: bool,
+        /// Include passwords in `--output json`
+        #[arg(long)]
+        include_secrets: bool,
+    },
+    /// Move a server within the order `ls` lists servers in, e.g.
+    /// `jump move web1 --before db1` or `jump move web1 3`
+    Move {
+        server_name: String,
+        /// Position to move to, counting from 1
+        #[arg(required_unless_present_any = ["before", "after"], conflicts_with_all = ["before", "after"])]
+        position: Option<usize>,
+        /// Put it just before this server
+        #[arg(long, value_name = "SERVER", conflicts_with = "after")]
+        before: Option<String>,
+        /// Put it just after this server
+        #[arg(long, value_name = "SERVER")]
+        after: Option<String>,
+    },
+    /// Reset the order `ls` lists servers in
+    Reorder {
+        /// Order the servers by name
+        #[arg(long, required = true)]
+        alphabetical: bool,
+    },
+    /// Change a field on every server a selector matches, after a preview,
+    /// e.g. `jump set --filter user:admin username ops`
+    Set {
+        field: SetField,
+        value: String,
+        /// The servers to change, e.g. `tag:prod user:admin`
+        #[arg(long = "filter", value_name = "SELECTOR", required = true, value_parser = select::parse_arg)]
+        filters: Vec<Selector>,
+        /// Only show what would change
+        #[arg(long)]
+        dry_run: bool,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Find servers by words in their name, tags, address, username or note,
+    /// ignoring case; every word must appear
+    Search {
+        #[arg(required = true)]
+        words: Vec<String>,
+    },
+    /// Show everything stored for a server
+    Show {
+        server_name: String,
+        /// Include the password in `--output json`
+        #[arg(long)]
+        include_secrets: bool,
+    },
+    /// Knock on a server's ports in order, as `conn` does before connecting
+    Knock { server_name: String },
+    /// Print what `jump conn` would connect to and run: the user, address,
+    /// port, method, key, `ProxyJump` hops and command, for other tools
+    Resolve {
+        server_name: String,
+        /// Print it as JSON, as `--output json` does
+        #[arg(long)]
+        json: bool,
+    },
+    /// Answer JSON-RPC requests from an editor or another tool, one per
+    /// line, with `list`, `resolve`, `add`, `remove` and `check`
+    Serve {
+        /// Read requests from stdin and answer on stdout, until stdin ends
+        #[arg(long, required = true)]
+        stdio: bool,
+    },
+    /// Print a server as an `ssh://` URI for other ssh clients
+    Uri {
+        server_name: String,
+        /// Draw it as a QR code, to scan with a phone
+        #[arg(long)]
+        qr: bool,
+    },
+    /// Check whether servers accept connections on their ssh port
+    Check {
+        /// Servers or selectors to check; all servers when omitted
+        #[arg(value_name = "SELECTOR", value_parser = select::parse_arg)]
+        server_names: Vec<Selector>,
+        /// Seconds to wait for each server
+        #[arg(long, default_value = "3")]
+        timeout: u64,
+        /// Tab-separated name, address and `up` or `down`, without colors
+        #[arg(long)]
+        plain: bool,
+        /// Skip the confirmation asked for more than `confirm.servers` servers
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        report: ReportOptions,
+        #[command(flatten)]
+        schedule: ScheduleOptions,
+    },
+    /// Connecting to server
+    Conn {
+        #[arg(required_unless_present = "group")]
+        server_name: Option<String>,
+        /// Connect to every server with this tag at once, each in its own
+        /// tmux pane or window running `jump conn <server>`
+        #[arg(
+            long,
+            value_name = "TAG",
+            conflicts_with = "server_name",
+            requires = "tmux"
+        )]
+        group: Option<String>,
+        /// How `--group` lays out its servers in tmux
+        #[arg(long, value_enum, requires = "group")]
+        tmux: Option<tmux::Layout>,
+        /// Type into every pane of `--tmux panes` at once
+        #[arg(long, requires = "group")]
+        sync: bool,
+        #[command(flatten)]
+        options: ConnOptions,
+        #[command(flatten)]
+        session: SessionOptions,
+        /// Extra arguments passed to ssh, e.g. `jump conn web -- -v`
+        #[arg(last = true)]
+        ssh_args: Vec<String>,
+    },
+    /// Attach to a device's serial console through the console server it
+    /// is stored with, e.g. `jump console rack3-sw1`
+    Console {
+        server_name: String,
+        #[command(flatten)]
+        options: ConnOptions,
+    },
+    /// Run a command on a server, e.g. `jump exec web -- uptime`, or on each
+    /// server a selector matches in turn
+    Exec {
+        #[arg(value_name = "SELECTOR", required_unless_present = "on", value_parser = select::parse_arg)]
+        server_name: Option<Selector>,
+        /// The servers to run on, as a selector like `tag:prod user:deploy`
+        #[arg(long, value_name = "SELECTOR", conflicts_with = "server_name", value_parser = select::parse_arg)]
+        on: Option<Selector>,
+        #[command(flatten)]
+        options: ConnOptions,
+        /// Allocate a tty (`ssh -t`), the default for network appliances
+        #[arg(short, long)]
+        tty: bool,
+        /// Never let ssh ask for anything (`BatchMode=yes`), failing instead;
+        /// the default when jump runs non-interactively, as under cron
+        #[arg(long, conflicts_with = "no_batch")]
+        batch: bool,
+        /// Let ssh ask for passwords and passphrases even when jump runs
+        /// non-interactively
+        #[arg(long)]
+        no_batch: bool,
+        /// Seconds ssh waits for the connection in batch mode
+        #[arg(long, value_name = "SECS", default_value_t = 10)]
+        connect_timeout: u32,
+        /// Skip the confirmation asked for more than `confirm.servers` servers
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        report: ReportOptions,
+        #[command(flatten)]
+        schedule: ScheduleOptions,
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Copy files with scp, naming remote paths as `<server_name>:<path>`, or
+    /// upload to many servers at once with `--to`
+    Cp {
+        source: String,
+        destination: String,
+        /// Copy directories recursively
+        #[arg(short, long)]
+        recursive: bool,
+        /// Copy with rsync, which keeps what an interrupted transfer wrote so
+        /// that running it again continues from there; scp is used, with a
+        /// warning, when rsync is missing on either end
+        #[arg(long, conflicts_with = "to")]
+        resume: bool,
+        /// Upload the local SOURCE to DESTINATION, a plain remote path, on
+        /// every server this selects, e.g. `--to tag:web`
+        #[arg(long, value_name = "SELECTOR", value_parser = select::parse_arg)]
+        to: Option<Selector>,
+        /// Keep starting uploads after one has failed, instead of leaving
+        /// the rest out
+        #[arg(long, requires = "to")]
+        continue_on_error: bool,
+        /// Skip the confirmation asked for more than `confirm.servers` servers
+        #[arg(short, long, requires = "to")]
+        yes: bool,
+        #[command(flatten)]
+        report: ReportOptions,
+        #[command(flatten)]
+        schedule: ScheduleOptions,
+        #[command(flatten)]
+        options: ConnOptions,
+    },
+    /// Show the connection log of `conn` and `exec`, newest first
+    Log {
+        /// Only connections to this server
+        #[arg(long)]
+        server: Option<String>,
+        /// Only connections started within this age, e.g. `7d` or `12h`
+        #[arg(long, value_name = "AGE", value_parser = audit::parse_age)]
+        since: Option<i64>,
+        /// Show at most this many entries
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// List the servers connected to last, numbered for `--conn`
+    Recent {
+        /// How many servers to list
+        #[arg(default_value_t = 5)]
+        count: usize,
+        /// Connect to the server with this number in the list
+        #[arg(long, value_name = "N")]
+        conn: Option<usize>,
+        #[command(flatten)]
+        options: ConnOptions,
+    },
+    /// List or prune the session recordings made by `conn --record`
+    #[command(subcommand)]
+    Recordings(RecordingsOpt),
+    /// Mount a remote directory locally through sshfs, e.g.
+    /// `jump mount web /srv/app ~/mnt/web`
+    Mount {
+        #[arg(required_unless_present = "list", requires_all = ["remote_path", "local_path"])]
+        server_name: Option<String>,
+        remote_path: Option<String>,
+        /// Created when it does not exist
+        local_path: Option<PathBuf>,
+        /// List the active mounts, forgetting those that went away
+        #[arg(long, conflicts_with = "server_name")]
+        list: bool,
+        /// Print the sshfs command instead of running it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Unmount what `jump mount` mounted, by local path or by server
+    Umount {
+        #[arg(value_name = "NAME|LOCAL_PATH")]
+        target: String,
+    },
+    /// List or close the connections shared through `multiplex`
+    #[command(subcommand)]
+    Sessions(SessionsOpt),
+    /// Record or show the host key `conn` checks before connecting
+    #[command(subcommand)]
+    Fingerprint(FingerprintOpt),
+    /// List or drop the host keys ssh keeps in the profile's own known_hosts
+    /// file, used with `isolated_known_hosts`
+    #[command(subcommand)]
+    KnownHosts(KnownHostsOpt),
+    /// Manage per-server ssh options, passed as `-o Key=Value`
+    #[command(subcommand)]
+    Option(OptionOpt),
+    /// Manage environment variables sent to the remote session with `SendEnv`;
+    /// the server's sshd must list them in `AcceptEnv`
+    #[command(subcommand)]
+    Env(EnvOpt),
+    /// Manage the tags servers are selected by, as in `jump ls tag:prod`
+    #[command(subcommand)]
+    Tag(TagOpt),
+    /// Manage the addresses `conn` falls back to when a server's own address
+    /// does not answer
+    #[command(subcommand)]
+    Address(AddressOpt),
+    /// Write servers to a bundle for `jump import` on another machine, as
+    /// JSON on stdout unless `--encrypted` is given
+    Export {
+        /// Servers to export; all of them when omitted
+        server_names: Vec<String>,
+        /// Encrypt the bundle with a passphrase, from `JUMP_PASSPHRASE` or
+        /// asked for, and write it to FILE
+        #[arg(long, value_name = "FILE")]
+        encrypted: Option<PathBuf>,
+        /// Ask for the passphrase even when `JUMP_PASSPHRASE` is set
+        #[arg(long, requires = "encrypted")]
+        passphrase_prompt: bool,
+        /// Include passwords in the bundle
+        #[arg(long)]
+        include_secrets: bool,
+        /// Write a Windows Terminal fragment, one profile per server running
+        /// `jump conn <name>`, instead of a bundle
+        #[arg(long, group = "profiles", conflicts_with_all = ["encrypted", "include_secrets"])]
+        windows_terminal: bool,
+        /// Write an iTerm2 Dynamic Profiles file instead of a bundle
+        #[arg(long, group = "profiles", conflicts_with_all = ["encrypted", "include_secrets"])]
+        iterm2: bool,
+        /// Write a Markdown table sorted by name, without secrets, instead of
+        /// a bundle
+        #[arg(long, group = "profiles", conflicts_with_all = ["encrypted", "include_secrets"])]
+        markdown: bool,
+        /// The columns of the Markdown table, in order
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            requires = "markdown",
+            default_values_t = markdown::Column::ALL
+        )]
+        columns: Vec<markdown::Column>,
+        /// Write to FILE instead of stdout
+        #[arg(long, value_name = "FILE", conflicts_with = "encrypted")]
+        out: Option<PathBuf>,
+    },
+    /// Edit every server in $EDITOR, then apply the changes after a preview
+    Edit {
+        /// Edit the whole store
+        #[arg(long, required = true)]
+        all: bool,
+        /// Show passwords instead of a placeholder
+        #[arg(long)]
+        include_secrets: bool,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Add the servers of a bundle written by `jump export`, after a preview
+    Import {
+        file: PathBuf,
+        /// What to do with servers whose name is already taken
+        #[arg(long, value_enum, default_value_t)]
+        on_conflict: Conflict,
+        /// Only show what would be imported
+        #[arg(long)]
+        dry_run: bool,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Keep the servers, without passwords, in a git repository
+    #[command(subcommand)]
+    Sync(SyncOpt),
+    /// Print the servers as /etc/hosts lines, or keep them in a hosts file
+    #[command(subcommand)]
+    Hosts(HostsOpt),
+    /// What is known of the local keys the servers use
+    #[command(subcommand)]
+    Keys(KeysOpt),
+    /// Move the servers that log in with one key to another, one at a time:
+    /// the new public key is added to authorized_keys with the old key, a
+    /// login with the new key is tried, then the new key is stored, e.g.
+    /// `jump rekey --from ~/.ssh/old --to ~/.ssh/new tag:prod`
+    Rekey {
+        /// The key the servers log in with now
+        #[arg(long, value_name = "KEY")]
+        from: PathBuf,
+        /// The key to move them to, with its public key in `KEY.pub`
+        #[arg(long, value_name = "KEY")]
+        to: PathBuf,
+        /// Only the servers this selects among those using `--from`
+        #[arg(value_name = "SELECTOR", value_parser = select::parse_arg)]
+        selector: Option<Selector>,
+        /// Go on with the other servers after one has failed, instead of
+        /// leaving them on the old key
+        #[arg(long)]
+        continue_on_error: bool,
+        /// Skip the confirmation asked for more than `confirm.servers` servers
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        options: ConnOptions,
+    },
+    /// List the servers that share an address and port with another
+    Dedupe,
+    /// Remove servers that are no longer needed, keeping them in
+    /// `~/.jump/trash` for `jump import`
+    #[command(group(clap::ArgGroup::new("decided").args(["delete", "archive"])))]
+    Prune {
+        /// Remove the servers whose expiry has passed
+        #[arg(long, required_unless_present_any = ["unreachable", "unreachable_for"])]
+        expired: bool,
+        /// Check every server and go through those that do not answer, asking
+        /// whether to keep, archive or delete each
+        #[arg(long, conflicts_with = "expired")]
+        unreachable: bool,
+        /// Only check the servers not connected to successfully within AGE,
+        /// e.g. `90d`; implies `--unreachable`
+        #[arg(long, value_name = "AGE", value_parser = audit::parse_age, conflicts_with = "expired")]
+        unreachable_for: Option<i64>,
+        /// Seconds to wait for each server
+        #[arg(long, default_value = "3", conflicts_with = "expired")]
+        timeout: u64,
+        /// Delete every unreachable server instead of asking about each
+        #[arg(long, conflicts_with_all = ["expired", "archive"])]
+        delete: bool,
+        /// Tag every unreachable server `archived` instead of asking about
+        /// each
+        #[arg(long, conflicts_with = "expired")]
+        archive: bool,
+        /// Skip the confirmation asked with `--delete` or `--archive`
+        #[arg(short, long, requires = "decided")]
+        yes: bool,
+        /// Only list the servers that would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Rewrite `~/.jump/cache/servers.txt`, the server list for shell
+    /// prompts, and remove the lists of profiles whose store is gone
+    #[command(visible_alias = "completion-data")]
+    RefreshCache,
+    /// Show which external binaries jump resolved and what is wrong with
+    /// the jump directory and the store
+    Doctor {
+        /// Apply the fixes after listing them
+        #[arg(long)]
+        fix: bool,
+        /// Skip the confirmation prompt
+        #[arg(short, long, requires = "fix")]
+        yes: bool,
+    },
+    /// Look for secrets jump may have leaked: shell history lines with
+    /// passwords, files under ~/.jump others can read, and plain exports
+    /// with passwords among PATHS; exits with 1 when there are errors
+    AuditSecrets {
+        /// Files and directories to look for exports in
+        paths: Vec<PathBuf>,
+        /// Tighten the permissions and scrub the history lines after
+        /// listing them
+        #[arg(long)]
+        fix: bool,
+        /// Skip the confirmation prompt
+        #[arg(short, long, requires = "fix")]
+        yes: bool,
+    },
+    /// Read every server through the strict parsers and list what is wrong
+    /// with each; exits with 1 when there are errors, not only warnings
+    Verify {
+        /// Check this store file instead of the profile's, e.g. an exported
+        /// one in CI
+        #[arg(long, value_name = "PATH")]
+        store: Option<PathBuf>,
+        /// Apply the fixes `jump doctor` knows, then check again
+        #[arg(long)]
+        fix: bool,
+        /// Skip the confirmation prompt
+        #[arg(short, long, requires = "fix")]
+        yes: bool,
+    },
+    /// Read or change settings in the config file
+    #[command(subcommand)]
+    Config(ConfigOpt),
+    /// Print a shell completion script, e.g. `jump completions bash`
+    Completions { shell: Shell },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Verify {
+    /// Keep the server and print why it could not be reached
+    Warn,
+    /// Only keep the server when it could be reached
+    Strict,
+}
+
+#[derive(Debug, Subcommand)]
+enum RecordingsOpt {
+    /// List recordings, oldest first
+    Ls,
+    /// Delete recordings last changed longer ago than AGE, e.g. `30d`
+    Prune {
+        #[arg(long, value_name = "AGE", value_parser = audit::parse_age)]
+        older_than: i64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum FingerprintOpt {
+    /// Fetch the host key with ssh-keyscan and record it, replacing the
+    /// recorded one
+    Fetch {
+        server_name: String,
+        /// Seconds to wait for the server
+        #[arg(long, default_value_t = hostkey::TIMEOUT)]
+        timeout: u32,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum KnownHostsOpt {
+    /// List the keys with the servers they belong to, `-` for none
+    Ls,
+    /// Drop the keys of a server, as after it was rebuilt
     Rm { server_name: String },
-    /// List all servers in current store
+}
+
+#[derive(Debug, Subcommand)]
+enum SessionsOpt {
+    /// List the live shared connections and the servers using them
     Ls,
-    /// Connecting to server
-    Conn { server_name: String },
+    /// Close the shared connection of a server, or all of them
+    Close {
+        #[arg(required_unless_present = "all")]
+        server_name: Option<String>,
+        #[arg(long, conflicts_with = "server_name")]
+        all: bool,
+    },
 }
 
-#[derive(Debug, Args, Serialize, Deserialize)]
-struct Server {
-    server_name: String,
-    username: String,
-    // #[arg(value_parser  = parse_ip)]
-    server_address: String,
-    #[arg(default_value = "22")]
-    port: u32,
-    #[command(subcommand)]
-    method: ConnectMethods,
+#[derive(Debug, Subcommand)]
+enum SyncOpt {
+    /// Sync with a repository: a local path, or a URL to clone
+    Init {
+        #[arg(value_name = "REPO")]
+        repo: String,
+    },
+    /// Commit the servers to the repository and push them
+    Push,
+    /// Pull the repository and merge its servers into the store
+    Pull {
+        /// Settle conflicts by keeping the local versions
+        #[arg(long, conflicts_with = "theirs")]
+        ours: bool,
+        /// Settle conflicts by taking the repository's versions
+        #[arg(long)]
+        theirs: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum HostsOpt {
+    /// Print `<ip> <name>` for every server whose address is an IP
+    Export {
+        /// Include servers with a hostname, by the address it resolves to
+        #[arg(long)]
+        resolve: bool,
+    },
+    /// Rewrite the block between `# BEGIN <marker>` and `# END <marker>` in
+    /// a hosts file, adding it at the end when there is none
+    Sync {
+        #[arg(long, default_value = "/etc/hosts")]
+        file: PathBuf,
+        #[arg(long, default_value = "jump")]
+        marker: String,
+        /// Include servers with a hostname, by the address it resolves to
+        #[arg(long)]
+        resolve: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum KeysOpt {
+    /// List each key servers last accepted, with the servers, and the key
+    /// servers no key was recorded for yet
+    Usage,
+}
+
+#[derive(Debug, Subcommand)]
+enum OptionOpt {
+    /// Set an option, e.g. `jump option set web1 ForwardAgent yes`
+    Set {
+        server_name: String,
+        #[arg(required_unless_present = "legacy")]
+        key: Option<String>,
+        #[arg(required_unless_present = "legacy")]
+        value: Option<String>,
+        /// Set HostKeyAlgorithms, KexAlgorithms, Ciphers and
+        /// PubkeyAcceptedAlgorithms to also allow the ssh-rsa, SHA-1 key
+        /// exchange and CBC ciphers of old devices
+        #[arg(long, conflicts_with_all = ["key", "value"])]
+        legacy: bool,
+    },
+    /// Remove an option
+    Unset {
+        server_name: String,
+        #[arg(required_unless_present = "legacy")]
+        key: Option<String>,
+        /// Remove every algorithm option, as set by `--legacy` or by hand
+        #[arg(long, conflicts_with = "key")]
+        legacy: bool,
+    },
+    /// List the options of a server
+    Ls { server_name: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum EnvOpt {
+    /// Set a variable, e.g. `jump env set web1 DEPLOY_ENV=staging`
+    Set {
+        server_name: String,
+        #[arg(value_name = "KEY=VAL", value_parser = parse_env_pair)]
+        pair: (String, String),
+    },
+    /// Remove a variable
+    Unset { server_name: String, name: String },
+    /// List the variables of a server
+    Ls { server_name: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum TagOpt {
+    /// Tag a server, e.g. `jump tag add web1 prod eu`
+    Add {
+        server_name: String,
+        #[arg(required = true, value_parser = parse_tag)]
+        tags: Vec<String>,
+    },
+    /// Remove tags from a server
+    Rm {
+        server_name: String,
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// List the tags of a server
+    Ls { server_name: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum AddressOpt {
+    /// Add an address, tried after the others, e.g.
+    /// `jump address add web1 203.0.113.9 --label public`
+    Add {
+        server_name: String,
+        address: String,
+        /// Name to pick the address with, as `conn --address-label <LABEL>`
+        #[arg(long)]
+        label: Option<String>,
+    },
+    /// Remove an address, given as the address or its label
+    Rm {
+        server_name: String,
+        address: String,
+    },
+    /// List the addresses of a server in the order `conn` tries them
+    Ls { server_name: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigOpt {
+    /// Print the value of a setting
+    Get { key: String },
+    /// Change a setting, e.g. `jump config set confirm.rm true`
+    Set { key: String, value: String },
+    /// Print the location of the config file
+    Path,
+    /// Open the config file in $EDITOR
+    Edit,
+    /// Show which `[defaults]` rules match an address and what `add` would
+    /// take from them
+    TestDefaults {
+        address: String,
+        /// Tags the server would be added with, for `tag:` rules
+        #[arg(long = "tag", value_name = "TAG", value_parser = parse_tag)]
+        tags: Vec<String>,
+    },
 }
 
-#[derive(Debug, Subcommand, Serialize, Deserialize)]
-enum ConnectMethods {
-    SSHKey(SSHKey),
-    Password(Password),
+fn main() -> Result<()> {
+    if let Some(prompt) = askpass::prompt() {
+        let password = std::env::var(askpass::SECRET_VAR)?;
+        match askpass::answer(&prompt, &password) {
+            Some(password) => println!("{}", password),
+            None => std::process::exit(exit::FAILURE),
+        }
+        return Ok(());
+    }
+    let config = Config::load();
+    if let Some(profile) = complete_servers_request() {
+        complete_servers(config.ok(), profile);
+        return Ok(());
+    }
+    let matches = arg_matches(config.as_ref().unwrap_or(&Config::default()));
+    let mut args = Jump::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Some(Opt::Add { server, given, .. }) = &mut args.opt {
+        let add = matches.subcommand_matches("add").expect("add was parsed");
+        given.port = add.value_source("port") == Some(ValueSource::CommandLine);
+        given.identity = add
+            .subcommand_matches("ssh-key")
+            .is_some_and(|key| key.value_source("path") == Some(ValueSource::CommandLine));
+        given.user = true;
+        let target = add
+            .get_one::<ssh::Target>("uri")
+            .or_else(|| add.get_one::<ssh::Target>("auto_name"));
+        if let Some(target) = target {
+            server.username = target.username.clone().unwrap_or_default();
+            server.server_address = target.address.clone();
+            server.port = target.port.unwrap_or(server.port);
+            given.user = target.username.is_some();
+            given.port = target.port.is_some();
+        }
+    }
+    log::set_verbosity(match (args.quiet, args.verbose) {
+        (true, _) => Verbosity::Quiet,
+        (_, true) => Verbosity::Verbose,
+        _ => Verbosity::Normal,
+    });
+    prompt::set_interactive(
+        args.interactive || (!args.non_interactive && io::stdin().is_terminal()),
+    );
+    let format = args.output;
+    if let Err(e) = run(args, config) {
+        match format {
+            OutputFormat::Json => eprintln!("{}", output::error(&e)),
+            OutputFormat::Text => eprintln!("Error: {:?}", e),
+        }
+        std::process::exit(error::exit_code(&e));
+    }
+    Ok(())
 }
 
-#[derive(Debug, Args, Serialize, Deserialize)]
-struct SSHKey {
-    #[arg(value_parser = parse_ssh_path, default_value = "~/.ssh/id_rsa")]
-    path: PathBuf,
+/// Run the command, or on a first run without one, the setup; a command
+/// that succeeds on a first run stands in for the setup
+fn run(args: Jump, config: Result<Config>) -> Result<()> {
+    let first_run = match &config {
+        Ok(config) => first_run(config, &args)?,
+        Err(_) => false,
+    };
+    if first_run && args.opt.is_none() {
+        return setup(&config?, args.profile.as_deref());
+    }
+    run_command(args, config)?;
+    if first_run {
+        if let Err(e) = Config::set("setup_done", "true") {
+            debug!("could not record that the setup is done: {}", e);
+        }
+    }
+    Ok(())
 }
 
-fn parse_ssh_path(str: &str) -> Result<PathBuf, Infallible> {
-    str.try_into()
+/// Whether the setup is due: there is no store yet, it was never offered,
+/// and questions may be asked
+fn first_run(config: &Config, args: &Jump) -> Result<bool> {
+    if config.setup_done || config.readonly || args.read_only || !prompt::interactive() {
+        return Ok(false);
+    }
+    let profile = args.profile.as_deref().unwrap_or(&config.default_profile);
+    let path = store_path(profile)?;
+    Ok(!path.exists() || !Store::open_read_only(&path)?.is_initialized()?)
 }
 
-#[derive(Debug, Parser, Serialize, Deserialize)]
-struct Password {
-    password: String,
+/// Walk through creating the store, taking the hosts of ~/.ssh/config,
+/// installing completions and adding a first server, each skippable
+fn setup(config: &Config, profile: Option<&str>) -> Result<()> {
+    let profile = profile.unwrap_or(&config.default_profile);
+    let path = store_path(profile)?;
+    println!("jump has no servers yet. A few questions set it up; each can be skipped.");
+    let store = setup::run(config, &path, |shell| completion_script(config, shell))?;
+    let _cache = store.as_ref().map(|store| CacheRefresh { store, profile });
+    match store {
+        Some(_) => println!("Done. `jump` now lists your servers, `jump --help` shows the rest."),
+        None => println!("Skipped. `jump init` creates the store later."),
+    }
+    Ok(())
 }
 
-impl Display for ConnectMethods {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ConnectMethods::SSHKey(key) => {
-                write!(f, "ssh:{}", key.path.to_str().unwrap())
+fn run_command(args: Jump, config: Result<Config>) -> Result<()> {
+    let fallback = Config::default();
+    let mut opt = args.opt.unwrap_or(Opt::Tui { probe: false });
+    if let Opt::Config(opt) = opt {
+        return config_command(config, opt);
+    }
+    if let Opt::Completions { shell } = opt {
+        return completions(config.as_ref().unwrap_or(&fallback), shell);
+    }
+    let mut config = config?;
+    debug!("config: {}", Config::path()?.display());
+    // From here on the profile in use, which per-profile files follow
+    if let Some(profile) = &args.profile {
+        config.default_profile = profile.clone();
+    }
+    let config = config;
+    let profile = &config.default_profile;
+    let path = store_path(profile)?;
+    // Before anything is created or migrated, so that it sees the problems
+    if let Opt::Doctor { fix, yes } = opt {
+        return doctor(&config, &path, fix, yes, args.read_only || config.readonly);
+    }
+    if let Opt::AuditSecrets { paths, fix, yes } = opt {
+        let style = Style::new(args.color.unwrap_or(config.color));
+        return audit_secrets(&paths, style, args.output, fix, yes);
+    }
+    if let Opt::Verify { store, fix, yes } = opt {
+        let read_only = args.read_only || config.readonly;
+        let style = Style::new(args.color.unwrap_or(config.color));
+        let path = store.unwrap_or(path);
+        return verify_store(&path, style, args.output, fix, yes, read_only);
+    }
+    if let Opt::Init {
+        force_recreate,
+        from,
+        yes,
+    } = opt
+    {
+        let read_only = args.read_only || config.readonly;
+        return init(&path, profile, force_recreate, from, yes, read_only);
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    debug!("store: {}", path.display());
+    let mut store = match args.read_only || config.readonly {
+        true => Store::open_read_only(&path)?,
+        false => Store::open(&path)?,
+    };
+    if !config.include.is_empty() && store.is_initialized()? {
+        let config_path = Config::path()?;
+        let dir = config_path.parent().unwrap_or(Path::new("."));
+        let included = include::load(&config, dir, &store)?;
+        store.include(included)?;
+    }
+    if let Opt::Conn { options, .. }
+    | Opt::Console { options, .. }
+    | Opt::Exec { options, .. }
+    | Opt::Cp { options, .. }
+    | Opt::Recent { options, .. }
+    | Opt::Rekey { options, .. } = &mut opt
+    {
+        options.via = jump_hosts(&store, &options.via)?;
+    }
+    let _cache = CacheRefresh {
+        store: &store,
+        profile,
+    };
+    let style = Style::new(args.color.unwrap_or(config.color));
+    if config.prune_expired && store.writable().is_ok() && !matches!(opt, Opt::Prune { .. }) {
+        match prune::expired(&store, profile, audit::now(), false) {
+            Ok(names) => {
+                for name in names {
+                    info!("removed {}, which has expired", name);
+                }
+            }
+            Err(e) => debug!("could not remove expired servers: {}", e),
+        }
+    }
+
+    match opt {
+        Opt::Tui { probe } => interactive(&store, &config, style, probe),
+        Opt::Add {
+            mut server,
+            verify,
+            verify_timeout,
+            legacy,
+            ssh_config,
+            given,
+            method,
+        } => {
+            server.method = method.unwrap_or_else(|| {
+                ConnectMethods::SSHKey(SSHKey {
+                    path: PathBuf::from(&config.default_identity),
+                })
+            });
+            if legacy {
+                set_legacy(&mut server.options);
+            }
+            match ssh_config {
+                Some(alias) => ssh_config::connect_by(&mut server, alias)?,
+                None => apply_defaults(&config, &mut server, given)?,
+            }
+            // Left empty by `--auto-name` and by `--uri` without a name
+            if server.server_name.is_empty() {
+                server.server_name = free_name(&store, &config, &server.server_address)?;
+            }
+            match verify {
+                Some(mode) => add_verified(&store, &config, &server, mode, verify_timeout),
+                None => Ok(store.add_checked(&server, config.strict_duplicates)?),
+            }
+        }
+        Opt::Rm { server_names, yes } => remove_servers(&store, &config, &server_names, yes),
+        Opt::Ls {
+            selectors,
+            explain,
+            tree,
+            plain,
+            long,
+            sort,
+            user,
+            subnet,
+            resolve,
+            include_secrets,
+        } => {
+            let mut servers = store.list()?;
+            let last_connected = store.last_connected()?;
+            if let Some(key) = sort {
+                output::sort(&mut servers, key, &last_connected);
+            }
+            servers.retain(|server| {
+                user.as_ref()
+                    .is_none_or(|user| select::glob(user, &server.username))
+                    && subnet.is_none_or(|subnet| subnet.matches(server, resolve))
+            });
+            if !explain {
+                servers = select::filter(servers, &selectors);
+            }
+            if args.output == OutputFormat::Json {
+                return match (explain, tree) {
+                    (true, _) => print_json(
+                        servers
+                            .iter()
+                            .map(|server| output::explanation(server, &selectors))
+                            .collect::<Vec<_>>(),
+                    ),
+                    (_, true) => print_json(
+                        output::tag_groups(&servers)
+                            .into_iter()
+                            .map(|(tag, members)| output::Group {
+                                tag,
+                                servers: members
+                                    .iter()
+                                    .map(|server| &*server.server_name)
+                                    .collect(),
+                            })
+                            .collect::<Vec<_>>(),
+                    ),
+                    _ => print_json(
+                        servers
+                            .iter()
+                            .map(|server| output::server(server, include_secrets))
+                            .collect::<Vec<_>>(),
+                    ),
+                };
+            }
+            let now = audit::now();
+            let out = match () {
+                _ if explain => output::explain(style, &servers, &selectors),
+                _ if tree => output::tree(style, &servers, now),
+                _ if long => output::long(style, &servers, &last_connected, terminal_width(), now),
+                _ => output::list(style, &servers, plain, now),
+            };
+            Ok(pager::page(&out, config.pager && !args.no_pager)?)
+        }
+        Opt::Move {
+            server_name,
+            position,
+            before,
+            after,
+        } => {
+            let place = match (position, before, after) {
+                (Some(position), _, _) => Place::At(position),
+                (_, Some(before), _) => Place::Before(before),
+                (_, _, Some(after)) => Place::After(after),
+                (None, None, None) => unreachable!("clap requires a position"),
+            };
+            Ok(store.move_server(&server_name, place)?)
+        }
+        Opt::Reorder { alphabetical: _ } => {
+            Ok(store.transaction(|| store.set_order(&store.names()?))?)
+        }
+        Opt::Set {
+            field,
+            value,
+            filters,
+            dry_run,
+            yes,
+        } => set_field(&store, field, &value, &filters, dry_run, yes),
+        Opt::Search { words } => {
+            let found = search::search(store.list()?, &words);
+            match args.output {
+                OutputFormat::Json => {
+                    print_json(found.iter().map(output::found).collect::<Vec<_>>())
+                }
+                OutputFormat::Text => {
+                    print!("{}", output::hits(style, &found));
+                    Ok(())
+                }
+            }
+        }
+        Opt::Knock { server_name } => {
+            let server = store.get(&server_name)?;
+            if server.knock.is_empty() {
+                return Err(JumpError::invalid(
+                    "knock",
+                    format!("no knock sequence stored for {}", server_name),
+                )
+                .into());
+            }
+            let delay = server
+                .knock_delay
+                .map_or(knock::DEFAULT_DELAY, Duration::from_millis);
+            knock::knock(&server.server_address, &server.knock, delay)?;
+            info!("knocked on {} ports", server.knock.len());
+            Ok(())
+        }
+        Opt::Resolve { server_name, json } => {
+            let format = match json {
+                true => OutputFormat::Json,
+                false => args.output,
+            };
+            resolve_command(&store, &config, &server_name, format)
+        }
+        Opt::Serve { stdio: _ } => Ok(serve::run(
+            io::stdin().lock(),
+            io::stdout(),
+            |method, params| serve::call(&store, &config, method, params),
+        )?),
+        Opt::Uri { server_name, qr } => {
+            let server = store.get(&server_name)?;
+            let uri = ssh::uri(&server)?;
+            match args.output {
+                OutputFormat::Json => print_json(output::Uri {
+                    server_name: &server.server_name,
+                    uri: &uri,
+                }),
+                OutputFormat::Text if qr => {
+                    print!("{}", qr::encode(uri.as_bytes())?.render());
+                    println!("{}", uri);
+                    Ok(())
+                }
+                OutputFormat::Text => {
+                    println!("{}", uri);
+                    Ok(())
+                }
+            }
+        }
+        Opt::Show {
+            server_name,
+            include_secrets,
+        } => match args.output {
+            OutputFormat::Json => {
+                print_json(output::server(&store.get(&server_name)?, include_secrets))
+            }
+            OutputFormat::Text => {
+                let server = store.get(&server_name)?;
+                let key_uses = store.key_uses(Some(&server_name))?;
+                let shown = output::show(style, &config, &server, key_uses.first(), audit::now());
+                print!("{}", shown);
+                Ok(())
+            }
+        },
+        Opt::Check {
+            server_names,
+            timeout,
+            plain,
+            yes,
+            report,
+            schedule,
+        } => {
+            let servers = match server_names.is_empty() {
+                true => store.list()?,
+                false => select::select(store.list()?, &server_names)?,
+            };
+            if !server_names.is_empty() && !confirm_selection(&config, &servers, "Check", yes)? {
+                return Ok(());
+            }
+            let limits = schedule.limits(&config);
+            check_servers(servers, style, args.output, timeout, plain, &report, limits)
+        }
+        Opt::Conn {
+            server_name: None,
+            group: Some(group),
+            tmux: Some(layout),
+            sync,
+            options,
+            ..
+        } => connect_to_group(
+            &store,
+            &config,
+            profile,
+            &group,
+            layout,
+            sync,
+            options.dry_run,
+        ),
+        Opt::Conn {
+            server_name,
+            options,
+            session,
+            ssh_args,
+            ..
+        } => {
+            let server_name = server_name.expect("clap requires a server or a group");
+            let server_name = resolve_name(&store, &config, server_name)?;
+            if session.new_window && open_in_new_window(&config, &server_name, options.dry_run)? {
+                return Ok(());
+            }
+            let outcome = connect::session(
+                &store,
+                &config,
+                style,
+                &server_name,
+                &options,
+                &session,
+                &ssh_args,
+            )?;
+            std::process::exit(session_code(outcome, &options));
+        }
+        Opt::Console {
+            server_name,
+            options,
+        } => {
+            let server_name = resolve_name(&store, &config, server_name)?;
+            let outcome = connect::console(&store, &config, style, &server_name, &options)?;
+            std::process::exit(session_code(outcome, &options));
+        }
+        Opt::Exec {
+            server_name,
+            on,
+            options,
+            tty,
+            batch,
+            no_batch,
+            connect_timeout,
+            yes,
+            report,
+            schedule,
+            command,
+        } => {
+            let selector = server_name.or(on).expect("clap requires a selector");
+            let batch = batch || (!no_batch && !prompt::interactive());
+            let exec = ssh::ExecOptions {
+                tty,
+                batch: batch.then_some(connect_timeout),
+            };
+            let run = Fleet {
+                yes,
+                report,
+                style,
+                limits: schedule.limits(&config),
+            };
+            exec_on_servers(&store, &config, &selector, &options, exec, run, &command)
+        }
+        Opt::Cp {
+            source,
+            destination,
+            recursive,
+            resume: _,
+            to: Some(selector),
+            continue_on_error,
+            yes,
+            report,
+            schedule,
+            options,
+        } => copy_to_servers(
+            &store,
+            &config,
+            &selector,
+            copy::Upload {
+                source,
+                destination,
+                recursive,
+                continue_on_error,
+            },
+            options,
+            Fleet {
+                yes,
+                report,
+                style,
+                limits: schedule.limits(&config),
+            },
+        ),
+        Opt::Cp {
+            source,
+            destination,
+            recursive,
+            resume,
+            options,
+            ..
+        } => copy_files(
+            &store,
+            &config,
+            style,
+            source,
+            destination,
+            recursive,
+            resume,
+            options,
+        ),
+        Opt::Log {
+            server,
+            since,
+            limit,
+        } => {
+            let since = since.map(|age| audit::now() - age);
+            let entries = store.log(server.as_deref(), since, limit)?;
+            match args.output {
+                OutputFormat::Json => print_json(entries),
+                OutputFormat::Text => Ok(pager::page(
+                    &output::log(style, &entries),
+                    config.pager && !args.no_pager,
+                )?),
             }
-            ConnectMethods::Password(p) => write!(f, "pass:{}", p.password),
+        }
+        Opt::Recent {
+            count,
+            conn,
+            options,
+        } => {
+            let recent = store.recent(count.max(conn.unwrap_or(0)))?;
+            if let Some(number) = conn {
+                let Some((server_name, _)) = number.checked_sub(1).and_then(|at| recent.get(at))
+                else {
+                    return Err(JumpError::invalid(
+                        "--conn",
+                        format!("there is no recent server {}", number),
+                    )
+                    .into());
+                };
+                let outcome = connect::session(
+                    &store,
+                    &config,
+                    style,
+                    server_name,
+                    &options,
+                    &SessionOptions::default(),
+                    &[],
+                )?;
+                std::process::exit(session_code(outcome, &options));
+            }
+            let now = audit::now();
+            match args.output {
+                OutputFormat::Json => print_json(
+                    recent
+                        .iter()
+                        .enumerate()
+                        .map(|(at, (server_name, connected_at))| output::Recent {
+                            number: at + 1,
+                            server_name,
+                            connected_at: *connected_at,
+                        })
+                        .collect::<Vec<_>>(),
+                ),
+                OutputFormat::Text if recent.is_empty() => {
+                    info!("no connections yet; `jump conn <name>` adds them here");
+                    Ok(())
+                }
+                OutputFormat::Text => {
+                    let width = recent.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+                    for (at, (server_name, connected_at)) in recent.iter().enumerate() {
+                        println!(
+                            "{}  {}{}  {}",
+                            at + 1,
+                            style.bold(server_name),
+                            " ".repeat(width - server_name.len()),
+                            audit::format_ago(*connected_at, now)
+                        );
+                    }
+                    Ok(())
+                }
+            }
+        }
+        Opt::Recordings(opt) => recordings(style, args.output, opt),
+        Opt::Sessions(opt) => sessions(&store, &config, opt),
+        Opt::Fingerprint(FingerprintOpt::Fetch {
+            server_name,
+            timeout,
+        }) => fetch_host_key(&store, &config, &server_name, timeout),
+        Opt::KnownHosts(opt) => known_hosts_command(&store, profile, opt),
+        Opt::Mount { list: true, .. } => list_mounts(&store, style, args.output),
+        Opt::Mount {
+            server_name: Some(server_name),
+            remote_path: Some(remote_path),
+            local_path: Some(local_path),
+            dry_run,
+            ..
+        } => mount(
+            &store,
+            &config,
+            &server_name,
+            &remote_path,
+            &local_path,
+            dry_run,
+        ),
+        Opt::Mount { .. } => unreachable!("clap requires a server and both paths"),
+        Opt::Umount { target } => umount(&store, &target),
+        Opt::Option(opt) => server_option(&store, opt),
+        Opt::Env(opt) => server_env(&store, opt),
+        Opt::Tag(opt) => server_tag(&store, opt),
+        Opt::Address(opt) => server_address(&store, opt),
+        Opt::Export {
+            server_names,
+            markdown: true,
+            columns,
+            out,
+            ..
+        } => {
+            let servers = named_servers(&store, &server_names)?;
+            let table = markdown::table(&servers, &columns);
+            write_out(table.trim_end().as_bytes(), out.as_deref(), servers.len())
+        }
+        Opt::Export {
+            server_names,
+            windows_terminal,
+            iterm2,
+            out,
+            ..
+        } if windows_terminal || iterm2 => {
+            let servers = named_servers(&store, &server_names)?;
+            let profiles = match iterm2 {
+                true => profiles::iterm2(&servers, &config.tag_colors),
+                false => profiles::windows_terminal(&servers, &config.tag_colors),
+            };
+            let json = serde_json::to_vec_pretty(&profiles)?;
+            write_out(&json, out.as_deref(), servers.len())
+        }
+        Opt::Export {
+            server_names,
+            encrypted,
+            passphrase_prompt,
+            include_secrets,
+            out,
+            ..
+        } => export(
+            &store,
+            server_names,
+            encrypted,
+            passphrase_prompt,
+            include_secrets,
+            out,
+        ),
+        Opt::Edit {
+            all: _,
+            include_secrets,
+            yes,
+        } => edit_store(&store, include_secrets, yes),
+        Opt::Import {
+            file,
+            on_conflict,
+            dry_run,
+            yes,
+        } => import(&store, &file, on_conflict, dry_run, yes),
+        Opt::Sync(opt) => sync_store(&store, &config, profile, opt),
+        Opt::Hosts(HostsOpt::Export { resolve }) => {
+            print!("{}", hosts::lines(&store.list()?, resolve));
+            Ok(())
+        }
+        Opt::Hosts(HostsOpt::Sync {
+            file,
+            marker,
+            resolve,
+        }) => {
+            let lines = hosts::lines(&store.list()?, resolve);
+            match hosts::sync(&file, &marker, &lines)? {
+                true => info!("updated {}", file.display()),
+                false => info!("{} is up to date", file.display()),
+            }
+            Ok(())
+        }
+        Opt::Keys(KeysOpt::Usage) => list_key_usage(&store),
+        Opt::Rekey {
+            from,
+            to,
+            selector,
+            continue_on_error,
+            yes,
+            options,
+        } => {
+            let keys = Rekey {
+                from,
+                to,
+                continue_on_error,
+                yes,
+            };
+            rekey_servers(&store, &config, style, selector, keys, options)
+        }
+        Opt::Dedupe => {
+            dedupe(&store.list()?);
+            Ok(())
+        }
+        Opt::RefreshCache => refresh_cache(&store, profile),
+        Opt::Prune {
+            expired: false,
+            unreachable_for,
+            timeout,
+            delete,
+            archive,
+            yes,
+            dry_run,
+            ..
+        } => {
+            let decided = match (delete, archive) {
+                (true, _) => Some(Pruning::Delete),
+                (_, true) => Some(Pruning::Archive),
+                _ => None,
+            };
+            prune_unreachable(
+                &store,
+                profile,
+                style,
+                unreachable_for,
+                timeout,
+                Limits::configured(&config),
+                decided,
+                yes,
+                dry_run,
+            )
+        }
+        Opt::Prune { dry_run, .. } => {
+            let names = prune::expired(&store, profile, audit::now(), dry_run)?;
+            for name in &names {
+                println!("{}", name);
+            }
+            match dry_run {
+                true => info!("{} servers have expired", names.len()),
+                false => info!("removed {} expired servers", names.len()),
+            }
+            Ok(())
+        }
+        Opt::Config(_)
+        | Opt::Completions { .. }
+        | Opt::Doctor { .. }
+        | Opt::Verify { .. }
+        | Opt::AuditSecrets { .. }
+        | Opt::Init { .. } => {
+            unreachable!()
         }
     }
 }
 
-impl From<String> for ConnectMethods {
-    fn from(method: String) -> Self {
-        let v = method.split(":").collect::<Vec<_>>();
-        match v[0] {
-            "ssh" => ConnectMethods::SSHKey(SSHKey {
-                path: PathBuf::from_str(v[1]).unwrap(),
-            }),
-            _ => ConnectMethods::Password(Password {
-                password: v[1].to_owned(),
-            }),
+/// The clap command with `add` defaults taken from the config file
+fn cli(config: &Config) -> clap::Command {
+    Jump::command().mut_subcommand("add", |add| {
+        add.arg(
+            clap::Arg::new("uri")
+                .long("uri")
+                .value_name("URI")
+                .value_parser(ssh::parse_uri)
+                .help(
+                    "Take the user, address and port from `ssh://user@host:port` or \
+                     `user@host:port`; the name defaults to the host's and the method to \
+                     the default key; without a user, a `[defaults]` rule has to give one",
+                ),
+        )
+        .arg(
+            clap::Arg::new("auto_name")
+                .long("auto-name")
+                .value_name("URI")
+                .value_parser(ssh::parse_uri)
+                .conflicts_with_all(["uri", "server_name"])
+                .help(
+                    "As --uri, but always naming the server after the host: the short \
+                     hostname, or the reverse-DNS name of an IP address",
+                ),
+        )
+        .subcommand_required(false)
+        .mut_arg("server_name", |name| {
+            name.required(false)
+                .required_unless_present_any(["uri", "auto_name"])
+                .default_value_if("uri", ArgPredicate::IsPresent, "")
+                .default_value_if("auto_name", ArgPredicate::IsPresent, "")
+        })
+        .mut_arg("username", |username| {
+            uri_replaces(username)
+                .required_unless_present_any(["uri", "auto_name", "ssh_config"])
+                .default_value_if("ssh_config", ArgPredicate::IsPresent, "")
+        })
+        .mut_arg("server_address", |address| {
+            uri_replaces(address)
+                .required_unless_present_any(["uri", "auto_name", "ssh_config"])
+                .default_value_if("ssh_config", ArgPredicate::IsPresent, "")
+        })
+        .mut_arg("port", |port| {
+            port.default_value(config.default_port.to_string())
+                .conflicts_with_all(["uri", "auto_name"])
+        })
+        .mut_subcommand("ssh-key", |key| {
+            key.mut_arg("path", |path| {
+                path.default_value(config.default_identity.clone())
+            })
+        })
+    })
+}
+
+/// The command line, where `add` needs a method unless given `--uri`,
+/// `--auto-name` or `--ssh-config`, and cannot have one with the last
+fn arg_matches(config: &Config) -> clap::ArgMatches {
+    let matches = cli(config).get_matches();
+    match matches.subcommand() {
+        Some(("add", add)) if add.contains_id("ssh_config") && add.subcommand().is_some() => {
+            let mut command = cli(config);
+            command.build();
+            let add = command
+                .find_subcommand_mut("add")
+                .expect("add is a subcommand");
+            add.error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--ssh-config leaves the method to ~/.ssh/config, so none can be given",
+            )
+            .exit();
         }
+        Some(("add", add)) if add.subcommand().is_none() => {
+            let target = add.contains_id("uri") || add.contains_id("auto_name");
+            if !target && !add.contains_id("ssh_config") {
+                let mut command = cli(config);
+                command.build();
+                let add = command
+                    .find_subcommand_mut("add")
+                    .expect("add is a subcommand");
+                add.error(
+                    clap::error::ErrorKind::MissingSubcommand,
+                    "a method is required: ssh-key, password or command",
+                )
+                .exit();
+            }
+            matches
+        }
+        _ => matches,
     }
 }
 
-fn main() -> Result<()> {
-    let args = Jump::parse();
-    let mut home = my_home()?.unwrap();
-    home.push(".jump/servers.db");
-    let conn = Connection::open(home)?;
-
-    match args.opt {
-        Opt::Initialize => initialize(conn),
-        Opt::Add(server) => add_server(conn, server),
-        Opt::Rm { server_name } => remove_server(conn, server_name),
-        Opt::Ls => list_servers(conn),
-        Opt::Conn { server_name } => connect_to_server(conn, server_name),
-    }
-}
-
-fn initialize(conn: Connection) -> Result<()> {
-    conn.execute(
-        "create table if not exists jump_servers (
-             id integer primary key,
-             server_name text not null unique,
-             username text not null,
-             server_address text not null,
-             port integer not null,
-             method text not null)",
-        [],
-    )?;
+/// A positional of `add` that `--uri` and `--auto-name` stand in for
+fn uri_replaces(arg: clap::Arg) -> clap::Arg {
+    arg.required(false)
+        .required_unless_present_any(["uri", "auto_name"])
+        .conflicts_with_all(["uri", "auto_name"])
+        .default_value_if("uri", ArgPredicate::IsPresent, "")
+        .default_value_if("auto_name", ArgPredicate::IsPresent, "")
+}
+
+/// Rewrites the profile's cache when dropped, at the end of a command, if
+/// the command changed the store or there is no cache yet. Failing to is
+/// only worth a debug message, since nothing depends on the cache.
+struct CacheRefresh<'a> {
+    store: &'a Store,
+    profile: &'a str,
+}
+
+impl Drop for CacheRefresh<'_> {
+    fn drop(&mut self) {
+        let missing = cache::path(self.profile).is_ok_and(|path| !path.exists());
+        if self.store.writable().is_err() || (self.store.changes() == 0 && !missing) {
+            return;
+        }
+        let written = match self.store.is_initialized() {
+            Ok(true) => self
+                .store
+                .list()
+                .and_then(|servers| cache::write(self.profile, &servers)),
+            other => other,
+        };
+        if let Err(e) = written {
+            debug!("could not write the server cache: {}", e);
+        }
+    }
+}
+
+/// Write the profile's cache and remove those of profiles without a store
+fn refresh_cache(store: &Store, profile: &str) -> Result<()> {
+    store.writable()?;
+    let servers = store.list()?;
+    cache::write(profile, &servers)?;
+    info!(
+        "{} servers in {}",
+        servers.len(),
+        cache::path(profile)?.display()
+    );
+    for stale in cache::profiles()? {
+        if !store_path(&stale)?.exists() && cache::remove(&stale)? {
+            info!("removed the cache of {}, whose store is gone", stale);
+        }
+    }
     Ok(())
 }
 
-fn add_server(conn: Connection, server: Server) -> Result<()> {
-    conn.execute(
-        "INSERT INTO jump_servers (server_name, username, server_address, port, method) values (?1, ?2, ?3, ?4, ?5)",
-        [server.server_name, server.username, server.server_address, server.port.to_string(), server.method.to_string()],
+fn store_path(profile: &str) -> Result<PathBuf> {
+    let mut path = config::jump_dir()?;
+    match profile {
+        "default" => path.push("servers.db"),
+        _ => path.push(format!("{}.db", profile)),
+    }
+    Ok(path)
+}
+
+/// Alternate between the interface and the ssh sessions started from it
+fn interactive(store: &Store, config: &Config, style: Style, probe: bool) -> Result<()> {
+    prompt::allow(
+        "which server to connect to",
+        "name it with `jump conn <name>`",
     )?;
+    let mut app = tui::App::new(store.list()?);
+    if probe {
+        app.probe(config.parallel);
+    }
+    while let tui::Action::Connect(server_name) = tui::run(&mut app, store, config)? {
+        let options = ConnOptions::default();
+        let outcome = connect::session(
+            store,
+            config,
+            style,
+            &server_name,
+            &options,
+            &SessionOptions::default(),
+            &[],
+        );
+        match outcome {
+            Ok(outcome) => {
+                session_code(outcome, &options);
+            }
+            Err(e) => app.notify(e.to_string()),
+        }
+    }
     Ok(())
 }
 
-fn remove_server(conn: Connection, server_name: String) -> Result<()> {
-    conn.execute(
-        "DELETE FROM jump_servers WHERE server_name = ?1",
-        [server_name],
-    )?;
+/// Add `server`, then run `true` on it in batch mode. A failure is only
+/// reported, unless `mode` is strict: the server is then removed again.
+fn add_verified(
+    store: &Store,
+    config: &Config,
+    server: &Server,
+    mode: Verify,
+    timeout: u32,
+) -> Result<()> {
+    store.add_checked(server, config.strict_duplicates)?;
+    if matches!(server.method, ConnectMethods::Command(_)) {
+        warn!(
+            "{} is reached through its own command, which --verify does not run",
+            server.server_name
+        );
+        return Ok(());
+    }
+    let exec = ssh::ExecOptions {
+        tty: false,
+        batch: Some(timeout),
+    };
+    let failure = match ssh::exec(
+        config,
+        server,
+        &ConnOptions::default(),
+        &["true".to_owned()],
+        exec,
+    ) {
+        Ok(invocation) => {
+            debug!("running {}", invocation);
+            connect::run_captured(&invocation).err()
+        }
+        Err(e) => Some(e.to_string()),
+    };
+    let Some(failure) = failure else {
+        info!("connected to {}", server.server_name);
+        return Ok(());
+    };
+    let failure = failure.trim();
+    if mode == Verify::Warn {
+        warn!(
+            "could not connect to {}, added it anyway: {}",
+            server.server_name, failure
+        );
+        return Ok(());
+    }
+    store.remove(&server.server_name)?;
+    Err(JumpError::Unverified {
+        name: server.server_name.clone(),
+        reason: failure.to_owned(),
+    }
+    .into())
+}
+
+/// A name for a server at `address` that no other server has, saying so
+fn free_name(store: &Store, config: &Config, address: &str) -> Result<String> {
+    let base = naming::derive(address, |ip| naming::reverse_lookup(config, ip));
+    let name = naming::free(&base, |name| Ok(store.find(name)?.is_some()))?;
+    match name == base {
+        true => info!("adding the server as {}", name),
+        false => info!("{} is taken, adding the server as {}", base, name),
+    }
+    Ok(name)
+}
+
+/// Which of the fields `[defaults]` rules fill in were given to `add`
+#[derive(Debug, Default, Clone, Copy)]
+struct Given {
+    user: bool,
+    port: bool,
+    identity: bool,
+}
+
+/// Fill in what the `[defaults]` rules of the config give for the fields
+/// not given to `add`, saying where each value came from
+fn apply_defaults(config: &Config, server: &mut Server, given: Given) -> Result<()> {
+    let resolved = defaults::resolve(config, &server.server_address, &server.tags);
+    let mut applied = vec![];
+    if !given.user {
+        let user = resolved.user.ok_or_else(|| {
+            JumpError::invalid(
+                "user",
+                format!(
+                    "none given for {}, and no [defaults] rule gives one",
+                    server.server_address
+                ),
+            )
+        })?;
+        server.username = user.value.clone();
+        applied.push(("user", user.value, user.rule));
+    }
+    if !given.port && resolved.port.rule.is_some() {
+        server.port = resolved.port.value;
+        applied.push(("port", resolved.port.value.to_string(), resolved.port.rule));
+    }
+    if let ConnectMethods::SSHKey(key) = &mut server.method {
+        if !given.identity && resolved.identity.rule.is_some() {
+            key.path = PathBuf::from(&resolved.identity.value);
+            applied.push(("identity", resolved.identity.value, resolved.identity.rule));
+        }
+    }
+    for (field, value, rule) in applied {
+        let rule = rule.expect("only values of rules are applied");
+        info!("{} {}, from [defaults.\"{}\"]", field, value, rule);
+    }
     Ok(())
 }
 
-fn list_servers(conn: Connection) -> Result<()> {
-    let mut stmt = conn
-        .prepare("SELECT server_name, username, server_address, port, method FROM jump_servers")?;
-    let servers = stmt.query_map([], |row| {
-        let method_string: String = row.get(4)?;
-        Ok(Server {
-            server_name: row.get(0)?,
-            username: row.get(1)?,
-            server_address: row.get(2)?,
-            port: row.get(3)?,
-            method: ConnectMethods::from(method_string),
-        })
+/// Report each group of servers pointing at the same sshd
+fn dedupe(servers: &[Server]) {
+    let groups = server::same_endpoint_groups(servers);
+    for group in &groups {
+        let names = group
+            .iter()
+            .map(|server| server.server_name.as_str())
+            .collect::<Vec<_>>();
+        println!(
+            "{}:{}: {}",
+            group[0].server_address,
+            group[0].port,
+            names.join(", ")
+        );
+    }
+    match groups.len() {
+        0 => info!("no two servers share an address and port"),
+        _ => info!("keep one server of each group and remove the others with `jump rm <name>`"),
+    }
+}
+
+/// Removing more than one server always asks first, unless `yes` is given
+fn remove_servers(store: &Store, config: &Config, selectors: &[Selector], yes: bool) -> Result<()> {
+    let names = select::select(store.list()?, selectors)?
+        .into_iter()
+        .map(|server| server.server_name)
+        .collect::<Vec<_>>();
+    store.writable()?;
+    let prompt = match &names[..] {
+        [name] => format!("Remove server {}?", name),
+        _ => format!("Remove {} servers: {}?", names.len(), names.join(", ")),
+    };
+    if !yes && (config.confirm.rm || names.len() > 1) && !prompt::confirm(&prompt, "--yes")? {
+        return Ok(());
+    }
+    store.transaction(|| {
+        for name in &names {
+            store.remove(name)?;
+        }
+        Ok(())
     })?;
-    for server in servers {
-        let server = server?;
+    Ok(())
+}
+
+/// Probe the servers, leaving out archived ones and, with `unreachable_for`,
+/// those connected to successfully within that many seconds, and go
+/// through those that do not answer: one at a time, or all at once with
+/// `decided`
+#[allow(clippy::too_many_arguments)]
+fn prune_unreachable(
+    store: &Store,
+    profile: &str,
+    style: Style,
+    unreachable_for: Option<i64>,
+    timeout: u64,
+    limits: Limits,
+    decided: Option<Pruning>,
+    yes: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let last_succeeded = store.last_succeeded()?;
+    let servers = prune::candidates(
+        store.list_stored()?,
+        &last_succeeded,
+        unreachable_for,
+        audit::now(),
+    );
+    let probes = wol::probe_all(&servers, Duration::from_secs(timeout), limits);
+    let down = servers
+        .into_iter()
+        .zip(probes)
+        .filter(|(_, (up, _))| !up)
+        .map(|(server, _)| server)
+        .collect::<Vec<_>>();
+    if down.is_empty() {
+        info!("every server answered");
+        return Ok(());
+    }
+    if !dry_run {
+        store.writable()?;
+    }
+
+    let mut pruned = vec![];
+    for server in down {
+        let last = match last_succeeded.get(&server.server_name) {
+            Some(last) => audit::format_time(*last),
+            None => "never".to_owned(),
+        };
+        let tags = match server.tags.is_empty() {
+            true => "-".to_owned(),
+            false => server.tags.join(", "),
+        };
         println!(
-            "{} username: {} address: {}",
-            server.server_name, server.username, server.server_address
+            "{}  {}@{}:{}\n  last connected: {}\n  tags: {}",
+            style.bold(&server.server_name),
+            server.username,
+            server.server_address,
+            server.port,
+            last,
+            tags
+        );
+        let pruning = match decided {
+            _ if dry_run => Pruning::Keep,
+            Some(pruning) => pruning,
+            None => {
+                match prompt::answer("Keep, archive or delete? [K/a/d]", "--delete or --archive")?
+                    .to_ascii_lowercase()
+                    .as_str()
+                {
+                    "a" | "archive" => Pruning::Archive,
+                    "d" | "delete" => Pruning::Delete,
+                    _ => Pruning::Keep,
+                }
+            }
+        };
+        pruned.push((server, pruning));
+    }
+    if dry_run {
+        info!("{} servers did not answer", pruned.len());
+        return Ok(());
+    }
+    if let Some(pruning) = decided {
+        let verb = match pruning {
+            Pruning::Delete => "Delete",
+            _ => "Archive",
+        };
+        let prompt = match &pruned[..] {
+            [(server, _)] => format!("{} {}?", verb, server.server_name),
+            _ => format!("{} these {} servers?", verb, pruned.len()),
+        };
+        if !yes && !prompt::confirm(&prompt, "--yes")? {
+            return Ok(());
+        }
+    }
+
+    let done = prune::apply(store, profile, pruned)?;
+    if done.archived > 0 {
+        info!("tagged {} servers {}", done.archived, prune::ARCHIVED);
+    }
+    if let Some(file) = done.trash {
+        info!(
+            "removed {} servers; `jump import {}` brings them back",
+            done.deleted,
+            ssh::shell_quote(&file.to_string_lossy())
         );
     }
     Ok(())
 }
 
-fn connect_to_server(conn: Connection, server_name: String) -> Result<()> {
-    let mut stmt = conn
-    .prepare("SELECT server_name, username, server_address, port, method FROM jump_servers where server_name = ?1")?;
-    let server = stmt.query_row([server_name], |row| {
-        let method_string: String = row.get(4)?;
-        Ok(Server {
-            server_name: row.get(0)?,
-            username: row.get(1)?,
-            server_address: row.get(2)?,
-            port: row.get(3)?,
-            method: ConnectMethods::from(method_string),
+/// Set `field` to `value` on the selected servers in one transaction, after
+/// listing the old and new value of each. Servers the field does not apply
+/// to are skipped and reported.
+fn set_field(
+    store: &Store,
+    field: SetField,
+    value: &str,
+    selectors: &[Selector],
+    dry_run: bool,
+    yes: bool,
+) -> Result<()> {
+    let selected = select::select(store.list()?, selectors)?;
+    let mut changed = vec![];
+    for (server, setting) in select::set_field(selected, field, value)? {
+        match setting {
+            Setting::Unchanged => println!("{}: already {}", server.server_name, value),
+            Setting::Changed(old) => {
+                println!("{}: {} → {}", server.server_name, old, value);
+                changed.push(server);
+            }
+            Setting::Skipped(reason) => println!("{}: skipped, {}", server.server_name, reason),
+        }
+    }
+    if dry_run || changed.is_empty() {
+        return Ok(());
+    }
+    store.writable()?;
+    let prompt = match &changed[..] {
+        [server] => format!("Change {}?", server.server_name),
+        _ => format!("Change {} servers?", changed.len()),
+    };
+    if !yes && !prompt::confirm(&prompt, "--yes")? {
+        return Ok(());
+    }
+    store.transaction(|| changed.iter().try_for_each(|server| store.update(server)))?;
+    Ok(())
+}
+
+fn print_json(value: impl serde::Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(&value)?);
+    Ok(())
+}
+
+/// The width to fit tables to, or `None` when stdout is not a terminal
+fn terminal_width() -> Option<usize> {
+    io::stdout()
+        .is_terminal()
+        .then(|| terminal::size().map_or(80, |(columns, _)| usize::from(columns)))
+}
+
+/// Group the servers by the key each last accepted, for finding those that
+/// still need a key about to be rotated
+fn list_key_usage(store: &Store) -> Result<()> {
+    let uses = store.key_uses(None)?;
+    let mut keys: Vec<(&Accepted, Vec<&str>)> = Vec::new();
+    for key_use in &uses {
+        let servers = match keys
+            .iter_mut()
+            .find(|(accepted, _)| accepted.fingerprint == key_use.accepted.fingerprint)
+        {
+            Some((_, servers)) => servers,
+            None => {
+                keys.push((&key_use.accepted, Vec::new()));
+                &mut keys.last_mut().unwrap().1
+            }
+        };
+        servers.push(&key_use.server_name);
+    }
+    for (accepted, servers) in &keys {
+        println!("{}  {}", accepted.fingerprint, accepted.key);
+        println!("    {}", servers.join(", "));
+    }
+    let unknown = store
+        .list()?
+        .into_iter()
+        .filter(|server| {
+            matches!(
+                server.method,
+                ConnectMethods::SSHKey(_) | ConnectMethods::SshConfig(_)
+            ) && !uses
+                .iter()
+                .any(|key_use| key_use.server_name == server.server_name)
+        })
+        .map(|server| server.server_name)
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        println!("not recorded yet");
+        println!("    {}", unknown.join(", "));
+    }
+    Ok(())
+}
+
+/// Probe every server, as many at once as `limits` allow; exits with 1 when
+/// any of them is down
+fn check_servers(
+    servers: Vec<Server>,
+    style: Style,
+    format: OutputFormat,
+    timeout: u64,
+    plain: bool,
+    report: &ReportOptions,
+    limits: Limits,
+) -> Result<()> {
+    let probes = wol::probe_all(&servers, Duration::from_secs(timeout), limits);
+    let up = probes.iter().map(|(up, _)| *up).collect::<Vec<_>>();
+    let hosts = servers
+        .iter()
+        .zip(&probes)
+        .map(|(server, (up, duration))| {
+            let outcome = match up {
+                true => report::Outcome::Ok,
+                false => report::Outcome::Unreachable,
+            };
+            report::Host::new(&server.server_name, outcome, *duration)
         })
+        .collect::<Vec<_>>();
+    report.write("check", &hosts)?;
+    match format {
+        OutputFormat::Json => print_json(
+            servers
+                .iter()
+                .zip(&up)
+                .map(|(server, up)| output::Check {
+                    server_name: &server.server_name,
+                    server_address: &server.server_address,
+                    port: server.port,
+                    up: *up,
+                })
+                .collect::<Vec<_>>(),
+        )?,
+        OutputFormat::Text => print!("{}", output::checks(style, &servers, &up, plain)),
+    }
+    if up.contains(&false) {
+        std::process::exit(exit::FAILURE);
+    }
+    Ok(())
+}
+
+fn recordings(style: Style, format: OutputFormat, opt: RecordingsOpt) -> Result<()> {
+    let recordings = match opt {
+        RecordingsOpt::Ls => record::list()?,
+        RecordingsOpt::Prune { older_than } => record::prune(audit::now() - older_than)?,
+    };
+    if format == OutputFormat::Json {
+        return print_json(recordings);
+    }
+    for recording in recordings {
+        println!(
+            "{}  {:>10}  {}",
+            audit::format_time(recording.modified),
+            recording.size,
+            style.bold(&recording.path.display().to_string())
+        );
+    }
+    Ok(())
+}
+
+fn mount(
+    store: &Store,
+    config: &Config,
+    server_name: &str,
+    remote_path: &str,
+    local_path: &Path,
+    dry_run: bool,
+) -> Result<()> {
+    let server = store.get(server_name)?;
+    let local_path = config::expand_path(local_path)?;
+    let (invocation, password) = mount::invocation(config, &server, remote_path, &local_path)?;
+    if dry_run {
+        println!("{}", invocation);
+        return Ok(());
+    }
+    fs::create_dir_all(&local_path)?;
+    let local_path = local_path.canonicalize()?;
+    if mount::is_mounted(&local_path) {
+        return Err(JumpError::invalid(
+            "mountpoint",
+            format!("something is already mounted on {}", local_path.display()),
+        )
+        .into());
+    }
+    debug!("running {}", invocation);
+    let status = mount::run(&invocation, password.as_deref())?;
+    if !status.success() {
+        return Err(JumpError::CommandFailed {
+            command: "sshfs".to_owned(),
+            message: format!("exited with {}", status),
+        }
+        .into());
+    }
+    store.add_mount(&mount::Mount {
+        server_name: server.server_name,
+        remote_path: remote_path.to_owned(),
+        local_path: local_path.clone(),
+        mounted_at: audit::now(),
     })?;
-    println!("connecting to server...");
-    match server.method {
-        ConnectMethods::Password(Password { password }) => {
-            Command::new("sshpass")
-                .args(vec![
-                    "-p",
-                    &password,
-                    "ssh",
-                    "-p",
-                    &server.port.to_string(),
-                    &format!("{}@{}", server.username, server.server_address),
-                ])
-                .stdin(Stdio::inherit())
-                .stdout(Stdio::inherit())
-                .stderr(Stdio::inherit())
-                .output()?;
-        }
-        ConnectMethods::SSHKey(SSHKey { path }) => {
-            Command::new("ssh")
-                .args(vec![
-                    "-i",
-                    path.to_str().ok_or(anyhow!("Invalid ssh key path"))?,
-                    "-p",
-                    &server.port.to_string(),
-                    &format!("{}@{}", server.username, server.server_address),
-                ])
-                .stdin(Stdio::inherit())
-                .stdout(Stdio::inherit())
-                .stderr(Stdio::inherit())
-                .output()?;
-        }
-    }
-    println!("server disconnected");
+    info!(
+        "mounted {}:{} on {}",
+        server_name,
+        remote_path,
+        local_path.display()
+    );
+    Ok(())
+}
+
+/// The recorded mounts still mounted, dropping the records of the others
+fn live_mounts(store: &Store) -> Result<Vec<mount::Mount>> {
+    let mut live = vec![];
+    for mount in store.mounts()? {
+        if mount::is_mounted(&mount.local_path) {
+            live.push(mount);
+        } else {
+            info!(
+                "forgetting {}, it is no longer mounted",
+                mount.local_path.display()
+            );
+            store.remove_mount(&mount.local_path)?;
+        }
+    }
+    Ok(live)
+}
+
+fn list_mounts(store: &Store, style: Style, format: OutputFormat) -> Result<()> {
+    let mounts = live_mounts(store)?;
+    if format == OutputFormat::Json {
+        return print_json(mounts);
+    }
+    for mount in mounts {
+        println!(
+            "{}:{}  {}  since {} UTC",
+            style.bold(&mount.server_name),
+            mount.remote_path,
+            mount.local_path.display(),
+            audit::format_time(mount.mounted_at)
+        );
+    }
+    Ok(())
+}
+
+/// Unmount the mount at `target`, or every mount of the server it names
+fn umount(store: &Store, target: &str) -> Result<()> {
+    let path = config::expand_path(Path::new(target))?;
+    let path = path.canonicalize().unwrap_or(path);
+    let mounts = store
+        .mounts()?
+        .into_iter()
+        .filter(|mount| mount.local_path == path || mount.server_name == target)
+        .collect::<Vec<_>>();
+    if mounts.is_empty() {
+        return Err(JumpError::invalid(
+            "umount target",
+            format!("jump mounted nothing at or from `{}`", target),
+        )
+        .into());
+    }
+    for mount in mounts {
+        if mount::is_mounted(&mount.local_path) {
+            let mut command = mount::unmount_command(&mount.local_path);
+            let status = command.status().map_err(|source| JumpError::Spawn {
+                program: command.get_program().to_string_lossy().into_owned(),
+                source,
+            })?;
+            if !status.success() {
+                return Err(JumpError::CommandFailed {
+                    command: format!("unmounting {}", mount.local_path.display()),
+                    message: format!("exited with {}", status),
+                }
+                .into());
+            }
+            info!("unmounted {}", mount.local_path.display());
+        }
+        store.remove_mount(&mount.local_path)?;
+    }
+    Ok(())
+}
+
+fn sessions(store: &Store, config: &Config, opt: SessionsOpt) -> Result<()> {
+    let servers = store.list()?;
+    let using = |socket: &Path| -> Result<Vec<&Server>> {
+        let mut using = vec![];
+        for server in &servers {
+            if multiplex::socket_path(server)? == socket {
+                using.push(server);
+            }
+        }
+        Ok(using)
+    };
+    match opt {
+        SessionsOpt::Ls => {
+            for socket in multiplex::sockets()? {
+                let using = using(&socket)?;
+                let destination = multiplex::destination(&using);
+                if !multiplex::control(config, &socket, &destination, "check")? {
+                    debug!("removing {}, its master is gone", socket.display());
+                    fs::remove_file(&socket).ok();
+                    continue;
+                }
+                let names = match using.is_empty() {
+                    true => "-".to_owned(),
+                    false => using
+                        .iter()
+                        .map(|server| &*server.server_name)
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                };
+                println!("{}\t{}\t{}", names, destination, socket.display());
+            }
+        }
+        SessionsOpt::Close {
+            server_name: Some(server_name),
+            ..
+        } => {
+            let server = store.get(&server_name)?;
+            let socket = multiplex::socket_path(&server)?;
+            if !socket.exists() {
+                info!("{} has no shared connection", server_name);
+                return Ok(());
+            }
+            multiplex::control(config, &socket, &ssh::destination(&server), "exit")?;
+            info!("closed the shared connection of {}", server_name);
+        }
+        SessionsOpt::Close { .. } => {
+            let sockets = multiplex::sockets()?;
+            for socket in &sockets {
+                let destination = multiplex::destination(&using(socket)?);
+                multiplex::control(config, socket, &destination, "exit")?;
+            }
+            info!("closed {} shared connections", sockets.len());
+        }
+    }
+    Ok(())
+}
+
+fn server_option(store: &Store, opt: OptionOpt) -> Result<()> {
+    match opt {
+        OptionOpt::Set {
+            server_name,
+            legacy: true,
+            ..
+        } => store.transaction(|| {
+            legacy::BUNDLE
+                .iter()
+                .try_for_each(|(key, value)| store.set_option(&server_name, key, value))
+        })?,
+        OptionOpt::Set {
+            server_name,
+            key,
+            value,
+            ..
+        } => {
+            let key = key.expect("clap requires a key without --legacy");
+            let value = value.expect("clap requires a value without --legacy");
+            store.set_option(&server_name, &key, &value)?
+        }
+        OptionOpt::Unset {
+            server_name,
+            legacy: true,
+            ..
+        } => {
+            let server = store.get(&server_name)?;
+            store.transaction(|| {
+                legacy::settings(&server)
+                    .iter()
+                    .try_for_each(|(key, _)| store.unset_option(&server_name, key))
+            })?
+        }
+        OptionOpt::Unset {
+            server_name, key, ..
+        } => store.unset_option(
+            &server_name,
+            &key.expect("clap requires a key without --legacy"),
+        )?,
+        OptionOpt::Ls { server_name } => {
+            for (key, value) in store.get(&server_name)?.options {
+                println!("{}={}", key, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Put the `--legacy` bundle into `options`, replacing what they had for
+/// the same keys
+fn set_legacy(options: &mut Vec<(String, String)>) {
+    options.retain(|(key, _)| !legacy::is_algorithm_option(key));
+    options.extend(
+        legacy::BUNDLE
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string())),
+    );
+}
+
+fn server_tag(store: &Store, opt: TagOpt) -> Result<()> {
+    match opt {
+        TagOpt::Add { server_name, tags } => store.transaction(|| {
+            tags.iter()
+                .try_for_each(|tag| store.add_tag(&server_name, tag))
+        })?,
+        TagOpt::Rm { server_name, tags } => store.transaction(|| {
+            tags.iter()
+                .try_for_each(|tag| store.remove_tag(&server_name, tag))
+        })?,
+        TagOpt::Ls { server_name } => {
+            for tag in store.get(&server_name)?.tags {
+                println!("{}", tag);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn server_address(store: &Store, opt: AddressOpt) -> Result<()> {
+    match opt {
+        AddressOpt::Add {
+            server_name,
+            address,
+            label,
+        } => store.add_address(&server_name, &Address { address, label })?,
+        AddressOpt::Rm {
+            server_name,
+            address,
+        } => {
+            if !store.remove_address(&server_name, &address)? {
+                return Err(JumpError::invalid(
+                    "address",
+                    format!("{} has no address or label `{}`", server_name, address),
+                )
+                .into());
+            }
+        }
+        AddressOpt::Ls { server_name } => {
+            for address in resolve::addresses(&store.get(&server_name)?) {
+                println!("{}", address);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn server_env(store: &Store, opt: EnvOpt) -> Result<()> {
+    match opt {
+        EnvOpt::Set {
+            server_name,
+            pair: (name, value),
+        } => store.set_env(&server_name, &name, &value)?,
+        EnvOpt::Unset { server_name, name } => store.unset_env(&server_name, &name)?,
+        EnvOpt::Ls { server_name } => {
+            for (name, value) in store.get(&server_name)?.env {
+                println!("{}={}", name, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print what `jump conn` would connect to and run, failing with the
+/// not-found exit code when that cannot be worked out
+fn resolve_command(
+    store: &Store,
+    config: &Config,
+    server_name: &str,
+    format: OutputFormat,
+) -> Result<()> {
+    let (server, invocation) = resolve::for_tools(store, config, server_name)?;
+    let resolved = resolve::resolved(&server, &invocation)?;
+    if format == OutputFormat::Json {
+        return print_json(resolved);
+    }
+    println!("name:     {}", resolved.server_name);
+    println!("user:     {}", resolved.user);
+    println!("address:  {}", resolved.address);
+    println!("port:     {}", resolved.port);
+    println!("method:   {}", resolved.method);
+    if let Some(identity) = &resolved.identity {
+        println!("identity: {}", identity);
+    }
+    if let Some(source) = &resolved.source {
+        println!("source:   {}", source);
+    }
+    if let Some(proxy_jump) = resolved.proxy_jump {
+        println!("jump:     {}", proxy_jump);
+    }
+    println!("command:  {}", invocation);
+    Ok(())
+}
+
+fn fetch_host_key(store: &Store, config: &Config, server_name: &str, timeout: u32) -> Result<()> {
+    let server = store.get(server_name)?;
+    store.writable()?;
+    let key = hostkey::scan(config, &server, timeout)?
+        .into_iter()
+        .next()
+        .expect("scan returns at least one key");
+    let key = key.to_string();
+    match &server.host_key {
+        Some(recorded) if *recorded == key => {}
+        Some(recorded) => warn!("replacing the recorded host key {}", recorded),
+        None => {}
+    }
+    store.set_host_key(&server.server_name, Some(&key))?;
+    println!("{}: {}", server.server_name, key);
+    Ok(())
+}
+
+fn known_hosts_command(store: &Store, profile: &str, opt: KnownHostsOpt) -> Result<()> {
+    let path = known_hosts::path(profile)?;
+    match opt {
+        KnownHostsOpt::Ls => {
+            let servers = store.list()?;
+            for entry in known_hosts::read(&path)? {
+                let names = servers
+                    .iter()
+                    .filter(|server| entry.matches(server))
+                    .map(|server| server.server_name.as_str())
+                    .collect::<Vec<_>>();
+                let names = match (names.is_empty(), entry.is_hashed()) {
+                    (false, _) => names.join(","),
+                    (true, true) => "- (hashed)".to_owned(),
+                    (true, false) => "-".to_owned(),
+                };
+                let key = entry
+                    .key
+                    .map_or("-".to_owned(), |key| key.to_string().replace(' ', "\t"));
+                println!("{}\t{}\t{}", names, entry.hosts.join(","), key);
+            }
+        }
+        KnownHostsOpt::Rm { server_name } => {
+            let server = store.get(&server_name)?;
+            match known_hosts::remove(&path, &server)? {
+                0 => println!("{} has no key in {}", server_name, path.display()),
+                1 => println!("removed the key of {}", server_name),
+                count => println!("removed {} keys of {}", count, server_name),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Report how a `conn` session went, returning the code to exit with. A
+/// failed pre-connect hook exits with its code right away, as does a session
+/// that a signal ended, as ssh did.
+fn session_code(outcome: connect::Outcome, options: &ConnOptions) -> i32 {
+    match outcome {
+        connect::Outcome::DryRun {
+            server,
+            invocation: Some(invocation),
+        } => {
+            println!("{}", invocation);
+            connect::note_identities_only(&server, options, &invocation);
+            exit::SUCCESS
+        }
+        connect::Outcome::DryRun {
+            server,
+            invocation: None,
+        } => {
+            println!("built-in client: {}", ssh::destination(&server));
+            exit::SUCCESS
+        }
+        connect::Outcome::HookFailed { hook, status } => {
+            eprintln!("pre-connect hook `{}` failed with {}", hook, status);
+            std::process::exit(status.code().unwrap_or(exit::FAILURE));
+        }
+        connect::Outcome::Ended {
+            code,
+            failed_hooks,
+            caught,
+        } => {
+            for (hook, status) in failed_hooks {
+                eprintln!("post-connect hook `{}` failed with {}", hook, status);
+            }
+            if let Some(signal) = caught {
+                debug!("exiting after signal {}, as ssh did", signal);
+                std::process::exit(code.unwrap_or(exit::FAILURE));
+            }
+            code.unwrap_or(exit::FAILURE)
+        }
+    }
+}
+
+/// Run this same `jump conn`, without `--new-window`, in a terminal window
+/// of its own; false when no terminal could be found, to connect here instead
+fn open_in_new_window(config: &Config, server_name: &str, dry_run: bool) -> Result<bool> {
+    let mut command = vec![std::env::current_exe()?.to_string_lossy().into_owned()];
+    command.extend(
+        std::env::args_os()
+            .skip(1)
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .filter(|arg| arg != "--new-window" && arg != "--dry-run"),
+    );
+    let Some(terminal) = window::launcher(config, &command)? else {
+        warn!("no terminal emulator found, connecting here; set terminal_command to name one");
+        return Ok(false);
+    };
+    match dry_run {
+        true => println!("{}", terminal),
+        false => {
+            window::spawn_detached(&terminal)?;
+            info!("opened {} in a new window", server_name);
+        }
+    }
+    Ok(true)
+}
+
+/// Open a tmux pane or window for each server tagged `group`, running
+/// `jump conn` there with the same profile
+fn connect_to_group(
+    store: &Store,
+    config: &Config,
+    profile: &str,
+    group: &str,
+    layout: tmux::Layout,
+    sync: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let members = store
+        .list()?
+        .into_iter()
+        .filter(|server| server.tags.iter().any(|tag| tag == group))
+        .collect::<Vec<_>>();
+    if members.is_empty() {
+        return Err(JumpError::NoMatch {
+            selector: format!("tag:{}", group),
+        }
+        .into());
+    }
+    if sync && layout == tmux::Layout::Windows {
+        warn!("--sync only applies to --tmux panes");
+    }
+    let jump = std::env::current_exe()?;
+    let members = members
+        .into_iter()
+        .map(|server| {
+            let command = [
+                &*jump.to_string_lossy(),
+                "--profile",
+                profile,
+                "conn",
+                &server.server_name,
+            ]
+            .map(ssh::shell_quote)
+            .join(" ");
+            (server.server_name, command)
+        })
+        .collect();
+    let group = tmux::Group::new(config, group, members, layout, sync)?;
+    if dry_run {
+        for invocation in group.plan() {
+            println!("{}", invocation);
+        }
+        return Ok(());
+    }
+    Ok(group.open()?)
+}
+
+/// The server `conn` means by `server_name`: the one of that name, or with
+/// `prefix_match`, the only one whose name starts with it. When several do,
+/// they are offered in a menu at a terminal and are an error otherwise, so
+/// that scripts never get a server picked for them.
+fn resolve_name(store: &Store, config: &Config, server_name: String) -> Result<String> {
+    if !config.prefix_match || store.find(&server_name)?.is_some() {
+        return Ok(server_name);
+    }
+    let candidates = store
+        .list()?
+        .into_iter()
+        .filter(|server| server.server_name.starts_with(&server_name))
+        .collect::<Vec<_>>();
+    match &candidates[..] {
+        [] => Err(JumpError::NotFound { name: server_name }.into()),
+        [server] => Ok(server.server_name.clone()),
+        _ if !prompt::interactive() || !io::stderr().is_terminal() => Err(JumpError::Ambiguous {
+            name: server_name,
+            candidates: candidates
+                .into_iter()
+                .map(|server| server.server_name)
+                .collect(),
+        }
+        .into()),
+        _ => match menu::pick(&server_name, &candidates)? {
+            Some(server) => Ok(server.server_name.clone()),
+            None => Err(anyhow::anyhow!("Cancelled")),
+        },
+    }
+}
+
+/// The `--via` hops as ssh takes them, with stored servers given by name
+/// turned into their user, address and port
+fn jump_hosts(store: &Store, hops: &[String]) -> Result<Vec<String>> {
+    hops.iter()
+        .map(|hop| match store.find(hop)? {
+            Some(server) => ssh::parse_hop(&ssh::hop(&server)).map_err(|reason| {
+                JumpError::invalid("--via", format!("{}: {}", server.server_name, reason)).into()
+            }),
+            None => Ok(hop.clone()),
+        })
+        .collect()
+}
+
+/// Run on the one selected server with its exit code, or on several as far
+/// as `fleet.limits` allow, summing up how each went and exiting with 1 when
+/// any of them failed. Without batch mode, or with a tty or the built-in
+/// client, several run one at a time instead, each with the terminal.
+fn exec_on_servers(
+    store: &Store,
+    config: &Config,
+    selector: &Selector,
+    options: &ConnOptions,
+    exec: ssh::ExecOptions,
+    fleet: Fleet,
+    command: &[String],
+) -> Result<()> {
+    let servers = select::select(store.list()?, std::slice::from_ref(selector))?;
+    if !options.dry_run && !confirm_selection(config, &servers, "Run on", fleet.yes)? {
+        return Ok(());
+    }
+    let confirmed = connect::pass_gates(config, fleet.style, &servers, options)?;
+    let lines = fleet.report.report_lines;
+    if let [server] = &servers[..] {
+        let started = Instant::now();
+        let result = exec_or_print(store, config, server, options, exec, confirmed[0], command);
+        let host = exec::outcome(server, started, &result, lines);
+        if !options.dry_run {
+            fleet.report.write("exec", &[host])?;
+        }
+        std::process::exit(result?.0);
+    }
+    if exec.batch.is_some() && !exec.tty && !options.use_native() && !options.dry_run {
+        let total = servers.len();
+        let hosts = exec::parallel(
+            store,
+            config,
+            &servers,
+            options,
+            exec,
+            fleet.limits,
+            lines,
+            command,
+            confirmed,
+            |finished| {
+                let name = &finished.server.server_name;
+                info!("==> {} <== ({}/{})", name, finished.done, total);
+                if let Some(output) = finished.output {
+                    let printed = io::stdout()
+                        .write_all(&output.stdout)
+                        .and_then(|()| io::stderr().write_all(&output.stderr));
+                    if let Err(e) = printed {
+                        warn!("could not print the output of {}: {}", name, e);
+                    }
+                }
+                warn_failed(finished.server, finished.result);
+            },
+        )?;
+        return fleet.finish("exec", &hosts);
+    }
+    let mut hosts = vec![];
+    for (i, (server, confirmed)) in servers.iter().zip(confirmed).enumerate() {
+        info!(
+            "==> {} <== ({}/{})",
+            server.server_name,
+            i + 1,
+            servers.len()
+        );
+        let started = Instant::now();
+        let result = exec_or_print(store, config, server, options, exec, confirmed, command);
+        warn_failed(server, &result);
+        hosts.push(exec::outcome(server, started, &result, lines));
+    }
+    if options.dry_run {
+        return Ok(());
+    }
+    fleet.finish("exec", &hosts)
+}
+
+/// Run on `server`, or on a dry run print what would run
+fn exec_or_print(
+    store: &Store,
+    config: &Config,
+    server: &Server,
+    options: &ConnOptions,
+    exec: ssh::ExecOptions,
+    confirmed: Option<gate::Ack>,
+    command: &[String],
+) -> Result<(i32, String)> {
+    if options.dry_run {
+        println!("{}", exec::dry_run(config, server, options, exec, command)?);
+        return Ok((exit::SUCCESS, String::new()));
+    }
+    exec::on_server(store, config, server, options, exec, confirmed, command)
+}
+
+/// Say how a run on one of several servers failed
+fn warn_failed(server: &Server, result: &Result<(i32, String)>) {
+    match result {
+        Ok((exit::SUCCESS, _)) => {}
+        Ok((code, _)) => warn!("{} exited with {}", server.server_name, code),
+        Err(e) => warn!("{}: {:#}", server.server_name, e),
+    }
+}
+
+/// List the servers a selector resolved to, unless it is just one, then ask
+/// before going on with more than `confirm.servers` of them unless `yes`
+fn confirm_selection(config: &Config, servers: &[Server], action: &str, yes: bool) -> Result<bool> {
+    if servers.len() < 2 {
+        return Ok(true);
+    }
+    let names = servers
+        .iter()
+        .map(|server| &*server.server_name)
+        .collect::<Vec<_>>()
+        .join(", ");
+    info!("{} servers: {}", servers.len(), names);
+    if yes || servers.len() <= config.confirm.servers {
+        return Ok(true);
+    }
+    Ok(prompt::confirm(
+        &format!("{} {} servers?", action, servers.len()),
+        "--yes",
+    )?)
+}
+
+/// What `rekey` moves from and to
+struct Rekey {
+    from: PathBuf,
+    to: PathBuf,
+    continue_on_error: bool,
+    yes: bool,
+}
+
+/// Move the servers `selector` picks among those using `keys.from` to
+/// `keys.to`, printing each result as it comes in. Those left on the old
+/// key, included ones among them, are listed at the end; exits with 1 when
+/// any failed or was skipped.
+fn rekey_servers(
+    store: &Store,
+    config: &Config,
+    style: Style,
+    selector: Option<Selector>,
+    keys: Rekey,
+    options: ConnOptions,
+) -> Result<()> {
+    let steps = rekey::Steps::new(&keys.to)?;
+    let selected = select::select(store.list()?, &Vec::from_iter(selector))?;
+    let (servers, included) = rekey::candidates(selected, &keys.from)?;
+    if servers.is_empty() && included.is_empty() {
+        info!("no server logs in with {}", keys.from.display());
+        return Ok(());
+    }
+    let mut stranded = vec![];
+    for server in &included {
+        warn!(
+            "{} comes from {}, which jump does not change; it stays on the old key",
+            server.server_name,
+            server.source.as_deref().unwrap_or(Path::new("")).display()
+        );
+        stranded.push(server.server_name.clone());
+    }
+    if options.dry_run {
+        for server in &servers {
+            for invocation in steps.invocations(config, server, &options)? {
+                println!("{}", invocation);
+            }
+        }
+        return Ok(());
+    }
+    if !servers.is_empty() {
+        store.writable()?;
+    }
+    if !confirm_selection(config, &servers, "Rekey", keys.yes)? {
+        return Ok(());
+    }
+    let confirmed = connect::pass_gates(config, style, &servers, &options)?;
+    let width = servers
+        .iter()
+        .map(|server| server.server_name.len())
+        .max()
+        .unwrap_or(0);
+    let total = servers.len().to_string();
+    let mut tried = 0;
+    let mut failed = false;
+    rekey::run(
+        store,
+        config,
+        &servers,
+        &options,
+        &steps,
+        keys.continue_on_error,
+        confirmed,
+        |server, rekeyed| {
+            let name = &server.server_name;
+            let padding = " ".repeat(width - name.len());
+            if let rekey::Rekeyed::Skipped = rekeyed {
+                println!(
+                    "{}{}  {}",
+                    style.bold(name),
+                    padding,
+                    style.yellow("skipped")
+                );
+                stranded.push(name.clone());
+                return;
+            }
+            tried += 1;
+            let progress = format!("[{:>width$}/{}]", tried, total, width = total.len());
+            match rekeyed {
+                rekey::Rekeyed::Failed(message) => {
+                    println!(
+                        "{} {}{}  {}\n{}",
+                        progress,
+                        style.bold(name),
+                        padding,
+                        style.red("failed"),
+                        message
+                    );
+                    failed = true;
+                    stranded.push(name.clone());
+                }
+                _ => println!(
+                    "{} {}{}  {}",
+                    progress,
+                    style.bold(name),
+                    padding,
+                    style.green("ok")
+                ),
+            }
+        },
+    );
+    if stranded.is_empty() {
+        info!("every server now logs in with {}", keys.to.display());
+        return Ok(());
+    }
+    warn!("still on {}: {}", keys.from.display(), stranded.join(", "));
+    if failed {
+        std::process::exit(exit::FAILURE);
+    }
+    Ok(())
+}
+
+/// Copy to or from one server, exiting with the exit code of scp or rsync
+#[allow(clippy::too_many_arguments)]
+fn copy_files(
+    store: &Store,
+    config: &Config,
+    style: Style,
+    source: String,
+    destination: String,
+    recursive: bool,
+    resume: bool,
+    options: ConnOptions,
+) -> Result<()> {
+    let server = store.get(&ssh::remote_server_name(&source, &destination)?)?;
+    let invocation = copy::invocation(
+        config,
+        &server,
+        &options,
+        &source,
+        &destination,
+        recursive,
+        resume,
+    )?;
+    if options.dry_run {
+        println!("{}", invocation);
+        connect::note_identities_only(&server, &options, &invocation);
+        return Ok(());
+    }
+    let code = copy::run(store, config, style, &server, &options, &invocation)?;
+    std::process::exit(code.unwrap_or(exit::FAILURE));
+}
+
+/// Upload to every selected server, printing each result as it comes in
+/// with how many are done, then those left out as skipped. Exits with 1 when
+/// any upload failed or was skipped.
+fn copy_to_servers(
+    store: &Store,
+    config: &Config,
+    selector: &Selector,
+    upload: copy::Upload,
+    options: ConnOptions,
+    fleet: Fleet,
+) -> Result<()> {
+    let servers = select::select(store.list()?, std::slice::from_ref(selector))?;
+    if !options.dry_run && !confirm_selection(config, &servers, "Upload to", fleet.yes)? {
+        return Ok(());
+    }
+    if options.dry_run {
+        let uploads = copy::uploads(config, &servers, &options, &upload);
+        for (server, invocation) in servers.iter().zip(uploads) {
+            let invocation = invocation?;
+            println!("{}", invocation);
+            connect::note_identities_only(server, &options, &invocation);
+        }
+        return Ok(());
+    }
+    let confirmed = connect::pass_gates(config, fleet.style, &servers, &options)?;
+    let style = fleet.style;
+    let width = servers
+        .iter()
+        .map(|server| server.server_name.len())
+        .max()
+        .unwrap_or(0);
+    let total = servers.len().to_string();
+    let hosts = copy::upload(
+        store,
+        config,
+        &servers,
+        &upload,
+        &options,
+        fleet.limits,
+        fleet.report.report_lines,
+        confirmed,
+        |uploaded| {
+            let name = &uploaded.server.server_name;
+            let progress = format!("[{:>width$}/{}]", uploaded.done, total, width = total.len());
+            let padding = " ".repeat(width - name.len());
+            match uploaded.host.is_ok() {
+                true => println!(
+                    "{} {}{}  {}",
+                    progress,
+                    style.bold(name),
+                    padding,
+                    style.green("ok")
+                ),
+                false => println!(
+                    "{} {}{}  {}\n{}",
+                    progress,
+                    style.bold(name),
+                    padding,
+                    style.red("failed"),
+                    uploaded.message.trim_end()
+                ),
+            }
+        },
+    );
+    for host in &hosts {
+        if host.outcome == report::Outcome::Skipped {
+            let padding = " ".repeat(width - host.server_name.len());
+            println!(
+                "{}{}  {}",
+                style.bold(&host.server_name),
+                padding,
+                style.yellow("skipped")
+            );
+        }
+    }
+    fleet.finish("cp", &hosts)
+}
+
+/// How a command across many servers asks first and sums up at the end
+struct Fleet {
+    yes: bool,
+    report: ReportOptions,
+    style: Style,
+    limits: Limits,
+}
+
+impl Fleet {
+    /// Write the report, print the summary when there was more than one
+    /// server, and exit with 1 when any of them did not go well
+    fn finish(&self, command: &str, hosts: &[report::Host]) -> Result<()> {
+        self.report.write(command, hosts)?;
+        if hosts.len() > 1 {
+            info!("{}", report::summary(self.style, hosts).trim_end());
+        }
+        if !hosts.iter().all(report::Host::is_ok) {
+            std::process::exit(exit::FAILURE);
+        }
+        Ok(())
+    }
+}
+
+fn config_command(config: Result<Config>, opt: ConfigOpt) -> Result<()> {
+    match opt {
+        ConfigOpt::Get { key } => match config?.get(&key)? {
+            toml::Value::String(value) => println!("{}", value),
+            value => println!("{}", value),
+        },
+        ConfigOpt::Set { key, value } => Config::set(&key, &value)?,
+        ConfigOpt::Path => println!("{}", Config::path()?.display()),
+        ConfigOpt::Edit => Config::edit()?,
+        ConfigOpt::TestDefaults { address, tags } => {
+            let resolved = defaults::resolve(&config?, &address, &tags);
+            match resolved.matched.is_empty() {
+                true => println!("no rule matches {}", address),
+                false => println!("rules:    {}", resolved.matched.join(", ")),
+            }
+            let from = |rule: Option<String>, fallback: &str| match rule {
+                Some(rule) => format!("[defaults.\"{}\"]", rule),
+                None => fallback.to_owned(),
+            };
+            match resolved.user {
+                Some(user) => println!("user:     {}  {}", user.value, from(user.rule, "")),
+                None => println!("user:     none, it has to be given"),
+            }
+            println!(
+                "port:     {}  {}",
+                resolved.port.value,
+                from(resolved.port.rule, "default_port")
+            );
+            println!(
+                "identity: {}  {}",
+                resolved.identity.value,
+                from(resolved.identity.rule, "default_identity")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// `jump [--profile <profile>] __complete-servers`, recognised ahead of clap
+/// so the helper stays out of `--help` and the generated scripts
+fn complete_servers_request() -> Option<Option<String>> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["__complete-servers"] => Some(None),
+        ["--profile", profile, "__complete-servers"] => Some(Some(profile.to_string())),
+        _ => None,
+    }
+}
+
+/// Print server names one per line, and nothing at all when there is no
+/// store yet, so that completion never fails in a fresh shell
+fn complete_servers(config: Option<Config>, profile: Option<String>) {
+    let config = config.unwrap_or_default();
+    let profile = profile.unwrap_or(config.default_profile);
+    let Ok(path) = store_path(&profile) else {
+        return;
+    };
+    if !path.exists() {
+        return;
+    }
+    let names = Store::open(&path).and_then(|store| match store.is_initialized()? {
+        true => store.names(),
+        false => Ok(vec![]),
+    });
+    for name in names.unwrap_or_default() {
+        println!("{}", name);
+    }
+}
+
+/// Write the completion script to stdout and where to put it to stderr
+fn completions(config: &Config, shell: Shell) -> Result<()> {
+    io::stdout().write_all(completion_script(config, shell)?.as_bytes())?;
+    let install = match shell {
+        Shell::Bash => "jump completions bash > ~/.local/share/bash-completion/completions/jump",
+        Shell::Zsh => "jump completions zsh > \"${fpath[1]}/_jump\"",
+        Shell::Fish => "jump completions fish > ~/.config/fish/completions/jump.fish",
+        Shell::PowerShell => "jump completions powershell >> $PROFILE",
+        Shell::Elvish => "jump completions elvish >> ~/.config/elvish/rc.elv",
+        _ => return Ok(()),
+    };
+    info!("To install, run: {}", install);
+    Ok(())
+}
+
+fn completion_script(config: &Config, shell: Shell) -> Result<String> {
+    let mut script = vec![];
+    clap_complete::generate(shell, &mut cli(config), "jump", &mut script);
+    Ok(complete::with_server_names(
+        shell,
+        String::from_utf8(script)?,
+    ))
+}
+
+fn export(
+    store: &Store,
+    server_names: Vec<String>,
+    encrypted: Option<PathBuf>,
+    passphrase_prompt: bool,
+    include_secrets: bool,
+    out: Option<PathBuf>,
+) -> Result<()> {
+    let servers = named_servers(store, &server_names)?;
+    let count = servers.len();
+    let json = Bundle::new(servers, include_secrets).to_json()?;
+    match encrypted {
+        Some(path) => {
+            let passphrase = passphrase(passphrase_prompt, true)?;
+            fs::write(&path, bundle::encrypt(&json, &passphrase)?)?;
+            info!("exported {} servers to {}", count, path.display());
+        }
+        None => {
+            if include_secrets {
+                warn!("the bundle holds passwords in plain text");
+            }
+            write_out(&json, out.as_deref(), count)?;
+        }
+    }
+    Ok(())
+}
+
+/// The named servers, or the whole store when there are no names
+fn named_servers(store: &Store, server_names: &[String]) -> Result<Vec<Server>> {
+    Ok(match server_names.is_empty() {
+        true => store.list()?,
+        false => server_names
+            .iter()
+            .map(|name| store.get(name))
+            .collect::<error::Result<_>>()?,
+    })
+}
+
+/// Write an export to `path`, or to stdout when there is none
+fn write_out(export: &[u8], path: Option<&Path>, count: usize) -> Result<()> {
+    match path {
+        Some(path) => {
+            fs::write(path, [export, b"\n"].concat())?;
+            info!("exported {} servers to {}", count, path.display());
+        }
+        None => {
+            io::stdout().write_all(export)?;
+            println!();
+        }
+    }
+    Ok(())
+}
+
+/// The bundle passphrase from `JUMP_PASSPHRASE`, or typed in when it is unset
+/// or `prompt` is given; `confirm` asks for it twice
+fn passphrase(prompt: bool, confirm: bool) -> Result<String> {
+    let passphrase = match std::env::var("JUMP_PASSPHRASE") {
+        Ok(passphrase) if !prompt => passphrase,
+        _ => {
+            let instead = "set JUMP_PASSPHRASE, without --passphrase-prompt";
+            let passphrase = prompt::secret("Passphrase: ", "the bundle passphrase", instead)?;
+            if confirm
+                && prompt::secret("Repeat passphrase: ", "the bundle passphrase", instead)?
+                    != passphrase
+            {
+                return Err(JumpError::invalid("passphrase", "the two typed do not match").into());
+            }
+            passphrase
+        }
+    };
+    if passphrase.is_empty() {
+        return Err(JumpError::invalid("passphrase", "it must not be empty").into());
+    }
+    Ok(passphrase)
+}
+
+fn edit_store(store: &Store, include_secrets: bool, yes: bool) -> Result<()> {
+    store.writable()?;
+    prompt::allow(
+        "for the servers to be edited in $EDITOR",
+        "change them with `jump set` or `jump import` instead",
+    )?;
+    let changes = match edit::edit(&store.list_stored()?, include_secrets)? {
+        Some(changes) if !changes.is_empty() => changes,
+        _ => {
+            info!("no changes");
+            return Ok(());
+        }
+    };
+    for change in &changes {
+        println!("{}", change);
+    }
+    if !yes && !prompt::confirm("Apply these changes?", "--yes")? {
+        return Ok(());
+    }
+    edit::apply(store, changes)?;
+    Ok(())
+}
+
+fn import(
+    store: &Store,
+    file: &Path,
+    on_conflict: Conflict,
+    dry_run: bool,
+    yes: bool,
+) -> Result<()> {
+    if !dry_run {
+        store.writable()?;
+    }
+    let data = fs::read(file)?;
+    let passphrase = match bundle::is_encrypted(&data) {
+        true => Some(passphrase(false, false)?),
+        false => None,
+    };
+    let bundle = Bundle::read(&data, passphrase.as_deref())?;
+    for server in &bundle.servers {
+        let action = match store.find(&server.server_name)? {
+            Some(_) => on_conflict
+                .to_possible_value()
+                .unwrap()
+                .get_name()
+                .to_owned(),
+            None => "new".to_owned(),
+        };
+        println!(
+            "{:<9} {} ({})",
+            action,
+            server.server_name,
+            ssh::destination(server)
+        );
+    }
+    let without_password = bundle
+        .servers
+        .iter()
+        .filter(|server| matches!(server.method, ConnectMethods::Password(_)))
+        .count();
+    if !bundle.secrets && without_password > 0 {
+        warn!(
+            "the bundle has no passwords; {} password servers keep the one they replace or get none",
+            without_password
+        );
+    }
+    if dry_run || bundle.servers.is_empty() {
+        return Ok(());
+    }
+    if !yes
+        && !prompt::confirm(
+            &format!("Import {} servers?", bundle.servers.len()),
+            "--yes",
+        )?
+    {
+        return Ok(());
+    }
+    for (name, outcome) in bundle::import(store, bundle, on_conflict)? {
+        match outcome {
+            Outcome::Added => info!("imported {}", name),
+            Outcome::Skipped => info!("skipped {}, which already exists", name),
+            Outcome::Replaced => info!("replaced {}", name),
+            Outcome::Renamed(new_name) => info!("imported {} as {}", name, new_name),
+        }
+    }
+    Ok(())
+}
+
+fn sync_store(store: &Store, config: &Config, profile: &str, opt: SyncOpt) -> Result<()> {
+    match opt {
+        SyncOpt::Init { repo } => match sync::init(config, store, &repo, profile)? {
+            sync::Init::Created => info!("servers committed to {}", repo),
+            sync::Init::Existing => info!(
+                "{} already holds servers, run `jump sync pull` to merge them",
+                repo
+            ),
+        },
+        SyncOpt::Push => match sync::push(config, store)? {
+            true => info!("servers pushed"),
+            false => info!("servers committed; the repository has no remote"),
+        },
+        SyncOpt::Pull { ours, theirs } => {
+            let resolution = match (ours, theirs) {
+                (true, _) => Some(Resolution::Ours),
+                (_, true) => Some(Resolution::Theirs),
+                _ => None,
+            };
+            let changes = sync::pull(config, store, resolution)?;
+            let mut conflicts = 0;
+            for change in &changes {
+                match change {
+                    Change::Take {
+                        name, server: None, ..
+                    } => info!("removed {}", name),
+                    Change::Take {
+                        name,
+                        existed: false,
+                        server: Some(server),
+                    } => match server.pointer("/method/Password").is_some() {
+                        true => info!("added {}, without its password, which is not synced", name),
+                        false => info!("added {}", name),
+                    },
+                    Change::Take { name, .. } => info!("updated {}", name),
+                    Change::Conflict {
+                        name,
+                        local,
+                        remote,
+                    } if resolution.is_none() => {
+                        conflicts += 1;
+                        let detail = match (local, remote) {
+                            (Some(local), Some(remote)) => {
+                                sync::differences(local, remote).join(", ")
+                            }
+                            (None, _) => "removed locally, changed in the repository".to_owned(),
+                            (_, None) => "changed locally, removed in the repository".to_owned(),
+                        };
+                        println!("conflict {}: {}", name, detail);
+                    }
+                    Change::Conflict { name, .. } if ours => info!("kept the local {}", name),
+                    Change::Conflict { name, .. } => info!("took {} from the repository", name),
+                }
+            }
+            if conflicts > 0 {
+                warn!(
+                    "{} conflicts left unsynced; settle them with `jump sync pull --ours` \
+                     or `--theirs`",
+                    conflicts
+                );
+            } else if changes.is_empty() {
+                info!("already up to date");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Create the store when it is missing, or rebuild it with
+/// `force_recreate`, and print where it lives, its schema version, row
+/// counts and what `jump doctor` finds wrong with it
+fn init(
+    path: &Path,
+    profile: &str,
+    force_recreate: bool,
+    from: Option<PathBuf>,
+    yes: bool,
+    read_only: bool,
+) -> Result<()> {
+    let existed = match force_recreate || !path.exists() {
+        true => false,
+        false => Store::open_as_is(path)
+            .and_then(|store| store.is_initialized())
+            .inspect_err(|_| {
+                info!("`jump init --force-recreate` rebuilds a store that cannot be read")
+            })?,
+    };
+    let state = match (force_recreate, existed, read_only) {
+        (true, _, true) | (false, false, true) => return Err(JumpError::ReadOnly.into()),
+        (true, _, false) => match recreate(path, from, yes)? {
+            Some(backup) => format!("recreated, the old one is at {}", backup.display()),
+            None => return Ok(()),
+        },
+        (false, true, _) => "already there".to_owned(),
+        (false, false, false) => {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            Store::open(path)?.initialize()?;
+            // Closed to others, as it may hold passwords
+            doctor::Fix::Chmod(path.to_owned()).apply()?;
+            "created".to_owned()
+        }
+    };
+    let store = match read_only {
+        true => Store::open_read_only(path)?,
+        false => Store::open(path)?,
+    };
+    // Upgrades the schema of a store from an older jump
+    if !read_only {
+        store.initialize()?;
+    }
+    let _cache = CacheRefresh {
+        store: &store,
+        profile,
+    };
+    println!("store:       {} ({})", path.display(), state);
+    println!("schema:      version {}", store.schema_version()?);
+    for (what, count) in store.row_counts()? {
+        println!("{:<12} {}", format!("{}:", what), count);
+    }
+    for finding in doctor::check(path)? {
+        // Those directories are created once they are needed
+        if !matches!(finding.fix, doctor::Fix::CreateDir(_)) {
+            warn!("{}: {}", finding.problem, finding.fix.describe());
+        }
+    }
+    Ok(())
+}
+
+/// Move the store aside and create it anew with the servers it still holds,
+/// or those of the export `from`, once confirmed. The backup, or `None`
+/// when it was not confirmed.
+fn recreate(path: &Path, from: Option<PathBuf>, yes: bool) -> Result<Option<PathBuf>> {
+    let servers = match from {
+        Some(file) => {
+            let data = fs::read(&file)?;
+            let passphrase = match bundle::is_encrypted(&data) {
+                true => Some(passphrase(false, false)?),
+                false => None,
+            };
+            Bundle::read(&data, passphrase.as_deref())?.servers
+        }
+        None => Store::open_as_is(path)
+            .and_then(|store| store.list())
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Unable to read the servers of {}: {}; rebuild it from an export with --from",
+                    path.display(),
+                    e
+                )
+            })?,
+    };
+    let stamp = format!("{}.bak-{}", path.display(), audit::now());
+    // Never over an earlier backup, even one made the same second
+    let backup = std::iter::once(PathBuf::from(&stamp))
+        .chain((2..).map(|n| PathBuf::from(format!("{}-{}", stamp, n))))
+        .find(|backup| !backup.exists())
+        .expect("some backup name is free");
+    let question = format!(
+        "Move {} to {} and create it anew with {} servers?",
+        path.display(),
+        backup.display(),
+        servers.len()
+    );
+    if !yes && !prompt::confirm(&question, "--yes")? {
+        return Ok(None);
+    }
+    if path.exists() {
+        fs::rename(path, &backup)?;
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let store = Store::open(path)?;
+    store.initialize()?;
+    doctor::Fix::Chmod(path.to_owned()).apply()?;
+    store.transaction(|| {
+        for server in &servers {
+            store.add(server)?;
+        }
+        Ok(())
+    })?;
+    Ok(Some(backup))
+}
+
+fn doctor(config: &Config, store_path: &Path, fix: bool, yes: bool, read_only: bool) -> Result<()> {
+    for tool in Tool::ALL {
+        match config::resolve(config, tool) {
+            Ok((path, source)) => println!("{}: {} ({})", tool.name(), path.display(), source),
+            Err(e) => println!("{}: {}", tool.name(), e),
+        }
+    }
+    let findings = doctor::check(store_path)?;
+    if findings.is_empty() {
+        println!("no problems found");
+        return Ok(());
+    }
+    for finding in &findings {
+        println!("{}: {}", finding.problem, finding.fix.describe());
+    }
+    let fixes = findings
+        .iter()
+        .map(|finding| &finding.fix)
+        .filter(|fix| !fix.is_manual())
+        .collect::<Vec<_>>();
+    if fixes.is_empty() {
+        return Ok(());
+    }
+    if !fix {
+        println!("run `jump doctor --fix` to apply the fixes");
+        return Ok(());
+    }
+    apply_fixes(&fixes, yes, read_only)?;
+    Ok(())
+}
+
+/// Apply `fixes` once confirmed, returning whether they were
+fn apply_fixes(fixes: &[&doctor::Fix], yes: bool, read_only: bool) -> Result<bool> {
+    if read_only {
+        return Err(JumpError::ReadOnly.into());
+    }
+    let prompt = match fixes.len() {
+        1 => "Apply this fix?".to_owned(),
+        count => format!("Apply these {} fixes?", count),
+    };
+    if !yes && !prompt::confirm(&prompt, "--yes")? {
+        return Ok(false);
+    }
+    for fix in fixes {
+        fix.apply()?;
+        println!("done: {}", fix.describe());
+    }
+    Ok(true)
+}
+
+fn verify_store(
+    store_path: &Path,
+    style: Style,
+    format: OutputFormat,
+    fix: bool,
+    yes: bool,
+    read_only: bool,
+) -> Result<()> {
+    let mut problems = verify::check(store_path)?;
+    if fix {
+        let fixes = problems
+            .iter()
+            .filter_map(|problem| problem.fix.as_ref())
+            .filter(|fix| !fix.is_manual())
+            .collect::<Vec<_>>();
+        if !fixes.is_empty() && apply_fixes(&fixes, yes, read_only)? {
+            problems = verify::check(store_path)?;
+        }
+    }
+    match format {
+        OutputFormat::Json => print_json(
+            problems
+                .iter()
+                .map(|problem| output::Problem {
+                    server_name: problem.server_name.as_deref(),
+                    severity: problem.severity,
+                    problem: &problem.problem,
+                    fix: problem.fix.as_ref().map(|fix| fix.describe()),
+                })
+                .collect::<Vec<_>>(),
+        )?,
+        OutputFormat::Text => print!("{}", output::problems(style, &problems)),
+    }
+    if problems
+        .iter()
+        .any(|problem| problem.severity == Severity::Error)
+    {
+        std::process::exit(exit::FAILURE);
+    }
+    Ok(())
+}
+
+fn audit_secrets(
+    paths: &[PathBuf],
+    style: Style,
+    format: OutputFormat,
+    fix: bool,
+    yes: bool,
+) -> Result<()> {
+    let mut findings = leaks::check(paths)?;
+    if fix {
+        let fixes = findings
+            .iter()
+            .map(|finding| &finding.fix)
+            .filter(|fix| !fix.is_manual())
+            .collect::<Vec<_>>();
+        // Only files of the user's own are changed, so --read-only does not apply
+        if !fixes.is_empty() && apply_fixes(&fixes, yes, false)? {
+            findings = leaks::check(paths)?;
+        }
+    }
+    match format {
+        OutputFormat::Json => print_json(
+            findings
+                .iter()
+                .map(|finding| output::Leak {
+                    severity: finding.severity,
+                    problem: &finding.problem,
+                    fix: finding.fix.describe(),
+                })
+                .collect::<Vec<_>>(),
+        )?,
+        OutputFormat::Text if findings.is_empty() => println!("no leaks found"),
+        OutputFormat::Text => {
+            for finding in &findings {
+                let severity = match finding.severity {
+                    Severity::Error => style.red("error"),
+                    Severity::Warning => style.yellow("warning"),
+                };
+                println!(
+                    "{}: {} ({})",
+                    severity,
+                    finding.problem,
+                    finding.fix.describe()
+                );
+            }
+            if !fix && findings.iter().any(|finding| !finding.fix.is_manual()) {
+                println!("run `jump audit-secrets --fix` to apply the fixes");
+            }
+        }
+    }
+    if findings
+        .iter()
+        .any(|finding| finding.severity == Severity::Error)
+    {
+        std::process::exit(exit::FAILURE);
+    }
     Ok(())
 }
@@ -0,0 +1,82 @@
+//! `jump export --markdown`: the servers as a GitHub-flavored table for
+//! runbooks and wikis, sorted by name so that regenerating it gives a small
+//! diff. Secrets never go into it; the method column only names the kind.
+
+use clap::ValueEnum;
+
+use crate::{audit, server::Server};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Column {
+    Name,
+    User,
+    Address,
+    Port,
+    Method,
+    Tags,
+    Notes,
+}
+
+impl Column {
+    pub const ALL: [Column; 7] = [
+        Column::Name,
+        Column::User,
+        Column::Address,
+        Column::Port,
+        Column::Method,
+        Column::Tags,
+        Column::Notes,
+    ];
+
+    fn title(&self) -> &'static str {
+        match self {
+            Column::Name => "Name",
+            Column::User => "User",
+            Column::Address => "Address",
+            Column::Port => "Port",
+            Column::Method => "Method",
+            Column::Tags => "Tags",
+            Column::Notes => "Notes",
+        }
+    }
+
+    fn value(&self, server: &Server) -> String {
+        match self {
+            Column::Name => server.server_name.clone(),
+            Column::User => server.username.clone(),
+            Column::Address => server.server_address.clone(),
+            Column::Port => server.port.to_string(),
+            Column::Method => audit::method_name(&server.method).to_owned(),
+            Column::Tags => server.tags.join(", "),
+            Column::Notes => server.note.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// The table of `servers` with `columns`, in that order
+pub fn table(servers: &[Server], columns: &[Column]) -> String {
+    let mut servers = servers.iter().collect::<Vec<_>>();
+    servers.sort_by(|a, b| a.server_name.cmp(&b.server_name));
+    let row = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+    let mut table = row(columns
+        .iter()
+        .map(|column| column.title().to_owned())
+        .collect());
+    table.push_str(&row(columns.iter().map(|_| "---".to_owned()).collect()));
+    for server in servers {
+        table.push_str(&row(columns
+            .iter()
+            .map(|column| escape(&column.value(server)))
+            .collect()));
+    }
+    table
+}
+
+/// A cell's text with pipes escaped and line breaks kept as `<br>`, which
+/// would otherwise end the cell or the row
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
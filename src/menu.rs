@@ -0,0 +1,104 @@
+//! The numbered menu `conn` shows when the start of a name matches several
+//! servers. It is drawn on stderr, so stdout stays free for the session.
+
+use std::io::{self, Write};
+
+use ratatui::crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal,
+};
+
+use crate::server::Server;
+
+/// Let the person at the terminal pick one of `servers`, whose names all
+/// start with `typed`. A number picks that entry; anything else is added to
+/// `typed` to narrow the list down. `None` when Esc or Ctrl-C cancels.
+pub fn pick<'a>(typed: &str, servers: &'a [Server]) -> io::Result<Option<&'a Server>> {
+    terminal::enable_raw_mode()?;
+    let picked = run(typed, servers);
+    terminal::disable_raw_mode()?;
+    eprintln!();
+    picked
+}
+
+fn run<'a>(typed: &str, servers: &'a [Server]) -> io::Result<Option<&'a Server>> {
+    let mut err = io::stderr();
+    let mut typed = typed.to_owned();
+    let mut input = String::new();
+    let mut shown = servers.iter().collect::<Vec<_>>();
+    show(&mut err, &shown)?;
+    loop {
+        write!(
+            err,
+            "\r\x1b[KNumber, or more of the name: {}{}",
+            typed, input
+        )?;
+        err.flush()?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Enter => {
+                if let Some(server) = input
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|number| shown.get(number.wrapping_sub(1)))
+                {
+                    return Ok(Some(server));
+                }
+                let refined = format!("{}{}", typed, input);
+                if let Some(server) = shown.iter().find(|s| s.server_name == refined) {
+                    return Ok(Some(server));
+                }
+                let matching = shown
+                    .iter()
+                    .copied()
+                    .filter(|server| server.server_name.starts_with(&refined))
+                    .collect::<Vec<_>>();
+                match &matching[..] {
+                    [] => write!(err, "\r\x1b[KNo server starts with {}\r\n", refined)?,
+                    [server] => return Ok(Some(server)),
+                    _ => {
+                        write!(err, "\r\x1b[K")?;
+                        show(&mut err, &matching)?;
+                        shown = matching;
+                        typed = refined;
+                    }
+                }
+                input.clear();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// One numbered line per server with where it points; raw mode needs the
+/// explicit carriage returns
+fn show(err: &mut impl Write, servers: &[&Server]) -> io::Result<()> {
+    let width = servers
+        .iter()
+        .map(|server| server.server_name.len())
+        .max()
+        .unwrap_or(0);
+    for (number, server) in (1..).zip(servers) {
+        write!(
+            err,
+            "{:>3}) {:width$}  {}@{}\r\n",
+            number,
+            server.server_name,
+            server.username,
+            server.server_address,
+            width = width
+        )?;
+    }
+    Ok(())
+}
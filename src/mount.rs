@@ -0,0 +1,178 @@
+//! `jump mount`: a remote directory mounted locally through sshfs. Active
+//! mounts are recorded in the store so `jump mount --list` and `jump umount`
+//! can find them again; records of mounts that went away are dropped.
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::{self, Config, Tool},
+    error::{JumpError, Result},
+    known_hosts, proxy,
+    server::{ConnectMethods, Password, SSHKey, Server},
+    ssh::{self, Invocation},
+};
+
+/// Where sshfs usually comes from, for when it is missing
+const SSHFS_HINT: &str = "install it with `apt install sshfs`, `dnf install fuse-sshfs` or \
+                          `brew install macfuse gromgit/fuse/sshfs-mac`";
+
+/// A remote directory jump mounted
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Mount {
+    pub server_name: String,
+    pub remote_path: String,
+    pub local_path: PathBuf,
+    /// Seconds since the Unix epoch
+    pub mounted_at: i64,
+}
+
+/// The sshfs command mounting `remote_path` of `server` on `local_path`,
+/// and the password to feed it on stdin for password servers
+pub fn invocation(
+    config: &Config,
+    server: &Server,
+    remote_path: &str,
+    local_path: &Path,
+) -> Result<(Invocation, Option<String>)> {
+    let (program, _) = config::resolve(config, Tool::Sshfs).map_err(|e| match e {
+        JumpError::ToolNotFound { tool, location } => JumpError::ToolNotFound {
+            tool,
+            location: format!("{}; {}", location, SSHFS_HINT),
+        },
+        e => e,
+    })?;
+    let mut args = match server.ssh_config_alias() {
+        Some(_) => vec![],
+        None => vec!["-p".to_owned(), server.port.to_string()],
+    };
+    let mut option = |value: String| {
+        args.push("-o".to_owned());
+        args.push(value);
+    };
+    let password = match &server.method {
+        ConnectMethods::SSHKey(SSHKey { path }) => {
+            option(format!(
+                "IdentityFile={}",
+                config::expand_path(path)?.display()
+            ));
+            None
+        }
+        ConnectMethods::Password(Password { password }) => {
+            option("password_stdin".to_owned());
+            Some(password.clone())
+        }
+        ConnectMethods::Command(_) => {
+            return Err(JumpError::invalid(
+                "method",
+                "sshfs needs ssh, which servers with a custom command are not reached by",
+            ))
+        }
+        ConnectMethods::SshConfig(_) => None,
+        ConnectMethods::Unsupported(_) => {
+            return Err(JumpError::UnsupportedMethod {
+                name: server.server_name.clone(),
+            })
+        }
+    };
+    if let Some(proxy) = proxy::resolve(config, server, None)? {
+        option(format!("ProxyCommand={}", proxy.command(config)?));
+    }
+    for (key, value) in &server.options {
+        option(format!("{}={}", key, value));
+    }
+    let stored = |key: &str| {
+        server
+            .options
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case(key))
+    };
+    if matches!(server.method, ConnectMethods::SSHKey(_))
+        && server.identities_only
+        && !stored("IdentitiesOnly")
+    {
+        option(ssh::IDENTITIES_ONLY.to_owned());
+    }
+    for value in known_hosts::options(config)? {
+        option(value);
+    }
+    let destination = match (
+        server.ssh_config_alias(),
+        server.server_address.contains(':'),
+    ) {
+        (Some(alias), _) => alias.to_owned(),
+        (None, true) => format!("{}@[{}]", server.username, server.server_address),
+        (None, false) => format!("{}@{}", server.username, server.server_address),
+    };
+    args.push(ssh::END_OF_OPTIONS.to_owned());
+    args.push(format!("{}:{}", destination, remote_path));
+    args.push(local_path.to_string_lossy().into_owned());
+    let invocation = Invocation {
+        program,
+        args,
+        secret: None,
+        env: vec![],
+    };
+    Ok((invocation, password))
+}
+
+/// Run sshfs, writing `password` to its stdin when there is one
+pub fn run(invocation: &Invocation, password: Option<&str>) -> Result<ExitStatus> {
+    let mut command = invocation.command();
+    if password.is_some() {
+        command.stdin(Stdio::piped());
+    }
+    let mut child = command.spawn().map_err(|source| JumpError::Spawn {
+        program: invocation.program.display().to_string(),
+        source,
+    })?;
+    if let (Some(password), Some(mut stdin)) = (password, child.stdin.take()) {
+        writeln!(stdin, "{}", password)?;
+    }
+    Ok(child.wait()?)
+}
+
+/// Whether something is mounted on `path`: it is on another device than its
+/// parent, or cannot even be looked at, as when sshfs died under it
+#[cfg(unix)]
+pub fn is_mounted(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    let Some(parent) = path.parent() else {
+        return false;
+    };
+    match (fs::metadata(path), fs::metadata(parent)) {
+        (Ok(mount), Ok(parent)) => mount.dev() != parent.dev(),
+        (Err(e), Ok(_)) => e.kind() != std::io::ErrorKind::NotFound,
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn is_mounted(path: &Path) -> bool {
+    path.exists()
+}
+
+/// The command unmounting `local_path`: `fusermount -u` where FUSE has one,
+/// `umount` elsewhere
+pub fn unmount_command(local_path: &Path) -> Command {
+    let fusermount = ["fusermount3", "fusermount"]
+        .iter()
+        .find_map(|name| config::search_path(name));
+    let mut command = match fusermount {
+        Some(fusermount) => {
+            let mut command = Command::new(fusermount);
+            command.arg("-u");
+            command
+        }
+        None => Command::new("umount"),
+    };
+    command.arg(local_path);
+    command
+}
@@ -0,0 +1,200 @@
+//! Connection sharing through ssh control sockets. With `multiplex` on, the
+//! first connection to a server becomes a master that lingers for
+//! `CONTROL_PERSIST`, and later `conn`, `exec` and `cp` calls ride on it
+//! instead of doing a handshake of their own.
+//!
+//! Sockets live in `~/.jump/sockets`, named by a hash of the endpoint so that
+//! the path stays under the unix socket limit whatever the server is called.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::{self, Config, Tool},
+    debug,
+    error::{JumpError, Result},
+    server::Server,
+    ssh,
+};
+
+/// How long a master outlives its last session
+pub const CONTROL_PERSIST: &str = "10m";
+
+/// Hex digits of the endpoint hash a socket is named by
+const NAME_LEN: usize = 16;
+
+/// `sun_path` holds 104 bytes on macOS and the BSDs, 108 on Linux; ssh
+/// binds a temporary name, the socket path plus a dot and 16 random
+/// characters, before renaming it into place
+const MAX_SOCKET_PATH: usize = 104 - 1 - 17;
+
+/// Whether connections to `server` are shared
+pub fn enabled(config: &Config, server: &Server) -> bool {
+    cfg!(unix) && (config.multiplex || server.multiplex)
+}
+
+/// The `-o` options sharing connections to `server`, creating the socket
+/// directory if needed. Empty when ssh is too old to keep a master around
+/// on its own, as connections are then made the usual way.
+pub fn args(config: &Config, server: &Server) -> Result<Vec<String>> {
+    if !persists(config) {
+        return Ok(vec![]);
+    }
+    let path = socket_path(server)?;
+    if let Some(dir) = path.parent() {
+        create_dir(dir)?;
+    }
+    let path = path
+        .to_str()
+        .ok_or(JumpError::invalid("socket directory", "not valid UTF-8"))?;
+    Ok([
+        "-o",
+        "ControlMaster=auto",
+        "-o",
+        &format!("ControlPersist={}", CONTROL_PERSIST),
+        "-o",
+        &format!("ControlPath={}", path),
+    ]
+    .map(str::to_owned)
+    .to_vec())
+}
+
+/// Where the control socket of `server` goes: named by its user, address
+/// and port, so servers sharing an endpoint share a master
+pub fn socket_path(server: &Server) -> Result<PathBuf> {
+    let endpoint = format!(
+        "{}@{}:{}",
+        server.username, server.server_address, server.port
+    );
+    Ok(socket_dir()?.join(&hash(&endpoint)[..NAME_LEN]))
+}
+
+/// `~/.jump/sockets`, or when that is too long a path for the sockets in it,
+/// a directory in `/tmp` named after it
+pub fn socket_dir() -> Result<PathBuf> {
+    let dir = config::jump_dir()?.join("sockets");
+    if dir.as_os_str().len() + 1 + NAME_LEN <= MAX_SOCKET_PATH {
+        return Ok(dir);
+    }
+    let short = format!("jump-{}", &hash(&dir.to_string_lossy())[..8]);
+    Ok(Path::new("/tmp").join(short))
+}
+
+/// The control sockets in the socket directory
+pub fn sockets() -> Result<Vec<PathBuf>> {
+    let dir = socket_dir()?;
+    let mut sockets = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>(),
+        Err(_) => vec![],
+    };
+    sockets.sort();
+    Ok(sockets)
+}
+
+/// `ssh -O <command>` against the master behind `socket`; `destination` is
+/// only there because ssh wants one
+pub fn control(config: &Config, socket: &Path, destination: &str, command: &str) -> Result<bool> {
+    let (ssh, _) = config::resolve(config, Tool::Ssh)?;
+    let status = Command::new(&ssh)
+        .arg("-o")
+        .arg(format!("ControlPath={}", socket.display()))
+        .args(["-O", command, ssh::END_OF_OPTIONS, destination])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|source| JumpError::Spawn {
+            program: ssh.display().to_string(),
+            source,
+        })?;
+    Ok(status.success())
+}
+
+/// The destination to hand `ssh -O` for the servers using `socket`
+pub fn destination(servers: &[&Server]) -> String {
+    servers
+        .first()
+        .map(|server| ssh::destination(server))
+        .unwrap_or("jump".to_owned())
+}
+
+/// Whether ssh has `ControlPersist`, added in OpenSSH 5.6; asked once
+fn persists(config: &Config) -> bool {
+    static PERSISTS: OnceLock<bool> = OnceLock::new();
+    *PERSISTS.get_or_init(|| {
+        let version = config::resolve(config, Tool::Ssh)
+            .ok()
+            .and_then(|(ssh, _)| Command::new(ssh).arg("-V").output().ok())
+            .map(|output| String::from_utf8_lossy(&output.stderr).into_owned())
+            .unwrap_or_default();
+        let persists = openssh_version(&version).is_some_and(|version| version >= (5, 6));
+        if !persists {
+            debug!(
+                "not sharing connections, `{}` has no ControlPersist",
+                version.trim()
+            );
+        }
+        persists
+    })
+}
+
+/// The major and minor version in `ssh -V` output, such as
+/// `OpenSSH_9.6p1 Ubuntu-3ubuntu13, OpenSSL 3.0.13`
+pub fn openssh_version(text: &str) -> Option<(u32, u32)> {
+    let rest = text.trim().strip_prefix("OpenSSH_")?;
+    let (major, rest) = rest.split_once('.')?;
+    let minor = rest
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect::<String>();
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// Create the socket directory readable by nobody else, refusing one that
+/// someone else already made
+#[cfg(unix)]
+fn create_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+    if !dir.exists() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+    }
+    let metadata = fs::metadata(dir)?;
+    let own = fs::metadata(config::jump_dir()?)
+        .map(|jump_dir| jump_dir.uid())
+        .unwrap_or(metadata.uid());
+    if metadata.uid() != own || metadata.permissions().mode() & 0o077 != 0 {
+        return Err(JumpError::invalid(
+            "socket directory",
+            format!(
+                "{} must belong to you and be closed to others (chmod 700)",
+                dir.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn create_dir(dir: &Path) -> Result<()> {
+    Ok(fs::create_dir_all(dir)?)
+}
+
+fn hash(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
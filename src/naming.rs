@@ -0,0 +1,68 @@
+//! Names for servers added without one, by `add --auto-name` and `add
+//! --uri`: the short hostname, so that adding many hosts needs no naming.
+
+use std::{net::IpAddr, process::Command};
+
+use crate::{
+    config::{self, Config, Tool},
+    debug,
+    error::Result,
+};
+
+/// The name of a server at `address`: the first label of a hostname, the
+/// first label of the name `reverse` finds for an IP address, or else the
+/// IP address itself with `:` and `%` as `-`
+pub fn derive(address: &str, reverse: impl Fn(IpAddr) -> Option<String>) -> String {
+    let address = address.trim_start_matches('[').trim_end_matches(']');
+    let ip = address
+        .split('%')
+        .next()
+        .and_then(|ip| ip.parse::<IpAddr>().ok());
+    match ip {
+        Some(ip) => match reverse(ip) {
+            Some(name) if name.parse::<IpAddr>().is_err() => short(&name).to_owned(),
+            _ => address.replace([':', '%'], "-"),
+        },
+        None => short(address).to_owned(),
+    }
+}
+
+/// The first label of `host`, unless that leaves nothing
+fn short(host: &str) -> &str {
+    match host.trim_end_matches('.').split('.').next() {
+        Some(label) if !label.is_empty() => label,
+        _ => host,
+    }
+}
+
+/// The canonical name `getent hosts` gives `ip`; `None` when it has none
+/// or getent is not there to ask
+pub fn reverse_lookup(config: &Config, ip: IpAddr) -> Option<String> {
+    let (getent, _) = config::resolve(config, Tool::Getent)
+        .map_err(|e| debug!("no reverse lookup of {}: {}", ip, e))
+        .ok()?;
+    let output = Command::new(getent)
+        .args(["hosts", &ip.to_string()])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // `address name alias...`
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .nth(1)
+        .map(str::to_owned)
+}
+
+/// `base`, or `base-2`, `base-3`, ... for the first one not `taken`
+pub fn free(base: &str, mut taken: impl FnMut(&str) -> Result<bool>) -> Result<String> {
+    let mut name = base.to_owned();
+    for n in 2.. {
+        if !taken(&name)? {
+            break;
+        }
+        name = format!("{}-{}", base, n);
+    }
+    Ok(name)
+}
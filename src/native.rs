@@ -0,0 +1,232 @@
+//! A built-in ssh client for machines without OpenSSH or sshpass, compiled
+//! in with the `native-ssh` feature and selected with `--native`.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use crossterm::terminal;
+use russh::{
+    client,
+    keys::{self, key},
+    ChannelMsg, Disconnect,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{
+    config,
+    error::JumpError,
+    prompt,
+    server::{ConnectMethods, Password, SSHKey, Server},
+};
+
+/// Open an interactive session with a pty, running `command` instead of the
+/// login shell when given. Returns the remote exit status.
+pub fn connect(server: &Server, env: &[(String, String)], command: Option<&str>) -> Result<u32> {
+    runtime()?.block_on(async {
+        let session = authenticate(server).await?;
+        let channel = session.channel_open_session().await?;
+        for (name, value) in env {
+            channel.set_env(false, name, value).await?;
+        }
+        let (cols, rows) = terminal::size()?;
+        let term = std::env::var("TERM").unwrap_or("xterm".to_owned());
+        channel
+            .request_pty(false, &term, cols.into(), rows.into(), 0, 0, &[])
+            .await?;
+        match command {
+            Some(command) => channel.exec(true, command).await?,
+            None => channel.request_shell(true).await?,
+        }
+        terminal::enable_raw_mode()?;
+        let status = bridge(channel, true).await;
+        terminal::disable_raw_mode()?;
+        session
+            .disconnect(Disconnect::ByApplication, "", "English")
+            .await
+            .ok();
+        status
+    })
+}
+
+/// Run `command` without a pty, like `ssh host command`. Returns the remote
+/// exit status.
+pub fn exec(server: &Server, command: &str) -> Result<u32> {
+    runtime()?.block_on(async {
+        let session = authenticate(server).await?;
+        let channel = session.channel_open_session().await?;
+        channel.exec(true, command).await?;
+        let status = bridge(channel, false).await;
+        session
+            .disconnect(Disconnect::ByApplication, "", "English")
+            .await
+            .ok();
+        status
+    })
+}
+
+fn runtime() -> Result<tokio::runtime::Runtime> {
+    Ok(tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?)
+}
+
+async fn authenticate(server: &Server) -> Result<client::Handle<Client>> {
+    let port = u16::try_from(server.port).map_err(|_| anyhow!("Invalid port {}", server.port))?;
+    let handler = Client {
+        host: server.server_address.clone(),
+        port,
+    };
+    let config = Arc::new(client::Config::default());
+    let mut session =
+        client::connect(config, (server.server_address.as_str(), port), handler).await?;
+    let accepted = match &server.method {
+        ConnectMethods::SSHKey(SSHKey { path }) => {
+            let key = load_key(&config::expand_path(path)?)?;
+            session
+                .authenticate_publickey(&server.username, Arc::new(key))
+                .await?
+        }
+        ConnectMethods::Password(Password { password }) => {
+            session
+                .authenticate_password(&server.username, password)
+                .await?
+        }
+        ConnectMethods::Command(_) => {
+            return Err(anyhow!(
+                "{} is connected to with a custom command, which the built-in client cannot run",
+                server.server_name
+            ))
+        }
+        ConnectMethods::SshConfig(_) => {
+            return Err(anyhow!(
+                "{} is connected to through ~/.ssh/config, which the built-in client does not read",
+                server.server_name
+            ))
+        }
+        ConnectMethods::Unsupported(_) => {
+            return Err(JumpError::UnsupportedMethod {
+                name: server.server_name.clone(),
+            }
+            .into())
+        }
+    };
+    if !accepted {
+        return Err(anyhow!(
+            "Authentication as {} on {} failed",
+            server.username,
+            server.server_address
+        ));
+    }
+    Ok(session)
+}
+
+/// Load a private key, asking for the passphrase when it is encrypted
+fn load_key(path: &Path) -> Result<key::KeyPair> {
+    match keys::load_secret_key(path, None) {
+        Err(keys::Error::KeyIsEncrypted) => {
+            let passphrase = prompt::secret(
+                &format!("Enter passphrase for {}: ", path.display()),
+                &format!("the passphrase of {}", path.display()),
+                "use ssh, without --native, with the key loaded into ssh-agent",
+            )?;
+            Ok(keys::load_secret_key(path, Some(&passphrase))?)
+        }
+        key => Ok(key?),
+    }
+}
+
+/// Copy stdin to the channel and the channel to stdout/stderr until the
+/// remote side exits, forwarding terminal size changes when `pty` is set
+async fn bridge(mut channel: russh::Channel<client::Msg>, pty: bool) -> Result<u32> {
+    let mut stdin = tokio::io::stdin();
+    let mut stdout = tokio::io::stdout();
+    let mut stderr = tokio::io::stderr();
+    let mut buf = vec![0; 4096];
+    let mut stdin_closed = false;
+    let mut size = terminal::size().unwrap_or((80, 24));
+    let mut resize = tokio::time::interval(Duration::from_millis(250));
+    let mut status = None;
+    loop {
+        tokio::select! {
+            read = stdin.read(&mut buf), if !stdin_closed => match read? {
+                0 => {
+                    stdin_closed = true;
+                    channel.eof().await?;
+                }
+                n => channel.data(&buf[..n]).await?,
+            },
+            _ = resize.tick(), if pty => {
+                let current = terminal::size().unwrap_or(size);
+                if current != size {
+                    size = current;
+                    channel.window_change(size.0.into(), size.1.into(), 0, 0).await?;
+                }
+            },
+            message = channel.wait() => match message {
+                Some(ChannelMsg::Data { data }) => {
+                    stdout.write_all(&data).await?;
+                    stdout.flush().await?;
+                }
+                Some(ChannelMsg::ExtendedData { data, .. }) => {
+                    stderr.write_all(&data).await?;
+                    stderr.flush().await?;
+                }
+                Some(ChannelMsg::ExitStatus { exit_status }) => status = Some(exit_status),
+                Some(_) => {}
+                None => break,
+            },
+        }
+    }
+    status.ok_or(anyhow!("Connection closed without an exit status"))
+}
+
+/// Checks host keys against `~/.ssh/known_hosts`, the way OpenSSH would
+struct Client {
+    host: String,
+    port: u16,
+}
+
+#[async_trait]
+impl client::Handler for Client {
+    type Error = anyhow::Error;
+
+    async fn check_server_key(&mut self, key: &key::PublicKey) -> Result<bool> {
+        match keys::check_known_hosts(&self.host, self.port, key) {
+            Ok(true) => Ok(true),
+            Ok(false) => {
+                prompt::allow(
+                    &format!("whether to trust the host key of {}", self.host),
+                    "add it to ~/.ssh/known_hosts first",
+                )?;
+                eprint!(
+                    "The authenticity of host {} can't be established.\n\
+                     {} key fingerprint is {}.\n\
+                     Are you sure you want to continue connecting? [y/N] ",
+                    self.host,
+                    key.name(),
+                    key.fingerprint()
+                );
+                io::stderr().flush()?;
+                let mut answer = String::new();
+                io::stdin().read_line(&mut answer)?;
+                if !matches!(answer.trim(), "y" | "Y" | "yes") {
+                    return Ok(false);
+                }
+                keys::learn_known_hosts(&self.host, self.port, key)?;
+                Ok(true)
+            }
+            Err(keys::Error::KeyChanged { line }) => Err(anyhow!(
+                "Host key for {} does not match line {} of known_hosts",
+                self.host,
+                line
+            )),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
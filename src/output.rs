@@ -0,0 +1,822 @@
+//! What commands print: the JSON shapes behind `--output json`, and the
+//! listings and tables people read. Servers in JSON are the serde form of
+//! [`Server`], so a field added there shows up here too.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    access,
+    audit::{self, LogEntry},
+    config::Config,
+    hooks::{post_connect_hooks, pre_connect_hooks},
+    key_usage::KeyUse,
+    knock, legacy,
+    search::{self, Found, Hit},
+    select::Selector,
+    server::{ConnectMethods, Server},
+    style::Style,
+    verify::{self, Severity},
+    wol,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned, possibly colored text for people
+    #[default]
+    Text,
+    /// One JSON document on stdout; failures as `{"error": ...}` on stderr
+    Json,
+}
+
+/// A server as JSON; the password of password servers is left out unless
+/// `include_secrets` is set
+pub fn server(server: &Server, include_secrets: bool) -> Value {
+    let mut value = serde_json::to_value(server).expect("servers always serialize");
+    if !include_secrets {
+        if let Some(password) = value.pointer_mut("/method/Password") {
+            *password = json!({});
+        }
+    }
+    value
+}
+
+/// One heading of `jump ls --tree`; `tag` is null for the untagged servers
+#[derive(Debug, Serialize)]
+pub struct Group<'a> {
+    pub tag: Option<&'a str>,
+    pub servers: Vec<&'a str>,
+}
+
+/// One row of `jump check`
+#[derive(Debug, Serialize)]
+pub struct Check<'a> {
+    pub server_name: &'a str,
+    pub server_address: &'a str,
+    pub port: u32,
+    pub up: bool,
+}
+
+/// One server of `jump recent`
+#[derive(Debug, Serialize)]
+pub struct Recent<'a> {
+    /// What `jump recent --conn` takes to connect to it
+    pub number: usize,
+    pub server_name: &'a str,
+    /// When it was last connected to, in seconds since the epoch
+    pub connected_at: i64,
+}
+
+/// The result of `jump uri`
+#[derive(Debug, Serialize)]
+pub struct Uri<'a> {
+    pub server_name: &'a str,
+    pub uri: &'a str,
+}
+
+/// The result of `jump resolve`: what `jump conn` connects to and runs
+#[derive(Debug, Serialize)]
+pub struct Resolved<'a> {
+    pub server_name: &'a str,
+    pub user: &'a str,
+    pub address: &'a str,
+    pub port: u32,
+    /// `ssh-key`, `password`, `custom` or `ssh-config`
+    pub method: &'a str,
+    /// The key file with `~` expanded, null for other methods
+    pub identity: Option<String>,
+    /// The `ProxyJump` hops, null when connecting directly
+    pub proxy_jump: Option<&'a str>,
+    /// The `include` file the server comes from, null for a stored one
+    pub source: Option<String>,
+    /// The program and its arguments, passwords as `<redacted>`
+    pub argv: Vec<String>,
+    /// Variables set for the program, passwords as `<redacted>`
+    pub env: BTreeMap<String, String>,
+}
+
+/// One problem of `jump verify`; `server_name` is null for the store's own
+#[derive(Debug, Serialize)]
+pub struct Problem<'a> {
+    pub server_name: Option<&'a str>,
+    pub severity: Severity,
+    pub problem: &'a str,
+    pub fix: Option<String>,
+}
+
+/// One finding of `jump audit-secrets`
+#[derive(Debug, Serialize)]
+pub struct Leak<'a> {
+    pub severity: Severity,
+    pub problem: &'a str,
+    pub fix: String,
+}
+
+/// One server of `jump ls --explain`: whether any selector matched it, and
+/// each term of each selector
+pub fn explanation(server: &Server, selectors: &[Selector]) -> Value {
+    let selectors = selectors
+        .iter()
+        .map(|selector| {
+            let terms = selector
+                .terms
+                .iter()
+                .map(|term| {
+                    json!({
+                        "term": term.text,
+                        "matched": term.matches(server),
+                        "value": term.value(server),
+                    })
+                })
+                .collect::<Vec<_>>();
+            json!({ "selector": selector.text, "matched": selector.matches(server), "terms": terms })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "server_name": server.server_name,
+        "matched": selectors.iter().any(|selector| selector["matched"] == true),
+        "selectors": selectors,
+    })
+}
+
+/// One server found by `jump search`, with the fields the words are in
+pub fn found(found: &Found) -> Value {
+    json!({ "server_name": found.server.server_name, "matches": found.hits })
+}
+
+/// What goes to stderr when a command fails
+pub fn error(error: &anyhow::Error) -> Value {
+    json!({ "error": error.to_string() })
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum SortKey {
+    Name,
+    User,
+    Address,
+    Port,
+    /// Most recently connected first
+    Last,
+}
+
+pub fn sort(servers: &mut [Server], key: SortKey, last_connected: &HashMap<String, i64>) {
+    match key {
+        SortKey::Name => servers.sort_by(|a, b| a.server_name.cmp(&b.server_name)),
+        SortKey::User => servers.sort_by(|a, b| a.username.cmp(&b.username)),
+        SortKey::Address => servers.sort_by(|a, b| a.server_address.cmp(&b.server_address)),
+        SortKey::Port => servers.sort_by_key(|server| server.port),
+        SortKey::Last => servers.sort_by_key(|server| {
+            std::cmp::Reverse(last_connected.get(&server.server_name).copied())
+        }),
+    }
+}
+
+/// `jump ls`: a line per server, or with `plain` its tab-separated fields
+pub fn list(style: Style, servers: &[Server], plain: bool, now: i64) -> String {
+    let width = id_width(servers);
+    let mut out = String::new();
+    for server in servers {
+        if plain {
+            let method = match server.method {
+                ConnectMethods::SSHKey(_) => "ssh-key",
+                ConnectMethods::Password(_) => "password",
+                ConnectMethods::Command(_) => "custom",
+                ConnectMethods::SshConfig(_) => "ssh-config",
+                ConnectMethods::Unsupported(_) => "unsupported",
+            };
+            out.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                server.server_name, server.username, server.server_address, server.port, method
+            ));
+            continue;
+        }
+        out.push_str(&format!("{}\n", server_line(style, server, width, now)));
+    }
+    out
+}
+
+/// The id, right-aligned to `id_width`, then the name and where it points,
+/// the include file it comes from and whether it has expired
+fn server_line(style: Style, server: &Server, id_width: usize, now: i64) -> String {
+    let source = match server.source.as_ref().and_then(|source| source.file_name()) {
+        Some(file) => format!(
+            " {}",
+            style.cyan(&format!("(from {})", file.to_string_lossy()))
+        ),
+        None => String::new(),
+    };
+    let expired = match server.expired(now) {
+        true => format!(" {}", style.red("(expired)")),
+        false => String::new(),
+    };
+    format!(
+        "{:>width$}  {} username: {} address: {}{}{}",
+        server.id.unwrap_or_default(),
+        style.bold(&server.server_name),
+        server.username,
+        address_summary(server),
+        source,
+        expired,
+        width = id_width
+    )
+}
+
+/// The server's own address, with how many fallbacks it has
+fn address_summary(server: &Server) -> String {
+    match server.addresses.len() {
+        0 => server.server_address.clone(),
+        n => format!("{} (+{})", server.server_address, n),
+    }
+}
+
+fn id_width(servers: &[Server]) -> usize {
+    servers
+        .iter()
+        .map(|server| server.id.unwrap_or_default().to_string().len())
+        .max()
+        .unwrap_or(0)
+}
+
+/// The servers under each of their tags, sorted by tag, and the untagged
+/// ones last; tags without servers are left out
+pub fn tag_groups(servers: &[Server]) -> Vec<(Option<&str>, Vec<&Server>)> {
+    let mut tagged: BTreeMap<&str, Vec<&Server>> = BTreeMap::new();
+    let mut untagged = vec![];
+    for server in servers {
+        for tag in &server.tags {
+            tagged.entry(tag).or_default().push(server);
+        }
+        if server.tags.is_empty() {
+            untagged.push(server);
+        }
+    }
+    let mut groups = tagged
+        .into_iter()
+        .map(|(tag, members)| (Some(tag), members))
+        .collect::<Vec<_>>();
+    if !untagged.is_empty() {
+        groups.push((None, untagged));
+    }
+    groups
+}
+
+/// `jump ls --tree`: one heading per tag with its count, members marked
+/// when they are in several
+pub fn tree(style: Style, servers: &[Server], now: i64) -> String {
+    let width = id_width(servers);
+    let mut out = String::new();
+    let mut marked = false;
+    for (tag, members) in tag_groups(servers) {
+        let heading = tag.unwrap_or("(ungrouped)");
+        out.push_str(&format!("{} ({})\n", style.bold(heading), members.len()));
+        for server in members {
+            let marker = match server.tags.len() > 1 {
+                true => {
+                    marked = true;
+                    format!(" {}", style.cyan("*"))
+                }
+                false => String::new(),
+            };
+            out.push_str(&format!(
+                "  {}{}\n",
+                server_line(style, server, width, now),
+                marker
+            ));
+        }
+    }
+    if marked {
+        out.push_str(&format!(
+            "{} also listed under another tag\n",
+            style.cyan("*")
+        ));
+    }
+    out
+}
+
+/// `jump ls --long`, fit to `fit` columns; passwords never show, the key is
+/// only its file name
+pub fn long(
+    style: Style,
+    servers: &[Server],
+    last_connected: &HashMap<String, i64>,
+    fit: Option<usize>,
+    now: i64,
+) -> String {
+    let rows = servers
+        .iter()
+        .map(|server| {
+            let (method, key) = match &server.method {
+                ConnectMethods::SSHKey(key) => (
+                    "ssh-key",
+                    key.path
+                        .file_name()
+                        .map_or("-".into(), |name| name.to_string_lossy()),
+                ),
+                ConnectMethods::Password(_) => ("password", "-".into()),
+                ConnectMethods::Command(_) => ("custom", "-".into()),
+                ConnectMethods::SshConfig(_) => ("ssh-config", "-".into()),
+                ConnectMethods::Unsupported(_) => (ConnectMethods::UNSUPPORTED, "-".into()),
+            };
+            let last =
+                last_connected
+                    .get(&server.server_name)
+                    .map_or("never".to_owned(), |&time| {
+                        // To the minute, seconds would only widen the column
+                        audit::format_time(time)[..16].to_owned()
+                    });
+            [
+                server.id.unwrap_or_default().to_string(),
+                server.server_name.clone(),
+                server.username.clone(),
+                address_summary(server),
+                server.port.to_string(),
+                method.to_owned(),
+                key.into_owned(),
+                match server.tags.is_empty() {
+                    true => "-".to_owned(),
+                    false => server.tags.join(","),
+                },
+                server.platform.map_or("-".to_owned(), |p| p.to_string()),
+                last,
+                date(server.created_at),
+                date(server.updated_at),
+                match server.expired(now) {
+                    true => "expired".to_owned(),
+                    false => date(server.expires_at),
+                },
+                match (&server.access_window, access::closed(server, now)) {
+                    (None, _) => "-".to_owned(),
+                    (Some(_), Ok(None)) => "open".to_owned(),
+                    (Some(_), Ok(Some(_))) => "closed".to_owned(),
+                    (Some(_), Err(_)) => "?".to_owned(),
+                },
+            ]
+        })
+        .collect();
+    let header = [
+        "ID",
+        "NAME",
+        "USER",
+        "ADDRESS",
+        "PORT",
+        "METHOD",
+        "KEY",
+        "TAGS",
+        "PLATFORM",
+        "LAST (UTC)",
+        "CREATED",
+        "UPDATED",
+        "EXPIRES",
+        "WINDOW",
+    ];
+    table(style, header, rows, fit)
+}
+
+/// Just the day, for columns where the time would only take up room
+fn date(time: Option<i64>) -> String {
+    time.map_or("-".to_owned(), |time| {
+        audit::format_time(time)[..10].to_owned()
+    })
+}
+
+/// `jump ls --explain`: every server, with whether it matched and the
+/// verdict on each term
+pub fn explain(style: Style, servers: &[Server], selectors: &[Selector]) -> String {
+    let width = selectors
+        .iter()
+        .flat_map(|selector| &selector.terms)
+        .map(|term| term.text.len())
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    for server in servers {
+        let verdict = match selectors.iter().any(|selector| selector.matches(server)) {
+            true => style.green("matched"),
+            false => style.red("not matched"),
+        };
+        out.push_str(&format!(
+            "{}: {}\n",
+            style.bold(&server.server_name),
+            verdict
+        ));
+        for (i, selector) in selectors.iter().enumerate() {
+            for (j, term) in selector.terms.iter().enumerate() {
+                let or = if i > 0 && j == 0 { "or " } else { "   " };
+                let result = match term.matches(server) {
+                    true => style.green("yes"),
+                    false => style.red("no "),
+                };
+                out.push_str(&format!(
+                    "  {}{:width$}  {}  {}\n",
+                    or,
+                    term.text,
+                    result,
+                    term.value(server),
+                    width = width
+                ));
+            }
+        }
+    }
+    out
+}
+
+/// `jump search`: each server on a line with the fields the words were
+/// found in, the words highlighted
+pub fn hits(style: Style, found: &[Found]) -> String {
+    let mut out = String::new();
+    for found in found {
+        let name = match found.hits.first() {
+            Some(hit) if hit.field == search::Field::Name => highlight(style, hit, Style::bold),
+            _ => style.bold(&found.server.server_name),
+        };
+        let fields = found
+            .hits
+            .iter()
+            .filter(|hit| hit.field != search::Field::Name)
+            .map(|hit| {
+                format!(
+                    "{}: {}",
+                    hit.field,
+                    highlight(style, hit, |_, text| text.to_owned())
+                )
+            })
+            .collect::<Vec<_>>();
+        match fields.is_empty() {
+            true => out.push_str(&format!("{}\n", name)),
+            false => out.push_str(&format!("{}  {}\n", name, fields.join("  "))),
+        }
+    }
+    out
+}
+
+/// `hit.value` with the found words in yellow and the rest through `rest`
+fn highlight(style: Style, hit: &Hit, rest: impl Fn(&Style, &str) -> String) -> String {
+    let mut parts = vec![];
+    let mut at = 0;
+    for &(start, end) in &hit.ranges {
+        // Words may overlap
+        let start = start.max(at);
+        if end > start {
+            parts.push((&hit.value[at..start], false));
+            parts.push((&hit.value[start..end], true));
+            at = end;
+        }
+    }
+    parts.push((&hit.value[at..], false));
+    parts
+        .into_iter()
+        .filter(|(text, _)| !text.is_empty())
+        .map(|(text, found)| match found {
+            true => style.yellow(text),
+            false => rest(&style, text),
+        })
+        .collect()
+}
+
+/// `jump show`: every setting of `server` that is set, with the key it was
+/// last seen to accept and the hooks `config` runs around it
+pub fn show(
+    style: Style,
+    config: &Config,
+    server: &Server,
+    key_use: Option<&KeyUse>,
+    now: i64,
+) -> String {
+    let mut out = String::new();
+    let mut line = |line: String| {
+        out.push_str(&line);
+        out.push('\n');
+    };
+    line(format!("id:       {}", server.id.unwrap_or_default()));
+    line(format!("name:     {}", style.bold(&server.server_name)));
+    if let Some(source) = &server.source {
+        line(format!("source:   {} (read-only)", source.display()));
+    }
+    line(format!("username: {}", server.username));
+    line(format!("address:  {}", server.server_address));
+    for address in &server.addresses {
+        line(format!("fallback: {}", address));
+    }
+    line(format!("port:     {}", server.port));
+    line(match &server.method {
+        ConnectMethods::SSHKey(key) => {
+            format!("method:   {} {}", style.cyan("ssh key"), key.path.display())
+        }
+        ConnectMethods::Password(_) => format!("method:   {}", style.yellow("password")),
+        ConnectMethods::Command(custom) => {
+            format!("method:   {} {}", style.cyan("custom"), custom.command)
+        }
+        ConnectMethods::SshConfig(config) => format!(
+            "method:   {} Host {}, whose settings ssh applies",
+            style.cyan("ssh config"),
+            config.alias
+        ),
+        ConnectMethods::Unsupported(_) => format!(
+            "method:   {} {}",
+            style.yellow(ConnectMethods::UNSUPPORTED),
+            server.method
+        ),
+    });
+    line(format!("x11:      {}", server.x11));
+    if server.forward_agent {
+        line("agent:    FORWARDED (the remote host can use your local keys)".to_owned());
+    }
+    if server.compression {
+        line("compress: yes".to_owned());
+    }
+    if !server.identities_only && matches!(server.method, ConnectMethods::SSHKey(_)) {
+        line("keys:     the agent's as well (identities_only off)".to_owned());
+    }
+    if server.multiplex {
+        line("shared:   yes (multiplex)".to_owned());
+    }
+    if let Some(host_key) = &server.host_key {
+        line(format!("host key: {}", host_key));
+    }
+    if let Some(key_use) = key_use {
+        line(format!(
+            "key used: {} ({}), {}",
+            key_use.accepted.fingerprint,
+            key_use.accepted.key,
+            audit::format_ago(key_use.used_at, now)
+        ));
+    }
+    for (key, value) in legacy::settings(server) {
+        line(format!(
+            "legacy:   {} (weaker algorithms allowed)",
+            style.yellow(&format!("{}={}", key, value))
+        ));
+    }
+    if let Some(command) = &server.remote_command {
+        line(format!("command:  {}", command));
+    }
+    if let Some(workdir) = &server.workdir {
+        line(format!("workdir:  {}", workdir));
+    }
+    if let Some(mac) = &server.mac {
+        let broadcast = server
+            .broadcast
+            .as_deref()
+            .unwrap_or(wol::DEFAULT_BROADCAST);
+        let automatic = if server.wake_on_fail {
+            ", on failure"
+        } else {
+            ""
+        };
+        line(format!("wake:     {} via {}{}", mac, broadcast, automatic));
+    }
+    if let Some(sequence) = knock::format_sequence(&server.knock) {
+        let delay = server
+            .knock_delay
+            .map_or(knock::DEFAULT_DELAY, Duration::from_millis);
+        line(format!(
+            "knock:    {}, {}ms apart",
+            sequence,
+            delay.as_millis()
+        ));
+    }
+    if server.always_record {
+        line("record:   every session".to_owned());
+    }
+    if let Some(platform) = server.platform {
+        line(format!("platform: {}", platform));
+    }
+    if let Some(proxy) = &server.proxy {
+        line(format!("proxy:    {}", proxy));
+    }
+    if !server.tags.is_empty() {
+        line(format!("tags:     {}", server.tags.join(", ")));
+    }
+    if let Some(note) = &server.note {
+        line(format!("note:     {}", note));
+    }
+    if let Some(created) = server.created_at {
+        line(format!("created:  {} UTC", audit::format_time(created)));
+    }
+    if let Some(updated) = server.updated_at {
+        line(format!("updated:  {} UTC", audit::format_time(updated)));
+    }
+    if let (Some(console_server), Some(target)) = (&server.console_server, &server.console_target) {
+        line(format!("console:  `{}` on {}", target, console_server));
+    }
+    if let Some(window) = &server.access_window {
+        line(match access::closed(server, now) {
+            Ok(None) => format!("window:   {} (open now)", window),
+            Ok(Some((window, Some(next)))) => format!(
+                "window:   {} (closed, opens {})",
+                window,
+                window.local_time(next)
+            ),
+            Ok(Some((window, None))) => format!("window:   {} (closed)", window),
+            Err(e) => format!("window:   {} ({})", window, e),
+        });
+    }
+    if let Some(max_session) = server.max_session {
+        line(format!(
+            "limit:    {} per session",
+            audit::format_age(max_session)
+        ));
+    }
+    if let Some(expires) = server.expires_at {
+        let expired = match server.expired(now) {
+            true => format!(" {}", style.red("(expired)")),
+            false => String::new(),
+        };
+        line(format!(
+            "expires:  {} UTC{}",
+            audit::format_time(expires),
+            expired
+        ));
+    }
+    for (hook, scope) in pre_connect_hooks(config, server) {
+        line(format!("pre:      {} ({})", hook, scope));
+    }
+    for (hook, scope) in post_connect_hooks(config, server) {
+        line(format!("post:     {} ({})", hook, scope));
+    }
+    if let Some(command) = &server.local_command {
+        line(format!("local:    {}", command));
+    }
+    if !server.options.is_empty() {
+        line("options:".to_owned());
+        for (key, value) in &server.options {
+            line(format!("  {}={}", key, value));
+        }
+    }
+    if !server.env.is_empty() {
+        line("env:".to_owned());
+        for (name, value) in &server.env {
+            line(format!("  {}={}", name, value));
+        }
+    }
+    out
+}
+
+/// `jump check`: each server with its address and whether it is up, or
+/// with `plain` their tab-separated fields
+pub fn checks(style: Style, servers: &[Server], up: &[bool], plain: bool) -> String {
+    let width = servers
+        .iter()
+        .map(|server| server.server_name.len())
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    for (server, up) in servers.iter().zip(up) {
+        let address = format!("{}:{}", server.server_address, server.port);
+        let state = if *up { "up" } else { "down" };
+        if plain {
+            out.push_str(&format!("{}\t{}\t{}\n", server.server_name, address, state));
+            continue;
+        }
+        let padding = " ".repeat(width - server.server_name.len());
+        let state = match up {
+            true => style.green(state),
+            false => style.red(state),
+        };
+        out.push_str(&format!(
+            "{}{}  {}  {}\n",
+            style.bold(&server.server_name),
+            padding,
+            address,
+            state
+        ));
+    }
+    out
+}
+
+/// `jump log`
+pub fn log(style: Style, entries: &[LogEntry]) -> String {
+    let rows = entries
+        .iter()
+        .map(|entry| {
+            let optional = |value: Option<i64>| value.map_or("-".to_owned(), |v| v.to_string());
+            [
+                audit::format_time(entry.started_at),
+                entry.server_name.clone(),
+                entry.local_user.clone(),
+                entry.command.clone(),
+                entry.method.clone(),
+                optional(entry.exit_code.map(i64::from)),
+                optional(entry.ended_at.map(|ended| ended - entry.started_at)),
+                entry.confirmed.clone().unwrap_or_else(|| "-".to_owned()),
+                match (&entry.ended_by, &entry.no_limit) {
+                    (Some(_), _) => "reached".to_owned(),
+                    (None, Some(reason)) => format!("lifted: {}", reason),
+                    (None, None) => "-".to_owned(),
+                },
+                entry
+                    .override_window
+                    .as_ref()
+                    .map_or("-".to_owned(), |reason| format!("overridden: {}", reason)),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = [
+        "STARTED (UTC)",
+        "SERVER",
+        "USER",
+        "COMMAND",
+        "METHOD",
+        "EXIT",
+        "SECONDS",
+        "CONFIRMED",
+        "LIMIT",
+        "WINDOW",
+    ];
+    table(style, header, rows, None)
+}
+
+/// Columns padded to their widest cell, under a bold header. With a `fit`
+/// width, the widest columns are cut short, down to their header, until the
+/// rows fit.
+pub fn table<const N: usize>(
+    style: Style,
+    header: [&str; N],
+    rows: Vec<[String; N]>,
+    fit: Option<usize>,
+) -> String {
+    let len = |cell: &str| cell.chars().count();
+    let mut widths = header.map(len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(len(cell));
+        }
+    }
+    if let Some(fit) = fit {
+        let gaps = 2 * N.saturating_sub(1);
+        while widths.iter().sum::<usize>() + gaps > fit {
+            let widest = (0..N)
+                .filter(|&i| widths[i] > len(header[i]).max(3))
+                .max_by_key(|&i| widths[i]);
+            match widest {
+                Some(i) => widths[i] -= 1,
+                None => break,
+            }
+        }
+    }
+    let line = |cells: Vec<String>| {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| match len(cell) > width {
+                true => format!("{}…", cell.chars().take(width - 1).collect::<String>()),
+                false => format!("{:width$}", cell, width = width),
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+    let mut table = style.bold(&line(header.map(str::to_owned).to_vec())) + "\n";
+    for row in rows {
+        table += &(line(row.to_vec()) + "\n");
+    }
+    table
+}
+
+/// `jump verify`: the problems under a heading per server, the store's own
+/// first, and a count of each severity
+pub fn problems(style: Style, problems: &[verify::Problem]) -> String {
+    if problems.is_empty() {
+        return "no problems found\n".to_owned();
+    }
+    let mut list = String::new();
+    let mut heading = None;
+    for problem in problems {
+        if heading != Some(&problem.server_name) {
+            heading = Some(&problem.server_name);
+            let name = problem.server_name.as_deref().unwrap_or("(store)");
+            list.push_str(&format!("{}\n", style.bold(name)));
+        }
+        let severity = match problem.severity {
+            Severity::Error => style.red("error"),
+            Severity::Warning => style.yellow("warning"),
+        };
+        list.push_str(&format!("  {}: {}", severity, problem.problem));
+        match &problem.fix {
+            Some(fix) => list.push_str(&format!(" ({})\n", fix.describe())),
+            None => list.push('\n'),
+        }
+    }
+    let count = |severity| {
+        problems
+            .iter()
+            .filter(|problem| problem.severity == severity)
+            .count()
+    };
+    let (errors, warnings) = (count(Severity::Error), count(Severity::Warning));
+    list.push_str(&format!(
+        "{} error{}, {} warning{}\n",
+        errors,
+        if errors == 1 { "" } else { "s" },
+        warnings,
+        if warnings == 1 { "" } else { "s" }
+    ));
+    list
+}
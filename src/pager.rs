@@ -0,0 +1,74 @@
+//! Paging long listings through `$PAGER`, much as git does: only when
+//! writing to a terminal the text would overflow, and quietly when the pager
+//! is quit before the end.
+
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    process::{Child, Command, Stdio},
+};
+
+use ratatui::crossterm::terminal;
+
+use crate::debug;
+
+/// Used when `$PAGER` is unset: keeps colors, and leaves the text on screen
+pub const DEFAULT: &str = "less -RFX";
+
+/// Print `text`, through the pager when `enabled` and the text is taller
+/// than the terminal. A closed pipe ends the output without an error.
+pub fn page(text: &str, enabled: bool) -> io::Result<()> {
+    if enabled && io::stdout().is_terminal() {
+        let rows = terminal::size().map_or(24, |(_, rows)| usize::from(rows));
+        if text.lines().count() > rows {
+            if let Some(pager) = spawn() {
+                return through(pager, text);
+            }
+        }
+    }
+    quietly(io::stdout().lock().write_all(text.as_bytes()))
+}
+
+/// The pager from `$PAGER`, or `DEFAULT`; none when it is empty or `cat`, or
+/// does not start
+fn spawn() -> Option<Child> {
+    let pager = env::var("PAGER").unwrap_or(DEFAULT.to_owned());
+    let mut words = pager.split_whitespace();
+    let program = words.next().filter(|program| *program != "cat")?;
+    let mut command = Command::new(program);
+    command.args(words).stdin(Stdio::piped());
+    // A bare `less` would otherwise show escape codes instead of colors
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    command
+        .spawn()
+        .inspect_err(|e| debug!("not paging, {} did not start: {}", program, e))
+        .ok()
+}
+
+fn through(mut pager: Child, text: &str) -> io::Result<()> {
+    // Ctrl-C is for the pager; jump waits for it either way
+    #[cfg(unix)]
+    let ignored = signal_hook::flag::register(
+        signal_hook::consts::SIGINT,
+        std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+    )?;
+    let written = match pager.stdin.take() {
+        Some(mut stdin) => quietly(stdin.write_all(text.as_bytes())),
+        None => Ok(()),
+    };
+    let waited = pager.wait();
+    #[cfg(unix)]
+    signal_hook::low_level::unregister(ignored);
+    written?;
+    waited.map(|_| ())
+}
+
+/// Treat a reader that went away as the end of the output
+fn quietly(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
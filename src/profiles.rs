@@ -0,0 +1,123 @@
+//! `jump export --windows-terminal` and `--iterm2`: one terminal profile per
+//! server, each running `jump conn <name>`. Profile GUIDs are derived from
+//! the server name, so importing a fresh export replaces the old profiles
+//! instead of adding copies.
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{server::Server, ssh::shell_quote};
+
+/// A Windows Terminal fragment: `{"profiles": [...]}`, for a file in a
+/// `Fragments/jump` folder
+pub fn windows_terminal(servers: &[Server], colors: &BTreeMap<String, String>) -> Value {
+    let profiles = servers
+        .iter()
+        .map(|server| {
+            let mut profile = json!({
+                "guid": format!("{{{}}}", guid(&server.server_name)),
+                "name": server.server_name,
+                "commandline": format!("jump conn {}", windows_quote(&server.server_name)),
+            });
+            if let Some(color) = color(server, colors) {
+                profile["tabColor"] = json!(color);
+            }
+            profile
+        })
+        .collect::<Vec<_>>();
+    json!({ "profiles": profiles })
+}
+
+/// An iTerm2 Dynamic Profiles file: `{"Profiles": [...]}`, for
+/// `~/Library/Application Support/iTerm2/DynamicProfiles`
+pub fn iterm2(servers: &[Server], colors: &BTreeMap<String, String>) -> Value {
+    let profiles = servers
+        .iter()
+        .map(|server| {
+            let mut profile = json!({
+                "Guid": guid(&server.server_name),
+                "Name": server.server_name,
+                "Custom Command": "Yes",
+                "Command": format!("jump conn {}", shell_quote(&server.server_name)),
+                "Tags": server.tags,
+            });
+            if let Some(color) = color(server, colors) {
+                let component = |at: usize| {
+                    let value = u8::from_str_radix(&color[at..at + 2], 16).unwrap_or(0);
+                    f64::from(value) / 255.0
+                };
+                profile["Use Tab Color"] = json!(true);
+                profile["Tab Color"] = json!({
+                    "Color Space": "sRGB",
+                    "Red Component": component(1),
+                    "Green Component": component(3),
+                    "Blue Component": component(5),
+                });
+            }
+            profile
+        })
+        .collect::<Vec<_>>();
+    json!({ "Profiles": profiles })
+}
+
+/// A name-based UUID (version 8, from SHA-256) that only depends on `name`
+pub fn guid(name: &str) -> String {
+    let digest = Sha256::digest(format!("jump profile {}", name).as_bytes());
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    bytes[6] = (bytes[6] & 0x0f) | 0x80;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// The color of the first of the server's tags that has one
+pub fn color<'a>(server: &Server, colors: &'a BTreeMap<String, String>) -> Option<&'a str> {
+    server
+        .tags
+        .iter()
+        .find_map(|tag| colors.get(tag))
+        .map(String::as_str)
+}
+
+/// Quote `arg` the way Windows programs split their command line: in double
+/// quotes when it has spaces or quotes, with backslashes doubled only ahead
+/// of a quote
+pub fn windows_quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_owned();
+    }
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            quoted.push(c);
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,66 @@
+//! Every question jump asks goes through here, so that with
+//! `--non-interactive` each one fails instead, saying how to do without it.
+
+use std::{
+    io::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::error::{JumpError, Result};
+
+static INTERACTIVE: AtomicBool = AtomicBool::new(true);
+
+pub fn set_interactive(interactive: bool) {
+    INTERACTIVE.store(interactive, Ordering::Relaxed);
+}
+
+pub fn interactive() -> bool {
+    INTERACTIVE.load(Ordering::Relaxed)
+}
+
+/// Fail unless questions may be asked. `what` is the question, as in "Not
+/// asking <what>", and `instead` how to go without it.
+pub fn allow(what: &str, instead: &str) -> Result<()> {
+    match interactive() {
+        true => Ok(()),
+        false => Err(JumpError::NonInteractive {
+            what: what.to_owned(),
+            instead: instead.to_owned(),
+        }),
+    }
+}
+
+/// A yes or no question answered on stdin; `bypass` is the flag that skips
+/// it
+pub fn confirm(question: &str, bypass: &str) -> Result<bool> {
+    allow(
+        &format!("\"{}\"", question),
+        &format!("pass {} to go ahead", bypass),
+    )?;
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// A line typed in answer to `question`, trimmed; `bypass` is the flag
+/// that skips it
+pub fn answer(question: &str, bypass: &str) -> Result<String> {
+    allow(
+        &format!("\"{}\"", question),
+        &format!("pass {} to go ahead", bypass),
+    )?;
+    print!("{} ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_owned())
+}
+
+/// A secret typed at the terminal without echo. `what` names it, as in
+/// "Not asking for <what>", and `instead` where else it can come from.
+pub fn secret(prompt: &str, what: &str, instead: &str) -> Result<String> {
+    allow(&format!("for {}", what), instead)?;
+    Ok(rpassword::prompt_password(prompt)?)
+}
@@ -0,0 +1,141 @@
+//! Tunnelling connections through an HTTP CONNECT or SOCKS proxy, as a
+//! `ProxyCommand` running `nc`.
+
+use std::{env, fmt};
+
+use crate::{
+    config::{self, Config, Tool},
+    error::{JumpError, Result},
+    server::Server,
+    ssh::shell_quote,
+    warn,
+};
+
+/// Checked in this order when neither the server nor the config name a proxy
+const ENV_VARS: [&str; 4] = ["https_proxy", "HTTPS_PROXY", "all_proxy", "ALL_PROXY"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Http,
+    Socks4,
+    Socks5,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proxy {
+    pub kind: Kind,
+    /// `host:port` of the proxy itself
+    pub address: String,
+}
+
+impl Proxy {
+    /// The `ProxyCommand` handing the connection to the proxy
+    pub fn command(&self, config: &Config) -> Result<String> {
+        let (nc, _) = config::resolve(config, Tool::Nc)?;
+        let protocol = match self.kind {
+            Kind::Http => "connect",
+            Kind::Socks4 => "4",
+            Kind::Socks5 => "5",
+        };
+        Ok(format!(
+            "{} -X {} -x {} %h %p",
+            shell_quote(&nc.to_string_lossy()),
+            protocol,
+            self.address
+        ))
+    }
+}
+
+impl fmt::Display for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            Kind::Http => write!(f, "{}", self.address),
+            Kind::Socks4 => write!(f, "socks4://{}", self.address),
+            Kind::Socks5 => write!(f, "socks5://{}", self.address),
+        }
+    }
+}
+
+/// Parse `host:port`, optionally behind an `http://`, `https://`, `socks4://`
+/// or `socks5://` scheme as found in `HTTPS_PROXY`
+pub fn parse(spec: &str) -> Result<Proxy, String> {
+    let (kind, rest) = match spec.split_once("://") {
+        None => (Kind::Http, spec),
+        Some((scheme, rest)) => match scheme.to_ascii_lowercase().as_str() {
+            "http" | "https" => (Kind::Http, rest),
+            "socks4" | "socks4a" => (Kind::Socks4, rest),
+            "socks5" | "socks5h" => (Kind::Socks5, rest),
+            _ => return Err(format!("unsupported proxy scheme `{}`", scheme)),
+        },
+    };
+    let address = rest.trim_end_matches('/');
+    if address.contains('@') {
+        return Err("proxy credentials are not supported".to_owned());
+    }
+    match address.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(Proxy {
+            kind,
+            address: address.to_owned(),
+        }),
+        _ => Err(format!("`{}` is not of the form host:port", spec)),
+    }
+}
+
+/// `parse` for a stored setting, kept in its normalized form
+pub fn parse_arg(spec: &str) -> Result<String, String> {
+    parse(spec).map(|proxy| proxy.to_string())
+}
+
+/// The proxy a connection to `server` goes through: `requested` by the
+/// command line, else the server's own, else the config's, else one from the
+/// environment. A stored `ProxyCommand` or `ProxyJump` option stands in for
+/// the server's own and keeps the config and environment out.
+pub fn resolve(
+    config: &Config,
+    server: &Server,
+    requested: Option<&Proxy>,
+) -> Result<Option<Proxy>> {
+    if let Some(proxy) = requested {
+        return Ok(Some(proxy.clone()));
+    }
+    let setting = |spec: &str| parse(spec).map_err(|reason| JumpError::invalid("proxy", reason));
+    if let Some(spec) = &server.proxy {
+        return setting(spec).map(Some);
+    }
+    let routed = server.options.iter().any(|(key, _)| {
+        key.eq_ignore_ascii_case("ProxyCommand") || key.eq_ignore_ascii_case("ProxyJump")
+    });
+    if routed {
+        return Ok(None);
+    }
+    if let Some(spec) = &config.proxy {
+        return setting(spec).map(Some);
+    }
+    Ok(from_env(&server.server_address))
+}
+
+/// The first usable proxy variable, unless `NO_PROXY` exempts `host`
+fn from_env(host: &str) -> Option<Proxy> {
+    if exempt(host) {
+        return None;
+    }
+    ENV_VARS.iter().find_map(|var| {
+        let value = env::var(var).ok().filter(|value| !value.is_empty())?;
+        parse(&value)
+            .inspect_err(|e| warn!("ignoring ${}: {}", var, e))
+            .ok()
+    })
+}
+
+/// Whether `NO_PROXY` lists `host`, itself or as a parent domain, or is `*`
+fn exempt(host: &str) -> bool {
+    let no_proxy = env::var("no_proxy")
+        .or_else(|_| env::var("NO_PROXY"))
+        .unwrap_or_default();
+    let host = host.to_ascii_lowercase();
+    no_proxy.split(',').map(str::trim).any(|entry| {
+        let domain = entry.trim_start_matches('.').to_ascii_lowercase();
+        entry == "*"
+            || !domain.is_empty() && (host == domain || host.ends_with(&format!(".{}", domain)))
+    })
+}
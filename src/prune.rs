@@ -0,0 +1,88 @@
+//! `jump prune`: servers whose expiry has passed are removed, and those
+//! that stopped answering are archived with a tag or removed. Removed
+//! servers go to the trash first, so that `jump import` brings them back.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{error::Result, server::Server, store::Store, trash};
+
+/// The tag archived servers get; `prune` passes them over from then on
+pub const ARCHIVED: &str = "archived";
+
+/// What `jump prune --unreachable` does with a server
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pruning {
+    Keep,
+    Archive,
+    Delete,
+}
+
+/// What `apply` did
+#[derive(Debug, Default, PartialEq)]
+pub struct Pruned {
+    pub archived: usize,
+    pub deleted: usize,
+    /// Where the deleted servers were saved
+    pub trash: Option<PathBuf>,
+}
+
+/// Remove the servers whose expiry has passed at `now` in one transaction,
+/// or only find them with `dry_run`; returns their names
+pub fn expired(store: &Store, profile: &str, now: i64, dry_run: bool) -> Result<Vec<String>> {
+    let expired = store
+        .list_stored()?
+        .into_iter()
+        .filter(|server| server.expired(now))
+        .collect::<Vec<_>>();
+    let names = expired
+        .iter()
+        .map(|server| server.server_name.clone())
+        .collect::<Vec<_>>();
+    if !dry_run && !expired.is_empty() {
+        trash::remove(store, profile, expired)?;
+    }
+    Ok(names)
+}
+
+/// The servers worth probing: not archived and, with `unreachable_for`,
+/// not connected to successfully within that many seconds of `now`
+pub fn candidates(
+    mut servers: Vec<Server>,
+    last_succeeded: &HashMap<String, i64>,
+    unreachable_for: Option<i64>,
+    now: i64,
+) -> Vec<Server> {
+    servers.retain(|server| !server.tags.iter().any(|tag| tag == ARCHIVED));
+    if let Some(age) = unreachable_for {
+        servers.retain(|server| {
+            last_succeeded
+                .get(&server.server_name)
+                .is_none_or(|last| *last < now - age)
+        });
+    }
+    servers
+}
+
+/// Archive and delete the servers as decided, archiving in one transaction
+pub fn apply(store: &Store, profile: &str, pruned: Vec<(Server, Pruning)>) -> Result<Pruned> {
+    let mut done = Pruned::default();
+    store.transaction(|| {
+        for (server, pruning) in &pruned {
+            if *pruning == Pruning::Archive {
+                store.add_tag(&server.server_name, ARCHIVED)?;
+                done.archived += 1;
+            }
+        }
+        Ok(())
+    })?;
+    let deleted = pruned
+        .into_iter()
+        .filter(|(_, pruning)| *pruning == Pruning::Delete)
+        .map(|(server, _)| server)
+        .collect::<Vec<_>>();
+    if !deleted.is_empty() {
+        done.deleted = deleted.len();
+        done.trash = Some(trash::remove(store, profile, deleted)?);
+    }
+    Ok(done)
+}
@@ -0,0 +1,403 @@
+//! Just enough of a QR code encoder for `jump uri --qr`: byte mode, error
+//! correction level L, versions 1 to 10, so up to 271 bytes. The module
+//! layout follows ISO/IEC 18004 and Project Nayuki's reference encoder.
+
+use crate::error::{JumpError, Result};
+
+/// Per version, the error correction codewords of each block and the data
+/// codewords of each block, at level L
+const BLOCKS: [(usize, &[usize]); 10] = [
+    (7, &[19]),
+    (10, &[34]),
+    (15, &[55]),
+    (20, &[80]),
+    (26, &[108]),
+    (18, &[68, 68]),
+    (20, &[78, 78]),
+    (24, &[97, 97]),
+    (30, &[116, 116]),
+    (18, &[68, 68, 69, 69]),
+];
+
+/// Level L in the format information
+const LEVEL_L: u32 = 1;
+
+pub struct Qr {
+    size: usize,
+    modules: Vec<bool>,
+    function: Vec<bool>,
+}
+
+/// The smallest code holding `data`
+pub fn encode(data: &[u8]) -> Result<Qr> {
+    let (version, codewords) = (1..=BLOCKS.len())
+        .find_map(|version| Some((version, codewords(version, data)?)))
+        .ok_or_else(|| {
+            JumpError::invalid(
+                "QR code",
+                format!("{} bytes do not fit, at most 271 do", data.len()),
+            )
+        })?;
+    let mut qr = Qr::new(version);
+    qr.draw_codewords(&interleave(version, &codewords));
+    let mask = (0..8)
+        .min_by_key(|&mask| {
+            qr.apply_mask(mask);
+            qr.draw_format(mask);
+            let penalty = qr.penalty();
+            qr.apply_mask(mask);
+            penalty
+        })
+        .expect("there are eight masks");
+    qr.apply_mask(mask);
+    qr.draw_format(mask);
+    Ok(qr)
+}
+
+/// The data codewords of `data` in byte mode, padded to fill `version`, or
+/// `None` when it does not fit
+fn codewords(version: usize, data: &[u8]) -> Option<Vec<u8>> {
+    let capacity = BLOCKS[version - 1].1.iter().sum::<usize>() * 8;
+    let count_bits = match version {
+        1..=9 => 8,
+        _ => 16,
+    };
+    let mut bits = vec![];
+    push_bits(&mut bits, 0b0100, 4);
+    push_bits(&mut bits, data.len() as u32, count_bits);
+    if data.len() >= 1 << count_bits {
+        return None;
+    }
+    for &byte in data {
+        push_bits(&mut bits, byte.into(), 8);
+    }
+    if bits.len() > capacity {
+        return None;
+    }
+    bits.resize((bits.len() + 4).min(capacity).next_multiple_of(8), false);
+    let mut codewords = bits
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | u8::from(bit)))
+        .collect::<Vec<_>>();
+    for pad in [0xec, 0x11].into_iter().cycle() {
+        if codewords.len() * 8 >= capacity {
+            break;
+        }
+        codewords.push(pad);
+    }
+    Some(codewords)
+}
+
+fn push_bits(bits: &mut Vec<bool>, value: u32, count: usize) {
+    bits.extend((0..count).rev().map(|i| value >> i & 1 == 1));
+}
+
+/// The data codewords split into blocks, each followed by its error
+/// correction, in the order they are placed
+fn interleave(version: usize, codewords: &[u8]) -> Vec<u8> {
+    let (ec_len, lengths) = BLOCKS[version - 1];
+    let divisor = divisor(ec_len);
+    let mut rest = codewords;
+    let blocks = lengths
+        .iter()
+        .map(|&len| {
+            let (block, after) = rest.split_at(len);
+            rest = after;
+            (block, remainder(block, &divisor))
+        })
+        .collect::<Vec<_>>();
+    let longest = lengths.iter().max().copied().unwrap_or(0);
+    let mut result = vec![];
+    for i in 0..longest {
+        result.extend(blocks.iter().filter_map(|(data, _)| data.get(i)));
+    }
+    for i in 0..ec_len {
+        result.extend(blocks.iter().map(|(_, ec)| ec[i]));
+    }
+    result
+}
+
+/// Product in GF(2^8) modulo x^8 + x^4 + x^3 + x^2 + 1
+fn multiply(x: u8, y: u8) -> u8 {
+    let mut z = 0u8;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x1d);
+        z ^= ((y >> i) & 1) * x;
+    }
+    z
+}
+
+/// The Reed-Solomon generator polynomial of `degree`, leading term left out
+fn divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0; degree];
+    result[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = multiply(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = multiply(root, 0x02);
+    }
+    result
+}
+
+fn remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0; divisor.len()];
+    for &byte in data {
+        let factor = byte ^ result.remove(0);
+        result.push(0);
+        for (x, &y) in result.iter_mut().zip(divisor) {
+            *x ^= multiply(y, factor);
+        }
+    }
+    result
+}
+
+impl Qr {
+    /// A code of `version` with only its function patterns drawn
+    fn new(version: usize) -> Qr {
+        let size = version * 4 + 17;
+        let mut qr = Qr {
+            size,
+            modules: vec![false; size * size],
+            function: vec![false; size * size],
+        };
+        for i in 0..size {
+            qr.set_function(6, i, i % 2 == 0);
+            qr.set_function(i, 6, i % 2 == 0);
+        }
+        for (x, y) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            qr.draw_finder(x, y);
+        }
+        let positions = alignment_positions(version);
+        let last = positions.len().saturating_sub(1);
+        for (i, &x) in positions.iter().enumerate() {
+            for (j, &y) in positions.iter().enumerate() {
+                // Three corners belong to the finders
+                let corner = (i == 0 || i == last) && (j == 0 || j == last);
+                if !corner || (i == last && j == last) {
+                    qr.draw_alignment(x, y);
+                }
+            }
+        }
+        // Reserved now, drawn once the mask is chosen
+        qr.draw_format(0);
+        qr.draw_version(version);
+        qr
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Whether the module in column `x` of row `y` is dark
+    pub fn dark(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    fn draw_finder(&mut self, x: usize, y: usize) {
+        for dy in -4..=4i32 {
+            for dx in -4..=4i32 {
+                let (xx, yy) = (x as i32 + dx, y as i32 + dy);
+                if (0..self.size as i32).contains(&xx) && (0..self.size as i32).contains(&yy) {
+                    let distance = dx.abs().max(dy.abs());
+                    self.set_function(xx as usize, yy as usize, distance != 2 && distance != 4);
+                }
+            }
+        }
+    }
+
+    fn draw_alignment(&mut self, x: usize, y: usize) {
+        for dy in 0..5 {
+            for dx in 0..5 {
+                let distance = (dx as i32 - 2).abs().max((dy as i32 - 2).abs());
+                self.set_function(x + dx - 2, y + dy - 2, distance != 1);
+            }
+        }
+    }
+
+    fn draw_format(&mut self, mask: u32) {
+        let data = LEVEL_L << 3 | mask;
+        let mut rem = data;
+        for _ in 0..10 {
+            rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+        }
+        let bits = (data << 10 | rem) ^ 0x5412;
+        let bit = |i: usize| bits >> i & 1 == 1;
+        let size = self.size;
+        for i in 0..6 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true);
+    }
+
+    fn draw_version(&mut self, version: usize) {
+        if version < 7 {
+            return;
+        }
+        let data = version as u32;
+        let mut rem = data;
+        for _ in 0..12 {
+            rem = (rem << 1) ^ ((rem >> 11) * 0x1f25);
+        }
+        let bits = data << 12 | rem;
+        for i in 0..18 {
+            let dark = bits >> i & 1 == 1;
+            let (a, b) = (self.size - 11 + i % 3, i / 3);
+            self.set_function(a, b, dark);
+            self.set_function(b, a, dark);
+        }
+    }
+
+    /// Fill the modules left free in the zigzag order, two columns at a
+    /// time from the bottom right
+    fn draw_codewords(&mut self, data: &[u8]) {
+        let mut i = 0;
+        let mut right = self.size - 1;
+        while right >= 1 {
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..self.size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = match upward {
+                        true => self.size - 1 - vertical,
+                        false => vertical,
+                    };
+                    if !self.function[y * self.size + x] && i < data.len() * 8 {
+                        self.modules[y * self.size + x] = data[i >> 3] >> (7 - (i & 7)) & 1 == 1;
+                        i += 1;
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    /// Flip the data modules selected by `mask`; applying it twice undoes it
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let flip = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                if flip && !self.function[y * self.size + x] {
+                    self.modules[y * self.size + x] ^= true;
+                }
+            }
+        }
+    }
+
+    /// How hard the code is to read, by the four rules of the standard:
+    /// long runs, 2x2 blocks, finder-like patterns and dark balance
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let lines = (0..size)
+            .map(|y| (0..size).map(|x| self.dark(x, y)).collect::<Vec<_>>())
+            .chain((0..size).map(|x| (0..size).map(|y| self.dark(x, y)).collect()));
+        let finder = [
+            true, false, true, true, true, false, true, false, false, false, false,
+        ];
+        let mut penalty = 0;
+        for line in lines {
+            for run in line.chunk_by(|a, b| a == b) {
+                if run.len() >= 5 {
+                    penalty += run.len() - 2;
+                }
+            }
+            for window in line.windows(finder.len()) {
+                if window == finder || window.iter().rev().eq(finder.iter()) {
+                    penalty += 40;
+                }
+            }
+        }
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let dark = self.dark(x, y);
+                if [(x + 1, y), (x, y + 1), (x + 1, y + 1)]
+                    .iter()
+                    .all(|&(x, y)| self.dark(x, y) == dark)
+                {
+                    penalty += 3;
+                }
+            }
+        }
+        let dark = self.modules.iter().filter(|&&dark| dark).count();
+        let total = size * size;
+        penalty
+            + (dark * 20)
+                .abs_diff(total * 10)
+                .div_ceil(total)
+                .saturating_sub(1)
+                * 10
+    }
+
+    /// The code as lines of half blocks, two rows of modules to a line, with
+    /// the light modules and a quiet zone drawn for a dark terminal
+    pub fn render(&self) -> String {
+        const QUIET: usize = 4;
+        let size = self.size as isize;
+        let light = |x: isize, y: isize| {
+            !(0..size).contains(&x) || !(0..size).contains(&y) || !self.dark(x as usize, y as usize)
+        };
+        let mut out = String::new();
+        let border = QUIET as isize;
+        for y in (-border..size + border).step_by(2) {
+            for x in -border..size + border {
+                let bottom = y + 1 < size + border && light(x, y + 1);
+                out.push(match (light(x, y), bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Centres of the alignment patterns along either axis
+fn alignment_positions(version: usize) -> Vec<usize> {
+    match version {
+        1 => vec![],
+        2..=6 => vec![6, version * 4 + 10],
+        7 => vec![6, 22, 38],
+        8 => vec![6, 24, 42],
+        9 => vec![6, 26, 46],
+        _ => vec![6, 28, 50],
+    }
+}
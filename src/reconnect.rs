@@ -0,0 +1,78 @@
+//! `conn --persist`: whether a session that ended is started again. Only
+//! ssh's own failure, exit code 255, after a connection that lasted counts
+//! as a drop; a logout, a remote command's exit code or a signal ends it
+//! for good. Reconnecting after a drop may fail while the network is still
+//! down, and is retried with a growing backoff.
+
+use std::time::Duration;
+
+use crate::{config, ssh};
+
+/// The longest wait between two attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// What to do after a connection ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Next {
+    /// Leave it ended, with its exit code
+    Stop,
+    /// Wait this long, then connect again
+    Reconnect(Duration),
+}
+
+/// The connections of one persisted session so far
+#[derive(Debug)]
+pub struct Reconnect {
+    backoff: Duration,
+    max_retries: u32,
+    established: Duration,
+    /// Whether a connection ever lasted `established`
+    connected: bool,
+    /// Attempts that failed since the last connection that lasted
+    failures: u32,
+    /// Connections started after the first
+    pub reconnects: u32,
+}
+
+impl Reconnect {
+    pub fn new(config: &config::Persist) -> Reconnect {
+        Reconnect {
+            backoff: Duration::from_secs(config.backoff),
+            max_retries: config.max_retries,
+            established: Duration::from_secs(config.established),
+            connected: false,
+            failures: 0,
+            reconnects: 0,
+        }
+    }
+
+    /// Given the exit code of the connection that ended and how long it
+    /// lasted. A first connection that failed quickly never got up, and is
+    /// reported as usual instead.
+    pub fn after(&mut self, code: Option<i32>, lasted: Duration) -> Next {
+        if code != Some(ssh::FAILED) {
+            return Next::Stop;
+        }
+        if lasted >= self.established {
+            self.connected = true;
+            self.failures = 0;
+        } else if !self.connected {
+            return Next::Stop;
+        } else {
+            self.failures += 1;
+            if self.max_retries > 0 && self.failures >= self.max_retries {
+                return Next::Stop;
+            }
+        }
+        self.reconnects += 1;
+        let doubled = self
+            .backoff
+            .saturating_mul(2u32.saturating_pow(self.failures));
+        Next::Reconnect(doubled.min(MAX_BACKOFF.max(self.backoff)))
+    }
+
+    /// Failed attempts in a row so far, and the most allowed, 0 for no limit
+    pub fn failures(&self) -> (u32, u32) {
+        (self.failures, self.max_retries)
+    }
+}
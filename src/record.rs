@@ -0,0 +1,162 @@
+//! Session transcripts for `conn --record`, written by `script` or
+//! asciinema wrapped around the ssh invocation
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    audit,
+    config::{self, Config, Tool},
+    error::{JumpError, Result},
+    ssh::{shell_quote, Invocation},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum RecordFormat {
+    /// A typescript written by `script`, replayable with `cat`
+    #[default]
+    Script,
+    /// An asciicast written by `asciinema rec`
+    Asciinema,
+}
+
+impl RecordFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            RecordFormat::Script => "log",
+            RecordFormat::Asciinema => "cast",
+        }
+    }
+}
+
+/// `~/.jump/recordings`
+pub fn dir() -> Result<PathBuf> {
+    Ok(config::jump_dir()?.join("recordings"))
+}
+
+/// `<dir>/<server>-<UTC timestamp>.<extension>`
+pub fn default_path(server_name: &str, format: RecordFormat, now: i64) -> Result<PathBuf> {
+    let stamp = audit::format_time(now)
+        .replace(['-', ':'], "")
+        .replace(' ', "-");
+    Ok(dir()?.join(format!("{}-{}.{}", server_name, stamp, format.extension())))
+}
+
+/// Wrap `invocation` so that the terminal session it starts is recorded to
+/// `path`. The environment carries over; a secret argument stays redacted,
+/// along with everything else when the command has to be passed as a string.
+pub fn wrap(
+    config: &Config,
+    invocation: Invocation,
+    format: RecordFormat,
+    path: &Path,
+) -> Result<Invocation> {
+    let path = path.to_string_lossy().into_owned();
+    let command = || {
+        std::iter::once(invocation.program.to_string_lossy().into_owned())
+            .chain(invocation.args.iter().cloned())
+            .map(|arg| shell_quote(&arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let (tool, args, secret) = match format {
+        RecordFormat::Asciinema => {
+            let args = vec!["rec".into(), "-q".into(), "-c".into(), command(), path];
+            (Tool::Asciinema, args, invocation.secret.map(|_| 3))
+        }
+        // util-linux; `-e` makes script exit with the session's code
+        RecordFormat::Script if cfg!(target_os = "linux") => {
+            let args = vec!["-q".into(), "-e".into(), "-c".into(), command(), path];
+            (Tool::Script, args, invocation.secret.map(|_| 3))
+        }
+        RecordFormat::Script if cfg!(windows) => {
+            return Err(JumpError::invalid(
+                "--record",
+                "script recordings are not supported on Windows",
+            ))
+        }
+        // The BSD script of macOS takes the command as separate arguments
+        RecordFormat::Script => {
+            let mut args = vec!["-q".into(), path];
+            args.push(invocation.program.to_string_lossy().into_owned());
+            let offset = args.len();
+            args.extend(invocation.args.iter().cloned());
+            (Tool::Script, args, invocation.secret.map(|i| i + offset))
+        }
+    };
+    let (program, _) = config::resolve(config, tool)?;
+    Ok(Invocation {
+        program,
+        args,
+        secret,
+        env: invocation.env,
+    })
+}
+
+/// Make a recording `wrap` set up add to its file instead of replacing it,
+/// for a session that connects again
+pub fn append(invocation: &mut Invocation, format: RecordFormat) {
+    let (at, flag) = match format {
+        // After `rec`
+        RecordFormat::Asciinema => (1, "--append"),
+        RecordFormat::Script => (0, "-a"),
+    };
+    invocation.args.insert(at, flag.to_owned());
+    if let Some(secret) = invocation.secret.as_mut().filter(|secret| **secret >= at) {
+        *secret += 1;
+    }
+}
+
+/// A file in the recordings directory
+#[derive(Debug, Serialize)]
+pub struct Recording {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Seconds since the Unix epoch
+    pub modified: i64,
+}
+
+/// The recordings, oldest first; none when nothing was recorded yet
+pub fn list() -> Result<Vec<Recording>> {
+    let dir = dir()?;
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut recordings = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as i64);
+        recordings.push(Recording {
+            path: entry.path(),
+            size: metadata.len(),
+            modified,
+        });
+    }
+    recordings.sort_by(|a, b| (a.modified, &a.path).cmp(&(b.modified, &b.path)));
+    Ok(recordings)
+}
+
+/// Delete recordings last modified before `before`, returning them
+pub fn prune(before: i64) -> Result<Vec<Recording>> {
+    let mut pruned = vec![];
+    for recording in list()? {
+        if recording.modified < before {
+            fs::remove_file(&recording.path)?;
+            pruned.push(recording);
+        }
+    }
+    Ok(pruned)
+}
@@ -0,0 +1,256 @@
+//! `jump rekey`: moving the servers that log in with one key to another.
+//! The new public key is appended to `authorized_keys` over the old key,
+//! then a login with the new key alone is tried, and only then is the new
+//! key stored. `run` takes the servers through this one at a time.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    audit,
+    config::{self, Config},
+    connect, debug,
+    error::{exit, JumpError, Result},
+    gate,
+    hostkey::HostKey,
+    prompt, report,
+    server::{ConnectMethods, SSHKey, Server},
+    ssh::{self, ConnOptions},
+    store::Store,
+};
+
+/// Seconds ssh waits for each server when jump runs non-interactively, in
+/// batch mode
+pub const CONNECT_TIMEOUT: u32 = 10;
+
+/// What `rekey` runs on each server: the new key appended with the old one,
+/// then a login with the new key alone
+pub struct Steps {
+    to: PathBuf,
+    append: [String; 1],
+    check: [String; 1],
+    exec: ssh::ExecOptions,
+}
+
+impl Steps {
+    /// The steps to the key at `to`, in batch mode when jump runs
+    /// non-interactively
+    pub fn new(to: &Path) -> Result<Steps> {
+        Ok(Steps {
+            to: to.to_path_buf(),
+            append: [append_command(&public_key(to)?)],
+            check: ["true".to_owned()],
+            exec: ssh::ExecOptions {
+                tty: false,
+                batch: (!prompt::interactive()).then_some(CONNECT_TIMEOUT),
+            },
+        })
+    }
+
+    /// The ssh of both steps on `server`, as `--dry-run` prints them
+    pub fn invocations(
+        &self,
+        config: &Config,
+        server: &Server,
+        options: &ConnOptions,
+    ) -> Result<[ssh::Invocation; 2]> {
+        let moved = with_key_only(server, &self.to);
+        Ok([
+            ssh::exec(config, server, options, &self.append, self.exec)?,
+            ssh::exec(config, &moved, options, &self.check, self.exec)?,
+        ])
+    }
+}
+
+/// How moving one server went
+#[derive(Debug, PartialEq, Eq)]
+pub enum Rekeyed {
+    Moved,
+    /// What went wrong
+    Failed(String),
+    /// Not tried, after another server failed
+    Skipped,
+}
+
+/// The servers among `servers` that log in with `from`: those jump can move,
+/// then those from include files, which it does not change
+pub fn candidates(servers: Vec<Server>, from: &Path) -> Result<(Vec<Server>, Vec<Server>)> {
+    let mut using = vec![];
+    for server in servers {
+        if uses(&server, from)? {
+            using.push(server);
+        }
+    }
+    Ok(using
+        .into_iter()
+        .partition(|server| server.source.is_none()))
+}
+
+/// Move `servers`, which passed their gates as `confirmed`, one at a time,
+/// handing each result to `rekeyed` as it comes in. Unless
+/// `continue_on_error`, the servers after a failure are skipped.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    store: &Store,
+    config: &Config,
+    servers: &[Server],
+    options: &ConnOptions,
+    steps: &Steps,
+    continue_on_error: bool,
+    confirmed: Vec<Option<gate::Ack>>,
+    mut rekeyed: impl FnMut(&Server, Rekeyed),
+) {
+    let mut failed = false;
+    for (server, confirmed) in servers.iter().zip(confirmed) {
+        if failed && !continue_on_error {
+            rekeyed(server, Rekeyed::Skipped);
+            continue;
+        }
+        // Only servers through a gate or outside a window are logged, to
+        // record that it was passed
+        let entry = match confirmed.is_some() || connect::window_override(server, options).is_some()
+        {
+            true => connect::log_start(store, config, server, "rekey", confirmed, options),
+            false => None,
+        };
+        let result = move_server(store, config, server, options, steps);
+        let code = match result {
+            Ok(()) => exit::SUCCESS,
+            Err(_) => exit::FAILURE,
+        };
+        audit::finish(store, entry, Some(code));
+        match result {
+            Ok(()) => rekeyed(server, Rekeyed::Moved),
+            Err(message) => {
+                failed = true;
+                rekeyed(server, Rekeyed::Failed(message));
+            }
+        }
+    }
+}
+
+/// Add the new key to `server`, try it, then store it; what went wrong
+fn move_server(
+    store: &Store,
+    config: &Config,
+    server: &Server,
+    options: &ConnOptions,
+    steps: &Steps,
+) -> std::result::Result<(), String> {
+    let run = |server: &Server, command: &[String], failure: &str| {
+        let invocation =
+            ssh::exec(config, server, options, command, steps.exec).map_err(|e| e.to_string())?;
+        debug!("running {}", invocation);
+        let output = connect::run_output(&invocation)?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = match report::tail(&stderr, 1).pop() {
+            Some(line) => line,
+            None => format!("ssh exited with {}", output.status),
+        };
+        Err(format!("{}: {}", failure, reason))
+    };
+    run(server, &steps.append, "could not add the new key")?;
+    let moved = with_key_only(server, &steps.to);
+    run(
+        &moved,
+        &steps.check,
+        "the new key was added but does not log in",
+    )?;
+    let mut server = server.clone();
+    server.method = ConnectMethods::SSHKey(SSHKey {
+        path: steps.to.clone(),
+    });
+    store
+        .update(&server)
+        .map_err(|e| format!("could not store the new key: {}", e))
+}
+
+/// Whether `server` logs in with the key at `key`, however either path is
+/// written
+pub fn uses(server: &Server, key: &Path) -> Result<bool> {
+    let ConnectMethods::SSHKey(SSHKey { path }) = &server.method else {
+        return Ok(false);
+    };
+    Ok(same_file(
+        &config::expand_path(path)?,
+        &config::expand_path(key)?,
+    ))
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// The public key line of the private key at `key`, from `<key>.pub`
+pub fn public_key(key: &Path) -> Result<String> {
+    let key = config::expand_path(key)?;
+    let mut path = key.clone().into_os_string();
+    path.push(".pub");
+    let path = PathBuf::from(path);
+    let invalid = |reason: String| JumpError::invalid("--to", reason);
+    if !key.is_file() {
+        return Err(invalid(format!("{} is not a file", key.display())));
+    }
+    let text = fs::read_to_string(&path)
+        .map_err(|e| invalid(format!("cannot read {}: {}", path.display(), e)))?;
+    let line = text.lines().map(str::trim).find(|line| !line.is_empty());
+    match line {
+        Some(line) if parse(line).is_some() => Ok(line.to_owned()),
+        _ => Err(invalid(format!("{} holds no public key", path.display()))),
+    }
+}
+
+/// The type and base64 blob of a public key line
+fn parse(line: &str) -> Option<(&str, &str)> {
+    let mut words = line.split_whitespace();
+    let (key_type, blob) = (words.next()?, words.next()?);
+    HostKey::from_public(key_type, blob)?;
+    Some((key_type, blob))
+}
+
+/// The remote command appending `public_key` to `~/.ssh/authorized_keys`,
+/// unless it is there already, creating the file and directory as ssh wants
+/// them
+pub fn append_command(public_key: &str) -> String {
+    let blob = parse(public_key).map_or(public_key, |(_, blob)| blob);
+    let file = "~/.ssh/authorized_keys";
+    format!(
+        "umask 077 && mkdir -p ~/.ssh && touch {file} && \
+         {{ grep -qF {blob} {file} || \
+         {{ [ ! -s {file} ] || [ -z \"$(tail -c 1 {file})\" ] || echo >> {file}; \
+         echo {key} >> {file}; }}; }}",
+        file = file,
+        blob = ssh::shell_quote(blob),
+        key = ssh::shell_quote(public_key),
+    )
+}
+
+/// `server` logging in with `key` and nothing else, for trying it: no
+/// agent keys and no passwords, which would let the login through without
+/// it
+pub fn with_key_only(server: &Server, key: &Path) -> Server {
+    let mut server = server.clone();
+    server.method = ConnectMethods::SSHKey(SSHKey {
+        path: key.to_path_buf(),
+    });
+    server.options.retain(|(name, _)| {
+        !name.eq_ignore_ascii_case("IdentitiesOnly")
+            && !name.eq_ignore_ascii_case("PreferredAuthentications")
+    });
+    server
+        .options
+        .push(("IdentitiesOnly".to_owned(), "yes".to_owned()));
+    server.options.push((
+        "PreferredAuthentications".to_owned(),
+        "publickey".to_owned(),
+    ));
+    server
+}
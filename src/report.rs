@@ -0,0 +1,200 @@
+//! The outcome of a command run across many servers, as `exec --on`,
+//! `cp --to` and `check` write it with `--report`, and the summary they
+//! print at the end. The file format is versioned so that tools can rely on
+//! it; fields are only ever added within a version.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::Args;
+use serde::Serialize;
+
+use crate::{error::Result, ssh, style::Style};
+
+/// The version of the report format, bumped whenever a field changes
+/// meaning or goes away
+pub const VERSION: u32 = 1;
+
+/// How many lines of stderr a failure keeps unless told otherwise
+pub const TAIL_LINES: usize = 20;
+
+/// What became of one server, worst first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    /// jump could not start anything for it, e.g. ssh failed to spawn
+    NotStarted,
+    /// ssh, scp or the probe could not reach or log in to it
+    Unreachable,
+    /// It ran, and failed
+    Failed,
+    /// It was left out after another server failed
+    Skipped,
+    Ok,
+}
+
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::NotStarted => "not started",
+            Outcome::Unreachable => "unreachable",
+            Outcome::Failed => "failed",
+            Outcome::Skipped => "skipped",
+            Outcome::Ok => "ok",
+        }
+    }
+}
+
+/// One server of a report
+#[derive(Debug, Clone, Serialize)]
+pub struct Host {
+    pub server_name: String,
+    pub outcome: Outcome,
+    /// Milliseconds from start to finish; 0 when it never started
+    pub duration_ms: u64,
+    pub exit_code: Option<i32>,
+    /// The end of stderr, for failures only
+    pub stderr_tail: Vec<String>,
+    /// Why it did not start, or what jump made of the failure
+    pub error: Option<String>,
+}
+
+impl Host {
+    pub fn new(server_name: &str, outcome: Outcome, duration: Duration) -> Host {
+        Host {
+            server_name: server_name.to_owned(),
+            outcome,
+            duration_ms: duration.as_millis() as u64,
+            exit_code: None,
+            stderr_tail: vec![],
+            error: None,
+        }
+    }
+
+    /// A command that ran to the end, ok when it exited with 0. ssh's own
+    /// exit code 255 means it never got as far as running anything.
+    pub fn finished(
+        server_name: &str,
+        duration: Duration,
+        exit_code: Option<i32>,
+        stderr: &str,
+        lines: usize,
+    ) -> Host {
+        let outcome = match exit_code {
+            Some(0) => Outcome::Ok,
+            Some(ssh::FAILED) => Outcome::Unreachable,
+            _ => Outcome::Failed,
+        };
+        Host {
+            exit_code,
+            stderr_tail: match outcome {
+                Outcome::Ok => vec![],
+                _ => tail(stderr, lines),
+            },
+            ..Host::new(server_name, outcome, duration)
+        }
+    }
+
+    pub fn not_started(server_name: &str, error: String) -> Host {
+        Host {
+            error: Some(error),
+            ..Host::new(server_name, Outcome::NotStarted, Duration::ZERO)
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.outcome == Outcome::Ok
+    }
+}
+
+/// A report as written to its file
+#[derive(Debug, Serialize)]
+pub struct Report<'a> {
+    pub version: u32,
+    /// `exec`, `cp` or `check`
+    pub command: &'a str,
+    pub hosts: &'a [Host],
+}
+
+/// Write the report of `command` to `path` as JSON
+pub fn write(path: &Path, command: &str, hosts: &[Host]) -> Result<()> {
+    let report = Report {
+        version: VERSION,
+        command,
+        hosts,
+    };
+    let json = serde_json::to_string_pretty(&report).expect("a report serializes");
+    fs::write(path, json + "\n")?;
+    Ok(())
+}
+
+/// The last `lines` non-empty lines of `stderr`
+pub fn tail(stderr: &str, lines: usize) -> Vec<String> {
+    let all = stderr
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    all[all.len().saturating_sub(lines)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// A line per server with its outcome, exit code and duration, failures
+/// first and otherwise in the order they were given
+pub fn summary(style: Style, hosts: &[Host]) -> String {
+    let mut sorted = hosts.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|host| host.outcome);
+    let width = hosts
+        .iter()
+        .map(|host| host.server_name.len())
+        .max()
+        .unwrap_or(0);
+    let mut summary = String::new();
+    for host in sorted {
+        let outcome = format!("{:<11}", host.outcome.name());
+        let outcome = match host.outcome {
+            Outcome::Ok => style.green(&outcome),
+            Outcome::Skipped => style.yellow(&outcome),
+            _ => style.red(&outcome),
+        };
+        let exit_code = match host.exit_code {
+            Some(code) => format!("exit {:<3}", code),
+            None => "-       ".to_owned(),
+        };
+        summary.push_str(&format!(
+            "{}{}  {}  {}  {:.1}s\n",
+            style.bold(&host.server_name),
+            " ".repeat(width - host.server_name.len()),
+            outcome,
+            exit_code,
+            host.duration_ms as f64 / 1000.0
+        ));
+    }
+    summary
+}
+
+/// `--report` and how much of stderr goes into it
+#[derive(Debug, Args)]
+pub struct ReportOptions {
+    /// Write the outcome on each server to this file as JSON
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>,
+    /// How many of the last lines of stderr the report keeps for a failure
+    #[arg(long, value_name = "N", default_value_t = TAIL_LINES, requires = "report")]
+    pub report_lines: usize,
+}
+
+impl ReportOptions {
+    /// Write the report of `command` when one was asked for
+    pub fn write(&self, command: &str, hosts: &[Host]) -> Result<()> {
+        match &self.report {
+            Some(path) => write(path, command, hosts),
+            None => Ok(()),
+        }
+    }
+}
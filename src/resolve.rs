@@ -0,0 +1,145 @@
+//! What `conn` connects to: the address it picks among a server's own and
+//! its fallbacks, and the ssh it runs. `jump resolve` and the `resolve`
+//! method of `jump serve` report the same, for tools to run it themselves.
+
+use std::time::Duration;
+
+use crate::{
+    audit,
+    config::{self, Config},
+    debug,
+    error::{JumpError, Result},
+    info,
+    output::Resolved,
+    server::{Address, ConnectMethods, Server},
+    ssh::{self, ConnOptions, SessionOptions},
+    store::Store,
+    warn, wol,
+};
+
+/// How long `conn` waits for each address before trying the next
+const ADDRESS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The server's own address, then its fallbacks
+pub fn addresses(server: &Server) -> Vec<Address> {
+    let own = Address {
+        address: server.server_address.clone(),
+        label: None,
+    };
+    std::iter::once(own)
+        .chain(server.addresses.iter().cloned())
+        .collect()
+}
+
+/// Point `server` at the address labelled `label`, or else at the first of
+/// its addresses that accepts connections. When none does, it is left at
+/// its own address so that waking it and ssh's error still apply.
+pub fn choose_address(server: &mut Server, label: Option<&str>) -> Result<()> {
+    if let Some(label) = label {
+        let address = server
+            .addresses
+            .iter()
+            .find(|address| address.label.as_deref() == Some(label))
+            .ok_or_else(|| {
+                JumpError::invalid(
+                    "address label",
+                    format!("{} has no address labelled `{}`", server.server_name, label),
+                )
+            })?;
+        server.server_address = address.address.clone();
+        return Ok(());
+    }
+    if server.addresses.is_empty() {
+        return Ok(());
+    }
+    for address in addresses(server) {
+        if wol::probe(&address.address, server.port, ADDRESS_TIMEOUT) {
+            info!("using {}", address);
+            server.server_address = address.address;
+            return Ok(());
+        }
+        debug!("{}:{} does not accept connections", address, server.port);
+    }
+    warn!("none of the addresses of {} answered", server.server_name);
+    Ok(())
+}
+
+/// The server `conn` connects to, at the address it picked, and the ssh it
+/// runs, `None` for the built-in client
+pub fn connection(
+    store: &Store,
+    config: &Config,
+    server_name: &str,
+    options: &ConnOptions,
+    session: &SessionOptions,
+    extra_args: &[String],
+) -> Result<(Server, Option<ssh::Invocation>)> {
+    let mut server = store.get(server_name)?;
+    choose_address(&mut server, session.address_label.as_deref())?;
+    let invocation = match options.use_native() {
+        true => None,
+        false => Some(ssh::connect(config, &server, options, session, extra_args)?),
+    };
+    Ok((server, invocation))
+}
+
+/// The server and the command `conn` would run for it without options,
+/// with anything short of a missing server reported as `Unresolved`
+pub fn for_tools(
+    store: &Store,
+    config: &Config,
+    server_name: &str,
+) -> Result<(Server, ssh::Invocation)> {
+    let resolved = connection(
+        store,
+        config,
+        server_name,
+        &ConnOptions::default(),
+        &SessionOptions::default(),
+        &[],
+    );
+    match resolved {
+        Ok((server, Some(invocation))) => Ok((server, invocation)),
+        Ok((server, None)) => {
+            unreachable!("{} resolved for the built-in client", server.server_name)
+        }
+        Err(
+            e @ (JumpError::NotFound { .. }
+            | JumpError::NoMatch { .. }
+            | JumpError::Unresolved { .. }),
+        ) => Err(e),
+        Err(e) => Err(JumpError::Unresolved {
+            name: server_name.to_owned(),
+            reason: e.to_string(),
+        }),
+    }
+}
+
+/// What `resolve --output json` prints
+pub fn resolved<'a>(server: &'a Server, invocation: &ssh::Invocation) -> Result<Resolved<'a>> {
+    let identity = match &server.method {
+        ConnectMethods::SSHKey(key) => Some(config::expand_path(&key.path)?),
+        _ => None,
+    };
+    let proxy_jump = server
+        .options
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("ProxyJump"))
+        .map(|(_, value)| value.as_str())
+        .filter(|value| !value.eq_ignore_ascii_case("none"));
+    Ok(Resolved {
+        server_name: &server.server_name,
+        user: &server.username,
+        address: &server.server_address,
+        port: server.port,
+        method: audit::method_name(&server.method),
+        identity: identity.map(|path| path.to_string_lossy().into_owned()),
+        proxy_jump,
+        source: server
+            .source
+            .as_ref()
+            .map(|source| source.to_string_lossy().into_owned()),
+        argv: invocation.argv(),
+        env: invocation.redacted_env().into_iter().collect(),
+    })
+}
@@ -0,0 +1,195 @@
+//! How `exec`, `cp --to` and `check` spread over many servers: at most
+//! `parallel` at once, at most `bastion_parallel` of those behind any one
+//! `ProxyJump` bastion, whose sshd turns away logins past its
+//! `MaxStartups`, and optionally `delay` apart when starting.
+
+use std::{
+    sync::{mpsc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::Args;
+
+use crate::{config::Config, server::Server};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub parallel: usize,
+    pub bastion_parallel: usize,
+    pub delay: Duration,
+}
+
+impl Limits {
+    /// The limits set in the config, with no delay
+    pub fn configured(config: &Config) -> Limits {
+        Limits {
+            parallel: config.parallel,
+            bastion_parallel: config.bastion_parallel,
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+/// `--parallel`, `--bastion-parallel` and `--delay`, falling back to the
+/// config
+#[derive(Debug, Args)]
+pub struct ScheduleOptions {
+    /// How many servers are worked on at once; `parallel` in the config, 8
+    /// unless set there
+    #[arg(long, short = 'j', visible_alias = "jobs", value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub parallel: Option<u32>,
+    /// How many of those may go through the same ProxyJump bastion;
+    /// `bastion_parallel` in the config, 4 unless set there
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub bastion_parallel: Option<u32>,
+    /// Milliseconds to wait between starting one server and the next
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    pub delay: u64,
+}
+
+impl ScheduleOptions {
+    pub fn limits(&self, config: &Config) -> Limits {
+        let configured = Limits::configured(config);
+        Limits {
+            parallel: self.parallel.map_or(configured.parallel, |n| n as usize),
+            bastion_parallel: self
+                .bastion_parallel
+                .map_or(configured.bastion_parallel, |n| n as usize),
+            delay: Duration::from_millis(self.delay),
+        }
+    }
+}
+
+/// The bastion a connection to `server` goes through: the first hop of its
+/// `ProxyJump` option
+pub fn bastion(server: &Server) -> Option<String> {
+    server
+        .options
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("ProxyJump"))
+        .and_then(|(_, value)| value.split(',').next())
+        .filter(|hop| !hop.is_empty() && !hop.eq_ignore_ascii_case("none"))
+        .map(str::to_owned)
+}
+
+/// What happened to one item, as `run` passes it on
+#[derive(Debug)]
+pub enum Event<R> {
+    Started(usize),
+    Finished(usize, R),
+}
+
+struct State {
+    started: Vec<bool>,
+    /// Items running per bastion, in the order of `bastions`
+    running: Vec<(String, usize)>,
+    not_before: Instant,
+    /// Results sent but not passed to `on_event` yet, which nothing starts
+    /// before, so that it can stop the rest in time
+    unhandled: usize,
+    stopped: bool,
+}
+
+/// Run `task` on items `0..bastions.len()` in order, as far as `limits`
+/// allow, where `bastions` gives each item's bastion. Every start and
+/// result goes to `on_event` on the calling thread; once it returns
+/// `false`, the items not started yet are left out.
+pub fn run<R: Send>(
+    bastions: &[Option<String>],
+    limits: Limits,
+    task: impl Fn(usize) -> R + Sync,
+    mut on_event: impl FnMut(Event<R>) -> bool,
+) {
+    let state = Mutex::new(State {
+        started: vec![false; bastions.len()],
+        running: vec![],
+        not_before: Instant::now(),
+        unhandled: 0,
+        stopped: false,
+    });
+    let changed = Condvar::new();
+    let (events, received) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..limits.parallel.clamp(1, bastions.len().max(1)) {
+            let events = events.clone();
+            let (state, changed, task) = (&state, &changed, &task);
+            scope.spawn(move || {
+                while let Some(i) = next(state, changed, bastions, limits) {
+                    events.send(Event::Started(i)).ok();
+                    let result = task(i);
+                    let mut state = state.lock().unwrap();
+                    if let Some(bastion) = &bastions[i] {
+                        if let Some((_, count)) =
+                            state.running.iter_mut().find(|(name, _)| name == bastion)
+                        {
+                            *count -= 1;
+                        }
+                    }
+                    state.unhandled += 1;
+                    events.send(Event::Finished(i, result)).ok();
+                }
+            });
+        }
+        drop(events);
+        for event in received {
+            let finished = matches!(event, Event::Finished(..));
+            let go_on = on_event(event);
+            let mut state = state.lock().unwrap();
+            state.stopped |= !go_on;
+            if finished {
+                state.unhandled -= 1;
+                changed.notify_all();
+            }
+        }
+    });
+}
+
+/// The next item a worker may start, waiting for a bastion slot or the
+/// delay; `None` once there is nothing left to start
+fn next(
+    state: &Mutex<State>,
+    changed: &Condvar,
+    bastions: &[Option<String>],
+    limits: Limits,
+) -> Option<usize> {
+    let mut state = state.lock().unwrap();
+    loop {
+        if state.stopped || state.started.iter().all(|started| *started) {
+            return None;
+        }
+        if state.unhandled > 0 {
+            state = changed.wait(state).unwrap();
+            continue;
+        }
+        let now = Instant::now();
+        if now < state.not_before {
+            let wait = state.not_before - now;
+            state = changed.wait_timeout(state, wait).unwrap().0;
+            continue;
+        }
+        let free = |bastion: &Option<String>, running: &[(String, usize)]| match bastion {
+            None => true,
+            Some(bastion) => running
+                .iter()
+                .find(|(name, _)| name == bastion)
+                .is_none_or(|(_, count)| *count < limits.bastion_parallel.max(1)),
+        };
+        let pick =
+            (0..bastions.len()).find(|&i| !state.started[i] && free(&bastions[i], &state.running));
+        match pick {
+            Some(i) => {
+                state.started[i] = true;
+                if let Some(bastion) = &bastions[i] {
+                    match state.running.iter_mut().find(|(name, _)| name == bastion) {
+                        Some((_, count)) => *count += 1,
+                        None => state.running.push((bastion.clone(), 1)),
+                    }
+                }
+                state.not_before = now + limits.delay;
+                return Some(i);
+            }
+            None => state = changed.wait(state).unwrap(),
+        }
+    }
+}
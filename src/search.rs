@@ -0,0 +1,117 @@
+//! Loose search for `jump search`: every word must appear, ignoring case,
+//! somewhere in a server's name, tags, address, username or note. Unlike
+//! selectors this needs no field names, for when a server is only half
+//! remembered.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::server::Server;
+
+/// Searched fields, in the order matches in them rank
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    Name,
+    Tags,
+    Address,
+    Username,
+    Note,
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Field::Name => "name",
+            Field::Tags => "tags",
+            Field::Address => "address",
+            Field::Username => "username",
+            Field::Note => "note",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A field some word was found in, and where
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Hit {
+    pub field: Field,
+    pub value: String,
+    /// Byte ranges of the words within `value`, sorted
+    #[serde(skip)]
+    pub ranges: Vec<(usize, usize)>,
+}
+
+#[derive(Debug)]
+pub struct Found {
+    pub server: Server,
+    /// In field order
+    pub hits: Vec<Hit>,
+}
+
+/// The servers containing every word, best first: those matching in their
+/// name, then in their tags, and so on, each group in stored order
+pub fn search(servers: Vec<Server>, words: &[String]) -> Vec<Found> {
+    let mut found: Vec<Found> = servers
+        .into_iter()
+        .filter_map(|server| {
+            let fields = [
+                (Field::Name, server.server_name.clone()),
+                (Field::Tags, server.tags.join(", ")),
+                (Field::Address, server.server_address.clone()),
+                (Field::Username, server.username.clone()),
+                (Field::Note, server.note.clone().unwrap_or_default()),
+            ];
+            let mut hits: Vec<Hit> = vec![];
+            for word in words {
+                let mut found = false;
+                for (field, value) in &fields {
+                    let Some(range) = find(value, word) else {
+                        continue;
+                    };
+                    found = true;
+                    match hits.iter_mut().find(|hit| hit.field == *field) {
+                        Some(hit) => hit.ranges.push(range),
+                        None => hits.push(Hit {
+                            field: *field,
+                            value: value.clone(),
+                            ranges: vec![range],
+                        }),
+                    }
+                }
+                if !found {
+                    return None;
+                }
+            }
+            hits.sort_by_key(|hit| hit.field);
+            for hit in &mut hits {
+                hit.ranges.sort();
+            }
+            Some(Found { server, hits })
+        })
+        .collect();
+    // Stable, so equal ranks keep the stored order
+    found.sort_by_key(|found| found.hits.first().map(|hit| hit.field));
+    found
+}
+
+/// The byte range of the first case-insensitive occurrence of `word`
+fn find(value: &str, word: &str) -> Option<(usize, usize)> {
+    if word.is_empty() {
+        return None;
+    }
+    let same = |a: char, b: char| a.to_lowercase().eq(b.to_lowercase());
+    value.char_indices().find_map(|(start, _)| {
+        let mut rest = value[start..].char_indices();
+        let mut end = start;
+        for expected in word.chars() {
+            let (offset, c) = rest.next()?;
+            if !same(c, expected) {
+                return None;
+            }
+            end = start + offset + c.len_utf8();
+        }
+        Some((start, end))
+    })
+}
@@ -0,0 +1,427 @@
+//! Selectors naming a set of servers, as `ls`, `rm`, `exec` and `check` take
+//! them: space-separated `field:pattern` terms that must all match, such as
+//! `tag:prod user:deploy`. A bare word stands for `name:` it, so a plain
+//! server name still selects that server. Patterns may use `*` and `?`.
+//! `jump set` changes a field on the servers they select.
+
+use std::{fmt, mem, net::IpAddr, path::PathBuf};
+
+use clap::ValueEnum;
+
+use crate::{
+    error::{JumpError, Result},
+    hosts,
+    server::{ConnectMethods, Platform, Server},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Id,
+    Name,
+    User,
+    Address,
+    Port,
+    Tag,
+    Method,
+    Platform,
+}
+
+impl Field {
+    const NAMES: [(&'static str, Field); 9] = [
+        ("id", Field::Id),
+        ("name", Field::Name),
+        ("user", Field::User),
+        ("address", Field::Address),
+        ("host", Field::Address),
+        ("port", Field::Port),
+        ("tag", Field::Tag),
+        ("method", Field::Method),
+        ("platform", Field::Platform),
+    ];
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub field: Field,
+    pub pattern: String,
+    /// The term as written
+    pub text: String,
+}
+
+impl Term {
+    pub fn matches(&self, server: &Server) -> bool {
+        match self.field {
+            Field::Id => server.id.is_some_and(|id| self.pattern == id.to_string()),
+            Field::Name => glob(&self.pattern, &server.server_name),
+            Field::User => glob(&self.pattern, &server.username),
+            Field::Address => glob(
+                &self.pattern.to_ascii_lowercase(),
+                &server.server_address.to_ascii_lowercase(),
+            ),
+            Field::Port => self.pattern == server.port.to_string(),
+            Field::Tag => server.tags.iter().any(|tag| glob(&self.pattern, tag)),
+            Field::Method => self.pattern == method(server),
+            Field::Platform => server
+                .platform
+                .is_some_and(|platform| self.pattern == platform.to_string()),
+        }
+    }
+
+    /// What the server has for the field, as `--explain` shows it
+    pub fn value(&self, server: &Server) -> String {
+        match self.field {
+            Field::Id => format!("id {}", server.id.unwrap_or_default()),
+            Field::Name => format!("name {}", server.server_name),
+            Field::User => format!("user {}", server.username),
+            Field::Address => format!("address {}", server.server_address),
+            Field::Port => format!("port {}", server.port),
+            Field::Tag if server.tags.is_empty() => "no tags".to_owned(),
+            Field::Tag => format!("tags {}", server.tags.join(", ")),
+            Field::Method => format!("method {}", method(server)),
+            Field::Platform => match server.platform {
+                Some(platform) => format!("platform {}", platform),
+                None => "no platform".to_owned(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    pub terms: Vec<Term>,
+    /// The expression as written
+    pub text: String,
+}
+
+impl Selector {
+    pub fn matches(&self, server: &Server) -> bool {
+        self.terms.iter().all(|term| term.matches(server))
+    }
+
+    /// The server this selector stands for, when it is one name without
+    /// wildcards
+    pub fn as_name(&self) -> Option<&str> {
+        match &self.terms[..] {
+            [term] if term.field == Field::Name && !term.pattern.contains(['*', '?']) => {
+                Some(term.pattern.as_str())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+pub fn parse(expr: &str) -> Result<Selector> {
+    let mut terms = vec![];
+    let mut offset = 0;
+    for token in expr.split(' ') {
+        let start = offset;
+        offset += token.len() + 1;
+        if token.is_empty() {
+            continue;
+        }
+        let error = |at: usize, len: usize, reason: String| {
+            JumpError::invalid(
+                "selector",
+                format!(
+                    "{}\n  {}\n  {}{}",
+                    reason,
+                    expr,
+                    " ".repeat(start + at),
+                    "^".repeat(len.max(1))
+                ),
+            )
+        };
+        let Some((name, pattern)) = token.split_once(':') else {
+            terms.push(Term {
+                field: Field::Name,
+                pattern: token.to_owned(),
+                text: token.to_owned(),
+            });
+            continue;
+        };
+        let Some(&(_, field)) = Field::NAMES.iter().find(|(known, _)| *known == name) else {
+            let known = Field::NAMES.map(|(known, _)| known).join(", ");
+            return Err(error(
+                0,
+                name.len(),
+                format!("unknown field `{}`, expected one of {}", name, known),
+            ));
+        };
+        let at = name.len() + 1;
+        if pattern.is_empty() {
+            return Err(error(at - 1, 1, format!("`{}:` needs a value", name)));
+        }
+        let valid = match field {
+            Field::Id => pattern.parse::<i64>().is_ok(),
+            Field::Port => pattern.parse::<u32>().is_ok(),
+            Field::Method => ["ssh-key", "password", "custom"].contains(&pattern),
+            Field::Platform => Platform::from_str(pattern, false).is_ok(),
+            _ => true,
+        };
+        if !valid {
+            let expected = match field {
+                Field::Id => "an id".to_owned(),
+                Field::Port => "a port number".to_owned(),
+                Field::Method => "one of ssh-key, password, custom".to_owned(),
+                _ => format!(
+                    "one of {}",
+                    Platform::value_variants()
+                        .iter()
+                        .map(Platform::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            };
+            return Err(error(
+                at,
+                pattern.len(),
+                format!("`{}` is not {}", pattern, expected),
+            ));
+        }
+        terms.push(Term {
+            field,
+            pattern: pattern.to_owned(),
+            text: token.to_owned(),
+        });
+    }
+    if terms.is_empty() {
+        return Err(JumpError::invalid("selector", "it is empty"));
+    }
+    Ok(Selector {
+        terms,
+        text: expr.trim().to_owned(),
+    })
+}
+
+/// `parse` for the command line
+pub fn parse_arg(expr: &str) -> Result<Selector, String> {
+    parse(expr).map_err(|e| match e {
+        JumpError::InvalidInput { reason, .. } => reason,
+        e => e.to_string(),
+    })
+}
+
+/// The servers any of `selectors` matches, in their stored order; all of
+/// them without selectors
+pub fn filter(servers: Vec<Server>, selectors: &[Selector]) -> Vec<Server> {
+    servers
+        .into_iter()
+        .filter(|server| {
+            selectors.is_empty() || selectors.iter().any(|selector| selector.matches(server))
+        })
+        .collect()
+}
+
+/// `filter`, where selecting nothing is an error: `NotFound` for a plain
+/// name, as before selectors existed
+pub fn select(servers: Vec<Server>, selectors: &[Selector]) -> Result<Vec<Server>> {
+    let selected = filter(servers, selectors);
+    if !selected.is_empty() {
+        return Ok(selected);
+    }
+    Err(match selectors {
+        [selector] if selector.as_name().is_some() => JumpError::NotFound {
+            name: selector.text.clone(),
+        },
+        _ => JumpError::NoMatch {
+            selector: selectors
+                .iter()
+                .map(|selector| format!("`{}`", selector))
+                .collect::<Vec<_>>()
+                .join(" or "),
+        },
+    })
+}
+
+/// The fields `jump set` changes
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum SetField {
+    Username,
+    Address,
+    Port,
+    /// The key file of servers connected to with a key
+    Key,
+}
+
+/// What setting a field did to a server
+#[derive(Debug, Clone, PartialEq)]
+pub enum Setting {
+    /// The field had the value already
+    Unchanged,
+    /// The old value, replaced by the new one
+    Changed(String),
+    /// The field does not apply to the server, for the reason given
+    Skipped(&'static str),
+}
+
+/// Set `field` to `value` on each of `servers`, with what that did to each;
+/// servers the field does not apply to are left as they were
+pub fn set_field(
+    servers: Vec<Server>,
+    field: SetField,
+    value: &str,
+) -> Result<Vec<(Server, Setting)>> {
+    let port = match field {
+        SetField::Port => Some(value.parse::<u32>().map_err(|_| JumpError::InvalidInput {
+            field: "port".to_owned(),
+            reason: format!("`{}` is not a port number", value),
+        })?),
+        _ => None,
+    };
+    let mut set = vec![];
+    for mut server in servers {
+        let old = match (field, &mut server.method) {
+            (SetField::Username, _) => mem::replace(&mut server.username, value.to_owned()),
+            (SetField::Address, _) => mem::replace(&mut server.server_address, value.to_owned()),
+            (SetField::Port, _) => mem::replace(&mut server.port, port.unwrap()).to_string(),
+            (SetField::Key, ConnectMethods::SSHKey(key)) => {
+                mem::replace(&mut key.path, PathBuf::from(value))
+                    .display()
+                    .to_string()
+            }
+            (SetField::Key, method) => {
+                let reason = match method {
+                    ConnectMethods::Password(_) => "it uses a password",
+                    ConnectMethods::Command(_) => "it uses a custom command",
+                    ConnectMethods::SshConfig(_) => "it uses ~/.ssh/config",
+                    _ => "it uses a method this version does not know",
+                };
+                set.push((server, Setting::Skipped(reason)));
+                continue;
+            }
+        };
+        let setting = match old == value {
+            true => Setting::Unchanged,
+            false => Setting::Changed(old),
+        };
+        set.push((server, setting));
+    }
+    Ok(set)
+}
+
+/// An IP network in CIDR notation, such as `10.1.2.0/24` or `fd00::/8`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Subnet {
+    network: IpAddr,
+    prefix: u32,
+}
+
+impl Subnet {
+    pub fn parse(text: &str) -> Result<Subnet> {
+        let error = |reason: String| JumpError::invalid("subnet", reason);
+        let Some((address, prefix)) = text.split_once('/') else {
+            return Err(error(format!(
+                "`{}` has no mask, such as `/24` in `10.1.2.0/24`",
+                text
+            )));
+        };
+        let network = address
+            .parse::<IpAddr>()
+            .map_err(|_| error(format!("`{}` is not an IP address", address)))?;
+        let prefix = prefix
+            .parse::<u32>()
+            .map_err(|_| error(format!("`/{}` is not a mask length", prefix)))?;
+        let bits = width(network);
+        if prefix > bits {
+            let family = match network {
+                IpAddr::V4(_) => "IPv4",
+                IpAddr::V6(_) => "IPv6",
+            };
+            return Err(error(format!(
+                "`/{}` is longer than the {} bits of an {} address",
+                prefix, bits, family
+            )));
+        }
+        Ok(Subnet { network, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        if width(ip) != width(self.network) {
+            return false;
+        }
+        // Shifting by all 128 bits, for `::/0`, overflows
+        let mask = u128::MAX.checked_shl(width(ip) - self.prefix).unwrap_or(0);
+        bits(ip) & mask == bits(self.network) & mask
+    }
+
+    /// Whether the address of `server` is in the subnet; a host name only
+    /// with `resolve`, by any address it resolves to
+    pub fn matches(&self, server: &Server, resolve: bool) -> bool {
+        match server.server_address.parse::<IpAddr>() {
+            Ok(ip) => self.contains(ip),
+            Err(_) if resolve => hosts::resolve(&server.server_address)
+                .into_iter()
+                .any(|ip| self.contains(ip)),
+            Err(_) => false,
+        }
+    }
+}
+
+/// `Subnet::parse` for the command line
+pub fn parse_subnet(text: &str) -> Result<Subnet, String> {
+    Subnet::parse(text).map_err(|e| match e {
+        JumpError::InvalidInput { reason, .. } => reason,
+        e => e.to_string(),
+    })
+}
+
+fn width(ip: IpAddr) -> u32 {
+    match ip {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+fn bits(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(ip) => u32::from(ip).into(),
+        IpAddr::V6(ip) => ip.into(),
+    }
+}
+
+fn method(server: &Server) -> &'static str {
+    match server.method {
+        ConnectMethods::SSHKey(_) => "ssh-key",
+        ConnectMethods::Password(_) => "password",
+        ConnectMethods::Command(_) => "custom",
+        ConnectMethods::SshConfig(_) => "ssh-config",
+        ConnectMethods::Unsupported(_) => "unsupported",
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` is any run of characters and
+/// `?` any one character
+pub fn glob(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), text.chars().collect());
+    // Backtrack to just after the last `*` on a mismatch
+    let (mut p, mut t) = (0, 0);
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,262 @@
+//! `jump serve --stdio`: JSON-RPC 2.0 over stdin and stdout, one message per
+//! line, for editors that list and resolve servers as the user types
+//! without starting jump each time. Results have the shapes of the matching
+//! command's `--output json`.
+
+use std::{
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    config::Config,
+    error::{self, JumpError},
+    output, resolve,
+    schedule::Limits,
+    select,
+    server::{ConnectMethods, SSHKey, Server},
+    store::Store,
+    wol,
+};
+
+/// Raised whenever a method or its params or result change shape, so that
+/// clients can tell from `hello` what they talk to
+pub const PROTOCOL: u32 = 1;
+
+/// Every method there is, as `hello` lists them
+pub const METHODS: [&str; 6] = ["hello", "list", "resolve", "add", "remove", "check"];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// A method that failed gets this less jump's exit code for the error, as
+/// -32003 for a server that is not found
+const FAILED: i64 = -32000;
+
+/// Why a call failed
+#[derive(Debug)]
+pub enum Error {
+    UnknownMethod(String),
+    InvalidParams(String),
+    Failed(anyhow::Error),
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(error: anyhow::Error) -> Error {
+        Error::Failed(error)
+    }
+}
+
+impl From<JumpError> for Error {
+    fn from(error: JumpError) -> Error {
+        Error::Failed(error.into())
+    }
+}
+
+/// The params of `list` and `check`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Selection {
+    /// Selectors as `ls` takes them, any of which a server matches
+    #[serde(default)]
+    filter: Vec<String>,
+    #[serde(default)]
+    include_secrets: bool,
+    /// Seconds a `check` waits for each server
+    timeout: Option<u64>,
+}
+
+/// The params of `resolve` and `remove`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Named {
+    name: String,
+}
+
+/// One call, answered with what the matching command prints for
+/// `--output json`
+pub fn call(store: &Store, config: &Config, method: &str, params: Value) -> Result<Value, Error> {
+    fn to_value(value: impl serde::Serialize) -> anyhow::Result<Value> {
+        Ok(serde_json::to_value(value)?)
+    }
+    match method {
+        "hello" => Ok(hello(store.is_read_only())),
+        "list" | "check" => {
+            let selection = self::params::<Selection>(params)?;
+            let selectors = selection
+                .filter
+                .iter()
+                .map(|filter| select::parse(filter))
+                .collect::<error::Result<Vec<_>>>()?;
+            let servers = select::filter(store.list()?, &selectors);
+            if method == "list" {
+                return Ok(to_value(
+                    servers
+                        .iter()
+                        .map(|server| output::server(server, selection.include_secrets))
+                        .collect::<Vec<_>>(),
+                )?);
+            }
+            let timeout = Duration::from_secs(selection.timeout.unwrap_or(5));
+            let probes = wol::probe_all(&servers, timeout, Limits::configured(config));
+            Ok(to_value(
+                servers
+                    .iter()
+                    .zip(probes)
+                    .map(|(server, (up, _))| output::Check {
+                        server_name: &server.server_name,
+                        server_address: &server.server_address,
+                        port: server.port,
+                        up,
+                    })
+                    .collect::<Vec<_>>(),
+            )?)
+        }
+        "resolve" => {
+            let name = self::params::<Named>(params)?.name;
+            let (server, invocation) = resolve::for_tools(store, config, &name)?;
+            Ok(to_value(resolve::resolved(&server, &invocation)?)?)
+        }
+        "add" => {
+            let server = server_from_params(config, params)?;
+            store.writable()?;
+            store.add_checked(&server, config.strict_duplicates)?;
+            Ok(output::server(&store.get(&server.server_name)?, false))
+        }
+        "remove" => {
+            let name = self::params::<Named>(params)?.name;
+            let server = store.get(&name)?;
+            store.remove(&name)?;
+            Ok(output::server(&server, false))
+        }
+        _ => Err(Error::UnknownMethod(method.to_owned())),
+    }
+}
+
+/// The server `add` is given: a server as `show --output json` prints it,
+/// with anything beyond the name, user, address and method left out taking
+/// the value `jump add` would give it
+pub fn server_from_params(config: &Config, params: Value) -> Result<Server, Error> {
+    let Value::Object(given) = params else {
+        return Err(Error::InvalidParams(
+            "add takes a server as an object".to_owned(),
+        ));
+    };
+    let required = ["server_name", "username", "server_address", "method"];
+    if let Some(missing) = required.iter().find(|field| !given.contains_key(**field)) {
+        return Err(Error::InvalidParams(format!("missing field `{}`", missing)));
+    }
+    let defaults = Server::new(
+        String::new(),
+        String::new(),
+        String::new(),
+        config.default_port,
+        ConnectMethods::SSHKey(SSHKey {
+            path: PathBuf::from(&config.default_identity),
+        }),
+    );
+    let mut server = serde_json::to_value(defaults).map_err(anyhow::Error::from)?;
+    for (field, value) in given {
+        server[field] = value;
+    }
+    let mut server = self::params::<Server>(server)?;
+    // Kept by the store, not given
+    server.id = None;
+    server.order_index = None;
+    server.created_at = None;
+    server.updated_at = None;
+    server.host_key = None;
+    Ok(server)
+}
+
+/// The result of `hello`
+pub fn hello(read_only: bool) -> Value {
+    json!({
+        "protocol": PROTOCOL,
+        "version": env!("CARGO_PKG_VERSION"),
+        "methods": METHODS,
+        "read_only": read_only,
+    })
+}
+
+/// The params of a call as `T`, an empty object when they were left out
+pub fn params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, Error> {
+    let params = match params {
+        Value::Null => json!({}),
+        params => params,
+    };
+    serde_json::from_value(params).map_err(|e| Error::InvalidParams(e.to_string()))
+}
+
+/// Answer each request on `input` with `call(method, params)` until it
+/// ends. Notifications, requests without an `id`, are run but not answered.
+pub fn run(
+    input: impl BufRead,
+    mut output: impl Write,
+    mut call: impl FnMut(&str, Value) -> Result<Value, Error>,
+) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Err(e) => Some(failure(Value::Null, PARSE_ERROR, e.to_string(), None)),
+            Ok(request) => answer(request, &mut call),
+        };
+        if let Some(response) = response {
+            writeln!(output, "{}", response)?;
+            output.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn answer(
+    mut request: Value,
+    call: &mut impl FnMut(&str, Value) -> Result<Value, Error>,
+) -> Option<Value> {
+    let id = request.get("id").cloned();
+    let reply_to = id.clone().unwrap_or(Value::Null);
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        let reason = "a request is an object with a `method`".to_owned();
+        return Some(failure(reply_to, INVALID_REQUEST, reason, None));
+    };
+    let method = method.to_owned();
+    let params = request
+        .get_mut("params")
+        .map(Value::take)
+        .unwrap_or_default();
+    let result = call(&method, params);
+    id.as_ref()?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": reply_to, "result": result }),
+        Err(Error::UnknownMethod(method)) => failure(
+            reply_to,
+            METHOD_NOT_FOUND,
+            format!("there is no method {}", method),
+            None,
+        ),
+        Err(Error::InvalidParams(reason)) => failure(reply_to, INVALID_PARAMS, reason, None),
+        Err(Error::Failed(e)) => {
+            let kind = e
+                .downcast_ref::<JumpError>()
+                .map_or("failed", JumpError::kind);
+            let code = FAILED - i64::from(error::exit_code(&e));
+            failure(reply_to, code, format!("{:#}", e), Some(kind))
+        }
+    })
+}
+
+fn failure(id: Value, code: i64, message: String, kind: Option<&str>) -> Value {
+    let mut error = json!({ "code": code, "message": message });
+    if let Some(kind) = kind {
+        error["data"] = json!({ "kind": kind });
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
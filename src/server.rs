@@ -0,0 +1,527 @@
+use std::{convert::Infallible, fmt::Display, path::PathBuf};
+
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    access, audit, doctor,
+    knock::{self, Knock},
+    proxy,
+    ssh::shell_quote,
+    wol,
+};
+
+#[derive(Debug, Clone, Args, Serialize, Deserialize)]
+pub struct Server {
+    /// Assigned by the store and never reused, even after the server is
+    /// removed; `None` until the server is stored
+    #[arg(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    /// Where `ls` lists the server unless sorted otherwise, as set by `jump
+    /// move`; `None` until stored, which puts it after every other server
+    #[arg(skip)]
+    #[serde(skip)]
+    pub order_index: Option<i64>,
+    /// When the server was added, in seconds since the epoch; `None` for
+    /// servers stored before this was kept track of
+    #[arg(skip)]
+    #[serde(default)]
+    pub created_at: Option<i64>,
+    /// When the server, or its options, environment, tags or addresses,
+    /// last changed
+    #[arg(skip)]
+    #[serde(default)]
+    pub updated_at: Option<i64>,
+    pub server_name: String,
+    pub username: String,
+    // #[arg(value_parser  = parse_ip)]
+    pub server_address: String,
+    #[arg(default_value = "22")]
+    pub port: u32,
+    /// X11 forwarding used by `conn`
+    #[arg(long, value_enum, default_value_t = X11::Off)]
+    pub x11: X11,
+    /// Forward the local ssh agent on `conn`; only use for trusted hosts
+    #[arg(long)]
+    pub forward_agent: bool,
+    /// Compress traffic to this server on `conn`, `exec` and `cp`
+    #[arg(long)]
+    pub compression: bool,
+    /// Share one connection between `conn`, `exec` and `cp` calls through
+    /// an ssh control socket
+    #[arg(long)]
+    #[serde(default)]
+    pub multiplex: bool,
+    /// Command `conn` runs on login, e.g. `tmux attach || tmux new`
+    #[arg(long)]
+    pub remote_command: Option<String>,
+    /// Remote directory `conn` starts the login shell in; ignored when a
+    /// remote command is set
+    #[arg(long)]
+    pub workdir: Option<String>,
+    /// Local command run before `conn`; a failure aborts the connection
+    #[arg(long)]
+    pub pre_connect: Option<String>,
+    /// Local command run after a `conn` session ends
+    #[arg(long)]
+    pub post_connect: Option<String>,
+    /// Local command ssh runs once the connection is up, as its
+    /// `LocalCommand`; ssh expands `%h`, `%p`, `%r` and `%n` in it
+    #[arg(long)]
+    pub local_command: Option<String>,
+    /// MAC address used to wake the server, e.g. `aa:bb:cc:dd:ee:ff`
+    #[arg(long, value_parser = parse_mac_arg)]
+    pub mac: Option<String>,
+    /// Broadcast address the Wake-on-LAN packet is sent to
+    #[arg(long, requires = "mac")]
+    pub broadcast: Option<String>,
+    /// Wake the server automatically when its ssh port does not answer
+    #[arg(long, requires = "mac")]
+    pub wake_on_fail: bool,
+    /// Port `conn` knocks on first, as `PORT` or `PORT/udp`; repeat for a
+    /// sequence, knocked in order
+    #[arg(long = "knock", value_name = "PORT[/PROTO]", value_parser = knock::parse_arg)]
+    #[serde(default)]
+    pub knock: Vec<Knock>,
+    /// Milliseconds between knocks, 200 unless set
+    #[arg(long, value_name = "MS", requires = "knock")]
+    #[serde(default)]
+    pub knock_delay: Option<u64>,
+    /// Record every `conn` session, as if `--record` was given
+    #[arg(long)]
+    pub always_record: bool,
+    /// Whether ssh only offers the stored key, with `IdentitiesOnly=yes`,
+    /// rather than the agent's keys first; `--no-identities-only` turns it
+    /// off
+    #[arg(long = "no-identities-only", action = ArgAction::SetFalse)]
+    #[serde(default = "identities_only")]
+    pub identities_only: bool,
+    /// What the server runs: jump starts no shell wrapper for the workdir on
+    /// Windows, and runs `exec` with a tty and `cp` with legacy scp on
+    /// network appliances
+    #[arg(long, value_enum)]
+    #[serde(default)]
+    pub platform: Option<Platform>,
+    /// HTTP proxy `conn`, `exec` and `cp` tunnel through, as `host:port`
+    #[arg(long, value_name = "HOST:PORT", value_parser = proxy::parse_arg)]
+    pub proxy: Option<String>,
+    /// Free-form note about the server, found by `jump search`
+    #[arg(long)]
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Label to select the server by, as `tag:<TAG>`; repeat for more
+    #[arg(long = "tag", value_name = "TAG", value_parser = parse_tag)]
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// When the server expires, in seconds since the epoch; given as an age
+    /// from now like `24h` or a UTC date like `2024-03-01 09:30`
+    #[arg(long = "expires", value_name = "AGE|DATE", value_parser = parse_expiry)]
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+    /// Longest a `conn` session may last, in seconds, given as an age like
+    /// `2h`; jump warns before the end and then disconnects
+    #[arg(long, value_name = "AGE", value_parser = parse_max_session)]
+    #[serde(default)]
+    pub max_session: Option<u64>,
+    /// When the server may be connected to, as `DAYS OPEN-CLOSE ZONE`, e.g.
+    /// `Mon-Fri 09:00-17:00 Europe/Berlin`; outside it jump refuses
+    /// without `--override-window`
+    #[arg(long, value_name = "WINDOW", value_parser = access::parse_arg)]
+    #[serde(default)]
+    pub access_window: Option<String>,
+    /// Stored server whose console server `jump console` attaches through,
+    /// for devices reached over their serial port
+    #[arg(long, value_name = "SERVER", requires = "console_target")]
+    #[serde(default)]
+    pub console_server: Option<String>,
+    /// Remote command that makes the console server attach to the device's
+    /// port, e.g. `connect rack3-sw1`
+    #[arg(long, value_name = "COMMAND", requires = "console_server")]
+    #[serde(default)]
+    pub console_target: Option<String>,
+    /// The host key recorded on first use, as `<type> SHA256:<fingerprint>`
+    #[arg(skip)]
+    #[serde(default)]
+    pub host_key: Option<String>,
+    /// Addresses `conn` falls back to, in order, when `server_address` does
+    /// not accept connections
+    #[arg(skip)]
+    #[serde(default)]
+    pub addresses: Vec<Address>,
+    /// Stored ssh options, passed as `-o Key=Value`
+    #[arg(skip)]
+    pub options: Vec<(String, String)>,
+    /// Stored environment variables sent with `SendEnv`
+    #[arg(skip)]
+    pub env: Vec<(String, String)>,
+    /// The `include` file the server was read from, rather than the store
+    #[arg(skip)]
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+    /// Taken by `add` from its method subcommand
+    #[arg(skip = ConnectMethods::SSHKey(SSHKey { path: PathBuf::new() }))]
+    pub method: ConnectMethods,
+}
+
+impl Server {
+    /// A server with everything beyond the connection details left unset
+    pub fn new(
+        server_name: String,
+        username: String,
+        server_address: String,
+        port: u32,
+        method: ConnectMethods,
+    ) -> Server {
+        Server {
+            id: None,
+            order_index: None,
+            created_at: None,
+            updated_at: None,
+            server_name,
+            username,
+            server_address,
+            port,
+            x11: X11::Off,
+            forward_agent: false,
+            compression: false,
+            remote_command: None,
+            workdir: None,
+            pre_connect: None,
+            post_connect: None,
+            local_command: None,
+            multiplex: false,
+            host_key: None,
+            mac: None,
+            broadcast: None,
+            wake_on_fail: false,
+            knock: vec![],
+            knock_delay: None,
+            always_record: false,
+            identities_only: true,
+            platform: None,
+            proxy: None,
+            note: None,
+            tags: vec![],
+            expires_at: None,
+            max_session: None,
+            access_window: None,
+            console_server: None,
+            console_target: None,
+            addresses: vec![],
+            options: vec![],
+            env: vec![],
+            source: None,
+            method,
+        }
+    }
+}
+
+fn identities_only() -> bool {
+    true
+}
+
+impl Server {
+    /// Whether both point at the same sshd: the same address, ignoring
+    /// case, and port
+    pub fn same_endpoint(&self, other: &Server) -> bool {
+        self.port == other.port
+            && self
+                .server_address
+                .eq_ignore_ascii_case(&other.server_address)
+    }
+}
+
+impl Server {
+    /// The `~/.ssh/config` alias of a server added with `--ssh-config`,
+    /// which ssh connects to on its own settings
+    pub fn ssh_config_alias(&self) -> Option<&str> {
+        match &self.method {
+            ConnectMethods::SshConfig(config) => Some(&config.alias),
+            _ => None,
+        }
+    }
+}
+
+impl Server {
+    /// Whether the server's expiry has passed by `now`
+    pub fn expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// Groups of two or more servers pointing at the same sshd, in the order
+/// their first member is listed
+pub fn same_endpoint_groups(servers: &[Server]) -> Vec<Vec<&Server>> {
+    let mut groups: Vec<Vec<&Server>> = vec![];
+    for server in servers {
+        match groups
+            .iter_mut()
+            .find(|group| group[0].same_endpoint(server))
+        {
+            Some(group) => group.push(server),
+            None => groups.push(vec![server]),
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+/// Another address the server is reachable at, such as a VPN or public IP
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Address {
+    pub address: String,
+    /// Name to force this address with, as `conn --address-label <LABEL>`
+    pub label: Option<String>,
+}
+
+impl Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.label {
+            Some(label) => write!(f, "{} ({})", self.address, label),
+            None => write!(f, "{}", self.address),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+pub enum X11 {
+    Off,
+    Trusted,
+    Untrusted,
+}
+
+impl Display for X11 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_possible_value().unwrap().get_name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+pub enum Platform {
+    Linux,
+    Bsd,
+    Windows,
+    /// Routers, switches and other appliances with a restricted shell
+    NetworkOs,
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_possible_value().unwrap().get_name())
+    }
+}
+
+#[derive(Debug, Clone, Subcommand, Serialize, Deserialize)]
+pub enum ConnectMethods {
+    SSHKey(SSHKey),
+    Password(Password),
+    /// Connect by running a local command instead of ssh, such as
+    /// `aws ssm start-session --target {address}`
+    Command(Custom),
+    /// Connect to a `Host` alias of `~/.ssh/config`, leaving everything
+    /// else to ssh; set by `add --ssh-config`
+    #[command(skip)]
+    SshConfig(SshConfig),
+    /// A method written by another version of jump that this one does not
+    /// know: the stored `kind:value` as a string, or the exported JSON
+    /// Kept as it is, so that writing the server back loses nothing.
+    #[command(skip)]
+    #[serde(untagged)]
+    Unsupported(serde_json::Value),
+}
+
+#[derive(Debug, Clone, Args, Serialize, Deserialize)]
+pub struct SSHKey {
+    #[arg(value_parser = parse_ssh_path, default_value = "~/.ssh/id_rsa")]
+    pub path: PathBuf,
+}
+
+fn parse_ssh_path(str: &str) -> Result<PathBuf, Infallible> {
+    Ok(str.into())
+}
+
+#[derive(Debug, Clone, Parser, Serialize, Deserialize)]
+pub struct Password {
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Args, Serialize, Deserialize)]
+pub struct Custom {
+    /// Run through the local shell, with {user}, {address} and {port}
+    /// replaced by the server's, shell-quoted
+    #[arg(value_parser = parse_template)]
+    pub command: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshConfig {
+    pub alias: String,
+}
+
+/// The placeholders a custom command may contain
+pub const PLACEHOLDERS: [&str; 3] = ["user", "address", "port"];
+
+impl Custom {
+    /// The command with the placeholders filled in for `server`
+    pub fn expand(&self, server: &Server) -> Result<String, String> {
+        let mut expanded = String::new();
+        let mut rest = self.command.as_str();
+        while let Some(start) = rest.find('{') {
+            let len = rest[start..]
+                .find('}')
+                .ok_or(format!("`{}` has an unclosed `{{`", self.command))?;
+            let name = &rest[start + 1..start + len];
+            let value = match name {
+                "user" => shell_quote(&server.username),
+                "address" => shell_quote(&server.server_address),
+                "port" => server.port.to_string(),
+                _ => {
+                    return Err(format!(
+                        "unknown placeholder `{{{}}}`, expected one of {}",
+                        name,
+                        PLACEHOLDERS.map(|name| format!("{{{}}}", name)).join(", ")
+                    ))
+                }
+            };
+            expanded.push_str(&rest[..start]);
+            expanded.push_str(&value);
+            rest = &rest[start + len + 1..];
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+}
+
+fn parse_template(command: &str) -> Result<String, String> {
+    let custom = Custom {
+        command: command.to_owned(),
+    };
+    let server = Server::new(
+        String::new(),
+        String::new(),
+        String::new(),
+        0,
+        ConnectMethods::Password(Password {
+            password: String::new(),
+        }),
+    );
+    custom.expand(&server)?;
+    Ok(command.to_owned())
+}
+
+impl Display for ConnectMethods {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectMethods::SSHKey(key) => {
+                write!(f, "ssh:{}", key.path.to_str().unwrap())
+            }
+            ConnectMethods::Password(p) => write!(f, "pass:{}", p.password),
+            ConnectMethods::Command(custom) => write!(f, "cmd:{}", custom.command),
+            ConnectMethods::SshConfig(config) => write!(f, "config:{}", config.alias),
+            ConnectMethods::Unsupported(serde_json::Value::String(method)) => {
+                write!(f, "{}", method)
+            }
+            ConnectMethods::Unsupported(method) => write!(f, "{}", method),
+        }
+    }
+}
+
+impl From<String> for ConnectMethods {
+    /// The stored `kind:value`, in the current format or an older one
+    /// `jump doctor` rewrites, and `Unsupported` for any other
+    fn from(method: String) -> Self {
+        let Some(current) = doctor::current_method(&method) else {
+            // An imported method is stored as its JSON, and read back as such
+            let value = serde_json::from_str::<serde_json::Value>(&method)
+                .ok()
+                .filter(serde_json::Value::is_object);
+            return ConnectMethods::Unsupported(value.unwrap_or(serde_json::Value::String(method)));
+        };
+        // Only the first colon separates the kind; Windows key paths
+        // (`C:\...`) and passwords may contain more
+        let (kind, value) = current
+            .split_once(':')
+            .expect("current methods have a kind");
+        match kind {
+            "ssh" => ConnectMethods::SSHKey(SSHKey {
+                path: PathBuf::from(value),
+            }),
+            "cmd" => ConnectMethods::Command(Custom {
+                command: value.to_owned(),
+            }),
+            "config" => ConnectMethods::SshConfig(SshConfig {
+                alias: value.to_owned(),
+            }),
+            _ => ConnectMethods::Password(Password {
+                password: value.to_owned(),
+            }),
+        }
+    }
+}
+
+impl ConnectMethods {
+    /// What `ls` and `show` print for a method this version does not know
+    pub const UNSUPPORTED: &'static str = "unsupported (added by newer jump?)";
+
+    pub fn is_supported(&self) -> bool {
+        !matches!(self, ConnectMethods::Unsupported(_))
+    }
+}
+
+pub fn parse_env_pair(pair: &str) -> Result<(String, String), String> {
+    let (name, value) = pair
+        .split_once('=')
+        .ok_or(format!("`{}` is not of the form KEY=VAL", pair))?;
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!(
+            "`{}` is not a valid environment variable name",
+            name
+        ));
+    }
+    Ok((name.to_owned(), value.to_owned()))
+}
+
+/// Tags are single words, kept apart by the commas `show` lists them with
+pub fn parse_tag(tag: &str) -> Result<String, String> {
+    if tag.is_empty() || tag.contains(|c: char| c.is_whitespace() || c == ',') {
+        return Err(format!(
+            "`{}` must be one word without commas or spaces",
+            tag
+        ));
+    }
+    Ok(tag.to_owned())
+}
+
+fn parse_expiry(expiry: &str) -> Result<i64, String> {
+    match audit::parse_age(expiry) {
+        Ok(age) => Ok(audit::now() + age),
+        Err(_) => audit::parse_time(expiry).map_err(|_| {
+            format!(
+                "`{}` is neither an age like 24h nor a date like 2024-03-01",
+                expiry
+            )
+        }),
+    }
+}
+
+fn parse_max_session(age: &str) -> Result<u64, String> {
+    match audit::parse_age(age)? {
+        secs if secs > 0 => Ok(secs as u64),
+        _ => Err(format!("`{}` is not a duration a session could last", age)),
+    }
+}
+
+fn parse_mac_arg(mac: &str) -> Result<String, String> {
+    let bytes = wol::parse_mac(mac).map_err(|e| e.to_string())?;
+    Ok(bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(":"))
+}
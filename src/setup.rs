@@ -0,0 +1,141 @@
+//! The setup offered on the first interactive run: creating the store,
+//! taking the hosts of ~/.ssh/config, installing completions and adding a
+//! first server, each asked about and skippable.
+
+use std::{fs, path::Path};
+
+use anyhow::Result;
+use clap_complete::Shell;
+
+use crate::{
+    config::{self, Config},
+    defaults,
+    error::JumpError,
+    info, prompt,
+    server::{ConnectMethods, SSHKey, Server, SshConfig},
+    ssh_config,
+    store::Store,
+    warn,
+};
+
+/// Walk through the setup for the store at `path`, installing what
+/// `completion_script` gives for the user's shell, and record that it was
+/// offered. The store, when it was created.
+pub fn run(
+    config: &Config,
+    path: &Path,
+    completion_script: impl FnOnce(Shell) -> Result<String>,
+) -> Result<Option<Store>> {
+    let store = match prompt::confirm(
+        &format!("Create the store at {}?", path.display()),
+        "`jump init`",
+    )? {
+        true => {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let store = Store::open(path)?;
+            store.initialize()?;
+            Some(store)
+        }
+        false => None,
+    };
+    if let Some(store) = &store {
+        let aliases = ssh_config::aliases(&ssh_config::load().unwrap_or_default());
+        let question = format!("Add the hosts of ~/.ssh/config ({})?", aliases.join(", "));
+        if !aliases.is_empty() && prompt::confirm(&question, "`jump add --ssh-config`")? {
+            for alias in aliases {
+                let mut server = Server::new(
+                    alias.clone(),
+                    String::new(),
+                    String::new(),
+                    config.default_port,
+                    ConnectMethods::SshConfig(SshConfig {
+                        alias: alias.clone(),
+                    }),
+                );
+                let added = ssh_config::connect_by(&mut server, alias)
+                    .and_then(|()| store.add_checked(&server, config.strict_duplicates));
+                match added {
+                    Ok(()) => info!("added {}", server.server_name),
+                    Err(e) => warn!("could not add {}: {}", server.server_name, e),
+                }
+            }
+        }
+    }
+    if let Some((shell, file)) =
+        Shell::from_env().and_then(|shell| Some((shell, completion_file(shell)?)))
+    {
+        let file = config::expand_path(Path::new(file))?;
+        let question = format!("Install {} completions to {}?", shell, file.display());
+        if prompt::confirm(&question, "`jump completions`")? {
+            if let Some(dir) = file.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(&file, completion_script(shell)?)?;
+            info!("installed {}", file.display());
+            if shell == Shell::Zsh {
+                info!("for zsh to find it, add `fpath+=~/.zfunc` to ~/.zshrc before `compinit`");
+            }
+        }
+    }
+    if let Some(store) = &store {
+        if prompt::confirm("Add a server now?", "`jump add`")? {
+            add_wizard(store, config)?;
+        }
+    }
+    Config::set("setup_done", "true")?;
+    Ok(store)
+}
+
+/// Where the setup installs completions for `shell`, a file of their own
+/// that the shell loads by itself
+fn completion_file(shell: Shell) -> Option<&'static str> {
+    match shell {
+        Shell::Bash => Some("~/.local/share/bash-completion/completions/jump"),
+        Shell::Zsh => Some("~/.zfunc/_jump"),
+        Shell::Fish => Some("~/.config/fish/completions/jump.fish"),
+        _ => None,
+    }
+}
+
+/// Ask for the name, address, user, port and key of a server; the config
+/// and its `[defaults]` rules suggest the last three
+fn add_wizard(store: &Store, config: &Config) -> Result<()> {
+    let ask = |question: &str, default: String| -> Result<String> {
+        let question = match default.is_empty() {
+            true => question.to_owned(),
+            false => format!("{} [{}]", question, default),
+        };
+        let answer = prompt::answer(&question, "`jump add`")?;
+        Ok(if answer.is_empty() { default } else { answer })
+    };
+    let name = ask("Name:", String::new())?;
+    let address = ask("Address:", String::new())?;
+    if name.is_empty() || address.is_empty() {
+        info!("no server added");
+        return Ok(());
+    }
+    let resolved = defaults::resolve(config, &address, &[]);
+    let user = resolved
+        .user
+        .map(|user| user.value)
+        .or_else(|| std::env::var("USER").ok())
+        .unwrap_or_default();
+    let user = ask("User:", user)?;
+    let port = ask("Port:", resolved.port.value.to_string())?;
+    let port = port
+        .parse()
+        .map_err(|_| JumpError::invalid("port", format!("`{}` is not a port", port)))?;
+    let key = ask("Key file:", resolved.identity.value)?;
+    let server = Server::new(
+        name,
+        user,
+        address,
+        port,
+        ConnectMethods::SSHKey(SSHKey { path: key.into() }),
+    );
+    store.add_checked(&server, config.strict_duplicates)?;
+    info!("added {}", server.server_name);
+    Ok(())
+}
@@ -0,0 +1,235 @@
+//! What jump does with SIGINT, SIGTERM and SIGHUP around an ssh session:
+//! pass them on to the session and keep going, so that the post-connect
+//! hooks, the recording and the connection log still happen when the
+//! terminal goes away. On unix the session gets a process group of its own,
+//! handed the terminal when jump has it, so Ctrl-C reaches ssh once instead
+//! of through both processes.
+
+use std::{
+    io,
+    process::{Child, Command},
+    time::Duration,
+};
+
+#[cfg(unix)]
+use std::sync::{
+    atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering},
+    Arc, OnceLock,
+};
+
+#[cfg(unix)]
+use nix::{
+    sys::signal::{killpg, Signal},
+    unistd::{getpgrp, Pid},
+};
+
+#[cfg(unix)]
+const CAUGHT: [i32; 3] = [
+    signal_hook::consts::SIGINT,
+    signal_hook::consts::SIGTERM,
+    signal_hook::consts::SIGHUP,
+];
+
+/// The process group of the session running, 0 between sessions
+#[cfg(unix)]
+static SESSION: AtomicI32 = AtomicI32::new(0);
+
+/// Whether the session running was handed the terminal, to be taken back
+#[cfg(unix)]
+static FOREGROUND: AtomicBool = AtomicBool::new(false);
+
+/// Whether the signals kill jump, as they did before it caught them;
+/// unregistering a handler leaves them ignored instead
+#[cfg(unix)]
+static FATAL: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+/// Catches the signals until dropped, passing them on to the session
+/// running, if any
+pub struct Catching {
+    #[cfg(unix)]
+    handle: signal_hook::iterator::Handle,
+    #[cfg(unix)]
+    last: Arc<AtomicI32>,
+    #[cfg(unix)]
+    received: Arc<AtomicUsize>,
+    #[cfg(unix)]
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(unix)]
+pub fn catch() -> io::Result<Catching> {
+    if let Some(fatal) = FATAL.get() {
+        fatal.store(false, Ordering::SeqCst);
+    }
+    let mut signals = signal_hook::iterator::Signals::new(CAUGHT)?;
+    let handle = signals.handle();
+    let last = Arc::new(AtomicI32::new(0));
+    let caught = last.clone();
+    let received = Arc::new(AtomicUsize::new(0));
+    let counted = received.clone();
+    let thread = std::thread::spawn(move || {
+        for signal in signals.forever() {
+            let group = SESSION.load(Ordering::SeqCst);
+            if let (true, Ok(forwarded)) = (group != 0, Signal::try_from(signal)) {
+                killpg(Pid::from_raw(group), forwarded).ok();
+            }
+            caught.store(signal, Ordering::SeqCst);
+            counted.fetch_add(1, Ordering::SeqCst);
+        }
+    });
+    Ok(Catching {
+        handle,
+        last,
+        received,
+        thread: Some(thread),
+    })
+}
+
+#[cfg(not(unix))]
+pub fn catch() -> io::Result<Catching> {
+    Ok(Catching {})
+}
+
+impl Catching {
+    /// The last signal that reached jump, if any
+    #[cfg(unix)]
+    pub fn caught(&self) -> Option<i32> {
+        Some(self.last.load(Ordering::SeqCst)).filter(|signal| *signal != 0)
+    }
+
+    #[cfg(not(unix))]
+    pub fn caught(&self) -> Option<i32> {
+        None
+    }
+
+    /// How many signals reached jump so far
+    #[cfg(unix)]
+    pub fn received(&self) -> usize {
+        self.received.load(Ordering::SeqCst)
+    }
+
+    #[cfg(not(unix))]
+    pub fn received(&self) -> usize {
+        0
+    }
+
+    /// Whether the last signal was Ctrl-C, rather than one ending jump
+    #[cfg(unix)]
+    pub fn interrupted(&self) -> bool {
+        self.caught() == Some(signal_hook::consts::SIGINT)
+    }
+
+    #[cfg(not(unix))]
+    pub fn interrupted(&self) -> bool {
+        false
+    }
+
+    /// Whether the terminal hung up, after which writing to it fails
+    #[cfg(unix)]
+    pub fn hung_up(&self) -> bool {
+        self.caught() == Some(signal_hook::consts::SIGHUP)
+    }
+
+    #[cfg(not(unix))]
+    pub fn hung_up(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Catching {
+    fn drop(&mut self) {
+        self.handle.close();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+        let fatal = FATAL.get_or_init(|| {
+            let fatal = Arc::new(AtomicBool::new(true));
+            for signal in CAUGHT {
+                signal_hook::flag::register_conditional_default(signal, fatal.clone()).ok();
+            }
+            fatal
+        });
+        fatal.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Start a session, in a process group of its own given the terminal when
+/// jump is in the foreground; `ended` undoes that once it exited
+#[cfg(unix)]
+pub fn spawn(command: &mut Command) -> io::Result<Child> {
+    use std::{io::IsTerminal, os::unix::process::CommandExt};
+
+    let child = command.process_group(0).spawn()?;
+    let group = Pid::from_raw(child.id() as i32);
+    // Not when jump itself runs in the background
+    let foreground = io::stdin().is_terminal()
+        && nix::unistd::tcgetpgrp(0).is_ok_and(|owner| owner == getpgrp())
+        && hand_terminal(group);
+    FOREGROUND.store(foreground, Ordering::SeqCst);
+    SESSION.store(group.as_raw(), Ordering::SeqCst);
+    // ssh stops when it reads the terminal before it was handed over
+    killpg(group, Signal::SIGCONT).ok();
+    Ok(child)
+}
+
+#[cfg(not(unix))]
+pub fn spawn(command: &mut Command) -> io::Result<Child> {
+    command.spawn()
+}
+
+/// Stop passing signals on to the session, and take the terminal back
+pub fn ended() {
+    #[cfg(unix)]
+    {
+        SESSION.store(0, Ordering::SeqCst);
+        if FOREGROUND.swap(false, Ordering::SeqCst) {
+            hand_terminal(getpgrp());
+        }
+    }
+}
+
+/// End the session running, if any, as for its `max_session`: SIGTERM to
+/// its process group, then SIGKILL when it is still there after `grace`
+#[cfg(unix)]
+pub fn end_session(grace: Duration) {
+    let group = SESSION.load(Ordering::SeqCst);
+    if group == 0 {
+        return;
+    }
+    killpg(Pid::from_raw(group), Signal::SIGTERM).ok();
+    let until = std::time::Instant::now() + grace;
+    while std::time::Instant::now() < until {
+        if SESSION.load(Ordering::SeqCst) != group {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    if SESSION.load(Ordering::SeqCst) == group {
+        killpg(Pid::from_raw(group), Signal::SIGKILL).ok();
+    }
+}
+
+#[cfg(not(unix))]
+pub fn end_session(_grace: Duration) {}
+
+/// Make `group` the foreground process group of the terminal on stdin,
+/// with SIGTTOU held back, which would stop jump for trying from the
+/// background
+#[cfg(unix)]
+fn hand_terminal(group: Pid) -> bool {
+    use nix::{
+        sys::signal::{pthread_sigmask, SigSet, SigmaskHow},
+        unistd::tcsetpgrp,
+    };
+
+    let mut ttou = SigSet::empty();
+    ttou.add(Signal::SIGTTOU);
+    let mut previous = SigSet::empty();
+    if pthread_sigmask(SigmaskHow::SIG_BLOCK, Some(&ttou), Some(&mut previous)).is_err() {
+        return false;
+    }
+    let handed = tcsetpgrp(0, group).is_ok();
+    pthread_sigmask(SigmaskHow::SIG_SETMASK, Some(&previous), None).ok();
+    handed
+}
@@ -0,0 +1,895 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+};
+
+use clap::Args;
+
+use crate::{
+    askpass,
+    config::{self, Config, Tool},
+    error::{JumpError, Result},
+    hooks, known_hosts, multiplex,
+    proxy::{self, Proxy},
+    record::RecordFormat,
+    server::{parse_env_pair, ConnectMethods, Custom, Password, Platform, SSHKey, Server, X11},
+};
+
+/// Flags shaping the ssh invocation. Arguments are merged in a fixed order:
+/// the jump hosts or the proxy, `default_ssh_args` from the config, then per-server options,
+/// then everything after `--` on the `conn` command line.
+#[derive(Debug, Default, Args)]
+pub struct ConnOptions {
+    /// Leave out `default_ssh_args`, `keep_alive` and `compression` from the config
+    #[arg(long)]
+    pub no_defaults: bool,
+    /// Compress traffic, useful on slow links (`ssh -C`)
+    #[arg(short = 'C', long)]
+    pub compress: bool,
+    /// Print the command that would be run instead of running it
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Also write ssh's own messages to this file, kept across runs
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+    /// Tunnel through this HTTP proxy instead of the configured one
+    #[arg(long, value_name = "HOST:PORT", value_parser = proxy::parse)]
+    pub proxy: Option<Proxy>,
+    /// Reach the server through these jump hosts, in order, each a stored
+    /// server or `[user@]host[:port]`, e.g. `--via bastion,10.1.0.5:2222`
+    #[arg(long, value_name = "HOP", value_delimiter = ',', value_parser = parse_hop, conflicts_with = "proxy")]
+    pub via: Vec<String>,
+    /// Go ahead on servers with a tag in `confirm_tags` without typing
+    /// their name
+    #[arg(long)]
+    pub yes_prod: bool,
+    /// Let ssh offer the agent's keys before the stored one, leaving out
+    /// the `IdentitiesOnly=yes` key servers get
+    #[arg(long)]
+    pub no_identities_only: bool,
+    /// Go ahead with servers outside their `access_window`, for this
+    /// reason, which the connection log keeps
+    #[arg(long, value_name = "REASON", value_parser = clap::builder::NonEmptyStringValueParser::new())]
+    pub override_window: Option<String>,
+    /// Add a stored key that has a passphrase to the running ssh-agent
+    /// first, so that it is asked for once rather than at every connection
+    #[arg(long)]
+    pub add_to_agent: bool,
+    /// Use the built-in ssh client instead of the external binaries
+    #[cfg(feature = "native-ssh")]
+    #[arg(long)]
+    pub native: bool,
+}
+
+impl ConnOptions {
+    /// Whether `--native` selected the built-in client
+    pub fn use_native(&self) -> bool {
+        #[cfg(feature = "native-ssh")]
+        return self.native;
+        #[cfg(not(feature = "native-ssh"))]
+        return false;
+    }
+}
+
+/// Flags that only make sense for an interactive `conn` session
+#[derive(Debug, Default, Args)]
+pub struct SessionOptions {
+    /// Forward X11 as an untrusted client (`ssh -X`)
+    #[arg(long, conflicts_with = "x11_trusted")]
+    pub x11: bool,
+    /// Forward X11 as a trusted client (`ssh -Y`)
+    #[arg(long)]
+    pub x11_trusted: bool,
+    /// Forward the local ssh agent for this session (`ssh -A`)
+    #[arg(short = 'A', long)]
+    pub forward_agent: bool,
+    /// Run this command instead of the stored remote command
+    #[arg(long)]
+    pub remote_command: Option<String>,
+    /// Start the login shell in this remote directory
+    #[arg(long = "cd", value_name = "DIR")]
+    pub workdir: Option<String>,
+    /// Skip the pre- and post-connect hooks and the local command
+    #[arg(long)]
+    pub no_hooks: bool,
+    /// Open the session in a terminal window of its own and return at once
+    #[arg(long)]
+    pub new_window: bool,
+    /// Send a Wake-on-LAN packet and wait for the ssh port before connecting
+    #[arg(long)]
+    pub wake: bool,
+    /// Connect even though the host key is not the one recorded, and
+    /// record the new one
+    #[arg(long)]
+    pub accept_new_hostkey: bool,
+    /// Connect to the address with this label instead of the first that answers
+    #[arg(long, value_name = "LABEL")]
+    pub address_label: Option<String>,
+    /// Send an extra environment variable, e.g. `--env DEPLOY_ENV=staging`
+    #[arg(long = "env", value_name = "KEY=VAL", value_parser = parse_env_pair)]
+    pub env: Vec<(String, String)>,
+    /// Record the session to PATH, by default a file in `~/.jump/recordings`
+    #[arg(long, value_name = "PATH")]
+    pub record: Option<Option<PathBuf>>,
+    /// How `--record` and `always_record` record the session
+    #[arg(long, value_enum, default_value_t)]
+    pub record_format: RecordFormat,
+    /// Connect again when the connection drops, rather than on a logout,
+    /// until Ctrl-C is pressed twice while waiting; see `[persist]`
+    #[arg(long)]
+    pub persist: bool,
+    /// Let the session outlast the server's `max_session`, for this reason,
+    /// which the connection log keeps
+    #[arg(long, value_name = "REASON", value_parser = clap::builder::NonEmptyStringValueParser::new())]
+    pub no_limit: Option<String>,
+    /// The device whose serial console `jump console` attaches to through
+    /// this server
+    #[arg(skip)]
+    pub console: Option<Box<Server>>,
+}
+
+/// A child process jump is about to run, kept as plain strings so that
+/// `--dry-run` prints exactly what would be executed.
+#[derive(Debug)]
+pub struct Invocation {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    /// Index into `args` of a secret that must never be printed
+    pub secret: Option<usize>,
+    /// Variables set in the child's environment
+    pub env: Vec<(String, String)>,
+}
+
+impl Invocation {
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        command.envs(self.env.iter().map(|(name, value)| (name, value)));
+        command
+    }
+    /// Run the command to completion with the terminal inherited
+    pub fn status(&self) -> Result<ExitStatus> {
+        self.command().status().map_err(|source| JumpError::Spawn {
+            program: self.program.display().to_string(),
+            source,
+        })
+    }
+}
+
+impl Invocation {
+    /// The program and its arguments, the secret among them as `<redacted>`
+    pub fn argv(&self) -> Vec<String> {
+        let mut argv = vec![self.program.to_string_lossy().into_owned()];
+        argv.extend(
+            self.args
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| match self.secret {
+                    Some(secret) if secret == i => REDACTED.to_owned(),
+                    _ => arg.clone(),
+                }),
+        );
+        argv
+    }
+
+    /// The variables set for the child, passwords as `<redacted>`
+    pub fn redacted_env(&self) -> Vec<(String, String)> {
+        self.env
+            .iter()
+            .map(|(name, value)| match is_secret_var(name) {
+                true => (name.clone(), REDACTED.to_owned()),
+                false => (name.clone(), value.clone()),
+            })
+            .collect()
+    }
+}
+
+/// Ends the options of ssh and the other tools, so that a stored user or
+/// address that starts with `-` after all is never taken for one
+pub const END_OF_OPTIONS: &str = "--";
+
+/// What stands in for a password wherever an invocation is shown
+pub const REDACTED: &str = "<redacted>";
+
+fn is_secret_var(name: &str) -> bool {
+    matches!(name, askpass::SECRET_VAR | SSHPASS_VAR)
+}
+
+impl Display for Invocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, value) in &self.env {
+            match is_secret_var(name) {
+                true => write!(f, "{}={} ", name, REDACTED)?,
+                false => write!(f, "{}={} ", name, shell_quote(value))?,
+            }
+        }
+        write!(f, "{}", shell_quote(&self.program.to_string_lossy()))?;
+        for (i, arg) in self.args.iter().enumerate() {
+            match self.secret {
+                Some(secret) if secret == i => write!(f, " {}", REDACTED)?,
+                _ => write!(f, " {}", shell_quote(arg))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The interactive session `conn` opens
+pub fn connect(
+    config: &Config,
+    server: &Server,
+    options: &ConnOptions,
+    session: &SessionOptions,
+    extra_args: &[String],
+) -> Result<Invocation> {
+    if let ConnectMethods::Command(custom) = &server.method {
+        return custom_command(server, custom);
+    }
+    let mut ssh_args = common_args(config, server, options)?;
+    match x11_mode(server, session) {
+        X11::Off => {}
+        X11::Trusted => ssh_args.push("-Y".to_owned()),
+        X11::Untrusted => ssh_args.push("-X".to_owned()),
+    }
+    if session.forward_agent || server.forward_agent {
+        ssh_args.push("-A".to_owned());
+    }
+    let env = session_env(server, session);
+    for (name, _) in &env {
+        ssh_args.push("-o".to_owned());
+        ssh_args.push(format!("SendEnv={}", name));
+    }
+    let local_command = server.local_command.as_ref().filter(|_| !session.no_hooks);
+    if let Some(command) = local_command {
+        ssh_args.push("-o".to_owned());
+        ssh_args.push("PermitLocalCommand=yes".to_owned());
+        ssh_args.push("-o".to_owned());
+        ssh_args.push(local_command_option(command));
+    }
+    let remote_command = remote_command(server, session);
+    if remote_command.is_some() {
+        ssh_args.push("-t".to_owned());
+    }
+    ssh_args.extend(extra_args.iter().cloned());
+    if config.forbid_agent_forwarding && forwards_agent(&ssh_args) {
+        return Err(JumpError::AgentForwardingForbidden {
+            name: server.server_name.clone(),
+        });
+    }
+    ssh_args.extend(port_args("-p", server));
+    ssh_args.push(END_OF_OPTIONS.to_owned());
+    ssh_args.push(destination(server));
+    // Passed as a single argument, ssh hands it to the remote shell verbatim
+    ssh_args.extend(remote_command);
+    let mut invocation = invocation(config, Tool::Ssh, &server.method, ssh_args)?;
+    invocation.env.extend(env);
+    if local_command.is_some() {
+        invocation.env.extend(hooks::target_env(server));
+    }
+    Ok(invocation)
+}
+
+/// The `-o` value running `command` as ssh's `LocalCommand`. ssh takes the
+/// rest of the option as the command, quotes and spaces included, and hands
+/// it to the local shell; `%` is doubled where it does not start a token.
+pub fn local_command_option(command: &str) -> String {
+    let mut escaped = String::new();
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        escaped.push(c);
+        if c == '%' {
+            match chars.next_if(|next| LOCAL_COMMAND_TOKENS.contains(*next)) {
+                Some(token) => escaped.push(token),
+                None => escaped.push('%'),
+            }
+        }
+    }
+    format!("LocalCommand={}", escaped)
+}
+
+/// The `%` tokens ssh expands in a `LocalCommand`
+const LOCAL_COMMAND_TOKENS: &str = "%CdfHhijKkLlnprTtu";
+
+/// The local command a custom server is connected to with, run through the
+/// shell like hooks are; ssh options do not apply to it
+pub fn custom_command(server: &Server, custom: &Custom) -> Result<Invocation> {
+    let command = custom
+        .expand(server)
+        .map_err(|reason| JumpError::invalid("custom command", reason))?;
+    let (program, flag) = match cfg!(windows) {
+        true => ("cmd", "/C"),
+        false => ("sh", "-c"),
+    };
+    Ok(Invocation {
+        program: PathBuf::from(program),
+        args: vec![flag.to_owned(), command],
+        secret: None,
+        env: vec![],
+    })
+}
+
+/// How `exec` runs its command
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ExecOptions {
+    /// Allocate a tty
+    pub tty: bool,
+    /// Batch mode, with the connect timeout in seconds: ssh fails instead of
+    /// asking for anything
+    pub batch: Option<u32>,
+}
+
+/// The exit code ssh reports its own failures with, as opposed to those of
+/// the remote command
+pub const FAILED: i32 = 255;
+
+/// What ssh prints when no authentication method was accepted
+pub const AUTH_DENIED: &str = "Permission denied (";
+
+/// A one-off remote command run by `exec`, with a tty when asked for or when
+/// the server is a network appliance. In batch mode key and agent logins
+/// get `BatchMode=yes`; password logins, which it would turn away, are only
+/// made through sshpass.
+pub fn exec(
+    config: &Config,
+    server: &Server,
+    options: &ConnOptions,
+    command: &[String],
+    exec: ExecOptions,
+) -> Result<Invocation> {
+    let mut ssh_args = common_args(config, server, options)?;
+    if let Some(timeout) = exec.batch {
+        match &server.method {
+            ConnectMethods::Password(_) if sshpass(config)?.is_none() => {
+                return Err(JumpError::NonInteractive {
+                    what: format!("for the password of {}", server.server_name),
+                    instead: "batch mode only sends it through sshpass, so install it or pass \
+                              --no-batch"
+                        .to_owned(),
+                })
+            }
+            ConnectMethods::Password(_) => {}
+            _ => {
+                ssh_args.push("-o".to_owned());
+                ssh_args.push("BatchMode=yes".to_owned());
+            }
+        }
+        ssh_args.push("-o".to_owned());
+        ssh_args.push(format!("ConnectTimeout={}", timeout));
+    }
+    if exec.tty || server.platform == Some(Platform::NetworkOs) {
+        ssh_args.push("-t".to_owned());
+    }
+    ssh_args.extend(port_args("-p", server));
+    ssh_args.push(END_OF_OPTIONS.to_owned());
+    ssh_args.push(destination(server));
+    ssh_args.extend(command.iter().cloned());
+    invocation(config, Tool::Ssh, &server.method, ssh_args)
+}
+
+/// The server named by whichever of `source` and `destination` is a
+/// `<server_name>:<path>` spec
+pub fn remote_server_name(source: &str, destination: &str) -> Result<String> {
+    match (remote_spec(source), remote_spec(destination)) {
+        (Some((name, _)), None) | (None, Some((name, _))) => Ok(name.to_owned()),
+        _ => Err(JumpError::invalid(
+            "cp arguments",
+            "exactly one of source and destination must be <server_name>:<path>",
+        )),
+    }
+}
+
+/// An scp transfer for `cp`, with the `<server_name>:` prefix replaced by the
+/// server's real destination
+pub fn copy(
+    config: &Config,
+    server: &Server,
+    options: &ConnOptions,
+    source: &str,
+    destination: &str,
+    recursive: bool,
+) -> Result<Invocation> {
+    let mut scp_args = common_args(config, server, options)?;
+    // Appliances rarely run an sftp server, which scp uses by default
+    if server.platform == Some(Platform::NetworkOs) {
+        scp_args.push("-O".to_owned());
+    }
+    scp_args.extend(port_args("-P", server));
+    if recursive {
+        scp_args.push("-r".to_owned());
+    }
+    scp_args.push(END_OF_OPTIONS.to_owned());
+    scp_args.push(copy_spec(server, source));
+    scp_args.push(copy_spec(server, destination));
+    invocation(config, Tool::Scp, &server.method, scp_args)
+}
+
+/// `cp --resume` through rsync, which keeps what an interrupted transfer
+/// wrote and continues from it when run again. rsync reaches the server
+/// through ssh with the options scp would get, passed as its `--rsh`.
+pub fn resumable_copy(
+    config: &Config,
+    server: &Server,
+    options: &ConnOptions,
+    source: &str,
+    destination: &str,
+    recursive: bool,
+) -> Result<Invocation> {
+    let (ssh, _) = config::resolve(config, Tool::Ssh)?;
+    let mut rsh = vec![ssh
+        .to_str()
+        .ok_or(JumpError::invalid("ssh binary path", "not valid UTF-8"))?
+        .to_owned()];
+    if let ConnectMethods::SSHKey(SSHKey { path }) = &server.method {
+        rsh.extend(key_args(path)?);
+    }
+    rsh.extend(common_args(config, server, options)?);
+    rsh.extend(port_args("-p", server));
+    let mut rsync_args = vec!["--partial".to_owned(), "--progress".to_owned()];
+    if recursive {
+        rsync_args.push("--recursive".to_owned());
+    }
+    rsync_args.push("--rsh".to_owned());
+    rsync_args.push(
+        rsh.iter()
+            .map(|arg| shell_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" "),
+    );
+    rsync_args.push(END_OF_OPTIONS.to_owned());
+    rsync_args.push(copy_spec(server, source));
+    rsync_args.push(copy_spec(server, destination));
+    invocation(config, Tool::Rsync, &server.method, rsync_args)
+}
+
+/// A `cp` argument as scp and rsync take it: a `<server_name>:<path>` spec
+/// with the prefix replaced by the server's real destination, and a local
+/// path as it is
+fn copy_spec(server: &Server, spec: &str) -> String {
+    let Some((_, path)) = remote_spec(spec) else {
+        return spec.to_owned();
+    };
+    match server.server_address.contains(':') && server.ssh_config_alias().is_none() {
+        true => format!("{}@[{}]:{}", server.username, server.server_address, path),
+        false => format!("{}:{}", destination(server), path),
+    }
+}
+
+fn remote_spec(spec: &str) -> Option<(&str, &str)> {
+    spec.split_once(':')
+}
+
+/// X11 forwarding for a session: the flags win over the stored setting
+pub fn x11_mode(server: &Server, session: &SessionOptions) -> X11 {
+    match (session.x11, session.x11_trusted) {
+        (_, true) => X11::Trusted,
+        (true, _) => X11::Untrusted,
+        _ => server.x11,
+    }
+}
+
+/// The stored variables with `--env` values replacing those of the same name
+pub fn session_env(server: &Server, session: &SessionOptions) -> Vec<(String, String)> {
+    let mut env = server.env.clone();
+    for (name, value) in &session.env {
+        env.retain(|(existing, _)| existing != name);
+        env.push((name.clone(), value.clone()));
+    }
+    env
+}
+
+/// The command run on login. A remote command, from the flag or stored,
+/// takes precedence over a workdir, which Windows servers have no POSIX
+/// shell to change into.
+pub fn remote_command(server: &Server, session: &SessionOptions) -> Option<String> {
+    let remote_command = session
+        .remote_command
+        .clone()
+        .or(server.remote_command.clone());
+    let workdir = session.workdir.as_ref().or(server.workdir.as_ref());
+    match (remote_command, workdir) {
+        (Some(command), _) => Some(command),
+        (None, Some(_)) if server.platform == Some(Platform::Windows) => None,
+        (None, Some(workdir)) => Some(login_in(workdir)),
+        (None, None) => None,
+    }
+}
+
+/// The options shared by every ssh/scp command line for a server: the
+/// proxy, then the configured defaults followed by the server's stored options.
+fn common_args(config: &Config, server: &Server, options: &ConnOptions) -> Result<Vec<String>> {
+    if !server.method.is_supported() {
+        return Err(JumpError::UnsupportedMethod {
+            name: server.server_name.clone(),
+        });
+    }
+    let mut args = vec![];
+    if !options.via.is_empty() {
+        args.push("-o".to_owned());
+        args.push(format!("ProxyJump={}", options.via.join(",")));
+    } else if let Some(proxy) = proxy::resolve(config, server, options.proxy.as_ref())? {
+        args.push("-o".to_owned());
+        args.push(format!("ProxyCommand={}", proxy.command(config)?));
+    }
+    if !options.no_defaults {
+        args.extend(config.default_ssh_args.iter().cloned());
+        if let Some(interval) = config.keep_alive {
+            args.push("-o".to_owned());
+            args.push(format!("ServerAliveInterval={}", interval));
+        }
+    }
+    for (key, value) in &server.options {
+        args.push("-o".to_owned());
+        args.push(format!("{}={}", key, value));
+    }
+    let default_compression = config.compression && !options.no_defaults;
+    if options.compress || server.compression || default_compression {
+        args.push("-C".to_owned());
+    }
+    if multiplex::enabled(config, server) {
+        args.extend(multiplex::args(config, server)?);
+    }
+    args.extend(known_hosts::args(config)?);
+    if identities_only(server, options) && !sets_option(&args, "IdentitiesOnly") {
+        args.push("-o".to_owned());
+        args.push(IDENTITIES_ONLY.to_owned());
+    }
+    Ok(args)
+}
+
+/// A `--via` hop as typed, before stored server names are looked up. ssh
+/// may hand hops to a shell, so only what a user, host and port are made of
+/// is let through.
+pub fn parse_hop(hop: &str) -> std::result::Result<String, String> {
+    let allowed = |c: char| c.is_ascii_alphanumeric() || "._-@:[]%".contains(c);
+    match hop {
+        "" => Err("a hop cannot be empty".to_owned()),
+        _ if hop.starts_with('-') || !hop.chars().all(allowed) => {
+            Err(format!("{} is not a host", hop))
+        }
+        _ => Ok(hop.to_owned()),
+    }
+}
+
+/// `server` as a `ProxyJump` hop: `user@host:port`, or the alias of a
+/// server kept in `~/.ssh/config`. The hop's own key is not passed on, ssh
+/// offers the agent's keys or those `~/.ssh/config` gives the host.
+pub fn hop(server: &Server) -> String {
+    match server.ssh_config_alias() {
+        Some(alias) => alias.to_owned(),
+        None if server.server_address.contains(':') => format!(
+            "{}@[{}]:{}",
+            server.username, server.server_address, server.port
+        ),
+        None => format!("{}:{}", destination(server), server.port),
+    }
+}
+
+/// What key servers get unless `identities_only` is off, so that an agent
+/// holding many keys does not use up the server's `MaxAuthTries` before the
+/// stored key is offered
+pub const IDENTITIES_ONLY: &str = "IdentitiesOnly=yes";
+
+/// What ssh prints when the server stopped taking keys
+pub const TOO_MANY_FAILURES: &str = "Too many authentication failures";
+
+/// Whether a key server is connected to with `IdentitiesOnly=yes`
+pub fn identities_only(server: &Server, options: &ConnOptions) -> bool {
+    matches!(server.method, ConnectMethods::SSHKey(_))
+        && server.identities_only
+        && !options.no_identities_only
+}
+
+/// Whether `args` already set the `-o` option `key`, which ssh takes the
+/// first value of
+fn sets_option(args: &[String], key: &str) -> bool {
+    args.iter().any(|arg| {
+        let option = arg.strip_prefix("-o").unwrap_or(arg);
+        option
+            .split_once('=')
+            .is_some_and(|(name, _)| name.trim().eq_ignore_ascii_case(key))
+    })
+}
+
+/// Whether `ssh_args` enable agent forwarding, via `-A` or a `ForwardAgent` option
+pub fn forwards_agent(ssh_args: &[String]) -> bool {
+    ssh_args.iter().any(|arg| {
+        let option = arg.strip_prefix("-o").unwrap_or(arg).to_ascii_lowercase();
+        arg == "-A" || option.starts_with("forwardagent=") && option != "forwardagent=no"
+    })
+}
+
+/// `user@address`, or the alias of a server added with `--ssh-config`
+pub fn destination(server: &Server) -> String {
+    match server.ssh_config_alias() {
+        Some(alias) => alias.to_owned(),
+        None => format!("{}@{}", server.username, server.server_address),
+    }
+}
+
+/// `-p <port>`, left out for a server added with `--ssh-config` so that the
+/// port of its `Host` applies
+fn port_args(flag: &str, server: &Server) -> Vec<String> {
+    match server.ssh_config_alias() {
+        Some(_) => vec![],
+        None => vec![flag.to_owned(), server.port.to_string()],
+    }
+}
+
+/// `ssh://user@host:port` for other ssh clients, with the user and host
+/// percent-encoded as RFC 3986 asks and IPv6 addresses in brackets. No
+/// password or key goes into it.
+pub fn uri(server: &Server) -> Result<String> {
+    if let ConnectMethods::Command(_) = server.method {
+        return Err(JumpError::invalid(
+            "server",
+            format!(
+                "{} connects with a custom command, not to an ssh server",
+                server.server_name
+            ),
+        ));
+    }
+    let host = match server.server_address.contains(':') {
+        true => format!("[{}]", server.server_address),
+        false => percent_encode(&server.server_address),
+    };
+    Ok(format!(
+        "ssh://{}@{}:{}",
+        percent_encode(&server.username),
+        host,
+        server.port
+    ))
+}
+
+/// Where `jump add --uri` points the new server
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    /// `None` when the string gave none, for a `[defaults]` rule to give
+    pub username: Option<String>,
+    pub address: String,
+    /// `None` when the string gave none, for the default port
+    pub port: Option<u32>,
+}
+
+const URI_FORMATS: &str = "accepted formats, the port defaulting to 22:
+  ssh://user@host             user@host
+  ssh://user@host:port        user@host:port
+  ssh://user@[2001:db8::1]    user@[2001:db8::1]:port
+  ssh://host:port             host:port";
+
+/// Parse `ssh://user@host:port` or `user@host:port`, the scheme, user and
+/// port optional and IPv6 addresses in brackets. Errors name the part that
+/// did not parse.
+pub fn parse_uri(text: &str) -> std::result::Result<Target, String> {
+    let fail = |reason: String| format!("{} in `{}`\n{}", reason, text, URI_FORMATS);
+    let (scheme, rest) = match text.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest.strip_suffix('/').unwrap_or(rest)),
+        None => (None, text),
+    };
+    if let Some(scheme) = scheme.filter(|scheme| !scheme.eq_ignore_ascii_case("ssh")) {
+        return Err(fail(format!("the scheme `{}://` is not ssh://", scheme)));
+    }
+    let (user, host_port) = match rest.rsplit_once('@') {
+        Some((user, host_port)) => (Some(user), host_port),
+        None => (None, rest),
+    };
+    if user == Some("") {
+        return Err(fail("the user is empty".to_owned()));
+    }
+    let username = user
+        .map(|user| match scheme {
+            Some(_) => percent_decode(user)
+                .ok_or_else(|| fail(format!("the user `{}` has a broken %-escape", user))),
+            None => Ok(user.to_owned()),
+        })
+        .transpose()?;
+    let (address, port) = match host_port.strip_prefix('[') {
+        Some(bracketed) => {
+            let (address, after) = bracketed
+                .split_once(']')
+                .ok_or_else(|| fail(format!("the host `{}` has no closing `]`", host_port)))?;
+            let port =
+                match after {
+                    "" => None,
+                    _ => Some(after.strip_prefix(':').ok_or_else(|| {
+                        fail(format!("`{}` after the host is not `:port`", after))
+                    })?),
+                };
+            (address, port)
+        }
+        // An IPv6 address without brackets cannot have a port
+        None if host_port.matches(':').count() > 1 => (host_port, None),
+        None => match host_port.split_once(':') {
+            Some((address, port)) => (address, Some(port)),
+            None => (host_port, None),
+        },
+    };
+    if address.is_empty() {
+        return Err(fail("the host is missing".to_owned()));
+    }
+    let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | ':' | '%');
+    if !address.chars().all(valid) {
+        return Err(fail(format!(
+            "the host `{}` is not a hostname or IP address",
+            address
+        )));
+    }
+    let port = port
+        .map(|port| {
+            port.parse()
+                .ok()
+                .filter(|port| (1..=65_535).contains(port))
+                .ok_or_else(|| {
+                    fail(format!(
+                        "the port `{}` is not a number from 1 to 65535",
+                        port
+                    ))
+                })
+        })
+        .transpose()?;
+    Ok(Target {
+        username,
+        address: address.to_owned(),
+        port,
+    })
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = vec![];
+    let mut rest = text.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        match byte {
+            b'%' => {
+                let hex = std::str::from_utf8(after.get(..2)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &after[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = after;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Everything but the unreserved characters and sub-delimiters of RFC 3986
+/// as `%XX`, so that `:` and `@` cannot end the user part early
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(byte).to_string()
+            }
+            b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// A remote command starting a login shell in `workdir`, still falling back
+/// to the shell's own start directory when the `cd` fails
+pub fn login_in(workdir: &str) -> String {
+    format!(
+        "cd {} || echo {} >&2; exec \"$SHELL\" -l",
+        shell_quote(workdir),
+        shell_quote(&format!("jump: cannot cd to {}", workdir))
+    )
+}
+
+/// Variable `sshpass -e` reads the password from
+pub const SSHPASS_VAR: &str = "SSHPASS";
+
+/// Wrap `tool_args` into an invocation of `tool`, through sshpass for
+/// password servers. The password goes in the child's environment, which
+/// unlike its arguments other users cannot see in `ps`. Without sshpass, as
+/// on Windows, ssh gets the password from jump itself via `SSH_ASKPASS`.
+pub fn invocation(
+    config: &Config,
+    tool: Tool,
+    method: &ConnectMethods,
+    tool_args: Vec<String>,
+) -> Result<Invocation> {
+    let (program, _) = config::resolve(config, tool)?;
+    let invocation = match method {
+        ConnectMethods::Password(Password { password }) => match sshpass(config)? {
+            Some(sshpass) => {
+                let mut args = vec![
+                    "-e".to_owned(),
+                    program
+                        .to_str()
+                        .ok_or(JumpError::invalid(
+                            &format!("{} binary path", tool.name()),
+                            "not valid UTF-8",
+                        ))?
+                        .to_owned(),
+                ];
+                args.extend(tool_args);
+                Invocation {
+                    program: sshpass,
+                    args,
+                    secret: None,
+                    env: vec![(SSHPASS_VAR.to_owned(), password.clone())],
+                }
+            }
+            None => Invocation {
+                program,
+                args: tool_args,
+                secret: None,
+                env: askpass::env(password)?,
+            },
+        },
+        ConnectMethods::Command(_) => {
+            return Err(JumpError::invalid(
+                "method",
+                format!(
+                    "{} needs ssh, which servers with a custom command are not reached by",
+                    tool.name()
+                ),
+            ))
+        }
+        ConnectMethods::SshConfig(_) => Invocation {
+            program,
+            args: tool_args,
+            secret: None,
+            env: vec![],
+        },
+        ConnectMethods::Unsupported(_) => {
+            return Err(JumpError::invalid(
+                "method",
+                "it is not one this version of jump knows",
+            ))
+        }
+        ConnectMethods::SSHKey(SSHKey { path }) => {
+            // rsync has the key in the ssh command line of its `--rsh`
+            let mut args = match tool {
+                Tool::Rsync => vec![],
+                _ => key_args(path)?,
+            };
+            args.extend(tool_args);
+            Invocation {
+                program,
+                args,
+                secret: None,
+                env: vec![],
+            }
+        }
+    };
+    Ok(invocation)
+}
+
+/// `-i <key>` for a key server
+fn key_args(path: &Path) -> Result<Vec<String>> {
+    let path = config::expand_path(path)?;
+    Ok(vec![
+        "-i".to_owned(),
+        path.to_str()
+            .ok_or(JumpError::invalid("ssh key path", "not valid UTF-8"))?
+            .to_owned(),
+    ])
+}
+
+/// sshpass when it is usable: never on Windows, and on other platforms when
+/// it was configured explicitly or is found on PATH
+fn sshpass(config: &Config) -> Result<Option<PathBuf>> {
+    if cfg!(windows) {
+        return Ok(None);
+    }
+    match config::resolve(config, Tool::Sshpass) {
+        Ok((path, _)) => Ok(Some(path)),
+        Err(e) if Tool::Sshpass.is_configured(config) => Err(e),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Quote `arg` for a POSIX shell, leaving it bare when that is unambiguous
+pub fn shell_quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "@%+=:,./_-".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
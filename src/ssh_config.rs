@@ -0,0 +1,242 @@
+//! Enough of `~/.ssh/config` for servers added with `add --ssh-config`,
+//! which jump only lists and ssh connects to by their `Host` alias: whether
+//! an alias is still declared, and the user, address and port it stands for.
+//! `Match` blocks are skipped, and `Include` only follows files whose name
+//! may have wildcards but whose directory does not.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config,
+    error::{JumpError, Result},
+    select::glob,
+    server::{ConnectMethods, Server, SshConfig},
+};
+
+/// `Include` nesting deeper than this is ignored, as ssh refuses it
+const MAX_DEPTH: usize = 16;
+
+/// A `Host` block, or the options before the first one as `Host *`
+#[derive(Debug, PartialEq)]
+pub struct Host {
+    pub patterns: Vec<String>,
+    /// Keywords in lowercase, with their values
+    pub options: Vec<(String, String)>,
+}
+
+impl Host {
+    /// Whether `alias` matches a pattern and no negated one, ignoring case
+    /// as ssh does
+    fn matches(&self, alias: &str) -> bool {
+        let alias = alias.to_ascii_lowercase();
+        let mut matched = false;
+        for pattern in &self.patterns {
+            let pattern = pattern.to_ascii_lowercase();
+            match pattern.strip_prefix('!') {
+                Some(negated) if glob(negated, &alias) => return false,
+                Some(_) => {}
+                None => matched |= glob(&pattern, &alias),
+            }
+        }
+        matched
+    }
+}
+
+/// What ssh connects to for an alias, where the config says
+#[derive(Debug, Default, PartialEq)]
+pub struct Settings {
+    pub host_name: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u32>,
+}
+
+/// The aliases that stand for one host each, leaving out patterns with
+/// wildcards and negations, each once
+pub fn aliases(hosts: &[Host]) -> Vec<String> {
+    let mut aliases: Vec<String> = vec![];
+    for pattern in hosts.iter().flat_map(|host| &host.patterns) {
+        let concrete = !pattern.contains(['*', '?', '!']);
+        if concrete
+            && !aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(pattern))
+        {
+            aliases.push(pattern.clone());
+        }
+    }
+    aliases
+}
+
+/// `~/.ssh/config`
+pub fn path() -> Result<PathBuf> {
+    config::expand_path(Path::new("~/.ssh/config"))
+}
+
+/// The blocks of `~/.ssh/config` and the files it includes; none when there
+/// is no such file
+pub fn load() -> Result<Vec<Host>> {
+    let mut hosts = vec![];
+    read(&path()?, 0, &mut hosts)?;
+    Ok(hosts)
+}
+
+fn read(path: &Path, depth: usize, hosts: &mut Vec<Host>) -> Result<()> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    for host in parse(&text) {
+        let includes = host
+            .options
+            .iter()
+            .filter(|(key, _)| key == "include")
+            .flat_map(|(_, value)| value.split_whitespace().map(str::to_owned))
+            .collect::<Vec<_>>();
+        hosts.push(host);
+        if depth < MAX_DEPTH {
+            for include in includes {
+                for path in included(&include)? {
+                    read(&path, depth + 1, hosts)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The files an `Include` names, relative to `~/.ssh` unless absolute
+fn included(include: &str) -> Result<Vec<PathBuf>> {
+    let path = config::expand_path(Path::new(include))?;
+    let path = match path.is_absolute() {
+        true => path,
+        false => config::expand_path(Path::new("~/.ssh"))?.join(path),
+    };
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if !name.contains(['*', '?']) {
+        return Ok(vec![path]);
+    }
+    let Some(dir) = path.parent() else {
+        return Ok(vec![]);
+    };
+    let mut paths = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| glob(&name, &entry.file_name().to_string_lossy()))
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>(),
+        Err(_) => vec![],
+    };
+    paths.sort();
+    Ok(paths)
+}
+
+/// The blocks of one file, `Include` lines kept as options of the block
+/// they appear in
+pub fn parse(text: &str) -> Vec<Host> {
+    let mut hosts = vec![Host {
+        patterns: vec!["*".to_owned()],
+        options: vec![],
+    }];
+    // Options inside a `Match` block are left out
+    let mut skipping = false;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = match line.split_once(|c: char| c == '=' || c.is_whitespace()) {
+            Some((key, value)) => (key, value.trim_start_matches([' ', '\t', '=']).trim()),
+            None => (line, ""),
+        };
+        let key = key.to_ascii_lowercase();
+        match key.as_str() {
+            "host" => {
+                skipping = false;
+                hosts.push(Host {
+                    patterns: value.split_whitespace().map(unquote).collect(),
+                    options: vec![],
+                });
+            }
+            "match" => skipping = true,
+            _ if skipping => {}
+            _ => {
+                let host = hosts.last_mut().expect("there is always a block");
+                host.options.push((key, unquote(value)));
+            }
+        }
+    }
+    hosts
+}
+
+fn unquote(value: &str) -> String {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+        .to_owned()
+}
+
+/// Whether a `Host` line names `alias` itself, not just a pattern matching it
+pub fn declares(hosts: &[Host], alias: &str) -> bool {
+    hosts.iter().any(|host| {
+        host.patterns
+            .iter()
+            .any(|pattern| pattern.eq_ignore_ascii_case(alias))
+    })
+}
+
+/// The `HostName`, `User` and `Port` ssh would use for `alias`: for each,
+/// the first value of the blocks matching it
+pub fn settings(hosts: &[Host], alias: &str) -> Settings {
+    let mut settings = Settings::default();
+    let matching = hosts.iter().filter(|host| host.matches(alias));
+    for (key, value) in matching.flat_map(|host| &host.options) {
+        match key.as_str() {
+            "hostname" if settings.host_name.is_none() => {
+                settings.host_name = Some(value.replace("%h", alias));
+            }
+            "user" if settings.user.is_none() => settings.user = Some(value.clone()),
+            "port" if settings.port.is_none() => settings.port = value.parse().ok(),
+            _ => {}
+        }
+    }
+    settings
+}
+
+/// The settings of `alias`, failing when no `Host` line declares it
+pub fn resolve(alias: &str) -> Result<Settings> {
+    let hosts = load()?;
+    if !declares(&hosts, alias) {
+        return Err(JumpError::invalid(
+            "ssh config alias",
+            format!("there is no `Host {}` in {}", alias, path()?.display()),
+        ));
+    }
+    Ok(settings(&hosts, alias))
+}
+
+/// Make `server` one connected to through the `alias` Host, or its own
+/// name's when empty, with the user, address and port it stands for kept
+/// only for listing and `check`
+pub fn connect_by(server: &mut Server, alias: String) -> Result<()> {
+    let alias = match alias.is_empty() {
+        true => server.server_name.clone(),
+        false => alias,
+    };
+    let settings = resolve(&alias)?;
+    server.username = settings
+        .user
+        .or_else(|| std::env::var("USER").ok())
+        .unwrap_or_default();
+    server.server_address = settings.host_name.unwrap_or_else(|| alias.clone());
+    server.port = settings.port.unwrap_or(22);
+    server.method = ConnectMethods::SshConfig(SshConfig { alias });
+    Ok(())
+}
@@ -0,0 +1,1289 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use rusqlite::{params, types::Value, Connection, OpenFlags, OptionalExtension};
+
+use crate::{
+    audit::{self, LogEntry},
+    error::{JumpError, Result},
+    key_usage::{Accepted, KeyUse},
+    knock,
+    mount::Mount,
+    server::{parse_tag, Address, ConnectMethods, Platform, Server, X11},
+    warn,
+};
+
+/// Schema changes applied after the original `jump_servers` table, in order.
+/// The number already applied is tracked in sqlite's `user_version`.
+const MIGRATIONS: &[&str] = &[
+    "create table server_options (
+         server_id integer not null references jump_servers(id) on delete cascade,
+         key text not null,
+         value text not null,
+         primary key (server_id, key))",
+    "alter table jump_servers add column x11 text not null default 'off'",
+    "alter table jump_servers add column forward_agent integer not null default 0",
+    "alter table jump_servers add column compression integer not null default 0",
+    "create table server_env (
+         server_id integer not null references jump_servers(id) on delete cascade,
+         name text not null,
+         value text not null,
+         primary key (server_id, name))",
+    "alter table jump_servers add column remote_command text",
+    "alter table jump_servers add column workdir text",
+    "alter table jump_servers add column pre_connect text;
+     alter table jump_servers add column post_connect text;",
+    "alter table jump_servers add column mac text;
+     alter table jump_servers add column broadcast text;
+     alter table jump_servers add column wake_on_fail integer not null default 0;",
+    // Entries outlive their server, so this is keyed by name
+    "create table connection_log (
+         id integer primary key,
+         server_name text not null,
+         local_user text not null,
+         command text not null,
+         method text not null,
+         started_at integer not null,
+         ended_at integer,
+         exit_code integer)",
+    "alter table jump_servers add column always_record integer not null default 0",
+    "alter table jump_servers add column proxy text",
+    "create table sync_state (key text primary key, value text not null)",
+    "alter table jump_servers add column platform text",
+    "create table server_tags (
+         server_id integer not null references jump_servers(id) on delete cascade,
+         tag text not null,
+         primary key (server_id, tag))",
+    "alter table jump_servers add column note text",
+    // Rebuilt so that ids of removed servers are never handed out again;
+    // with foreign keys off, dropping the old table keeps the children
+    "PRAGMA foreign_keys = off;
+     BEGIN;
+     create table jump_servers_new (
+         id integer primary key autoincrement,
+         server_name text not null unique,
+         username text not null,
+         server_address text not null,
+         port integer not null,
+         method text not null,
+         x11 text not null default 'off',
+         forward_agent integer not null default 0,
+         compression integer not null default 0,
+         remote_command text,
+         workdir text,
+         pre_connect text,
+         post_connect text,
+         mac text,
+         broadcast text,
+         wake_on_fail integer not null default 0,
+         always_record integer not null default 0,
+         proxy text,
+         platform text,
+         note text);
+     insert into jump_servers_new select id, server_name, username, server_address, port,
+         method, x11, forward_agent, compression, remote_command, workdir, pre_connect,
+         post_connect, mac, broadcast, wake_on_fail, always_record, proxy, platform, note
+         from jump_servers;
+     drop table jump_servers;
+     alter table jump_servers_new rename to jump_servers;
+     COMMIT;
+     PRAGMA foreign_keys = on;",
+    "create table server_addresses (
+         server_id integer not null references jump_servers(id) on delete cascade,
+         position integer not null,
+         address text not null,
+         label text,
+         primary key (server_id, position))",
+    "alter table jump_servers add column order_index integer;
+     update jump_servers set order_index = id;",
+    // Left null for servers added before, as their age is unknown
+    "alter table jump_servers add column created_at integer;
+     alter table jump_servers add column updated_at integer;",
+    "alter table jump_servers add column knock text;
+     alter table jump_servers add column knock_delay integer;",
+    "alter table jump_servers add column expires_at integer",
+    "alter table jump_servers add column local_command text",
+    "alter table jump_servers add column multiplex integer not null default 0",
+    "alter table jump_servers add column host_key text",
+    // Keyed by name like the log, as a mount can outlive a rename
+    "create table mounts (
+         local_path text primary key,
+         server_name text not null,
+         remote_path text not null,
+         mounted_at integer not null)",
+    "alter table connection_log add column confirmed text",
+    "alter table jump_servers add column identities_only integer not null default 1",
+    // Keyed by name like the log
+    "create table key_usage (
+         server_name text primary key,
+         fingerprint text not null,
+         key text not null,
+         used_at integer not null)",
+    // `server_name` is indexed by its unique constraint already; `log`,
+    // `recent` and `last_connected` look entries up by server and time
+    "create index connection_log_server on connection_log (server_name, started_at)",
+    "alter table jump_servers add column max_session integer;
+     alter table connection_log add column no_limit text;
+     alter table connection_log add column ended_by text;",
+    "alter table jump_servers add column console_server text;
+     alter table jump_servers add column console_target text;",
+    "alter table jump_servers add column access_window text;
+     alter table connection_log add column override_window text;",
+];
+
+const SERVER_COLUMNS: &str = "server_name, username, server_address, port, method, x11, \
+                              forward_agent, compression, remote_command, workdir, \
+                              pre_connect, post_connect, mac, broadcast, wake_on_fail, \
+                              always_record, proxy, platform, note, id, \
+                              order_index, created_at, updated_at, knock, knock_delay, \
+                              expires_at, local_command, multiplex, host_key, \
+                              identities_only, max_session, console_server, \
+                              console_target, access_window";
+
+/// The options, environment, tags and addresses of the server in the row,
+/// each as a JSON array ordered as their own methods order them, so that a
+/// server takes one query instead of five
+const CHILD_COLUMNS: &str = "\
+    (SELECT json_group_array(json_array(key, value)) FROM (SELECT key, value \
+     FROM server_options WHERE server_id = jump_servers.id ORDER BY key)), \
+    (SELECT json_group_array(json_array(name, value)) FROM (SELECT name, value \
+     FROM server_env WHERE server_id = jump_servers.id ORDER BY name)), \
+    (SELECT json_group_array(tag) FROM (SELECT tag \
+     FROM server_tags WHERE server_id = jump_servers.id ORDER BY tag)), \
+    (SELECT json_group_array(json_array(address, label)) FROM (SELECT address, label \
+     FROM server_addresses WHERE server_id = jump_servers.id ORDER BY position))";
+
+/// What `row_counts` counts, and in which table
+const ROW_COUNTS: [(&str, &str); 8] = [
+    ("servers", "jump_servers"),
+    ("tags", "server_tags"),
+    ("options", "server_options"),
+    ("env", "server_env"),
+    ("addresses", "server_addresses"),
+    ("mounts", "mounts"),
+    ("keys used", "key_usage"),
+    ("log entries", "connection_log"),
+];
+
+/// Prepared statements kept per connection; more than jump has, so that
+/// none is prepared twice
+const STATEMENT_CACHE: usize = 64;
+
+/// How long ordinary writes wait for another process to release the database
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where `move_server` puts a server in the manual order
+#[derive(Debug, Clone, PartialEq)]
+pub enum Place {
+    /// At this position, counting from 1
+    At(usize),
+    Before(String),
+    After(String),
+}
+
+/// The sqlite database holding the servers of one profile
+pub struct Store {
+    conn: Connection,
+    read_only: bool,
+    /// Servers read from the `include` files, listed after the stored ones
+    included: Vec<Server>,
+}
+
+impl Store {
+    pub fn open(path: &Path) -> Result<Store> {
+        Store::from_connection(Connection::open(path)?, false)
+    }
+
+    /// Open a store that this process must never change. sqlite itself
+    /// refuses writes, and every method that writes fails with `ReadOnly`
+    /// before trying.
+    pub fn open_read_only(path: &Path) -> Result<Store> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        Store::from_connection(Connection::open_with_flags(path, flags)?, true)
+    }
+
+    /// Open a store without upgrading its schema, so `jump doctor` can look
+    /// at it as it is. Nothing but `upgrade` and `set_raw_method` should
+    /// write to it.
+    pub fn open_as_is(path: &Path) -> Result<Store> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        Ok(Store {
+            conn,
+            read_only: false,
+            included: vec![],
+        })
+    }
+
+    pub fn open_in_memory() -> Result<Store> {
+        Store::from_connection(Connection::open_in_memory()?, false)
+    }
+
+    fn from_connection(conn: Connection, read_only: bool) -> Result<Store> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE);
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        let store = Store {
+            conn,
+            read_only,
+            included: vec![],
+        };
+        if store.is_initialized()? {
+            store.migrate()?;
+        }
+        Ok(store)
+    }
+
+    /// Add servers read from `include` files, which `list`, `find` and
+    /// `names` return along with the stored ones but nothing may change.
+    /// Their names must not be taken by stored servers, and their fields are
+    /// held to what `add` allows.
+    pub fn include(&mut self, servers: Vec<Server>) -> Result<()> {
+        for server in &servers {
+            check_fields(server).map_err(|e| {
+                let file = server.source.clone().unwrap_or_default();
+                JumpError::invalid("include", format!("{}: {}", file.display(), e))
+            })?;
+        }
+        self.included.extend(servers);
+        Ok(())
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// How many rows this process has changed since opening the store
+    pub fn changes(&self) -> u64 {
+        self.conn.total_changes()
+    }
+
+    /// Fail unless the store may be changed
+    pub fn writable(&self) -> Result<()> {
+        match self.read_only {
+            true => Err(JumpError::ReadOnly),
+            false => Ok(()),
+        }
+    }
+
+    pub fn initialize(&self) -> Result<()> {
+        self.writable()?;
+        self.conn.execute(
+            "create table if not exists jump_servers (
+                 id integer primary key,
+                 server_name text not null unique,
+                 username text not null,
+                 server_address text not null,
+                 port integer not null,
+                 method text not null)",
+            [],
+        )?;
+        self.migrate()
+    }
+
+    pub fn is_initialized(&self) -> Result<bool> {
+        let count: u32 = self.conn.query_row(
+            "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = 'jump_servers'",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// The schema version the store is at, as `MIGRATIONS.len()` once
+    /// upgraded; 0 for the original table alone
+    pub fn schema_version(&self) -> Result<usize> {
+        Ok(self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?)
+    }
+
+    /// How many rows each kind of data has, as `(what, count)` in the order
+    /// `jump init` prints them
+    pub fn row_counts(&self) -> Result<Vec<(&'static str, i64)>> {
+        ROW_COUNTS
+            .iter()
+            .map(|(what, table)| {
+                let count =
+                    self.conn
+                        .query_row(&format!("SELECT count(*) FROM {}", table), [], |row| {
+                            row.get(0)
+                        })?;
+                Ok((*what, count))
+            })
+            .collect()
+    }
+
+    /// How many schema changes the store is missing
+    pub fn pending_migrations(&self) -> Result<usize> {
+        Ok(MIGRATIONS.len().saturating_sub(self.schema_version()?))
+    }
+
+    /// Apply the schema changes the store is missing
+    pub fn upgrade(&self) -> Result<()> {
+        self.writable()?;
+        self.conn.pragma_update(None, "foreign_keys", true)?;
+        self.migrate()
+    }
+
+    fn migrate(&self) -> Result<()> {
+        let version: usize = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if self.read_only && version < MIGRATIONS.len() {
+            return Err(JumpError::invalid(
+                "store",
+                "it needs upgrading, open it once without --read-only",
+            ));
+        }
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            self.conn.execute_batch(migration)?;
+            self.conn.pragma_update(None, "user_version", i + 1)?;
+        }
+        Ok(())
+    }
+
+    /// Add `server` as `add` does: when it is new and another server has
+    /// its address and port, say so, or refuse it with `strict_duplicates`
+    pub fn add_checked(&self, server: &Server, strict_duplicates: bool) -> Result<()> {
+        if self.find(&server.server_name)?.is_none() {
+            let same = self
+                .list()?
+                .into_iter()
+                .filter(|other| other.same_endpoint(server))
+                .map(|other| other.server_name)
+                .collect::<Vec<_>>();
+            if !same.is_empty() {
+                let address = format!("{}:{}", server.server_address, server.port);
+                if strict_duplicates {
+                    return Err(JumpError::DuplicateAddress {
+                        name: server.server_name.clone(),
+                        address,
+                        others: same,
+                    });
+                }
+                warn!("{} is already the address of {}", address, same.join(", "));
+            }
+        }
+        self.add(server)
+    }
+
+    /// Store a new server under `server.id` and at `server.order_index` when
+    /// it has them, as when a server is replaced, and under a fresh id after
+    /// every other server otherwise. Timestamps it has are kept, as when
+    /// importing; missing ones are now.
+    pub fn add(&self, server: &Server) -> Result<()> {
+        self.writable()?;
+        check_fields(server)?;
+        let now = audit::now();
+        if self.find(&server.server_name)?.is_some() {
+            return Err(JumpError::Duplicate {
+                name: server.server_name.clone(),
+            });
+        }
+        self.conn.execute(
+            "INSERT INTO jump_servers (server_name, username, server_address, port, method, x11, forward_agent, compression, remote_command, workdir, pre_connect, post_connect, mac, broadcast, wake_on_fail, always_record, proxy, platform, note, id, order_index, created_at, knock, knock_delay, expires_at, local_command, multiplex, host_key, identities_only, max_session, console_server, console_target, access_window) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, coalesce(?21, (SELECT coalesce(max(order_index), 0) + 1 FROM jump_servers)), ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33)",
+            params![server.server_name, server.username, server.server_address, server.port, server.method.to_string(), server.x11.to_string(), server.forward_agent, server.compression, server.remote_command, server.workdir, server.pre_connect, server.post_connect, server.mac, server.broadcast, server.wake_on_fail, server.always_record, server.proxy, server.platform.map(|platform| platform.to_string()), server.note, server.id, server.order_index, server.created_at.unwrap_or(now), knock::format_sequence(&server.knock), server.knock_delay, server.expires_at, server.local_command, server.multiplex, server.host_key, server.identities_only, server.max_session, server.console_server, server.console_target, server.access_window],
+        )?;
+        for (key, value) in &server.options {
+            self.set_option(&server.server_name, key, value)?;
+        }
+        for (name, value) in &server.env {
+            self.set_env(&server.server_name, name, value)?;
+        }
+        for tag in &server.tags {
+            self.add_tag(&server.server_name, tag)?;
+        }
+        for address in &server.addresses {
+            self.add_address(&server.server_name, address)?;
+        }
+        // Set last, as adding the children above counts as changing it
+        self.conn.execute(
+            "UPDATE jump_servers SET updated_at = ?2 WHERE server_name = ?1",
+            params![server.server_name, server.updated_at.unwrap_or(now)],
+        )?;
+        Ok(())
+    }
+
+    /// Write the fields of a stored server, found by its id, other than its
+    /// options, environment, tags and addresses, and mark it updated now
+    pub fn update(&self, server: &Server) -> Result<()> {
+        self.writable()?;
+        self.not_included(&server.server_name)?;
+        check_fields(server)?;
+        let changed = self.conn.execute(
+            "UPDATE jump_servers SET server_name = ?2, username = ?3, server_address = ?4, port = ?5, method = ?6, x11 = ?7, forward_agent = ?8, compression = ?9, remote_command = ?10, workdir = ?11, pre_connect = ?12, post_connect = ?13, mac = ?14, broadcast = ?15, wake_on_fail = ?16, always_record = ?17, proxy = ?18, platform = ?19, note = ?20, knock = ?22, knock_delay = ?23, expires_at = ?24, local_command = ?25, multiplex = ?26, host_key = ?27, identities_only = ?28, max_session = ?29, console_server = ?30, console_target = ?31, access_window = ?32, updated_at = ?21 WHERE id = ?1",
+            params![server.id, server.server_name, server.username, server.server_address, server.port, server.method.to_string(), server.x11.to_string(), server.forward_agent, server.compression, server.remote_command, server.workdir, server.pre_connect, server.post_connect, server.mac, server.broadcast, server.wake_on_fail, server.always_record, server.proxy, server.platform.map(|platform| platform.to_string()), server.note, audit::now(), knock::format_sequence(&server.knock), server.knock_delay, server.expires_at, server.local_command, server.multiplex, server.host_key, server.identities_only, server.max_session, server.console_server, server.console_target, server.access_window],
+        )?;
+        match changed {
+            0 => Err(JumpError::NotFound {
+                name: server.server_name.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Run `f` in a transaction, rolled back when it fails
+    pub fn transaction<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        self.writable()?;
+        self.conn.execute_batch("BEGIN IMMEDIATE")?;
+        match f() {
+            Ok(value) => {
+                self.conn.execute_batch("COMMIT")?;
+                Ok(value)
+            }
+            Err(e) => {
+                self.conn.execute_batch("ROLLBACK")?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Remove a server, returning whether it existed
+    pub fn remove(&self, server_name: &str) -> Result<bool> {
+        self.writable()?;
+        self.not_included(server_name)?;
+        let removed = self.conn.execute(
+            "DELETE FROM jump_servers WHERE server_name = ?1",
+            [server_name],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /// The stored servers in their manual order, then the included ones
+    pub fn list(&self) -> Result<Vec<Server>> {
+        let mut servers = self.list_stored()?;
+        servers.extend(self.included.iter().cloned());
+        Ok(servers)
+    }
+
+    /// Only the servers of the store itself, for what writes them back
+    pub fn list_stored(&self) -> Result<Vec<Server>> {
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT {SERVER_COLUMNS}, {CHILD_COLUMNS} FROM jump_servers ORDER BY order_index, id"
+        ))?;
+        let servers = stmt
+            .query_map([], server_with_children)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(servers)
+    }
+
+    /// Just the server names, sorted, for shell completion
+    pub fn names(&self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT server_name FROM jump_servers ORDER BY server_name")?;
+        let mut names = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        if !self.included.is_empty() {
+            names.extend(
+                self.included
+                    .iter()
+                    .map(|server| server.server_name.clone()),
+            );
+            names.sort();
+        }
+        Ok(names)
+    }
+
+    /// The server names in their manual order
+    pub fn order(&self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT server_name FROM jump_servers ORDER BY order_index, id")?;
+        let names = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(names)
+    }
+
+    /// Renumber the manual order to follow `names`; run it in a transaction
+    /// so that nobody sees the order half-changed
+    pub fn set_order(&self, names: &[String]) -> Result<()> {
+        self.writable()?;
+        let mut stmt = self
+            .conn
+            .prepare_cached("UPDATE jump_servers SET order_index = ?2 WHERE server_name = ?1")?;
+        for (index, name) in (1..).zip(names) {
+            stmt.execute(params![name, index])?;
+        }
+        Ok(())
+    }
+
+    /// Move a server to `place` in the manual order, renumbering the whole
+    /// order in one transaction
+    pub fn move_server(&self, server_name: &str, place: Place) -> Result<()> {
+        self.get(server_name)?;
+        self.transaction(|| {
+            let mut names = self.order()?;
+            let from = names
+                .iter()
+                .position(|name| name == server_name)
+                .expect("the server exists");
+            let anchor = |anchor: &str| {
+                names
+                    .iter()
+                    .position(|name| name == anchor)
+                    .ok_or_else(|| JumpError::NotFound {
+                        name: anchor.to_owned(),
+                    })
+            };
+            let index = match &place {
+                Place::At(position) => position.saturating_sub(1),
+                Place::Before(before) => anchor(before)?,
+                Place::After(after) => anchor(after)? + 1,
+            };
+            // Past the server itself, the anchors shift down once it is taken out
+            let index = match !matches!(place, Place::At(_)) && index > from {
+                true => index - 1,
+                false => index,
+            };
+            let name = names.remove(from);
+            names.insert(index.min(names.len()), name);
+            self.set_order(&names)
+        })
+    }
+
+    /// Record the host key of a server, or forget it with `None`; not an
+    /// edit of the server, so `updated_at` stays as it is
+    pub fn set_host_key(&self, server_name: &str, host_key: Option<&str>) -> Result<()> {
+        self.writable()?;
+        self.stored(server_name)?;
+        self.conn.execute(
+            "UPDATE jump_servers SET host_key = ?2 WHERE server_name = ?1",
+            params![server_name, host_key],
+        )?;
+        Ok(())
+    }
+
+    /// The server with this name, read with one query
+    pub fn find(&self, server_name: &str) -> Result<Option<Server>> {
+        let server = self
+            .conn
+            .prepare_cached(&format!(
+                "SELECT {SERVER_COLUMNS}, {CHILD_COLUMNS} FROM jump_servers WHERE server_name = ?1"
+            ))?
+            .query_row([server_name], server_with_children)
+            .optional()?;
+        Ok(server.or_else(|| {
+            self.included
+                .iter()
+                .find(|server| server.server_name == server_name)
+                .cloned()
+        }))
+    }
+
+    /// Like `find`, but a missing server is an error
+    pub fn get(&self, server_name: &str) -> Result<Server> {
+        self.find(server_name)?.ok_or_else(|| JumpError::NotFound {
+            name: server_name.to_owned(),
+        })
+    }
+
+    /// Like `get` for a server about to be changed, which an included one
+    /// cannot be
+    fn stored(&self, server_name: &str) -> Result<Server> {
+        self.not_included(server_name)?;
+        self.get(server_name)
+    }
+
+    /// Fail when `server_name` was read from an `include` file
+    fn not_included(&self, server_name: &str) -> Result<()> {
+        match self
+            .included
+            .iter()
+            .find(|server| server.server_name == server_name)
+        {
+            Some(server) => Err(JumpError::Included {
+                name: server_name.to_owned(),
+                file: server.source.clone().unwrap_or_default(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn options(&self, server_name: &str) -> Result<Vec<(String, String)>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT key, value FROM server_options
+             WHERE server_id = (SELECT id FROM jump_servers WHERE server_name = ?1)
+             ORDER BY key",
+        )?;
+        let options = stmt
+            .query_map([server_name], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(options)
+    }
+
+    pub fn set_option(&self, server_name: &str, key: &str, value: &str) -> Result<()> {
+        self.writable()?;
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(JumpError::invalid(
+                "ssh option name",
+                format!("`{}` may only contain letters and digits", key),
+            ));
+        }
+        self.stored(server_name)?;
+        let changed = self.conn.execute(
+            "INSERT INTO server_options (server_id, key, value)
+             SELECT id, ?2, ?3 FROM jump_servers WHERE server_name = ?1
+             ON CONFLICT (server_id, key) DO UPDATE SET value = excluded.value",
+            [server_name, key, value],
+        )?;
+        self.touch(server_name, changed)
+    }
+
+    pub fn unset_option(&self, server_name: &str, key: &str) -> Result<()> {
+        self.writable()?;
+        self.stored(server_name)?;
+        let changed = self.conn.execute(
+            "DELETE FROM server_options
+             WHERE server_id = (SELECT id FROM jump_servers WHERE server_name = ?1)
+             AND key = ?2",
+            [server_name, key],
+        )?;
+        self.touch(server_name, changed)
+    }
+
+    pub fn env(&self, server_name: &str) -> Result<Vec<(String, String)>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT name, value FROM server_env
+             WHERE server_id = (SELECT id FROM jump_servers WHERE server_name = ?1)
+             ORDER BY name",
+        )?;
+        let env = stmt
+            .query_map([server_name], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(env)
+    }
+
+    pub fn set_env(&self, server_name: &str, name: &str, value: &str) -> Result<()> {
+        self.writable()?;
+        self.stored(server_name)?;
+        let changed = self.conn.execute(
+            "INSERT INTO server_env (server_id, name, value)
+             SELECT id, ?2, ?3 FROM jump_servers WHERE server_name = ?1
+             ON CONFLICT (server_id, name) DO UPDATE SET value = excluded.value",
+            [server_name, name, value],
+        )?;
+        self.touch(server_name, changed)
+    }
+
+    pub fn unset_env(&self, server_name: &str, name: &str) -> Result<()> {
+        self.writable()?;
+        self.stored(server_name)?;
+        let changed = self.conn.execute(
+            "DELETE FROM server_env
+             WHERE server_id = (SELECT id FROM jump_servers WHERE server_name = ?1)
+             AND name = ?2",
+            [server_name, name],
+        )?;
+        self.touch(server_name, changed)
+    }
+
+    pub fn tags(&self, server_name: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT tag FROM server_tags
+             WHERE server_id = (SELECT id FROM jump_servers WHERE server_name = ?1)
+             ORDER BY tag",
+        )?;
+        let tags = stmt
+            .query_map([server_name], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(tags)
+    }
+
+    pub fn add_tag(&self, server_name: &str, tag: &str) -> Result<()> {
+        self.writable()?;
+        parse_tag(tag).map_err(|reason| JumpError::invalid("tag", reason))?;
+        self.stored(server_name)?;
+        let changed = self.conn.execute(
+            "INSERT INTO server_tags (server_id, tag)
+             SELECT id, ?2 FROM jump_servers WHERE server_name = ?1
+             ON CONFLICT (server_id, tag) DO NOTHING",
+            [server_name, tag],
+        )?;
+        self.touch(server_name, changed)
+    }
+
+    pub fn remove_tag(&self, server_name: &str, tag: &str) -> Result<()> {
+        self.writable()?;
+        self.stored(server_name)?;
+        let changed = self.conn.execute(
+            "DELETE FROM server_tags
+             WHERE server_id = (SELECT id FROM jump_servers WHERE server_name = ?1)
+             AND tag = ?2",
+            [server_name, tag],
+        )?;
+        self.touch(server_name, changed)
+    }
+
+    /// Mark the server as changed now, when `changed` rows were
+    fn touch(&self, server_name: &str, changed: usize) -> Result<()> {
+        if changed > 0 {
+            self.conn.execute(
+                "UPDATE jump_servers SET updated_at = ?2 WHERE server_name = ?1",
+                params![server_name, audit::now()],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The fallback addresses of a server, in the order `conn` tries them
+    pub fn addresses(&self, server_name: &str) -> Result<Vec<Address>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT address, label FROM server_addresses
+             WHERE server_id = (SELECT id FROM jump_servers WHERE server_name = ?1)
+             ORDER BY position",
+        )?;
+        let addresses = stmt
+            .query_map([server_name], |row| {
+                Ok(Address {
+                    address: row.get(0)?,
+                    label: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(addresses)
+    }
+
+    /// Append a fallback address, tried after the ones already stored
+    pub fn add_address(&self, server_name: &str, address: &Address) -> Result<()> {
+        self.writable()?;
+        not_an_option("address", &address.address)?;
+        let server = self.stored(server_name)?;
+        if let Some(label) = &address.label {
+            parse_tag(label).map_err(|reason| JumpError::invalid("address label", reason))?;
+            if server
+                .addresses
+                .iter()
+                .any(|a| a.label.as_ref() == Some(label))
+            {
+                return Err(JumpError::invalid(
+                    "address label",
+                    format!(
+                        "{} already has an address labelled `{}`",
+                        server_name, label
+                    ),
+                ));
+            }
+        }
+        let taken = server.server_address == address.address
+            || server
+                .addresses
+                .iter()
+                .any(|a| a.address == address.address);
+        if taken {
+            return Err(JumpError::invalid(
+                "address",
+                format!("{} already has {}", server_name, address.address),
+            ));
+        }
+        let changed = self.conn.execute(
+            "INSERT INTO server_addresses (server_id, position, address, label)
+             SELECT id, (SELECT coalesce(max(position), 0) + 1 FROM server_addresses
+                         WHERE server_id = jump_servers.id), ?2, ?3
+             FROM jump_servers WHERE server_name = ?1",
+            params![server_name, address.address, address.label],
+        )?;
+        self.touch(server_name, changed)
+    }
+
+    /// Remove the fallback address that is `address` or labelled with it,
+    /// returning whether there was one
+    pub fn remove_address(&self, server_name: &str, address: &str) -> Result<bool> {
+        self.writable()?;
+        self.stored(server_name)?;
+        let removed = self.conn.execute(
+            "DELETE FROM server_addresses
+             WHERE server_id = (SELECT id FROM jump_servers WHERE server_name = ?1)
+             AND (address = ?2 OR label = ?2)",
+            [server_name, address],
+        )?;
+        self.touch(server_name, removed)?;
+        Ok(removed > 0)
+    }
+
+    /// Record a new connection log entry, returning its id
+    pub fn log_start(&self, entry: &LogEntry) -> Result<i64> {
+        self.briefly(|conn| insert_log(conn, entry))
+    }
+
+    /// `prune_log` and `log_start` in one transaction, as every session
+    /// starts with both
+    pub fn log_start_pruning(&self, entry: &LogEntry, before: i64) -> Result<i64> {
+        self.briefly(|conn| {
+            let transaction = conn.unchecked_transaction()?;
+            transaction
+                .prepare_cached("DELETE FROM connection_log WHERE started_at < ?1")?
+                .execute([before])?;
+            let id = insert_log(&transaction, entry)?;
+            transaction.commit()?;
+            Ok(id)
+        })
+    }
+
+    /// Set the `no_limit` or `ended_by` of an entry, leaving those given as
+    /// `None` as they are
+    pub fn log_limit(&self, id: i64, no_limit: Option<&str>, ended_by: Option<&str>) -> Result<()> {
+        self.briefly(|conn| {
+            conn.execute(
+                "UPDATE connection_log SET no_limit = coalesce(?2, no_limit),
+                 ended_by = coalesce(?3, ended_by) WHERE id = ?1",
+                params![id, no_limit, ended_by],
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Set why `--override-window` let the session of an entry start
+    pub fn log_override(&self, id: i64, reason: &str) -> Result<()> {
+        self.briefly(|conn| {
+            conn.execute(
+                "UPDATE connection_log SET override_window = ?2 WHERE id = ?1",
+                params![id, reason],
+            )
+        })?;
+        Ok(())
+    }
+
+    pub fn log_end(&self, id: i64, ended_at: i64, exit_code: Option<i32>) -> Result<()> {
+        self.briefly(|conn| {
+            conn.execute(
+                "UPDATE connection_log SET ended_at = ?2, exit_code = ?3 WHERE id = ?1",
+                params![id, ended_at, exit_code],
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Drop entries started before `before`, returning how many went
+    pub fn prune_log(&self, before: i64) -> Result<usize> {
+        self.briefly(|conn| {
+            conn.execute("DELETE FROM connection_log WHERE started_at < ?1", [before])
+        })
+    }
+
+    /// Run a connection log write that gives up quickly instead of waiting
+    /// for another jump holding the database, since nothing depends on it
+    fn briefly<T>(&self, write: impl FnOnce(&Connection) -> rusqlite::Result<T>) -> Result<T> {
+        self.writable()?;
+        self.conn.busy_timeout(Duration::from_millis(100))?;
+        let result = write(&self.conn);
+        self.conn.busy_timeout(BUSY_TIMEOUT)?;
+        Ok(result?)
+    }
+
+    /// Log entries, newest first, optionally only for one server, started at
+    /// or after `since`, and at most `limit` of them
+    pub fn log(
+        &self,
+        server_name: Option<&str>,
+        since: Option<i64>,
+        limit: Option<usize>,
+    ) -> Result<Vec<LogEntry>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT server_name, local_user, command, method, started_at, ended_at, exit_code,
+                    confirmed, no_limit, ended_by, override_window
+             FROM connection_log
+             WHERE (?1 IS NULL OR server_name = ?1) AND (?2 IS NULL OR started_at >= ?2)
+             ORDER BY started_at DESC, id DESC
+             LIMIT ?3",
+        )?;
+        let limit = limit.map_or(-1, |limit| limit as i64);
+        let entries = stmt
+            .query_map(params![server_name, since, limit], |row| {
+                Ok(LogEntry {
+                    server_name: row.get(0)?,
+                    local_user: row.get(1)?,
+                    command: row.get(2)?,
+                    method: row.get(3)?,
+                    started_at: row.get(4)?,
+                    ended_at: row.get(5)?,
+                    exit_code: row.get(6)?,
+                    confirmed: row.get(7)?,
+                    no_limit: row.get(8)?,
+                    ended_by: row.get(9)?,
+                    override_window: row.get(10)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    /// When each server in the connection log was last connected to
+    pub fn last_connected(&self) -> Result<HashMap<String, i64>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT server_name, max(started_at) FROM connection_log GROUP BY server_name",
+        )?;
+        let last = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+        Ok(last)
+    }
+
+    /// The `limit` servers connected to last, each once, most recent first,
+    /// with when that was; servers removed since are left out
+    pub fn recent(&self, limit: usize) -> Result<Vec<(String, i64)>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT log.server_name, max(log.started_at), max(log.id) FROM connection_log log
+             JOIN jump_servers ON jump_servers.server_name = log.server_name
+             GROUP BY log.server_name
+             ORDER BY 2 DESC, 3 DESC
+             LIMIT ?1",
+        )?;
+        let recent = stmt
+            .query_map([limit as i64], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(recent)
+    }
+
+    /// When each server in the connection log was last connected to with
+    /// the session ending well
+    pub fn last_succeeded(&self) -> Result<HashMap<String, i64>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT server_name, max(started_at) FROM connection_log
+             WHERE exit_code = 0 GROUP BY server_name",
+        )?;
+        let last = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+        Ok(last)
+    }
+
+    /// Record the key a server last accepted, giving up as quickly as log
+    /// writes do
+    pub fn record_key_use(&self, key_use: &KeyUse) -> Result<()> {
+        self.briefly(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO key_usage (server_name, fingerprint, key, used_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    key_use.server_name,
+                    key_use.accepted.fingerprint,
+                    key_use.accepted.key,
+                    key_use.used_at
+                ],
+            )
+        })?;
+        Ok(())
+    }
+
+    /// The last accepted key of each stored server that has one, or only of
+    /// `server_name`, in the order of `ls`
+    pub fn key_uses(&self, server_name: Option<&str>) -> Result<Vec<KeyUse>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT usage.server_name, fingerprint, key, used_at FROM key_usage usage
+             JOIN jump_servers ON jump_servers.server_name = usage.server_name
+             WHERE ?1 IS NULL OR usage.server_name = ?1
+             ORDER BY order_index, id",
+        )?;
+        let uses = stmt
+            .query_map([server_name], |row| {
+                Ok(KeyUse {
+                    server_name: row.get(0)?,
+                    accepted: Accepted {
+                        fingerprint: row.get(1)?,
+                        key: row.get(2)?,
+                    },
+                    used_at: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(uses)
+    }
+
+    /// Record a mount made by `jump mount`, replacing any earlier one on the
+    /// same local path
+    pub fn add_mount(&self, mount: &Mount) -> Result<()> {
+        self.writable()?;
+        self.conn.execute(
+            "INSERT OR REPLACE INTO mounts (local_path, server_name, remote_path, mounted_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                mount.local_path.to_string_lossy(),
+                mount.server_name,
+                mount.remote_path,
+                mount.mounted_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_mount(&self, local_path: &Path) -> Result<()> {
+        self.writable()?;
+        self.conn.execute(
+            "DELETE FROM mounts WHERE local_path = ?1",
+            [local_path.to_string_lossy()],
+        )?;
+        Ok(())
+    }
+
+    /// The recorded mounts, oldest first
+    pub fn mounts(&self) -> Result<Vec<Mount>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT server_name, remote_path, local_path, mounted_at FROM mounts
+             ORDER BY mounted_at, local_path",
+        )?;
+        let mounts = stmt
+            .query_map([], |row| {
+                Ok(Mount {
+                    server_name: row.get(0)?,
+                    remote_path: row.get(1)?,
+                    local_path: PathBuf::from(row.get::<_, String>(2)?),
+                    mounted_at: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(mounts)
+    }
+
+    /// Every server's `method` column as stored, with its name
+    pub fn raw_methods(&self) -> Result<Vec<(String, String)>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT server_name, method FROM jump_servers ORDER BY rowid")?;
+        let methods = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(methods)
+    }
+
+    /// Every server's columns as stored, without the lenient parsing of
+    /// `list`, for `jump verify`
+    pub fn raw_servers(&self) -> Result<Vec<RawServer>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT server_name, username, server_address, port, method, x11, platform, proxy,
+                    mac, host_key
+             FROM jump_servers ORDER BY rowid",
+        )?;
+        let servers = stmt
+            .query_map([], |row| {
+                Ok(RawServer {
+                    server_name: row.get(0)?,
+                    username: row.get(1)?,
+                    server_address: row.get(2)?,
+                    port: match row.get::<_, Value>(3)? {
+                        Value::Integer(port) => Some(port),
+                        _ => None,
+                    },
+                    method: row.get(4)?,
+                    x11: row.get(5)?,
+                    platform: row.get(6)?,
+                    proxy: row.get(7)?,
+                    mac: row.get(8)?,
+                    host_key: row.get(9)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(servers)
+    }
+
+    /// Rows pointing at a server that is gone, as `(table, rowid)`
+    pub fn dangling_rows(&self) -> Result<Vec<(String, i64)>> {
+        let mut stmt = self.conn.prepare_cached("PRAGMA foreign_key_check")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    /// Delete what `dangling_rows` finds, returning how many rows went
+    pub fn remove_dangling_rows(&self) -> Result<usize> {
+        self.writable()?;
+        let rows = self.dangling_rows()?;
+        self.transaction(|| {
+            for (table, rowid) in &rows {
+                // Table names come from SQLite itself, not from input
+                self.conn.execute(
+                    &format!("DELETE FROM \"{}\" WHERE rowid = ?1", table),
+                    [rowid],
+                )?;
+            }
+            Ok(rows.len())
+        })
+    }
+
+    /// Overwrite a server's `method` column as it is, for `jump doctor` to
+    /// bring old rows up to date
+    pub fn set_raw_method(&self, server_name: &str, method: &str) -> Result<()> {
+        self.writable()?;
+        self.conn.execute(
+            "UPDATE jump_servers SET method = ?2 WHERE server_name = ?1",
+            [server_name, method],
+        )?;
+        Ok(())
+    }
+
+    /// A value `jump sync` recorded, such as the last synced commit
+    pub fn sync_state(&self, key: &str) -> Result<Option<String>> {
+        let value = self
+            .conn
+            .query_row(
+                "SELECT value FROM sync_state WHERE key = ?1",
+                [key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(value)
+    }
+
+    pub fn unset_sync_state(&self, key: &str) -> Result<()> {
+        self.writable()?;
+        self.conn
+            .execute("DELETE FROM sync_state WHERE key = ?1", [key])?;
+        Ok(())
+    }
+
+    pub fn set_sync_state(&self, key: &str, value: &str) -> Result<()> {
+        self.writable()?;
+        self.conn.execute(
+            "INSERT INTO sync_state (key, value) VALUES (?1, ?2)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            [key, value],
+        )?;
+        Ok(())
+    }
+}
+
+/// A row of `jump_servers` as stored, with a port that is not an integer
+/// as `None`
+#[derive(Debug, Clone)]
+pub struct RawServer {
+    pub server_name: String,
+    pub username: String,
+    pub server_address: String,
+    pub port: Option<i64>,
+    pub method: String,
+    pub x11: String,
+    pub platform: Option<String>,
+    pub proxy: Option<String>,
+    pub mac: Option<String>,
+    pub host_key: Option<String>,
+}
+
+/// Refuse the fields that end up on the ssh command line when they start
+/// with `-`, as ssh would take them for options such as `-oProxyCommand=`
+fn check_fields(server: &Server) -> Result<()> {
+    not_an_option("name", &server.server_name)?;
+    not_an_option("username", &server.username)?;
+    not_an_option("address", &server.server_address)?;
+    if let Some(alias) = server.ssh_config_alias() {
+        not_an_option("ssh config alias", alias)?;
+    }
+    for address in &server.addresses {
+        not_an_option("address", &address.address)?;
+    }
+    Ok(())
+}
+
+fn not_an_option(field: &str, value: &str) -> Result<()> {
+    if value.starts_with('-') {
+        return Err(JumpError::invalid(
+            field,
+            format!(
+                "`{}` starts with `-`, which ssh would take for an option",
+                value
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn insert_log(conn: &Connection, entry: &LogEntry) -> rusqlite::Result<i64> {
+    conn.prepare_cached(
+        "INSERT INTO connection_log
+         (server_name, local_user, command, method, started_at, ended_at, exit_code, confirmed,
+          no_limit, ended_by, override_window)
+         values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+    )?
+    .execute(params![
+        entry.server_name,
+        entry.local_user,
+        entry.command,
+        entry.method,
+        entry.started_at,
+        entry.ended_at,
+        entry.exit_code,
+        entry.confirmed,
+        entry.no_limit,
+        entry.ended_by,
+        entry.override_window
+    ])?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// A server from `SERVER_COLUMNS` followed by `CHILD_COLUMNS`
+fn server_with_children(row: &rusqlite::Row) -> rusqlite::Result<Server> {
+    let mut server = server_from_row(row)?;
+    let first = row.as_ref().column_count() - 4;
+    server.options = json_column(row, first)?;
+    server.env = json_column(row, first + 1)?;
+    server.tags = json_column(row, first + 2)?;
+    server.addresses = json_column::<Vec<(String, Option<String>)>>(row, first + 3)?
+        .into_iter()
+        .map(|(address, label)| Address { address, label })
+        .collect();
+    Ok(server)
+}
+
+fn json_column<T: serde::de::DeserializeOwned>(
+    row: &rusqlite::Row,
+    index: usize,
+) -> rusqlite::Result<T> {
+    let json: String = row.get(index)?;
+    serde_json::from_str(&json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e.into())
+    })
+}
+
+fn server_from_row(row: &rusqlite::Row) -> rusqlite::Result<Server> {
+    let method_string: String = row.get(4)?;
+    let x11: String = row.get(5)?;
+    let platform: Option<String> = row.get(17)?;
+    Ok(Server {
+        id: row.get(19)?,
+        order_index: row.get(20)?,
+        created_at: row.get(21)?,
+        updated_at: row.get(22)?,
+        server_name: row.get(0)?,
+        username: row.get(1)?,
+        server_address: row.get(2)?,
+        port: row.get(3)?,
+        x11: <X11 as clap::ValueEnum>::from_str(&x11, true).unwrap_or(X11::Off),
+        forward_agent: row.get(6)?,
+        compression: row.get(7)?,
+        remote_command: row.get(8)?,
+        workdir: row.get(9)?,
+        pre_connect: row.get(10)?,
+        post_connect: row.get(11)?,
+        local_command: row.get(26)?,
+        multiplex: row.get(27)?,
+        host_key: row.get(28)?,
+        identities_only: row.get(29)?,
+        mac: row.get(12)?,
+        broadcast: row.get(13)?,
+        wake_on_fail: row.get(14)?,
+        knock: knock::parse_sequence(row.get(23)?),
+        knock_delay: row.get(24)?,
+        always_record: row.get(15)?,
+        proxy: row.get(16)?,
+        platform: platform
+            .and_then(|platform| <Platform as clap::ValueEnum>::from_str(&platform, true).ok()),
+        note: row.get(18)?,
+        tags: vec![],
+        expires_at: row.get(25)?,
+        max_session: row.get(30)?,
+        console_server: row.get(31)?,
+        console_target: row.get(32)?,
+        access_window: row.get(33)?,
+        addresses: vec![],
+        options: vec![],
+        env: vec![],
+        source: None,
+        method: ConnectMethods::from(method_string),
+    })
+}
@@ -0,0 +1,74 @@
+//! Terminal colors for human-readable output. Machine-readable modes never
+//! go through here.
+
+use std::{
+    env,
+    io::{self, IsTerminal},
+};
+
+use crate::config::ColorChoice;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Style {
+    enabled: bool,
+}
+
+impl Style {
+    /// Colors are on for `always`, off for `never`, and for `auto` only when
+    /// stdout is a terminal and `NO_COLOR` is unset or empty
+    pub fn new(choice: ColorChoice) -> Style {
+        let enabled = match choice {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()) && io::stdout().is_terminal()
+            }
+        };
+        Style { enabled }
+    }
+
+    pub fn plain() -> Style {
+        Style { enabled: false }
+    }
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        match self.enabled {
+            true => format!("\x1b[{}m{}\x1b[0m", code, text),
+            false => text.to_owned(),
+        }
+    }
+
+    pub fn bold(&self, text: &str) -> String {
+        self.paint("1", text)
+    }
+
+    pub fn red(&self, text: &str) -> String {
+        self.paint("31", text)
+    }
+
+    pub fn green(&self, text: &str) -> String {
+        self.paint("32", text)
+    }
+
+    pub fn yellow(&self, text: &str) -> String {
+        self.paint("33", text)
+    }
+
+    pub fn cyan(&self, text: &str) -> String {
+        self.paint("36", text)
+    }
+
+    /// `text` in a `#rrggbb` color, for terminals with 24-bit color
+    pub fn rgb(&self, color: &str, text: &str) -> String {
+        let component = |at: usize| {
+            color
+                .get(at..at + 2)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .unwrap_or(0)
+        };
+        self.paint(
+            &format!("1;38;2;{};{};{}", component(1), component(3), component(5)),
+            text,
+        )
+    }
+}
@@ -0,0 +1,391 @@
+//! Keeping a store in a git repository. The servers are written, sorted and
+//! without passwords, as a bundle `jump import` can also read. Pulls merge
+//! against the version of the last sync, recorded in the store: changes made
+//! on one side only are applied, changes made on both are conflicts.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde_json::Value;
+
+use crate::{
+    bundle::{self, Bundle},
+    config::{self, Config, Tool},
+    debug,
+    error::{JumpError, Result},
+    server::Server,
+    store::Store,
+};
+
+/// Servers by name, in the form they are compared in
+pub type Servers = BTreeMap<String, Value>;
+
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    /// The remote version replaces the local one; `None` removes it
+    Take {
+        name: String,
+        server: Option<Value>,
+        existed: bool,
+    },
+    /// Both sides changed the server since the last sync
+    Conflict {
+        name: String,
+        local: Option<Value>,
+        remote: Option<Value>,
+    },
+}
+
+/// Which side wins the conflicts of a pull
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolution {
+    Ours,
+    Theirs,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Init {
+    /// The store was written to the repository and committed
+    Created,
+    /// The repository already holds servers, to be merged by `pull`
+    Existing,
+}
+
+/// The repository a store syncs with
+struct Repo {
+    dir: PathBuf,
+    /// The servers file, relative to `dir`
+    file: String,
+}
+
+impl Repo {
+    fn load(store: &Store) -> Result<Repo> {
+        match (store.sync_state("repo")?, store.sync_state("file")?) {
+            (Some(dir), Some(file)) => Ok(Repo {
+                dir: PathBuf::from(dir),
+                file,
+            }),
+            _ => Err(JumpError::invalid(
+                "sync",
+                "not set up, run `jump sync init <repo>` first",
+            )),
+        }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.join(&self.file)
+    }
+}
+
+/// Set up syncing with `target`: an existing directory, made a repository
+/// when it is not one yet, a URL or bare repository cloned into
+/// `~/.jump/sync`, or a new directory
+pub fn init(config: &Config, store: &Store, target: &str, profile: &str) -> Result<Init> {
+    store.writable()?;
+    let bare = Path::new(target).is_dir()
+        && git(
+            config,
+            Path::new(target),
+            &["rev-parse", "--is-bare-repository"],
+        )
+        .is_ok_and(|bare| bare == "true");
+    let dir = if Path::new(target).is_dir() && !bare {
+        let dir = fs::canonicalize(target)?;
+        if !git_succeeds(config, &dir, &["rev-parse", "--git-dir"])? {
+            git(config, &dir, &["init", "--quiet"])?;
+        }
+        dir
+    } else if bare || is_remote(target) {
+        let dir = config::jump_dir()?.join("sync").join(profile);
+        fs::create_dir_all(&dir)?;
+        let source = match bare {
+            true => fs::canonicalize(target)?.to_string_lossy().into_owned(),
+            false => target.to_owned(),
+        };
+        git(config, &dir, &["clone", "--quiet", &source, "."])?;
+        dir
+    } else {
+        fs::create_dir_all(target)?;
+        let dir = fs::canonicalize(target)?;
+        git(config, &dir, &["init", "--quiet"])?;
+        dir
+    };
+    let file = match profile {
+        "default" => "jump/servers.json".to_owned(),
+        _ => format!("jump/{}.json", profile),
+    };
+    let repo = Repo { dir, file };
+    let init = match repo.path().exists() {
+        true => {
+            store.unset_sync_state("commit")?;
+            Init::Existing
+        }
+        false => {
+            write(store, &repo)?;
+            commit(config, &repo)?;
+            store.set_sync_state("commit", &head(config, &repo.dir)?)?;
+            Init::Created
+        }
+    };
+    store.set_sync_state("repo", &repo.dir.to_string_lossy())?;
+    store.set_sync_state("file", &repo.file)?;
+    Ok(init)
+}
+
+/// Commit the store and push it, returning whether anything was pushed. Fails
+/// when the file changed in the repository since the last sync, so that a
+/// pull merges those changes first.
+pub fn push(config: &Config, store: &Store) -> Result<bool> {
+    store.writable()?;
+    let repo = Repo::load(store)?;
+    if let Some(commit) = store.sync_state("commit")? {
+        let range = format!("{}..HEAD", commit);
+        let unchanged = git_succeeds(
+            config,
+            &repo.dir,
+            &["diff", "--quiet", &range, "--", &repo.file],
+        )?;
+        if !unchanged {
+            return Err(JumpError::invalid(
+                "sync",
+                "the servers in the repository changed, run `jump sync pull` first",
+            ));
+        }
+    }
+    write(store, &repo)?;
+    commit(config, &repo)?;
+    let pushed = match remote(config, &repo.dir)? {
+        Some(remote) => {
+            git(config, &repo.dir, &["push", "--quiet", &remote, "HEAD"])?;
+            true
+        }
+        None => false,
+    };
+    store.set_sync_state("commit", &head(config, &repo.dir)?)?;
+    Ok(pushed)
+}
+
+/// Fetch the repository and merge its servers into the store. Unless a
+/// `resolution` settles them, conflicts leave the sync unrecorded, so pulling
+/// again reports the same conflicts.
+pub fn pull(config: &Config, store: &Store, resolution: Option<Resolution>) -> Result<Vec<Change>> {
+    store.writable()?;
+    let repo = Repo::load(store)?;
+    if remote(config, &repo.dir)?.is_some() {
+        git(config, &repo.dir, &["pull", "--quiet", "--ff-only"])?;
+    }
+    let remote = servers(Bundle::read(&fs::read(repo.path())?, None)?.servers);
+    let base = match store.sync_state("commit")? {
+        Some(commit) => {
+            let spec = format!("{}:{}", commit, repo.file);
+            let data = git(config, &repo.dir, &["show", &spec])?;
+            Some(servers(Bundle::read(data.as_bytes(), None)?.servers))
+        }
+        None => None,
+    };
+    let local = servers(store.list_stored()?);
+    let changes = merge(base.as_ref(), &local, &remote);
+    store.transaction(|| {
+        for change in &changes {
+            let (name, server) = match change {
+                Change::Take { name, server, .. } => (name, server),
+                Change::Conflict { name, remote, .. } if resolution == Some(Resolution::Theirs) => {
+                    (name, remote)
+                }
+                Change::Conflict { .. } => continue,
+            };
+            take(store, name, server.as_ref())?;
+        }
+        Ok(())
+    })?;
+    let conflicts = changes
+        .iter()
+        .any(|change| matches!(change, Change::Conflict { .. }));
+    if resolution.is_some() || !conflicts {
+        store.set_sync_state("commit", &head(config, &repo.dir)?)?;
+    }
+    Ok(changes)
+}
+
+fn take(store: &Store, name: &str, server: Option<&Value>) -> Result<()> {
+    let existing = store.find(name)?;
+    if existing.is_some() {
+        store.remove(name)?;
+    }
+    if let Some(server) = server {
+        let mut server: Server = serde_json::from_value(server.clone())
+            .map_err(|e| JumpError::invalid("synced server", e.to_string()))?;
+        if let Some(existing) = &existing {
+            bundle::keep_password(&mut server, existing);
+        }
+        // Stamped here: the other store's times are its own
+        server.id = existing.as_ref().and_then(|existing| existing.id);
+        server.order_index = existing.as_ref().and_then(|existing| existing.order_index);
+        server.created_at = existing.and_then(|existing| existing.created_at);
+        server.updated_at = None;
+        store.add(&server)?;
+    }
+    Ok(())
+}
+
+/// Three-way merge of server lists, by name. Without a base, as on the first
+/// pull, servers only the remote has are added and any other difference is a
+/// conflict.
+pub fn merge(base: Option<&Servers>, local: &Servers, remote: &Servers) -> Vec<Change> {
+    let empty = Servers::new();
+    let base = base.unwrap_or(&empty);
+    let names: BTreeSet<&String> = base
+        .keys()
+        .chain(local.keys())
+        .chain(remote.keys())
+        .collect();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let (base, local, remote) = (base.get(name), local.get(name), remote.get(name));
+            if local == remote || base == remote {
+                None
+            } else if base == local {
+                Some(Change::Take {
+                    name: name.clone(),
+                    server: remote.cloned(),
+                    existed: local.is_some(),
+                })
+            } else {
+                Some(Change::Conflict {
+                    name: name.clone(),
+                    local: local.cloned(),
+                    remote: remote.cloned(),
+                })
+            }
+        })
+        .collect()
+}
+
+/// The fields two versions of a server disagree on
+pub fn differences(local: &Value, remote: &Value) -> Vec<String> {
+    let (Some(local), Some(remote)) = (local.as_object(), remote.as_object()) else {
+        return vec![];
+    };
+    let keys: BTreeSet<&String> = local.keys().chain(remote.keys()).collect();
+    keys.into_iter()
+        .filter(|key| local.get(*key) != remote.get(*key))
+        .cloned()
+        .collect()
+}
+
+/// Servers as they are synced: without their passwords, and without what
+/// only means something in the store they come from, their order and when
+/// they were added and changed there
+fn synced(servers: Vec<Server>) -> Bundle {
+    let mut bundle = Bundle::new(servers, false);
+    for server in &mut bundle.servers {
+        server.order_index = None;
+        server.created_at = None;
+        server.updated_at = None;
+    }
+    bundle
+}
+
+/// Servers in the form they are compared in, as they are synced
+pub fn servers(servers: Vec<Server>) -> Servers {
+    synced(servers)
+        .servers
+        .iter()
+        .map(|server| {
+            let value = serde_json::to_value(server).expect("servers serialize");
+            (server.server_name.clone(), value)
+        })
+        .collect()
+}
+
+fn write(store: &Store, repo: &Repo) -> Result<()> {
+    let mut servers = store.list_stored()?;
+    servers.sort_by(|a, b| a.server_name.cmp(&b.server_name));
+    let mut json = synced(servers).to_json()?;
+    json.push(b'\n');
+    if let Some(dir) = repo.path().parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(repo.path(), json)?;
+    Ok(())
+}
+
+/// Commit the servers file when it changed
+fn commit(config: &Config, repo: &Repo) -> Result<()> {
+    git(config, &repo.dir, &["add", "--", &repo.file])?;
+    let changed = !git_succeeds(
+        config,
+        &repo.dir,
+        &["diff", "--cached", "--quiet", "--", &repo.file],
+    )?;
+    if changed {
+        git(
+            config,
+            &repo.dir,
+            &[
+                "commit",
+                "--quiet",
+                "-m",
+                "Update jump servers",
+                "--",
+                &repo.file,
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn head(config: &Config, dir: &Path) -> Result<String> {
+    git(config, dir, &["rev-parse", "HEAD"])
+}
+
+/// The first configured remote, if any
+fn remote(config: &Config, dir: &Path) -> Result<Option<String>> {
+    let remotes = git(config, dir, &["remote"])?;
+    Ok(remotes.lines().next().map(str::to_owned))
+}
+
+fn is_remote(target: &str) -> bool {
+    target.contains("://") || (target.contains('@') && target.contains(':'))
+}
+
+fn git_command(config: &Config, dir: &Path, args: &[&str]) -> Result<Command> {
+    let (program, _) = config::resolve(config, Tool::Git)?;
+    debug!("running git {} in {}", args.join(" "), dir.display());
+    let mut command = Command::new(program);
+    command.arg("-C").arg(dir).args(args);
+    Ok(command)
+}
+
+/// Run git, returning its trimmed output; a failure carries git's message
+fn git(config: &Config, dir: &Path, args: &[&str]) -> Result<String> {
+    let output = git_command(config, dir, args)?
+        .output()
+        .map_err(|source| JumpError::Spawn {
+            program: "git".to_owned(),
+            source,
+        })?;
+    if !output.status.success() {
+        return Err(JumpError::CommandFailed {
+            command: format!("git {}", args.join(" ")),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Run git for its exit status, as for `diff --quiet`
+fn git_succeeds(config: &Config, dir: &Path, args: &[&str]) -> Result<bool> {
+    let output = git_command(config, dir, args)?
+        .output()
+        .map_err(|source| JumpError::Spawn {
+            program: "git".to_owned(),
+            source,
+        })?;
+    Ok(output.status.success())
+}
@@ -0,0 +1,182 @@
+//! `conn --group`, which opens a tmux pane or window per server, each
+//! running its own `jump conn`
+
+use std::{env, path::PathBuf};
+
+use clap::ValueEnum;
+
+use crate::{
+    config::{self, Config, Tool},
+    error::{JumpError, Result},
+    ssh::Invocation,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Layout {
+    /// One window, tiled with a pane per server
+    Panes,
+    /// A window per server
+    Windows,
+}
+
+/// Whether jump runs inside a tmux session
+pub fn inside() -> bool {
+    env::var_os("TMUX").is_some_and(|tmux| !tmux.is_empty())
+}
+
+/// The group's panes or windows and how to open them. Inside tmux they go
+/// into the current session; outside it a new session is started, named
+/// after the group, and attached to at the end.
+pub struct Group {
+    tmux: PathBuf,
+    name: String,
+    /// Member names with the shell command run for each
+    members: Vec<(String, String)>,
+    layout: Layout,
+    sync: bool,
+    inside: bool,
+}
+
+impl Group {
+    /// `members` must not be empty
+    pub fn new(
+        config: &Config,
+        name: &str,
+        members: Vec<(String, String)>,
+        layout: Layout,
+        sync: bool,
+    ) -> Result<Group> {
+        let (tmux, _) = config::resolve(config, Tool::Tmux)?;
+        Ok(Group {
+            tmux,
+            name: name.to_owned(),
+            members,
+            layout,
+            sync,
+            inside: inside(),
+        })
+    }
+
+    fn tmux(&self, args: &[&str]) -> Invocation {
+        Invocation {
+            program: self.tmux.clone(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            secret: None,
+            env: vec![],
+        }
+    }
+
+    /// Everything that would be run, with placeholders for the ids tmux
+    /// hands out, for `--dry-run`
+    pub fn plan(&self) -> Vec<Invocation> {
+        let mut plan = vec![self.create()];
+        plan.extend(
+            self.members[1..]
+                .iter()
+                .flat_map(|(name, command)| self.add(name, command, "<session>", "<window>")),
+        );
+        plan.extend(self.finish("<session>", "<window>"));
+        plan
+    }
+
+    /// Open every member. One that cannot be opened is reported on stderr
+    /// and the rest still are; the group fails at the end if any was left
+    /// out, without closing what did open.
+    pub fn open(&self) -> Result<()> {
+        let created = capture(&self.create())?;
+        let (session, window) = created.trim().split_once(' ').unwrap_or(("", ""));
+        let mut failed = vec![];
+        for (name, command) in &self.members[1..] {
+            let added = self
+                .add(name, command, session, window)
+                .iter()
+                .try_for_each(|invocation| capture(invocation).map(|_| ()));
+            if let Err(e) = added {
+                eprintln!("could not open {}: {}", name, e);
+                failed.push(name.clone());
+            }
+        }
+        for invocation in self.finish(session, window) {
+            match invocation.args[0].as_str() {
+                // Takes over the terminal until the session is detached
+                "attach-session" => {
+                    invocation.status()?;
+                }
+                _ => {
+                    capture(&invocation)?;
+                }
+            }
+        }
+        match failed.is_empty() {
+            true => Ok(()),
+            false => Err(JumpError::CommandFailed {
+                command: "tmux".to_owned(),
+                message: format!("could not open {}", failed.join(", ")),
+            }),
+        }
+    }
+
+    /// The new window or session holding the first member, printing the ids
+    /// the others are added to
+    fn create(&self) -> Invocation {
+        let (first, command) = &self.members[0];
+        let window_name = match self.layout {
+            Layout::Panes => &self.name,
+            Layout::Windows => first,
+        };
+        let session_name = format!("jump-{}", self.name);
+        let mut args = match self.inside {
+            true => vec!["new-window"],
+            false => vec!["new-session", "-d", "-s", &session_name],
+        };
+        args.extend([
+            "-P",
+            "-F",
+            "#{session_id} #{window_id}",
+            "-n",
+            window_name,
+            command,
+        ]);
+        self.tmux(&args)
+    }
+
+    /// Adding one more member, then keeping the panes tiled
+    fn add(&self, name: &str, command: &str, session: &str, window: &str) -> Vec<Invocation> {
+        match self.layout {
+            Layout::Panes => vec![
+                self.tmux(&["split-window", "-t", window, command]),
+                self.tmux(&["select-layout", "-t", window, "tiled"]),
+            ],
+            Layout::Windows => vec![self.tmux(&["new-window", "-t", session, "-n", name, command])],
+        }
+    }
+
+    fn finish(&self, session: &str, window: &str) -> Vec<Invocation> {
+        let mut finish = vec![];
+        if self.sync && self.layout == Layout::Panes {
+            finish.push(self.tmux(&["set-window-option", "-t", window, "synchronize-panes", "on"]));
+        }
+        if !self.inside {
+            finish.push(self.tmux(&["attach-session", "-t", session]));
+        }
+        finish
+    }
+}
+
+/// Run a tmux command for its output, failing with what tmux printed
+fn capture(invocation: &Invocation) -> Result<String> {
+    let output = invocation
+        .command()
+        .output()
+        .map_err(|source| JumpError::Spawn {
+            program: invocation.program.display().to_string(),
+            source,
+        })?;
+    if !output.status.success() {
+        return Err(JumpError::CommandFailed {
+            command: format!("tmux {}", invocation.args[0]),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
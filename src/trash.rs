@@ -0,0 +1,87 @@
+//! Servers removed by `jump prune`, kept with their passwords as export
+//! bundles under `~/.jump/trash` so that `jump import <file>` brings a
+//! wrongly pruned one back.
+
+use std::{fs, path::PathBuf};
+
+use crate::{audit, bundle::Bundle, config, debug, error::Result, server::Server, store::Store};
+
+/// `~/.jump/trash`
+pub fn dir() -> Result<PathBuf> {
+    Ok(config::jump_dir()?.join("trash"))
+}
+
+/// Write `servers` to `<dir>/<profile>-<UTC timestamp>.json`, readable by
+/// nobody else, and return the file
+pub fn save(profile: &str, servers: Vec<Server>, now: i64) -> Result<PathBuf> {
+    let stamp = audit::format_time(now)
+        .replace(['-', ':'], "")
+        .replace(' ', "-");
+    let dir = dir()?;
+    create_dir(&dir)?;
+    let mut path = dir.join(format!("{}-{}.json", profile, stamp));
+    // Two prunes within a second
+    let mut count = 1;
+    while path.exists() {
+        count += 1;
+        path = dir.join(format!("{}-{}-{}.json", profile, stamp, count));
+    }
+    write(&path, &Bundle::new(servers, true).to_json()?)?;
+    Ok(path)
+}
+
+/// Remove `servers` from `store` in one transaction after saving them to
+/// the trash, returning the trash file
+pub fn remove(store: &Store, profile: &str, servers: Vec<Server>) -> Result<PathBuf> {
+    store.writable()?;
+    let names = servers
+        .iter()
+        .map(|server| server.server_name.clone())
+        .collect::<Vec<_>>();
+    let file = save(profile, servers, audit::now())?;
+    debug!("saved {} to {}", names.join(", "), file.display());
+    store.transaction(|| {
+        for name in &names {
+            store.remove(name)?;
+        }
+        Ok(())
+    })?;
+    Ok(file)
+}
+
+#[cfg(unix)]
+fn create_dir(dir: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn create_dir(dir: &std::path::Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn write(path: &std::path::Path, data: &[u8]) -> Result<()> {
+    use std::{io::Write, os::unix::fs::OpenOptionsExt};
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(data)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn write(path: &std::path::Path, data: &[u8]) -> Result<()> {
+    fs::write(path, data)?;
+    Ok(())
+}
@@ -0,0 +1,454 @@
+//! The full-screen interface `jump` opens without a subcommand
+
+use std::{
+    collections::HashMap,
+    sync::mpsc::{Receiver, TryRecvError},
+    time::Duration,
+};
+
+use anyhow::Result;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState},
+    Frame,
+};
+
+use crate::{
+    audit,
+    config::Config,
+    server::{ConnectMethods, Password, SSHKey, Server},
+    store::Store,
+    wol,
+};
+
+/// How long a probe waits for a server's ssh port
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often the list is redrawn while probes are out, if no key comes
+const PROBE_REDRAW: Duration = Duration::from_millis(100);
+
+/// What the interface was left for
+pub enum Action {
+    Connect(String),
+    Quit,
+}
+
+pub struct App {
+    servers: Vec<Server>,
+    filter: String,
+    mode: Mode,
+    table: TableState,
+    message: Option<String>,
+    probes: Option<Probes>,
+}
+
+/// Whether each server's ssh port accepts connections, as the background
+/// probes of `tui --probe` find out
+struct Probes {
+    /// The names probed, in the order of the results' indexes
+    names: Vec<String>,
+    results: Receiver<(usize, bool)>,
+    up: HashMap<String, bool>,
+    done: bool,
+}
+
+enum Mode {
+    Browse,
+    Search,
+    ConfirmDelete(String),
+    Add(Form),
+}
+
+const FIELDS: [&str; 6] = [
+    "name",
+    "username",
+    "address",
+    "port",
+    "identity file",
+    "password",
+];
+
+/// The add form; a non-empty password makes a password server, otherwise
+/// the identity file is used
+struct Form {
+    values: [String; 6],
+    focus: usize,
+}
+
+impl Form {
+    fn new(config: &Config) -> Form {
+        Form {
+            values: [
+                String::new(),
+                String::new(),
+                String::new(),
+                config.default_port.to_string(),
+                config.default_identity.clone(),
+                String::new(),
+            ],
+            focus: 0,
+        }
+    }
+
+    fn server(&self) -> Result<Server, String> {
+        let [name, username, address, port, identity, password] = &self.values;
+        if name.is_empty() || username.is_empty() || address.is_empty() {
+            return Err("name, username and address are required".to_owned());
+        }
+        let port = port
+            .parse()
+            .map_err(|_| format!("`{}` is not a valid port", port))?;
+        let method = match password.is_empty() {
+            true => ConnectMethods::SSHKey(SSHKey {
+                path: identity.into(),
+            }),
+            false => ConnectMethods::Password(Password {
+                password: password.clone(),
+            }),
+        };
+        Ok(Server::new(
+            name.clone(),
+            username.clone(),
+            address.clone(),
+            port,
+            method,
+        ))
+    }
+}
+
+impl App {
+    pub fn new(servers: Vec<Server>) -> App {
+        let mut app = App {
+            servers,
+            filter: String::new(),
+            mode: Mode::Browse,
+            table: TableState::default(),
+            message: None,
+            probes: None,
+        };
+        app.clamp_selection();
+        app
+    }
+
+    /// Start probing every server's ssh port, `parallel` at once; rows
+    /// show the results as they come in
+    pub fn probe(&mut self, parallel: usize) {
+        let targets = self
+            .servers
+            .iter()
+            .map(|server| (server.server_address.clone(), server.port))
+            .collect();
+        self.probes = Some(Probes {
+            names: self
+                .servers
+                .iter()
+                .map(|server| server.server_name.clone())
+                .collect(),
+            results: wol::probe_in_background(targets, PROBE_TIMEOUT, parallel),
+            up: HashMap::new(),
+            done: false,
+        });
+    }
+
+    /// Take in the probe results that arrived, without waiting
+    fn receive_probes(&mut self) {
+        let Some(probes) = &mut self.probes else {
+            return;
+        };
+        loop {
+            match probes.results.try_recv() {
+                Ok((i, up)) => {
+                    probes.up.insert(probes.names[i].clone(), up);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    probes.done = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Whether probe results are still to come
+    fn probing(&self) -> bool {
+        self.probes.as_ref().is_some_and(|probes| !probes.done)
+    }
+
+    /// Show `message` in the status line on the next draw
+    pub fn notify(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    /// The servers matching the search, by name, username or address;
+    /// expired ones are left out
+    fn visible(&self) -> Vec<&Server> {
+        let filter = self.filter.to_lowercase();
+        let now = audit::now();
+        self.servers
+            .iter()
+            .filter(|server| !server.expired(now))
+            .filter(|server| {
+                [
+                    &server.server_name,
+                    &server.username,
+                    &server.server_address,
+                ]
+                .iter()
+                .any(|field| field.to_lowercase().contains(&filter))
+            })
+            .collect()
+    }
+
+    fn selected(&self) -> Option<String> {
+        let visible = self.visible();
+        let server = visible.get(self.table.selected()?)?;
+        Some(server.server_name.clone())
+    }
+
+    fn clamp_selection(&mut self) {
+        let len = self.visible().len();
+        let selected = match len {
+            0 => None,
+            _ => Some(self.table.selected().unwrap_or(0).min(len - 1)),
+        };
+        self.table.select(selected);
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let len = self.visible().len();
+        if let Some(selected) = self.table.selected() {
+            let next = (selected as isize + delta).clamp(0, len as isize - 1);
+            self.table.select(Some(next as usize));
+        }
+    }
+
+    fn handle(&mut self, key: KeyEvent, store: &Store, config: &Config) -> Result<Option<Action>> {
+        self.message = None;
+        match &mut self.mode {
+            Mode::Browse => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(Some(Action::Quit)),
+                KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+                KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+                KeyCode::Enter => return Ok(self.selected().map(Action::Connect)),
+                KeyCode::Char('d') => {
+                    if let Some(name) = self.selected() {
+                        self.mode = Mode::ConfirmDelete(name);
+                    }
+                }
+                KeyCode::Char('a') => self.mode = Mode::Add(Form::new(config)),
+                KeyCode::Char('/') => self.mode = Mode::Search,
+                _ => {}
+            },
+            Mode::Search => {
+                match key.code {
+                    KeyCode::Enter => self.mode = Mode::Browse,
+                    KeyCode::Esc => {
+                        self.filter.clear();
+                        self.mode = Mode::Browse;
+                    }
+                    KeyCode::Backspace => {
+                        self.filter.pop();
+                    }
+                    KeyCode::Char(c) => self.filter.push(c),
+                    _ => {}
+                }
+                self.table.select(Some(0));
+                self.clamp_selection();
+            }
+            Mode::ConfirmDelete(name) => {
+                if let KeyCode::Char('y') = key.code {
+                    match store.remove(name) {
+                        Ok(_) => {
+                            self.message = Some(format!("Removed {}", name));
+                            self.servers = store.list()?;
+                            self.clamp_selection();
+                        }
+                        Err(e) => self.message = Some(e.to_string()),
+                    }
+                }
+                self.mode = Mode::Browse;
+            }
+            Mode::Add(form) => match key.code {
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Tab | KeyCode::Down => form.focus = (form.focus + 1) % FIELDS.len(),
+                KeyCode::BackTab | KeyCode::Up => {
+                    form.focus = (form.focus + FIELDS.len() - 1) % FIELDS.len()
+                }
+                KeyCode::Backspace => {
+                    form.values[form.focus].pop();
+                }
+                KeyCode::Char(c) => form.values[form.focus].push(c),
+                KeyCode::Enter => match form.server() {
+                    Ok(server) => match store.add(&server) {
+                        Ok(()) => {
+                            self.message = Some(format!("Added {}", server.server_name));
+                            self.servers = store.list()?;
+                            self.mode = Mode::Browse;
+                            self.clamp_selection();
+                        }
+                        Err(e) => self.message = Some(e.to_string()),
+                    },
+                    Err(e) => self.message = Some(e),
+                },
+                _ => {}
+            },
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [list, status] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let rows = self
+            .visible()
+            .into_iter()
+            .map(|server| {
+                let auth = match &server.method {
+                    ConnectMethods::SSHKey(key) => key.path.display().to_string(),
+                    ConnectMethods::Password(_) => "password".to_owned(),
+                    ConnectMethods::Command(_) => "custom".to_owned(),
+                    ConnectMethods::SshConfig(config) => format!("ssh config: {}", config.alias),
+                    ConnectMethods::Unsupported(_) => ConnectMethods::UNSUPPORTED.to_owned(),
+                };
+                let mut cells = vec![
+                    Cell::from(server.server_name.clone()),
+                    Cell::from(server.username.clone()),
+                    Cell::from(server.server_address.clone()),
+                    Cell::from(server.port.to_string()),
+                    Cell::from(auth),
+                ];
+                if let Some(probes) = &self.probes {
+                    // Grey until the probe is back
+                    let color = match probes.up.get(&server.server_name) {
+                        Some(true) => Color::Green,
+                        Some(false) => Color::Red,
+                        None => Color::DarkGray,
+                    };
+                    cells.insert(0, Cell::from("●").style(Style::new().fg(color)));
+                }
+                Row::new(cells)
+            })
+            .collect::<Vec<_>>();
+        let mut widths = vec![
+            Constraint::Percentage(20),
+            Constraint::Percentage(15),
+            Constraint::Percentage(25),
+            Constraint::Length(6),
+            Constraint::Fill(1),
+        ];
+        let mut header = vec!["NAME", "USER", "ADDRESS", "PORT", "AUTH"];
+        if self.probes.is_some() {
+            widths.insert(0, Constraint::Length(1));
+            header.insert(0, "");
+        }
+        let table = Table::new(rows, widths)
+            .header(Row::new(header).style(Style::new().add_modifier(Modifier::BOLD)))
+            .block(Block::default().borders(Borders::ALL).title(" jump "))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, list, &mut self.table);
+
+        let line = match (&self.mode, &self.message) {
+            (Mode::Search, _) => format!("/{}", self.filter),
+            (_, Some(message)) => message.clone(),
+            _ if !self.filter.is_empty() => format!("filter: {}  (/ to change)", self.filter),
+            _ => match &self.probes {
+                Some(probes) if !probes.done => format!(
+                    "enter connect  / search  a add  d delete  q quit  (probed {} of {})",
+                    probes.up.len(),
+                    probes.names.len()
+                ),
+                _ => "enter connect  / search  a add  d delete  q quit".to_owned(),
+            },
+        };
+        frame.render_widget(Paragraph::new(line), status);
+
+        match &self.mode {
+            Mode::ConfirmDelete(name) => {
+                let area = centered(frame.area(), 40, 3);
+                frame.render_widget(Clear, area);
+                frame.render_widget(
+                    Paragraph::new(format!("Delete {}? (y/n)", name))
+                        .block(Block::default().borders(Borders::ALL)),
+                    area,
+                );
+            }
+            Mode::Add(form) => {
+                let area = centered(frame.area(), 60, FIELDS.len() as u16 + 2);
+                let lines = FIELDS
+                    .iter()
+                    .zip(&form.values)
+                    .enumerate()
+                    .map(|(i, (field, value))| {
+                        let value = match *field {
+                            "password" => "*".repeat(value.len()),
+                            _ => value.clone(),
+                        };
+                        let line = Line::from(format!("{:>14}: {}", field, value));
+                        match i == form.focus {
+                            true => line.style(Style::new().add_modifier(Modifier::REVERSED)),
+                            false => line,
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                frame.render_widget(Clear, area);
+                frame.render_widget(
+                    Paragraph::new(lines).block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title(" add server (tab next, enter save, esc cancel) "),
+                    ),
+                    area,
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// Puts the terminal back however the interface is left, including by `?`
+struct Restore;
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
+
+/// Run the interface until the user quits or picks a server to connect to.
+/// The terminal is restored before returning, so the caller can hand it to
+/// ssh and call `run` again with the same `app` afterwards.
+pub fn run(app: &mut App, store: &Store, config: &Config) -> Result<Action> {
+    let mut terminal = ratatui::init();
+    let _restore = Restore;
+    loop {
+        app.receive_probes();
+        terminal.draw(|frame| app.draw(frame))?;
+        // While probes are out, wake up now and then to show their results;
+        // keys are handled as soon as they come either way
+        if app.probing() && !event::poll(PROBE_REDRAW)? {
+            continue;
+        }
+        // Resize events need no handling beyond the redraw above
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                if let Some(action) = app.handle(key, store, config)? {
+                    return Ok(action);
+                }
+            }
+        }
+    }
+}
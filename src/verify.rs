@@ -0,0 +1,263 @@
+//! `jump verify`: every row of a store read through the strict parsers,
+//! with the files and rows it refers to, as a list of problems per server.
+//! Errors are what would make jump fail or misbehave; warnings are what
+//! still works but should be cleaned up. Where `jump doctor` knows a
+//! mechanical fix it comes along, for `jump verify --fix`.
+
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    config,
+    doctor::{self, Fix},
+    error::Result,
+    hostkey::HostKey,
+    proxy,
+    server::{ConnectMethods, Platform, SSHKey, SshConfig, X11},
+    ssh::shell_quote,
+    ssh_config,
+    store::{RawServer, Store},
+    wol,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Something wrong with a server, or with the store when `server_name` is
+/// `None`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub server_name: Option<String>,
+    pub severity: Severity,
+    pub problem: String,
+    pub fix: Option<Fix>,
+}
+
+/// The problems of the store at `store_path`, store-wide ones first and
+/// then by server in stored order
+pub fn check(store_path: &Path) -> Result<Vec<Problem>> {
+    let mut problems = vec![];
+    if !store_path.exists() {
+        problems.push(Problem {
+            server_name: None,
+            severity: Severity::Error,
+            problem: format!("there is no store at {}", store_path.display()),
+            fix: None,
+        });
+        return Ok(problems);
+    }
+    let store = Store::open_as_is(store_path)?;
+    let mut store_problem = |severity, problem: String, fix| {
+        problems.push(Problem {
+            server_name: None,
+            severity,
+            problem,
+            fix,
+        })
+    };
+    if !store.is_initialized()? {
+        store_problem(
+            Severity::Error,
+            "there is no servers table".to_owned(),
+            Some(Fix::Manual("jump init".to_owned())),
+        );
+        return Ok(problems);
+    }
+    let pending = store.pending_migrations()?;
+    if pending > 0 {
+        // The columns the other checks read may not be there yet
+        store_problem(
+            Severity::Error,
+            format!(
+                "the store is missing {} schema change{}, so its servers were not checked",
+                pending,
+                if pending == 1 { "" } else { "s" }
+            ),
+            Some(Fix::Migrate(store_path.to_owned())),
+        );
+        return Ok(problems);
+    }
+    let dangling = store.dangling_rows()?;
+    if !dangling.is_empty() {
+        let mut tables = dangling
+            .iter()
+            .map(|(table, _)| table.as_str())
+            .collect::<Vec<_>>();
+        tables.dedup();
+        store_problem(
+            Severity::Error,
+            format!(
+                "{} row{} in {} belong to servers that are gone",
+                dangling.len(),
+                if dangling.len() == 1 { "" } else { "s" },
+                tables.join(", ")
+            ),
+            Some(Fix::DanglingRows(store_path.to_owned())),
+        );
+    }
+    let servers = store.raw_servers()?;
+    let ssh_hosts = match servers
+        .iter()
+        .any(|server| server.method.starts_with("config:"))
+    {
+        true => ssh_config::load()?,
+        false => vec![],
+    };
+    for server in servers {
+        check_server(store_path, &server, &ssh_hosts, &mut problems)?;
+    }
+    Ok(problems)
+}
+
+fn check_server(
+    store_path: &Path,
+    server: &RawServer,
+    ssh_hosts: &[ssh_config::Host],
+    problems: &mut Vec<Problem>,
+) -> Result<()> {
+    let name = &server.server_name;
+    let mut problem = |severity, problem: String, fix| {
+        problems.push(Problem {
+            server_name: Some(name.clone()),
+            severity,
+            problem,
+            fix,
+        })
+    };
+    let required = [
+        ("name", &server.server_name),
+        ("username", &server.username),
+        ("address", &server.server_address),
+    ];
+    for (field, value) in required {
+        if value.trim().is_empty() {
+            problem(Severity::Error, format!("the {} is empty", field), None);
+        }
+    }
+    match server.port {
+        Some(1..=65535) => {}
+        Some(port) => problem(
+            Severity::Error,
+            format!("the port {} is outside 1-65535", port),
+            None,
+        ),
+        None => problem(Severity::Error, "the port is not a number".to_owned(), None),
+    }
+
+    let method = match doctor::current_method(&server.method) {
+        Some(method) if method == server.method => Some(method),
+        Some(method) => {
+            problem(
+                Severity::Warning,
+                "the method is in an old format".to_owned(),
+                Some(Fix::Method {
+                    store: store_path.to_owned(),
+                    server_name: name.clone(),
+                    method: method.clone(),
+                }),
+            );
+            Some(method)
+        }
+        None => {
+            // Only the kind, as the rest may be a password
+            let kind = server.method.split(':').next().unwrap_or_default();
+            problem(
+                Severity::Error,
+                format!("the method `{}:…` is not one jump knows", kind),
+                Some(Fix::Manual("jump edit --all --include-secrets".to_owned())),
+            );
+            None
+        }
+    };
+    match method.map(ConnectMethods::from) {
+        Some(ConnectMethods::SSHKey(SSHKey { path })) => {
+            let key = config::expand_path(&path)?;
+            if !key.exists() {
+                problem(
+                    Severity::Error,
+                    format!("the key {} does not exist", key.display()),
+                    Some(Fix::Manual(format!(
+                        "jump set key <path> --filter name:{}",
+                        shell_quote(name)
+                    ))),
+                );
+            } else if doctor::is_open(&key) {
+                problem(
+                    Severity::Warning,
+                    format!("the key {} can be read by others", key.display()),
+                    Some(Fix::Chmod(key)),
+                );
+            }
+        }
+        Some(ConnectMethods::SshConfig(SshConfig { alias }))
+            if !ssh_config::declares(ssh_hosts, &alias) =>
+        {
+            problem(
+                Severity::Error,
+                format!(
+                    "there is no `Host {}` in {} any more",
+                    alias,
+                    ssh_config::path()?.display()
+                ),
+                None,
+            );
+        }
+        _ => {}
+    }
+
+    if X11::from_str(&server.x11, true).is_err() {
+        problem(
+            Severity::Error,
+            format!("the X11 mode `{}` is not one jump knows", server.x11),
+            None,
+        );
+    }
+    if let Some(platform) = &server.platform {
+        if Platform::from_str(platform, true).is_err() {
+            problem(
+                Severity::Warning,
+                format!("the platform `{}` is not one jump knows", platform),
+                None,
+            );
+        }
+    }
+    if let Some(reason) = server
+        .proxy
+        .as_deref()
+        .and_then(|spec| proxy::parse(spec).err())
+    {
+        problem(
+            Severity::Error,
+            format!("the proxy is invalid: {}", reason),
+            None,
+        );
+    }
+    if let Some(mac) = &server.mac {
+        if wol::parse_mac(mac).is_err() {
+            problem(
+                Severity::Error,
+                format!("the MAC address `{}` is invalid", mac),
+                None,
+            );
+        }
+    }
+    if let Some(host_key) = &server.host_key {
+        if HostKey::parse(host_key).is_none() {
+            problem(
+                Severity::Warning,
+                "the recorded host key does not parse".to_owned(),
+                Some(Fix::Manual(format!(
+                    "jump fingerprint fetch {}",
+                    shell_quote(name)
+                ))),
+            );
+        }
+    }
+    Ok(())
+}
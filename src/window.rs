@@ -0,0 +1,131 @@
+//! `conn --new-window`: running a command in a terminal window of its own,
+//! through the `terminal_command` template or an emulator found on the
+//! system
+
+use std::{path::PathBuf, process::Stdio};
+
+use crate::{
+    config::Config,
+    error::{JumpError, Result},
+    ssh::{shell_quote, Invocation},
+};
+
+/// Emulators tried in order on Linux and the BSDs, with the arguments that
+/// come before the command
+#[cfg(all(unix, not(target_os = "macos")))]
+const EMULATORS: [(&str, &[&str]); 9] = [
+    ("x-terminal-emulator", &["-e"]),
+    ("gnome-terminal", &["--"]),
+    ("konsole", &["-e"]),
+    ("xfce4-terminal", &["-x"]),
+    ("alacritty", &["-e"]),
+    ("kitty", &[]),
+    ("wezterm", &["start", "--"]),
+    ("foot", &[]),
+    ("xterm", &["-e"]),
+];
+
+/// How to run `command` in a new window: the configured template with
+/// `{cmd}` replaced, or else the first emulator found; `None` without one
+pub fn launcher(config: &Config, command: &[String]) -> Result<Option<Invocation>> {
+    let Some(template) = &config.terminal_command else {
+        return Ok(detect(command));
+    };
+    if !template.contains("{cmd}") {
+        return Err(JumpError::invalid(
+            "terminal_command",
+            format!("`{}` has no {{cmd}} for the command to run", template),
+        ));
+    }
+    let quoted = command
+        .iter()
+        .map(|word| shell_quote(word))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let (program, flag) = match cfg!(windows) {
+        true => ("cmd", "/C"),
+        false => ("sh", "-c"),
+    };
+    Ok(Some(invocation(
+        program,
+        &[flag],
+        &[template.replace("{cmd}", &quoted)],
+    )))
+}
+
+fn invocation(program: impl Into<PathBuf>, before: &[&str], command: &[String]) -> Invocation {
+    Invocation {
+        program: program.into(),
+        args: before
+            .iter()
+            .map(|arg| arg.to_string())
+            .chain(command.iter().cloned())
+            .collect(),
+        secret: None,
+        env: vec![],
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn detect(command: &[String]) -> Option<Invocation> {
+    let graphical = ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|var| std::env::var_os(var).is_some_and(|value| !value.is_empty()));
+    if !graphical {
+        return None;
+    }
+    if let Some(terminal) = std::env::var_os("TERMINAL").filter(|terminal| !terminal.is_empty()) {
+        return Some(invocation(terminal, &["-e"], command));
+    }
+    EMULATORS.iter().find_map(|(name, before)| {
+        crate::config::search_path(name).map(|path| invocation(path, before, command))
+    })
+}
+
+/// Terminal.app, told through AppleScript to run the command
+#[cfg(target_os = "macos")]
+fn detect(command: &[String]) -> Option<Invocation> {
+    let quoted = command
+        .iter()
+        .map(|word| shell_quote(word))
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"");
+    Some(invocation(
+        "osascript",
+        &[
+            "-e",
+            &format!("tell application \"Terminal\" to do script \"{}\"", quoted),
+            "-e",
+            "tell application \"Terminal\" to activate",
+        ],
+        &[],
+    ))
+}
+
+/// Windows Terminal when installed, or a console window from `start`
+#[cfg(windows)]
+fn detect(command: &[String]) -> Option<Invocation> {
+    Some(match crate::config::search_path("wt") {
+        Some(wt) => invocation(wt, &[], command),
+        None => invocation("cmd", &["/C", "start", ""], command),
+    })
+}
+
+/// Start the terminal without waiting for it, in its own process group so
+/// that closing this terminal does not close the new window
+pub fn spawn_detached(invocation: &Invocation) -> Result<()> {
+    let mut command = invocation.command();
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    command.spawn().map_err(|source| JumpError::Spawn {
+        program: invocation.program.display().to_string(),
+        source,
+    })?;
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use std::{
+    io::{self, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    config::Config,
+    log::{self, Verbosity},
+    schedule::{self, Event, Limits},
+    server::Server,
+};
+
+pub const DEFAULT_BROADCAST: &str = "255.255.255.255";
+
+/// Parse a MAC address written as `aa:bb:cc:dd:ee:ff` or `aa-bb-cc-dd-ee-ff`
+pub fn parse_mac(mac: &str) -> Result<[u8; 6]> {
+    let separator = if mac.contains('-') { '-' } else { ':' };
+    let parts = mac.split(separator).collect::<Vec<_>>();
+    if parts.len() != 6 {
+        return Err(anyhow!("Invalid MAC address `{}`", mac));
+    }
+    let mut bytes = [0; 6];
+    for (byte, part) in bytes.iter_mut().zip(parts) {
+        if part.len() != 2 {
+            return Err(anyhow!("Invalid MAC address `{}`", mac));
+        }
+        *byte =
+            u8::from_str_radix(part, 16).map_err(|_| anyhow!("Invalid MAC address `{}`", mac))?;
+    }
+    Ok(bytes)
+}
+
+/// Six 0xff bytes followed by the MAC address sixteen times
+pub fn magic_packet(mac: [u8; 6]) -> [u8; 102] {
+    let mut packet = [0xff; 102];
+    for chunk in packet[6..].chunks_mut(6) {
+        chunk.copy_from_slice(&mac);
+    }
+    packet
+}
+
+/// Broadcast the magic packet for `mac` to the discard port of `broadcast`
+pub fn wake(mac: [u8; 6], broadcast: &str) -> Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_broadcast(true)?;
+    socket.send_to(&magic_packet(mac), (broadcast, 9))?;
+    Ok(())
+}
+
+/// Whether a TCP connection to `address:port` succeeds within `timeout`
+pub fn probe(address: &str, port: u32, timeout: Duration) -> bool {
+    socket_addrs(address, port)
+        .iter()
+        .any(|addr| TcpStream::connect_timeout(addr, timeout).is_ok())
+}
+
+/// Whether each server answers on its port, and how long it took to tell,
+/// as many probed at once as `limits` allow. A probe only opens a TCP
+/// connection to the server itself, so no bastion is in the way.
+pub fn probe_all(servers: &[Server], timeout: Duration, limits: Limits) -> Vec<(bool, Duration)> {
+    let mut probes = vec![(false, Duration::ZERO); servers.len()];
+    schedule::run(
+        &vec![None; servers.len()],
+        limits,
+        |i| {
+            let started = Instant::now();
+            let up = probe(&servers[i].server_address, servers[i].port, timeout);
+            (up, started.elapsed())
+        },
+        |event| {
+            if let Event::Finished(i, probe) = event {
+                probes[i] = probe;
+            }
+            true
+        },
+    );
+    probes
+}
+
+/// Probe every `(address, port)` of `targets` on up to `threads` threads,
+/// sending each one's index and whether it is up as soon as it is known.
+/// The probes run in the background; once the receiver is dropped, they
+/// stop at the next result.
+pub fn probe_in_background(
+    targets: Vec<(String, u32)>,
+    timeout: Duration,
+    threads: usize,
+) -> Receiver<(usize, bool)> {
+    let (sender, receiver) = mpsc::channel();
+    let threads = threads.clamp(1, targets.len().max(1));
+    let queue = Arc::new(Mutex::new(targets.into_iter().enumerate()));
+    for _ in 0..threads {
+        let queue = Arc::clone(&queue);
+        let sender = sender.clone();
+        thread::spawn(move || loop {
+            let next = queue.lock().expect("no probe panics holding it").next();
+            let Some((i, (address, port))) = next else {
+                break;
+            };
+            if sender.send((i, probe(&address, port, timeout))).is_err() {
+                break;
+            }
+        });
+    }
+    receiver
+}
+
+/// Poll `address:port` every `interval` until it accepts connections,
+/// calling `tick` after each failed attempt. Returns false on timeout.
+pub fn wait_for_port(
+    address: &str,
+    port: u32,
+    timeout: Duration,
+    interval: Duration,
+    mut tick: impl FnMut(),
+) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if probe(address, port, interval) {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        tick();
+        thread::sleep(interval);
+    }
+}
+
+/// Wake the host and block until its ssh port opens, printing progress dots
+pub fn wake_and_wait(
+    mac: [u8; 6],
+    broadcast: &str,
+    address: &str,
+    port: u32,
+    timeout: Duration,
+) -> Result<()> {
+    wake(mac, broadcast)?;
+    let progress = log::enabled(Verbosity::Normal);
+    if progress {
+        eprint!("waking {}", address);
+    }
+    let up = wait_for_port(address, port, timeout, Duration::from_secs(1), || {
+        if progress {
+            eprint!(".");
+            io::stderr().flush().ok();
+        }
+    });
+    if progress {
+        eprintln!();
+    }
+    match up {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "{}:{} did not come up within {}s",
+            address,
+            port,
+            timeout.as_secs()
+        )),
+    }
+}
+
+/// Wake the server when asked to, or when `wake_on_fail` is set and a quick
+/// probe of its ssh port fails
+pub fn wake_if_needed(config: &Config, server: &Server, requested: bool) -> Result<()> {
+    let automatic =
+        server.wake_on_fail && !probe(&server.server_address, server.port, Duration::from_secs(2));
+    if !requested && !automatic {
+        return Ok(());
+    }
+    let mac = server
+        .mac
+        .as_deref()
+        .ok_or(anyhow!("No MAC address stored for {}", server.server_name))?;
+    wake_and_wait(
+        parse_mac(mac)?,
+        server.broadcast.as_deref().unwrap_or(DEFAULT_BROADCAST),
+        &server.server_address,
+        server.port,
+        Duration::from_secs(config.wake_timeout),
+    )
+}
+
+fn socket_addrs(address: &str, port: u32) -> Vec<SocketAddr> {
+    u16::try_from(port)
+        .ok()
+        .and_then(|port| (address, port).to_socket_addrs().ok())
+        .map(|addrs| addrs.collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,435 @@
+mod common;
+
+use std::path::Path;
+
+use common::{home, jump};
+use jiff::{civil::date, tz::TimeZone};
+use jump::{
+    access::{self, Window},
+    audit,
+    error::JumpError,
+    server::{ConnectMethods, SSHKey, Server},
+};
+
+/// Seconds since the epoch of a UTC date and time
+fn utc(year: i16, month: i8, day: i8, hour: i8, minute: i8) -> i64 {
+    date(year, month, day)
+        .at(hour, minute, 0, 0)
+        .to_zoned(TimeZone::UTC)
+        .unwrap()
+        .timestamp()
+        .as_second()
+}
+
+/// When a window open from 09:00 every day next opens after `utc`
+fn nine(zone: &str, utc: i64) -> Option<i64> {
+    Window::parse(&format!("Mon-Sun 09:00-17:00 {}", zone))
+        .unwrap()
+        .next_open(utc)
+}
+
+const BERLIN: &str = "CET-1CEST,M3.5.0,M10.5.0/3";
+
+#[test]
+fn windows_are_parsed_and_written_out_again() {
+    let window = |text: &str| Window::parse(text).map(|window| window.to_string());
+    assert_eq!(
+        window("mon-fri 09:00-17:00 UTC").unwrap(),
+        "Mon-Fri 09:00-17:00 UTC"
+    );
+    assert_eq!(
+        window("Fri-Mon 22:00-06:00 +05:30").unwrap(),
+        "Mon,Fri-Sun 22:00-06:00 +05:30"
+    );
+    assert_eq!(
+        window("Sat,Sun,Wed 00:00-24:00 UTC").unwrap(),
+        "Wed,Sat-Sun 00:00-24:00 UTC"
+    );
+    for invalid in [
+        "Mon-Fri 09:00-17:00",
+        "Mon-Fri 9:00-17:00 UTC",
+        "Mon-Fri 09:00-25:00 UTC",
+        "Mon-Fri 24:00-06:00 UTC",
+        "Someday 09:00-17:00 UTC",
+        "Mon-Fri 09:00-09:00 UTC",
+        "Mon-Fri 09:00-17:00 Mars/Olympus_Mons",
+    ] {
+        assert!(window(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn posix_rules_switch_at_their_boundaries() {
+    // Into summer time on the last Sunday of March
+    assert_eq!(
+        nine(BERLIN, utc(2024, 3, 29, 12, 0)),
+        Some(utc(2024, 3, 30, 8, 0))
+    );
+    assert_eq!(
+        nine(BERLIN, utc(2024, 3, 30, 12, 0)),
+        Some(utc(2024, 3, 31, 7, 0))
+    );
+    // Back on the last Sunday of October
+    assert_eq!(
+        nine(BERLIN, utc(2024, 10, 26, 12, 0)),
+        Some(utc(2024, 10, 27, 8, 0))
+    );
+
+    // In the south, summer spans the new year
+    let sydney = "AEST-10AEDT,M10.1.0,M4.1.0/3";
+    assert_eq!(
+        nine(sydney, utc(2024, 1, 14, 12, 0)),
+        Some(utc(2024, 1, 14, 22, 0))
+    );
+    assert_eq!(
+        nine(sydney, utc(2024, 7, 14, 12, 0)),
+        Some(utc(2024, 7, 14, 23, 0))
+    );
+    assert_eq!(
+        nine(sydney, utc(2024, 4, 6, 12, 0)),
+        Some(utc(2024, 4, 6, 23, 0))
+    );
+    assert_eq!(
+        nine(sydney, utc(2024, 10, 5, 12, 0)),
+        Some(utc(2024, 10, 5, 22, 0))
+    );
+
+    // Without dates, the US ones
+    assert_eq!(
+        nine("EST5EDT", utc(2024, 3, 9, 12, 0)),
+        Some(utc(2024, 3, 9, 14, 0))
+    );
+    assert_eq!(
+        nine("EST5EDT", utc(2024, 3, 9, 15, 0)),
+        Some(utc(2024, 3, 10, 13, 0))
+    );
+
+    assert_eq!(
+        nine("-03:30", utc(2024, 3, 9, 0, 0)),
+        Some(utc(2024, 3, 9, 12, 30))
+    );
+}
+
+#[test]
+fn windows_follow_daylight_saving_time() {
+    let window = Window::parse(&format!("Mon-Fri 09:00-17:00 {}", BERLIN)).unwrap();
+    // 09:30 CET on the Friday before, and 09:30 CEST on the Monday after
+    assert!(window.is_open(utc(2024, 3, 29, 8, 30)));
+    assert!(window.is_open(utc(2024, 4, 1, 7, 30)));
+    assert!(!window.is_open(utc(2024, 4, 1, 15, 30)));
+    assert_eq!(
+        window.next_open(utc(2024, 3, 29, 16, 0)),
+        Some(utc(2024, 4, 1, 7, 0))
+    );
+    assert_eq!(
+        window.local_time(utc(2024, 4, 1, 7, 0)),
+        format!("2024-04-01 09:00 {}", BERLIN)
+    );
+
+    // 02:30 never comes on the day the clocks go forward, so the window
+    // opens as the gap ends
+    let window = Window::parse(&format!("Sun 02:30-04:00 {}", BERLIN)).unwrap();
+    assert_eq!(
+        window.next_open(utc(2024, 3, 30, 12, 0)),
+        Some(utc(2024, 3, 31, 1, 0))
+    );
+    // And 02:30 comes twice as they go back; the window opens at the first
+    assert_eq!(
+        window.next_open(utc(2024, 10, 26, 12, 0)),
+        Some(utc(2024, 10, 27, 0, 30))
+    );
+    // A window wholly in the gap stays shut that day
+    let window = Window::parse(&format!("Sun 02:15-02:45 {}", BERLIN)).unwrap();
+    assert_eq!(
+        window.next_open(utc(2024, 3, 30, 12, 0)),
+        Some(utc(2024, 4, 7, 0, 15))
+    );
+}
+
+#[test]
+fn windows_across_midnight_belong_to_the_day_they_open() {
+    // 2024-01-06 is a Saturday
+    let window = Window::parse("Sat,Sun 22:00-06:00 UTC").unwrap();
+    assert!(!window.is_open(utc(2024, 1, 5, 23, 0)));
+    assert!(!window.is_open(utc(2024, 1, 6, 5, 0)));
+    assert!(window.is_open(utc(2024, 1, 6, 23, 0)));
+    assert!(window.is_open(utc(2024, 1, 7, 5, 0)));
+    assert!(window.is_open(utc(2024, 1, 8, 5, 59)));
+    assert!(!window.is_open(utc(2024, 1, 8, 6, 0)));
+    assert!(!window.is_open(utc(2024, 1, 8, 23, 0)));
+    assert_eq!(
+        window.next_open(utc(2024, 1, 8, 6, 0)),
+        Some(utc(2024, 1, 13, 22, 0))
+    );
+}
+
+#[test]
+fn database_zones_are_followed() {
+    if !Path::new("/usr/share/zoneinfo/Europe/Berlin").exists() {
+        return;
+    }
+    let berlin = "Europe/Berlin";
+    assert_eq!(
+        nine(berlin, utc(2024, 3, 30, 12, 0)),
+        Some(utc(2024, 3, 31, 7, 0))
+    );
+    assert_eq!(
+        nine(berlin, utc(2024, 10, 26, 12, 0)),
+        Some(utc(2024, 10, 27, 8, 0))
+    );
+    // Past the transitions in the database, by the rule at its end
+    assert_eq!(
+        nine(berlin, utc(2060, 3, 26, 12, 0)),
+        Some(utc(2060, 3, 27, 8, 0))
+    );
+    assert_eq!(
+        nine(berlin, utc(2060, 3, 27, 12, 0)),
+        Some(utc(2060, 3, 28, 7, 0))
+    );
+    // Before summer time came back in 1980
+    assert_eq!(
+        nine(berlin, utc(1975, 6, 30, 12, 0)),
+        Some(utc(1975, 7, 1, 8, 0))
+    );
+}
+
+#[test]
+fn closed_and_unreadable_windows_need_an_override() {
+    let server = |window: Option<&str>| {
+        let mut server = Server::new(
+            "web".to_owned(),
+            "deploy".to_owned(),
+            "10.0.0.1".to_owned(),
+            22,
+            ConnectMethods::SSHKey(SSHKey {
+                path: "/keys/web".into(),
+            }),
+        );
+        server.access_window = window.map(str::to_owned);
+        server
+    };
+    // A Saturday
+    let now = utc(2024, 1, 6, 12, 0);
+
+    let always = server(None);
+    assert!(access::pass(&always, None, now).is_ok());
+    assert!(!access::needs_override(&always, now));
+    let open = server(Some("Sat 09:00-17:00 UTC"));
+    assert!(access::pass(&open, None, now).is_ok());
+    assert!(!access::needs_override(&open, now));
+
+    let closed = server(Some("Mon-Fri 09:00-17:00 UTC"));
+    match access::pass(&closed, None, now) {
+        Err(JumpError::OutsideWindow { window, next, .. }) => {
+            assert_eq!(window, "Mon-Fri 09:00-17:00 UTC");
+            assert_eq!(next, "2024-01-08 09:00 UTC");
+        }
+        other => panic!("{:?}", other),
+    }
+    assert!(access::needs_override(&closed, now));
+    assert!(access::pass(&closed, Some("INC-42"), now).is_ok());
+
+    let unreadable = server(Some("Mon-Fri 09:00-17:00 Mars/Olympus_Mons"));
+    assert!(matches!(
+        access::pass(&unreadable, None, now),
+        Err(JumpError::InvalidWindow { .. })
+    ));
+    assert!(access::needs_override(&unreadable, now));
+    assert!(access::pass(&unreadable, Some("INC-42"), now).is_ok());
+}
+
+/// A window that is closed for at least the next hour
+fn closed_window() -> String {
+    let hour = audit::now().rem_euclid(86_400) / 3_600;
+    format!(
+        "Mon-Sun {:02}:00-{:02}:00 UTC",
+        (hour + 2) % 24,
+        (hour + 3) % 24
+    )
+}
+
+#[cfg(unix)]
+mod cli {
+    use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf};
+
+    use super::*;
+    use crate::common::command;
+
+    /// A store with `web`, whose window is closed, and `db`, whose window
+    /// is always open, and a fake ssh that writes its arguments to `args`
+    fn store(test: &str) -> PathBuf {
+        let home = home(test);
+        jump(&home, &["init"]);
+        let closed = closed_window();
+        for (name, window) in [("web", closed.as_str()), ("db", "Mon-Sun 00:00-24:00 UTC")] {
+            jump(
+                &home,
+                &[
+                    "add",
+                    name,
+                    "deploy",
+                    "10.0.0.1",
+                    "--access-window",
+                    window,
+                    "ssh-key",
+                ],
+            );
+        }
+        jump(&home, &["add", "cache", "deploy", "10.0.0.2", "ssh-key"]);
+        fs::write(home.join("config.toml"), "track_keys = false\n").unwrap();
+        fs::write(
+            home.join("ssh"),
+            format!("#!/bin/sh\necho \"$@\" > {}\n", home.join("args").display()),
+        )
+        .unwrap();
+        fs::set_permissions(home.join("ssh"), fs::Permissions::from_mode(0o755)).unwrap();
+        home
+    }
+
+    fn conn(home: &Path, args: &[&str]) -> std::process::Output {
+        command(home)
+            .arg("conn")
+            .args(args)
+            .env("JUMP_SSH_BIN", home.join("ssh"))
+            .env("JUMP_SSH_KEYSCAN_BIN", home.join("no-keyscan"))
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn servers_outside_their_window_are_refused() {
+        let home = store("access-refused");
+        let output = conn(&home, &["web"]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains(&format!(
+                "web may only be accessed {}, next at",
+                closed_window()
+            )),
+            "{}",
+            stderr
+        );
+        assert!(stderr.contains("--override-window"), "{}", stderr);
+        assert!(!home.join("args").exists());
+
+        let output = conn(&home, &["db"]);
+        assert!(output.status.success(), "{:?}", output);
+
+        // Nor is anything run on it
+        let output = command(&home)
+            .args(["exec", "web", "--", "uptime"])
+            .env("JUMP_SSH_BIN", home.join("ssh"))
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("web may only be accessed"));
+    }
+
+    #[test]
+    fn an_override_goes_ahead_and_is_logged() {
+        let home = store("access-override");
+        let output = conn(&home, &["web", "--override-window", "INC-42 outage"]);
+        assert!(output.status.success(), "{:?}", output);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("web is outside its access window"),
+            "{}",
+            stderr
+        );
+        assert!(stderr.contains("going ahead: INC-42 outage"), "{}", stderr);
+
+        // Only where it was needed
+        conn(&home, &["db", "--override-window", "INC-42 outage"]);
+        let log = jump(&home, &["log", "--output", "json"]);
+        let log = serde_json::from_str::<serde_json::Value>(&log).unwrap();
+        let window = |name: &str| {
+            log.as_array()
+                .unwrap()
+                .iter()
+                .find(|entry| entry["server_name"] == name)
+                .unwrap()["override_window"]
+                .clone()
+        };
+        assert_eq!(window("web"), "INC-42 outage");
+        assert_eq!(window("db"), serde_json::Value::Null);
+        assert!(jump(&home, &["--color", "never", "log"]).contains("overridden: INC-42 outage"));
+    }
+
+    #[test]
+    fn windows_that_cannot_be_read_are_closed() {
+        if !Path::new("/usr/share/zoneinfo/Europe/Berlin").exists() {
+            return;
+        }
+        let home = store("access-unreadable");
+        jump(
+            &home,
+            &[
+                "add",
+                "berlin",
+                "deploy",
+                "10.0.0.3",
+                "--access-window",
+                "Mon-Sun 00:00-24:00 Europe/Berlin",
+                "ssh-key",
+            ],
+        );
+        // A tz database without the zone
+        fs::create_dir(home.join("zoneinfo")).unwrap();
+        fs::copy(
+            "/usr/share/zoneinfo/Europe/Berlin",
+            home.join("zoneinfo/Elsewhere"),
+        )
+        .unwrap();
+        let conn = |args: &[&str]| {
+            command(&home)
+                .arg("conn")
+                .args(args)
+                .env("JUMP_SSH_BIN", home.join("ssh"))
+                .env("JUMP_SSH_KEYSCAN_BIN", home.join("no-keyscan"))
+                .env("TZDIR", home.join("zoneinfo"))
+                .output()
+                .unwrap()
+        };
+        let output = conn(&["berlin"]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("The access window of berlin cannot be read, so it is taken as closed"),
+            "{}",
+            stderr
+        );
+        assert!(!home.join("args").exists());
+
+        let output = conn(&["berlin", "--override-window", "INC-43 zone"]);
+        assert!(output.status.success(), "{:?}", output);
+        let log = jump(&home, &["--color", "never", "log"]);
+        assert!(log.contains("overridden: INC-43 zone"), "{}", log);
+    }
+
+    #[test]
+    fn ls_and_show_tell_whether_the_window_is_open() {
+        let home = store("access-ls");
+        let output = jump(&home, &["--color", "never", "ls", "--long"]);
+        let window = |name: &str| {
+            output
+                .lines()
+                .find(|line| line.split_whitespace().nth(1) == Some(name))
+                .and_then(|line| line.split_whitespace().last())
+                .unwrap()
+                .to_owned()
+        };
+        assert_eq!(window("web"), "closed");
+        assert_eq!(window("db"), "open");
+        assert_eq!(window("cache"), "-");
+
+        let show = jump(&home, &["show", "web"]);
+        assert!(
+            show.contains(&format!("window:   {} (closed, opens ", closed_window())),
+            "{}",
+            show
+        );
+    }
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use std::{net::TcpListener, path::PathBuf};
+
+use common::{home, jump, run};
+use jump::{
+    resolve,
+    server::{Address, ConnectMethods, SSHKey, Server},
+};
+
+fn store(test: &str, port: u32) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &[
+            "add",
+            "web",
+            "deploy",
+            "127.0.0.2",
+            &port.to_string(),
+            "ssh-key",
+        ],
+    );
+    home
+}
+
+#[test]
+fn addresses_are_listed_in_order_and_removed_by_label() {
+    let home = store("address-ls", 22);
+    jump(
+        &home,
+        &["address", "add", "web", "10.0.0.5", "--label", "vpn"],
+    );
+    jump(&home, &["address", "add", "web", "203.0.113.9"]);
+    assert_eq!(
+        jump(&home, &["address", "ls", "web"]),
+        "127.0.0.2\n10.0.0.5 (vpn)\n203.0.113.9\n"
+    );
+    assert!(jump(&home, &["--color", "never", "ls"]).contains("address: 127.0.0.2 (+2)"));
+    assert!(
+        jump(&home, &["--color", "never", "show", "web"]).contains("fallback: 10.0.0.5 (vpn)\n")
+    );
+
+    let duplicate = run(
+        &home,
+        &["address", "add", "web", "10.0.0.6", "--label", "vpn"],
+    );
+    assert!(!duplicate.status.success());
+    jump(&home, &["address", "rm", "web", "vpn"]);
+    assert_eq!(
+        jump(&home, &["address", "ls", "web"]),
+        "127.0.0.2\n203.0.113.9\n"
+    );
+    assert!(!run(&home, &["address", "rm", "web", "vpn"])
+        .status
+        .success());
+}
+
+#[test]
+fn export_and_import_carry_the_addresses() {
+    let home = store("address-bundle", 22);
+    jump(
+        &home,
+        &["address", "add", "web", "10.0.0.5", "--label", "vpn"],
+    );
+    let bundle = home.join("servers.json");
+    std::fs::write(&bundle, jump(&home, &["export"])).unwrap();
+    jump(&home, &["rm", "--yes", "web"]);
+    jump(&home, &["import", "--yes", bundle.to_str().unwrap()]);
+    assert_eq!(
+        jump(&home, &["address", "ls", "web"]),
+        "127.0.0.2\n10.0.0.5 (vpn)\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn conn_falls_back_to_the_first_address_that_answers() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port() as u32;
+    let home = store("address-conn", port);
+    jump(
+        &home,
+        &["address", "add", "web", "127.0.0.1", "--label", "local"],
+    );
+    jump(
+        &home,
+        &["address", "add", "web", "10.0.0.5", "--label", "vpn"],
+    );
+
+    let output = run(&home, &["conn", "--dry-run", "web"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("deploy@127.0.0.1"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("using 127.0.0.1 (local)"));
+
+    let forced = jump(
+        &home,
+        &["conn", "--dry-run", "--address-label", "vpn", "web"],
+    );
+    assert!(forced.contains("deploy@10.0.0.5"));
+    let unknown = run(
+        &home,
+        &["conn", "--dry-run", "--address-label", "public", "web"],
+    );
+    assert!(!unknown.status.success());
+}
+
+#[test]
+fn a_label_picks_its_address_without_probing() {
+    let mut server = Server::new(
+        "web".to_owned(),
+        "deploy".to_owned(),
+        "10.0.0.1".to_owned(),
+        22,
+        ConnectMethods::SSHKey(SSHKey {
+            path: "/keys/web".into(),
+        }),
+    );
+    server.addresses = vec![Address {
+        address: "10.0.0.5".to_owned(),
+        label: Some("vpn".to_owned()),
+    }];
+    assert_eq!(
+        resolve::addresses(&server)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        ["10.0.0.1", "10.0.0.5 (vpn)"]
+    );
+
+    let error = resolve::choose_address(&mut server.clone(), Some("public")).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Invalid address label: web has no address labelled `public`"
+    );
+    resolve::choose_address(&mut server, Some("vpn")).unwrap();
+    assert_eq!(server.server_address, "10.0.0.5");
+}
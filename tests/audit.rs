@@ -0,0 +1,116 @@
+mod common;
+
+use jump::audit::{format_time, parse_age};
+
+use common::{home, jump};
+
+#[test]
+fn ages() {
+    assert_eq!(parse_age("45s"), Ok(45));
+    assert_eq!(parse_age("30m"), Ok(1_800));
+    assert_eq!(parse_age("12h"), Ok(43_200));
+    assert_eq!(parse_age("7d"), Ok(604_800));
+    assert_eq!(parse_age("2w"), Ok(1_209_600));
+    assert!(parse_age("").is_err());
+    assert!(parse_age("d").is_err());
+    assert!(parse_age("7y").is_err());
+}
+
+#[test]
+fn times_are_utc() {
+    assert_eq!(format_time(0), "1970-01-01 00:00:00");
+    assert_eq!(format_time(951_825_600), "2000-02-29 12:00:00");
+    assert_eq!(format_time(1_709_285_400), "2024-03-01 09:30:00");
+}
+
+#[test]
+fn empty_log() {
+    let home = home("audit-empty");
+    jump(&home, &["initialize"]);
+    assert_eq!(jump(&home, &["log", "--output", "json"]), "[]\n");
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        fs,
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+    };
+
+    use rusqlite::Connection;
+
+    use super::*;
+    use crate::common::command;
+
+    /// A store with a key and a password server, and an ssh exiting with 7
+    fn store(test: &str) -> PathBuf {
+        let home = home(test);
+        jump(&home, &["initialize"]);
+        jump(
+            &home,
+            &["add", "web", "deploy", "10.0.0.1", "22", "ssh-key"],
+        );
+        jump(
+            &home,
+            &[
+                "add", "db", "admin", "10.0.0.2", "22", "password", "hunter2",
+            ],
+        );
+        let ssh = home.join("ssh");
+        fs::write(&ssh, "#!/bin/sh\nexit 7\n").unwrap();
+        fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+        home
+    }
+
+    fn connect(home: &Path, args: &[&str]) -> Option<i32> {
+        command(home)
+            .args(args)
+            .env("JUMP_SSH_BIN", home.join("ssh"))
+            .env("JUMP_SSHPASS_BIN", home.join("ssh"))
+            .env("USER", "alice")
+            .output()
+            .unwrap()
+            .status
+            .code()
+    }
+
+    #[test]
+    fn connections_are_logged() {
+        let home = store("audit-logged");
+        assert_eq!(connect(&home, &["conn", "web"]), Some(7));
+        assert_eq!(connect(&home, &["exec", "db", "--", "uptime"]), Some(7));
+        let log = jump(&home, &["log", "--output", "json"]);
+        assert!(!log.contains("hunter2"));
+        let entries = serde_json::from_str::<serde_json::Value>(&log).unwrap();
+        let entries = entries.as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["server_name"], "db");
+        assert_eq!(entries[0]["command"], "exec");
+        assert_eq!(entries[0]["method"], "password");
+        assert_eq!(entries[1]["server_name"], "web");
+        assert_eq!(entries[1]["local_user"], "alice");
+        assert_eq!(entries[1]["exit_code"], 7);
+        assert!(entries[1]["ended_at"].as_i64() >= entries[1]["started_at"].as_i64());
+
+        let web = jump(&home, &["log", "--server", "web", "--since", "1h"]);
+        assert_eq!(web.lines().count(), 2);
+        let row = web
+            .lines()
+            .nth(1)
+            .unwrap()
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        assert_eq!(row[2..7], ["web", "alice", "conn", "ssh-key", "7"]);
+    }
+
+    #[test]
+    fn a_locked_store_does_not_block_connections() {
+        let home = store("audit-locked");
+        let db = Connection::open(home.join(".jump").join("servers.db")).unwrap();
+        db.execute_batch("BEGIN IMMEDIATE").unwrap();
+        assert_eq!(connect(&home, &["conn", "web"]), Some(7));
+        db.execute_batch("ROLLBACK").unwrap();
+        assert_eq!(jump(&home, &["log", "--output", "json"]), "[]\n");
+    }
+}
@@ -0,0 +1,59 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt, path::Path, process::Output};
+
+use common::{command, home, jump};
+
+fn connect(home: &Path, args: &[&str]) -> Output {
+    command(home)
+        .args(args)
+        .env("JUMP_SSH_BIN", home.join("ssh"))
+        .env("JUMP_SSH_KEYSCAN_BIN", home.join("no-keyscan"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn banner_names_the_server_before_ssh_starts() {
+    let home = home("banner");
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &[
+            "add",
+            "web",
+            "deploy",
+            "10.0.0.1",
+            "--tag",
+            "prod",
+            "--note",
+            "frontend\nsecond line",
+            "ssh-key",
+        ],
+    );
+    let ssh = home.join("ssh");
+    fs::write(&ssh, "#!/bin/sh\necho ssh >&2\n").unwrap();
+    fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let stderr = |output: Output| String::from_utf8(output.stderr).unwrap();
+    assert!(!stderr(connect(&home, &["conn", "web"])).contains("deploy@10.0.0.1"));
+
+    fs::write(
+        home.join("config.toml"),
+        "banner = true\nset_title = true\n[tag_colors]\nprod = \"#ff0000\"\n",
+    )
+    .unwrap();
+    let output = connect(&home, &["--color", "never", "conn", "web"]);
+    // Not a terminal, so no title escape
+    assert_eq!(output.stdout, b"");
+    assert!(stderr(output)
+        .starts_with("web  deploy@10.0.0.1:22  [prod]  frontend\nconnecting to server...\nssh\n"));
+
+    let colored = stderr(connect(&home, &["--color", "always", "conn", "web"]));
+    assert!(colored.starts_with("\x1b[1;38;2;255;0;0mweb\x1b[0m  deploy@10.0.0.1:22"));
+
+    let quiet = stderr(connect(&home, &["--quiet", "conn", "web"]));
+    assert_eq!(quiet, "ssh\n");
+}
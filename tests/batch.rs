@@ -0,0 +1,157 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf, time::Duration};
+
+use common::{command, home, jump};
+use jump::{
+    config::Config,
+    exec,
+    report::Outcome,
+    schedule::Limits,
+    server::{ConnectMethods, SSHKey, Server},
+    ssh::{ConnOptions, ExecOptions},
+    store::Store,
+};
+
+/// A store with a key and a password server, and an ssh that is denied
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    jump(
+        &home,
+        &["add", "db", "admin", "10.0.0.2", "password", "hunter2"],
+    );
+    let ssh = home.join("ssh");
+    fs::write(
+        &ssh,
+        "#!/bin/sh\necho 'deploy@10.0.0.1: Permission denied (publickey).' >&2\nexit 255\n",
+    )
+    .unwrap();
+    fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+    home
+}
+
+#[test]
+fn denied_logins_fail_clearly_in_batch_mode() {
+    let home = store("batch-denied");
+    let output = command(&home)
+        .args(["exec", "web", "--", "uptime"])
+        .env("JUMP_SSH_BIN", home.join("ssh"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Authentication to web failed (batch mode)"));
+
+    let output = command(&home)
+        .args(["exec", "--no-batch", "web", "--", "uptime"])
+        .env("JUMP_SSH_BIN", home.join("ssh"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(255));
+}
+
+#[test]
+fn password_servers_need_sshpass_in_batch_mode() {
+    let home = store("batch-password");
+    // Nothing on PATH, so there is no sshpass to answer for ssh
+    let exec = |args: &[&str]| {
+        command(&home)
+            .args(args)
+            .env("JUMP_SSH_BIN", home.join("ssh"))
+            .env("PATH", &home)
+            .output()
+            .unwrap()
+    };
+    let output = exec(&["exec", "--dry-run", "db", "--", "uptime"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("only sends it through sshpass, so install it or pass --no-batch"));
+    assert!(
+        exec(&["exec", "--dry-run", "--no-batch", "db", "--", "uptime"])
+            .status
+            .success()
+    );
+    let dry_run = exec(&["exec", "--dry-run", "web", "--", "uptime"]);
+    assert!(
+        String::from_utf8_lossy(&dry_run.stdout).contains("-o BatchMode=yes -o ConnectTimeout=10")
+    );
+}
+
+#[test]
+fn parallel_runs_hand_over_each_server_whole() {
+    let home = home("batch-parallel");
+    fs::create_dir_all(&home).unwrap();
+    let ssh = home.join("ssh");
+    fs::write(
+        &ssh,
+        "#!/bin/sh\n\
+         case \"$*\" in *10.0.0.2*) echo 'Permission denied (publickey).' >&2; exit 255;; esac\n\
+         echo \"up on $*\"\n",
+    )
+    .unwrap();
+    fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+    let mut config = Config::default();
+    config.binaries.ssh = Some(ssh);
+    let servers = [("web", "10.0.0.1"), ("db", "10.0.0.2")].map(|(name, address)| {
+        Server::new(
+            name.to_owned(),
+            "deploy".to_owned(),
+            address.to_owned(),
+            22,
+            ConnectMethods::SSHKey(SSHKey {
+                path: "/keys/web".into(),
+            }),
+        )
+    });
+    let store = Store::open_in_memory().unwrap();
+    store.initialize().unwrap();
+    let limits = Limits {
+        parallel: 1,
+        bastion_parallel: 1,
+        delay: Duration::ZERO,
+    };
+    let mut finished = vec![];
+    let hosts = exec::parallel(
+        &store,
+        &config,
+        &servers,
+        &ConnOptions::default(),
+        ExecOptions {
+            tty: false,
+            batch: Some(5),
+        },
+        limits,
+        3,
+        &["uptime".to_owned()],
+        vec![None, None],
+        |done| {
+            let stdout = done.output.map(|output| output.stdout.clone());
+            finished.push((done.server.server_name.clone(), done.done, stdout));
+        },
+    )
+    .unwrap();
+    assert_eq!(finished.len(), 2);
+    assert_eq!(finished[0].0, "web");
+    assert_eq!(finished[0].1, 1);
+    assert!(String::from_utf8_lossy(finished[0].2.as_ref().unwrap()).contains("up on"));
+    assert_eq!(finished[1].0, "db");
+    assert_eq!(
+        hosts
+            .iter()
+            .map(|host| (host.server_name.as_str(), host.outcome, host.exit_code))
+            .collect::<Vec<_>>(),
+        [
+            ("web", Outcome::Ok, Some(0)),
+            ("db", Outcome::Unreachable, Some(255)),
+        ]
+    );
+    assert!(hosts[1]
+        .error
+        .as_deref()
+        .unwrap()
+        .contains("Authentication to db failed (batch mode)"));
+}
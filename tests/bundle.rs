@@ -0,0 +1,221 @@
+mod common;
+
+use std::{fs, io::Write, path::PathBuf, process::Stdio};
+
+use jump::{
+    bundle::{self, Bundle, Conflict, Outcome},
+    error::JumpError,
+    server::{ConnectMethods, Password, SSHKey, Server},
+    store::Store,
+};
+
+use common::{command, home, jump, run};
+
+fn server(name: &str, address: &str, method: ConnectMethods) -> Server {
+    Server::new(
+        name.to_owned(),
+        "deploy".to_owned(),
+        address.to_owned(),
+        22,
+        method,
+    )
+}
+
+fn password(password: &str) -> ConnectMethods {
+    ConnectMethods::Password(Password {
+        password: password.to_owned(),
+    })
+}
+
+fn key() -> ConnectMethods {
+    ConnectMethods::SSHKey(SSHKey {
+        path: PathBuf::from("/keys/web"),
+    })
+}
+
+fn store() -> Store {
+    let store = Store::open_in_memory().unwrap();
+    store.initialize().unwrap();
+    store
+}
+
+fn stored_password(store: &Store, name: &str) -> String {
+    match store.get(name).unwrap().method {
+        ConnectMethods::Password(Password { password }) => password,
+        method => panic!("{} has {}", name, method),
+    }
+}
+
+#[test]
+fn encryption_round_trips() {
+    let sealed = bundle::encrypt(b"{}", "correct horse").unwrap();
+    assert!(sealed.starts_with(b"jump-bundle 1\n"));
+    assert!(bundle::is_encrypted(&sealed));
+    assert_eq!(bundle::decrypt(&sealed, "correct horse").unwrap(), b"{}");
+    assert!(matches!(
+        bundle::decrypt(&sealed, "wrong"),
+        Err(JumpError::WrongPassphrase)
+    ));
+}
+
+#[test]
+fn tampering_is_detected() {
+    let mut sealed = bundle::encrypt(b"{}", "pass").unwrap();
+    // The salt is part of the authenticated header
+    sealed[15] ^= 1;
+    assert!(bundle::decrypt(&sealed, "pass").is_err());
+    let newer = [b"jump-bundle 2\n".as_slice(), &sealed[14..]].concat();
+    assert!(matches!(
+        bundle::decrypt(&newer, "pass"),
+        Err(JumpError::InvalidInput { .. })
+    ));
+}
+
+#[test]
+fn secrets_stay_out_unless_asked_for() {
+    let servers = || vec![server("db", "10.0.0.2", password("hunter2"))];
+    let json = Bundle::new(servers(), false).to_json().unwrap();
+    assert!(!String::from_utf8(json).unwrap().contains("hunter2"));
+    let json = Bundle::new(servers(), true).to_json().unwrap();
+    assert!(String::from_utf8(json).unwrap().contains("hunter2"));
+}
+
+#[test]
+fn newer_versions_are_refused() {
+    let json = br#"{"version": 99, "secrets": false, "servers": []}"#;
+    assert!(matches!(
+        Bundle::read(json, None),
+        Err(JumpError::InvalidInput { .. })
+    ));
+    let sealed = bundle::encrypt(b"{}", "pass").unwrap();
+    assert!(Bundle::read(&sealed, None).is_err());
+}
+
+#[test]
+fn fields_added_later_are_optional() {
+    let mut value =
+        serde_json::to_value(Bundle::new(vec![server("web", "10.0.0.1", key())], false)).unwrap();
+    value["servers"][0]
+        .as_object_mut()
+        .unwrap()
+        .remove("platform");
+    let bundle = Bundle::read(value.to_string().as_bytes(), None).unwrap();
+    assert_eq!(bundle.servers[0].platform, None);
+}
+
+#[test]
+fn conflicts_are_skipped_replaced_or_renamed() {
+    let bundle = || {
+        Bundle::new(
+            vec![
+                server("web", "10.0.0.9", key()),
+                server("db", "10.0.0.2", password("new")),
+            ],
+            false,
+        )
+    };
+    let store = store();
+    store.add(&server("web", "10.0.0.1", key())).unwrap();
+    store
+        .add(&server("db", "10.0.0.2", password("old")))
+        .unwrap();
+
+    let outcomes = bundle::import(&store, bundle(), Conflict::Skip).unwrap();
+    assert_eq!(outcomes[0], ("web".to_owned(), Outcome::Skipped));
+    assert_eq!(store.get("web").unwrap().server_address, "10.0.0.1");
+
+    bundle::import(&store, bundle(), Conflict::Overwrite).unwrap();
+    assert_eq!(store.get("web").unwrap().server_address, "10.0.0.9");
+    // Left out of the bundle, the password is kept
+    assert_eq!(stored_password(&store, "db"), "old");
+
+    let outcomes = bundle::import(&store, bundle(), Conflict::Rename).unwrap();
+    assert_eq!(
+        outcomes[1],
+        ("db".to_owned(), Outcome::Renamed("db-2".to_owned()))
+    );
+    assert_eq!(store.list().unwrap().len(), 4);
+}
+
+#[test]
+fn failed_imports_change_nothing() {
+    let store = store();
+    let mut bundle = Bundle::new(vec![server("web", "10.0.0.1", key())], true);
+    let mut invalid = server("db", "10.0.0.2", key());
+    invalid.options = vec![("Not A Key".to_owned(), "x".to_owned())];
+    bundle.servers.push(invalid);
+    assert!(bundle::import(&store, bundle, Conflict::Skip).is_err());
+    assert!(store.list().unwrap().is_empty());
+}
+
+#[test]
+fn export_and_import_between_stores() {
+    let source = home("bundle-source");
+    jump(&source, &["initialize"]);
+    jump(
+        &source,
+        &["add", "web", "deploy", "10.0.0.1", "22", "ssh-key"],
+    );
+    jump(
+        &source,
+        &[
+            "add", "db", "admin", "10.0.0.2", "22", "password", "hunter2",
+        ],
+    );
+    let file = source.join("team.jmp");
+    let file_arg = file.to_str().unwrap();
+    let output = command(&source)
+        .args(["export", "--encrypted", file_arg, "--include-secrets"])
+        .env("JUMP_PASSPHRASE", "shared")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!String::from_utf8_lossy(&fs::read(&file).unwrap()).contains("hunter2"));
+
+    let target = home("bundle-target");
+    jump(&target, &["initialize"]);
+    let wrong = command(&target)
+        .args(["import", file_arg, "--yes"])
+        .env("JUMP_PASSPHRASE", "guess")
+        .output()
+        .unwrap();
+    assert_eq!(wrong.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&wrong.stderr).contains("Wrong passphrase"));
+    let empty = command(&target)
+        .args(["import", file_arg, "--yes"])
+        .env("JUMP_PASSPHRASE", "")
+        .output()
+        .unwrap();
+    assert_eq!(empty.status.code(), Some(1));
+    assert!(
+        String::from_utf8_lossy(&empty.stderr).contains("Invalid passphrase: it must not be empty")
+    );
+
+    let mut child = command(&target)
+        .args(["--interactive", "import", file_arg])
+        .env("JUMP_PASSPHRASE", "shared")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"y\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let preview = String::from_utf8(output.stdout).unwrap();
+    assert!(preview.starts_with("new       web (deploy@10.0.0.1)\nnew       db (admin@10.0.0.2)\n"));
+    let listed = jump(&target, &["ls", "--output", "json", "--include-secrets"]);
+    assert!(listed.contains("hunter2"));
+
+    let plain = jump(&source, &["export", "web"]);
+    let plain_file = source.join("web.json");
+    fs::write(&plain_file, plain).unwrap();
+    let output = run(
+        &target,
+        &["import", plain_file.to_str().unwrap(), "--dry-run"],
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "skip      web (deploy@10.0.0.1)\n"
+    );
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use std::fs;
+
+use jump::cache;
+
+use common::{home, jump, run};
+
+#[test]
+fn cache_files_map_back_to_profiles() {
+    assert_eq!(cache::profile("servers.txt"), Some("default"));
+    assert_eq!(cache::profile("work.txt"), Some("work"));
+    assert_eq!(cache::profile(".servers.txt.123"), None);
+    assert_eq!(cache::profile(".txt"), None);
+    assert_eq!(cache::profile("notes.md"), None);
+}
+
+#[test]
+fn the_cache_follows_every_change_to_the_store() {
+    let home = home("cache");
+    let servers = home.join(".jump/cache/servers.txt");
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    jump(&home, &["add", "db", "postgres", "10.0.0.2", "ssh-key"]);
+    assert_eq!(
+        fs::read_to_string(&servers).unwrap(),
+        "web\tdeploy@10.0.0.1\ndb\tpostgres@10.0.0.2\n"
+    );
+    jump(
+        &home,
+        &["set", "address", "10.0.0.9", "--filter", "name:db", "--yes"],
+    );
+    jump(&home, &["rm", "web", "--yes"]);
+    assert_eq!(
+        fs::read_to_string(&servers).unwrap(),
+        "db\tpostgres@10.0.0.9\n"
+    );
+
+    // Written again on demand, and on the next command when missing
+    fs::remove_file(&servers).unwrap();
+    jump(&home, &["ls"]);
+    assert!(servers.exists());
+    fs::write(&servers, "stale\n").unwrap();
+    let output = run(&home, &["refresh-cache"]);
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(&servers).unwrap(),
+        "db\tpostgres@10.0.0.9\n"
+    );
+}
+
+#[test]
+fn refresh_cache_drops_the_lists_of_deleted_profiles() {
+    let home = home("cache-profiles");
+    jump(&home, &["--profile", "work", "initialize"]);
+    jump(
+        &home,
+        &[
+            "--profile",
+            "work",
+            "add",
+            "ci",
+            "ci",
+            "10.1.0.1",
+            "ssh-key",
+        ],
+    );
+    let work = home.join(".jump/cache/work.txt");
+    assert_eq!(fs::read_to_string(&work).unwrap(), "ci\tci@10.1.0.1\n");
+
+    fs::remove_file(home.join(".jump/work.db")).unwrap();
+    jump(&home, &["initialize"]);
+    let output = run(&home, &["completion-data"]);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("removed the cache of work, whose store is gone"));
+    assert!(!work.exists());
+    assert!(home.join(".jump/cache/servers.txt").exists());
+}
@@ -0,0 +1,74 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf};
+
+use jump::{
+    capture::{self, MAX_LOG_SIZE},
+    ssh::Invocation,
+};
+
+use common::{command, home, jump};
+
+fn sh(script: &str) -> Invocation {
+    Invocation {
+        program: PathBuf::from("/bin/sh"),
+        args: vec!["-c".to_owned(), script.to_owned()],
+        secret: None,
+        env: vec![],
+    }
+}
+
+#[test]
+fn stderr_is_appended_after_a_header() {
+    let home = home("capture-append");
+    let log = home.join("logs").join("web.log");
+    for code in [0, 3] {
+        let status = capture::status(&sh(&format!("echo oops >&2; exit {}", code)), &log).unwrap();
+        assert_eq!(status.code(), Some(code));
+    }
+    let content = fs::read_to_string(&log).unwrap();
+    let lines = content.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("== "));
+    assert!(lines[0].ends_with(" UTC /bin/sh -c 'echo oops >&2; exit 0'"));
+    assert_eq!(lines[1], "oops");
+    assert_eq!(lines[3], "oops");
+}
+
+#[test]
+fn full_logs_are_rotated() {
+    let home = home("capture-rotate");
+    fs::create_dir_all(&home).unwrap();
+    let log = home.join("web.log");
+    fs::write(&log, vec![b'x'; MAX_LOG_SIZE as usize]).unwrap();
+    capture::status(&sh("echo fresh >&2"), &log).unwrap();
+    let rotated = fs::metadata(home.join("web.log.1")).unwrap();
+    assert_eq!(rotated.len(), MAX_LOG_SIZE);
+    assert!(fs::read_to_string(&log).unwrap().ends_with("\nfresh\n"));
+}
+
+#[test]
+fn log_connections_writes_under_the_jump_dir() {
+    let home = home("capture-config");
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &["add", "web", "deploy", "10.0.0.1", "22", "ssh-key"],
+    );
+    jump(&home, &["config", "set", "log_connections", "true"]);
+    let ssh = home.join("ssh");
+    fs::write(&ssh, "#!/bin/sh\necho refused >&2\necho out\nexit 255\n").unwrap();
+    fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+    let output = command(&home)
+        .args(["exec", "web", "--", "true"])
+        .env("JUMP_SSH_BIN", &ssh)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(255));
+    assert_eq!(output.stdout, b"out\n");
+    assert_eq!(output.stderr, b"refused\n");
+    let log = fs::read_to_string(home.join(".jump").join("logs").join("web.log")).unwrap();
+    assert!(log.ends_with("deploy@10.0.0.1 true\nrefused\n"), "{}", log);
+}
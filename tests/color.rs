@@ -0,0 +1,62 @@
+mod common;
+
+use std::{net::TcpListener, path::PathBuf};
+
+use common::{command, home, jump, run};
+
+/// A store with one server, listening on a local port so `check` sees it up
+fn store(test: &str) -> (PathBuf, TcpListener) {
+    let home = home(test);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &["add", "web", "deploy", "127.0.0.1", &port, "ssh-key"],
+    );
+    (home, listener)
+}
+
+#[test]
+fn always_colors_listings() {
+    let (home, _listener) = store("color-always");
+    assert!(jump(&home, &["ls", "--color", "always"]).contains('\x1b'));
+    assert!(jump(&home, &["show", "web", "--color", "always"]).contains('\x1b'));
+    assert!(jump(&home, &["check", "--color", "always"]).contains('\x1b'));
+}
+
+#[test]
+fn plain_output_never_has_escape_codes() {
+    let (home, _listener) = store("color-plain");
+    let ls = jump(&home, &["ls", "--plain", "--color", "always"]);
+    assert!(ls.starts_with("web\tdeploy\t127.0.0.1\t"), "{}", ls);
+    assert!(!ls.contains('\x1b'));
+    let check = jump(&home, &["check", "--plain", "--color", "always"]);
+    assert!(check.ends_with("\tup\n"));
+    assert!(!check.contains('\x1b'));
+}
+
+#[test]
+fn no_color_and_pipes_disable_colors() {
+    let (home, _listener) = store("color-auto");
+    // stdout is a pipe here, so auto never colors
+    assert!(!jump(&home, &["ls"]).contains('\x1b'));
+    assert!(!jump(&home, &["ls", "--color", "never"]).contains('\x1b'));
+    let output = command(&home)
+        .args(["ls", "--color", "auto"])
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert!(!String::from_utf8(output.stdout).unwrap().contains('\x1b'));
+}
+
+#[test]
+fn down_servers_fail_the_check() {
+    let (home, listener) = store("color-down");
+    drop(listener);
+    let output = run(&home, &["check", "--plain", "--timeout", "1"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .ends_with("\tdown\n"));
+}
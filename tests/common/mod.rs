@@ -0,0 +1,38 @@
+//! Helpers for tests that drive the `jump` binary against a scratch home
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// An empty home directory unique to `test`
+pub fn home(test: &str) -> PathBuf {
+    let home = std::env::temp_dir().join(format!("jump-{}-{}", test, std::process::id()));
+    fs::remove_dir_all(&home).ok();
+    home
+}
+
+/// jump with `home` as its home directory and config location
+pub fn command(home: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_jump"));
+    command
+        .env("HOME", home)
+        .env("JUMP_CONFIG", home.join("config.toml"))
+        .env_remove("NO_COLOR");
+    for var in ["https_proxy", "HTTPS_PROXY", "all_proxy", "ALL_PROXY"] {
+        command.env_remove(var);
+    }
+    command
+}
+
+pub fn run(home: &Path, args: &[&str]) -> Output {
+    command(home).args(args).output().unwrap()
+}
+
+/// Run jump, which must succeed, and return its stdout
+pub fn jump(home: &Path, args: &[&str]) -> String {
+    let output = run(home, args);
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use common::{home, jump};
+
+fn script(shell: &str) -> String {
+    jump(&home("completions"), &["completions", shell])
+}
+
+#[test]
+fn every_shell_covers_the_subcommands() {
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        let script = script(shell);
+        for name in [
+            "add", "conn", "exec", "cp", "option", "env", "ssh-key", "password",
+        ] {
+            assert!(
+                script.contains(name),
+                "{} completions are missing `{}`",
+                shell,
+                name
+            );
+        }
+    }
+}
+
+#[test]
+fn flags_are_included() {
+    let script = script("bash");
+    for flag in ["--dry-run", "--forward-agent", "--profile", "--x11"] {
+        assert!(
+            script.contains(flag),
+            "bash completions are missing `{}`",
+            flag
+        );
+    }
+}
+
+#[test]
+fn scripts_complete_server_names() {
+    for shell in ["bash", "zsh", "fish"] {
+        assert!(
+            script(shell).contains("__complete-servers"),
+            "{} completions do not complete server names",
+            shell
+        );
+    }
+}
+
+#[test]
+fn server_names_are_listed_sorted() {
+    let home = home("complete-servers");
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    jump(&home, &["add", "db", "deploy", "10.0.0.2", "ssh-key"]);
+    assert_eq!(jump(&home, &["__complete-servers"]), "db\nweb\n");
+    assert_eq!(
+        jump(&home, &["--profile", "other", "__complete-servers"]),
+        ""
+    );
+}
+
+#[test]
+fn no_store_means_no_output() {
+    let home = home("complete-fresh");
+    assert_eq!(jump(&home, &["__complete-servers"]), "");
+    assert!(!home.exists());
+}
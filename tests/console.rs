@@ -0,0 +1,98 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf};
+
+use common::{command, home, jump, run};
+
+/// A store with the console server `cons` and the switch `rack3-sw1`
+/// behind it, and a fake ssh that writes its arguments to `args`
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["init"]);
+    jump(&home, &["add", "cons", "admin", "10.0.0.50", "ssh-key"]);
+    jump(
+        &home,
+        &[
+            "add",
+            "rack3-sw1",
+            "admin",
+            "10.1.0.1",
+            "--console-server",
+            "cons",
+            "--console-target",
+            "connect rack3-sw1",
+            "ssh-key",
+        ],
+    );
+    fs::write(home.join("config.toml"), "track_keys = false\n").unwrap();
+    fs::write(
+        home.join("ssh"),
+        format!("#!/bin/sh\necho \"$@\" > {}\n", home.join("args").display()),
+    )
+    .unwrap();
+    fs::set_permissions(home.join("ssh"), fs::Permissions::from_mode(0o755)).unwrap();
+    home
+}
+
+#[test]
+fn console_attaches_through_the_console_server() {
+    let home = store("console-attach");
+    assert!(jump(&home, &["show", "rack3-sw1"]).contains("console:  `connect rack3-sw1` on cons"));
+
+    let output = command(&home)
+        .args(["console", "rack3-sw1"])
+        .env("JUMP_SSH_BIN", home.join("ssh"))
+        .env("JUMP_SSH_KEYSCAN_BIN", home.join("no-keyscan"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let args = fs::read_to_string(home.join("args")).unwrap();
+    assert!(args.contains(" -t "), "{}", args);
+    assert!(
+        args.ends_with("-- admin@10.0.0.50 connect rack3-sw1\n"),
+        "{}",
+        args
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(
+            "attaching to the console of rack3-sw1 through cons; type ~. at the start of a \
+             line to disconnect"
+        ),
+        "{}",
+        stderr
+    );
+
+    // Logged as a console session of the switch, not a shell on the server
+    let log = jump(&home, &["log", "--output", "json"]);
+    let log = serde_json::from_str::<serde_json::Value>(&log).unwrap();
+    assert_eq!(log[0]["server_name"], "rack3-sw1");
+    assert_eq!(log[0]["command"], "console");
+}
+
+#[test]
+fn a_server_without_a_console_is_refused() {
+    let home = store("console-none");
+    let output = run(&home, &["console", "cons"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("cons has no console server"));
+
+    // The two go together
+    let output = run(
+        &home,
+        &[
+            "add",
+            "sw2",
+            "admin",
+            "10.1.0.2",
+            "--console-target",
+            "connect sw2",
+            "ssh-key",
+        ],
+    );
+    assert!(!output.status.success());
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use std::path::PathBuf;
+
+use jump::{
+    server::{ConnectMethods, Custom, Server},
+    ssh,
+};
+
+use common::{home, jump, run};
+
+fn server(command: &str) -> Server {
+    Server::new(
+        "bastion".to_owned(),
+        "ec2-user".to_owned(),
+        "i-0abc 123".to_owned(),
+        22,
+        ConnectMethods::Command(Custom {
+            command: command.to_owned(),
+        }),
+    )
+}
+
+#[test]
+fn placeholders_are_filled_in_quoted() {
+    let custom = Custom {
+        command: "aws ssm start-session --target {address} --port {port} # {user}".to_owned(),
+    };
+    assert_eq!(
+        custom.expand(&server("")).unwrap(),
+        "aws ssm start-session --target 'i-0abc 123' --port 22 # ec2-user"
+    );
+    let unknown = Custom {
+        command: "gcloud compute ssh {host}".to_owned(),
+    };
+    assert_eq!(
+        unknown.expand(&server("")).unwrap_err(),
+        "unknown placeholder `{host}`, expected one of {user}, {address}, {port}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn conn_runs_the_command_through_the_shell() {
+    let server = server("gcloud compute ssh {user}@{address}");
+    let invocation = ssh::connect(
+        &jump::config::Config::default(),
+        &server,
+        &Default::default(),
+        &Default::default(),
+        &[],
+    )
+    .unwrap();
+    assert_eq!(invocation.program, PathBuf::from("sh"));
+    assert_eq!(
+        invocation.args,
+        ["-c", "gcloud compute ssh ec2-user@'i-0abc 123'"]
+    );
+}
+
+#[test]
+fn custom_servers_are_stored_and_listed() {
+    let home = home("custom-store");
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &[
+            "add",
+            "bastion",
+            "ec2-user",
+            "i-0abc123",
+            "command",
+            "aws ssm start-session --target {address}",
+        ],
+    );
+    assert_eq!(
+        jump(&home, &["ls", "--plain", "method:custom"]),
+        "bastion\tec2-user\ti-0abc123\t22\tcustom\n"
+    );
+    assert!(jump(&home, &["--color", "never", "show", "bastion"])
+        .contains("method:   custom aws ssm start-session --target {address}\n"));
+    assert!(jump(&home, &["conn", "--dry-run", "bastion"])
+        .contains("aws ssm start-session --target i-0abc123"));
+    assert!(
+        !run(&home, &["exec", "--dry-run", "bastion", "--", "uptime"])
+            .status
+            .success()
+    );
+
+    let unknown = run(
+        &home,
+        &[
+            "add",
+            "vm",
+            "me",
+            "vm-1",
+            "command",
+            "gcloud compute ssh {host}",
+        ],
+    );
+    assert!(!unknown.status.success());
+    assert!(String::from_utf8_lossy(&unknown.stderr).contains("{user}, {address}, {port}"));
+}
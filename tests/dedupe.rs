@@ -0,0 +1,50 @@
+mod common;
+
+use std::fs;
+
+use common::{home, jump, run};
+
+#[test]
+fn adding_a_second_server_at_an_address_warns() {
+    let home = home("dedupe-warn");
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web1", "deploy", "10.0.0.1", "ssh-key"]);
+    let output = run(&home, &["add", "web-1", "root", "10.0.0.1", "ssh-key"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("warning: 10.0.0.1:22 is already the address of web1"));
+    let other_port = run(
+        &home,
+        &["add", "web2", "root", "10.0.0.1", "2222", "ssh-key"],
+    );
+    assert!(other_port.stderr.is_empty());
+
+    fs::write(home.join("config.toml"), "strict_duplicates = true\n").unwrap();
+    let refused = run(&home, &["add", "web3", "root", "10.0.0.1", "ssh-key"]);
+    assert_eq!(refused.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&refused.stderr).contains(
+        "10.0.0.1:22 is already the address of web1, web-1; strict_duplicates forbids adding web3"
+    ));
+    assert_eq!(jump(&home, &["ls", "--plain"]).lines().count(), 3);
+}
+
+#[test]
+fn dedupe_lists_each_group() {
+    let home = home("dedupe-report");
+    jump(&home, &["initialize"]);
+    assert_eq!(jump(&home, &["dedupe"]), "");
+    for (name, address) in [
+        ("web1", "web.example.com"),
+        ("db", "10.0.0.2"),
+        ("web-1", "WEB.example.com"),
+        ("db-old", "10.0.0.2"),
+        ("cache", "10.0.0.3"),
+        ("www", "web.example.com"),
+    ] {
+        jump(&home, &["add", name, "deploy", address, "ssh-key"]);
+    }
+    assert_eq!(
+        jump(&home, &["dedupe"]),
+        "web.example.com:22: web1, web-1, www\n10.0.0.2:22: db, db-old\n"
+    );
+}
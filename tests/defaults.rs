@@ -0,0 +1,120 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use jump::{config::Config, defaults};
+
+use common::{home, jump, run};
+
+const CONFIG: &str = r#"
+default_identity = "~/.ssh/id_ed25519"
+
+[defaults."*.corp.example.com"]
+user = "deploy"
+identity = "~/.ssh/work_ed25519"
+port = 2222
+
+[defaults."db*.corp.example.com"]
+port = 5022
+
+[defaults."tag:home"]
+user = "pi"
+"#;
+
+#[test]
+fn the_longest_matching_pattern_wins_each_value() {
+    let config: Config = toml::from_str(CONFIG).unwrap();
+    let db = defaults::resolve(&config, "DB1.corp.example.com", &[]);
+    assert_eq!(db.matched, ["db*.corp.example.com", "*.corp.example.com"]);
+    assert_eq!(db.port.value, 5022);
+    assert_eq!(db.user.unwrap().rule.as_deref(), Some("*.corp.example.com"));
+
+    let home = defaults::resolve(&config, "10.0.0.5", &["home".to_owned()]);
+    assert_eq!(home.user.unwrap().value, "pi");
+    assert_eq!(home.port.value, 22);
+    assert_eq!(home.port.rule, None);
+    assert_eq!(home.identity.value, "~/.ssh/id_ed25519");
+}
+
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    fs::create_dir_all(&home).unwrap();
+    fs::write(home.join("config.toml"), CONFIG).unwrap();
+    jump(&home, &["initialize"]);
+    home
+}
+
+#[test]
+fn add_takes_what_was_not_given_from_the_rules() {
+    let home = store("defaults-add");
+    // Without a method, as `--uri` allows, the key is the rule's too
+    let output = run(&home, &["add", "web", "--uri", "web1.corp.example.com"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("user deploy, from [defaults.\"*.corp.example.com\"]\n"));
+    assert!(stderr.contains("port 2222, from"));
+    assert!(stderr.contains("identity ~/.ssh/work_ed25519, from"));
+
+    // Explicit arguments win
+    jump(
+        &home,
+        &[
+            "add",
+            "app",
+            "root",
+            "app.corp.example.com",
+            "22",
+            "ssh-key",
+            "~/.ssh/other",
+        ],
+    );
+    jump(
+        &home,
+        &["add", "pi", "--uri", "10.0.0.5", "--tag", "home", "ssh-key"],
+    );
+
+    let plain = jump(&home, &["ls", "--plain"]);
+    assert!(plain.contains("web\tdeploy\tweb1.corp.example.com\t2222\tssh-key\n"));
+    assert!(plain.contains("app\troot\tapp.corp.example.com\t22\tssh-key\n"));
+    assert!(plain.contains("pi\tpi\t10.0.0.5\t22\tssh-key\n"));
+    let app = jump(&home, &["--output", "json", "show", "app"]);
+    assert!(app.contains("~/.ssh/other"), "{}", app);
+
+    let missing = run(&home, &["add", "nas", "--uri", "192.168.1.2", "ssh-key"]);
+    assert!(!missing.status.success());
+    assert!(String::from_utf8_lossy(&missing.stderr)
+        .contains("none given for 192.168.1.2, and no [defaults] rule gives one"));
+
+    // A positional is never taken for another, so a missing one is reported
+    let typo = run(&home, &["add", "typo", "deploy", "ssh-key"]);
+    assert_eq!(typo.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&typo.stderr).contains("<SERVER_ADDRESS>"));
+}
+
+#[test]
+fn test_defaults_names_the_rule_behind_each_value() {
+    let home = store("defaults-test");
+    assert_eq!(
+        jump(&home, &["config", "test-defaults", "db2.corp.example.com"]),
+        "rules:    db*.corp.example.com, *.corp.example.com\n\
+         user:     deploy  [defaults.\"*.corp.example.com\"]\n\
+         port:     5022  [defaults.\"db*.corp.example.com\"]\n\
+         identity: ~/.ssh/work_ed25519  [defaults.\"*.corp.example.com\"]\n"
+    );
+    assert_eq!(
+        jump(&home, &["config", "test-defaults", "10.0.0.5"]),
+        "no rule matches 10.0.0.5\n\
+         user:     none, it has to be given\n\
+         port:     22  default_port\n\
+         identity: ~/.ssh/id_ed25519  default_identity\n"
+    );
+    assert!(jump(
+        &home,
+        &["config", "test-defaults", "10.0.0.5", "--tag", "home"]
+    )
+    .contains("user:     pi  [defaults.\"tag:home\"]\n"));
+
+    fs::write(home.join("config.toml"), "[defaults.\"*.lan\"]\nport = 0\n").unwrap();
+    let invalid = run(&home, &["config", "test-defaults", "nas.lan"]);
+    assert!(String::from_utf8_lossy(&invalid.stderr).contains("has port 0, not 1 to 65535"));
+}
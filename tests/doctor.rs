@@ -0,0 +1,122 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+use jump::doctor::current_method;
+use rusqlite::Connection;
+
+use common::{command, home, jump, run};
+
+fn mode(path: &Path) -> u32 {
+    fs::metadata(path).unwrap().permissions().mode() & 0o777
+}
+
+#[test]
+fn old_method_rows_map_to_the_current_format() {
+    let current = |raw: &str| current_method(raw);
+    assert_eq!(current("pass:hunter2").as_deref(), Some("pass:hunter2"));
+    assert_eq!(current("password:a:b").as_deref(), Some("pass:a:b"));
+    assert_eq!(current("key:~/.ssh/id").as_deref(), Some("ssh:~/.ssh/id"));
+    assert_eq!(current("~/.ssh/id").as_deref(), Some("ssh:~/.ssh/id"));
+    assert_eq!(current("/keys/a:b").as_deref(), Some("ssh:/keys/a:b"));
+    assert_eq!(
+        current("ssh:C:\\keys\\id").as_deref(),
+        Some("ssh:C:\\keys\\id")
+    );
+    assert_eq!(current("hunter2"), None);
+    assert_eq!(current("telnet:x"), None);
+}
+
+#[test]
+fn doctor_lists_without_changing_anything() {
+    let home = home("doctor-dry-run");
+    let output = jump(&home, &["doctor"]);
+    assert!(output.contains(".jump does not exist: create"));
+    assert!(output.contains("there is no store at"));
+    assert!(output.contains("run `jump init`"));
+    assert!(output.ends_with("run `jump doctor --fix` to apply the fixes\n"));
+    assert!(!home.join(".jump").exists());
+
+    let refused = command(&home)
+        .args(["doctor", "--fix"])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .unwrap();
+    assert!(!refused.status.success());
+    assert!(!home.join(".jump").exists());
+}
+
+#[test]
+fn fix_creates_directories_and_closes_files() {
+    let home = home("doctor-fix");
+    jump(&home, &["initialize"]);
+    let key = home.join("id_web");
+    fs::write(&key, "key").unwrap();
+    fs::set_permissions(&key, fs::Permissions::from_mode(0o644)).unwrap();
+    let key = key.to_str().unwrap();
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key", key]);
+    jump(
+        &home,
+        &["add", "db", "deploy", "10.0.0.2", "ssh-key", "/nonexistent"],
+    );
+    let db = home.join(".jump/servers.db");
+    fs::set_permissions(&db, fs::Permissions::from_mode(0o644)).unwrap();
+
+    let output = jump(&home, &["doctor", "--fix", "--yes"]);
+    assert!(output.contains("the key /nonexistent of db does not exist"));
+    assert!(output.contains("run `jump set key <path> --filter name:db`"));
+    assert!(output.contains(&format!("done: chmod 600 {}", key)));
+    assert_eq!(mode(&db), 0o600);
+    assert_eq!(mode(Path::new(key)), 0o600);
+    assert_eq!(mode(&home.join(".jump/recordings")), 0o700);
+
+    let again = jump(&home, &["doctor"]);
+    assert!(!again.contains("chmod"));
+    assert!(!again.contains("--fix"));
+}
+
+#[test]
+fn fix_migrates_and_rewrites_old_rows() {
+    let home = home("doctor-migrate");
+    fs::create_dir_all(home.join(".jump")).unwrap();
+    let db = home.join(".jump/servers.db");
+    let conn = Connection::open(&db).unwrap();
+    conn.execute_batch(
+        "create table jump_servers (
+            id integer primary key,
+            server_name text not null unique,
+            username text not null,
+            server_address text not null,
+            port integer not null,
+            method text not null
+        );
+        insert into jump_servers values (1, 'web', 'deploy', '10.0.0.1', 22, 'password:hunter2');
+        insert into jump_servers values (2, 'db', 'deploy', '10.0.0.2', 22, 'hunter2');",
+    )
+    .unwrap();
+    drop(conn);
+
+    let output = jump(&home, &["doctor"]);
+    assert!(output.contains("schema change"));
+    assert!(
+        output.contains("web has its method in an old format: store the method of web as `pass:…`")
+    );
+    assert!(output.contains("the method of db is not one jump knows"));
+    assert!(!output.contains("hunter2"));
+
+    jump(&home, &["doctor", "--fix", "--yes"]);
+    let conn = Connection::open(&db).unwrap();
+    let method: String = conn
+        .query_row(
+            "SELECT method FROM jump_servers WHERE server_name = 'web'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(method, "pass:hunter2");
+    assert!(jump(&home, &["ls", "--plain"]).starts_with("web\tdeploy\t10.0.0.1\t22\tpassword\n"));
+    assert!(!jump(&home, &["doctor"]).contains("schema change"));
+    assert_eq!(run(&home, &["doctor", "--yes"]).status.code(), Some(2));
+}
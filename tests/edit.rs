@@ -0,0 +1,135 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use common::{command, home, jump};
+
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web1", "admin", "10.0.0.1", "ssh-key"]);
+    jump(
+        &home,
+        &["add", "db", "admin", "10.0.0.2", "password", "secret"],
+    );
+    home
+}
+
+/// Run `jump edit --all --yes` with an editor running `script` on the file
+fn edit(home: &Path, script: &str) -> String {
+    let editor = home.join("editor");
+    fs::write(
+        &editor,
+        format!("#!/bin/sh\nset -e\n{}\nrm -f \"$1.orig\"\n", script),
+    )
+    .unwrap();
+    fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+    let output = command(home)
+        .env("EDITOR", &editor)
+        .args(["--interactive", "edit", "--all", "--yes"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn changes_keep_the_id_and_password() {
+    let home = store("edit-update");
+    let diff = edit(
+        &home,
+        "sed -i.orig -e 's/\"web1\"/\"web\"/' -e 's/port = 22/port = 2222/' \"$1\"",
+    );
+    assert_eq!(
+        diff,
+        "~ web1\n    port: 22 → 2222\n    server_name: web1 → web\n\
+         ~ db\n    port: 22 → 2222\n"
+    );
+    assert_eq!(
+        jump(&home, &["--color", "never", "ls"]),
+        "1  web username: admin address: 10.0.0.1\n2  db username: admin address: 10.0.0.2\n"
+    );
+    let db = jump(
+        &home,
+        &["show", "db", "--output", "json", "--include-secrets"],
+    );
+    assert!(db.contains("\"password\": \"secret\""));
+}
+
+#[test]
+fn entries_are_added_and_removed() {
+    let home = store("edit-add-remove");
+    let diff = edit(
+        &home,
+        "cat > \"$1\" <<'EOF'\n\
+         [[servers]]\n\
+         id = 2\n\
+         server_name = \"db\"\n\
+         username = \"admin\"\n\
+         server_address = \"10.0.0.2\"\n\
+         port = 22\n\
+         x11 = \"Off\"\n\
+         forward_agent = false\n\
+         compression = false\n\
+         wake_on_fail = false\n\
+         always_record = false\n\
+         options = []\n\
+         env = []\n\
+         method = { Password = { password = \"<secret>\" } }\n\
+         \n\
+         [[servers]]\n\
+         server_name = \"cache\"\n\
+         username = \"ops\"\n\
+         server_address = \"10.0.0.3\"\n\
+         port = 22\n\
+         x11 = \"Off\"\n\
+         forward_agent = false\n\
+         compression = false\n\
+         wake_on_fail = false\n\
+         always_record = false\n\
+         options = []\n\
+         env = []\n\
+         method = { SSHKey = { path = \"/keys/ops\" } }\n\
+         EOF",
+    );
+    assert_eq!(diff, "+ cache\n- web1\n");
+    assert_eq!(
+        jump(&home, &["ls", "--plain"]),
+        "db\tadmin\t10.0.0.2\t22\tpassword\ncache\tops\t10.0.0.3\t22\tssh-key\n"
+    );
+}
+
+#[test]
+fn a_broken_file_is_reopened_with_the_error() {
+    let home = store("edit-reopen");
+    let seen = home.join("seen.toml");
+    let diff = edit(
+        &home,
+        &format!(
+            "if grep -q '^# error:' \"$1\"; then\n\
+               cp \"$1\" {seen}\n\
+               sed -i.orig -e 's/id = \"one\"/id = 1/' -e 's/10.0.0.1/10.0.0.9/' \"$1\"\n\
+             else\n\
+               sed -i.orig -e 's/id = 1/id = \"one\"/' -e 's/10.0.0.1/10.0.0.9/' \"$1\"\n\
+             fi",
+            seen = seen.display()
+        ),
+    );
+    assert_eq!(diff, "~ web1\n    server_address: 10.0.0.1 → 10.0.0.9\n");
+    let seen = fs::read_to_string(seen).unwrap();
+    assert!(seen.starts_with("# error: "), "{}", seen);
+    assert!(seen.contains("id = \"one\""));
+}
+
+#[test]
+fn saving_an_empty_file_changes_nothing() {
+    let home = store("edit-empty");
+    assert_eq!(edit(&home, ": > \"$1\""), "");
+    assert_eq!(jump(&home, &["ls", "--plain"]).lines().count(), 2);
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use common::{command, home, jump, run};
+
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &["add", "web", "deploy", "10.0.0.1", "22", "ssh-key"],
+    );
+    home
+}
+
+fn code(home: &Path, args: &[&str]) -> Option<i32> {
+    run(home, args).status.code()
+}
+
+#[test]
+fn success() {
+    let home = store("exit-success");
+    assert_eq!(code(&home, &["ls"]), Some(0));
+}
+
+#[test]
+fn usage() {
+    let home = store("exit-usage");
+    assert_eq!(code(&home, &["frobnicate"]), Some(2));
+    assert_eq!(code(&home, &["add", "web"]), Some(2));
+}
+
+#[test]
+fn not_found() {
+    let home = store("exit-not-found");
+    assert_eq!(code(&home, &["show", "nope"]), Some(3));
+    assert_eq!(code(&home, &["rm", "nope", "-y"]), Some(3));
+    assert_eq!(code(&home, &["conn", "nope", "--dry-run"]), Some(3));
+    assert_eq!(code(&home, &["--output", "json", "show", "nope"]), Some(3));
+}
+
+#[test]
+fn duplicate() {
+    let home = store("exit-duplicate");
+    let output = run(&home, &["add", "web", "other", "10.0.0.2", "22", "ssh-key"]);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("A server named web already exists"));
+}
+
+#[test]
+fn store_error() {
+    // Never initialized, so there is no servers table
+    let home = home("exit-store");
+    assert_eq!(code(&home, &["ls"]), Some(5));
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+    use super::*;
+
+    fn ssh(home: &Path, script: &str, mode: u32) -> PathBuf {
+        let ssh = home.join("ssh");
+        fs::write(&ssh, script).unwrap();
+        fs::set_permissions(&ssh, fs::Permissions::from_mode(mode)).unwrap();
+        ssh
+    }
+
+    #[test]
+    fn spawn_failure() {
+        let home = store("exit-spawn");
+        let ssh = ssh(&home, "#!/bin/sh\n", 0o644);
+        for args in [&["conn", "web"][..], &["exec", "web", "--", "true"]] {
+            let output = command(&home)
+                .args(args)
+                .env("JUMP_SSH_BIN", &ssh)
+                .output()
+                .unwrap();
+            assert_eq!(output.status.code(), Some(6), "{:?}", output);
+        }
+    }
+
+    #[test]
+    fn ssh_code_passes_through() {
+        let home = store("exit-ssh");
+        let ssh = ssh(&home, "#!/bin/sh\nexit 42\n", 0o755);
+        for args in [&["conn", "web"][..], &["exec", "web", "--", "true"]] {
+            let output = command(&home)
+                .args(args)
+                .env("JUMP_SSH_BIN", &ssh)
+                .output()
+                .unwrap();
+            assert_eq!(output.status.code(), Some(42), "{:?}", output);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use jump::audit;
+
+use common::{home, jump, run};
+
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &[
+            "add",
+            "spot",
+            "ec2-user",
+            "10.0.0.1",
+            "--expires",
+            "24h",
+            "ssh-key",
+        ],
+    );
+    jump(
+        &home,
+        &[
+            "add",
+            "review-42",
+            "deploy",
+            "10.0.0.2",
+            "--expires",
+            "2020-01-01 12:00",
+            "ssh-key",
+        ],
+    );
+    jump(&home, &["add", "db", "admin", "10.0.0.3", "ssh-key"]);
+    home
+}
+
+#[test]
+fn dates_parse_as_utc() {
+    assert_eq!(audit::parse_time("1970-01-01"), Ok(0));
+    assert_eq!(
+        audit::format_time(audit::parse_time("2024-02-29T09:30").unwrap()),
+        "2024-02-29 09:30:00"
+    );
+    assert_eq!(
+        audit::format_time(audit::parse_time("2020-01-01 12:00:05").unwrap()),
+        "2020-01-01 12:00:05"
+    );
+    assert!(audit::parse_time("2024-13-01").is_err());
+    assert!(audit::parse_time("tomorrow").is_err());
+}
+
+#[test]
+fn expired_servers_are_flagged_and_pruned() {
+    let home = store("expiry-prune");
+    let listed = jump(&home, &["--color", "never", "ls"]);
+    assert!(listed.contains("address: 10.0.0.2 (expired)\n"));
+    assert!(listed.contains("address: 10.0.0.1\n"));
+    assert!(jump(&home, &["--color", "never", "show", "review-42"])
+        .contains("expires:  2020-01-01 12:00:00 UTC (expired)\n"));
+
+    let conn = run(&home, &["conn", "--dry-run", "review-42"]);
+    assert!(conn.status.success());
+    assert!(String::from_utf8_lossy(&conn.stderr).contains("review-42 expired 2020-01-01"));
+
+    assert_eq!(
+        jump(&home, &["prune", "--expired", "--dry-run"]),
+        "review-42\n"
+    );
+    assert_eq!(jump(&home, &["ls", "--plain"]).lines().count(), 3);
+    assert_eq!(jump(&home, &["prune", "--expired"]), "review-42\n");
+    assert_eq!(jump(&home, &["prune", "--expired"]), "");
+    assert_eq!(jump(&home, &["ls", "--plain"]).lines().count(), 2);
+}
+
+#[test]
+fn prune_expired_removes_them_on_any_command() {
+    let home = store("expiry-config");
+    fs::write(home.join("config.toml"), "prune_expired = true\n").unwrap();
+    let output = run(&home, &["ls", "--plain"]);
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("removed review-42, which has expired")
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 2);
+}
+
+#[test]
+fn export_carries_the_expiry() {
+    let source = store("expiry-export");
+    let bundle = jump(&source, &["export", "review-42"]);
+    assert!(bundle.contains("\"expires_at\": 1577880000"));
+    let target = home("expiry-import");
+    jump(&target, &["initialize"]);
+    fs::write(target.join("bundle.json"), bundle).unwrap();
+    jump(
+        &target,
+        &[
+            "import",
+            "--yes",
+            target.join("bundle.json").to_str().unwrap(),
+        ],
+    );
+    assert!(jump(&target, &["--color", "never", "ls"]).contains("(expired)"));
+}
@@ -0,0 +1,167 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    io::Write,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Output, Stdio},
+};
+
+use common::{command, home, jump};
+
+/// A store where db is tagged prod, and an ssh and scp noting each run
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    jump(
+        &home,
+        &[
+            "add", "db-main", "admin", "10.0.0.2", "--tag", "prod", "ssh-key",
+        ],
+    );
+    fs::write(
+        home.join("config.toml"),
+        "confirm_tags = [\"prod\"]\nprefix_match = true\n",
+    )
+    .unwrap();
+    for tool in ["ssh", "scp"] {
+        let path = home.join(tool);
+        fs::write(
+            &path,
+            format!(
+                "#!/bin/sh\necho {} >> {}\n",
+                tool,
+                home.join("ran").display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    home
+}
+
+fn run(home: &Path, args: &[&str], typed: Option<&str>) -> Output {
+    let mut command = command(home);
+    command
+        .args(args)
+        .env("JUMP_SSH_BIN", home.join("ssh"))
+        .env("JUMP_SCP_BIN", home.join("scp"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if typed.is_some() {
+        command.arg("--interactive");
+    }
+    let mut child = command.spawn().unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    if let Some(typed) = typed {
+        writeln!(stdin, "{}", typed).unwrap();
+    }
+    drop(stdin);
+    child.wait_with_output().unwrap()
+}
+
+fn ran(home: &Path) -> String {
+    fs::read_to_string(home.join("ran")).unwrap_or_default()
+}
+
+fn confirmed(home: &Path) -> Vec<(String, String)> {
+    let log = jump(home, &["log", "--output", "json"]);
+    let entries: serde_json::Value = serde_json::from_str(&log).unwrap();
+    entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .rev()
+        .map(|entry| {
+            (
+                entry["command"].as_str().unwrap().to_owned(),
+                entry["confirmed"].as_str().unwrap_or("-").to_owned(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn tagged_servers_need_their_name_or_yes_prod() {
+    let home = store("gate-conn");
+    assert!(run(&home, &["conn", "web"], None).status.success());
+
+    // `db` only resolves to db-main through prefix matching
+    let output = run(&home, &["conn", "db"], None);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("pass --yes-prod to go ahead"));
+    assert_eq!(ran(&home), "ssh\n");
+    assert!(run(&home, &["conn", "--dry-run", "db"], None)
+        .status
+        .success());
+
+    let output = run(&home, &["conn", "db"], Some("db"));
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("!!! db-main is tagged prod !!!\n    admin@10.0.0.2:22\n"));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("\"db\" is not db-main, so nothing was run on it"));
+    assert_eq!(ran(&home), "ssh\n");
+
+    assert!(run(&home, &["conn", "db"], Some("db-main"))
+        .status
+        .success());
+    assert!(run(&home, &["conn", "db", "--yes-prod"], None)
+        .status
+        .success());
+    assert_eq!(ran(&home), "ssh\nssh\nssh\n");
+    assert_eq!(
+        confirmed(&home),
+        [
+            ("conn".to_owned(), "-".to_owned()),
+            ("conn".to_owned(), "typed".to_owned()),
+            ("conn".to_owned(), "--yes-prod".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn exec_and_cp_stop_before_running_anywhere() {
+    let home = store("gate-exec");
+    let output = run(&home, &["exec", "--on", "name:*", "--", "uptime"], None);
+    assert!(!output.status.success());
+    assert_eq!(ran(&home), "");
+    let output = run(
+        &home,
+        &["exec", "--on", "name:*", "--yes-prod", "--", "uptime"],
+        None,
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(ran(&home), "ssh\nssh\n");
+
+    assert!(!run(&home, &["cp", "a.txt", "db-main:/tmp/"], None)
+        .status
+        .success());
+    assert!(
+        !run(&home, &["cp", "--to", "tag:prod", "a.txt", "/tmp/"], None)
+            .status
+            .success()
+    );
+    assert_eq!(ran(&home), "ssh\nssh\n");
+    assert!(run(
+        &home,
+        &["cp", "--to", "name:*", "--yes-prod", "a.txt", "/tmp/"],
+        None
+    )
+    .status
+    .success());
+    assert_eq!(ran(&home), "ssh\nssh\nscp\nscp\n");
+    assert_eq!(
+        confirmed(&home),
+        [
+            ("exec".to_owned(), "-".to_owned()),
+            ("exec".to_owned(), "--yes-prod".to_owned()),
+            ("cp".to_owned(), "--yes-prod".to_owned()),
+        ]
+    );
+}
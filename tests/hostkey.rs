@@ -0,0 +1,161 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use jump::hostkey::{self, Check, HostKey};
+
+use common::{command, home, jump};
+
+const ED25519: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIFiqLTXlUENhzzRtHJcEPuOC81Xdm5nsVA1K7ZHWxKq5";
+const ED25519_FINGERPRINT: &str = "SHA256:TcRlwOU2eD562bBOliqeIZWhGC/BlGTL1nhSbwFyvzI";
+const ECDSA: &str = "AAAAE2VjZHNhLXNoYTItbmlzdHAyNTYAAAAIbmlzdHAyNTYAAABBBC7FMBwnFyYWIYAe3ICUW9Da\
+                     iOuMejBok+FEP/AfpaunJeUCKm6QGBwVA0qV2MeIDHRHmmShuGuJ+sctU4KnIPI=";
+const ECDSA_FINGERPRINT: &str = "SHA256:JH7db3SXW2T0+y9hsFyxxh+d8jiSQhC6x0GcDGqGRV8";
+
+fn key(key_type: &str, fingerprint: &str) -> HostKey {
+    HostKey {
+        key_type: key_type.to_owned(),
+        fingerprint: fingerprint.to_owned(),
+    }
+}
+
+#[test]
+fn keyscan_output_gives_fingerprints_ed25519_first() {
+    let output = format!(
+        "# 10.0.0.1:22 SSH-2.0-OpenSSH_9.6\n\
+         10.0.0.1 ecdsa-sha2-nistp256 {}\n\
+         # 10.0.0.1:22 SSH-2.0-OpenSSH_9.6\n\
+         10.0.0.1 ssh-ed25519 {}\n",
+        ECDSA, ED25519
+    );
+    assert_eq!(
+        hostkey::parse_keyscan(&output),
+        [
+            key("ssh-ed25519", ED25519_FINGERPRINT),
+            key("ecdsa-sha2-nistp256", ECDSA_FINGERPRINT)
+        ]
+    );
+    assert!(hostkey::parse_keyscan("10.0.0.1 ssh-ed25519 not*base64\n").is_empty());
+}
+
+#[test]
+fn a_key_only_changes_within_its_type() {
+    let ed25519 = key("ssh-ed25519", ED25519_FINGERPRINT);
+    let ecdsa = key("ecdsa-sha2-nistp256", ECDSA_FINGERPRINT);
+    let scanned = [ed25519.clone(), ecdsa.clone()];
+    assert_eq!(hostkey::check(&ecdsa, &scanned), Check::Same);
+    let rebuilt = key("ssh-ed25519", "SHA256:other");
+    assert_eq!(
+        hostkey::check(&rebuilt, &scanned),
+        Check::Changed(ed25519.clone())
+    );
+    let rsa = key("ssh-rsa", "SHA256:rsa");
+    assert_eq!(hostkey::check(&rsa, &scanned), Check::Changed(ed25519));
+    assert_eq!(HostKey::parse(&ecdsa.to_string()).as_ref(), Some(&ecdsa));
+}
+
+/// A store with one server, an ssh that succeeds slowly enough for the
+/// first-use scan to finish, and an ssh-keyscan printing the key in `key`
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    let script = |name: &str, body: String| {
+        let path = home.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}", body)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    };
+    script(
+        "ssh",
+        format!("echo ran >> {}\nsleep 0.5\n", home.join("ran").display()),
+    );
+    script(
+        "ssh-keyscan",
+        format!(
+            "printf '%s\\n' \"$*\" > {}\necho \"10.0.0.1 $(cat {})\"\n",
+            home.join("scanned").display(),
+            home.join("key").display()
+        ),
+    );
+    set_key(&home, &format!("ssh-ed25519 {}", ED25519));
+    home
+}
+
+fn set_key(home: &Path, key: &str) {
+    fs::write(home.join("key"), key).unwrap();
+}
+
+fn conn(home: &Path, args: &[&str]) -> std::process::Output {
+    command(home)
+        .arg("conn")
+        .args(args)
+        .env("JUMP_SSH_BIN", home.join("ssh"))
+        .env("JUMP_SSH_KEYSCAN_BIN", home.join("ssh-keyscan"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn the_first_connection_records_the_key_and_later_ones_check_it() {
+    let home = store("hostkey-tofu");
+    let output = conn(&home, &["web"]);
+    assert!(output.status.success(), "{:?}", output);
+    let recorded = format!("host key: ssh-ed25519 {}", ED25519_FINGERPRINT);
+    assert!(jump(&home, &["show", "web"]).contains(&recorded));
+    assert_eq!(
+        fs::read_to_string(home.join("scanned")).unwrap(),
+        "-T 5 -p 22 -t ed25519,ecdsa,rsa -- 10.0.0.1\n"
+    );
+
+    assert!(conn(&home, &["web"]).status.success());
+    set_key(&home, &format!("ssh-ed25519 {}", ECDSA));
+    let output = conn(&home, &["web"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("The host key of web changed from ssh-ed25519"));
+    assert_eq!(fs::read_to_string(home.join("ran")).unwrap(), "ran\nran\n");
+
+    let output = conn(&home, &["web", "--accept-new-hostkey"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(conn(&home, &["web"]).status.success());
+}
+
+#[test]
+fn fingerprint_fetch_records_the_preferred_key() {
+    let home = store("hostkey-fetch");
+    set_key(
+        &home,
+        &format!(
+            "ecdsa-sha2-nistp256 {}\n10.0.0.1 ssh-ed25519 {}",
+            ECDSA, ED25519
+        ),
+    );
+    let output = command(&home)
+        .args(["fingerprint", "fetch", "web", "--timeout", "2"])
+        .env("JUMP_SSH_KEYSCAN_BIN", home.join("ssh-keyscan"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("web: ssh-ed25519 {}\n", ED25519_FINGERPRINT)
+    );
+    assert!(fs::read_to_string(home.join("scanned"))
+        .unwrap()
+        .starts_with("-T 2 "));
+
+    set_key(&home, "");
+    let output = command(&home)
+        .args(["fingerprint", "fetch", "web"])
+        .env("JUMP_SSH_KEYSCAN_BIN", home.join("ssh-keyscan"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No host key could be fetched"));
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use jump::hosts;
+
+use common::{home, jump, run};
+
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    jump(&home, &["add", "v6", "deploy", "fd00::1", "ssh-key"]);
+    jump(&home, &["add", "local", "deploy", "localhost", "ssh-key"]);
+    home
+}
+
+#[test]
+fn export_lists_ip_addresses() {
+    let home = store("hosts-export");
+    assert_eq!(
+        jump(&home, &["hosts", "export"]),
+        "10.0.0.1 web\nfd00::1 v6\n"
+    );
+    let resolved = jump(&home, &["hosts", "export", "--resolve"]);
+    assert!(
+        resolved.ends_with("127.0.0.1 local\n") || resolved.ends_with("::1 local\n"),
+        "{}",
+        resolved
+    );
+}
+
+#[test]
+fn only_the_block_is_replaced() {
+    let content = "127.0.0.1 localhost\n# BEGIN jump\n10.0.0.9 old\n# END jump\n::1 localhost\n";
+    assert_eq!(
+        hosts::replace_block(content, "jump", "10.0.0.1 web\n").unwrap(),
+        "127.0.0.1 localhost\n# BEGIN jump\n10.0.0.1 web\n# END jump\n::1 localhost\n"
+    );
+    assert_eq!(
+        hosts::replace_block("127.0.0.1 localhost", "jump", "").unwrap(),
+        "127.0.0.1 localhost\n# BEGIN jump\n# END jump\n"
+    );
+    assert!(hosts::replace_block("# END jump\n# BEGIN jump\n", "jump", "").is_err());
+    assert!(hosts::replace_block("# BEGIN jump\n", "jump", "").is_err());
+}
+
+#[test]
+fn sync_keeps_the_block_up_to_date() {
+    let home = store("hosts-sync");
+    let file = home.join("hosts");
+    fs::write(&file, "127.0.0.1 localhost\n\n# managed by hand\n").unwrap();
+    let sync = ["hosts", "sync", "--file", file.to_str().unwrap()];
+    jump(&home, &sync);
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "127.0.0.1 localhost\n\n# managed by hand\n\
+         # BEGIN jump\n10.0.0.1 web\nfd00::1 v6\n# END jump\n"
+    );
+
+    fs::write(
+        &file,
+        fs::read_to_string(&file).unwrap() + "10.1.1.1 added-later\n",
+    )
+    .unwrap();
+    jump(&home, &["rm", "v6"]);
+    jump(&home, &sync);
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "127.0.0.1 localhost\n\n# managed by hand\n\
+         # BEGIN jump\n10.0.0.1 web\n# END jump\n10.1.1.1 added-later\n"
+    );
+    assert!(!home.join(".hosts.jump").exists());
+
+    let other = jump(&home, &[&sync[..], &["--marker", "lab"]].concat());
+    assert_eq!(other, "");
+    assert!(fs::read_to_string(&file)
+        .unwrap()
+        .ends_with("# BEGIN lab\n10.0.0.1 web\n# END lab\n"));
+    assert!(
+        !run(&home, &["hosts", "sync", "--file", "/nonexistent/hosts"])
+            .status
+            .success()
+    );
+}
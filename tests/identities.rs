@@ -0,0 +1,73 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf};
+
+use common::{command, home, jump, run};
+
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    jump(
+        &home,
+        &[
+            "add",
+            "legacy",
+            "deploy",
+            "10.0.0.2",
+            "--no-identities-only",
+            "ssh-key",
+        ],
+    );
+    home
+}
+
+#[test]
+fn dry_runs_say_why_identities_only_is_there() {
+    let home = store("identities-dry-run");
+    let output = run(&home, &["conn", "web", "--dry-run"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(" -o IdentitiesOnly=yes "), "{}", stdout);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("IdentitiesOnly=yes keeps ssh to the stored key"));
+
+    let output = run(&home, &["conn", "web", "--dry-run", "--no-identities-only"]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("IdentitiesOnly"));
+    assert!(output.stderr.is_empty());
+
+    let legacy = jump(&home, &["exec", "legacy", "--dry-run", "--", "uptime"]);
+    assert!(!legacy.contains("IdentitiesOnly"));
+    assert!(jump(&home, &["show", "legacy"]).contains("identities_only off"));
+}
+
+#[test]
+fn too_many_authentication_failures_point_at_the_agent() {
+    let home = store("identities-too-many");
+    let ssh = home.join("ssh");
+    fs::write(
+        &ssh,
+        "#!/bin/sh\n\
+         echo 'Received disconnect from 10.0.0.2 port 22:2: Too many authentication failures' >&2\n\
+         exit 255\n",
+    )
+    .unwrap();
+    fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+    let stderr = |args: &[&str]| {
+        let output = command(&home)
+            .args(args)
+            .env("JUMP_SSH_BIN", &ssh)
+            .env("JUMP_SSH_KEYSCAN_BIN", home.join("no-keyscan"))
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(255));
+        String::from_utf8(output.stderr).unwrap()
+    };
+    let legacy = stderr(&["conn", "legacy"]);
+    assert!(legacy.contains("Too many authentication failures\n"));
+    assert!(legacy.contains("legacy stopped taking keys before ssh got to"));
+    assert!(legacy.contains("Leave out --no-identities-only"));
+    let web = stderr(&["exec", "--no-batch", "web", "--", "uptime"]);
+    assert!(web.contains("web stopped taking keys after too many tries"));
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use jump::{
+    edit,
+    server::{ConnectMethods, SSHKey, Server},
+};
+
+use common::{home, jump, run};
+
+fn key_server(name: &str, address: &str) -> Server {
+    Server::new(
+        name.to_owned(),
+        "deploy".to_owned(),
+        address.to_owned(),
+        22,
+        ConnectMethods::SSHKey(SSHKey {
+            path: PathBuf::from("~/.ssh/id_ed25519"),
+        }),
+    )
+}
+
+/// A store with `web`, including a TOML document with `api` and another
+/// `web`, and an export of another store with `db`
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["init"]);
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+
+    fs::create_dir_all(home.join("include")).unwrap();
+    let document = edit::document(
+        &[key_server("api", "10.0.1.1"), key_server("web", "10.0.1.2")],
+        true,
+    )
+    .unwrap();
+    fs::write(home.join("include/projectA.toml"), document).unwrap();
+
+    let team = home.join("team");
+    jump(&team, &["init"]);
+    jump(&team, &["add", "db", "deploy", "10.0.2.1", "ssh-key"]);
+    fs::write(home.join("team.json"), jump(&team, &["export"])).unwrap();
+
+    fs::write(
+        home.join("config.toml"),
+        "include = [\"include/projectA.toml\", \"team.json\"]\n",
+    )
+    .unwrap();
+    home
+}
+
+#[test]
+fn included_servers_are_listed_with_their_source() {
+    let home = store("include-list");
+    let output = run(&home, &["ls", "--plain"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "web\tdeploy\t10.0.0.1\t22\tssh-key\n\
+         api\tdeploy\t10.0.1.1\t22\tssh-key\n\
+         db\tdeploy\t10.0.2.1\t22\tssh-key\n"
+    );
+    let toml = home.join("include/projectA.toml");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!(
+            "web in {} is left out, as the store has a server of that name",
+            toml.display()
+        )),
+        "{}",
+        stderr
+    );
+
+    let listing = jump(&home, &["--color", "never", "ls"]);
+    assert!(listing.contains("api username: deploy address: 10.0.1.1 (from projectA.toml)"));
+    assert!(listing.contains("db username: deploy address: 10.0.2.1 (from team.json)"));
+    assert!(jump(&home, &["show", "api"])
+        .contains(&format!("source:   {} (read-only)", toml.display())));
+    let resolved = jump(&home, &["resolve", "db", "--json"]);
+    let resolved = serde_json::from_str::<serde_json::Value>(&resolved).unwrap();
+    assert_eq!(
+        resolved["source"],
+        home.join("team.json").display().to_string()
+    );
+}
+
+#[test]
+fn included_servers_cannot_be_changed() {
+    let home = store("include-read-only");
+    let toml = home.join("include/projectA.toml");
+    let refused = format!(
+        "api comes from {}, which jump does not change; edit that file instead",
+        toml.display()
+    );
+    for args in [
+        &["rm", "api", "--yes"][..],
+        &["tag", "add", "api", "prod"],
+        &["set", "port", "2222", "--filter", "api", "--yes"],
+    ] {
+        let output = run(&home, args);
+        assert!(!output.status.success(), "{:?}", args);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains(&refused), "{:?}: {}", args, stderr);
+    }
+    let output = run(&home, &["add", "api", "deploy", "10.0.0.9", "ssh-key"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn changes_to_the_files_show_at_the_next_run() {
+    let home = store("include-reload");
+    let json = fs::read_to_string(home.join("team.json")).unwrap();
+    fs::write(home.join("team.json"), json.replace("10.0.2.1", "10.0.2.9")).unwrap();
+    assert!(jump(&home, &["ls", "--plain"]).contains("db\tdeploy\t10.0.2.9\t22\tssh-key\n"));
+
+    // A file that is gone, as on an unmounted drive, is skipped
+    fs::remove_file(home.join("team.json")).unwrap();
+    let output = run(&home, &["ls", "--plain"]);
+    assert!(output.status.success());
+    assert!(!String::from_utf8(output.stdout).unwrap().contains("db"));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("skipping the include"));
+}
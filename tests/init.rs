@@ -0,0 +1,111 @@
+mod common;
+
+use std::fs;
+
+use common::{home, jump, run};
+
+#[test]
+fn running_it_again_only_reports() {
+    let home = home("init-again");
+    let created = jump(&home, &["init"]);
+    let store = home.join(".jump/servers.db");
+    assert!(
+        created.starts_with(&format!("store:       {} (created)\n", store.display())),
+        "{}",
+        created
+    );
+    assert!(created.contains("\nservers:     0\n"), "{}", created);
+
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    jump(&home, &["tag", "add", "web", "prod"]);
+    // The old name still works, and changes nothing either
+    let again = jump(&home, &["initialize"]);
+    assert!(again.contains("(already there)\n"), "{}", again);
+    assert!(
+        again.contains("\nservers:     1\ntags:        1\n"),
+        "{}",
+        again
+    );
+    assert_eq!(jump(&home, &["ls", "--plain"]).lines().count(), 1);
+
+    let help = jump(&home, &["--help"]);
+    assert!(help.contains("  init "), "{}", help);
+    assert!(!help.contains("initialize"), "{}", help);
+}
+
+#[test]
+fn force_recreate_keeps_the_servers_and_a_backup() {
+    let home = home("init-recreate");
+    jump(&home, &["init"]);
+    jump(
+        &home,
+        &["add", "web", "deploy", "10.0.0.1", "password", "pw"],
+    );
+    jump(&home, &["add", "db", "deploy", "10.0.0.2", "ssh-key"]);
+    let servers = jump(&home, &["ls", "--plain"]);
+
+    for _ in 0..2 {
+        let output = jump(&home, &["init", "--force-recreate", "--yes"]);
+        assert!(
+            output.contains("(recreated, the old one is at "),
+            "{}",
+            output
+        );
+    }
+    assert_eq!(jump(&home, &["ls", "--plain"]), servers);
+    assert!(jump(&home, &["export", "--include-secrets"]).contains("\"pw\""));
+    // One backup per run, even within the same second
+    let backups = fs::read_dir(home.join(".jump"))
+        .unwrap()
+        .filter(|entry| {
+            let name = entry.as_ref().unwrap().file_name();
+            name.to_string_lossy().starts_with("servers.db.bak-")
+        })
+        .count();
+    assert_eq!(backups, 2);
+}
+
+#[test]
+fn a_store_that_cannot_be_read_is_rebuilt_from_an_export() {
+    let home = home("init-corrupt");
+    jump(&home, &["init"]);
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    let export = home.join("servers.json");
+    jump(&home, &["export", "--out", export.to_str().unwrap()]);
+    fs::write(home.join(".jump/servers.db"), "not a database").unwrap();
+
+    let output = run(&home, &["init"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("jump init --force-recreate"));
+    let output = run(&home, &["init", "--force-recreate", "--yes"]);
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("rebuild it from an export with --from")
+    );
+
+    let from = export.to_str().unwrap();
+    jump(
+        &home,
+        &["init", "--force-recreate", "--from", from, "--yes"],
+    );
+    assert_eq!(
+        jump(&home, &["ls", "--plain"]),
+        "web\tdeploy\t10.0.0.1\t22\tssh-key\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn problems_are_warned_about() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = home("init-warnings");
+    jump(&home, &["init"]);
+    let store = home.join(".jump/servers.db");
+    assert_eq!(
+        fs::metadata(&store).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+    fs::set_permissions(&store, fs::Permissions::from_mode(0o644)).unwrap();
+    let output = run(&home, &["init"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("can be read by others"));
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use common::{command, home, jump, run};
+
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web1", "deploy", "10.0.0.1", "ssh-key"]);
+    jump(&home, &["add", "web2", "deploy", "10.0.0.2", "ssh-key"]);
+    home
+}
+
+fn stderr(home: &Path, args: &[&str]) -> String {
+    let output = run(home, args);
+    assert!(!output.status.success(), "{:?}", output);
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn prompts_fail_without_a_terminal() {
+    let home = store("interactive-off");
+    assert!(stderr(&home, &["rm", "name:web*"]).contains(
+        "Not asking \"Remove 2 servers: web1, web2?\" in non-interactive mode; pass --yes"
+    ));
+    assert!(stderr(
+        &home,
+        &[
+            "export",
+            "--encrypted",
+            home.join("bundle").to_str().unwrap(),
+            "--passphrase-prompt"
+        ]
+    )
+    .contains("Not asking for the bundle passphrase in non-interactive mode; set JUMP_PASSPHRASE"));
+    assert!(stderr(&home, &[]).contains("jump conn <name>"));
+    assert_eq!(jump(&home, &["ls", "--plain"]).lines().count(), 2);
+}
+
+#[test]
+fn interactive_forces_prompts() {
+    let home = store("interactive-on");
+    let mut child = command(&home)
+        .args(["--interactive", "rm", "name:web*"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"n\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("[y/N]"));
+    assert_eq!(jump(&home, &["ls", "--plain"]).lines().count(), 2);
+
+    assert!(!run(&home, &["--interactive", "--non-interactive", "ls"])
+        .status
+        .success());
+}
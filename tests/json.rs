@@ -0,0 +1,231 @@
+mod common;
+
+use std::net::TcpListener;
+
+use common::{home, jump, run};
+
+const KEY_SERVER: &str = r#"{
+  "access_window": null,
+  "addresses": [],
+  "always_record": false,
+  "broadcast": null,
+  "compression": false,
+  "console_server": null,
+  "console_target": null,
+  "created_at": 0,
+  "env": [
+    [
+      "DEPLOY_ENV",
+      "staging"
+    ]
+  ],
+  "expires_at": null,
+  "forward_agent": false,
+  "host_key": null,
+  "id": 1,
+  "identities_only": true,
+  "knock": [],
+  "knock_delay": null,
+  "local_command": null,
+  "mac": null,
+  "max_session": null,
+  "method": {
+    "SSHKey": {
+      "path": "~/.ssh/deploy"
+    }
+  },
+  "multiplex": false,
+  "note": null,
+  "options": [
+    [
+      "ServerAliveInterval",
+      "30"
+    ]
+  ],
+  "platform": null,
+  "port": 22,
+  "post_connect": null,
+  "pre_connect": null,
+  "proxy": null,
+  "remote_command": null,
+  "server_address": "10.0.0.1",
+  "server_name": "web",
+  "tags": [],
+  "updated_at": 0,
+  "username": "deploy",
+  "wake_on_fail": false,
+  "workdir": "/srv",
+  "x11": "Off"
+}"#;
+
+const PASSWORD_SERVER: &str = r#"{
+  "access_window": null,
+  "addresses": [],
+  "always_record": false,
+  "broadcast": null,
+  "compression": false,
+  "console_server": null,
+  "console_target": null,
+  "created_at": 0,
+  "env": [],
+  "expires_at": null,
+  "forward_agent": false,
+  "host_key": null,
+  "id": 2,
+  "identities_only": true,
+  "knock": [],
+  "knock_delay": null,
+  "local_command": null,
+  "mac": null,
+  "max_session": null,
+  "method": {
+    "Password": {}
+  },
+  "multiplex": false,
+  "note": null,
+  "options": [],
+  "platform": null,
+  "port": 2222,
+  "post_connect": null,
+  "pre_connect": null,
+  "proxy": null,
+  "remote_command": null,
+  "server_address": "10.0.0.2",
+  "server_name": "db",
+  "tags": [],
+  "updated_at": 0,
+  "username": "admin",
+  "wake_on_fail": false,
+  "workdir": null,
+  "x11": "Off"
+}"#;
+
+fn store(test: &str) -> std::path::PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &[
+            "add",
+            "web",
+            "deploy",
+            "10.0.0.1",
+            "22",
+            "--workdir",
+            "/srv",
+            "ssh-key",
+            "~/.ssh/deploy",
+        ],
+    );
+    jump(
+        &home,
+        &["option", "set", "web", "ServerAliveInterval", "30"],
+    );
+    jump(&home, &["env", "set", "web", "DEPLOY_ENV=staging"]);
+    jump(
+        &home,
+        &[
+            "add", "db", "admin", "10.0.0.2", "2222", "password", "hunter2",
+        ],
+    );
+    home
+}
+
+#[test]
+fn ls() {
+    let home = store("json-ls");
+    assert_eq!(
+        without_times(&jump(&home, &["ls", "--output", "json"])),
+        format!(
+            "[\n{},\n{}\n]\n",
+            indent(KEY_SERVER),
+            indent(PASSWORD_SERVER)
+        )
+    );
+}
+
+#[test]
+fn show() {
+    let home = store("json-show");
+    assert_eq!(
+        without_times(&jump(&home, &["show", "web", "--output", "json"])),
+        format!("{}\n", KEY_SERVER)
+    );
+    assert_eq!(
+        without_times(&jump(&home, &["show", "db", "--output", "json"])),
+        format!("{}\n", PASSWORD_SERVER)
+    );
+}
+
+#[test]
+fn secrets_only_on_request() {
+    let home = store("json-secrets");
+    assert!(!jump(&home, &["ls", "--output", "json"]).contains("hunter2"));
+    let shown = jump(
+        &home,
+        &["show", "db", "--output", "json", "--include-secrets"],
+    );
+    assert!(shown.contains("\"Password\": {\n      \"password\": \"hunter2\"\n    }"));
+}
+
+#[test]
+fn check() {
+    let home = home("json-check");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &["add", "up", "u", "127.0.0.1", &port.to_string(), "ssh-key"],
+    );
+    assert_eq!(
+        jump(&home, &["check", "--output", "json"]),
+        format!(
+            r#"[
+  {{
+    "server_name": "up",
+    "server_address": "127.0.0.1",
+    "port": {},
+    "up": true
+  }}
+]
+"#,
+            port
+        )
+    );
+}
+
+#[test]
+fn errors_go_to_stderr_as_json() {
+    let home = store("json-error");
+    let output = run(&home, &["show", "nope", "--output", "json"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "{\"error\":\"No server named nope\"}\n"
+    );
+}
+
+/// `json` with the timestamps, which depend on when the test ran, zeroed
+fn without_times(json: &str) -> String {
+    json.lines()
+        .map(|line| match line.split_once(": ") {
+            Some((key, _))
+                if key.ends_with("\"created_at\"") || key.ends_with("\"updated_at\"") =>
+            {
+                format!("{}: 0,\n", key)
+            }
+            _ => format!("{}\n", line),
+        })
+        .collect()
+}
+
+/// `pretty` as it appears nested one level deep
+fn indent(pretty: &str) -> String {
+    pretty
+        .lines()
+        .map(|line| format!("  {}", line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
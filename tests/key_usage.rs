@@ -0,0 +1,122 @@
+mod common;
+
+use jump::key_usage::{accepted, Accepted};
+
+const ACCEPTED: &str = "debug1: Server accepts key: /home/me/.ssh/id_ed25519 ED25519 SHA256:6ZkY3pH0Lw7sD2yYxq9n3c1rFv0eTq8uPzBq2mWcXkE explicit";
+
+#[test]
+fn the_key_counts_once_the_login_went_through_with_it() {
+    let lines = [
+        "debug1: Offering public key: /home/me/.ssh/id_rsa RSA SHA256:abc explicit",
+        ACCEPTED,
+        "debug1: Authenticated to 10.0.0.1 ([10.0.0.1]:22) using \"publickey\".",
+    ];
+    assert_eq!(
+        accepted(&lines),
+        Some(Accepted {
+            fingerprint: "SHA256:6ZkY3pH0Lw7sD2yYxq9n3c1rFv0eTq8uPzBq2mWcXkE".to_owned(),
+            key: "/home/me/.ssh/id_ed25519".to_owned(),
+        })
+    );
+    // Older ssh, and a key only the agent has, known by its comment
+    let older = [
+        "Server accepts key: me@laptop ECDSA SHA256:xyz agent",
+        "Authentication succeeded (publickey).",
+    ];
+    assert_eq!(accepted(&older).unwrap().key, "me@laptop");
+}
+
+#[test]
+fn nothing_is_taken_from_a_login_that_did_not_finish() {
+    assert_eq!(accepted(&[ACCEPTED]), None);
+    assert_eq!(
+        accepted(&[
+            ACCEPTED,
+            "debug1: Authenticated to 10.0.0.1 ([10.0.0.1]:22) using \"keyboard-interactive\"."
+        ]),
+        None
+    );
+    assert_eq!(
+        accepted(&[
+            "debug1: Server accepts key: garbled",
+            "debug1: Authentication succeeded (publickey)."
+        ]),
+        None
+    );
+}
+
+#[cfg(unix)]
+mod conn {
+    use std::{
+        fs,
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+    };
+
+    use super::common::{command, home, jump};
+
+    /// A store with two key servers, and an ssh that notes its arguments
+    /// and, given `-v`, says which key it was let in with
+    fn store(test: &str) -> PathBuf {
+        let home = home(test);
+        jump(&home, &["initialize"]);
+        jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+        jump(&home, &["add", "db", "deploy", "10.0.0.2", "ssh-key"]);
+        fs::write(
+            home.join("ssh"),
+            format!(
+                "#!/bin/sh\n\
+                 echo \"$@\" > {}\n\
+                 if [ \"$1\" = -v ]; then\n\
+                 echo 'debug1: Server accepts key: /keys/old_ed25519 ED25519 SHA256:old' >&2\n\
+                 echo 'debug1: Authenticated to host ([10.0.0.1]:22) using \"publickey\".' >&2\n\
+                 fi\n\
+                 echo 'remote banner' >&2\n",
+                home.join("args").display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(home.join("ssh"), fs::Permissions::from_mode(0o755)).unwrap();
+        home
+    }
+
+    fn connect(home: &Path, name: &str) -> String {
+        let output = command(home)
+            .args(["conn", name])
+            .env("JUMP_SSH_BIN", home.join("ssh"))
+            .env("JUMP_SSH_KEYSCAN_BIN", home.join("no-keyscan"))
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stderr).unwrap()
+    }
+
+    #[test]
+    fn the_accepted_key_is_recorded_and_the_debug_output_hidden() {
+        let home = store("key-usage-conn");
+        let stderr = connect(&home, "web");
+        assert!(!stderr.contains("debug1"), "{}", stderr);
+        assert!(stderr.contains("remote banner"), "{}", stderr);
+
+        let show = jump(&home, &["show", "web"]);
+        assert!(
+            show.contains("key used: SHA256:old (/keys/old_ed25519), "),
+            "{}",
+            show
+        );
+        assert_eq!(
+            jump(&home, &["keys", "usage"]),
+            "SHA256:old  /keys/old_ed25519\n    web\nnot recorded yet\n    db\n"
+        );
+    }
+
+    #[test]
+    fn track_keys_off_leaves_ssh_quiet() {
+        let home = store("key-usage-off");
+        fs::write(home.join("config.toml"), "track_keys = false\n").unwrap();
+        connect(&home, "web");
+        let args = fs::read_to_string(home.join("args")).unwrap();
+        assert!(!args.starts_with("-v "), "{}", args);
+        assert!(!jump(&home, &["show", "web"]).contains("key used"));
+    }
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use std::{
+    net::{TcpListener, UdpSocket},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use jump::knock::{self, Knock, Protocol};
+
+use common::{home, jump, run};
+
+#[test]
+fn knocks_parse_and_print() {
+    assert_eq!(
+        knock::parse_arg("7000").unwrap(),
+        Knock {
+            port: 7000,
+            protocol: Protocol::Tcp
+        }
+    );
+    assert_eq!(
+        knock::parse_arg("8000/UDP").unwrap().to_string(),
+        "8000/udp"
+    );
+    assert!(knock::parse_arg("70000").is_err());
+    assert!(knock::parse_arg("7000/icmp").is_err());
+    let sequence = knock::format_sequence(&[
+        knock::parse_arg("7000").unwrap(),
+        knock::parse_arg("8000/udp").unwrap(),
+    ]);
+    assert_eq!(sequence.as_deref(), Some("7000/tcp 8000/udp"));
+    assert_eq!(knock::parse_sequence(sequence).len(), 2);
+    assert_eq!(knock::format_sequence(&[]), None);
+}
+
+/// Local ports that report, in the order they are knocked on, which of them
+/// saw a knock
+fn listeners() -> (Vec<Knock>, mpsc::Receiver<u16>) {
+    let (sender, received) = mpsc::channel();
+    let mut knocks = vec![];
+    for protocol in [Protocol::Tcp, Protocol::Udp, Protocol::Tcp] {
+        let sender = sender.clone();
+        let port = match protocol {
+            Protocol::Tcp => {
+                let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+                let port = listener.local_addr().unwrap().port();
+                thread::spawn(move || {
+                    listener.accept().unwrap();
+                    sender.send(port).unwrap();
+                });
+                port
+            }
+            Protocol::Udp => {
+                let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+                let port = socket.local_addr().unwrap().port();
+                thread::spawn(move || {
+                    socket.recv(&mut [0; 16]).unwrap();
+                    sender.send(port).unwrap();
+                });
+                port
+            }
+        };
+        knocks.push(Knock { port, protocol });
+    }
+    (knocks, received)
+}
+
+fn received(received: &mpsc::Receiver<u16>, count: usize) -> Vec<u16> {
+    (0..count)
+        .map(|_| received.recv_timeout(Duration::from_secs(5)).unwrap())
+        .collect()
+}
+
+#[test]
+fn ports_are_knocked_in_order() {
+    let (knocks, receiver) = listeners();
+    knock::knock("127.0.0.1", &knocks, Duration::from_millis(50)).unwrap();
+    assert_eq!(
+        received(&receiver, 3),
+        knocks.iter().map(|knock| knock.port).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn knock_command_uses_the_stored_sequence() {
+    let (knocks, receiver) = listeners();
+    let home = home("knock-command");
+    jump(&home, &["initialize"]);
+    let mut add = vec![
+        "add".to_owned(),
+        "edge".to_owned(),
+        "deploy".to_owned(),
+        "127.0.0.1".to_owned(),
+        "--knock-delay".to_owned(),
+        "50".to_owned(),
+    ];
+    for knock in &knocks {
+        add.extend(["--knock".to_owned(), knock.to_string()]);
+    }
+    add.push("ssh-key".to_owned());
+    jump(&home, &add.iter().map(String::as_str).collect::<Vec<_>>());
+
+    let shown = jump(&home, &["--color", "never", "show", "edge"]);
+    assert!(shown.contains(&format!(
+        "knock:    {}/tcp {}/udp {}/tcp, 50ms apart\n",
+        knocks[0].port, knocks[1].port, knocks[2].port
+    )));
+    jump(&home, &["knock", "edge"]);
+    assert_eq!(
+        received(&receiver, 3),
+        knocks.iter().map(|knock| knock.port).collect::<Vec<_>>()
+    );
+
+    jump(&home, &["add", "plain", "deploy", "127.0.0.1", "ssh-key"]);
+    let output = run(&home, &["knock", "plain", "--output", "json"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "{\"error\":\"Invalid knock: no knock sequence stored for plain\"}\n"
+    );
+}
@@ -0,0 +1,89 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt};
+
+use common::{home, jump};
+
+const KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIFiqLTXlUENhzzRtHJcEPuOC81Xdm5nsVA1K7ZHWxKq5";
+const FINGERPRINT: &str = "SHA256:TcRlwOU2eD562bBOliqeIZWhGC/BlGTL1nhSbwFyvzI";
+
+#[test]
+fn isolated_known_hosts_point_ssh_at_a_file_per_profile() {
+    let home = home("known-hosts-isolated");
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    let dry_run = jump(&home, &["conn", "--dry-run", "web"]);
+    assert!(!dry_run.contains("UserKnownHostsFile"));
+
+    fs::write(home.join("config.toml"), "isolated_known_hosts = true\n").unwrap();
+    let file = home.join(".jump/known_hosts");
+    let dry_run = jump(&home, &["conn", "--dry-run", "web"]);
+    assert!(dry_run.contains(&format!(
+        "-o UserKnownHostsFile={} -o HashKnownHosts=no",
+        file.display()
+    )));
+    let mode = fs::metadata(&file).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    assert!(jump(&home, &["exec", "--dry-run", "web", "--", "true"]).contains("UserKnownHostsFile"));
+
+    jump(&home, &["--profile", "lab", "initialize"]);
+    jump(
+        &home,
+        &["--profile", "lab", "add", "pi", "pi", "10.0.1.1", "ssh-key"],
+    );
+    let dry_run = jump(&home, &["--profile", "lab", "conn", "--dry-run", "pi"]);
+    assert!(dry_run.contains(".jump/lab.known_hosts"));
+}
+
+#[test]
+fn known_hosts_entries_map_back_to_servers() {
+    let home = home("known-hosts-ls");
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    jump(
+        &home,
+        &["add", "db", "admin", "10.0.0.2", "2222", "ssh-key"],
+    );
+    jump(&home, &["address", "add", "web", "203.0.113.9"]);
+    fs::create_dir_all(home.join(".jump")).unwrap();
+    let file = home.join(".jump/known_hosts");
+    fs::write(
+        &file,
+        format!(
+            "# a comment\n\
+             10.0.0.1 ssh-ed25519 {key}\n\
+             [10.0.0.2]:2222,db.lab ssh-ed25519 {key}\n\
+             10.0.0.2 ssh-ed25519 {key}\n\
+             |1|c2FsdA==|aGFzaA== ssh-ed25519 {key}\n\
+             203.0.113.9 ssh-ed25519 {key}\n",
+            key = KEY
+        ),
+    )
+    .unwrap();
+
+    assert_eq!(
+        jump(&home, &["known-hosts", "ls"]),
+        format!(
+            "web\t10.0.0.1\tssh-ed25519\t{fp}\n\
+             db\t[10.0.0.2]:2222,db.lab\tssh-ed25519\t{fp}\n\
+             -\t10.0.0.2\tssh-ed25519\t{fp}\n\
+             - (hashed)\t|1|c2FsdA==|aGFzaA==\tssh-ed25519\t{fp}\n\
+             web\t203.0.113.9\tssh-ed25519\t{fp}\n",
+            fp = FINGERPRINT
+        )
+    );
+
+    assert_eq!(
+        jump(&home, &["known-hosts", "rm", "web"]),
+        "removed 2 keys of web\n"
+    );
+    let left = fs::read_to_string(&file).unwrap();
+    assert!(left.starts_with("# a comment\n[10.0.0.2]:2222"));
+    assert!(!left.contains("10.0.0.1 ") && !left.contains("203.0.113.9"));
+    assert_eq!(
+        jump(&home, &["known-hosts", "rm", "web"]),
+        format!("web has no key in {}\n", file.display())
+    );
+}
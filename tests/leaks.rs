@@ -0,0 +1,130 @@
+mod common;
+
+use jump::leaks::leaks_secret;
+
+#[test]
+fn passwords_and_passphrases_given_to_jump_are_found() {
+    for line in [
+        "jump add db root 10.0.0.2 password hunter2",
+        "~/.cargo/bin/jump add db root 10.0.0.2 22 password 'hunter 2'",
+        ": 1700000000:0;jump add db root 10.0.0.2 password hunter2",
+        "- cmd: jump add db root 10.0.0.2 password hunter2",
+        "JUMP_PASSPHRASE=s3cret jump import team.jmp",
+        "cd /srv && jump --profile work add db root 10.0.0.2 password hunter2",
+    ] {
+        assert!(leaks_secret(line), "{}", line);
+    }
+    for line in [
+        "jump add db root 10.0.0.2 ssh-key ~/.ssh/id_ed25519",
+        "jump add db root 10.0.0.2 password",
+        "jump add --help password --help",
+        "JUMP_PASSPHRASE= jump import team.jmp",
+        "git commit -m 'password reset for jump hosts'",
+        "echo password hunter2",
+    ] {
+        assert!(!leaks_secret(line), "{}", line);
+    }
+}
+
+#[cfg(unix)]
+mod audit {
+    use std::{
+        fs,
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+        process::Output,
+    };
+
+    use super::common::{command, home, jump};
+
+    /// A home with a history holding a password, a store others can read
+    /// and a plain export with passwords under `exports`
+    fn leaky(test: &str) -> PathBuf {
+        let home = home(test);
+        jump(&home, &["init"]);
+        jump(
+            &home,
+            &["add", "db", "root", "10.0.0.2", "password", "hunter2"],
+        );
+        fs::write(
+            home.join(".bash_history"),
+            "ls\njump add db root 10.0.0.2 password hunter2\njump conn db\n",
+        )
+        .unwrap();
+        let store = home.join(".jump/servers.db");
+        fs::set_permissions(&store, fs::Permissions::from_mode(0o644)).unwrap();
+        fs::create_dir_all(home.join("exports")).unwrap();
+        fs::write(
+            home.join("exports/team.json"),
+            jump(&home, &["export", "--include-secrets"]),
+        )
+        .unwrap();
+        fs::write(home.join("exports/names.json"), jump(&home, &["export"])).unwrap();
+        home
+    }
+
+    fn audit(home: &Path, args: &[&str]) -> Output {
+        command(home)
+            .arg("audit-secrets")
+            .arg(home.join("exports"))
+            .args(args)
+            .env_remove("HISTFILE")
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn each_leak_is_reported_with_its_remedy() {
+        let home = leaky("leaks-report");
+        let output = audit(&home, &[]);
+        assert_eq!(output.status.code(), Some(1));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let history = home.join(".bash_history");
+        let store = home.join(".jump/servers.db");
+        let export = home.join("exports/team.json");
+        assert!(stdout.contains(&format!(
+            "error: {0} has a secret given to jump on line 2 (remove those lines from {0})\n",
+            history.display()
+        )));
+        assert!(stdout.contains(&format!(
+            "error: {0} can be read by others (chmod 600 {0})\n",
+            store.display()
+        )));
+        assert!(stdout.contains(&format!(
+            "error: {0} is an export with 1 password in plain text; \
+             `jump export --encrypted` writes one without (run `shred -u {0}`)\n",
+            export.display()
+        )));
+        assert!(!stdout.contains("names.json"), "{}", stdout);
+        assert!(!stdout.contains("hunter2"), "{}", stdout);
+        assert!(stdout.ends_with("run `jump audit-secrets --fix` to apply the fixes\n"));
+    }
+
+    #[test]
+    fn fix_scrubs_the_history_and_closes_the_files() {
+        let home = leaky("leaks-fix");
+        let output = audit(&home, &["--fix", "--yes"]);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(
+            stdout.contains("done: remove those lines from"),
+            "{}",
+            stdout
+        );
+        assert_eq!(
+            fs::read_to_string(home.join(".bash_history")).unwrap(),
+            "ls\njump conn db\n"
+        );
+        let store = home.join(".jump/servers.db");
+        assert_eq!(
+            fs::metadata(store).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        // Deleting an export is left to the user
+        assert_eq!(output.status.code(), Some(1));
+        assert!(home.join("exports/team.json").exists());
+        fs::remove_file(home.join("exports/team.json")).unwrap();
+        let clean = audit(&home, &[]);
+        assert!(clean.status.success(), "{:?}", clean);
+        assert_eq!(String::from_utf8(clean.stdout).unwrap(), "no leaks found\n");
+    }
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use common::{home, jump};
+
+#[test]
+fn legacy_servers_pass_the_old_algorithms_to_ssh() {
+    let home = home("legacy");
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &["add", "sw1", "admin", "10.0.9.1", "--legacy", "ssh-key"],
+    );
+    jump(&home, &["add", "sw2", "admin", "10.0.9.2", "ssh-key"]);
+    let dry_run = jump(&home, &["conn", "--dry-run", "sw1"]);
+    assert!(dry_run.contains("-o HostKeyAlgorithms=+ssh-rsa"));
+    assert!(dry_run
+        .contains("-o KexAlgorithms=+diffie-hellman-group14-sha1,diffie-hellman-group1-sha1"));
+    assert!(dry_run.contains("-o Ciphers=+aes128-cbc,aes256-cbc,3des-cbc"));
+    assert!(dry_run.contains("-o PubkeyAcceptedAlgorithms=+ssh-rsa"));
+    let shown = jump(&home, &["--color", "never", "show", "sw1"]);
+    assert!(shown.contains("legacy:   HostKeyAlgorithms=+ssh-rsa (weaker algorithms allowed)\n"));
+    assert!(!jump(&home, &["show", "sw2"]).contains("legacy:"));
+
+    jump(
+        &home,
+        &[
+            "option",
+            "set",
+            "sw2",
+            "KexAlgorithms",
+            "+diffie-hellman-group1-sha1",
+        ],
+    );
+    jump(
+        &home,
+        &["option", "set", "sw2", "ServerAliveInterval", "30"],
+    );
+    let doctor = jump(&home, &["doctor"]);
+    assert!(doctor.contains(
+        "sw1 still allows legacy algorithms (Ciphers, HostKeyAlgorithms, KexAlgorithms, PubkeyAcceptedAlgorithms)"
+    ));
+    assert!(doctor.contains("sw2 still allows legacy algorithms (KexAlgorithms)"));
+    assert!(doctor.contains("run `jump option unset sw2 --legacy`"));
+
+    jump(&home, &["option", "unset", "sw2", "--legacy"]);
+    assert_eq!(
+        jump(&home, &["option", "ls", "sw2"]),
+        "ServerAliveInterval=30\n"
+    );
+    jump(&home, &["option", "set", "sw2", "--legacy"]);
+    assert!(jump(&home, &["conn", "--dry-run", "sw2"]).contains("-o Ciphers=+aes128-cbc"));
+    jump(&home, &["option", "unset", "sw1", "--legacy"]);
+    assert!(!jump(&home, &["doctor"]).contains("sw1 still allows"));
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use jump::{audit, config::MaxSession, limit};
+
+/// A terminal whose output the test reads back
+#[derive(Clone, Default)]
+struct Terminal(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Terminal {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Terminal {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+#[test]
+fn the_timer_warns_before_the_limit() {
+    let terminal = Terminal::default();
+    let settings = MaxSession {
+        warning: 1,
+        grace: 1,
+    };
+    let timer = limit::start("lab", Duration::from_secs(2), &settings, terminal.clone());
+    thread::sleep(Duration::from_millis(1500));
+    assert_eq!(
+        terminal.text(),
+        "\r\njump: lab disconnects in 1s, at its max_session of 2s\r\n"
+    );
+    assert!(!timer.reached());
+    thread::sleep(Duration::from_millis(1000));
+    assert!(timer.reached());
+
+    // A session that ended first stops it
+    let terminal = Terminal::default();
+    let timer = limit::start("lab", Duration::from_secs(1), &settings, terminal.clone());
+    drop(timer);
+    thread::sleep(Duration::from_millis(1200));
+    assert_eq!(terminal.text(), "");
+}
+
+#[test]
+fn ages_read_back() {
+    for (secs, age) in [(7_200, "2h"), (5_400, "90m"), (45, "45s"), (604_800, "1w")] {
+        assert_eq!(audit::format_age(secs), age);
+        assert_eq!(audit::parse_age(age), Ok(secs as i64));
+    }
+}
+
+#[cfg(unix)]
+mod conn {
+    use std::{
+        fs,
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+        process::Output,
+        time::{Duration, Instant},
+    };
+
+    use super::common::{command, home, jump, run};
+
+    /// A store with `lab`, limited to a second per session, no warning and
+    /// a second of grace, and an ssh running `script`
+    fn store(test: &str, script: &str) -> PathBuf {
+        let home = home(test);
+        jump(&home, &["init"]);
+        jump(
+            &home,
+            &[
+                "add",
+                "lab",
+                "deploy",
+                "10.0.0.1",
+                "--max-session",
+                "1s",
+                "ssh-key",
+            ],
+        );
+        fs::write(
+            home.join("config.toml"),
+            "track_keys = false\n\n[max_session]\nwarning = 0\ngrace = 1\n",
+        )
+        .unwrap();
+        fs::write(home.join("ssh"), format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(home.join("ssh"), fs::Permissions::from_mode(0o755)).unwrap();
+        home
+    }
+
+    fn conn(home: &Path, args: &[&str]) -> (Output, Duration) {
+        let started = Instant::now();
+        let output = command(home)
+            .args(["conn", "lab"])
+            .args(args)
+            .env("JUMP_SSH_BIN", home.join("ssh"))
+            .env("JUMP_SSH_KEYSCAN_BIN", home.join("no-keyscan"))
+            .output()
+            .unwrap();
+        (output, started.elapsed())
+    }
+
+    fn log(home: &Path) -> serde_json::Value {
+        let log = jump(home, &["log", "--output", "json"]);
+        serde_json::from_str::<serde_json::Value>(&log).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn a_session_is_ended_at_the_limit() {
+        let home = store("limit-reached", "exec sleep 30");
+        assert!(jump(&home, &["show", "lab"]).contains("limit:    1s per session"));
+        let (output, took) = conn(&home, &[]);
+        assert!(!output.status.success());
+        assert!(took < Duration::from_secs(10), "{:?}", took);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("disconnected from lab at its max_session of 1s"),
+            "{}",
+            stderr
+        );
+        assert_eq!(log(&home)["ended_by"], "max_session");
+        assert!(jump(&home, &["--color", "never", "log"]).contains("reached"));
+    }
+
+    #[test]
+    fn ssh_ignoring_sigterm_is_killed_after_the_grace() {
+        let home = store(
+            "limit-killed",
+            "trap '' TERM\nwhile true; do sleep 0.1; done",
+        );
+        let (output, took) = conn(&home, &[]);
+        assert!(!output.status.success());
+        assert!(took < Duration::from_secs(10), "{:?}", took);
+        assert_eq!(log(&home)["ended_by"], "max_session");
+    }
+
+    #[test]
+    fn no_limit_needs_a_reason_that_is_logged() {
+        let home = store("limit-lifted", "sleep 2");
+        let output = run(&home, &["conn", "lab", "--no-limit", ""]);
+        assert!(!output.status.success());
+
+        let (output, took) = conn(&home, &["--no-limit", "kernel build"]);
+        assert!(output.status.success(), "{:?}", output);
+        assert!(took >= Duration::from_secs(2));
+        let entry = log(&home);
+        assert_eq!(entry["no_limit"], "kernel build");
+        assert_eq!(entry["ended_by"], serde_json::Value::Null);
+        assert!(jump(&home, &["--color", "never", "log"]).contains("lifted: kernel build"));
+    }
+}
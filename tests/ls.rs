@@ -0,0 +1,233 @@
+mod common;
+
+use std::path::PathBuf;
+
+use jump::{
+    audit,
+    config::Config,
+    output,
+    server::{ConnectMethods, SSHKey, Server},
+    style::Style,
+};
+
+use common::{command, home, jump, run};
+
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &[
+            "add",
+            "web",
+            "deploy",
+            "10.0.0.1",
+            "2222",
+            "--tag",
+            "prod",
+            "--tag",
+            "eu",
+            "ssh-key",
+            "/keys/web_ed25519",
+        ],
+    );
+    jump(
+        &home,
+        &["add", "db", "admin", "10.0.0.2", "password", "hunter2"],
+    );
+    home
+}
+
+#[test]
+fn long_lists_every_column_without_secrets() {
+    let home = store("ls-long");
+    let output = jump(&home, &["--color", "never", "ls", "--long"]);
+    let today = &audit::format_time(audit::now())[..10];
+    assert_eq!(
+        output,
+        format!(
+            "ID  NAME  USER    ADDRESS   PORT  METHOD    KEY          TAGS     PLATFORM  LAST (UTC)  CREATED     UPDATED     EXPIRES  WINDOW\n\
+             1   web   deploy  10.0.0.1  2222  ssh-key   web_ed25519  eu,prod  -         never       {today}  {today}  -        -\n\
+             2   db    admin   10.0.0.2  22    password  -            -        -         never       {today}  {today}  -        -\n"
+        )
+    );
+    assert!(!output.contains("hunter2"));
+}
+
+#[test]
+fn sorting_composes_with_selectors() {
+    let home = store("ls-sort");
+    jump(&home, &["add", "cache", "deploy", "10.0.0.3", "ssh-key"]);
+    let names = |args: &[&str]| {
+        jump(&home, &[&["ls", "--plain"], args].concat())
+            .lines()
+            .map(|line| line.split('\t').next().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&["--sort", "name"]), ["cache", "db", "web"]);
+    assert_eq!(names(&["--sort", "port"]), ["db", "cache", "web"]);
+    assert_eq!(names(&["--sort", "name", "user:deploy"]), ["cache", "web"]);
+}
+
+#[test]
+fn ids_lead_each_line_and_are_not_reused() {
+    let home = store("ls-ids");
+    jump(&home, &["rm", "db"]);
+    jump(&home, &["add", "cache", "deploy", "10.0.0.3", "ssh-key"]);
+    let output = jump(&home, &["--color", "never", "ls"]);
+    assert_eq!(
+        output,
+        "1  web username: deploy address: 10.0.0.1\n\
+         3  cache username: deploy address: 10.0.0.3\n"
+    );
+    assert!(jump(&home, &["--color", "never", "show", "cache"]).starts_with("id:       3\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn last_connection_is_shown_and_sorted_by() {
+    let home = store("ls-last");
+    let status = command(&home)
+        .env("JUMP_SSH_BIN", "/bin/true")
+        .args(["conn", "db"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = jump(
+        &home,
+        &["--color", "never", "ls", "--long", "--sort", "last"],
+    );
+    let rows = output.lines().skip(1).collect::<Vec<_>>();
+    assert!(rows[0].starts_with("2   db "));
+    assert!(!rows[0].contains(" never "));
+    assert!(rows[1].contains(" never "));
+}
+
+#[cfg(unix)]
+#[test]
+fn a_reader_going_away_is_not_an_error() {
+    use std::{io::Read, process::Stdio};
+
+    use jump::{
+        server::{ConnectMethods, SSHKey, Server},
+        store::Store,
+    };
+
+    let home = store("ls-broken-pipe");
+    // More than a pipe holds, so jump is still writing when the reader goes
+    let store = Store::open(&home.join(".jump/servers.db")).unwrap();
+    store
+        .transaction(|| {
+            for i in 0..3000 {
+                let method = ConnectMethods::SSHKey(SSHKey {
+                    path: PathBuf::from("/keys/id"),
+                });
+                let server = Server::new(
+                    format!("server-{}", i),
+                    "deploy".to_owned(),
+                    "10.0.0.1".to_owned(),
+                    22,
+                    method,
+                );
+                store.add(&server)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+    let mut child = command(&home)
+        .arg("ls")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut start = [0; 16];
+    child.stdout.take().unwrap().read_exact(&mut start).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stderr.is_empty(), "{:?}", output);
+}
+
+#[test]
+fn servers_keep_the_order_they_are_moved_to() {
+    let home = store("ls-move");
+    jump(&home, &["add", "cache", "deploy", "10.0.0.3", "ssh-key"]);
+    let names = || {
+        jump(&home, &["ls", "--plain"])
+            .lines()
+            .map(|line| line.split('\t').next().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(), ["web", "db", "cache"]);
+    jump(&home, &["move", "cache", "--before", "web"]);
+    assert_eq!(names(), ["cache", "web", "db"]);
+    jump(&home, &["move", "cache", "--after", "web"]);
+    assert_eq!(names(), ["web", "cache", "db"]);
+    jump(&home, &["move", "web", "3"]);
+    assert_eq!(names(), ["cache", "db", "web"]);
+    jump(&home, &["rm", "--yes", "db"]);
+    jump(&home, &["add", "api", "deploy", "10.0.0.4", "ssh-key"]);
+    assert_eq!(names(), ["cache", "web", "api"]);
+    jump(&home, &["reorder", "--alphabetical"]);
+    assert_eq!(names(), ["api", "cache", "web"]);
+    assert!(!run(&home, &["move", "web", "--before", "db"])
+        .status
+        .success());
+}
+
+#[test]
+fn tables_cut_their_widest_columns_to_fit() {
+    let rows = vec![
+        ["web".to_owned(), "a rather long note".to_owned()],
+        ["db".to_owned(), "short".to_owned()],
+    ];
+    assert_eq!(
+        output::table(Style::plain(), ["NAME", "NOTE"], rows.clone(), None),
+        "NAME  NOTE\n\
+         web   a rather long note\n\
+         db    short\n"
+    );
+    assert_eq!(
+        output::table(Style::plain(), ["NAME", "NOTE"], rows.clone(), Some(15)),
+        "NAME  NOTE\n\
+         web   a rather…\n\
+         db    short\n"
+    );
+    // Never narrower than the header
+    assert_eq!(
+        output::table(Style::plain(), ["NAME", "NOTE"], rows, Some(1)),
+        "NAME  NOTE\n\
+         web   a r…\n\
+         db    sho…\n"
+    );
+}
+
+#[test]
+fn show_lists_the_settings_that_are_set() {
+    let mut server = Server::new(
+        "web".to_owned(),
+        "deploy".to_owned(),
+        "10.0.0.1".to_owned(),
+        2222,
+        ConnectMethods::SSHKey(SSHKey {
+            path: "/keys/web".into(),
+        }),
+    );
+    server.id = Some(1);
+    server.tags = vec!["prod".to_owned(), "eu".to_owned()];
+    server.expires_at = Some(0);
+    let shown = output::show(Style::plain(), &Config::default(), &server, None, 60);
+    assert_eq!(
+        shown.lines().collect::<Vec<_>>(),
+        [
+            "id:       1",
+            "name:     web",
+            "username: deploy",
+            "address:  10.0.0.1",
+            "port:     2222",
+            "method:   ssh key /keys/web",
+            &format!("x11:      {}", server.x11),
+            "tags:     prod, eu",
+            "expires:  1970-01-01 00:00:00 UTC (expired)",
+        ]
+    );
+}
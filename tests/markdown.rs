@@ -0,0 +1,51 @@
+mod common;
+
+use common::{home, jump, run};
+
+#[test]
+fn markdown_export_is_a_sorted_table_without_secrets() {
+    let home = home("markdown");
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &[
+            "add",
+            "web",
+            "deploy",
+            "10.0.0.1",
+            "--tag",
+            "prod",
+            "--tag",
+            "eu",
+            "--note",
+            "nginx | certbot",
+            "ssh-key",
+        ],
+    );
+    jump(
+        &home,
+        &[
+            "add", "db", "admin", "10.0.0.2", "2222", "password", "hunter2",
+        ],
+    );
+    assert_eq!(
+        jump(&home, &["export", "--markdown"]),
+        "| Name | User | Address | Port | Method | Tags | Notes |\n\
+         | --- | --- | --- | --- | --- | --- | --- |\n\
+         | db | admin | 10.0.0.2 | 2222 | password |  |  |\n\
+         | web | deploy | 10.0.0.1 | 22 | ssh-key | eu, prod | nginx \\| certbot |\n"
+    );
+    assert_eq!(
+        jump(
+            &home,
+            &["export", "--markdown", "--columns", "address,name", "web"]
+        ),
+        "| Address | Name |\n| --- | --- |\n| 10.0.0.1 | web |\n"
+    );
+
+    let output = run(&home, &["export", "--markdown", "--include-secrets"]);
+    assert!(!output.status.success());
+    assert!(!run(&home, &["export", "--columns", "name"])
+        .status
+        .success());
+}
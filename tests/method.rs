@@ -0,0 +1,115 @@
+mod common;
+
+use std::path::PathBuf;
+
+use jump::server::{ConnectMethods, Custom, Password, SSHKey, SshConfig};
+use rusqlite::Connection;
+use serde_json::json;
+
+use common::{home, jump, run};
+
+#[test]
+fn every_stored_format_reads_back_as_the_current_one() {
+    for (stored, current) in [
+        ("ssh:~/.ssh/id", "ssh:~/.ssh/id"),
+        ("pass:a:b", "pass:a:b"),
+        ("cmd:mosh web", "cmd:mosh web"),
+        ("config:web", "config:web"),
+        ("password:hunter2", "pass:hunter2"),
+        ("key:~/.ssh/id", "ssh:~/.ssh/id"),
+        ("custom:mosh web", "cmd:mosh web"),
+        ("~/.ssh/id", "ssh:~/.ssh/id"),
+        ("ssh:C:\\keys\\id", "ssh:C:\\keys\\id"),
+    ] {
+        let method = ConnectMethods::from(stored.to_owned());
+        assert!(method.is_supported(), "{}", stored);
+        assert_eq!(method.to_string(), current);
+        assert_eq!(
+            ConnectMethods::from(current.to_owned()).to_string(),
+            current
+        );
+    }
+}
+
+#[test]
+fn unknown_methods_are_kept_as_they_are() {
+    for stored in ["kerberos:EXAMPLE.COM", "hunter2", ""] {
+        let method = ConnectMethods::from(stored.to_owned());
+        assert!(!method.is_supported(), "{}", stored);
+        assert_eq!(method.to_string(), stored);
+    }
+
+    // Every known method survives an export, and a newer one does too
+    for method in [
+        ConnectMethods::SSHKey(SSHKey {
+            path: PathBuf::from("~/.ssh/id"),
+        }),
+        ConnectMethods::Password(Password {
+            password: "pw".to_owned(),
+        }),
+        ConnectMethods::Command(Custom {
+            command: "mosh web".to_owned(),
+        }),
+        ConnectMethods::SshConfig(SshConfig {
+            alias: "web".to_owned(),
+        }),
+    ] {
+        let exported = serde_json::to_value(&method).unwrap();
+        let imported = serde_json::from_value::<ConnectMethods>(exported.clone()).unwrap();
+        assert!(imported.is_supported());
+        assert_eq!(serde_json::to_value(&imported).unwrap(), exported);
+    }
+    let newer = json!({"Kerberos": {"realm": "EXAMPLE.COM"}});
+    let imported = serde_json::from_value::<ConnectMethods>(newer.clone()).unwrap();
+    assert!(!imported.is_supported());
+    assert_eq!(serde_json::to_value(&imported).unwrap(), newer);
+    // Stored, then read back
+    let stored = ConnectMethods::from(imported.to_string());
+    assert_eq!(serde_json::to_value(&stored).unwrap(), newer);
+}
+
+#[test]
+fn a_row_from_a_newer_jump_is_listed_but_not_connected_to() {
+    let home = home("method-newer");
+    jump(&home, &["init"]);
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    jump(&home, &["add", "kdc", "deploy", "10.0.0.2", "ssh-key"]);
+    Connection::open(home.join(".jump/servers.db"))
+        .unwrap()
+        .execute(
+            "UPDATE jump_servers SET method = 'kerberos:EXAMPLE.COM' WHERE server_name = 'kdc'",
+            [],
+        )
+        .unwrap();
+
+    assert_eq!(
+        jump(&home, &["ls", "--plain"]),
+        "web\tdeploy\t10.0.0.1\t22\tssh-key\nkdc\tdeploy\t10.0.0.2\t22\tunsupported\n"
+    );
+    let show = jump(&home, &["show", "kdc"]);
+    assert!(
+        show.contains("unsupported (added by newer jump?) kerberos:EXAMPLE.COM"),
+        "{}",
+        show
+    );
+
+    let output = run(&home, &["conn", "kdc"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("kdc uses a connection method this version of jump does not know"),
+        "{}",
+        stderr
+    );
+    // Changing something else keeps the method as it was
+    jump(&home, &["set", "port", "2222", "--filter", "kdc", "--yes"]);
+    let method: String = Connection::open(home.join(".jump/servers.db"))
+        .unwrap()
+        .query_row(
+            "SELECT method FROM jump_servers WHERE server_name = 'kdc'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(method, "kerberos:EXAMPLE.COM");
+}
@@ -0,0 +1,101 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf};
+
+use common::{command, home, jump};
+
+/// A store with a key and a password server, and an sshfs that logs its
+/// arguments and stdin without mounting anything
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &["add", "web", "deploy", "10.0.0.1", "2222", "ssh-key"],
+    );
+    jump(
+        &home,
+        &["add", "db", "admin", "fe80::1", "password", "hunter2"],
+    );
+    let sshfs = home.join("sshfs");
+    fs::write(
+        &sshfs,
+        format!(
+            "#!/bin/sh\necho \"$@\" > {log}\n[ -t 0 ] || cat >> {log}\n",
+            log = home.join("sshfs.log").display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&sshfs, fs::Permissions::from_mode(0o755)).unwrap();
+    home
+}
+
+#[test]
+fn sshfs_gets_the_stored_connection() {
+    let home = store("mount-args");
+    let mountpoint = home.join("mnt/web");
+    let dry_run = command(&home)
+        .args(["mount", "--dry-run", "web", "/srv/app"])
+        .arg(&mountpoint)
+        .env("JUMP_SSHFS_BIN", home.join("sshfs"))
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&dry_run.stdout),
+        format!(
+            "{} -p 2222 -o IdentityFile={} -o IdentitiesOnly=yes -- deploy@10.0.0.1:/srv/app {}\n",
+            home.join("sshfs").display(),
+            home.join(".ssh/id_rsa").display(),
+            mountpoint.display()
+        )
+    );
+    assert!(!mountpoint.exists());
+
+    let output = command(&home)
+        .args(["mount", "db", "/var/lib"])
+        .arg(home.join("mnt/db"))
+        .env("JUMP_SSHFS_BIN", home.join("sshfs"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let mountpoint = home.join("mnt/db").canonicalize().unwrap();
+    assert_eq!(
+        fs::read_to_string(home.join("sshfs.log")).unwrap(),
+        format!(
+            "-p 22 -o password_stdin -- admin@[fe80::1]:/var/lib {}\nhunter2\n",
+            mountpoint.display()
+        )
+    );
+}
+
+#[test]
+fn mounts_that_went_away_are_forgotten() {
+    let home = store("mount-stale");
+    let output = command(&home)
+        .args(["mount", "web", "/srv"])
+        .arg(home.join("mnt"))
+        .env("JUMP_SSHFS_BIN", home.join("sshfs"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    // The fake sshfs mounted nothing, so the record is stale at once
+    let list = command(&home).args(["mount", "--list"]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&list.stdout), "");
+    assert!(String::from_utf8_lossy(&list.stderr).contains("no longer mounted"));
+    let umount = command(&home).args(["umount", "web"]).output().unwrap();
+    assert!(String::from_utf8_lossy(&umount.stderr).contains("jump mounted nothing"));
+}
+
+#[test]
+fn a_missing_sshfs_says_how_to_get_it() {
+    let home = store("mount-missing");
+    let output = command(&home)
+        .args(["mount", "web", "/srv", "mnt"])
+        .env("PATH", home.join("empty"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(6));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("sshfs not found in PATH; install it"));
+}
@@ -0,0 +1,121 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use jump::multiplex;
+
+use common::{command, home, jump};
+
+/// A store with one shared server and an ssh reporting `version`, logging
+/// every other call to `ssh.log`
+fn store(test: &str, version: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &["add", "web", "deploy", "10.0.0.1", "--multiplex", "ssh-key"],
+    );
+    let ssh = home.join("ssh");
+    fs::write(
+        &ssh,
+        format!(
+            "#!/bin/sh\n[ \"$1\" = -V ] && echo '{}' >&2 && exit 0\necho \"$@\" >> {}\n",
+            version,
+            home.join("ssh.log").display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+    home
+}
+
+fn dry_run(home: &Path) -> String {
+    let output = command(home)
+        .args(["conn", "--dry-run", "web"])
+        .env("JUMP_SSH_BIN", home.join("ssh"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn versions_parse() {
+    assert_eq!(
+        multiplex::openssh_version("OpenSSH_9.6p1 Ubuntu-3ubuntu13, OpenSSL 3.0.13\n"),
+        Some((9, 6))
+    );
+    assert_eq!(multiplex::openssh_version("OpenSSH_5.3p1"), Some((5, 3)));
+    assert_eq!(multiplex::openssh_version("Dropbear v2022.83"), None);
+}
+
+#[test]
+fn shared_servers_get_a_control_socket() {
+    let home = store("multiplex-args", "OpenSSH_9.6p1, OpenSSL 3.0.13");
+    let line = dry_run(&home);
+    let dir = home.join(".jump/sockets");
+    assert!(line.contains("-o ControlMaster=auto -o ControlPersist=10m -o ControlPath="));
+    assert!(line.contains(&dir.display().to_string()));
+    let mode = fs::metadata(&dir).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o700);
+
+    let old = store("multiplex-old", "OpenSSH_5.3p1, OpenSSL 1.0.1e-fips");
+    assert!(!dry_run(&old).contains("Control"));
+}
+
+#[test]
+fn long_homes_put_sockets_in_tmp() {
+    let home = store(&format!("multiplex-{}", "long".repeat(20)), "OpenSSH_9.6p1");
+    let line = dry_run(&home);
+    let path = line
+        .split_whitespace()
+        .find_map(|arg| arg.strip_prefix("ControlPath="))
+        .unwrap();
+    assert!(path.starts_with("/tmp/jump-"), "{}", path);
+    assert!(path.len() < 104 - 17);
+}
+
+#[test]
+fn sessions_are_listed_and_closed() {
+    let home = store("multiplex-sessions", "OpenSSH_9.6p1");
+    let path = dry_run(&home)
+        .split_whitespace()
+        .find_map(|arg| arg.strip_prefix("ControlPath=").map(str::to_owned))
+        .unwrap();
+    // A master would have made it; ssh only has to answer `-O check`
+    fs::write(&path, "").unwrap();
+
+    let sessions = |args: &[&str]| {
+        let output = command(&home)
+            .arg("sessions")
+            .args(args)
+            .env("JUMP_SSH_BIN", home.join("ssh"))
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(
+        sessions(&["ls"]),
+        format!("web\tdeploy@10.0.0.1\t{}\n", path)
+    );
+    sessions(&["close", "web"]);
+    sessions(&["close", "--all"]);
+    let log = fs::read_to_string(home.join("ssh.log")).unwrap();
+    let calls = log.lines().collect::<Vec<_>>();
+    let control = format!("-o ControlPath={} -O", path);
+    assert_eq!(
+        calls,
+        [
+            format!("{} check -- deploy@10.0.0.1", control),
+            format!("{} exit -- deploy@10.0.0.1", control),
+            format!("{} exit -- deploy@10.0.0.1", control),
+        ]
+    );
+}
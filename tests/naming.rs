@@ -0,0 +1,90 @@
+mod common;
+
+use std::net::IpAddr;
+
+use jump::naming::{derive, free};
+
+use common::{home, jump};
+
+fn unknown(_: IpAddr) -> Option<String> {
+    None
+}
+
+#[test]
+fn hostnames_are_cut_to_their_first_label() {
+    assert_eq!(derive("web1.eu.example.com", unknown), "web1");
+    assert_eq!(derive("web1.example.com.", unknown), "web1");
+    assert_eq!(derive("nas", unknown), "nas");
+}
+
+#[test]
+fn ip_addresses_take_their_reverse_dns_name_if_any() {
+    assert_eq!(derive("10.0.0.7", unknown), "10.0.0.7");
+    let reverse = |ip: IpAddr| Some(format!("host-{}.lan", ip.to_string().replace('.', "-")));
+    assert_eq!(derive("10.0.0.7", reverse), "host-10-0-0-7");
+    // A PTR record that is an address again says nothing
+    assert_eq!(
+        derive("10.0.0.7", |_| Some("10.0.0.7".to_owned())),
+        "10.0.0.7"
+    );
+}
+
+#[test]
+fn ipv6_literals_keep_to_name_characters() {
+    assert_eq!(derive("fe80::1", unknown), "fe80--1");
+    assert_eq!(derive("[2001:db8::2]", unknown), "2001-db8--2");
+    assert_eq!(derive("fe80::1%eth0", unknown), "fe80--1-eth0");
+    assert_eq!(
+        derive("2001:db8::2", |_| Some("router.example.com".to_owned())),
+        "router"
+    );
+}
+
+#[test]
+fn taken_names_get_a_numeric_suffix() {
+    let taken = ["web", "web-2"];
+    let free = |base| free(base, |name| Ok(taken.contains(&name))).unwrap();
+    assert_eq!(free("web"), "web-3");
+    assert_eq!(free("db"), "db");
+}
+
+#[cfg(unix)]
+#[test]
+fn auto_name_leaves_out_the_name_positional() {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    let home = home("naming-auto");
+    jump(&home, &["initialize"]);
+    let getent = home.join("getent");
+    fs::write(
+        &getent,
+        "#!/bin/sh\n[ \"$2\" = 192.0.2.5 ] && echo \"$2 backup.lab.example.com backup\"\n",
+    )
+    .unwrap();
+    fs::set_permissions(&getent, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let add = |args: &[&str]| {
+        let output = common::command(&home)
+            .env("JUMP_GETENT_BIN", &getent)
+            .args(["add", "--auto-name"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stderr).unwrap()
+    };
+    assert!(add(&["deploy@web.example.com", "ssh-key"]).contains("adding the server as web\n"));
+    assert!(add(&["deploy@web.example.org:2222", "ssh-key"])
+        .contains("web is taken, adding the server as web-2\n"));
+    add(&["root@192.0.2.5", "ssh-key"]);
+    add(&["root@192.0.2.6"]);
+    assert_eq!(
+        jump(&home, &["ls", "--plain"]),
+        "web\tdeploy\tweb.example.com\t22\tssh-key\n\
+         web-2\tdeploy\tweb.example.org\t2222\tssh-key\n\
+         backup\troot\t192.0.2.5\t22\tssh-key\n\
+         192.0.2.6\troot\t192.0.2.6\t22\tssh-key\n"
+    );
+    let named = common::run(&home, &["add", "--auto-name", "root@192.0.2.7", "lab"]);
+    assert_eq!(named.status.code(), Some(2));
+}
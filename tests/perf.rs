@@ -0,0 +1,102 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use jump::store::Store;
+
+use common::{home, jump, run};
+
+const SERVERS: usize = 5_000;
+
+/// A store of `SERVERS` servers, each with a tag, an option and a fallback
+/// address, written in one transaction as adding them one by one would take
+/// minutes
+fn large_store(home: &Path) {
+    jump(home, &["initialize"]);
+    let conn = rusqlite::Connection::open(home.join(".jump/servers.db")).unwrap();
+    conn.execute_batch("BEGIN").unwrap();
+    for i in 0..SERVERS {
+        conn.execute(
+            "INSERT INTO jump_servers (server_name, username, server_address, port, method)
+             VALUES (?1, 'deploy', ?2, 22, 'ssh:~/.ssh/id_ed25519')",
+            (
+                format!("srv-{}", i),
+                format!("10.0.{}.{}", i / 256, i % 256),
+            ),
+        )
+        .unwrap();
+        let id = conn.last_insert_rowid();
+        conn.execute(
+            "INSERT INTO server_tags (server_id, tag) VALUES (?1, ?2)",
+            (id, format!("rack{}", i % 40)),
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO server_options (server_id, key, value) VALUES (?1, 'ServerAliveInterval', '30')",
+            [id],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO server_addresses (server_id, position, address, label)
+             VALUES (?1, 0, ?2, 'vpn')",
+            (id, format!("172.16.{}.{}", i / 256, i % 256)),
+        )
+        .unwrap();
+    }
+    conn.execute_batch("COMMIT").unwrap();
+}
+
+/// The fastest of `runs` runs of `task`, which leaves out the noise of a
+/// busy machine
+fn fastest(runs: usize, mut task: impl FnMut()) -> Duration {
+    (0..runs)
+        .map(|_| {
+            let started = Instant::now();
+            task();
+            started.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+#[test]
+fn resolving_in_a_large_store_takes_milliseconds() {
+    let home = home("perf-resolve");
+    fs::create_dir_all(&home).unwrap();
+    fs::write(home.join("ssh"), "").unwrap();
+    fs::write(
+        home.join("config.toml"),
+        format!("[binaries]\nssh = \"{}\"\n", home.join("ssh").display()),
+    )
+    .unwrap();
+    large_store(&home);
+
+    let store = Store::open(&home.join(".jump/servers.db")).unwrap();
+    let lookup = fastest(20, || {
+        let server = store.get("srv-4321").unwrap();
+        assert_eq!(server.tags, ["rack1"]);
+        assert_eq!(server.addresses[0].address, "172.16.16.225");
+    });
+    eprintln!("get: {:?}", lookup);
+    assert!(lookup < Duration::from_millis(5), "get took {:?}", lookup);
+
+    // The whole process, started from a prompt widget or a script
+    let resolve = fastest(5, || {
+        assert!(run(&home, &["resolve", "srv-4321"]).status.success());
+    });
+    eprintln!("jump resolve: {:?}", resolve);
+    assert!(
+        resolve < Duration::from_millis(100),
+        "jump resolve took {:?}",
+        resolve
+    );
+
+    let list = fastest(3, || assert_eq!(store.list().unwrap().len(), SERVERS));
+    eprintln!("list: {:?}", list);
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use common::{home, jump, run};
+
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    for (name, address) in [
+        ("web1", "10.0.0.1"),
+        ("web2", "10.0.0.2"),
+        ("db", "10.0.0.3"),
+    ] {
+        jump(&home, &["add", name, "deploy", address, "ssh-key"]);
+    }
+    fs::write(home.join("config.toml"), "prefix_match = true\n").unwrap();
+    home
+}
+
+#[test]
+fn a_unique_prefix_names_the_server() {
+    let home = store("prefix-unique");
+    let output = jump(&home, &["conn", "--dry-run", "d"]);
+    assert!(output.contains("deploy@10.0.0.3"));
+}
+
+#[test]
+fn an_ambiguous_prefix_lists_the_candidates_without_a_terminal() {
+    let home = store("prefix-ambiguous");
+    let output = run(&home, &["conn", "--dry-run", "web"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("web matches several servers: web1, web2")
+    );
+    assert_eq!(
+        run(&home, &["conn", "--dry-run", "x"]).status.code(),
+        Some(3)
+    );
+}
+
+#[test]
+fn prefixes_are_only_taken_when_enabled() {
+    let home = store("prefix-disabled");
+    fs::remove_file(home.join("config.toml")).unwrap();
+    assert_eq!(
+        run(&home, &["conn", "--dry-run", "d"]).status.code(),
+        Some(3)
+    );
+}
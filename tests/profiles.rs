@@ -0,0 +1,82 @@
+mod common;
+
+use std::fs;
+
+use jump::profiles;
+use serde_json::Value;
+
+use common::{home, jump, run};
+
+#[test]
+fn guids_and_quoting_follow_each_format() {
+    let guid = profiles::guid("web");
+    assert_eq!(guid, profiles::guid("web"));
+    assert_ne!(guid, profiles::guid("web-2"));
+    assert_eq!(guid.len(), 36);
+    assert_eq!(&guid[14..15], "8");
+
+    assert_eq!(profiles::windows_quote("web"), "web");
+    assert_eq!(profiles::windows_quote("my box"), "\"my box\"");
+    assert_eq!(profiles::windows_quote("a\"b"), "\"a\\\"b\"");
+    assert_eq!(profiles::windows_quote("a b\\"), "\"a b\\\\\"");
+    assert_eq!(profiles::windows_quote("a\\b c"), "\"a\\b c\"");
+}
+
+#[test]
+fn export_writes_terminal_profiles() {
+    let home = home("profiles");
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &[
+            "add",
+            "my \"web\"",
+            "deploy",
+            "10.0.0.1",
+            "--tag",
+            "prod",
+            "ssh-key",
+        ],
+    );
+    jump(&home, &["add", "db", "admin", "10.0.0.2", "ssh-key"]);
+    fs::write(
+        home.join("config.toml"),
+        "[tag_colors]\nprod = \"#ff0000\"\n",
+    )
+    .unwrap();
+
+    let terminal: Value =
+        serde_json::from_str(&jump(&home, &["export", "--windows-terminal"])).unwrap();
+    let web = &terminal["profiles"][0];
+    assert_eq!(web["name"], "my \"web\"");
+    assert_eq!(web["commandline"], "jump conn \"my \\\"web\\\"\"");
+    assert_eq!(web["guid"], format!("{{{}}}", profiles::guid("my \"web\"")));
+    assert_eq!(web["tabColor"], "#ff0000");
+    assert_eq!(terminal["profiles"][1]["commandline"], "jump conn db");
+    assert!(terminal["profiles"][1].get("tabColor").is_none());
+
+    let file = home.join("jump.json");
+    jump(
+        &home,
+        &[
+            "export",
+            "--iterm2",
+            "--out",
+            file.to_str().unwrap(),
+            "db",
+            "my \"web\"",
+        ],
+    );
+    let iterm: Value = serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+    assert_eq!(iterm["Profiles"][0]["Command"], "jump conn db");
+    let web = &iterm["Profiles"][1];
+    assert_eq!(web["Command"], "jump conn 'my \"web\"'");
+    assert_eq!(web["Guid"], profiles::guid("my \"web\""));
+    assert_eq!(web["Tab Color"]["Red Component"], 1.0);
+
+    let both = run(&home, &["export", "--iterm2", "--windows-terminal"]);
+    assert_eq!(both.status.code(), Some(2));
+    fs::write(home.join("config.toml"), "[tag_colors]\nprod = \"red\"\n").unwrap();
+    let invalid = run(&home, &["export", "--iterm2"]);
+    assert!(String::from_utf8_lossy(&invalid.stderr).contains("not a `#rrggbb` color"));
+}
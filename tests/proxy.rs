@@ -0,0 +1,179 @@
+mod common;
+
+use std::{env, path::PathBuf};
+
+use jump::{
+    config::Config,
+    proxy::{self, Kind, Proxy},
+    server::{ConnectMethods, SSHKey, Server},
+    ssh::{self, ConnOptions, ExecOptions},
+};
+
+use common::{command, home, jump};
+
+fn exe() -> PathBuf {
+    env::current_exe().unwrap()
+}
+
+fn config() -> Config {
+    let mut config = Config::default();
+    config.binaries.ssh = Some(exe());
+    config.binaries.nc = Some(exe());
+    config
+}
+
+fn server() -> Server {
+    Server::new(
+        "web".to_owned(),
+        "deploy".to_owned(),
+        "10.0.0.1".to_owned(),
+        22,
+        ConnectMethods::SSHKey(SSHKey {
+            path: PathBuf::from("/keys/web"),
+        }),
+    )
+}
+
+fn proxy_command(address: &str) -> String {
+    format!(
+        "ProxyCommand={} -X connect -x {} %h %p",
+        exe().display(),
+        address
+    )
+}
+
+#[test]
+fn parses_addresses_and_urls() {
+    let http = |address: &str| Proxy {
+        kind: Kind::Http,
+        address: address.to_owned(),
+    };
+    assert_eq!(proxy::parse("proxy:3128"), Ok(http("proxy:3128")));
+    assert_eq!(proxy::parse("http://proxy:3128/"), Ok(http("proxy:3128")));
+    assert_eq!(proxy::parse("HTTPS://proxy:443"), Ok(http("proxy:443")));
+    assert_eq!(
+        proxy::parse("socks5h://proxy:1080").unwrap().kind,
+        Kind::Socks5
+    );
+    assert_eq!(
+        proxy::parse_arg("http://proxy:3128"),
+        Ok("proxy:3128".to_owned())
+    );
+    for invalid in [
+        "proxy",
+        "proxy:http",
+        ":3128",
+        "ftp://proxy:21",
+        "http://u:p@proxy:3128",
+    ] {
+        assert!(proxy::parse(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn flag_wins_over_server_and_config() {
+    let mut config = config();
+    config.proxy = Some("global:3128".to_owned());
+    let mut server = server();
+    let invocation = ssh::exec(
+        &config,
+        &server,
+        &ConnOptions::default(),
+        &[],
+        ExecOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        invocation.args[2..4],
+        ["-o".to_owned(), proxy_command("global:3128")]
+    );
+
+    server.proxy = Some("own:8080".to_owned());
+    let invocation = ssh::exec(
+        &config,
+        &server,
+        &ConnOptions::default(),
+        &[],
+        ExecOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(invocation.args[3], proxy_command("own:8080"));
+
+    let options = ConnOptions {
+        proxy: Some(proxy::parse("once:80").unwrap()),
+        ..Default::default()
+    };
+    let invocation = ssh::exec(&config, &server, &options, &[], ExecOptions::default()).unwrap();
+    assert_eq!(invocation.args[3], proxy_command("once:80"));
+}
+
+#[test]
+fn stored_proxy_options_keep_the_config_out() {
+    let mut config = config();
+    config.proxy = Some("global:3128".to_owned());
+    let mut server = server();
+    server.options = vec![("ProxyJump".to_owned(), "bastion".to_owned())];
+    let invocation = ssh::exec(
+        &config,
+        &server,
+        &ConnOptions::default(),
+        &[],
+        ExecOptions::default(),
+    )
+    .unwrap();
+    assert!(!invocation
+        .args
+        .iter()
+        .any(|arg| arg.starts_with("ProxyCommand=")));
+}
+
+#[test]
+fn socks_proxies_use_their_protocol() {
+    let mut server = server();
+    server.proxy = Some("socks5://proxy:1080".to_owned());
+    let invocation = ssh::copy(
+        &config(),
+        &server,
+        &ConnOptions::default(),
+        "a",
+        "web:b",
+        false,
+    )
+    .unwrap();
+    assert_eq!(
+        invocation.args[3],
+        format!("ProxyCommand={} -X 5 -x proxy:1080 %h %p", exe().display())
+    );
+}
+
+#[test]
+fn environment_is_the_fallback() {
+    let home = home("proxy-env");
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &["add", "web", "deploy", "10.0.0.1", "22", "ssh-key"],
+    );
+    let dry_run = |vars: &[(&str, &str)]| {
+        let mut command = command(&home);
+        command
+            .args(["conn", "web", "--dry-run"])
+            .env("JUMP_SSH_BIN", exe())
+            .env("JUMP_NC_BIN", exe())
+            .env_remove("no_proxy")
+            .env_remove("NO_PROXY")
+            .envs(vars.iter().copied());
+        let output = command.output().unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert!(!dry_run(&[]).contains("ProxyCommand"));
+    let expected = format!("'{}'", proxy_command("proxy:8080"));
+    assert!(dry_run(&[("HTTPS_PROXY", "http://proxy:8080/")]).contains(&expected));
+    assert!(dry_run(&[("ALL_PROXY", "proxy:8080")]).contains(&expected));
+    let exempt = dry_run(&[
+        ("HTTPS_PROXY", "proxy:8080"),
+        ("NO_PROXY", "localhost,10.0.0.1"),
+    ]);
+    assert!(!exempt.contains("ProxyCommand"));
+}
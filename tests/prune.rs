@@ -0,0 +1,211 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    io::Write,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{Output, Stdio},
+};
+
+use rusqlite::Connection;
+
+use common::{command, home, jump};
+use jump::{
+    prune::{self, Pruning},
+    server::{ConnectMethods, SSHKey, Server},
+    store::Store,
+};
+
+/// A store with `up` answering on a listener kept open by the caller and
+/// `old` and `gone` on a port nothing listens on
+fn store(test: &str, listener: &TcpListener) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    let port = listener.local_addr().unwrap().port().to_string();
+    jump(
+        &home,
+        &["add", "up", "deploy", "127.0.0.1", &port, "ssh-key"],
+    );
+    let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+    let closed_port = closed.local_addr().unwrap().port().to_string();
+    drop(closed);
+    jump(
+        &home,
+        &[
+            "add",
+            "old",
+            "deploy",
+            "127.0.0.1",
+            &closed_port,
+            "--tag",
+            "lab",
+            "ssh-key",
+        ],
+    );
+    jump(
+        &home,
+        &[
+            "add",
+            "gone",
+            "deploy",
+            "127.0.0.1",
+            &closed_port,
+            "ssh-key",
+        ],
+    );
+    home
+}
+
+fn run(home: &Path, args: &[&str], typed: &str) -> Output {
+    let mut child = command(home)
+        .args(args)
+        .arg("--interactive")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(typed.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn trash(home: &Path) -> Vec<PathBuf> {
+    fs::read_dir(home.join(".jump/trash"))
+        .map(|entries| entries.map(|entry| entry.unwrap().path()).collect())
+        .unwrap_or_default()
+}
+
+#[test]
+fn unreachable_servers_are_kept_archived_or_deleted_one_by_one() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let home = store("prune-unreachable", &listener);
+
+    let listed = jump(
+        &home,
+        &["--color", "never", "prune", "--unreachable", "--dry-run"],
+    );
+    assert!(listed.contains("old  deploy@127.0.0.1:"));
+    assert!(listed.contains("  last connected: never\n  tags: lab\n"));
+    assert!(listed.contains("gone  deploy@127.0.0.1:"));
+    assert!(!listed.contains("up  "));
+
+    let output = run(&home, &["prune", "--unreachable"], "a\nd\n");
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("tagged 1 servers archived"));
+    assert!(stderr.contains("removed 1 servers; `jump import "));
+    assert_eq!(jump(&home, &["ls", "--plain"]).lines().count(), 2);
+    assert_eq!(jump(&home, &["tag", "ls", "old"]), "archived\nlab\n");
+
+    let trash = trash(&home);
+    assert_eq!(trash.len(), 1);
+    assert!(fs::read_to_string(&trash[0]).unwrap().contains("\"gone\""));
+    jump(&home, &["import", trash[0].to_str().unwrap(), "--yes"]);
+    assert_eq!(jump(&home, &["ls", "--plain"]).lines().count(), 3);
+
+    // Archived servers are passed over from then on
+    let output = run(&home, &["prune", "--unreachable"], "\n");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("gone  ") && !stdout.contains("old  "));
+    assert_eq!(jump(&home, &["ls", "--plain"]).lines().count(), 3);
+}
+
+#[test]
+fn scripted_prune_skips_servers_connected_to_lately() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let home = store("prune-scripted", &listener);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    let conn = Connection::open(home.join(".jump/servers.db")).unwrap();
+    conn.execute(
+        "insert into connection_log (server_name, local_user, command, method, started_at,
+            ended_at, exit_code) values ('old', 'me', 'conn', 'ssh-key', ?1, ?1, 0)",
+        [now - 86_400],
+    )
+    .unwrap();
+    drop(conn);
+
+    let refused = run(&home, &["prune", "--unreachable", "--delete"], "\n");
+    assert!(refused.status.success());
+    assert_eq!(jump(&home, &["ls", "--plain"]).lines().count(), 3);
+
+    jump(
+        &home,
+        &["prune", "--unreachable-for", "90d", "--delete", "--yes"],
+    );
+    assert_eq!(
+        jump(&home, &["ls", "--plain"])
+            .lines()
+            .map(|line| line.split('\t').next().unwrap())
+            .collect::<Vec<_>>(),
+        ["up", "old"]
+    );
+    assert_eq!(trash(&home).len(), 1);
+}
+
+#[test]
+fn archived_and_recently_reached_servers_are_not_probed() {
+    let server = |name: &str, tags: &[&str]| {
+        let mut server = Server::new(
+            name.to_owned(),
+            "deploy".to_owned(),
+            "10.0.0.1".to_owned(),
+            22,
+            ConnectMethods::SSHKey(SSHKey {
+                path: "/keys/k".into(),
+            }),
+        );
+        server.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        server
+    };
+    let servers = vec![
+        server("old", &[]),
+        server("recent", &[]),
+        server("archived", &[prune::ARCHIVED]),
+        server("never", &[]),
+    ];
+    let last = [("old".to_owned(), 100), ("recent".to_owned(), 950)].into();
+    let names = |servers: Vec<Server>| {
+        servers
+            .into_iter()
+            .map(|server| server.server_name)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        names(prune::candidates(servers.clone(), &last, None, 1_000)),
+        ["old", "recent", "never"]
+    );
+    assert_eq!(
+        names(prune::candidates(servers.clone(), &last, Some(100), 1_000)),
+        ["old", "never"]
+    );
+
+    let store = Store::open_in_memory().unwrap();
+    store.initialize().unwrap();
+    for server in &servers {
+        store.add(server).unwrap();
+    }
+    let done = prune::apply(
+        &store,
+        "default",
+        vec![
+            (servers[0].clone(), Pruning::Archive),
+            (servers[3].clone(), Pruning::Keep),
+        ],
+    )
+    .unwrap();
+    assert_eq!(done.archived, 1);
+    assert_eq!(done.trash, None);
+    assert_eq!(store.get("old").unwrap().tags, [prune::ARCHIVED]);
+    assert!(store.get("never").unwrap().tags.is_empty());
+}
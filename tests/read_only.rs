@@ -0,0 +1,45 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt};
+
+use common::{command, home, jump, run};
+
+#[test]
+fn nothing_is_written() {
+    let home = home("read-only");
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &["add", "web", "deploy", "10.0.0.1", "22", "ssh-key"],
+    );
+    let ssh = home.join("ssh");
+    fs::write(&ssh, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+    let db = home.join(".jump").join("servers.db");
+    let before = fs::read(&db).unwrap();
+
+    for args in [
+        &["add", "db", "admin", "10.0.0.2", "ssh-key"][..],
+        &["rm", "web"],
+        &["option", "set", "web", "Compression", "yes"],
+    ] {
+        let output = run(&home, &[&["--read-only"], args].concat());
+        assert_eq!(output.status.code(), Some(5), "{:?}", output);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Store is read-only"));
+    }
+    assert!(jump(&home, &["--read-only", "ls", "--plain"]).starts_with("web\t"));
+    let output = command(&home)
+        .args(["--read-only", "conn", "web"])
+        .env("JUMP_SSH_BIN", &ssh)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fs::read(&db).unwrap(), before);
+
+    // The config setting works like the flag
+    fs::write(home.join("config.toml"), "readonly = true\n").unwrap();
+    assert_eq!(run(&home, &["rm", "--yes", "web"]).status.code(), Some(5));
+    assert_eq!(jump(&home, &["log", "--output", "json"]), "[]\n");
+}
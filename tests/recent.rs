@@ -0,0 +1,68 @@
+mod common;
+
+use jump::audit::format_ago;
+use rusqlite::Connection;
+
+use common::{home, jump};
+
+#[test]
+fn ages_are_given_in_their_largest_unit() {
+    assert_eq!(format_ago(1_000, 1_030), "just now");
+    assert_eq!(format_ago(1_000, 1_000 + 5 * 60 + 59), "5m ago");
+    assert_eq!(format_ago(0, 3 * 3_600), "3h ago");
+    assert_eq!(format_ago(0, 2 * 86_400), "2d ago");
+    assert_eq!(format_ago(0, 15 * 86_400), "2w ago");
+    assert_eq!(format_ago(100, 0), "just now");
+}
+
+#[test]
+fn recent_lists_distinct_servers_and_connects_by_number() {
+    let home = home("recent");
+    jump(&home, &["initialize"]);
+    for (name, address) in [("web", "10.0.0.1"), ("db", "10.0.0.2"), ("old", "10.0.0.3")] {
+        jump(&home, &["add", name, "deploy", address, "ssh-key"]);
+    }
+    assert_eq!(jump(&home, &["recent"]), "");
+    assert_eq!(jump(&home, &["--output", "json", "recent"]), "[]\n");
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    let conn = Connection::open(home.join(".jump/servers.db")).unwrap();
+    for (name, ago) in [
+        ("web", 7_200),
+        ("db", 600),
+        ("web", 120),
+        ("old", 30),
+        ("gone", 10),
+    ] {
+        conn.execute(
+            "insert into connection_log (server_name, local_user, command, method, started_at)
+             values (?1, 'me', 'conn', 'ssh-key', ?2)",
+            rusqlite::params![name, now - ago],
+        )
+        .unwrap();
+    }
+    drop(conn);
+    jump(&home, &["rm", "old"]);
+
+    assert_eq!(
+        jump(&home, &["--color", "never", "recent"]),
+        "1  web  2m ago\n2  db   10m ago\n"
+    );
+    assert_eq!(
+        jump(&home, &["--color", "never", "recent", "1"]),
+        "1  web  2m ago\n"
+    );
+    let json: serde_json::Value =
+        serde_json::from_str(&jump(&home, &["--output", "json", "recent"])).unwrap();
+    assert_eq!(json[1]["number"], 2);
+    assert_eq!(json[1]["server_name"], "db");
+    assert_eq!(json[1]["connected_at"], now - 600);
+
+    let dry_run = jump(&home, &["recent", "--conn", "2", "--dry-run"]);
+    assert!(dry_run.contains("deploy@10.0.0.2"));
+    let missing = common::run(&home, &["recent", "--conn", "3"]);
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("there is no recent server 3"));
+}
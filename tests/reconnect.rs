@@ -0,0 +1,193 @@
+mod common;
+
+use std::time::Duration;
+
+use jump::{
+    config,
+    reconnect::{Next, Reconnect},
+};
+
+const DROPPED: Option<i32> = Some(255);
+
+fn reconnect(max_retries: u32) -> Reconnect {
+    Reconnect::new(&config::Persist {
+        backoff: 2,
+        max_retries,
+        established: 10,
+    })
+}
+
+fn secs(secs: u64) -> Duration {
+    Duration::from_secs(secs)
+}
+
+#[test]
+fn only_a_connection_that_lasted_and_failed_is_resumed() {
+    // A logout, the remote command's own code, a signal
+    for code in [Some(0), Some(1), Some(130), None] {
+        assert_eq!(reconnect(10).after(code, secs(3600)), Next::Stop);
+    }
+    // Never got up, as with a wrong address
+    assert_eq!(reconnect(10).after(DROPPED, secs(1)), Next::Stop);
+
+    let mut session = reconnect(10);
+    assert_eq!(session.after(DROPPED, secs(60)), Next::Reconnect(secs(2)));
+    assert_eq!(session.reconnects, 1);
+    // A clean exit after reconnecting still ends it
+    assert_eq!(session.after(Some(0), secs(60)), Next::Stop);
+}
+
+#[test]
+fn failed_attempts_back_off_until_the_limit() {
+    let mut session = reconnect(3);
+    assert_eq!(session.after(DROPPED, secs(60)), Next::Reconnect(secs(2)));
+    assert_eq!(session.after(DROPPED, secs(1)), Next::Reconnect(secs(4)));
+    assert_eq!(session.after(DROPPED, secs(1)), Next::Reconnect(secs(8)));
+    assert_eq!(session.failures(), (2, 3));
+    assert_eq!(session.after(DROPPED, secs(1)), Next::Stop);
+
+    // A connection that lasted starts the count again
+    let mut session = reconnect(2);
+    session.after(DROPPED, secs(60));
+    session.after(DROPPED, secs(1));
+    assert_eq!(session.after(DROPPED, secs(60)), Next::Reconnect(secs(2)));
+    assert_eq!(session.failures(), (0, 2));
+
+    // Without a limit, waits stop growing at a minute
+    let mut session = reconnect(0);
+    session.after(DROPPED, secs(60));
+    for _ in 0..40 {
+        assert!(
+            matches!(session.after(DROPPED, secs(1)), Next::Reconnect(wait) if wait <= secs(60))
+        );
+    }
+    assert_eq!(session.after(DROPPED, secs(1)), Next::Reconnect(secs(60)));
+}
+
+#[cfg(unix)]
+mod conn {
+    use std::{
+        fs,
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+        process::Output,
+    };
+
+    use super::common::{command, home, jump};
+
+    /// A store with one server, a config that reconnects at once and
+    /// counts a second as a connection that lasted, and an ssh that ends
+    /// its runs as `endings` say: `drop` after a second, `fail` at once
+    /// or `logout`
+    fn store(test: &str, endings: &[&str], max_retries: u32) -> PathBuf {
+        let home = home(test);
+        jump(&home, &["init"]);
+        jump(
+            &home,
+            &["add", "web", "deploy", "10.0.0.1", "password", "pw"],
+        );
+        fs::write(
+            home.join("config.toml"),
+            format!("track_keys = false\n\n[persist]\nbackoff = 0\nmax_retries = {max_retries}\nestablished = 1\n"),
+        )
+        .unwrap();
+        let cases = endings
+            .iter()
+            .enumerate()
+            .map(|(i, ending)| {
+                let exit = match *ending {
+                    "drop" => "sleep 1; exit 255",
+                    "fail" => "exit 255",
+                    _ => "exit 0",
+                };
+                format!("{}) {} ;;\n", i + 1, exit)
+            })
+            .collect::<String>();
+        fs::write(
+            home.join("ssh"),
+            format!(
+                "#!/bin/sh\n\
+                 echo x >> {runs}\n\
+                 case $(wc -l < {runs}) in\n{cases}*) exit 255 ;;\nesac\n",
+                runs = home.join("runs").display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(home.join("ssh"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(home.join("sshpass"), "#!/bin/sh\nshift\nexec \"$@\"\n").unwrap();
+        fs::set_permissions(home.join("sshpass"), fs::Permissions::from_mode(0o755)).unwrap();
+        home
+    }
+
+    fn persist(home: &Path) -> (Output, usize) {
+        let output = command(home)
+            .args(["conn", "web", "--persist"])
+            .env("JUMP_SSH_BIN", home.join("ssh"))
+            .env("JUMP_SSHPASS_BIN", home.join("sshpass"))
+            .env("JUMP_SSH_KEYSCAN_BIN", home.join("no-keyscan"))
+            .output()
+            .unwrap();
+        let runs = fs::read_to_string(home.join("runs"))
+            .unwrap()
+            .lines()
+            .count();
+        (output, runs)
+    }
+
+    #[test]
+    fn a_dropped_connection_comes_back_until_the_logout() {
+        let home = store("reconnect-dropped", &["drop", "fail", "logout"], 10);
+        let (output, runs) = persist(&home);
+        assert!(output.status.success(), "{:?}", output);
+        assert_eq!(runs, 3);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains(
+                "connection to web lost, reconnecting in 0s, attempt 1 of 10; Ctrl-C twice to stop"
+            ),
+            "{}",
+            stderr
+        );
+        assert!(
+            stderr.contains("reconnecting to web (reconnect 1)"),
+            "{}",
+            stderr
+        );
+        assert!(stderr.contains("attempt 2 of 10"), "{}", stderr);
+        assert!(
+            stderr.contains("reconnecting to web (reconnect 2)"),
+            "{}",
+            stderr
+        );
+        // One entry for the whole session
+        let log = jump(&home, &["log", "--output", "json"]);
+        let entries = serde_json::from_str::<serde_json::Value>(&log).unwrap();
+        assert_eq!(entries.as_array().unwrap().len(), 1);
+        assert_eq!(entries[0]["exit_code"], 0);
+    }
+
+    #[test]
+    fn a_logout_or_a_first_failure_is_final() {
+        let home = store("reconnect-logout", &["logout"], 10);
+        let (output, runs) = persist(&home);
+        assert!(output.status.success());
+        assert_eq!(runs, 1);
+
+        let home = store("reconnect-unreachable", &["fail"], 10);
+        let (output, runs) = persist(&home);
+        assert_eq!(output.status.code(), Some(255));
+        assert_eq!(runs, 1);
+    }
+
+    #[test]
+    fn it_gives_up_after_max_retries() {
+        let home = store("reconnect-give-up", &["drop"], 2);
+        let (output, runs) = persist(&home);
+        assert_eq!(output.status.code(), Some(255));
+        // The first connection and two failed attempts
+        assert_eq!(runs, 3);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("attempt 2 of 2"), "{}", stderr);
+        assert!(!stderr.contains("attempt 3"), "{}", stderr);
+    }
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use jump::{
+    config::Config,
+    record::{self, RecordFormat},
+    ssh::Invocation,
+};
+
+use common::home;
+
+fn invocation(secret: Option<usize>) -> Invocation {
+    Invocation {
+        program: PathBuf::from("/usr/bin/sshpass"),
+        args: ["-p", "hunter2", "/usr/bin/ssh", "deploy@10.0.0.1"]
+            .map(str::to_owned)
+            .to_vec(),
+        secret,
+        env: vec![("SSH_ASKPASS".to_owned(), "/bin/jump".to_owned())],
+    }
+}
+
+fn config() -> Config {
+    let exe = std::env::current_exe().unwrap();
+    let mut config = Config::default();
+    config.binaries.script = Some(exe.clone());
+    config.binaries.asciinema = Some(exe);
+    config
+}
+
+#[test]
+fn default_paths() {
+    let path = record::default_path("web", RecordFormat::Script, 1_709_285_400).unwrap();
+    assert!(path.ends_with(Path::new("recordings").join("web-20240301-093000.log")));
+    let path = record::default_path("web", RecordFormat::Asciinema, 0).unwrap();
+    assert!(path.ends_with("web-19700101-000000.cast"));
+}
+
+#[test]
+fn asciinema_gets_the_command_as_a_string() {
+    let wrapped = record::wrap(
+        &config(),
+        invocation(Some(1)),
+        RecordFormat::Asciinema,
+        Path::new("/tmp/web.cast"),
+    )
+    .unwrap();
+    assert_eq!(
+        wrapped.args,
+        [
+            "rec",
+            "-q",
+            "-c",
+            "/usr/bin/sshpass -p hunter2 /usr/bin/ssh deploy@10.0.0.1",
+            "/tmp/web.cast"
+        ]
+    );
+    assert_eq!(wrapped.env, invocation(None).env);
+    assert!(!wrapped.to_string().contains("hunter2"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn script_keeps_the_exit_code() {
+    let wrapped = record::wrap(
+        &config(),
+        invocation(None),
+        RecordFormat::Script,
+        Path::new("/tmp/web.log"),
+    )
+    .unwrap();
+    assert_eq!(wrapped.args[..3], ["-q", "-e", "-c"]);
+    assert_eq!(wrapped.secret, None);
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn script_gets_the_command_as_arguments() {
+    let wrapped = record::wrap(
+        &config(),
+        invocation(Some(1)),
+        RecordFormat::Script,
+        Path::new("/tmp/web.log"),
+    )
+    .unwrap();
+    assert_eq!(
+        wrapped.args[..3],
+        ["-q", "/tmp/web.log", "/usr/bin/sshpass"]
+    );
+    assert_eq!(wrapped.args[wrapped.secret.unwrap()], "hunter2");
+}
+
+#[test]
+fn reconnecting_appends_to_the_recording() {
+    let mut wrapped = record::wrap(
+        &config(),
+        invocation(Some(1)),
+        RecordFormat::Asciinema,
+        Path::new("/tmp/web.cast"),
+    )
+    .unwrap();
+    record::append(&mut wrapped, RecordFormat::Asciinema);
+    assert_eq!(wrapped.args[..3], ["rec", "--append", "-q"]);
+    assert!(!wrapped.to_string().contains("hunter2"));
+
+    let mut script = invocation(Some(1));
+    record::append(&mut script, RecordFormat::Script);
+    assert_eq!(script.args[..2], ["-a", "-p"]);
+    assert_eq!(script.args[script.secret.unwrap()], "hunter2");
+}
+
+#[test]
+fn no_recordings_yet() {
+    let home = home("record-none");
+    let output = common::run(&home, &["recordings", "ls", "--output", "json"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "[]\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn recorded_sessions_land_in_the_recordings_dir() {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    let home = home("record-conn");
+    common::jump(&home, &["initialize"]);
+    common::jump(
+        &home,
+        &[
+            "add",
+            "web",
+            "deploy",
+            "10.0.0.1",
+            "22",
+            "--always-record",
+            "ssh-key",
+        ],
+    );
+    // Stands in for both ssh and script: writes the last argument, the
+    // recording, and exits like the session did
+    let fake = home.join("fake");
+    fs::write(
+        &fake,
+        "#!/bin/sh\nfor last; do :; done\necho session > \"$last\"\nexit 5\n",
+    )
+    .unwrap();
+    fs::set_permissions(&fake, fs::Permissions::from_mode(0o755)).unwrap();
+    let output = common::command(&home)
+        .args(["conn", "web"])
+        .env("JUMP_SSH_BIN", &fake)
+        .env("JUMP_SCRIPT_BIN", &fake)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(5));
+    let recordings = fs::read_dir(home.join(".jump").join("recordings"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    assert_eq!(recordings.len(), 1);
+    assert_eq!(fs::read_to_string(&recordings[0]).unwrap(), "session\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&format!("recording saved to {}", recordings[0].display())));
+}
@@ -0,0 +1,233 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Output,
+};
+
+use common::{command, home, jump};
+use jump::{
+    config::Config,
+    rekey::{self, Rekeyed, Steps},
+    ssh::ConnOptions,
+    store::Store,
+};
+
+const NEW_KEY: &str =
+    "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIJ+wWWIdVx2OTsIpf98KBu+DaiQfrW/cytXoE/09ztm4 deploy@ci";
+
+/// A store with `servers` on the key `old` and `other` on another, the key
+/// `new` with its public key, and a fake ssh that appends its arguments to
+/// `calls` and runs the remote command here with `remote` as its home; the
+/// server at 10.0.0.3 turns every login down
+fn store(test: &str, servers: &[(&str, &str)]) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["init"]);
+    for (name, address) in servers {
+        let old = home.join("old");
+        jump(
+            &home,
+            &[
+                "add",
+                name,
+                "deploy",
+                address,
+                "ssh-key",
+                old.to_str().unwrap(),
+            ],
+        );
+    }
+    jump(
+        &home,
+        &[
+            "add",
+            "other",
+            "deploy",
+            "10.0.0.9",
+            "ssh-key",
+            "/keys/other",
+        ],
+    );
+    fs::write(home.join("old"), "").unwrap();
+    fs::write(home.join("new"), "").unwrap();
+    fs::write(home.join("new.pub"), format!("{}\n", NEW_KEY)).unwrap();
+    let remote = home.join("remote");
+    fs::create_dir_all(remote.join(".ssh")).unwrap();
+    // Without a newline at the end, which the new key must not be glued to
+    fs::write(
+        remote.join(".ssh/authorized_keys"),
+        "ssh-ed25519 AAAAold deploy@laptop",
+    )
+    .unwrap();
+    let ssh = home.join("ssh");
+    fs::write(
+        &ssh,
+        format!(
+            "#!/bin/sh\n\
+             echo \"ssh $*\" >> {calls}\n\
+             case \"$*\" in *10.0.0.3*) echo 'Permission denied (publickey).' >&2; exit 255;; esac\n\
+             for last; do :; done\n\
+             HOME={remote} exec sh -c \"$last\"\n",
+            calls = home.join("calls").display(),
+            remote = remote.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+    home
+}
+
+fn rekey(home: &Path, extra: &[&str]) -> Output {
+    let old = home.join("old");
+    let new = home.join("new");
+    command(home)
+        .args([
+            "--color",
+            "never",
+            "rekey",
+            "--from",
+            old.to_str().unwrap(),
+            "--to",
+            new.to_str().unwrap(),
+        ])
+        .args(extra)
+        .env("JUMP_SSH_BIN", home.join("ssh"))
+        .output()
+        .unwrap()
+}
+
+fn key_of(home: &Path, name: &str) -> String {
+    jump(home, &["show", name])
+        .lines()
+        .find_map(|line| line.strip_prefix("method:   ssh key "))
+        .unwrap()
+        .to_owned()
+}
+
+#[test]
+fn servers_on_the_old_key_move_to_the_new_one() {
+    let home = store("rekey-moved", &[("web", "10.0.0.1"), ("db", "10.0.0.2")]);
+    let output = rekey(&home, &[]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("[1/2] web  ok"), "{}", stdout);
+    assert!(stdout.contains("[2/2] db   ok"), "{}", stdout);
+
+    // Added once, on a line of its own
+    assert_eq!(
+        fs::read_to_string(home.join("remote/.ssh/authorized_keys")).unwrap(),
+        format!("ssh-ed25519 AAAAold deploy@laptop\n{}\n", NEW_KEY)
+    );
+    let new = home.join("new").display().to_string();
+    assert_eq!(key_of(&home, "web"), new);
+    assert_eq!(key_of(&home, "db"), new);
+    assert_eq!(key_of(&home, "other"), "/keys/other");
+
+    // The login is tried with the new key alone
+    let calls = fs::read_to_string(home.join("calls")).unwrap();
+    let tries = calls
+        .lines()
+        .filter(|line| line.ends_with(" true"))
+        .collect::<Vec<_>>();
+    assert_eq!(tries.len(), 2, "{}", calls);
+    assert!(tries[0].contains(&format!("-i {}", new)), "{}", tries[0]);
+    assert!(tries[0].contains("PreferredAuthentications=publickey"));
+
+    let output = rekey(&home, &[]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("no server logs in with"));
+}
+
+#[test]
+fn a_failure_leaves_the_rest_on_the_old_key() {
+    let servers = [("a", "10.0.0.1"), ("b", "10.0.0.3"), ("c", "10.0.0.2")];
+    let home = store("rekey-failed", &servers);
+    let old = home.join("old").display().to_string();
+    let output = rekey(&home, &["--yes"]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("[1/3] a  ok"), "{}", stdout);
+    assert!(
+        stdout
+            .contains("[2/3] b  failed\ncould not add the new key: Permission denied (publickey)."),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("c  skipped"), "{}", stdout);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!("still on {}: b, c", old)),
+        "{}",
+        stderr
+    );
+    assert_eq!(key_of(&home, "b"), old);
+    assert_eq!(key_of(&home, "c"), old);
+
+    let output = rekey(&home, &["--continue-on-error", "--yes"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!("still on {}: b\n", old)),
+        "{}",
+        stderr
+    );
+    assert_eq!(key_of(&home, "c"), home.join("new").display().to_string());
+}
+
+#[test]
+fn a_selector_narrows_the_servers() {
+    let home = store("rekey-selector", &[("web", "10.0.0.1"), ("db", "10.0.0.2")]);
+    let output = rekey(&home, &["db"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(key_of(&home, "web"), home.join("old").display().to_string());
+    assert_eq!(key_of(&home, "db"), home.join("new").display().to_string());
+
+    // Nothing is run or changed on a dry run
+    let output = rekey(&home, &["--dry-run"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 2);
+    assert_eq!(key_of(&home, "web"), home.join("old").display().to_string());
+}
+
+#[test]
+fn each_server_is_handed_over_moved_failed_or_skipped() {
+    let servers = [("a", "10.0.0.1"), ("b", "10.0.0.3"), ("c", "10.0.0.2")];
+    let home = store("rekey-library", &servers);
+    let store = Store::open(&home.join(".jump/servers.db")).unwrap();
+    let (using, included) = rekey::candidates(store.list().unwrap(), &home.join("old")).unwrap();
+    assert!(included.is_empty());
+    let mut config = Config::default();
+    config.binaries.ssh = Some(home.join("ssh"));
+    let mut rekeyed = vec![];
+    rekey::run(
+        &store,
+        &config,
+        &using,
+        &ConnOptions::default(),
+        &Steps::new(&home.join("new")).unwrap(),
+        false,
+        vec![None; using.len()],
+        |server, result| rekeyed.push((server.server_name.clone(), result)),
+    );
+    assert_eq!(
+        rekeyed,
+        [
+            ("a".to_owned(), Rekeyed::Moved),
+            (
+                "b".to_owned(),
+                Rekeyed::Failed(
+                    "could not add the new key: Permission denied (publickey).".to_owned()
+                )
+            ),
+            ("c".to_owned(), Rekeyed::Skipped),
+        ]
+    );
+    assert_eq!(key_of(&home, "a"), home.join("new").display().to_string());
+    assert_eq!(key_of(&home, "c"), home.join("old").display().to_string());
+}
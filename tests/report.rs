@@ -0,0 +1,177 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt, path::Path, time::Duration};
+
+use jump::{
+    report::{self, Host, Outcome},
+    style::Style,
+};
+use serde_json::{json, Value};
+
+use common::{command, home, jump};
+
+#[test]
+fn the_summary_puts_failures_first() {
+    assert_eq!(report::tail("a\n\nb\nc  \n", 2), ["b", "c"]);
+    let hosts = [
+        Host::finished("web1", Duration::from_millis(1200), Some(0), "", 5),
+        Host::new("web2", Outcome::Skipped, Duration::ZERO),
+        Host::finished("db", Duration::from_millis(300), Some(2), "boom\n", 5),
+        Host::not_started("cache", "unable to run ssh".to_owned()),
+    ];
+    assert_eq!(hosts[2].stderr_tail, ["boom"]);
+    assert!(hosts[0].stderr_tail.is_empty());
+    assert_eq!(
+        report::summary(Style::plain(), &hosts),
+        "cache  not started  -         0.0s\n\
+         db     failed       exit 2    0.3s\n\
+         web2   skipped      -         0.0s\n\
+         web1   ok           exit 0    1.2s\n"
+    );
+}
+
+fn script(home: &Path, name: &str, body: &str) {
+    let path = home.join(name);
+    fs::write(&path, format!("#!/bin/sh\n{}", body)).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+fn read(path: &Path) -> Value {
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn exec_reports_every_host() {
+    let home = home("report-exec");
+    jump(&home, &["initialize"]);
+    for (name, address) in [
+        ("web1", "10.0.0.1"),
+        ("web2", "10.0.0.2"),
+        ("web3", "10.0.0.3"),
+    ] {
+        jump(&home, &["add", name, "deploy", address, "ssh-key"]);
+    }
+    jump(
+        &home,
+        &["add", "db", "admin", "10.0.0.4", "password", "hunter2"],
+    );
+    script(
+        &home,
+        "ssh",
+        "case \"$*\" in\n\
+         *10.0.0.2*) printf 'one\\ntwo\\nthree\\n' >&2; exit 3 ;;\n\
+         *10.0.0.3*) echo 'ssh: Could not resolve hostname web3' >&2; exit 255 ;;\n\
+         esac\n",
+    );
+    let file = home.join("report.json");
+    let output = command(&home)
+        .args(["--color", "never", "exec", "--on", "name:*", "--yes"])
+        .args(["--report", file.to_str().unwrap(), "--report-lines", "2"])
+        .args(["--", "uptime"])
+        .env("JUMP_SSH_BIN", home.join("ssh"))
+        .env("JUMP_SSHPASS_BIN", home.join("missing"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let summary = stderr.lines().rev().take(4).collect::<Vec<_>>();
+    assert!(summary[3].starts_with("db    not started"), "{}", stderr);
+    assert!(summary[2].starts_with("web3  unreachable  exit 255"));
+    assert!(summary[1].starts_with("web2  failed       exit 3"));
+    assert!(summary[0].starts_with("web1  ok           exit 0"));
+
+    let report = read(&file);
+    assert_eq!(report["version"], 1);
+    assert_eq!(report["command"], "exec");
+    let hosts = report["hosts"].as_array().unwrap();
+    let field = |field: &str| {
+        hosts
+            .iter()
+            .map(|host| host[field].clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        field("server_name"),
+        [json!("web1"), json!("web2"), json!("web3"), json!("db")]
+    );
+    assert_eq!(
+        field("outcome"),
+        [
+            json!("ok"),
+            json!("failed"),
+            json!("unreachable"),
+            json!("not-started")
+        ]
+    );
+    assert_eq!(
+        field("exit_code"),
+        [json!(0), json!(3), json!(255), Value::Null]
+    );
+    assert_eq!(hosts[1]["stderr_tail"], json!(["two", "three"]));
+    assert_eq!(
+        hosts[2]["stderr_tail"],
+        json!(["ssh: Could not resolve hostname web3"])
+    );
+    assert!(hosts[3]["error"].as_str().unwrap().contains("sshpass"));
+    assert_eq!(hosts[3]["duration_ms"], 0);
+}
+
+#[test]
+fn cp_and_check_share_the_format() {
+    let home = home("report-cp");
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web1", "deploy", "10.0.0.1", "ssh-key"]);
+    jump(
+        &home,
+        &["add", "web2", "deploy", "127.0.0.1", "9", "ssh-key"],
+    );
+    script(
+        &home,
+        "scp",
+        "echo 'scp: /srv: Permission denied' >&2\nexit 1\n",
+    );
+    let file = home.join("report.json");
+    let output = command(&home)
+        .args(["cp", "--to", "name:web*", "--yes", "--continue-on-error"])
+        .args([
+            "--report",
+            file.to_str().unwrap(),
+            "app.tar",
+            "/srv/app.tar",
+        ])
+        .env("JUMP_SCP_BIN", home.join("scp"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let report = read(&file);
+    assert_eq!(report["command"], "cp");
+    assert_eq!(report["hosts"][0]["outcome"], "failed");
+    assert_eq!(report["hosts"][0]["exit_code"], 1);
+    assert_eq!(
+        report["hosts"][1]["stderr_tail"],
+        json!(["scp: /srv: Permission denied"])
+    );
+
+    let output = command(&home)
+        .args(["cp", "--to", "web1", "--report", file.to_str().unwrap()])
+        .args(["app.tar", "/srv/app.tar"])
+        .env("JUMP_SCP_BIN", home.join("missing"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(read(&file)["hosts"][0]["outcome"], "not-started");
+
+    let output = command(&home)
+        .args(["check", "--timeout", "1", "web2", "--report"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let report = read(&file);
+    assert_eq!(report["version"], 1);
+    assert_eq!(report["command"], "check");
+    assert_eq!(report["hosts"][0]["server_name"], "web2");
+    assert_eq!(report["hosts"][0]["outcome"], "unreachable");
+}
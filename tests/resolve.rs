@@ -0,0 +1,119 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use common::{home, jump, run};
+
+/// A store with a key server behind two bastions and a password server,
+/// and stand-ins for ssh and sshpass in the config
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    fs::create_dir_all(&home).unwrap();
+    fs::write(home.join("ssh"), "").unwrap();
+    fs::write(home.join("sshpass"), "").unwrap();
+    fs::write(
+        home.join("config.toml"),
+        format!(
+            "[binaries]\nssh = \"{0}/ssh\"\nsshpass = \"{0}/sshpass\"\n",
+            home.display()
+        ),
+    )
+    .unwrap();
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &[
+            "add",
+            "web",
+            "deploy",
+            "10.0.0.1",
+            "2222",
+            "ssh-key",
+            "~/.ssh/deploy",
+        ],
+    );
+    jump(
+        &home,
+        &["option", "set", "web", "ProxyJump", "bastion,edge"],
+    );
+    jump(
+        &home,
+        &["add", "db", "root", "10.0.0.2", "password", "hunter2"],
+    );
+    home
+}
+
+#[test]
+fn the_json_shape_is_stable() {
+    let home = store("resolve-json");
+    let home_dir = home.display();
+    assert_eq!(
+        jump(&home, &["resolve", "web", "--json"]),
+        format!(
+            r#"{{
+  "server_name": "web",
+  "user": "deploy",
+  "address": "10.0.0.1",
+  "port": 2222,
+  "method": "ssh-key",
+  "identity": "{home_dir}/.ssh/deploy",
+  "proxy_jump": "bastion,edge",
+  "source": null,
+  "argv": [
+    "{home_dir}/ssh",
+    "-i",
+    "{home_dir}/.ssh/deploy",
+    "-o",
+    "ProxyJump=bastion,edge",
+    "-o",
+    "IdentitiesOnly=yes",
+    "-p",
+    "2222",
+    "--",
+    "deploy@10.0.0.1"
+  ],
+  "env": {{}}
+}}
+"#
+        )
+    );
+    let db = jump(&home, &["--output", "json", "resolve", "db"]);
+    assert!(!db.contains("hunter2"), "{}", db);
+    assert!(db.contains(
+        r#"  "method": "password",
+  "identity": null,
+  "proxy_jump": null,"#
+    ));
+    assert!(db.contains(r#""SSHPASS": "<redacted>""#), "{}", db);
+}
+
+#[test]
+fn it_agrees_with_conn() {
+    let home = store("resolve-conn");
+    for name in ["web", "db"] {
+        let resolved = jump(&home, &["resolve", name]);
+        let command = resolved
+            .lines()
+            .find_map(|line| line.strip_prefix("command:  "))
+            .unwrap();
+        assert_eq!(
+            jump(&home, &["conn", name, "--dry-run"]).lines().next(),
+            Some(command)
+        );
+    }
+}
+
+#[test]
+fn failures_exit_as_not_found() {
+    let home = store("resolve-failures");
+    assert_eq!(run(&home, &["resolve", "nope"]).status.code(), Some(3));
+
+    fs::remove_file(home.join("sshpass")).unwrap();
+    let output = run(&home, &["resolve", "db"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Unable to resolve db: sshpass not found")
+    );
+}
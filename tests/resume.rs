@@ -0,0 +1,88 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use common::{command, home, jump};
+
+/// A store with one key server, and fake binaries that append their name
+/// and arguments to `calls`; the fake ssh exits with `remote_rsync`'s code,
+/// as `command -v rsync` would
+fn store(test: &str, remote_rsync: i32) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["init"]);
+    jump(&home, &["add", "db", "deploy", "10.0.0.2", "ssh-key"]);
+    let calls = home.join("calls");
+    for (tool, exit) in [("ssh", remote_rsync), ("scp", 0), ("rsync", 0)] {
+        let path = home.join(tool);
+        fs::write(
+            &path,
+            format!(
+                "#!/bin/sh\necho \"{} $*\" >> {}\nexit {}\n",
+                tool,
+                calls.display(),
+                exit
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    home
+}
+
+/// Run `cp --resume` and return its stderr and the calls made
+fn resume(home: &Path, rsync: &Path) -> (String, Vec<String>) {
+    let output = command(home)
+        .args(["cp", "--resume", "db:/srv/dump.sql", "."])
+        .env("JUMP_SSH_BIN", home.join("ssh"))
+        .env("JUMP_SCP_BIN", home.join("scp"))
+        .env("JUMP_RSYNC_BIN", rsync)
+        .env("JUMP_SSH_KEYSCAN_BIN", home.join("no-keyscan"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let calls = fs::read_to_string(home.join("calls"))
+        .unwrap()
+        .lines()
+        .map(str::to_owned)
+        .collect();
+    (String::from_utf8(output.stderr).unwrap(), calls)
+}
+
+#[test]
+fn rsync_on_both_ends_keeps_partial_files() {
+    let home = store("resume-rsync", 0);
+    let (stderr, calls) = resume(&home, &home.join("rsync"));
+    assert!(!stderr.contains("scp"), "{}", stderr);
+    assert_eq!(calls.len(), 2, "{:?}", calls);
+    assert!(calls[0].starts_with("ssh "), "{:?}", calls);
+    assert!(calls[0].ends_with("-- deploy@10.0.0.2 command -v rsync"));
+    assert!(calls[1].starts_with("rsync --partial --progress --rsh "));
+    assert!(calls[1].ends_with("-- deploy@10.0.0.2:/srv/dump.sql ."));
+}
+
+#[test]
+fn a_missing_rsync_falls_back_to_scp() {
+    let home = store("resume-remote-scp", 127);
+    let (stderr, calls) = resume(&home, &home.join("rsync"));
+    assert!(
+        stderr.contains(
+            "rsync is not installed on db, copying with scp, which starts over when interrupted"
+        ),
+        "{}",
+        stderr
+    );
+    assert!(calls.last().unwrap().starts_with("scp "), "{:?}", calls);
+
+    let home = store("resume-local-scp", 0);
+    let (stderr, calls) = resume(&home, &home.join("no-rsync"));
+    assert!(stderr.contains("rsync is not installed here"), "{}", stderr);
+    // The server is not asked when rsync is missing here
+    assert_eq!(calls.len(), 1, "{:?}", calls);
+    assert!(calls[0].starts_with("scp "), "{:?}", calls);
+}
@@ -0,0 +1,226 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use jump::schedule::{self, Event, Limits};
+
+use common::{command, home, jump};
+
+fn limits(parallel: usize, bastion_parallel: usize) -> Limits {
+    Limits {
+        parallel,
+        bastion_parallel,
+        delay: Duration::ZERO,
+    }
+}
+
+/// The most items `run` had going at once, over `bastions`, and the order
+/// they were started in
+fn most_at_once(bastions: &[Option<String>], limits: Limits) -> (usize, Vec<usize>) {
+    let running = AtomicUsize::new(0);
+    let most = AtomicUsize::new(0);
+    let mut started = vec![];
+    schedule::run(
+        bastions,
+        limits,
+        |_| {
+            most.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(50));
+            running.fetch_sub(1, Ordering::SeqCst);
+        },
+        |event| {
+            if let Event::Started(i) = event {
+                started.push(i);
+            }
+            true
+        },
+    );
+    (most.into_inner(), started)
+}
+
+#[test]
+fn no_more_than_parallel_run_at_once() {
+    let (most, started) = most_at_once(&vec![None; 6], limits(2, 4));
+    assert_eq!(most, 2);
+    let mut started = started;
+    started.sort();
+    assert_eq!(started, [0, 1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn servers_behind_one_bastion_are_capped_lower() {
+    let behind = Some("bastion".to_owned());
+    let bastions = [behind.clone(), behind.clone(), behind.clone(), behind];
+    assert_eq!(most_at_once(&bastions, limits(8, 1)).0, 1);
+    let mixed = [
+        Some("a".to_owned()),
+        Some("a".to_owned()),
+        Some("b".to_owned()),
+        None,
+    ];
+    // The second server behind `a` waits, the others go ahead of it
+    let (most, started) = most_at_once(&mixed, limits(8, 1));
+    assert_eq!(most, 3);
+    assert_eq!(started.last(), Some(&1));
+}
+
+#[test]
+fn starts_are_spread_by_the_delay() {
+    let started = Instant::now();
+    let mut at = vec![];
+    schedule::run(
+        &vec![None; 3],
+        Limits {
+            delay: Duration::from_millis(100),
+            ..limits(8, 4)
+        },
+        |_| {},
+        |event| {
+            if let Event::Started(_) = event {
+                at.push(started.elapsed());
+            }
+            true
+        },
+    );
+    assert!(at[2] >= Duration::from_millis(200), "{:?}", at);
+}
+
+#[test]
+fn nothing_starts_once_told_to_stop() {
+    let mut finished = 0;
+    schedule::run(
+        &vec![None; 5],
+        limits(1, 1),
+        |i| i,
+        |event| match event {
+            Event::Started(_) => true,
+            Event::Finished(i, result) => {
+                assert_eq!(i, result);
+                finished += 1;
+                i < 1
+            }
+        },
+    );
+    assert_eq!(finished, 2);
+}
+
+/// A store of `count` servers, the first `behind` of them reached through
+/// one bastion
+fn store(test: &str, count: usize, behind: usize) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    for n in 1..=count {
+        let name = format!("web{}", n);
+        let address = format!("10.0.0.{}", n);
+        jump(&home, &["add", &name, "deploy", &address, "ssh-key"]);
+        if n <= behind {
+            jump(
+                &home,
+                &["option", "set", &name, "ProxyJump", "deploy@bastion"],
+            );
+        }
+    }
+    home
+}
+
+/// Run `exec` across every server with an ssh that records when each run
+/// started and ended, in nanoseconds, returning those spans by start
+fn spans(home: &Path, args: &[&str]) -> Vec<(u128, u128)> {
+    let ssh = home.join("ssh");
+    let spawns = home.join("spawns");
+    fs::write(
+        &ssh,
+        format!(
+            "#!/bin/sh\nstart=$(date +%s%N)\nsleep 0.3\necho \"$start $(date +%s%N)\" >> {}\n",
+            spawns.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+    let output = command(home)
+        .args(["exec", "--on", "name:*", "--yes"])
+        .args(args)
+        .args(["--", "uptime"])
+        .env("JUMP_SSH_BIN", &ssh)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let mut spans = fs::read_to_string(spawns)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let (start, end) = line.split_once(' ').unwrap();
+            (start.parse().unwrap(), end.parse().unwrap())
+        })
+        .collect::<Vec<_>>();
+    spans.sort();
+    spans
+}
+
+/// The most spans that overlap at any start
+fn overlap(spans: &[(u128, u128)]) -> usize {
+    spans
+        .iter()
+        .map(|(at, _)| {
+            spans
+                .iter()
+                .filter(|(start, end)| start <= at && at < end)
+                .count()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+#[test]
+fn exec_runs_parallel_servers_at_once() {
+    let home = store("schedule-parallel", 4, 0);
+    let spans = spans(&home, &["--parallel", "2"]);
+    assert_eq!(spans.len(), 4);
+    assert_eq!(overlap(&spans), 2);
+}
+
+#[test]
+fn exec_caps_servers_behind_a_bastion() {
+    let home = store("schedule-bastion", 3, 3);
+    let spans = spans(&home, &["--bastion-parallel", "1"]);
+    assert_eq!(overlap(&spans), 1);
+
+    fs::write(home.join("config.toml"), "bastion_parallel = 2\n").unwrap();
+    fs::remove_file(home.join("spawns")).unwrap();
+    assert_eq!(overlap(&self::spans(&home, &[])), 2);
+}
+
+#[test]
+fn exec_staggers_starts_by_the_delay() {
+    let home = store("schedule-delay", 3, 0);
+    let spans = spans(&home, &["--delay", "150"]);
+    for pair in spans.windows(2) {
+        assert!(pair[1].0 - pair[0].0 >= 140_000_000, "{:?}", spans);
+    }
+}
+
+#[test]
+fn exec_shows_how_many_servers_are_done() {
+    let home = store("schedule-progress", 2, 0);
+    let ssh = home.join("ssh");
+    fs::write(&ssh, "#!/bin/sh\necho up\n").unwrap();
+    fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+    let output = command(&home)
+        .args(["exec", "--on", "name:*", "-j", "1", "--", "uptime"])
+        .env("JUMP_SSH_BIN", &ssh)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"up\nup\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("2 servers: web1, web2\n==> web1 <== (1/2)\n==> web2 <== (2/2)\n"));
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use std::path::PathBuf;
+
+use jump::{
+    search::{self, Field},
+    server::{ConnectMethods, SSHKey, Server},
+};
+
+use common::{home, jump};
+
+fn server(name: &str, address: &str, note: Option<&str>) -> Server {
+    let mut server = Server::new(
+        name.to_owned(),
+        "deploy".to_owned(),
+        address.to_owned(),
+        22,
+        ConnectMethods::SSHKey(SSHKey {
+            path: PathBuf::from("/keys/id"),
+        }),
+    );
+    server.note = note.map(str::to_owned);
+    server
+}
+
+fn servers() -> Vec<Server> {
+    let mut cache = server("cache", "10.0.0.3", Some("Redis for the DB layer"));
+    cache.tags = vec!["prod".to_owned()];
+    vec![
+        server("web", "db-proxy.internal", None),
+        cache,
+        server("db-main", "10.0.0.2", Some("Primary Postgres")),
+        server("mail", "10.0.0.4", None),
+    ]
+}
+
+fn names(words: &[&str]) -> Vec<String> {
+    let words = words
+        .iter()
+        .map(|word| word.to_string())
+        .collect::<Vec<_>>();
+    search::search(servers(), &words)
+        .into_iter()
+        .map(|found| found.server.server_name)
+        .collect()
+}
+
+#[test]
+fn name_matches_rank_first() {
+    assert_eq!(names(&["db"]), ["db-main", "web", "cache"]);
+}
+
+#[test]
+fn every_word_must_match_ignoring_case() {
+    assert_eq!(names(&["DB", "redis"]), ["cache"]);
+    assert_eq!(names(&["PROD", "postgres"]), Vec::<String>::new());
+    assert_eq!(names(&["prod"]), ["cache"]);
+    assert_eq!(names(&["deploy"]), ["web", "cache", "db-main", "mail"]);
+}
+
+#[test]
+fn hits_locate_the_words() {
+    let words = ["db".to_owned(), "layer".to_owned()];
+    let found = search::search(servers(), &words);
+    assert_eq!(found.len(), 1);
+    let note = &found[0].hits[0];
+    assert_eq!(note.field, Field::Note);
+    assert_eq!(note.ranges, [(14, 16), (17, 22)]);
+
+    let words = ["ÄR".to_owned()];
+    let found = search::search(vec![server("bär", "10.0.0.9", None)], &words);
+    assert_eq!(found[0].hits[0].ranges, [(1, 4)]);
+}
+
+#[test]
+fn json_lists_the_matching_fields() {
+    let home = home("search-json");
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &[
+            "add",
+            "web",
+            "deploy",
+            "db-proxy.internal",
+            "--note",
+            "Serves the DB",
+            "ssh-key",
+        ],
+    );
+    let output = jump(&home, &["--output", "json", "search", "db"]);
+    let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!([{
+            "server_name": "web",
+            "matches": [
+                { "field": "address", "value": "db-proxy.internal" },
+                { "field": "note", "value": "Serves the DB" },
+            ],
+        }])
+    );
+    assert_eq!(
+        jump(&home, &["--color", "never", "search", "serves"]),
+        "web  note: Serves the DB\n"
+    );
+}
@@ -0,0 +1,275 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use jump::{
+    error::JumpError,
+    select::{self, glob, Subnet},
+    server::{ConnectMethods, Password, Platform, SSHKey, Server},
+};
+
+use common::{home, jump, run};
+
+fn server(name: &str, username: &str, port: u32, tags: &[&str]) -> Server {
+    let mut server = Server::new(
+        name.to_owned(),
+        username.to_owned(),
+        format!("{}.example.com", name),
+        port,
+        ConnectMethods::SSHKey(SSHKey {
+            path: PathBuf::from("/keys/id"),
+        }),
+    );
+    server.tags = tags.iter().map(|tag| tag.to_string()).collect();
+    server
+}
+
+fn servers() -> Vec<Server> {
+    let mut db = server("db", "admin", 5432, &["prod"]);
+    db.method = ConnectMethods::Password(Password {
+        password: "secret".to_owned(),
+    });
+    db.platform = Some(Platform::Bsd);
+    let mut servers = vec![
+        server("web1", "deploy", 22, &["prod", "eu"]),
+        server("web2", "root", 22, &["staging"]),
+        db,
+    ];
+    for (id, server) in (1..).zip(&mut servers) {
+        server.id = Some(id);
+    }
+    servers
+}
+
+fn names(expr: &[&str]) -> Vec<String> {
+    let selectors = expr
+        .iter()
+        .map(|expr| select::parse(expr).unwrap())
+        .collect::<Vec<_>>();
+    select::filter(servers(), &selectors)
+        .into_iter()
+        .map(|server| server.server_name)
+        .collect()
+}
+
+#[test]
+fn terms_are_anded_and_selectors_ored() {
+    assert_eq!(names(&["tag:prod"]), ["web1", "db"]);
+    assert_eq!(names(&["tag:prod user:deploy"]), ["web1"]);
+    assert_eq!(names(&["name:web*", "db"]), ["web1", "web2", "db"]);
+    assert_eq!(names(&["  port:22   user:root "]), ["web2"]);
+    assert_eq!(names(&["method:password"]), ["db"]);
+    assert_eq!(names(&["id:2"]), ["web2"]);
+    assert_eq!(names(&["platform:bsd"]), ["db"]);
+    assert_eq!(names(&["host:WEB?.EXAMPLE.com"]), ["web1", "web2"]);
+    assert_eq!(names(&["web"]), Vec::<String>::new());
+    assert_eq!(names(&[]), ["web1", "web2", "db"]);
+}
+
+#[test]
+fn errors_point_at_the_bad_token() {
+    let reason = select::parse_arg("tag:prod colour:red").unwrap_err();
+    assert_eq!(
+        reason,
+        "unknown field `colour`, expected one of id, name, user, address, host, port, tag, method, platform\n  \
+         tag:prod colour:red\n           ^^^^^^"
+    );
+    let reason = select::parse_arg("port:ssh").unwrap_err();
+    assert_eq!(reason, "`ssh` is not a port number\n  port:ssh\n       ^^^");
+    assert!(select::parse_arg("user:")
+        .unwrap_err()
+        .starts_with("`user:` needs a value"));
+    assert!(select::parse_arg("platform:mac").is_err());
+    assert!(select::parse_arg("  ").is_err());
+}
+
+#[test]
+fn selecting_nothing_is_an_error() {
+    let parse = |expr: &str| vec![select::parse(expr).unwrap()];
+    assert!(matches!(
+        select::select(servers(), &parse("nope")),
+        Err(JumpError::NotFound { name }) if name == "nope"
+    ));
+    assert!(matches!(
+        select::select(servers(), &parse("tag:nope")),
+        Err(JumpError::NoMatch { selector }) if selector == "`tag:nope`"
+    ));
+    assert_eq!(select::select(servers(), &parse("db")).unwrap().len(), 1);
+}
+
+#[test]
+fn globs() {
+    assert!(glob("web*", "web"));
+    assert!(glob("*-prod-*", "eu-prod-1"));
+    assert!(glob("w?b", "web"));
+    assert!(glob("*a*b", "xaab"));
+    assert!(!glob("web", "web1"));
+    assert!(!glob("w?b", "wb"));
+}
+
+#[test]
+fn subnets_match_addresses_of_their_family() {
+    let subnet = Subnet::parse("10.1.2.0/24").unwrap();
+    assert!(subnet.contains("10.1.2.200".parse().unwrap()));
+    assert!(!subnet.contains("10.1.3.1".parse().unwrap()));
+    assert!(!subnet.contains("::ffff:10.1.2.1".parse().unwrap()));
+    assert!(Subnet::parse("10.1.2.7/24")
+        .unwrap()
+        .contains("10.1.2.1".parse().unwrap()));
+    assert!(Subnet::parse("0.0.0.0/0")
+        .unwrap()
+        .contains("203.0.113.9".parse().unwrap()));
+    assert!(Subnet::parse("::/0")
+        .unwrap()
+        .contains("fd00::1".parse().unwrap()));
+    let ula = Subnet::parse("fd00::/8").unwrap();
+    assert!(ula.contains("fdab::1".parse().unwrap()));
+    assert!(!ula.contains("fe80::1".parse().unwrap()));
+
+    let reason = |text| select::parse_subnet(text).unwrap_err();
+    assert_eq!(
+        reason("10.1.2.0"),
+        "`10.1.2.0` has no mask, such as `/24` in `10.1.2.0/24`"
+    );
+    assert_eq!(reason("10.1.2/24"), "`10.1.2` is not an IP address");
+    assert_eq!(reason("10.1.2.0/x"), "`/x` is not a mask length");
+    assert_eq!(
+        reason("10.1.2.0/33"),
+        "`/33` is longer than the 32 bits of an IPv4 address"
+    );
+    assert_eq!(
+        reason("fd00::/129"),
+        "`/129` is longer than the 128 bits of an IPv6 address"
+    );
+}
+
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &[
+            "add", "web1", "deploy", "10.0.0.1", "--tag", "prod", "ssh-key",
+        ],
+    );
+    jump(&home, &["add", "web2", "root", "10.0.0.2", "ssh-key"]);
+    jump(&home, &["tag", "add", "web2", "staging", "eu"]);
+    home
+}
+
+#[test]
+fn ls_explains_the_verdicts() {
+    let home = store("select-explain");
+    assert_eq!(
+        jump(&home, &["ls", "--plain", "tag:prod"]),
+        "web1\tdeploy\t10.0.0.1\t22\tssh-key\n"
+    );
+    let explained = jump(
+        &home,
+        &[
+            "--color",
+            "never",
+            "ls",
+            "--explain",
+            "user:deploy",
+            "tag:eu",
+        ],
+    );
+    assert_eq!(
+        explained,
+        "web1: matched\n     user:deploy  yes  user deploy\n  or tag:eu       no   tags prod\n\
+         web2: matched\n     user:deploy  no   user root\n  or tag:eu       yes  tags eu, staging\n"
+    );
+}
+
+#[test]
+fn rm_by_selector() {
+    let home = store("select-rm");
+    let output = run(&home, &["rm", "tag:nope"]);
+    assert_eq!(output.status.code(), Some(3));
+    // More than one server is only removed after confirming, which cannot
+    // be asked without a terminal
+    let output = run(&home, &["rm", "name:web*"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("pass --yes to go ahead"));
+    assert_eq!(jump(&home, &["ls", "--plain"]).lines().count(), 2);
+    jump(&home, &["rm", "--yes", "name:web*"]);
+    assert_eq!(jump(&home, &["ls"]), "");
+}
+
+#[test]
+fn exec_runs_on_every_selected_server() {
+    let home = store("select-exec");
+    let output = jump(
+        &home,
+        &["exec", "--dry-run", "--on", "name:web*", "--", "uptime"],
+    );
+    assert_eq!(output.lines().count(), 2);
+    assert!(output.contains("deploy@10.0.0.1 uptime"));
+    assert!(output.contains("root@10.0.0.2 uptime"));
+}
+
+#[test]
+fn many_servers_need_yes() {
+    let home = store("select-confirm");
+    fs::write(home.join("config.toml"), "[confirm]\nservers = 1\n").unwrap();
+    let output = run(&home, &["exec", "--on", "name:web*", "--", "uptime"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("2 servers: web1, web2"));
+    assert!(stderr.contains("Not asking \"Run on 2 servers?\""));
+    let output = run(
+        &home,
+        &["check", "--yes", "--timeout", "1", "--plain", "name:web*"],
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 2);
+    assert_eq!(run(&home, &["check", "name:nope*"]).status.code(), Some(3));
+}
+
+#[test]
+fn ls_tree_groups_by_tag() {
+    let home = store("select-tree");
+    jump(&home, &["add", "db", "admin", "10.0.0.3", "ssh-key"]);
+    assert_eq!(
+        jump(&home, &["--color", "never", "ls", "--tree"]),
+        "eu (1)\n  2  web2 username: root address: 10.0.0.2 *\n\
+         prod (1)\n  1  web1 username: deploy address: 10.0.0.1\n\
+         staging (1)\n  2  web2 username: root address: 10.0.0.2 *\n\
+         (ungrouped) (1)\n  3  db username: admin address: 10.0.0.3\n\
+         * also listed under another tag\n"
+    );
+    assert_eq!(
+        jump(&home, &["--color", "never", "ls", "--tree", "user:deploy"]),
+        "prod (1)\n  1  web1 username: deploy address: 10.0.0.1\n"
+    );
+}
+
+#[test]
+fn ls_filters_by_user_and_subnet() {
+    let home = store("select-subnet");
+    jump(&home, &["add", "db", "root", "10.0.1.3", "ssh-key"]);
+    jump(&home, &["add", "local", "root", "localhost", "ssh-key"]);
+    let ls = |args: &[&str]| {
+        let output = jump(&home, &[&["--output", "json", "ls"], args].concat());
+        let servers: serde_json::Value = serde_json::from_str(&output).unwrap();
+        servers
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|server| server["server_name"].as_str().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ls(&["--user", "root"]), ["web2", "db", "local"]);
+    assert_eq!(ls(&["--subnet", "10.0.0.0/24"]), ["web1", "web2"]);
+    assert_eq!(
+        ls(&["--subnet", "10.0.0.0/16", "--user", "r*"]),
+        ["web2", "db"]
+    );
+    assert_eq!(ls(&["--subnet", "10.0.0.0/16", "tag:eu"]), ["web2"]);
+    assert_eq!(ls(&["--subnet", "127.0.0.0/8"]), Vec::<String>::new());
+    assert_eq!(ls(&["--subnet", "127.0.0.0/8", "--resolve"]), ["local"]);
+
+    let output = run(&home, &["ls", "--subnet", "10.0.0.0/40"]);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("`/40` is longer than the 32 bits of an IPv4 address"));
+}
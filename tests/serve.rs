@@ -0,0 +1,277 @@
+mod common;
+
+use std::{
+    io::Write,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use serde_json::{json, Value};
+
+use common::{command, home, jump, run};
+use jump::{config::Config, serve, store::Store};
+
+/// A store with `web` and `db`, and `api` included from a file
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["init"]);
+    jump(
+        &home,
+        &[
+            "add", "web", "deploy", "10.0.0.1", "--tag", "prod", "ssh-key",
+        ],
+    );
+    jump(
+        &home,
+        &[
+            "add", "db", "admin", "10.0.0.2", "2222", "password", "hunter2",
+        ],
+    );
+    let team = home.join("team");
+    jump(&team, &["init"]);
+    jump(&team, &["add", "api", "deploy", "10.0.1.1", "ssh-key"]);
+    std::fs::write(home.join("team.json"), jump(&team, &["export"])).unwrap();
+    std::fs::write(home.join("config.toml"), "include = [\"team.json\"]\n").unwrap();
+    home
+}
+
+/// Send `requests`, one per line, to `jump serve --stdio` and return its
+/// answers
+fn serve(home: &Path, args: &[&str], requests: &[Value]) -> Vec<Value> {
+    let mut child = command(home)
+        .args(args)
+        .args(["serve", "--stdio"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for request in requests {
+        writeln!(stdin, "{}", request).unwrap();
+    }
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn cli_json(home: &Path, args: &[&str]) -> Value {
+    serde_json::from_str(&jump(home, args)).unwrap()
+}
+
+#[test]
+fn reads_answer_as_the_cli_does() {
+    let home = store("serve-read");
+    let answers = serve(
+        &home,
+        &[],
+        &[
+            request(1, "hello", json!({ "protocol": 1 })),
+            request(2, "list", json!({})),
+            request(3, "list", json!({ "filter": ["tag:prod", "api"] })),
+            request(4, "resolve", json!({ "name": "db" })),
+        ],
+    );
+    assert_eq!(answers.len(), 4);
+    assert_eq!(answers[0]["id"], 1);
+    assert_eq!(answers[0]["result"]["protocol"], 1);
+    assert_eq!(
+        answers[0]["result"]["methods"],
+        json!(["hello", "list", "resolve", "add", "remove", "check"])
+    );
+    assert_eq!(answers[0]["result"]["read_only"], false);
+    assert_eq!(
+        answers[1]["result"],
+        cli_json(&home, &["ls", "--output", "json"])
+    );
+    let names = answers[2]["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|server| server["server_name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["web", "api"]);
+    assert_eq!(
+        answers[3]["result"],
+        cli_json(&home, &["resolve", "db", "--json"])
+    );
+}
+
+#[test]
+fn changes_are_written_to_the_store() {
+    let home = store("serve-write");
+    let answers = serve(
+        &home,
+        &[],
+        &[
+            request(
+                1,
+                "add",
+                json!({
+                    "server_name": "cache",
+                    "username": "ops",
+                    "server_address": "10.0.0.3",
+                    "method": { "SSHKey": { "path": "~/.ssh/cache" } },
+                    "tags": ["prod"],
+                }),
+            ),
+            request(2, "remove", json!({ "name": "web" })),
+            request(
+                3,
+                "add",
+                json!({ "server_name": "db", "username": "a", "server_address": "b", "method": { "SSHKey": { "path": "k" } } }),
+            ),
+            request(4, "remove", json!({ "name": "api" })),
+            request(5, "remove", json!({ "name": "nope" })),
+        ],
+    );
+    assert_eq!(answers[0]["result"]["server_name"], "cache");
+    assert_eq!(answers[0]["result"]["port"], 22);
+    assert_eq!(answers[0]["result"]["tags"], json!(["prod"]));
+    assert_eq!(answers[1]["result"]["server_name"], "web");
+    assert_eq!(answers[2]["error"]["code"], -32004);
+    assert_eq!(answers[2]["error"]["data"]["kind"], "duplicate");
+    // Servers from an include file are not jump's to change
+    assert_eq!(answers[3]["error"]["data"]["kind"], "included");
+    assert_eq!(answers[4]["error"]["code"], -32003);
+    assert_eq!(answers[4]["error"]["data"]["kind"], "not_found");
+    assert_eq!(
+        jump(&home, &["ls", "--plain"]),
+        "db\tadmin\t10.0.0.2\t2222\tpassword\n\
+         cache\tops\t10.0.0.3\t22\tssh-key\n\
+         api\tdeploy\t10.0.1.1\t22\tssh-key\n"
+    );
+
+    let answers = serve(
+        &home,
+        &["--read-only"],
+        &[
+            request(1, "hello", json!(null)),
+            request(2, "remove", json!({ "name": "db" })),
+        ],
+    );
+    assert_eq!(answers[0]["result"]["read_only"], true);
+    assert_eq!(answers[1]["error"]["code"], -32005);
+    assert_eq!(answers[1]["error"]["data"]["kind"], "read_only");
+    assert!(run(&home, &["show", "db"]).status.success());
+}
+
+#[test]
+fn check_probes_the_servers() {
+    let home = home("serve-check");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    jump(&home, &["init"]);
+    jump(&home, &["add", "up", "u", "127.0.0.1", &port, "ssh-key"]);
+    let answers = serve(
+        &home,
+        &[],
+        &[request(
+            1,
+            "check",
+            json!({ "filter": ["up"], "timeout": 2 }),
+        )],
+    );
+    assert_eq!(
+        answers[0]["result"],
+        json!([{ "server_name": "up", "server_address": "127.0.0.1", "port": port.parse::<u32>().unwrap(), "up": true }])
+    );
+}
+
+#[test]
+fn bad_requests_get_protocol_errors() {
+    let home = store("serve-errors");
+    let mut child = command(&home)
+        .args(["serve", "--stdio"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    writeln!(stdin, "not json").unwrap();
+    writeln!(stdin, "{}", json!({ "id": 1 })).unwrap();
+    writeln!(stdin, "{}", request(2, "rename", json!({}))).unwrap();
+    writeln!(
+        stdin,
+        "{}",
+        request(3, "resolve", json!({ "server": "db" }))
+    )
+    .unwrap();
+    // A notification is not answered
+    writeln!(stdin, "{}", json!({ "jsonrpc": "2.0", "method": "hello" })).unwrap();
+    writeln!(stdin, "{}", request(4, "hello", json!({}))).unwrap();
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+    let answers = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    let codes = answers
+        .iter()
+        .map(|answer| (answer["id"].clone(), answer["error"]["code"].clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        [
+            (Value::Null, json!(-32700)),
+            (json!(1), json!(-32600)),
+            (json!(2), json!(-32601)),
+            (json!(3), json!(-32602)),
+            (json!(4), Value::Null),
+        ]
+    );
+}
+
+#[test]
+fn calls_run_against_the_store_they_are_given() {
+    let store = Store::open_in_memory().unwrap();
+    store.initialize().unwrap();
+    let config = Config {
+        default_port: 2222,
+        ..Config::default()
+    };
+    let call = |method: &str, params: Value| serve::call(&store, &config, method, params);
+
+    let added = call(
+        "add",
+        json!({
+            "server_name": "web",
+            "username": "deploy",
+            "server_address": "10.0.0.1",
+            "method": { "SSHKey": { "path": "~/.ssh/web" } },
+            "id": 42,
+            "created_at": 1,
+        }),
+    )
+    .unwrap();
+    assert_eq!(added["port"], 2222);
+    // The store's own fields are not taken from the caller
+    assert_ne!(added["id"], 42);
+    assert_ne!(added["created_at"], 1);
+
+    let listed = call("list", json!({ "filter": ["tag:prod"] })).unwrap();
+    assert_eq!(listed, json!([]));
+    let listed = call("list", json!(null)).unwrap();
+    assert_eq!(listed[0]["server_name"], "web");
+
+    match call("add", json!({ "server_name": "db" })) {
+        Err(serve::Error::InvalidParams(reason)) => assert_eq!(reason, "missing field `username`"),
+        other => panic!("{:?}", other),
+    }
+    assert!(matches!(
+        call("nope", json!(null)),
+        Err(serve::Error::UnknownMethod(_))
+    ));
+    call("remove", json!({ "name": "web" })).unwrap();
+    assert!(store.list().unwrap().is_empty());
+}
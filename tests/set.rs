@@ -0,0 +1,145 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use common::{home, jump, run};
+use jump::{
+    select::{self, SetField, Setting},
+    server::{ConnectMethods, Password, SSHKey, Server},
+};
+
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web1", "admin", "10.0.0.1", "ssh-key"]);
+    jump(&home, &["add", "web2", "admin", "10.0.0.2", "ssh-key"]);
+    jump(
+        &home,
+        &["add", "db", "admin", "10.0.0.3", "password", "secret"],
+    );
+    jump(&home, &["add", "cache", "ops", "10.0.0.4", "ssh-key"]);
+    home
+}
+
+#[test]
+fn dry_run_lists_old_and_new_values() {
+    let home = store("set-dry-run");
+    assert_eq!(
+        jump(
+            &home,
+            &[
+                "set",
+                "--filter",
+                "user:admin",
+                "username",
+                "ops",
+                "--dry-run"
+            ]
+        ),
+        "web1: admin → ops\nweb2: admin → ops\ndb: admin → ops\n"
+    );
+    assert_eq!(
+        jump(&home, &["ls", "--plain", "user:ops"]),
+        "cache\tops\t10.0.0.4\t22\tssh-key\n"
+    );
+}
+
+#[test]
+fn changes_every_selected_server() {
+    let home = store("set-apply");
+    jump(
+        &home,
+        &["set", "--filter", "user:admin", "username", "ops", "--yes"],
+    );
+    assert_eq!(jump(&home, &["ls", "--plain", "user:admin"]), "");
+    jump(
+        &home,
+        &["set", "--filter", "name:web*", "port", "2222", "-y"],
+    );
+    assert_eq!(
+        jump(&home, &["ls", "--plain", "port:2222"]),
+        "web1\tops\t10.0.0.1\t2222\tssh-key\nweb2\tops\t10.0.0.2\t2222\tssh-key\n"
+    );
+    assert!(!run(&home, &["set", "--filter", "db", "port", "ssh", "-y"])
+        .status
+        .success());
+}
+
+#[test]
+fn key_skips_servers_with_a_password() {
+    let home = store("set-key");
+    let output = jump(
+        &home,
+        &["set", "--filter", "user:admin", "key", "/keys/ops", "--yes"],
+    );
+    assert!(output.contains("db: skipped, it uses a password\n"));
+    assert!(jump(&home, &["--color", "never", "show", "web1"]).contains("/keys/ops"));
+}
+
+#[test]
+fn nothing_changes_without_confirmation() {
+    let home = store("set-confirm");
+    jump(
+        &home,
+        &[
+            "--interactive",
+            "set",
+            "--filter",
+            "name:web*",
+            "username",
+            "ops",
+        ],
+    );
+    assert_eq!(
+        jump(&home, &["ls", "--plain", "user:admin"])
+            .lines()
+            .count(),
+        3
+    );
+}
+
+#[test]
+fn fields_are_set_where_they_apply() {
+    let server = |name: &str, method: ConnectMethods| {
+        Server::new(
+            name.to_owned(),
+            "admin".to_owned(),
+            "10.0.0.1".to_owned(),
+            22,
+            method,
+        )
+    };
+    let servers = vec![
+        server(
+            "web",
+            ConnectMethods::SSHKey(SSHKey {
+                path: "/keys/old".into(),
+            }),
+        ),
+        server(
+            "db",
+            ConnectMethods::Password(Password {
+                password: "hunter2".to_owned(),
+            }),
+        ),
+    ];
+
+    let set = select::set_field(servers.clone(), SetField::Key, "/keys/new").unwrap();
+    assert_eq!(set[0].1, Setting::Changed("/keys/old".to_owned()));
+    assert!(
+        matches!(&set[0].0.method, ConnectMethods::SSHKey(key) if key.path == Path::new("/keys/new"))
+    );
+    assert_eq!(set[1].1, Setting::Skipped("it uses a password"));
+
+    let set = select::set_field(servers.clone(), SetField::Port, "22").unwrap();
+    assert!(set
+        .iter()
+        .all(|(_, setting)| *setting == Setting::Unchanged));
+    let set = select::set_field(servers.clone(), SetField::Port, "2222").unwrap();
+    assert_eq!(set[1].0.port, 2222);
+    let error = select::set_field(servers, SetField::Port, "ssh").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Invalid port: `ssh` is not a port number"
+    );
+}
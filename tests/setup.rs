@@ -0,0 +1,102 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Output, Stdio},
+};
+
+use common::{command, home, jump, run};
+
+/// A home with an `~/.ssh/config` of one host, and no store
+fn fresh(test: &str) -> PathBuf {
+    let home = home(test);
+    fs::create_dir_all(home.join(".ssh")).unwrap();
+    fs::write(
+        home.join(".ssh/config"),
+        "Host web1\n  HostName 10.0.0.1\n  User deploy\n\nHost *\n  ServerAliveInterval 30\n",
+    )
+    .unwrap();
+    home
+}
+
+/// `jump` with questions forced on, given `answers`
+fn answering(home: &Path, args: &[&str], answers: &str) -> Output {
+    let mut child = command(home)
+        .arg("--interactive")
+        .args(args)
+        .env("SHELL", "/bin/bash")
+        .env("USER", "me")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(answers.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn the_first_run_walks_through_each_step() {
+    let home = fresh("setup-all");
+    let output = answering(&home, &[], "y\ny\ny\ny\ndb\n10.0.0.2\n\n2200\n\n");
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!(
+        "Create the store at {}?",
+        home.join(".jump/servers.db").display()
+    )));
+    assert!(stdout.contains("Add the hosts of ~/.ssh/config (web1)?"));
+
+    let plain = jump(&home, &["ls", "--plain"]);
+    assert!(
+        plain.contains("web1\tdeploy\t10.0.0.1\t22\tssh-config\n"),
+        "{}",
+        plain
+    );
+    assert!(
+        plain.contains("db\tme\t10.0.0.2\t2200\tssh-key\n"),
+        "{}",
+        plain
+    );
+    let completions =
+        fs::read_to_string(home.join(".local/share/bash-completion/completions/jump")).unwrap();
+    assert!(completions.contains("jump"));
+    let config = fs::read_to_string(home.join("config.toml")).unwrap();
+    assert!(config.contains("setup_done = true"), "{}", config);
+}
+
+#[test]
+fn every_step_can_be_skipped_and_it_is_offered_once() {
+    let home = fresh("setup-skipped");
+    let output = answering(&home, &[], "n\nn\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("`jump init` creates the store"));
+    assert!(!home.join(".jump/servers.db").exists());
+    assert!(!home.join(".local").exists());
+
+    let again = answering(&home, &[], "");
+    assert!(!String::from_utf8_lossy(&again.stdout).contains("Create the store"));
+}
+
+#[test]
+fn a_command_or_non_interactive_mode_bypasses_it() {
+    let home = fresh("setup-bypassed");
+    // Not a terminal, so not interactive
+    assert!(!String::from_utf8_lossy(&run(&home, &[]).stdout).contains("Create the store"));
+    assert!(!home.join("config.toml").exists());
+
+    let output = answering(&home, &["initialize"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Create the store"));
+    let config = fs::read_to_string(home.join("config.toml")).unwrap();
+    assert!(config.contains("setup_done = true"), "{}", config);
+}
@@ -0,0 +1,121 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::{command, home, jump};
+
+/// A store with one server, an ssh that notes its process group and the
+/// signals it gets in `events` and runs until one ends it, and a
+/// post-connect hook noting the exit code there too
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    let events = home.join("events");
+    fs::write(
+        home.join("ssh"),
+        format!(
+            "#!/bin/sh\n\
+             events={}\n\
+             trap 'echo TERM >> $events; exit 143' TERM\n\
+             trap 'echo HUP >> $events; exit 129' HUP\n\
+             trap 'echo INT >> $events; exit 130' INT\n\
+             echo \"group $(ps -o pgid= -p $$ | tr -d ' ')\" >> $events\n\
+             while true; do sleep 0.05; done\n",
+            events.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(home.join("ssh"), fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(
+        home.join("config.toml"),
+        format!(
+            "post_connect = \"echo hook $JUMP_EXIT_CODE >> {}\"\n",
+            events.display()
+        ),
+    )
+    .unwrap();
+    home
+}
+
+/// `jump conn web` with the fake ssh, once ssh is up
+fn connect(home: &Path) -> Child {
+    let child = command(home)
+        .args(["conn", "web"])
+        .env("JUMP_SSH_BIN", home.join("ssh"))
+        .env("JUMP_SSH_KEYSCAN_BIN", home.join("no-keyscan"))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let started = Instant::now();
+    while !events(home).starts_with("group") {
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "ssh never started"
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+    child
+}
+
+fn events(home: &Path) -> String {
+    fs::read_to_string(home.join("events")).unwrap_or_default()
+}
+
+fn kill(signal: &str, child: &Child) {
+    let status = Command::new("kill")
+        .args([signal, &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn ssh_runs_in_a_process_group_of_its_own() {
+    let home = store("signals-group");
+    let mut child = connect(&home);
+    let group = events(&home)
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("group "))
+        .map(str::to_owned)
+        .unwrap();
+    assert_ne!(group, child.id().to_string());
+    kill("-INT", &child);
+    assert_eq!(child.wait().unwrap().code(), Some(130));
+    assert!(events(&home).ends_with("\nINT\nhook 130\n"));
+}
+
+#[test]
+fn termination_is_passed_on_and_the_hooks_still_run() {
+    let home = store("signals-term");
+    let mut child = connect(&home);
+    kill("-TERM", &child);
+    assert_eq!(child.wait().unwrap().code(), Some(143));
+    let events = events(&home);
+    assert!(events.ends_with("\nTERM\nhook 143\n"), "{}", events);
+}
+
+#[test]
+fn a_closed_terminal_ends_the_session_before_the_hooks() {
+    let home = store("signals-hangup");
+    let mut child = connect(&home);
+    kill("-HUP", &child);
+    assert_eq!(child.wait().unwrap().code(), Some(129));
+    let events = events(&home);
+    assert!(events.ends_with("\nHUP\nhook 129\n"), "{}", events);
+    // The session was logged as having ended
+    let recent = jump(&home, &["recent"]);
+    assert!(recent.contains("web"), "{}", recent);
+}
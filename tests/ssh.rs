@@ -0,0 +1,761 @@
+use std::{env, path::PathBuf};
+
+use jump::{
+    askpass,
+    config::Config,
+    connect::{self, Outcome},
+    error::JumpError,
+    server::{ConnectMethods, Password, Platform, SSHKey, Server, X11},
+    ssh::{self, ConnOptions, ExecOptions, SessionOptions},
+    store::Store,
+    style::Style,
+};
+
+/// A config whose binaries all resolve to an existing file, so the builders
+/// never depend on what is installed
+fn config() -> Config {
+    let exe = env::current_exe().unwrap();
+    let mut config = Config::default();
+    config.binaries.ssh = Some(exe.clone());
+    config.binaries.sshpass = Some(exe.clone());
+    config.binaries.scp = Some(exe.clone());
+    config.binaries.rsync = Some(exe);
+    config
+}
+
+fn exe() -> String {
+    env::current_exe().unwrap().to_str().unwrap().to_owned()
+}
+
+fn server(method: ConnectMethods) -> Server {
+    Server::new(
+        "web".to_owned(),
+        "deploy".to_owned(),
+        "10.0.0.1".to_owned(),
+        2222,
+        method,
+    )
+}
+
+fn key_server() -> Server {
+    server(ConnectMethods::SSHKey(SSHKey {
+        path: PathBuf::from("/keys/web"),
+    }))
+}
+
+fn password_server() -> Server {
+    server(ConnectMethods::Password(Password {
+        password: "hunter2".to_owned(),
+    }))
+}
+
+fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn connect_with_key() {
+    let invocation = ssh::connect(
+        &config(),
+        &key_server(),
+        &ConnOptions::default(),
+        &SessionOptions::default(),
+        &[],
+    )
+    .unwrap();
+    assert_eq!(invocation.program, env::current_exe().unwrap());
+    assert_eq!(
+        invocation.args,
+        strings(&[
+            "-i",
+            "/keys/web",
+            "-o",
+            "IdentitiesOnly=yes",
+            "-p",
+            "2222",
+            "--",
+            "deploy@10.0.0.1"
+        ])
+    );
+    assert_eq!(invocation.secret, None);
+}
+
+#[test]
+fn connect_with_password() {
+    let invocation = ssh::connect(
+        &config(),
+        &password_server(),
+        &ConnOptions::default(),
+        &SessionOptions::default(),
+        &[],
+    )
+    .unwrap();
+    assert_eq!(
+        invocation.args,
+        strings(&["-e", &exe(), "-p", "2222", "--", "deploy@10.0.0.1"])
+    );
+    let command = invocation.command();
+    assert!(command.get_args().all(|arg| arg != "hunter2"));
+    assert!(command
+        .get_envs()
+        .any(|(name, value)| name == "SSHPASS" && value == Some("hunter2".as_ref())));
+    let printed = invocation.to_string();
+    assert!(printed.starts_with("SSHPASS=<redacted> "));
+    assert!(!printed.contains("hunter2"));
+}
+
+#[test]
+fn arguments_merge_in_order() {
+    let mut config = config();
+    config.default_ssh_args = strings(&["-o", "LogLevel=ERROR"]);
+    config.keep_alive = Some(30);
+    let mut server = key_server();
+    server.options = vec![("Compression".to_owned(), "yes".to_owned())];
+    let invocation = ssh::connect(
+        &config,
+        &server,
+        &ConnOptions::default(),
+        &SessionOptions::default(),
+        &strings(&["-v"]),
+    )
+    .unwrap();
+    assert_eq!(
+        invocation.args,
+        strings(&[
+            "-i",
+            "/keys/web",
+            "-o",
+            "LogLevel=ERROR",
+            "-o",
+            "ServerAliveInterval=30",
+            "-o",
+            "Compression=yes",
+            "-o",
+            "IdentitiesOnly=yes",
+            "-v",
+            "-p",
+            "2222",
+            "--",
+            "deploy@10.0.0.1",
+        ])
+    );
+
+    let no_defaults = ConnOptions {
+        no_defaults: true,
+        ..Default::default()
+    };
+    let invocation = ssh::connect(
+        &config,
+        &server,
+        &no_defaults,
+        &SessionOptions::default(),
+        &[],
+    )
+    .unwrap();
+    assert!(!invocation.args.contains(&"LogLevel=ERROR".to_owned()));
+}
+
+#[test]
+fn x11_flags_override_the_stored_mode() {
+    let mut server = key_server();
+    server.x11 = X11::Untrusted;
+    let trusted = SessionOptions {
+        x11_trusted: true,
+        ..Default::default()
+    };
+    assert_eq!(ssh::x11_mode(&server, &trusted), X11::Trusted);
+    let invocation =
+        ssh::connect(&config(), &server, &ConnOptions::default(), &trusted, &[]).unwrap();
+    assert!(invocation.args.contains(&"-Y".to_owned()));
+    assert!(!invocation.args.contains(&"-X".to_owned()));
+}
+
+#[test]
+fn workdir_is_quoted_into_a_login_shell() {
+    let session = SessionOptions {
+        workdir: Some("/srv/my app".to_owned()),
+        ..Default::default()
+    };
+    let invocation = ssh::connect(
+        &config(),
+        &key_server(),
+        &ConnOptions::default(),
+        &session,
+        &[],
+    )
+    .unwrap();
+    assert!(invocation.args.contains(&"-t".to_owned()));
+    assert_eq!(
+        invocation.args.last().unwrap(),
+        "cd '/srv/my app' || echo 'jump: cannot cd to /srv/my app' >&2; exec \"$SHELL\" -l"
+    );
+}
+
+#[test]
+fn remote_command_wins_over_workdir() {
+    let mut server = key_server();
+    server.remote_command = Some("tmux attach".to_owned());
+    server.workdir = Some("/srv/app".to_owned());
+    let invocation = ssh::connect(
+        &config(),
+        &server,
+        &ConnOptions::default(),
+        &SessionOptions::default(),
+        &[],
+    )
+    .unwrap();
+    assert_eq!(invocation.args.last().unwrap(), "tmux attach");
+}
+
+#[test]
+fn windows_servers_get_no_workdir_wrapper() {
+    let mut server = key_server();
+    server.platform = Some(Platform::Windows);
+    server.workdir = Some("C:\\work".to_owned());
+    let invocation = ssh::connect(
+        &config(),
+        &server,
+        &ConnOptions::default(),
+        &SessionOptions::default(),
+        &[],
+    )
+    .unwrap();
+    assert_eq!(invocation.args.last().unwrap(), "deploy@10.0.0.1");
+}
+
+#[test]
+fn network_appliances_get_a_tty_and_legacy_scp() {
+    let mut server = key_server();
+    server.platform = Some(Platform::NetworkOs);
+    let exec = ssh::exec(
+        &config(),
+        &server,
+        &ConnOptions::default(),
+        &strings(&["show version"]),
+        ExecOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(exec.args[4], "-t");
+    let copy = ssh::copy(
+        &config(),
+        &server,
+        &ConnOptions::default(),
+        "web:startup-config",
+        ".",
+        false,
+    )
+    .unwrap();
+    assert_eq!(copy.args[4], "-O");
+
+    server.platform = Some(Platform::Linux);
+    let copy = ssh::copy(
+        &config(),
+        &server,
+        &ConnOptions::default(),
+        "web:a",
+        ".",
+        false,
+    )
+    .unwrap();
+    assert!(!copy.args.contains(&"-O".to_owned()));
+}
+
+#[test]
+fn session_env_overrides_stored_env() {
+    let mut server = key_server();
+    server.env = vec![("DEPLOY_ENV".to_owned(), "prod".to_owned())];
+    let session = SessionOptions {
+        env: vec![("DEPLOY_ENV".to_owned(), "staging".to_owned())],
+        ..Default::default()
+    };
+    let invocation =
+        ssh::connect(&config(), &server, &ConnOptions::default(), &session, &[]).unwrap();
+    assert_eq!(
+        invocation.env,
+        [("DEPLOY_ENV".to_owned(), "staging".to_owned())]
+    );
+    assert!(invocation.args.contains(&"SendEnv=DEPLOY_ENV".to_owned()));
+}
+
+#[test]
+fn local_command_is_one_option_value() {
+    let mut server = key_server();
+    server.local_command = Some("tmux rename-window '%n' && echo 50% done".to_owned());
+    let invocation = ssh::connect(
+        &config(),
+        &server,
+        &ConnOptions::default(),
+        &SessionOptions::default(),
+        &[],
+    )
+    .unwrap();
+    assert_eq!(
+        invocation.args[4..8],
+        strings(&[
+            "-o",
+            "PermitLocalCommand=yes",
+            "-o",
+            "LocalCommand=tmux rename-window '%n' && echo 50%% done",
+        ])
+    );
+    assert!(invocation
+        .env
+        .contains(&("JUMP_SERVER_NAME".to_owned(), "web".to_owned())));
+    assert_eq!(
+        ssh::local_command_option("100%% %h %"),
+        "LocalCommand=100%% %h %%"
+    );
+
+    let session = SessionOptions {
+        no_hooks: true,
+        ..Default::default()
+    };
+    let invocation =
+        ssh::connect(&config(), &server, &ConnOptions::default(), &session, &[]).unwrap();
+    assert!(!invocation
+        .args
+        .iter()
+        .any(|arg| arg.contains("LocalCommand")));
+}
+
+#[test]
+fn forbidden_agent_forwarding() {
+    let mut config = config();
+    config.forbid_agent_forwarding = true;
+    let result = ssh::connect(
+        &config,
+        &key_server(),
+        &ConnOptions::default(),
+        &SessionOptions::default(),
+        &strings(&["-o", "ForwardAgent=yes"]),
+    );
+    assert!(matches!(
+        result,
+        Err(JumpError::AgentForwardingForbidden { name }) if name == "web"
+    ));
+    assert!(ssh::forwards_agent(&strings(&["-oforwardagent=yes"])));
+    assert!(!ssh::forwards_agent(&strings(&["-o", "ForwardAgent=no"])));
+}
+
+#[test]
+fn exec_appends_the_command() {
+    let invocation = ssh::exec(
+        &config(),
+        &key_server(),
+        &ConnOptions::default(),
+        &strings(&["uptime"]),
+        ExecOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        invocation.args,
+        strings(&[
+            "-i",
+            "/keys/web",
+            "-o",
+            "IdentitiesOnly=yes",
+            "-p",
+            "2222",
+            "--",
+            "deploy@10.0.0.1",
+            "uptime"
+        ])
+    );
+}
+
+#[test]
+fn copy_translates_the_remote_side() {
+    assert_eq!(
+        ssh::remote_server_name("./build", "web:/srv").unwrap(),
+        "web"
+    );
+    assert!(ssh::remote_server_name("a:/x", "b:/y").is_err());
+    assert!(matches!(
+        ssh::remote_server_name("./a", "./b"),
+        Err(JumpError::InvalidInput { .. })
+    ));
+
+    let mut server = key_server();
+    server.server_address = "fe80::1".to_owned();
+    let invocation = ssh::copy(
+        &config(),
+        &server,
+        &ConnOptions::default(),
+        "./build",
+        "web:/srv",
+        true,
+    )
+    .unwrap();
+    assert_eq!(
+        invocation.args,
+        strings(&[
+            "-i",
+            "/keys/web",
+            "-o",
+            "IdentitiesOnly=yes",
+            "-P",
+            "2222",
+            "-r",
+            "--",
+            "./build",
+            "deploy@[fe80::1]:/srv",
+        ])
+    );
+}
+
+#[test]
+fn resumable_copy_gives_rsync_the_ssh_command_line() {
+    let mut server = key_server();
+    server.server_address = "fe80::1".to_owned();
+    let invocation = ssh::resumable_copy(
+        &config(),
+        &server,
+        &ConnOptions::default(),
+        "web:/srv/dump.sql",
+        ".",
+        false,
+    )
+    .unwrap();
+    assert_eq!(invocation.program, PathBuf::from(exe()));
+    assert_eq!(
+        invocation.args,
+        strings(&[
+            "--partial",
+            "--progress",
+            "--rsh",
+            &format!("{} -i /keys/web -o IdentitiesOnly=yes -p 2222", exe()),
+            "--",
+            "deploy@[fe80::1]:/srv/dump.sql",
+            ".",
+        ])
+    );
+
+    // sshpass wraps rsync, and so the ssh it starts
+    let invocation = ssh::resumable_copy(
+        &config(),
+        &password_server(),
+        &ConnOptions::default(),
+        "./dumps",
+        "web:/srv",
+        true,
+    )
+    .unwrap();
+    assert_eq!(
+        invocation.args,
+        strings(&[
+            "-e",
+            &exe(),
+            "--partial",
+            "--progress",
+            "--recursive",
+            "--rsh",
+            &format!("{} -p 2222", exe()),
+            "--",
+            "./dumps",
+            "deploy@10.0.0.1:/srv",
+        ])
+    );
+}
+
+#[test]
+fn shell_quoting() {
+    assert_eq!(ssh::shell_quote("plain-arg_1.txt"), "plain-arg_1.txt");
+    assert_eq!(ssh::shell_quote(""), "''");
+    assert_eq!(ssh::shell_quote("it's"), "'it'\\''s'");
+}
+
+#[test]
+fn askpass_only_answers_password_prompts() {
+    assert_eq!(
+        askpass::answer("deploy@10.0.0.1's password: ", "hunter2"),
+        Some("hunter2")
+    );
+    assert_eq!(
+        askpass::answer(
+            "Are you sure you want to continue connecting (yes/no)? ",
+            "hunter2"
+        ),
+        None
+    );
+}
+
+#[test]
+fn askpass_secret_is_redacted() {
+    let invocation = ssh::Invocation {
+        program: PathBuf::from("ssh"),
+        args: strings(&["deploy@10.0.0.1"]),
+        secret: None,
+        env: askpass::env("hunter2").unwrap(),
+    };
+    let printed = invocation.to_string();
+    assert!(printed.contains("JUMP_ASKPASS_PASSWORD=<redacted>"));
+    assert!(!printed.contains("hunter2"));
+}
+
+#[test]
+fn batch_mode_never_lets_ssh_ask() {
+    let batch = ExecOptions {
+        tty: false,
+        batch: Some(5),
+    };
+    let uptime = strings(&["uptime"]);
+    let invocation = ssh::exec(
+        &config(),
+        &key_server(),
+        &ConnOptions::default(),
+        &uptime,
+        batch,
+    )
+    .unwrap();
+    assert_eq!(
+        invocation.args,
+        strings(&[
+            "-i",
+            "/keys/web",
+            "-o",
+            "IdentitiesOnly=yes",
+            "-o",
+            "BatchMode=yes",
+            "-o",
+            "ConnectTimeout=5",
+            "-p",
+            "2222",
+            "--",
+            "deploy@10.0.0.1",
+            "uptime"
+        ])
+    );
+    // sshpass answers the password prompt BatchMode would turn away
+    let invocation = ssh::exec(
+        &config(),
+        &password_server(),
+        &ConnOptions::default(),
+        &uptime,
+        batch,
+    )
+    .unwrap();
+    assert!(!invocation.args.contains(&"BatchMode=yes".to_owned()));
+    assert!(invocation.args.contains(&"ConnectTimeout=5".to_owned()));
+}
+
+#[test]
+fn missing_binaries() {
+    let mut config = config();
+    config.binaries.ssh = Some(PathBuf::from("/nonexistent/ssh"));
+    let error = ssh::exec(
+        &config,
+        &key_server(),
+        &ConnOptions::default(),
+        &strings(&["uptime"]),
+        ExecOptions::default(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "ssh not found at /nonexistent/ssh (from config.toml)"
+    );
+    assert!(matches!(error, JumpError::ToolNotFound { tool, .. } if tool == "ssh"));
+}
+
+#[test]
+fn key_servers_only_offer_the_stored_key() {
+    let args = |server: &Server, options: &ConnOptions| {
+        ssh::connect(&config(), server, options, &SessionOptions::default(), &[])
+            .unwrap()
+            .args
+    };
+    let identities_only = "IdentitiesOnly=yes".to_owned();
+    let mut server = key_server();
+    assert!(args(&server, &ConnOptions::default()).contains(&identities_only));
+    let no_identities_only = ConnOptions {
+        no_identities_only: true,
+        ..Default::default()
+    };
+    assert!(!args(&server, &no_identities_only).contains(&identities_only));
+    assert!(!args(&password_server(), &ConnOptions::default()).contains(&identities_only));
+
+    // A stored value is the one ssh takes, so none is added after it
+    server.options = vec![("identitiesonly".to_owned(), "no".to_owned())];
+    let stored = args(&server, &ConnOptions::default());
+    assert!(!stored.contains(&identities_only));
+    assert!(stored.contains(&"identitiesonly=no".to_owned()));
+
+    server.options.clear();
+    server.identities_only = false;
+    assert!(!args(&server, &ConnOptions::default()).contains(&identities_only));
+}
+
+#[test]
+fn stored_fields_never_reach_ssh_as_options() {
+    let hostile = "-oProxyCommand=touch /tmp/pwned";
+    let mut servers = Vec::new();
+    for field in ["username", "address"] {
+        let mut server = key_server();
+        match field {
+            "username" => server.username = hostile.to_owned(),
+            _ => server.server_address = hostile.to_owned(),
+        }
+        servers.push(server);
+    }
+    for server in &servers {
+        let options = ConnOptions::default();
+        let invocations = [
+            ssh::connect(&config(), server, &options, &SessionOptions::default(), &[]).unwrap(),
+            ssh::exec(
+                &config(),
+                server,
+                &options,
+                &strings(&["uptime"]),
+                ExecOptions::default(),
+            )
+            .unwrap(),
+            ssh::copy(&config(), server, &options, "./a", "web:/b", false).unwrap(),
+        ];
+        for invocation in invocations {
+            // Everything before the end of the options comes from jump
+            let end = invocation
+                .args
+                .iter()
+                .position(|arg| arg == ssh::END_OF_OPTIONS)
+                .unwrap_or_else(|| panic!("no -- in {:?}", invocation.args));
+            assert!(
+                !invocation.args[..end]
+                    .iter()
+                    .any(|arg| arg.contains("ProxyCommand")),
+                "{:?}",
+                invocation.args
+            );
+            assert!(invocation.args[end + 1..]
+                .iter()
+                .any(|arg| arg.contains("ProxyCommand")));
+        }
+    }
+}
+
+#[test]
+fn every_builder_goes_through_the_via_chain() {
+    let options = ConnOptions {
+        via: strings(&["ops@bastion.example:2200", "deploy@[fe80::2]:2022"]),
+        ..ConnOptions::default()
+    };
+    let jump = strings(&[
+        "-o",
+        "ProxyJump=ops@bastion.example:2200,deploy@[fe80::2]:2022",
+    ]);
+    let invocations = [
+        ssh::connect(
+            &config(),
+            &key_server(),
+            &options,
+            &SessionOptions::default(),
+            &[],
+        )
+        .unwrap(),
+        ssh::exec(
+            &config(),
+            &key_server(),
+            &options,
+            &strings(&["uptime"]),
+            ExecOptions::default(),
+        )
+        .unwrap(),
+        ssh::copy(&config(), &key_server(), &options, "./a", "web:/b", false).unwrap(),
+    ];
+    for invocation in invocations {
+        assert_eq!(invocation.args[2..4], jump, "{:?}", invocation.args);
+        // The target keeps its own port
+        assert!(invocation.args.windows(2).any(|pair| pair[1] == "2222"));
+    }
+
+    let invocation = ssh::resumable_copy(
+        &config(),
+        &key_server(),
+        &options,
+        "web:/srv/dump.sql",
+        ".",
+        false,
+    )
+    .unwrap();
+    assert_eq!(
+        invocation.args[3],
+        format!(
+            "{} -i /keys/web -o 'ProxyJump=ops@bastion.example:2200,deploy@[fe80::2]:2022' \
+             -o IdentitiesOnly=yes -p 2222",
+            exe()
+        )
+    );
+
+    // sshpass only answers the target's password prompt
+    let invocation = ssh::connect(
+        &config(),
+        &password_server(),
+        &options,
+        &SessionOptions::default(),
+        &[],
+    )
+    .unwrap();
+    assert!(invocation
+        .args
+        .contains(&"ProxyJump=ops@bastion.example:2200,deploy@[fe80::2]:2022".to_owned()));
+}
+
+#[test]
+fn stored_servers_become_hops() {
+    let mut server = key_server();
+    assert_eq!(ssh::hop(&server), "deploy@10.0.0.1:2222");
+    server.server_address = "fe80::1".to_owned();
+    assert_eq!(ssh::hop(&server), "deploy@[fe80::1]:2222");
+
+    for hop in ["bastion", "ops@10.0.0.9:2200", "[fe80::1%eth0]:22"] {
+        assert_eq!(ssh::parse_hop(hop).unwrap(), hop);
+    }
+    for hop in ["", "-oProxyCommand=sh", "a;touch x", "a b", "$(id)"] {
+        assert!(ssh::parse_hop(hop).is_err(), "{}", hop);
+    }
+}
+
+#[test]
+fn sessions_end_at_a_dry_run_or_a_failed_hook() {
+    let store = Store::open_in_memory().unwrap();
+    store.initialize().unwrap();
+    store.add(&key_server()).unwrap();
+    let session = |config: &Config, options: &ConnOptions| {
+        connect::session(
+            &store,
+            config,
+            Style::plain(),
+            "web",
+            options,
+            &SessionOptions::default(),
+            &[],
+        )
+        .unwrap()
+    };
+
+    let dry_run = ConnOptions {
+        dry_run: true,
+        ..ConnOptions::default()
+    };
+    match session(&config(), &dry_run) {
+        Outcome::DryRun {
+            server,
+            invocation: Some(invocation),
+        } => {
+            assert_eq!(server.server_name, "web");
+            assert!(invocation.args.contains(&"deploy@10.0.0.1".to_owned()));
+        }
+        outcome => panic!("{:?}", outcome),
+    }
+
+    let config = Config {
+        pre_connect: Some("exit 3".to_owned()),
+        ..config()
+    };
+    match session(&config, &ConnOptions::default()) {
+        Outcome::HookFailed { hook, status } => {
+            assert_eq!(hook, "exit 3");
+            assert_eq!(status.code(), Some(3));
+        }
+        outcome => panic!("{:?}", outcome),
+    }
+}
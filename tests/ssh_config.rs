@@ -0,0 +1,106 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use jump::ssh_config::{declares, parse, settings, Settings};
+
+use common::{home, jump, run};
+
+const CONFIG: &str = "\
+User fallback
+
+Host prod-web staging-*
+    HostName 10.1.1.5
+    Port=2222
+
+Match host db
+    User matched
+
+Host db !db-old
+    HostName %h.internal
+    User \"ops\"
+";
+
+#[test]
+fn settings_come_from_the_first_matching_value() {
+    let hosts = parse(CONFIG);
+    assert!(declares(&hosts, "prod-web"));
+    assert!(declares(&hosts, "DB"));
+    assert!(!declares(&hosts, "staging-1"));
+    assert_eq!(
+        settings(&hosts, "prod-web"),
+        Settings {
+            host_name: Some("10.1.1.5".to_owned()),
+            user: Some("fallback".to_owned()),
+            port: Some(2222),
+        }
+    );
+    assert_eq!(settings(&hosts, "staging-1").port, Some(2222));
+    assert_eq!(
+        settings(&hosts, "db"),
+        Settings {
+            host_name: Some("db.internal".to_owned()),
+            user: Some("fallback".to_owned()),
+            port: None,
+        }
+    );
+    // The negated pattern keeps the block from matching
+    assert_eq!(settings(&hosts, "db-old").host_name, None);
+}
+
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    fs::create_dir_all(home.join(".ssh")).unwrap();
+    fs::write(
+        home.join(".ssh/config"),
+        "Host prod-web\n  HostName 10.1.1.5\n  User deploy\n  Port 2222\n  Include extra\n",
+    )
+    .unwrap();
+    fs::write(home.join(".ssh/extra"), "Host bastion\n  User jump\n").unwrap();
+    jump(&home, &["initialize"]);
+    home
+}
+
+#[test]
+fn config_servers_connect_by_their_alias() {
+    let home = store("ssh-config");
+    jump(&home, &["add", "prod-web", "--ssh-config"]);
+    jump(
+        &home,
+        &["add", "web", "--ssh-config=prod-web", "--tag", "prod"],
+    );
+    jump(&home, &["add", "bastion", "--ssh-config"]);
+
+    let plain = jump(&home, &["ls", "--plain"]);
+    assert!(plain.contains("web\tdeploy\t10.1.1.5\t2222\tssh-config\n"));
+    assert!(plain.contains("bastion\tjump\tbastion\t22\tssh-config\n"));
+    assert!(jump(&home, &["--color", "never", "show", "web"])
+        .contains("method:   ssh config Host prod-web"));
+
+    let conn = jump(&home, &["conn", "web", "--dry-run"]);
+    assert!(conn.trim_end().ends_with("ssh -- prod-web"), "{}", conn);
+    let exec = jump(&home, &["exec", "web", "--dry-run", "--", "uptime"]);
+    assert!(exec.trim_end().ends_with(" -- prod-web uptime"));
+    assert!(!exec.contains("-p"));
+    let cp = jump(&home, &["cp", "--dry-run", "app.conf", "web:/etc/app.conf"]);
+    assert!(cp
+        .trim_end()
+        .ends_with("scp -- app.conf prod-web:/etc/app.conf"));
+
+    let missing = run(&home, &["add", "nope", "--ssh-config"]);
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("there is no `Host nope` in"));
+    let method = run(&home, &["add", "app", "--ssh-config", "ssh-key"]);
+    assert_eq!(method.status.code(), Some(2));
+}
+
+#[test]
+fn verify_reports_aliases_gone_from_the_config() {
+    let home = store("ssh-config-verify");
+    jump(&home, &["add", "web", "--ssh-config=prod-web"]);
+    jump(&home, &["verify"]);
+    fs::write(home.join(".ssh/config"), "Host prod-app\n").unwrap();
+    let output = run(&home, &["--color", "never", "verify"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout)
+        .contains("web\n  error: there is no `Host prod-web` in"));
+}
@@ -0,0 +1,461 @@
+use std::path::PathBuf;
+
+use jump::{
+    audit::LogEntry,
+    error::JumpError,
+    server::{Address, ConnectMethods, Custom, Password, Platform, SSHKey, Server, SshConfig, X11},
+    store::{Place, Store},
+};
+
+fn server(name: &str, method: ConnectMethods) -> Server {
+    Server::new(
+        name.to_owned(),
+        "deploy".to_owned(),
+        "10.0.0.1".to_owned(),
+        22,
+        method,
+    )
+}
+
+fn key(path: &str) -> ConnectMethods {
+    ConnectMethods::SSHKey(SSHKey {
+        path: PathBuf::from(path),
+    })
+}
+
+fn store() -> Store {
+    let store = Store::open_in_memory().unwrap();
+    store.initialize().unwrap();
+    store
+}
+
+#[test]
+fn add_and_find() {
+    let store = store();
+    let mut web = server("web", key("/keys/web"));
+    web.port = 2222;
+    web.x11 = X11::Trusted;
+    web.workdir = Some("/srv/app".to_owned());
+    web.platform = Some(Platform::NetworkOs);
+    web.note = Some("behind the load balancer".to_owned());
+    store.add(&web).unwrap();
+
+    let found = store.find("web").unwrap().unwrap();
+    assert_eq!(found.server_name, "web");
+    assert_eq!(found.username, "deploy");
+    assert_eq!(found.port, 2222);
+    assert_eq!(found.x11, X11::Trusted);
+    assert_eq!(found.workdir.as_deref(), Some("/srv/app"));
+    assert_eq!(found.platform, Some(Platform::NetworkOs));
+    assert_eq!(found.note.as_deref(), Some("behind the load balancer"));
+    match found.method {
+        ConnectMethods::SSHKey(SSHKey { path }) => assert_eq!(path, PathBuf::from("/keys/web")),
+        _ => panic!("expected an ssh key"),
+    }
+}
+
+#[test]
+fn password_round_trips() {
+    let store = store();
+    let db = server(
+        "db",
+        ConnectMethods::Password(Password {
+            password: "hun:ter2".to_owned(),
+        }),
+    );
+    store.add(&db).unwrap();
+    match store.get("db").unwrap().method {
+        ConnectMethods::Password(Password { password }) => assert_eq!(password, "hun:ter2"),
+        _ => panic!("expected a password"),
+    }
+}
+
+#[test]
+fn custom_command_round_trips() {
+    let store = store();
+    let command = "aws ssm start-session --target {address}: --region eu-west-1";
+    let bastion = server(
+        "bastion",
+        ConnectMethods::Command(Custom {
+            command: command.to_owned(),
+        }),
+    );
+    store.add(&bastion).unwrap();
+    match store.get("bastion").unwrap().method {
+        ConnectMethods::Command(custom) => assert_eq!(custom.command, command),
+        _ => panic!("expected a custom command"),
+    }
+}
+
+#[test]
+fn windows_key_paths_round_trip() {
+    let store = store();
+    store
+        .add(&server("win", key(r"C:\Users\me\.ssh\id_ed25519")))
+        .unwrap();
+    match store.get("win").unwrap().method {
+        ConnectMethods::SSHKey(SSHKey { path }) => {
+            assert_eq!(path, PathBuf::from(r"C:\Users\me\.ssh\id_ed25519"))
+        }
+        _ => panic!("expected an ssh key"),
+    }
+}
+
+#[test]
+fn missing_server() {
+    let store = store();
+    assert!(store.find("nope").unwrap().is_none());
+    let error = store.get("nope").unwrap_err();
+    assert_eq!(error.to_string(), "No server named nope");
+    assert!(matches!(error, JumpError::NotFound { name } if name == "nope"));
+}
+
+#[test]
+fn duplicate_names_are_rejected() {
+    let store = store();
+    store.add(&server("web", key("/keys/web"))).unwrap();
+    let error = store.add(&server("web", key("/keys/other"))).unwrap_err();
+    assert!(matches!(error, JumpError::Duplicate { name } if name == "web"));
+}
+
+#[test]
+fn fields_that_look_like_ssh_options_are_rejected() {
+    let store = store();
+    let hostile = "-oProxyCommand=touch /tmp/pwned";
+    let mut cases = Vec::new();
+    for field in ["name", "username", "address", "fallback"] {
+        let mut web = server("web", key("/keys/web"));
+        match field {
+            "name" => web.server_name = hostile.to_owned(),
+            "username" => web.username = hostile.to_owned(),
+            "address" => web.server_address = hostile.to_owned(),
+            _ => web.addresses.push(Address {
+                address: hostile.to_owned(),
+                label: None,
+            }),
+        }
+        cases.push(web);
+    }
+    let mut aliased = server(
+        "prod",
+        ConnectMethods::SshConfig(SshConfig {
+            alias: hostile.to_owned(),
+        }),
+    );
+    aliased.server_address = "prod".to_owned();
+    cases.push(aliased);
+    for case in &cases {
+        let error = store.add(case).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("which ssh would take for an option"),
+            "{}",
+            error
+        );
+    }
+    assert!(store.list().unwrap().is_empty());
+
+    store.add(&server("web", key("/keys/web"))).unwrap();
+    let mut web = store.get("web").unwrap();
+    web.username = "-l root".to_owned();
+    assert!(store.update(&web).is_err());
+    let fallback = Address {
+        address: "-J evil".to_owned(),
+        label: None,
+    };
+    assert!(store.add_address("web", &fallback).is_err());
+    assert_eq!(store.get("web").unwrap().username, "deploy");
+}
+
+#[test]
+fn uninitialized_store() {
+    let store = Store::open_in_memory().unwrap();
+    assert!(matches!(store.list(), Err(JumpError::Store(_))));
+}
+
+#[test]
+fn list_and_remove() {
+    let store = store();
+    store.add(&server("web", key("/keys/web"))).unwrap();
+    store.add(&server("db", key("/keys/db"))).unwrap();
+    let names = store
+        .list()
+        .unwrap()
+        .into_iter()
+        .map(|server| server.server_name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["web", "db"]);
+
+    assert!(store.remove("web").unwrap());
+    assert!(!store.remove("web").unwrap());
+    assert_eq!(store.list().unwrap().len(), 1);
+}
+
+#[test]
+fn ids_are_never_reused() {
+    let store = store();
+    store.add(&server("web", key("/keys/web"))).unwrap();
+    store.add(&server("db", key("/keys/db"))).unwrap();
+    store.remove("db").unwrap();
+    store.add(&server("cache", key("/keys/cache"))).unwrap();
+    assert_eq!(store.get("web").unwrap().id, Some(1));
+    assert_eq!(store.get("cache").unwrap().id, Some(3));
+
+    // A replaced server keeps its id
+    let mut web = store.get("web").unwrap();
+    store.remove("web").unwrap();
+    web.port = 2222;
+    store.add(&web).unwrap();
+    assert_eq!(store.get("web").unwrap().id, Some(1));
+}
+
+#[test]
+fn timestamps_are_kept_or_set_and_edits_update_them() {
+    let store = store();
+    let mut imported = server("web", key("/keys/web"));
+    imported.created_at = Some(1_000);
+    imported.updated_at = Some(2_000);
+    imported.tags = vec!["prod".to_owned()];
+    store.add(&imported).unwrap();
+    let web = store.get("web").unwrap();
+    assert_eq!((web.created_at, web.updated_at), (Some(1_000), Some(2_000)));
+
+    store.add(&server("db", key("/keys/db"))).unwrap();
+    let db = store.get("db").unwrap();
+    assert!(db.created_at.unwrap() > 2_000);
+    assert_eq!(db.created_at, db.updated_at);
+
+    store.remove_tag("web", "staging").unwrap();
+    assert_eq!(store.get("web").unwrap().updated_at, Some(2_000));
+    store.set_option("web", "ForwardAgent", "no").unwrap();
+    let web = store.get("web").unwrap();
+    assert_eq!(web.created_at, Some(1_000));
+    assert!(web.updated_at.unwrap() > 2_000);
+}
+
+#[test]
+fn options_and_env() {
+    let store = store();
+    store.add(&server("web", key("/keys/web"))).unwrap();
+    store
+        .set_option("web", "ServerAliveInterval", "30")
+        .unwrap();
+    store
+        .set_option("web", "ServerAliveInterval", "60")
+        .unwrap();
+    store.set_option("web", "Compression", "yes").unwrap();
+    store.set_env("web", "DEPLOY_ENV", "staging").unwrap();
+
+    let web = store.get("web").unwrap();
+    assert_eq!(
+        web.options,
+        [
+            ("Compression".to_owned(), "yes".to_owned()),
+            ("ServerAliveInterval".to_owned(), "60".to_owned()),
+        ]
+    );
+    assert_eq!(web.env, [("DEPLOY_ENV".to_owned(), "staging".to_owned())]);
+
+    store.unset_option("web", "Compression").unwrap();
+    store.unset_env("web", "DEPLOY_ENV").unwrap();
+    let web = store.get("web").unwrap();
+    assert_eq!(web.options.len(), 1);
+    assert!(web.env.is_empty());
+}
+
+#[test]
+fn invalid_option_names_are_rejected() {
+    let store = store();
+    store.add(&server("web", key("/keys/web"))).unwrap();
+    assert!(matches!(
+        store.set_option("web", "Bad=Key", "x"),
+        Err(JumpError::InvalidInput { field, .. }) if field == "ssh option name"
+    ));
+    assert!(matches!(
+        store.set_option("nope", "Compression", "yes"),
+        Err(JumpError::NotFound { .. })
+    ));
+}
+
+#[test]
+fn tags() {
+    let store = store();
+    let mut web = server("web", key("/keys/web"));
+    web.tags = vec!["prod".to_owned(), "eu".to_owned()];
+    store.add(&web).unwrap();
+    store.add_tag("web", "prod").unwrap();
+    store.add_tag("web", "frontend").unwrap();
+    assert_eq!(store.get("web").unwrap().tags, ["eu", "frontend", "prod"]);
+
+    store.remove_tag("web", "eu").unwrap();
+    assert_eq!(store.tags("web").unwrap(), ["frontend", "prod"]);
+    assert!(matches!(
+        store.add_tag("web", "two words"),
+        Err(JumpError::InvalidInput { field, .. }) if field == "tag"
+    ));
+}
+
+#[test]
+fn removing_a_server_drops_its_options() {
+    let store = store();
+    store.add(&server("web", key("/keys/web"))).unwrap();
+    store.set_option("web", "Compression", "yes").unwrap();
+    store.remove("web").unwrap();
+    store.add(&server("web", key("/keys/web"))).unwrap();
+    assert!(store.get("web").unwrap().options.is_empty());
+}
+
+fn entry(server_name: &str, started_at: i64) -> LogEntry {
+    LogEntry {
+        server_name: server_name.to_owned(),
+        local_user: "alice".to_owned(),
+        command: "conn".to_owned(),
+        method: "ssh-key".to_owned(),
+        started_at,
+        ended_at: None,
+        exit_code: None,
+        confirmed: None,
+        no_limit: None,
+        ended_by: None,
+        override_window: None,
+    }
+}
+
+#[test]
+fn connection_log() {
+    let store = store();
+    let first = store.log_start(&entry("web", 100)).unwrap();
+    store.log_start(&entry("db", 200)).unwrap();
+    store.log_start(&entry("web", 300)).unwrap();
+    store.log_end(first, 160, Some(255)).unwrap();
+
+    let all = store.log(None, None, None).unwrap();
+    let starts = all.iter().map(|e| e.started_at).collect::<Vec<_>>();
+    assert_eq!(starts, [300, 200, 100]);
+    assert_eq!(all[2].ended_at, Some(160));
+    assert_eq!(all[2].exit_code, Some(255));
+    assert_eq!(all[0].exit_code, None);
+
+    assert_eq!(store.log(Some("web"), None, None).unwrap().len(), 2);
+    assert_eq!(store.log(None, Some(200), None).unwrap().len(), 2);
+    assert_eq!(store.log(None, None, Some(1)).unwrap()[0].started_at, 300);
+
+    assert_eq!(store.prune_log(200).unwrap(), 1);
+    assert_eq!(store.log(None, None, None).unwrap().len(), 2);
+
+    let id = store.log_start_pruning(&entry("db", 400), 300).unwrap();
+    let left = store.log(None, None, None).unwrap();
+    assert_eq!(left.len(), 2);
+    assert_eq!((left[0].started_at, left[1].started_at), (400, 300));
+    store.log_end(id, 460, Some(0)).unwrap();
+    assert_eq!(
+        store.log(None, None, Some(1)).unwrap()[0].ended_at,
+        Some(460)
+    );
+}
+
+#[test]
+fn children_come_with_every_server() {
+    let store = store();
+    let mut web = server("web", key("/keys/web"));
+    web.tags = vec!["prod".to_owned(), "eu".to_owned()];
+    store.add(&web).unwrap();
+    store.add(&server("db", key("/keys/db"))).unwrap();
+    store
+        .set_option("web", "ServerAliveInterval", "30")
+        .unwrap();
+    store.set_env("web", "LANG", "C").unwrap();
+
+    let web = store.get("web").unwrap();
+    assert_eq!(web.tags, ["eu", "prod"]);
+    assert_eq!(
+        web.options,
+        [("ServerAliveInterval".to_owned(), "30".to_owned())]
+    );
+    assert_eq!(web.env, [("LANG".to_owned(), "C".to_owned())]);
+    let listed = store.list().unwrap();
+    assert_eq!(listed[0].tags, web.tags);
+    assert!(listed[1].tags.is_empty() && listed[1].options.is_empty());
+}
+
+#[test]
+fn read_only_refuses_every_write() {
+    let dir = std::env::temp_dir().join(format!("jump-store-read-only-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("servers.db");
+    std::fs::remove_file(&path).ok();
+    let writable = Store::open(&path).unwrap();
+    writable.initialize().unwrap();
+    writable.add(&server("web", key("/keys/web"))).unwrap();
+    drop(writable);
+    let before = std::fs::read(&path).unwrap();
+
+    let store = Store::open_read_only(&path).unwrap();
+    assert!(store.is_read_only());
+    assert_eq!(store.list().unwrap().len(), 1);
+    let refused = [
+        store.add(&server("db", key("/keys/db"))),
+        store.remove("web").map(|_| ()),
+        store.set_option("web", "Compression", "yes"),
+        store.set_env("web", "DEPLOY_ENV", "staging"),
+        store.add_tag("web", "prod"),
+        store.log_start(&entry("web", 100)).map(|_| ()),
+        store.set_sync_state("commit", "abc"),
+        store.transaction(|| Ok(())),
+    ];
+    for result in refused {
+        assert!(matches!(result, Err(JumpError::ReadOnly)), "{:?}", result);
+    }
+    assert_eq!(std::fs::read(&path).unwrap(), before);
+}
+
+#[test]
+fn adding_at_a_taken_address_warns_or_is_refused() {
+    let store = store();
+    store
+        .add_checked(&server("web", key("/keys/web")), true)
+        .unwrap();
+    // Same address and port as web
+    let result = store.add_checked(&server("web2", key("/keys/web")), true);
+    match result {
+        Err(JumpError::DuplicateAddress {
+            address, others, ..
+        }) => {
+            assert_eq!(address, "10.0.0.1:22");
+            assert_eq!(others, ["web"]);
+        }
+        other => panic!("{:?}", other),
+    }
+    assert!(store.find("web2").unwrap().is_none());
+    store
+        .add_checked(&server("web2", key("/keys/web")), false)
+        .unwrap();
+    assert!(store.find("web2").unwrap().is_some());
+}
+
+#[test]
+fn servers_move_to_a_place_in_the_order() {
+    let store = store();
+    for name in ["a", "b", "c", "d"] {
+        store.add(&server(name, key("/keys/k"))).unwrap();
+    }
+    let order = |place: Place| {
+        store.move_server("b", place).unwrap();
+        store.order().unwrap().join(",")
+    };
+    assert_eq!(order(Place::At(1)), "b,a,c,d");
+    assert_eq!(order(Place::After("c".to_owned())), "a,c,b,d");
+    assert_eq!(order(Place::Before("d".to_owned())), "a,c,b,d");
+    assert_eq!(order(Place::Before("a".to_owned())), "b,a,c,d");
+    // Past the end is the end
+    assert_eq!(order(Place::At(9)), "a,c,d,b");
+    assert!(matches!(
+        store.move_server("b", Place::After("z".to_owned())),
+        Err(JumpError::NotFound { name }) if name == "z"
+    ));
+    assert!(matches!(
+        store.move_server("z", Place::At(1)),
+        Err(JumpError::NotFound { .. })
+    ));
+}
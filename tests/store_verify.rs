@@ -0,0 +1,106 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt};
+
+use rusqlite::Connection;
+use serde_json::Value;
+
+use common::{home, jump, run};
+
+#[test]
+fn verify_lists_problems_per_server_and_fails_on_errors() {
+    let home = home("store-verify");
+    jump(&home, &["initialize"]);
+    let key = home.join("id_web");
+    fs::write(&key, "key").unwrap();
+    fs::set_permissions(&key, fs::Permissions::from_mode(0o600)).unwrap();
+    let key = key.to_str().unwrap();
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key", key]);
+    jump(
+        &home,
+        &["add", "db", "deploy", "10.0.0.2", "password", "hunter2"],
+    );
+    jump(&home, &["tag", "add", "db", "prod"]);
+    assert_eq!(jump(&home, &["verify"]), "no problems found\n");
+
+    let db = home.join(".jump/servers.db");
+    let conn = Connection::open(&db).unwrap();
+    conn.execute_batch(
+        "PRAGMA foreign_keys = off;
+         update jump_servers set port = 70000, x11 = 'sideways', mac = 'zz'
+            where server_name = 'db';
+         insert into server_tags (server_id, tag) values (99, 'orphan');",
+    )
+    .unwrap();
+    drop(conn);
+
+    let output = run(&home, &["verify"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with(
+        "(store)\n  error: 1 row in server_tags belong to servers that are gone (delete the rows"
+    ));
+    assert!(stdout.contains(
+        "db\n  error: the port 70000 is outside 1-65535\n  \
+         error: the X11 mode `sideways` is not one jump knows\n  \
+         error: the MAC address `zz` is invalid\n"
+    ));
+    assert!(!stdout.contains("web"));
+    assert!(!stdout.contains("hunter2"));
+    assert!(stdout.ends_with("4 errors, 0 warnings\n"));
+
+    let json: Value =
+        serde_json::from_slice(&run(&home, &["--output", "json", "verify"]).stdout).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 4);
+    assert_eq!(json[0]["server_name"], Value::Null);
+    assert_eq!(json[1]["server_name"], "db");
+    assert_eq!(json[1]["severity"], "error");
+
+    // What is left needs a hand, so it still fails
+    let output = run(&home, &["verify", "--fix", "--yes"]);
+    assert_eq!(output.status.code(), Some(1));
+    let output = String::from_utf8(output.stdout).unwrap();
+    assert!(output.contains("done: delete the rows of removed servers"));
+    assert!(!output.contains("(store)"));
+}
+
+#[test]
+fn verify_checks_another_store_and_warns_about_old_rows() {
+    let home = home("store-verify-other");
+    fs::create_dir_all(&home).unwrap();
+    let db = home.join("exported.db");
+    assert!(!run(&home, &["verify", "--store", db.to_str().unwrap()])
+        .status
+        .success());
+
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &[
+            "add",
+            "web",
+            "deploy",
+            "10.0.0.1",
+            "ssh-key",
+            "/nonexistent",
+        ],
+    );
+    fs::copy(home.join(".jump/servers.db"), &db).unwrap();
+    let conn = Connection::open(&db).unwrap();
+    conn.execute_batch("update jump_servers set method = 'password:hunter2'")
+        .unwrap();
+    drop(conn);
+
+    let output = run(&home, &["verify", "--store", db.to_str().unwrap()]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(
+        "web\n  warning: the method is in an old format (store the method of web as `pass:…`)\n"
+    ));
+    assert!(!stdout.contains("hunter2"));
+    assert!(stdout.ends_with("0 errors, 1 warning\n"));
+    // The profile's own store is left alone
+    assert!(!run(&home, &["verify"]).status.success());
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use jump::{
+    server::{ConnectMethods, SSHKey, Server},
+    sync::{self, Change, Servers},
+};
+
+use common::{command, home, jump};
+
+fn server(name: &str, address: &str) -> Server {
+    Server::new(
+        name.to_owned(),
+        "deploy".to_owned(),
+        address.to_owned(),
+        22,
+        ConnectMethods::SSHKey(SSHKey {
+            path: PathBuf::from("/keys/web"),
+        }),
+    )
+}
+
+fn servers(list: &[(&str, &str)]) -> Servers {
+    sync::servers(
+        list.iter()
+            .map(|(name, address)| server(name, address))
+            .collect(),
+    )
+}
+
+fn names(changes: &[Change]) -> Vec<String> {
+    changes
+        .iter()
+        .map(|change| match change {
+            Change::Take {
+                name, server: None, ..
+            } => format!("remove {}", name),
+            Change::Take { name, existed, .. } => {
+                format!("{} {}", if *existed { "update" } else { "add" }, name)
+            }
+            Change::Conflict { name, .. } => format!("conflict {}", name),
+        })
+        .collect()
+}
+
+#[test]
+fn one_sided_changes_are_taken() {
+    let base = servers(&[("web", "10.0.0.1"), ("old", "10.0.0.3")]);
+    let local = servers(&[
+        ("web", "10.0.0.1"),
+        ("old", "10.0.0.3"),
+        ("mine", "10.0.0.4"),
+    ]);
+    let remote = servers(&[("web", "10.0.0.9"), ("theirs", "10.0.0.5")]);
+    let changes = sync::merge(Some(&base), &local, &remote);
+    assert_eq!(names(&changes), ["remove old", "add theirs", "update web"]);
+}
+
+#[test]
+fn changes_on_both_sides_conflict() {
+    let base = servers(&[("web", "10.0.0.1"), ("db", "10.0.0.2")]);
+    let local = servers(&[("web", "10.0.0.8"), ("db", "10.0.0.6")]);
+    let remote = servers(&[("web", "10.0.0.9"), ("db", "10.0.0.6")]);
+    let changes = sync::merge(Some(&base), &local, &remote);
+    assert_eq!(names(&changes), ["conflict web"]);
+    let Change::Conflict {
+        local: Some(local),
+        remote: Some(remote),
+        ..
+    } = &changes[0]
+    else {
+        panic!("{:?}", changes);
+    };
+    assert_eq!(sync::differences(local, remote), ["server_address"]);
+}
+
+#[test]
+fn without_a_base_only_additions_are_taken() {
+    let local = servers(&[("web", "10.0.0.1"), ("mine", "10.0.0.4")]);
+    let remote = servers(&[("web", "10.0.0.9"), ("theirs", "10.0.0.5")]);
+    let changes = sync::merge(None, &local, &remote);
+    assert_eq!(names(&changes), ["add theirs", "conflict web"]);
+}
+
+fn git(dir: &Path, args: &[&str]) -> bool {
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// `jump sync` in `home`, with a git identity for its commits
+fn sync(home: &Path, args: &[&str]) -> String {
+    let output = command(home)
+        .arg("sync")
+        .args(args)
+        .env("GIT_AUTHOR_NAME", "jump")
+        .env("GIT_AUTHOR_EMAIL", "jump@localhost")
+        .env("GIT_COMMITTER_NAME", "jump")
+        .env("GIT_COMMITTER_EMAIL", "jump@localhost")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn stores_sync_through_a_remote() {
+    let remote = home("sync-remote");
+    if !git(
+        Path::new("."),
+        &["init", "--quiet", "--bare", remote.to_str().unwrap()],
+    ) {
+        eprintln!("git is unavailable, skipping");
+        return;
+    }
+    let remote = remote.to_str().unwrap();
+
+    let laptop = home("sync-laptop");
+    jump(&laptop, &["initialize"]);
+    jump(&laptop, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    jump(
+        &laptop,
+        &["add", "db", "admin", "10.0.0.2", "password", "hunter2"],
+    );
+    sync(&laptop, &["init", remote]);
+    sync(&laptop, &["push"]);
+    let file = laptop.join(".jump/sync/default/jump/servers.json");
+    assert!(!std::fs::read_to_string(file).unwrap().contains("hunter2"));
+
+    let desktop = home("sync-desktop");
+    jump(&desktop, &["initialize"]);
+    jump(&desktop, &["add", "web", "deploy", "10.0.0.9", "ssh-key"]);
+    sync(&desktop, &["init", remote]);
+    assert_eq!(sync(&desktop, &["pull"]), "conflict web: server_address\n");
+    // Unsettled, the conflict is reported again while the addition is not
+    assert_eq!(sync(&desktop, &["pull"]), "conflict web: server_address\n");
+    assert_eq!(jump(&desktop, &["ls", "--plain"]).lines().count(), 2);
+    sync(&desktop, &["pull", "--theirs"]);
+    assert!(jump(&desktop, &["show", "web"]).contains("10.0.0.1"));
+
+    jump(&desktop, &["rm", "--yes", "db"]);
+    sync(&desktop, &["push"]);
+    sync(&laptop, &["pull"]);
+    assert_eq!(
+        jump(&laptop, &["ls", "--plain"]),
+        "web\tdeploy\t10.0.0.1\t22\tssh-key\n"
+    );
+    assert_eq!(sync(&laptop, &["pull"]), "");
+}
+
+#[test]
+fn the_stores_own_times_are_not_synced() {
+    let remote = home("sync-times-remote");
+    if !git(
+        Path::new("."),
+        &["init", "--quiet", "--bare", remote.to_str().unwrap()],
+    ) {
+        eprintln!("git is unavailable, skipping");
+        return;
+    }
+    let remote = remote.to_str().unwrap();
+
+    let laptop = home("sync-times-laptop");
+    jump(&laptop, &["initialize"]);
+    jump(&laptop, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    sync(&laptop, &["init", remote]);
+    sync(&laptop, &["push"]);
+    let file = laptop.join(".jump/sync/default/jump/servers.json");
+    assert!(!std::fs::read_to_string(file)
+        .unwrap()
+        .contains("\"created_at\": 1"));
+
+    // The same server, added a second later
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    let desktop = home("sync-times-desktop");
+    jump(&desktop, &["initialize"]);
+    jump(&desktop, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    sync(&desktop, &["init", remote]);
+    assert_eq!(sync(&desktop, &["pull"]), "");
+
+    // Taken servers are stamped with when they were taken
+    jump(&laptop, &["add", "db", "admin", "10.0.0.2", "ssh-key"]);
+    sync(&laptop, &["push"]);
+    sync(&desktop, &["pull"]);
+    assert!(jump(&desktop, &["show", "db"]).contains("created: "));
+}
@@ -0,0 +1,88 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use common::{command, home, jump};
+
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    for name in ["web1", "web2", "web3"] {
+        jump(
+            &home,
+            &["add", name, "deploy", "10.0.0.1", "--tag", "web", "ssh-key"],
+        );
+    }
+    jump(&home, &["add", "db", "admin", "10.0.0.2", "ssh-key"]);
+    home
+}
+
+/// A tmux that logs its arguments, hands out fixed ids and cannot split
+/// for web2
+fn fake_tmux(home: &Path) -> PathBuf {
+    let tmux = home.join("tmux");
+    fs::write(
+        &tmux,
+        format!(
+            "#!/bin/sh\n\
+             echo \"$@\" >> {log}\n\
+             case \"$*\" in\n\
+             *'conn web2'*) echo 'no space for new pane' >&2; exit 1 ;;\n\
+             new-*) echo '$1 @7' ;;\n\
+             esac\n",
+            log = home.join("tmux.log").display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&tmux, fs::Permissions::from_mode(0o755)).unwrap();
+    tmux
+}
+
+#[test]
+fn panes_open_inside_tmux_and_failures_are_reported() {
+    let home = store("tmux-panes");
+    let tmux = fake_tmux(&home);
+    let output = command(&home)
+        .env("JUMP_TMUX_BIN", &tmux)
+        .env("TMUX", "/tmp/tmux-1000/default,1,0")
+        .args(["conn", "--group", "web", "--tmux", "panes", "--sync"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("could not open web2"));
+    let log = fs::read_to_string(home.join("tmux.log")).unwrap();
+    let lines = log.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 5, "{}", log);
+    assert!(lines[0].starts_with("new-window -P -F #{session_id} #{window_id} -n web "));
+    assert!(lines[0].ends_with(" --profile default conn web1"));
+    assert!(lines[1].starts_with("split-window -t @7 "));
+    assert!(lines[2].ends_with("conn web3"));
+    assert_eq!(lines[3], "select-layout -t @7 tiled");
+    assert_eq!(lines[4], "set-window-option -t @7 synchronize-panes on");
+}
+
+#[test]
+fn windows_start_a_session_outside_tmux() {
+    let home = store("tmux-windows");
+    let tmux = fake_tmux(&home);
+    let output = command(&home)
+        .env("JUMP_TMUX_BIN", &tmux)
+        .env_remove("TMUX")
+        .args(["conn", "--group", "web", "--tmux", "windows", "--dry-run"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 4, "{}", stdout);
+    assert!(lines[0].contains(" new-session -d -s jump-web -P "));
+    assert!(lines[1].contains(" new-window -t '<session>' -n web2 "));
+    assert!(lines[3].ends_with(" attach-session -t '<session>'"));
+    assert!(!home.join("tmux.log").exists());
+}
@@ -0,0 +1,156 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use common::{command, home, jump};
+use jump::{
+    config::Config,
+    copy::{self, Upload},
+    report::Outcome,
+    schedule::Limits,
+    ssh::ConnOptions,
+    store::Store,
+};
+
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    for (name, address) in [
+        ("web1", "10.0.0.1"),
+        ("web2", "10.0.0.2"),
+        ("web3", "10.0.0.3"),
+    ] {
+        jump(
+            &home,
+            &["add", name, "deploy", address, "--tag", "web", "ssh-key"],
+        );
+    }
+    home
+}
+
+/// An scp that fails for 10.0.0.2
+fn fake_scp(home: &Path) -> PathBuf {
+    let scp = home.join("scp");
+    fs::write(
+        &scp,
+        "#!/bin/sh\n\
+         case \"$*\" in\n\
+         *10.0.0.2:*) echo 'scp: /etc/nginx: Permission denied' >&2; exit 1 ;;\n\
+         esac\n",
+    )
+    .unwrap();
+    fs::set_permissions(&scp, fs::Permissions::from_mode(0o755)).unwrap();
+    scp
+}
+
+fn upload(home: &Path, args: &[&str]) -> (Option<i32>, String) {
+    let output = command(home)
+        .env("JUMP_SCP_BIN", fake_scp(home))
+        .args([
+            "--color",
+            "never",
+            "cp",
+            "--to",
+            "tag:web",
+            "nginx.conf",
+            "/etc/nginx/nginx.conf",
+        ])
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.code(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn every_host_is_reported_and_failures_fail_the_run() {
+    let home = store("upload-continue");
+    let (code, stdout) = upload(&home, &["--continue-on-error"]);
+    assert_eq!(code, Some(1));
+    assert!(stdout.contains("web1  ok\n"));
+    assert!(stdout.contains("web2  failed\nscp: /etc/nginx: Permission denied\n"));
+    assert!(stdout.contains("web3  ok\n"));
+}
+
+#[test]
+fn the_rest_is_skipped_after_a_failure() {
+    let home = store("upload-fail-fast");
+    let (code, stdout) = upload(&home, &["--jobs", "1"]);
+    assert_eq!(code, Some(1));
+    assert_eq!(
+        stdout,
+        "[1/3] web1  ok\n[2/3] web2  failed\nscp: /etc/nginx: Permission denied\nweb3  skipped\n"
+    );
+}
+
+#[test]
+fn dry_run_prints_each_upload() {
+    let home = store("upload-dry-run");
+    let (code, stdout) = upload(&home, &["--dry-run"]);
+    assert_eq!(code, Some(0));
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].ends_with(" nginx.conf deploy@10.0.0.2:/etc/nginx/nginx.conf"));
+}
+
+#[test]
+fn uploads_are_handed_over_as_they_finish() {
+    let home = store("upload-library");
+    let store = Store::open(&home.join(".jump/servers.db")).unwrap();
+    let servers = store.list().unwrap();
+    let mut config = Config::default();
+    config.binaries.scp = Some(fake_scp(&home));
+    let upload = Upload {
+        source: "nginx.conf".to_owned(),
+        destination: "/etc/nginx/nginx.conf".to_owned(),
+        recursive: false,
+        continue_on_error: false,
+    };
+    let limits = Limits {
+        parallel: 1,
+        bastion_parallel: 1,
+        delay: Duration::ZERO,
+    };
+    let mut uploaded = vec![];
+    let hosts = copy::upload(
+        &store,
+        &config,
+        &servers,
+        &upload,
+        &ConnOptions::default(),
+        limits,
+        3,
+        vec![None; servers.len()],
+        |done| uploaded.push((done.server.server_name.clone(), done.message.to_owned())),
+    );
+    assert_eq!(
+        uploaded,
+        [
+            ("web1".to_owned(), String::new()),
+            (
+                "web2".to_owned(),
+                "scp: /etc/nginx: Permission denied\n".to_owned()
+            ),
+        ]
+    );
+    assert_eq!(
+        hosts
+            .iter()
+            .map(|host| (host.server_name.as_str(), host.outcome))
+            .collect::<Vec<_>>(),
+        [
+            ("web1", Outcome::Ok),
+            ("web2", Outcome::Failed),
+            ("web3", Outcome::Skipped),
+        ]
+    );
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use std::path::PathBuf;
+
+use jump::{
+    qr,
+    server::{ConnectMethods, Custom, Password, SSHKey, Server},
+    ssh,
+};
+
+use common::{home, jump, run};
+
+fn server(username: &str, address: &str, method: ConnectMethods) -> Server {
+    Server::new(
+        "web".to_owned(),
+        username.to_owned(),
+        address.to_owned(),
+        2222,
+        method,
+    )
+}
+
+#[test]
+fn uris_escape_the_user_and_leave_out_secrets() {
+    let key = || {
+        ConnectMethods::SSHKey(SSHKey {
+            path: PathBuf::from("/keys/id"),
+        })
+    };
+    assert_eq!(
+        ssh::uri(&server("deploy", "web.example.com", key())).unwrap(),
+        "ssh://deploy@web.example.com:2222"
+    );
+    assert_eq!(
+        ssh::uri(&server("ad min:x@y", "fe80::1", key())).unwrap(),
+        "ssh://ad%20min%3Ax%40y@[fe80::1]:2222"
+    );
+    let password = ConnectMethods::Password(Password {
+        password: "hunter2".to_owned(),
+    });
+    assert!(!ssh::uri(&server("deploy", "10.0.0.1", password))
+        .unwrap()
+        .contains("hunter2"));
+    let custom = ConnectMethods::Command(Custom {
+        command: "aws ssm start-session --target {address}".to_owned(),
+    });
+    assert!(ssh::uri(&server("deploy", "i-0abc", custom)).is_err());
+}
+
+#[test]
+fn qr_codes_hold_their_format_and_size() {
+    let code = qr::encode(b"ssh://deploy@10.0.0.1:22").unwrap();
+    let size = code.size();
+    assert_eq!(size, 25);
+    // Level L with the mask chosen, BCH-coded, along the top-left finder
+    let format = (0..6)
+        .map(|y| code.dark(8, y))
+        .chain([code.dark(8, 7), code.dark(8, 8), code.dark(7, 8)])
+        .chain((0..6).rev().map(|x| code.dark(x, 8)))
+        .collect::<Vec<_>>();
+    let mirrored = (0..8)
+        .map(|i| code.dark(size - 1 - i, 8))
+        .chain((size - 7..size).map(|y| code.dark(8, y)))
+        .collect::<Vec<_>>();
+    assert_eq!(format, mirrored);
+    assert!(code.dark(8, size - 8), "the dark module");
+
+    let rendered = code.render();
+    assert_eq!(rendered.lines().count(), 17);
+    assert!(rendered.lines().all(|line| line.chars().count() == 33));
+    assert_eq!(qr::encode(&[b'a'; 100]).unwrap().size(), 37);
+    assert!(qr::encode(&[b'a'; 272]).is_err());
+}
+
+#[test]
+fn uri_prints_text_json_and_qr() {
+    let home = home("uri");
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &["add", "web", "deploy", "10.0.0.1", "password", "hunter2"],
+    );
+    assert_eq!(jump(&home, &["uri", "web"]), "ssh://deploy@10.0.0.1:22\n");
+    assert_eq!(
+        jump(&home, &["--output", "json", "uri", "web"]),
+        "{\n  \"server_name\": \"web\",\n  \"uri\": \"ssh://deploy@10.0.0.1:22\"\n}\n"
+    );
+    let qr = jump(&home, &["uri", "--qr", "web"]);
+    assert!(qr.ends_with("\nssh://deploy@10.0.0.1:22\n"));
+    assert!(qr.contains('▀'));
+    assert_eq!(run(&home, &["uri", "db"]).status.code(), Some(3));
+}
+
+#[test]
+fn uris_and_scp_style_strings_parse() {
+    let target = |username: &str, address: &str, port| ssh::Target {
+        username: Some(username.to_owned()),
+        address: address.to_owned(),
+        port,
+    };
+    assert_eq!(
+        ssh::parse_uri("ssh://deploy@203.0.113.7:2222"),
+        Ok(target("deploy", "203.0.113.7", Some(2222)))
+    );
+    assert_eq!(
+        ssh::parse_uri("deploy@web.example.com"),
+        Ok(target("deploy", "web.example.com", None))
+    );
+    assert_eq!(
+        ssh::parse_uri("SSH://ad%20min%3Ax@[fe80::1]:22/"),
+        Ok(target("ad min:x", "fe80::1", Some(22)))
+    );
+    assert_eq!(
+        ssh::parse_uri("root@fe80::1"),
+        Ok(target("root", "fe80::1", None))
+    );
+    let web = server(
+        "ad min:x@y",
+        "fe80::1",
+        ConnectMethods::Password(Password {
+            password: String::new(),
+        }),
+    );
+    assert_eq!(
+        ssh::parse_uri(&ssh::uri(&web).unwrap()),
+        Ok(target("ad min:x@y", "fe80::1", Some(2222)))
+    );
+
+    let error = |text: &str| ssh::parse_uri(text).unwrap_err();
+    assert!(error("deploy@host:22a").starts_with("the port `22a` is not a number"));
+    assert!(error("deploy@host:0").starts_with("the port `0`"));
+    assert!(error("sftp://deploy@host").starts_with("the scheme `sftp://`"));
+    assert_eq!(
+        ssh::parse_uri("ssh://host:22"),
+        Ok(ssh::Target {
+            username: None,
+            address: "host".to_owned(),
+            port: Some(22),
+        })
+    );
+    assert!(error("@host").starts_with("the user is empty"));
+    assert!(error("deploy@").starts_with("the host is missing"));
+    assert!(error("deploy@[::1").starts_with("the host `[::1` has no closing `]`"));
+    assert!(error("deploy@[::1]22").starts_with("`22` after the host"));
+    assert!(error("ssh://de%zzploy@host").starts_with("the user `de%zzploy`"));
+    assert!(error("deploy@host/path").contains("accepted formats"));
+}
+
+#[test]
+fn add_takes_a_uri_and_names_the_server_after_its_host() {
+    let home = home("uri-add");
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &["add", "--uri", "ssh://deploy@web.example.com:2222"],
+    );
+    jump(&home, &["add", "--uri", "root@web.example.com"]);
+    jump(&home, &["add", "--uri", "deploy@[fe80::1]:22", "lab"]);
+    jump(
+        &home,
+        &["add", "--uri", "admin@203.0.113.7", "password", "hunter2"],
+    );
+    assert_eq!(
+        jump(&home, &["ls", "--plain"]),
+        "web\tdeploy\tweb.example.com\t2222\tssh-key\n\
+         web-2\troot\tweb.example.com\t22\tssh-key\n\
+         lab\tdeploy\tfe80::1\t22\tssh-key\n\
+         203.0.113.7\tadmin\t203.0.113.7\t22\tpassword\n"
+    );
+
+    let both = run(&home, &["add", "--uri", "a@b", "name", "user"]);
+    assert_eq!(both.status.code(), Some(2));
+    let malformed = run(&home, &["add", "--uri", "deploy@host:22a"]);
+    assert_eq!(malformed.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&malformed.stderr).contains("the port `22a`"));
+    let no_method = run(&home, &["add", "db", "admin", "10.0.0.2"]);
+    assert!(String::from_utf8_lossy(&no_method.stderr).contains("a method is required"));
+}
@@ -0,0 +1,81 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Output,
+};
+
+use common::{command, home, jump};
+
+/// A store with one server, and `ssh` replaced by a binary that exits at once
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(
+        &home,
+        &["add", "web", "deploy", "10.0.0.1", "22", "ssh-key"],
+    );
+    let ssh = home.join("ssh");
+    fs::write(&ssh, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+    home
+}
+
+fn conn(home: &Path, flag: &str) -> Output {
+    let mut command = command(home);
+    command
+        .args(["conn", "web"])
+        .env("JUMP_SSH_BIN", home.join("ssh"));
+    if !flag.is_empty() {
+        command.arg(flag);
+    }
+    command.output().unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn progress_goes_to_stderr() {
+    let home = store("verbosity-normal");
+    let output = conn(&home, "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        stderr(&output),
+        "connecting to server...\nserver disconnected\n"
+    );
+}
+
+#[test]
+fn quiet_prints_nothing() {
+    let home = store("verbosity-quiet");
+    let output = conn(&home, "--quiet");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn verbose_shows_the_store_and_command() {
+    let home = store("verbosity-verbose");
+    let output = conn(&home, "-v");
+    assert!(output.stdout.is_empty());
+    let stderr = stderr(&output);
+    let db = home.join(".jump").join("servers.db");
+    assert!(stderr.contains(&format!("jump: store: {}\n", db.display())));
+    assert!(stderr.contains(&format!("jump: running {} ", home.join("ssh").display())));
+    assert!(stderr.contains("deploy@10.0.0.1"));
+    assert!(stderr.contains("jump: session lasted "));
+}
+
+#[test]
+fn quiet_and_verbose_conflict() {
+    let home = store("verbosity-conflict");
+    assert!(!conn(&home, "-qv").status.success());
+}
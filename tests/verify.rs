@@ -0,0 +1,101 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Output,
+};
+
+use common::{command, home, jump};
+
+/// An ssh that records its arguments and only reaches 10.0.0.1
+fn fake_ssh(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    let ssh = home.join("ssh");
+    fs::write(
+        &ssh,
+        format!(
+            "#!/bin/sh\nprintf '%s\\n' \"$*\" >> {}\n\
+             case \"$*\" in *10.0.0.1*) exit 0;; esac\n\
+             echo 'ssh: connect to host 10.0.0.9 port 22: Connection timed out' >&2\nexit 255\n",
+            home.join("ran").display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+    home
+}
+
+fn add(home: &Path, args: &[&str]) -> Output {
+    command(home)
+        .arg("add")
+        .args(args)
+        .env("JUMP_SSH_BIN", home.join("ssh"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn verify_connects_in_batch_mode_with_a_timeout() {
+    let home = fake_ssh("verify-ok");
+    let output = add(&home, &["--verify", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    assert!(output.status.success(), "{:?}", output);
+    let ran = fs::read_to_string(home.join("ran")).unwrap();
+    assert!(ran.contains("-o BatchMode=yes -o ConnectTimeout=5"));
+    assert!(ran.trim_end().ends_with("deploy@10.0.0.1 true"));
+
+    add(
+        &home,
+        &[
+            "--verify",
+            "--verify-timeout",
+            "2",
+            "api",
+            "deploy",
+            "10.0.0.1",
+            "ssh-key",
+        ],
+    );
+    assert!(fs::read_to_string(home.join("ran"))
+        .unwrap()
+        .contains("ConnectTimeout=2"));
+}
+
+#[test]
+fn a_failed_verify_keeps_the_server_unless_strict() {
+    let home = fake_ssh("verify-failed");
+    let output = add(&home, &["--verify", "db", "admin", "10.0.0.9", "ssh-key"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("could not connect to db, added it anyway: ssh: connect to host"));
+    assert!(jump(&home, &["ls", "--plain"]).starts_with("db\t"));
+
+    let output = add(
+        &home,
+        &["--verify=strict", "cache", "admin", "10.0.0.9", "ssh-key"],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Could not connect to cache, so it was not added"));
+    assert!(!jump(&home, &["ls", "--plain"]).contains("cache"));
+}
+
+#[test]
+fn password_servers_without_sshpass_cannot_be_verified() {
+    let home = fake_ssh("verify-password");
+    let output = command(&home)
+        .args(["add", "--verify=strict", "db", "admin", "10.0.0.1"])
+        .args(["password", "hunter2"])
+        .env("JUMP_SSH_BIN", home.join("ssh"))
+        .env("PATH", &home)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("through sshpass"));
+    assert!(!home.join("ran").exists());
+    assert_eq!(jump(&home, &["ls", "--plain"]), "");
+}
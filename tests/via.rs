@@ -0,0 +1,72 @@
+mod common;
+
+use std::path::PathBuf;
+
+use rusqlite::Connection;
+
+use common::{home, jump, run};
+
+/// A store with a target `db` and two bastions, the second on its own port
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["init"]);
+    jump(&home, &["add", "db", "deploy", "10.1.0.5", "ssh-key"]);
+    jump(&home, &["add", "edge", "ops", "203.0.113.7", "ssh-key"]);
+    jump(
+        &home,
+        &["add", "inner", "ops", "10.1.0.1", "2200", "ssh-key"],
+    );
+    home
+}
+
+#[test]
+fn stored_hops_are_looked_up_for_every_command() {
+    let home = store("via-stored");
+    let chain = "ProxyJump=ops@203.0.113.7:22,ops@10.1.0.1:2200,admin@198.51.100.1:2022";
+    for args in [
+        &["conn", "db", "--dry-run"][..],
+        &["exec", "db", "--dry-run", "--", "uptime"],
+        &["cp", "db:/srv/dump.sql", ".", "--dry-run"],
+        &["cp", "db:/srv/dump.sql", ".", "--resume", "--dry-run"],
+        &["cp", "./build", "/srv", "--to", "db", "--yes", "--dry-run"],
+    ] {
+        let via = ["--via", "edge,inner", "--via", "admin@198.51.100.1:2022"];
+        let args = [&args[..1], &via, &args[1..]].concat();
+        let output = run(&home, &args);
+        assert!(output.status.success(), "{:?}: {:?}", args, output);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains(chain), "{:?}: {}", args, stdout);
+    }
+}
+
+#[test]
+fn hops_that_are_not_hosts_are_refused() {
+    let home = store("via-refused");
+    let output = run(
+        &home,
+        &["conn", "db", "--dry-run", "--via", "-oProxyCommand=sh"],
+    );
+    assert!(!output.status.success());
+
+    Connection::open(home.join(".jump/servers.db"))
+        .unwrap()
+        .execute(
+            "UPDATE jump_servers SET username = 'x;touch pwned' WHERE server_name = 'edge'",
+            [],
+        )
+        .unwrap();
+    let output = run(&home, &["conn", "db", "--dry-run", "--via", "edge"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("edge: x;touch pwned@203.0.113.7:22 is not a host"),
+        "{}",
+        stderr
+    );
+
+    let output = run(
+        &home,
+        &["conn", "db", "--via", "edge", "--proxy", "proxy:3128"],
+    );
+    assert!(!output.status.success());
+}
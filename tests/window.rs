@@ -0,0 +1,86 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf, thread, time::Duration};
+
+use common::{command, home, jump};
+
+fn store(test: &str) -> PathBuf {
+    let home = home(test);
+    jump(&home, &["initialize"]);
+    jump(&home, &["add", "web", "deploy", "10.0.0.1", "ssh-key"]);
+    home
+}
+
+#[test]
+fn the_configured_terminal_runs_the_same_conn() {
+    let home = store("window-config");
+    let terminal = home.join("terminal");
+    fs::write(
+        &terminal,
+        format!(
+            "#!/bin/sh\nprintf '%s\\n' \"$@\" > {}\n",
+            home.join("ran").display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&terminal, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(
+        home.join("config.toml"),
+        format!("terminal_command = \"{} -e {{cmd}}\"\n", terminal.display()),
+    )
+    .unwrap();
+
+    let dry_run = jump(
+        &home,
+        &["conn", "--new-window", "--dry-run", "--cd", "/srv", "web"],
+    );
+    assert!(dry_run.starts_with("sh -c "));
+    assert!(dry_run.contains("-e "));
+    assert!(!dry_run.contains("new-window"));
+
+    let output = command(&home)
+        .args(["conn", "web", "--new-window", "--cd", "/srv"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let ran = home.join("ran");
+    for _ in 0..50 {
+        if ran.exists() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    let args = fs::read_to_string(ran).unwrap();
+    let args = args.lines().collect::<Vec<_>>();
+    assert_eq!(args[0], "-e");
+    assert_eq!(args[1], env!("CARGO_BIN_EXE_jump"));
+    assert_eq!(args[2..], ["conn", "web", "--cd", "/srv"]);
+}
+
+#[test]
+fn without_a_terminal_conn_stays_here() {
+    let home = store("window-none");
+    let output = command(&home)
+        .env_remove("DISPLAY")
+        .env_remove("WAYLAND_DISPLAY")
+        .args(["conn", "--new-window", "--dry-run", "web"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no terminal emulator found"));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("deploy@10.0.0.1"));
+
+    fs::write(
+        home.join("config.toml"),
+        "terminal_command = \"xterm -e\"\n",
+    )
+    .unwrap();
+    let output = command(&home)
+        .args(["conn", "--new-window", "web"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("has no {cmd}"));
+}
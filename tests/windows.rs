@@ -0,0 +1,61 @@
+#![cfg(windows)]
+
+use std::{env, path::PathBuf};
+
+use jump::{
+    config::{self, Config},
+    server::{ConnectMethods, Password, Server},
+    ssh::{self, ConnOptions, SessionOptions},
+};
+
+#[test]
+fn expands_userprofile_in_key_paths() {
+    let profile = env::var("USERPROFILE").unwrap();
+    let expanded = config::expand_path(&PathBuf::from(r"%USERPROFILE%\.ssh\id_rsa")).unwrap();
+    assert_eq!(expanded, PathBuf::from(profile).join(r".ssh\id_rsa"));
+}
+
+#[test]
+fn leaves_unknown_variables_alone() {
+    assert_eq!(
+        config::expand_windows_vars(r"%JUMP_NO_SUCH_VAR%\id_rsa"),
+        r"%JUMP_NO_SUCH_VAR%\id_rsa"
+    );
+}
+
+#[test]
+fn stores_live_under_appdata() {
+    let appdata = env::var("APPDATA").unwrap();
+    assert_eq!(
+        config::jump_dir().unwrap(),
+        PathBuf::from(appdata).join("jump")
+    );
+}
+
+#[test]
+fn password_servers_use_askpass() {
+    let mut config = Config::default();
+    config.binaries.ssh = Some(env::current_exe().unwrap());
+    let server = Server::new(
+        "win".to_owned(),
+        "me".to_owned(),
+        "10.0.0.2".to_owned(),
+        22,
+        ConnectMethods::Password(Password {
+            password: "hunter2".to_owned(),
+        }),
+    );
+    let invocation = ssh::connect(
+        &config,
+        &server,
+        &ConnOptions::default(),
+        &SessionOptions::default(),
+        &[],
+    )
+    .unwrap();
+    assert_eq!(invocation.program, env::current_exe().unwrap());
+    assert!(invocation
+        .env
+        .iter()
+        .any(|(name, value)| name == "SSH_ASKPASS_REQUIRE" && value == "force"));
+}
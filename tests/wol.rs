@@ -0,0 +1,80 @@
+use std::{net::TcpListener, time::Duration};
+
+use jump::wol::{magic_packet, parse_mac, probe_in_background, wait_for_port};
+
+#[test]
+fn parses_both_separators() {
+    let expected = [0xaa, 0xbb, 0xcc, 0x01, 0x02, 0x03];
+    assert_eq!(parse_mac("aa:bb:cc:01:02:03").unwrap(), expected);
+    assert_eq!(parse_mac("AA-BB-CC-01-02-03").unwrap(), expected);
+}
+
+#[test]
+fn rejects_malformed_macs() {
+    assert!(parse_mac("aa:bb:cc:01:02").is_err());
+    assert!(parse_mac("aa:bb:cc:01:02:zz").is_err());
+    assert!(parse_mac("aabb:cc:01:02:03").is_err());
+}
+
+#[test]
+fn magic_packet_layout() {
+    let mac = [1, 2, 3, 4, 5, 6];
+    let packet = magic_packet(mac);
+    assert_eq!(packet[..6], [0xff; 6]);
+    for chunk in packet[6..].chunks(6) {
+        assert_eq!(chunk, mac);
+    }
+}
+
+#[test]
+fn waits_for_an_open_port() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port() as u32;
+    let up = wait_for_port(
+        "127.0.0.1",
+        port,
+        Duration::from_secs(1),
+        Duration::from_millis(50),
+        || {},
+    );
+    assert!(up);
+}
+
+#[test]
+fn times_out_on_a_closed_port() {
+    let port = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port() as u32
+    };
+    let mut ticks = 0;
+    let up = wait_for_port(
+        "127.0.0.1",
+        port,
+        Duration::from_millis(200),
+        Duration::from_millis(50),
+        || ticks += 1,
+    );
+    assert!(!up);
+    assert!(ticks > 0);
+}
+
+#[test]
+fn background_probes_report_each_target_once() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let open = listener.local_addr().unwrap().port() as u32;
+    let closed = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port() as u32
+    };
+    let targets = [open, closed, open, closed, open]
+        .iter()
+        .map(|port| ("127.0.0.1".to_owned(), *port))
+        .collect();
+    let results = probe_in_background(targets, Duration::from_secs(1), 2);
+    let mut up = results.iter().collect::<Vec<_>>();
+    up.sort();
+    assert_eq!(
+        up,
+        [(0, true), (1, false), (2, true), (3, false), (4, true)]
+    );
+}